@@ -0,0 +1,70 @@
+//! Every prime, lazily: a segmented sieve that keeps extending itself.
+
+use crate::sieve::{SEGMENT, base_primes, sieve_segment};
+
+/// Every prime, in order; see [`Primes::iter`].
+#[derive(Debug, Clone, Copy)]
+pub struct Primes;
+
+impl Primes {
+       /// Every prime, 2 onwards, one segment sieved at a time as the iteration gets to it.
+       ///
+       /// ## Example
+       /// ```
+       /// use primes::Primes;
+       ///
+       /// assert_eq!(Primes::iter().take(5).collect::<Vec<_>>(), [2, 3, 5, 7, 11]);
+       /// assert_eq!(Primes::iter().nth(9_999), Some(104_729));
+       /// ```
+       pub fn iter() -> Iter { Iter { base: Vec::new(), base_covers: 0, next_low: 2, current: Vec::new().into_iter() } }
+}
+
+/// The iterator of [`Primes::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter {
+       /// Every prime up to `sqrt(base_covers)`.
+       base:        Vec<usize>,
+       /// How far up `base` can sieve.
+       base_covers: usize,
+       /// Start of the next segment.
+       next_low:    usize,
+       /// The rest of the current segment's primes.
+       current:     std::vec::IntoIter<usize>,
+}
+
+impl Iterator for Iter {
+       type Item = usize;
+
+       fn next(&mut self) -> Option<usize> {
+              loop {
+                     if let Some(prime) = self.current.next() {
+                            return Some(prime);
+                     }
+                     let low = self.next_low;
+                     let high = low.checked_add(SEGMENT - 1)?;
+                     if high > self.base_covers {
+                            // sieve the base primes well ahead, so they're redone only every so often
+                            self.base_covers = high.saturating_mul(4);
+                            self.base = base_primes(self.base_covers);
+                     }
+                     self.current = sieve_segment(&self.base, low, high).collect::<Vec<_>>().into_iter();
+                     self.next_low = high + 1;
+              }
+       }
+}
+
+impl std::iter::FusedIterator for Iter {}
+
+#[cfg(test)]
+mod tests {
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::prime_sieve;
+
+       #[test]
+       fn test_runs_on_past_several_segments() {
+              let max = 4 * SEGMENT + 99;
+              assert_eq!(Primes::iter().take_while(|&p| p <= max).collect::<Vec<_>>(), prime_sieve(None, max));
+       }
+}
@@ -0,0 +1,30 @@
+//! # Primes: sieves, and the primes they find
+//!
+//! Library counterpart to `xtask primes`:
+//! - [`prime_sieve`]: the naive sieve of Eratosthenes, a flag per number up to `max`
+//! - [`bit_sieve`]: the same, with a bit per odd number
+//! - [`primes_in`], [`count_primes`]: segmented sieves, a cache-sized window of flags at a time
+//! - [`wheel_sieve`]: flags only for numbers coprime to 2·3·5 (8 in 30)
+//! - [`parallel_sieve`]: the segments spread over a `sync::ThreadPool`, with each worker's timing
+//! - [`nth_prime`]: segments counted upwards until the `n`th turns up
+//! - [`Primes::iter`]: every prime, lazily
+//! - [`is_prime`]: a single number, by Miller–Rabin
+//!
+//! ## Example
+//! ```
+//! use primes::{Primes, count_primes, is_prime, primes_in};
+//!
+//! assert_eq!(primes_in(10..30), [11, 13, 17, 19, 23, 29]);
+//! assert_eq!(count_primes(0, 1_000_000, true), 78_498);
+//! assert!(Primes::iter().take(100).all(is_prime));
+//! ```
+
+mod iter;
+mod parallel;
+mod primality;
+mod sieve;
+
+pub use iter::{Iter, Primes};
+pub use parallel::{Sieved, WorkerTiming, parallel_sieve};
+pub use primality::is_prime;
+pub use sieve::{bit_sieve, count_primes, nth_prime, prime_sieve, primes_in, wheel_sieve};
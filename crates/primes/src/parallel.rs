@@ -0,0 +1,90 @@
+//! The segmented sieve, spread over a `sync::ThreadPool`.
+//!
+//! Each segment is a pool task: it needs only the base primes (shared, read-only) and its own window, so the tasks
+//! never touch each other's memory. The pool hands back the tasks' results in submission order, so merging is
+//! concatenation.
+
+use std::{collections::BTreeMap,
+          thread,
+          time::{Duration, Instant}};
+
+use sync::ThreadPool;
+
+use crate::sieve::{base_primes, segment, segments};
+
+/// What a segmented sieve found: the primes (unless only counting), and how many.
+#[derive(Debug, Default)]
+pub struct Sieved {
+       /// Empty when only counting.
+       pub primes:  Vec<usize>,
+       pub count:   usize,
+       /// Each pool worker's share, by worker name.
+       pub timings: BTreeMap<String, WorkerTiming>,
+}
+
+/// One pool worker's share of a [`parallel_sieve`].
+#[derive(Debug, Default)]
+pub struct WorkerTiming {
+       pub segments: usize,
+       pub primes:   usize,
+       /// Time spent sieving segments (not waiting for them).
+       pub busy:     Duration,
+}
+
+/// The primes in `min..=max` (or only their number, if `count_only`), sieved in segments (each with the wheel, if
+/// `wheel`) by a pool of `threads` workers; plus how each worker spent its time.
+///
+/// ## Panics
+/// If `threads` is zero, or the pool's threads can't be spawned.
+pub fn parallel_sieve(min: usize, max: usize, threads: usize, wheel: bool, count_only: bool) -> Sieved {
+       let base = base_primes(max);
+       let pool = ThreadPool::builder().size(threads).name("sieve").build().expect("spawn the sieve's thread pool");
+       let segments = pool.scope(|s| {
+              for (low, high) in segments(min, max) {
+                     let base = &base;
+                     s.spawn(move || {
+                            let began = Instant::now();
+                            let (primes, count) = segment(base, low, high, wheel, count_only);
+                            let worker = thread::current().name().unwrap_or("<unnamed>").to_string();
+                            (primes, count, worker, began.elapsed())
+                     });
+              }
+       });
+       let mut sieved = Sieved::default();
+       for (primes, count, worker, busy) in segments {
+              let timing = sieved.timings.entry(worker).or_default();
+              timing.segments += 1;
+              timing.primes += count;
+              timing.busy += busy;
+              sieved.primes.extend(primes);
+              sieved.count += count;
+       }
+       sieved
+}
+
+#[cfg(test)]
+mod tests {
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::{prime_sieve, sieve::SEGMENT};
+
+       #[test]
+       fn test_matches_the_naive_sieve() {
+              let (min, max) = (1000, 5 * SEGMENT + 3);
+              let naive = prime_sieve(Some(min), max);
+              for wheel in [false, true] {
+                     let sieved = parallel_sieve(min, max, 3, wheel, false);
+                     assert_eq!(sieved.primes, naive);
+                     assert_eq!(sieved.count, naive.len());
+                     assert_eq!(sieved.timings.values().map(|timing| timing.segments).sum::<usize>(), 5);
+                     assert_eq!(sieved.timings.values().map(|timing| timing.primes).sum::<usize>(), naive.len());
+              }
+       }
+
+       #[test]
+       fn test_count_only_collects_nothing() {
+              let sieved = parallel_sieve(0, 100_000, 2, true, true);
+              assert_eq!((sieved.count, sieved.primes.len()), (9592, 0));
+       }
+}
@@ -0,0 +1,90 @@
+//! One number at a time: a deterministic Miller–Rabin test, no sieve.
+
+/// Bases that make Miller–Rabin exact for every 64-bit number (the first 12 primes).
+const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// Whether `n` is prime; exact, for every `usize` up to 64 bits, in a few dozen modular exponentiations.
+///
+/// Writing `n - 1 = d·2^s` with `d` odd: a prime `n` makes, for every base `a`, either `a^d ≡ 1` or
+/// `a^(d·2^r) ≡ -1 (mod n)` for some `r < s`. A composite fails that for most bases, and for 64-bit numbers,
+/// for at least one of the first twelve primes.
+///
+/// ## Example
+/// ```
+/// use primes::is_prime;
+///
+/// assert!(is_prime(104_729));
+/// assert!(!is_prime(104_729 * 3));
+/// assert!(is_prime((1 << 61) - 1)); // a Mersenne prime: instant, where trial division would crawl
+/// ```
+pub fn is_prime(n: usize) -> bool {
+       let n = n as u64;
+       if n < 2 {
+              return false;
+       }
+       if let Some(&witness) = WITNESSES.iter().find(|&&witness| n.is_multiple_of(witness)) {
+              return n == witness;
+       }
+       let s = (n - 1).trailing_zeros();
+       let d = (n - 1) >> s;
+       WITNESSES.iter().all(|&a| {
+              let mut x = pow_mod(a, d, n);
+              if x == 1 || x == n - 1 {
+                     return true;
+              }
+              for _ in 1..s {
+                     x = mul_mod(x, x, n);
+                     if x == n - 1 {
+                            return true;
+                     }
+              }
+              false
+       })
+}
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 { (u128::from(a) * u128::from(b) % u128::from(m)) as u64 }
+
+fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+       let mut result = 1;
+       base %= m;
+       while exp > 0 {
+              if exp & 1 == 1 {
+                     result = mul_mod(result, base, m);
+              }
+              base = mul_mod(base, base, m);
+              exp >>= 1;
+       }
+       result
+}
+
+#[cfg(test)]
+mod tests {
+       use quickcheck_macros::quickcheck;
+
+       use super::*;
+       use crate::prime_sieve;
+
+       #[test]
+       fn test_agrees_with_the_sieve_to_100_000() {
+              let primes = prime_sieve(None, 100_000);
+              let found: Vec<usize> = (0..=100_000).filter(|&n| is_prime(n)).collect();
+              assert!(found == primes, "{} primes found, the sieve has {}", found.len(), primes.len());
+       }
+
+       #[test]
+       fn test_strong_pseudoprimes_and_large_primes() {
+              // composites that fool a Miller–Rabin test on the first few bases
+              for composite in [2_047, 1_373_653, 25_326_001, 3_215_031_751, 3_825_123_056_546_413_051] {
+                     assert!(!is_prime(composite), "{composite}");
+              }
+              for prime in [4_294_967_291, (1 << 61) - 1, 18_446_744_073_709_551_557] {
+                     assert!(is_prime(prime), "{prime}");
+              }
+       }
+
+       #[quickcheck]
+       fn qc_products_are_composite(a: u32, b: u32) -> bool {
+              let (a, b) = (usize::from(a as u16).max(2), usize::from(b as u16).max(2));
+              !is_prime(a * b)
+       }
+}
@@ -0,0 +1,288 @@
+//! Sieves of Eratosthenes: the naive one, a bit-packed one, and segmented ones, plain or with a wheel.
+//!
+//! The segmented sieves find the "base" primes up to `sqrt(max)` first (with the naive sieve), then cut the range
+//! into segments of [`SEGMENT`] numbers, each crossed off by the base primes within its own window, independent of
+//! every other segment: the unit of work [`parallel_sieve`](crate::parallel_sieve) hands out, and what
+//! [`count_primes`] keeps in memory at a time.
+//!
+//! ## Wheel
+//! Only 8 of every 30 numbers are coprime to 2·3·5: those ≡ 1, 7, 11, 13, 17, 19, 23, 29 (mod 30). Every prime
+//! past 5 is one of them, so the [`wheel_sieve`] keeps a flag for those 8 alone (27% of the plain sieve's memory),
+//! and crosses off only the multiples `p·m` with `m` itself on the wheel: the others are multiples of 2, 3 or 5,
+//! which have no flag to clear.
+
+use std::ops::{Bound, RangeBounds};
+
+/// Numbers per segment: a `Vec<bool>` of 256 KiB, sized to stay in a core's L2 cache while it's crossed off.
+pub(crate) const SEGMENT: usize = 1 << 18;
+
+/// The wheel's modulus, 2·3·5.
+const WHEEL: usize = 30;
+/// The residues mod [`WHEEL`] coprime to it: the numbers the wheel keeps.
+const SPOKES: [usize; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+/// From each spoke to the next (wrapping around to the next turn).
+const GAPS: [usize; 8] = [6, 4, 2, 4, 2, 4, 6, 2];
+/// Each residue's spoke index, if it's on the wheel.
+const SPOKE_OF: [Option<usize>; WHEEL] = {
+       let mut spoke_of = [None; WHEEL];
+       let mut i = 0;
+       while i < SPOKES.len() {
+              spoke_of[SPOKES[i]] = Some(i);
+              i += 1;
+       }
+       spoke_of
+};
+
+/// I'll be surprised if this works efficiently as a mechanical, literal, procedure.
+pub fn prime_sieve(min: Option<usize>, max: usize) -> Vec<usize> {
+       if max < 2 {
+              return Vec::new();
+       }
+       // buncha default yes's
+       let mut primes = vec![true; max + 1];
+       primes[0] = false;
+       primes[1] = false;
+       // no need to go past sqrt(n).floor()
+       for i in 2..=max.isqrt() {
+              // skip if index was marked as multiple of preceding num
+              if primes[i] {
+                     // first value that's not been sieved would require p >= us, which would be us
+                     let mut index = i.pow(2);
+                     // false for al p * n indices
+                     while index <= max {
+                            primes[index] = false;
+                            index += i;
+                     }
+              }
+       }
+       let min = min.unwrap_or(0);
+       // collect unsieved bits
+       let mut result = vec![];
+       for (i, b) in primes.iter().enumerate().skip(min) {
+              if *b {
+                     result.push(i);
+              }
+       }
+       result
+}
+
+/// The primes in `min..=max`, from a bit per odd number: 1/16th the naive sieve's memory, read 64 flags at a time.
+pub fn bit_sieve(min: usize, max: usize) -> Vec<usize> {
+       if max < 2 {
+              return Vec::new();
+       }
+       // bit `i` stands for `2i + 1`, and is set once that's known composite (1 counting as one)
+       let odds = max.div_ceil(2);
+       let mut composite = vec![0_u64; odds.div_ceil(64)];
+       composite[0] = 1;
+       for p in (3..=max.isqrt()).step_by(2) {
+              if composite[p / 2 / 64] & (1 << (p / 2 % 64)) == 0 {
+                     // even multiples have no bit; odd ones are 2p apart
+                     for multiple in (p * p..=max).step_by(2 * p) {
+                            composite[multiple / 2 / 64] |= 1 << (multiple / 2 % 64);
+                     }
+              }
+       }
+       let mut primes: Vec<usize> = (min..=2).filter(|&n| n == 2).collect();
+       for (w, &word) in composite.iter().enumerate() {
+              let mut unmarked = !word;
+              while unmarked != 0 {
+                     let n = 2 * (w * 64 + unmarked.trailing_zeros() as usize) + 1;
+                     if n > max {
+                            break;
+                     }
+                     if n >= min {
+                            primes.push(n);
+                     }
+                     unmarked &= unmarked - 1;
+              }
+       }
+       primes
+}
+
+/// The primes in `range`, a segment at a time.
+///
+/// ## Panics
+/// If `range` has no end: every prime there is would take a while. [`Primes::iter`](crate::Primes::iter) yields
+/// them one at a time instead.
+pub fn primes_in(range: impl RangeBounds<usize>) -> Vec<usize> {
+       let min = match range.start_bound() {
+              Bound::Included(&min) => min,
+              Bound::Excluded(&min) => min + 1,
+              Bound::Unbounded => 0,
+       };
+       let max = match range.end_bound() {
+              Bound::Included(&max) => max,
+              Bound::Excluded(0) => return Vec::new(),
+              Bound::Excluded(&end) => end - 1,
+              Bound::Unbounded => panic!("`primes_in` needs a range with an end; `Primes::iter()` has no end"),
+       };
+       let base = base_primes(max);
+       segments(min, max).flat_map(|(low, high)| sieve_segment(&base, low, high)).collect()
+}
+
+/// The primes in `min..=max`, sieving only the numbers coprime to 30; see the [module docs](self).
+pub fn wheel_sieve(min: usize, max: usize) -> Vec<usize> {
+       let low = min.max(2);
+       if low > max {
+              return Vec::new();
+       }
+       wheel_segment(&base_primes(max), low, max).collect()
+}
+
+/// How many primes are in `min..=max`, sieving a segment at a time: memory for one segment's flags, not the range's.
+pub fn count_primes(min: usize, max: usize, wheel: bool) -> usize {
+       let base = base_primes(max);
+       segments(min, max).map(|(low, high)| segment(&base, low, high, wheel, true).1).sum()
+}
+
+/// The `n`th prime (the 1st is 2), or `None` for the 0th.
+///
+/// Sieves segments upwards from 2, counting, until the `n`th turns up. The base primes are sieved up to the root of an
+/// estimate of the `n`th prime, `n (ln n + ln ln n)` (an upper bound from the 6th on); should the count fall short
+/// of it anyway, the estimate doubles, and the base primes with it.
+pub fn nth_prime(n: usize) -> Option<usize> {
+       if n == 0 {
+              return None;
+       }
+       let ln = (n as f64).ln();
+       let mut estimate = if n < 6 { 13 } else { (n as f64 * (ln + ln.ln())) as usize };
+       let mut base = base_primes(estimate);
+       let (mut seen, mut low) = (0, 2);
+       loop {
+              if low > estimate {
+                     estimate *= 2;
+                     base = base_primes(estimate);
+              }
+              let high = (low + SEGMENT - 1).min(estimate);
+              let (primes, count) = segment(&base, low, high, false, false);
+              if seen + count >= n {
+                     return Some(primes[n - seen - 1]);
+              }
+              (seen, low) = (seen + count, high + 1);
+       }
+}
+
+/// Every prime up to `sqrt(max)`: all a segmented sieve up to `max` crosses off with.
+pub(crate) fn base_primes(max: usize) -> Vec<usize> { prime_sieve(None, max.isqrt()) }
+
+/// `min..=max` cut into `(low, high)` segments of [`SEGMENT`] numbers (the last one shorter), from 2 at the lowest.
+pub(crate) fn segments(min: usize, max: usize) -> impl Iterator<Item = (usize, usize)> {
+       (min.max(2)..=max).step_by(SEGMENT).map(move |low| (low, low.saturating_add(SEGMENT - 1).min(max)))
+}
+
+/// One segment's primes (or just their number, if `count_only`), sieved plainly or with the wheel.
+pub(crate) fn segment(base: &[usize], low: usize, high: usize, wheel: bool, count_only: bool) -> (Vec<usize>, usize) {
+       let primes: Box<dyn Iterator<Item = usize>> =
+              if wheel { Box::new(wheel_segment(base, low, high)) } else { Box::new(sieve_segment(base, low, high)) };
+       if count_only {
+              (Vec::new(), primes.count())
+       } else {
+              let primes: Vec<_> = primes.collect();
+              let count = primes.len();
+              (primes, count)
+       }
+}
+
+/// The primes in `low..=high` (with `low >= 2`), given every prime up to `sqrt(high)` in `base`.
+pub(crate) fn sieve_segment(base: &[usize], low: usize, high: usize) -> impl Iterator<Item = usize> {
+       let mut is_prime = vec![true; high - low + 1];
+       for &p in base.iter().take_while(|&&p| p * p <= high) {
+              // multiples below p² were crossed off by smaller primes, and p itself must stay
+              let mut multiple = (p * p).max(low.div_ceil(p) * p);
+              while multiple <= high {
+                     is_prime[multiple - low] = false;
+                     multiple += p;
+              }
+       }
+       is_prime.into_iter().enumerate().filter(|&(_, prime)| prime).map(move |(offset, _)| low + offset)
+}
+
+/// The smallest number `>= n` on the wheel.
+fn next_on_wheel(mut n: usize) -> usize {
+       while SPOKE_OF[n % WHEEL].is_none() {
+              n += 1;
+       }
+       n
+}
+
+/// As [`sieve_segment`], with a flag only for the numbers on the wheel (and 2, 3, 5 added back by hand).
+fn wheel_segment(base: &[usize], low: usize, high: usize) -> impl Iterator<Item = usize> {
+       let first_turn = low / WHEEL;
+       let slot = |n: usize| (n / WHEEL - first_turn) * SPOKES.len() + SPOKE_OF[n % WHEEL].expect("n is on the wheel");
+       let mut is_prime = vec![true; (high / WHEEL - first_turn + 1) * SPOKES.len()];
+       for &p in base.iter().skip_while(|&&p| p <= 5).take_while(|&&p| p * p <= high) {
+              // multiples below p² were crossed off by smaller primes, and p itself must stay
+              let mut m = next_on_wheel(p.max(low.div_ceil(p)));
+              while p * m <= high {
+                     is_prime[slot(p * m)] = false;
+                     m += GAPS[SPOKE_OF[m % WHEEL].expect("m is on the wheel")];
+              }
+       }
+       let wheel_primes = is_prime
+              .into_iter()
+              .enumerate()
+              .filter(|&(_, prime)| prime)
+              .map(move |(slot, _)| (first_turn + slot / SPOKES.len()) * WHEEL + SPOKES[slot % SPOKES.len()]);
+       [2, 3, 5].into_iter().chain(wheel_primes).filter(move |n| (low..=high).contains(n) && *n > 1)
+}
+
+#[cfg(test)]
+mod tests {
+       use pretty_assertions::assert_eq;
+       use quickcheck_macros::quickcheck;
+
+       use super::*;
+
+       const UNDER_100: [usize; 25] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97];
+
+       #[test]
+       fn test_the_sieves_agree_under_100() {
+              assert_eq!(prime_sieve(None, 100), UNDER_100);
+              assert_eq!(wheel_sieve(0, 100), UNDER_100);
+              assert_eq!(bit_sieve(0, 100), UNDER_100);
+              assert_eq!(primes_in(..100), UNDER_100);
+              assert_eq!(count_primes(0, 100, false), 25);
+              assert_eq!(count_primes(0, 100, true), 25);
+       }
+
+       #[test]
+       fn test_tiny_and_empty_ranges() {
+              assert_eq!(prime_sieve(None, 0), []);
+              assert_eq!(prime_sieve(None, 1), []);
+              assert_eq!(wheel_sieve(0, 1), []);
+              assert_eq!(bit_sieve(0, 1), []);
+              assert_eq!(bit_sieve(3, 2), []);
+              assert_eq!(primes_in(..0), []);
+              assert_eq!(primes_in(..=2), [2]);
+              assert_eq!(primes_in(90..97), []);
+              assert_eq!(count_primes(24, 28, true), 0);
+       }
+
+       #[test]
+       fn test_across_segment_boundaries() {
+              let max = 3 * SEGMENT + 17;
+              let all = prime_sieve(None, max);
+              assert_eq!(primes_in(..=max), all);
+              assert_eq!(wheel_sieve(0, max), all);
+              assert_eq!(count_primes(SEGMENT - 5, max, true), all.iter().filter(|&&p| p >= SEGMENT - 5).count());
+       }
+
+       #[test]
+       fn test_nth_prime() {
+              assert_eq!(nth_prime(0), None);
+              assert_eq!((1..=25).map(|n| nth_prime(n).unwrap()).collect::<Vec<_>>(), UNDER_100);
+              assert_eq!(nth_prime(10_000), Some(104_729));
+       }
+
+       #[test]
+       #[should_panic(expected = "needs a range with an end")]
+       fn test_primes_in_needs_an_end() { primes_in(10..); }
+
+       #[quickcheck]
+       fn qc_the_others_match_naive(min: u16, len: u16) -> bool {
+              let (min, max) = (usize::from(min), usize::from(min) + usize::from(len));
+              let naive = prime_sieve(Some(min), max);
+              primes_in(min..=max) == naive && wheel_sieve(min, max) == naive && bit_sieve(min, max) == naive
+       }
+}
@@ -0,0 +1,188 @@
+//! Channel throughput benchmarks.
+//!
+//! `cargo bench --package sync --bench channels`
+//!
+//! `producers` threads each send `MESSAGES_PER_PRODUCER` messages to a single consumer;
+//! the reported time covers thread spawn through the last message received.
+//!
+//! `payload_*` give each message a `PAYLOAD`-byte buffer, boxed per message or carved from an `AtomicArena`
+//! that is reset (keeping its one warm chunk) between iterations: the gap is the allocator's share of the cost.
+//!
+//! `work_queue` is many-to-many instead: `WORK_THREADS` producers and as many consumers share one `WorkQueue`, the
+//! consumers taking up to `batch` items per lock acquisition. Batch size 1 is a plain one-item-per-pop queue; where
+//! the time stops falling as the batch grows is the crossover past which batching no longer pays.
+//!
+//! `stream_*` hand `STREAM_BYTES` from one thread to another in `chunk`-byte pieces: through a `byte_pipe` (copied
+//! into and out of its ring, no allocation) or as a `Vec` per chunk over a std channel.
+
+#[cfg(not(feature = "shuttle"))]
+use std::io::{self, Write};
+use std::{sync::mpsc as std_mpsc, thread};
+
+use divan::Bencher;
+// like lib.rs, a `shuttle` build leaves this out
+#[cfg(not(feature = "shuttle"))]
+use sync::byte_pipe;
+use sync::{AtomicArena, WorkQueue,
+           channel::{self, BlockingChannel}};
+
+fn main() { divan::main(); }
+
+const PRODUCERS: &[usize] = &[1, 2, 4, 8];
+const MESSAGES_PER_PRODUCER: usize = 10_000;
+const PAYLOAD: usize = 64;
+const BATCHES: &[usize] = &[1, 2, 4, 8, 16, 32, 64];
+const WORK_THREADS: usize = 4;
+const STREAM_BYTES: usize = 4 << 20;
+const CHUNKS: &[usize] = &[64, 1024, 16 * 1024];
+#[cfg(not(feature = "shuttle"))]
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+#[divan::bench(args = PRODUCERS)]
+fn lock_free_mpsc(bencher: Bencher, producers: usize) {
+       bencher.bench(|| {
+              let (sender, receiver) = channel::mpsc();
+              thread::scope(|s| {
+                     for _ in 0..producers {
+                            let sender = sender.clone();
+                            s.spawn(move || {
+                                   for i in 0..MESSAGES_PER_PRODUCER {
+                                          sender.send(i).unwrap();
+                                   }
+                            });
+                     }
+                     drop(sender);
+                     receiver.iter().count()
+              })
+       });
+}
+
+#[divan::bench(args = PRODUCERS)]
+fn mutex_condvar(bencher: Bencher, producers: usize) {
+       bencher.bench(|| {
+              let channel = BlockingChannel::unbounded();
+              thread::scope(|s| {
+                     let handles: Vec<_> = (0..producers)
+                            .map(|_| {
+                                   s.spawn(|| {
+                                          for i in 0..MESSAGES_PER_PRODUCER {
+                                                 channel.send(i).unwrap();
+                                          }
+                                   })
+                            })
+                            .collect();
+                     let consumer = s.spawn(|| std::iter::from_fn(|| channel.recv().ok()).count());
+                     for handle in handles {
+                            handle.join().unwrap();
+                     }
+                     channel.close();
+                     consumer.join().unwrap()
+              })
+       });
+}
+
+#[divan::bench(args = PRODUCERS)]
+fn std_mpsc(bencher: Bencher, producers: usize) {
+       bencher.bench(|| {
+              let (sender, receiver) = std_mpsc::channel();
+              thread::scope(|s| {
+                     for _ in 0..producers {
+                            let sender = sender.clone();
+                            s.spawn(move || {
+                                   for i in 0..MESSAGES_PER_PRODUCER {
+                                          sender.send(i).unwrap();
+                                   }
+                            });
+                     }
+                     drop(sender);
+                     receiver.iter().count()
+              })
+       });
+}
+
+#[divan::bench(args = BATCHES)]
+fn work_queue(bencher: Bencher, batch: usize) {
+       bencher.bench(|| {
+              let queue = WorkQueue::new();
+              thread::scope(|s| {
+                     let consumers: Vec<_> = (0..WORK_THREADS)
+                            .map(|_| s.spawn(|| std::iter::from_fn(|| queue.pop_batch(batch).ok()).flatten().count()))
+                            .collect();
+                     let producers: Vec<_> = (0..WORK_THREADS)
+                            .map(|_| {
+                                   s.spawn(|| {
+                                          for i in 0..MESSAGES_PER_PRODUCER {
+                                                 queue.push(i).unwrap();
+                                          }
+                                   })
+                            })
+                            .collect();
+                     for producer in producers {
+                            producer.join().unwrap();
+                     }
+                     queue.close();
+                     consumers.into_iter().map(|consumer| consumer.join().unwrap()).sum::<usize>()
+              })
+       });
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(args = CHUNKS)]
+fn stream_byte_pipe(bencher: Bencher, chunk: usize) {
+       bencher.bench(|| {
+              let (mut writer, mut reader) = byte_pipe(PIPE_CAPACITY);
+              thread::scope(|s| {
+                     s.spawn(move || {
+                            let data = vec![7_u8; chunk];
+                            for _ in 0..STREAM_BYTES / chunk {
+                                   writer.write_all(&data).unwrap();
+                            }
+                     });
+                     let mut buffer = vec![0_u8; chunk];
+                     std::iter::from_fn(|| io::Read::read(&mut reader, &mut buffer).ok().filter(|&read| read > 0)).sum::<usize>()
+              })
+       });
+}
+
+#[divan::bench(args = CHUNKS)]
+fn stream_std_mpsc(bencher: Bencher, chunk: usize) {
+       bencher.bench(|| {
+              let (sender, receiver) = std_mpsc::channel();
+              thread::scope(|s| {
+                     s.spawn(move || {
+                            for _ in 0..STREAM_BYTES / chunk {
+                                   sender.send(vec![7_u8; chunk]).unwrap();
+                            }
+                     });
+                     receiver.iter().map(|data| data.len()).sum::<usize>()
+              })
+       });
+}
+
+#[divan::bench(args = PRODUCERS)]
+fn payload_boxed(bencher: Bencher, producers: usize) { bencher.bench(|| send_payloads(producers, |i| Box::new([i as u8; PAYLOAD]))); }
+
+#[divan::bench(args = PRODUCERS)]
+fn payload_arena(bencher: Bencher, producers: usize) {
+       let mut arena = AtomicArena::with_chunk_size(producers * MESSAGES_PER_PRODUCER * PAYLOAD);
+       bencher.bench_local(|| {
+              arena.reset();
+              let arena = &arena;
+              send_payloads(producers, |i| &*arena.alloc([i as u8; PAYLOAD]))
+       });
+}
+
+/// `producers` threads each send `MESSAGES_PER_PRODUCER` payloads over a `BlockingChannel`; returns the count received.
+fn send_payloads<P: Send + std::fmt::Debug>(producers: usize, payload: impl Fn(usize) -> P + Sync) -> usize {
+       let channel = BlockingChannel::unbounded();
+       thread::scope(|s| {
+              for _ in 0..producers {
+                     s.spawn(|| {
+                            for i in 0..MESSAGES_PER_PRODUCER {
+                                   channel.send(payload(i)).unwrap();
+                            }
+                     });
+              }
+              (0..producers * MESSAGES_PER_PRODUCER).filter(|_| channel.recv().is_ok()).count()
+       })
+}
@@ -0,0 +1,82 @@
+//! Contended counter benchmarks: one shared `AtomicUsize` vs a [`ShardedCounter`], and id generators.
+//!
+//! `cargo bench --package sync --bench counters`
+//!
+//! Every bench thread increments one shared counter; the reported time is per increment.
+//! 50 threads is `atomics-demos ch2 progress`'s Fetch_&_Modify workload (50 threads counting items done).
+//! - `single_atomic`: every `fetch_add` fights for the same cache line
+//! - `sharded`: each thread adds to its own padded shard; contention only when threads outnumber shards
+//! - `*_then_sum`: the same, plus a read of the total every increment (the sharded read walks every shard)
+//! - `ids_*`: handing out unique ids: an [`IdGen`] (sequential, or timestamped: a clock read per id) against
+//!   the obvious `Mutex<u64>` counter
+
+#[cfg(not(feature = "shuttle"))]
+use std::sync::LazyLock;
+use std::{hint::black_box,
+          sync::atomic::{AtomicUsize, Ordering::Relaxed}};
+
+use divan::Bencher;
+use sync::Mutex;
+// like lib.rs, a `shuttle` build leaves these out
+#[cfg(not(feature = "shuttle"))]
+use sync::{IdGen, ShardedCounter};
+
+fn main() { divan::main(); }
+
+const THREADS: &[usize] = &[1, 4, 8, 50];
+
+#[divan::bench(threads = THREADS)]
+fn single_atomic(bencher: Bencher) {
+       static COUNTER: AtomicUsize = AtomicUsize::new(0);
+       bencher.bench(|| COUNTER.fetch_add(1, Relaxed));
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn sharded(bencher: Bencher) {
+       static COUNTER: LazyLock<ShardedCounter> = LazyLock::new(ShardedCounter::new);
+       bencher.bench(|| COUNTER.inc());
+}
+
+#[divan::bench(threads = THREADS)]
+fn single_atomic_then_sum(bencher: Bencher) {
+       static COUNTER: AtomicUsize = AtomicUsize::new(0);
+       bencher.bench(|| {
+              COUNTER.fetch_add(1, Relaxed);
+              black_box(COUNTER.load(Relaxed))
+       });
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn sharded_then_sum(bencher: Bencher) {
+       static COUNTER: LazyLock<ShardedCounter> = LazyLock::new(ShardedCounter::new);
+       bencher.bench(|| {
+              COUNTER.inc();
+              black_box(COUNTER.sum())
+       });
+}
+
+#[divan::bench(threads = THREADS)]
+fn ids_mutex_counter(bencher: Bencher) {
+       static NEXT: Mutex<u64> = Mutex::new(0);
+       bencher.bench(|| {
+              let mut next = NEXT.lock();
+              *next += 1;
+              *next
+       });
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn ids_sequential(bencher: Bencher) {
+       static IDS: LazyLock<IdGen> = LazyLock::new(IdGen::sequential);
+       bencher.bench(|| IDS.next());
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn ids_timestamped(bencher: Bencher) {
+       static IDS: LazyLock<IdGen> = LazyLock::new(IdGen::timestamped);
+       bencher.bench(|| IDS.next());
+}
@@ -0,0 +1,76 @@
+//! Contended lock benchmarks.
+//!
+//! `cargo bench --package sync --bench locks`
+//!
+//! Every bench thread hammers one shared lock; the reported time is per acquire+release.
+//! - `short_*`: single increment in the critical section; the unfair `SpinLock` tends to win here,
+//!   as the releasing core often re-acquires while it still owns the cache line.
+//! - `long_*`: a longer critical section; waiters pile up and the `TicketLock`'s strict FIFO handoff
+//!   narrows (or reverses) the gap while guaranteeing no thread is starved.
+//!
+//! `BENCH_PIN=1 cargo bench ...` pins each bench thread to its own core (round robin) the first time it runs,
+//! taking core migrations out of the run-to-run noise.
+
+use std::{cell::Cell,
+          hint::black_box,
+          sync::{LazyLock,
+                 atomic::{AtomicUsize, Ordering::Relaxed}}};
+
+use divan::Bencher;
+use sync::{SpinLock, TicketLock, affinity};
+
+fn main() { divan::main(); }
+
+const THREADS: &[usize] = &[1, 2, 4, 8];
+/// Iterations of busy work held inside the `long_*` critical sections.
+const LONG_WORK: u64 = 200;
+
+fn busy_work(n: u64) -> u64 { (0..n).fold(0, |acc, x| black_box(acc ^ x)) }
+
+/// With `BENCH_PIN` set, pin the calling thread (once) to the next core in turn. Best effort: unpinned if it fails.
+fn pin_once() {
+       static ENABLED: LazyLock<bool> = LazyLock::new(|| std::env::var_os("BENCH_PIN").is_some());
+       static NEXT_CORE: AtomicUsize = AtomicUsize::new(0);
+       thread_local! {
+              static PINNED: Cell<bool> = const { Cell::new(false) };
+       }
+       if *ENABLED && !PINNED.replace(true) {
+              let _ = affinity::pin_to_core(NEXT_CORE.fetch_add(1, Relaxed) % affinity::core_count());
+       }
+}
+
+#[divan::bench(threads = THREADS)]
+fn short_spin_lock(bencher: Bencher) {
+       static LOCK: SpinLock<u64> = SpinLock::new(0);
+       bencher.bench(|| {
+              pin_once();
+              *LOCK.lock() += 1
+       });
+}
+
+#[divan::bench(threads = THREADS)]
+fn short_ticket_lock(bencher: Bencher) {
+       static LOCK: TicketLock<u64> = TicketLock::new(0);
+       bencher.bench(|| {
+              pin_once();
+              *LOCK.lock() += 1
+       });
+}
+
+#[divan::bench(threads = THREADS)]
+fn long_spin_lock(bencher: Bencher) {
+       static LOCK: SpinLock<u64> = SpinLock::new(0);
+       bencher.bench(|| {
+              pin_once();
+              *LOCK.lock() += busy_work(LONG_WORK)
+       });
+}
+
+#[divan::bench(threads = THREADS)]
+fn long_ticket_lock(bencher: Bencher) {
+       static LOCK: TicketLock<u64> = TicketLock::new(0);
+       bencher.bench(|| {
+              pin_once();
+              *LOCK.lock() += busy_work(LONG_WORK)
+       });
+}
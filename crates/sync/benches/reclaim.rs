@@ -0,0 +1,80 @@
+//! Memory-reclamation benchmarks: hazard pointers vs epochs.
+//!
+//! `cargo bench --package sync --bench reclaim` (add `--features epoch` to run `treiber_*` on epochs)
+//!
+//! Every bench thread works on one shared pointer:
+//! - `read_*`: protect (or pin), load, dereference; hazard pointers pay a `SeqCst` fence per read
+//! - `replace_*`: swap in a fresh box and retire (or defer) the old one; reclamation scans are amortized in
+//! - `treiber_*`: a push + pop on the [`TreiberStack`], through whichever backend the `epoch` feature selects
+
+// like lib.rs, a `shuttle` build leaves out hazard pointers, epochs and the stack: no benches left there
+#![cfg_attr(feature = "shuttle", expect(unused_imports, dead_code))]
+
+use std::{hint::black_box,
+          sync::{LazyLock,
+                 atomic::{AtomicPtr, Ordering::SeqCst}}};
+
+use divan::Bencher;
+#[cfg(not(feature = "shuttle"))]
+use sync::{TreiberStack, epoch, hazard};
+
+fn main() { divan::main(); }
+
+const THREADS: &[usize] = &[1, 2, 4, 8];
+
+fn shared() -> AtomicPtr<u64> { AtomicPtr::new(Box::into_raw(Box::new(0))) }
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn read_hazard(bencher: Bencher) {
+       static SHARED: LazyLock<AtomicPtr<u64>> = LazyLock::new(shared);
+       bencher.bench(|| {
+              let mut hazard = hazard::HazardPointer::new();
+              // SAFETY: never retired in this bench.
+              black_box(unsafe { *hazard.protect(&SHARED) })
+       });
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn read_epoch(bencher: Bencher) {
+       static SHARED: LazyLock<AtomicPtr<u64>> = LazyLock::new(shared);
+       bencher.bench(|| {
+              let _guard = epoch::pin();
+              // SAFETY: never retired in this bench.
+              black_box(unsafe { *SHARED.load(SeqCst) })
+       });
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn replace_hazard(bencher: Bencher) {
+       static SHARED: LazyLock<AtomicPtr<u64>> = LazyLock::new(shared);
+       bencher.bench(|| {
+              let old = SHARED.swap(Box::into_raw(Box::new(1)), SeqCst);
+              // SAFETY: from `Box::into_raw`; swapped out, so unreachable and retired only by us.
+              unsafe { hazard::retire(old) };
+       });
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn replace_epoch(bencher: Bencher) {
+       static SHARED: LazyLock<AtomicPtr<u64>> = LazyLock::new(shared);
+       bencher.bench(|| {
+              let guard = epoch::pin();
+              let old = SHARED.swap(Box::into_raw(Box::new(1)), SeqCst);
+              // SAFETY: from `Box::into_raw`; swapped out, so unreachable and deferred only by us.
+              unsafe { guard.defer_destroy(old) };
+       });
+}
+
+#[cfg(not(feature = "shuttle"))]
+#[divan::bench(threads = THREADS)]
+fn treiber_push_pop(bencher: Bencher) {
+       static STACK: TreiberStack<u64> = TreiberStack::new();
+       bencher.bench(|| {
+              STACK.push(1);
+              black_box(STACK.pop().map(|value| *value))
+       });
+}
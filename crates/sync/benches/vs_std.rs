@@ -0,0 +1,133 @@
+//! The crate's locks and channels against their `std` counterparts.
+//!
+//! `cargo bench --package sync --bench vs_std`
+//!
+//! Each bench runs at 1/4/16/64 threads; the `ns` columns are latency (per operation, all threads contending),
+//! the `item/s` columns throughput (operations per second, summed over threads).
+//! - `exclusive`: every thread takes one shared lock (`RwLock`s for writing) and holds it for `work` rounds of busy work;
+//!   `work = 0` is pure lock overhead, longer sections show how each lock's waiters cope with a held lock
+//!   (`Mutex`'s fixed spin budget against `AdaptiveMutex`'s tuned one shows up here)
+//! - `shared`: the same, reading an `RwLock`; readers never wait on each other, so this is reader-count overhead
+//! - `channel_*`: `threads` producers each send `MESSAGES_PER_PRODUCER` to one consumer; one item is one message
+//!
+//! On fewer cores than threads, the spinning locks degrade by design (the holder is descheduled while waiters spin);
+//! compare against `std` at the same thread count, not across counts.
+
+use std::{hint::black_box, sync::mpsc as std_mpsc, thread};
+
+use divan::{Bencher, counter::ItemsCount};
+use sync::{AdaptiveMutex, Mutex, RwLock, SpinLock, channel};
+
+fn main() { divan::main(); }
+
+const THREADS: &[usize] = &[1, 4, 16, 64];
+/// Rounds of busy work inside the critical section.
+const WORK: &[u64] = &[0, 50, 500];
+const MESSAGES_PER_PRODUCER: usize = 1_000;
+
+fn busy_work(n: u64) -> u64 { (0..n).fold(0, |acc, x| black_box(acc ^ x)) }
+
+/// `std`'s locks, under names divan's output tells apart from the crate's namesakes.
+struct StdMutex(std::sync::Mutex<u64>);
+struct StdRwLock(std::sync::RwLock<u64>);
+
+/// A lock around a `u64`, so each bench body is written once for every implementation.
+trait Lock: Sync {
+       fn new() -> Self;
+       /// Run `f` holding the lock exclusively.
+       fn with(&self, f: impl FnOnce(&mut u64));
+}
+
+impl Lock for SpinLock<u64> {
+       fn new() -> Self { Self::new(0) }
+
+       fn with(&self, f: impl FnOnce(&mut u64)) { f(&mut self.lock()) }
+}
+impl Lock for Mutex<u64> {
+       fn new() -> Self { Self::new(0) }
+
+       fn with(&self, f: impl FnOnce(&mut u64)) { f(&mut self.lock()) }
+}
+impl Lock for AdaptiveMutex<u64> {
+       fn new() -> Self { Self::new(0) }
+
+       fn with(&self, f: impl FnOnce(&mut u64)) { f(&mut self.lock()) }
+}
+impl Lock for StdMutex {
+       fn new() -> Self { Self(std::sync::Mutex::new(0)) }
+
+       fn with(&self, f: impl FnOnce(&mut u64)) { f(&mut self.0.lock().unwrap()) }
+}
+impl Lock for RwLock<u64> {
+       fn new() -> Self { Self::new(0) }
+
+       fn with(&self, f: impl FnOnce(&mut u64)) { f(&mut self.write()) }
+}
+impl Lock for StdRwLock {
+       fn new() -> Self { Self(std::sync::RwLock::new(0)) }
+
+       fn with(&self, f: impl FnOnce(&mut u64)) { f(&mut self.0.write().unwrap()) }
+}
+
+/// A lock that can also be held shared.
+trait ReadLock: Lock {
+       /// Run `f` holding the lock shared.
+       fn with_read(&self, f: impl FnOnce(&u64));
+}
+
+impl ReadLock for RwLock<u64> {
+       fn with_read(&self, f: impl FnOnce(&u64)) { f(&self.read()) }
+}
+impl ReadLock for StdRwLock {
+       fn with_read(&self, f: impl FnOnce(&u64)) { f(&self.0.read().unwrap()) }
+}
+
+#[divan::bench(types = [SpinLock<u64>, Mutex<u64>, AdaptiveMutex<u64>, StdMutex, RwLock<u64>, StdRwLock], threads = THREADS, args = WORK)]
+fn exclusive<L: Lock>(bencher: Bencher, work: u64) {
+       let lock = L::new();
+       bencher.counter(ItemsCount::new(1_usize)).bench(|| lock.with(|value| *value += busy_work(work)));
+}
+
+#[divan::bench(types = [RwLock<u64>, StdRwLock], threads = THREADS, args = WORK)]
+fn shared<L: ReadLock>(bencher: Bencher, work: u64) {
+       let lock = L::new();
+       bencher.counter(ItemsCount::new(1_usize)).bench(|| lock.with_read(|value| _ = black_box(*value + busy_work(work))));
+}
+
+#[divan::bench(args = THREADS)]
+fn channel_mpsc(bencher: Bencher, producers: usize) {
+       bencher.counter(ItemsCount::new(producers * MESSAGES_PER_PRODUCER)).bench(|| {
+              let (sender, receiver) = channel::mpsc();
+              thread::scope(|s| {
+                     for _ in 0..producers {
+                            let sender = sender.clone();
+                            s.spawn(move || {
+                                   for i in 0..MESSAGES_PER_PRODUCER {
+                                          sender.send(i).unwrap();
+                                   }
+                            });
+                     }
+                     drop(sender);
+                     receiver.iter().count()
+              })
+       });
+}
+
+#[divan::bench(args = THREADS)]
+fn channel_std_mpsc(bencher: Bencher, producers: usize) {
+       bencher.counter(ItemsCount::new(producers * MESSAGES_PER_PRODUCER)).bench(|| {
+              let (sender, receiver) = std_mpsc::channel();
+              thread::scope(|s| {
+                     for _ in 0..producers {
+                            let sender = sender.clone();
+                            s.spawn(move || {
+                                   for i in 0..MESSAGES_PER_PRODUCER {
+                                          sender.send(i).unwrap();
+                                   }
+                            });
+                     }
+                     drop(sender);
+                     receiver.iter().count()
+              })
+       });
+}
@@ -0,0 +1,214 @@
+//! Futex mutex whose spin-before-sleeping budget tunes itself to the workload.
+//!
+//! [`Mutex`](crate::Mutex) spins a fixed 100 iterations before sleeping: too few when the lock is held for a little
+//! longer than that (every waiter pays for a futex round trip it nearly avoided), wasted cycles when it's held for
+//! much longer (no amount of spinning would have won). Here each lock keeps a running estimate of how many spins
+//! contended acquisitions have needed lately, after glibc's `PTHREAD_MUTEX_ADAPTIVE_NP`:
+//! - spin up to `2 × estimate + MIN_SPINS` (capped at `MAX_SPINS`) before sleeping
+//! - acquired while spinning after `n` spins: the estimate moves an eighth of the way toward `n`
+//! - spun the whole budget in vain: the estimate shrinks by an eighth, so long-held locks stop spinning
+//!
+//! The estimate is a relaxed heuristic: racing updates may lose one another, which costs nothing but tuning speed.
+//! Otherwise the same three-state futex protocol as [`Mutex`](crate::Mutex); no poisoning.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::AdaptiveMutex;
+//!
+//! let counter = AdaptiveMutex::new(0);
+//! thread::scope(|s| {
+//!        for _ in 0..4 {
+//!               s.spawn(|| *counter.lock() += 1);
+//!        }
+//! });
+//! assert!(counter.spin_limit() <= AdaptiveMutex::<()>::MAX_SPINS);
+//! assert_eq!(counter.into_inner(), 4);
+//! ```
+
+use std::{cell::UnsafeCell,
+          ops::{Deref, DerefMut}};
+
+use crate::{atomic::{AtomicU32,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn, spin_loop},
+            futex::{wait, wake_one},
+            histogram::{WaitSite, WaitTimer}};
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+const CONTENDED: u32 = 2;
+
+/// Mutual exclusion lock that spins for as long as spinning has recently paid off, then sleeps (futex).
+pub struct AdaptiveMutex<T> {
+       state:         AtomicU32,
+       /// Running average of the spins a contended acquisition needed.
+       spin_estimate: AtomicU32,
+       value:         UnsafeCell<T>,
+}
+// SAFETY: the state only lets one thread at a time reach `value`.
+unsafe impl<T> Sync for AdaptiveMutex<T> where T: Send {}
+
+impl<T> AdaptiveMutex<T> {
+       /// Most spins before sleeping, however well spinning has done lately.
+       pub const MAX_SPINS: u32 = 1_000;
+       /// Spins always allowed, however badly spinning has done lately.
+       pub const MIN_SPINS: u32 = 10;
+
+       loom_const_fn! {
+              pub fn new(value: T) -> Self {
+                     Self { state: AtomicU32::new(UNLOCKED), spin_estimate: AtomicU32::new(0), value: UnsafeCell::new(value) }
+              }
+       }
+
+       /// Block until the lock is ours.
+       pub fn lock(&self) -> AdaptiveMutexGuard<'_, T> {
+              if self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_err() {
+                     self.lock_contended();
+              }
+              AdaptiveMutexGuard { mutex: self }
+       }
+
+       /// Take the lock only if it's free right now.
+       pub fn try_lock(&self) -> Option<AdaptiveMutexGuard<'_, T>> {
+              self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).ok().map(|_| AdaptiveMutexGuard { mutex: self })
+       }
+
+       /// How many times the next contended `lock` will spin before sleeping.
+       pub fn spin_limit(&self) -> u32 { (2 * self.spin_estimate.load(Relaxed) + Self::MIN_SPINS).min(Self::MAX_SPINS) }
+
+       /// No locking needed: `&mut self` proves exclusive access.
+       pub fn get_mut(&mut self) -> &mut T { self.value.get_mut() }
+
+       pub fn into_inner(self) -> T { self.value.into_inner() }
+
+       #[cold]
+       fn lock_contended(&self) {
+              let mut waited = WaitTimer::new(WaitSite::Mutex);
+              waited.start();
+              let limit = self.spin_limit();
+              let estimate = self.spin_estimate.load(Relaxed);
+              let mut spins = 0;
+              // spin only while merely locked: if others already sleep, queue up behind them
+              while spins < limit {
+                     match self.state.load(Relaxed) {
+                            UNLOCKED => {
+                                   if self.state.compare_exchange_weak(UNLOCKED, LOCKED, Acquire, Relaxed).is_ok() {
+                                          // an eighth of the way toward what this acquisition needed
+                                          let tuned = estimate.saturating_add_signed((spins as i32 - estimate as i32) / 8);
+                                          self.spin_estimate.store(tuned, Relaxed);
+                                          return;
+                                   }
+                            }
+                            CONTENDED => break,
+                            _ => {}
+                     }
+                     spins += 1;
+                     spin_loop();
+              }
+              if spins == limit {
+                     self.spin_estimate.store(estimate - estimate.div_ceil(8), Relaxed); // spinning didn't pay: do less of it
+              }
+              // as `Mutex`: we can't tell whether other waiters remain after we wake, so always lock as CONTENDED
+              while self.state.swap(CONTENDED, Acquire) != UNLOCKED {
+                     wait(&self.state, CONTENDED);
+              }
+       }
+
+       /// Release the lock, waking one sleeper if there may be any.
+       fn unlock(&self) {
+              if self.state.swap(UNLOCKED, Release) == CONTENDED {
+                     wake_one(&self.state);
+              }
+       }
+}
+
+impl<T: Default> Default for AdaptiveMutex<T> {
+       fn default() -> Self { Self::new(T::default()) }
+}
+
+impl<T> std::fmt::Debug for AdaptiveMutex<T> {
+       fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+              f.debug_struct("AdaptiveMutex").field("spin_limit", &self.spin_limit()).finish_non_exhaustive()
+       }
+}
+
+/// Exclusive access to an [`AdaptiveMutex`]'s value; unlocks on drop.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct AdaptiveMutexGuard<'a, T> {
+       mutex: &'a AdaptiveMutex<T>,
+}
+// SAFETY: the guard only hands out `&T` when shared, so `T: Sync` suffices (and is needed: `&AdaptiveMutex<T>` alone
+// would make it `Sync` for any `T: Send`).
+unsafe impl<T> Sync for AdaptiveMutexGuard<'_, T> where T: Sync {}
+impl<T> Deref for AdaptiveMutexGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: the guard's existence proves we hold the lock.
+              unsafe { &*self.mutex.value.get() }
+       }
+}
+impl<T> DerefMut for AdaptiveMutexGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T {
+              // SAFETY: the guard's existence proves we hold the lock.
+              unsafe { &mut *self.mutex.value.get() }
+       }
+}
+impl<T> Drop for AdaptiveMutexGuard<'_, T> {
+       fn drop(&mut self) { self.mutex.unlock(); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{thread, time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_contended_counter() {
+              const NUM_THREADS: usize = 8;
+              const PER_THREAD: usize = 10_000;
+              let counter = AdaptiveMutex::new(0);
+              thread::scope(|s| {
+                     for _ in 0..NUM_THREADS {
+                            s.spawn(|| {
+                                   for _ in 0..PER_THREAD {
+                                          *counter.lock() += 1;
+                                   }
+                            });
+                     }
+              });
+              assert!((AdaptiveMutex::<()>::MIN_SPINS..=AdaptiveMutex::<()>::MAX_SPINS).contains(&counter.spin_limit()));
+              assert_eq!(counter.into_inner(), NUM_THREADS * PER_THREAD);
+       }
+
+       #[test]
+       fn test_try_lock() {
+              let mutex = AdaptiveMutex::new(());
+              let guard = mutex.lock();
+              assert!(mutex.try_lock().is_none());
+              drop(guard);
+              assert!(mutex.try_lock().is_some());
+       }
+
+       /// A lock held far longer than any spin budget: every waiter spins in vain, and the budget shrinks to the floor.
+       #[test]
+       fn test_long_holds_stop_the_spinning() {
+              let mutex = AdaptiveMutex::new(());
+              mutex.spin_estimate.store(AdaptiveMutex::<()>::MAX_SPINS, Relaxed);
+              for _ in 0..60 {
+                     let guard = mutex.lock();
+                     thread::scope(|s| {
+                            let waiter = s.spawn(|| drop(mutex.lock()));
+                            thread::sleep(Duration::from_micros(200));
+                            drop(guard);
+                            waiter.join().unwrap();
+                     });
+              }
+              assert!(mutex.spin_limit() < AdaptiveMutex::<()>::MIN_SPINS + 10, "limit still {}", mutex.spin_limit());
+       }
+}
@@ -0,0 +1,280 @@
+//! Pin threads to CPU cores, so benchmarks stop measuring the scheduler moving threads around.
+//!
+//! A thread migrated mid-benchmark leaves its cache lines behind; a contended lock's numbers then swing with
+//! wherever the scheduler happened to put the threads. Pinning each bench thread to its own core removes that noise
+//! (and pinning two to the *same* core is a cheap way to study time-slicing instead of true parallelism).
+//!
+//! - [`pin_to_core`]: pin the calling thread
+//! - [`SpawnPinned`]: `thread::Builder` extension that pins the new thread before running its closure,
+//!   reporting a failed pin from the spawn call itself
+//!
+//! | platform | mechanism                                                | strength                            |
+//! |----------|----------------------------------------------------------|-------------------------------------|
+//! | Linux    | `sched_setaffinity` to a one-core set                    | hard: the thread runs only there    |
+//! | macOS    | `THREAD_AFFINITY_POLICY` tag `core + 1`                  | hint: same-tag threads share a core; `Unsupported` on Apple silicon |
+//! | Windows  | `SetThreadAffinityMask` with one bit                     | hard                                |
+//! | other    | —                                                        | always `Unsupported`                |
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::affinity::{self, SpawnPinned};
+//!
+//! let last_core = affinity::core_count() - 1;
+//! match thread::Builder::new().spawn_pinned(last_core, move || affinity::current_core()) {
+//!        Ok(handle) => {
+//!               let core = handle.join().unwrap();
+//!               assert!(core.is_none_or(|core| core == last_core));
+//!        }
+//!        Err(error) => println!("no pinning here: {error}"),
+//! }
+//! ```
+
+use std::{io,
+          thread::{self, JoinHandle, Scope, ScopedJoinHandle}};
+
+use crate::{Joinable,
+            channel::{self, RecvError}};
+
+/// Cores available to this process (at least 1).
+pub fn core_count() -> usize { thread::available_parallelism().map_or(1, usize::from) }
+
+/// The core the calling thread is running on right now, where the OS says (Linux); `None` elsewhere.
+pub fn current_core() -> Option<usize> {
+       #[cfg(target_os = "linux")]
+       {
+              // SAFETY: no arguments, no memory touched.
+              let core = unsafe { libc::sched_getcpu() };
+              usize::try_from(core).ok()
+       }
+       #[cfg(not(target_os = "linux"))]
+       None
+}
+
+/// Pin the calling thread to `core` (numbered from 0, as the OS numbers them).
+///
+/// ## Errors
+/// - `InvalidInput` if `core` is beyond what the platform call can express
+/// - `Unsupported` where there's no affinity API (or, on macOS, where the kernel ignores affinity tags)
+/// - the OS error otherwise (e.g. `core` isn't one this process may use)
+pub fn pin_to_core(core: usize) -> io::Result<()> { imp::pin_to_core(core) }
+
+fn invalid_core(core: usize) -> io::Error { io::Error::new(io::ErrorKind::InvalidInput, format!("no core {core} to pin to")) }
+
+#[cfg(target_os = "linux")]
+mod imp {
+       use std::{io, mem};
+
+       pub(super) fn pin_to_core(core: usize) -> io::Result<()> {
+              if core >= libc::CPU_SETSIZE as usize {
+                     return Err(super::invalid_core(core));
+              }
+              // SAFETY: `cpu_set_t` is a plain bitmask; all zeroes is the empty set.
+              let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+              // SAFETY: `core` is within `CPU_SETSIZE`, checked above.
+              unsafe { libc::CPU_SET(core, &mut set) };
+              // SAFETY: pid 0 is the calling thread; the set is live and its size is passed along.
+              match unsafe { libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &raw const set) } {
+                     0 => Ok(()),
+                     _ => Err(io::Error::last_os_error()),
+              }
+       }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+       use std::io;
+
+       pub(super) fn pin_to_core(core: usize) -> io::Result<()> {
+              // tag 0 means "no affinity", so core n gets tag n + 1
+              let tag = core.checked_add(1).and_then(|tag| libc::integer_t::try_from(tag).ok()).ok_or_else(|| super::invalid_core(core))?;
+              let mut policy = libc::thread_affinity_policy { affinity_tag: tag };
+              // SAFETY: `pthread_self` is always valid; `pthread_mach_thread_np` borrows its port (no reference to release).
+              let thread = unsafe { libc::pthread_mach_thread_np(libc::pthread_self()) };
+              // SAFETY: the policy struct is live and matches the flavour; its count is one `integer_t`.
+              let result = unsafe {
+                     libc::thread_policy_set(
+                            thread,
+                            libc::THREAD_AFFINITY_POLICY as _,
+                            (&raw mut policy).cast(),
+                            libc::THREAD_AFFINITY_POLICY_COUNT,
+                     )
+              };
+              match result {
+                     libc::KERN_SUCCESS => Ok(()),
+                     libc::KERN_NOT_SUPPORTED => {
+                            Err(io::Error::new(io::ErrorKind::Unsupported, "thread affinity tags not supported on this Mac"))
+                     }
+                     code => Err(io::Error::other(format!("thread_policy_set failed: kern_return_t {code}"))),
+              }
+       }
+}
+
+#[cfg(windows)]
+mod imp {
+       use std::{ffi::c_void, io};
+
+       #[link(name = "kernel32")]
+       unsafe extern "system" {
+              fn GetCurrentThread() -> *mut c_void;
+              fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+       }
+
+       pub(super) fn pin_to_core(core: usize) -> io::Result<()> {
+              let mask = u32::try_from(core).ok().and_then(|core| 1_usize.checked_shl(core)).ok_or_else(|| super::invalid_core(core))?;
+              // SAFETY: the pseudo-handle for the current thread is always valid, and needs no closing.
+              match unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) } {
+                     0 => Err(io::Error::last_os_error()),
+                     _previous_mask => Ok(()),
+              }
+       }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod imp {
+       use std::io;
+
+       pub(super) fn pin_to_core(_core: usize) -> io::Result<()> {
+              Err(io::Error::new(io::ErrorKind::Unsupported, "thread affinity not supported on this platform"))
+       }
+}
+
+/// Spawn threads already pinned to a core.
+///
+/// The new thread pins itself first and reports back; the spawn call waits for that report,
+/// so a failed pin is an `Err` here (and the closure never runs) rather than a silently unpinned thread.
+pub trait SpawnPinned {
+       /// ## Errors
+       /// If the thread can't be created, or can't be pinned (see [`pin_to_core`]).
+       fn spawn_pinned<F, T>(self, core: usize, f: F) -> io::Result<Pinned<JoinHandle<Option<T>>>>
+       where
+              F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static;
+
+       /// As [`spawn_pinned`](Self::spawn_pinned), into a [`thread::scope`].
+       ///
+       /// ## Errors
+       /// If the thread can't be created, or can't be pinned (see [`pin_to_core`]).
+       fn spawn_scoped_pinned<'scope, F, T>(
+              self,
+              scope: &'scope Scope<'scope, '_>,
+              core: usize,
+              f: F,
+       ) -> io::Result<Pinned<ScopedJoinHandle<'scope, Option<T>>>>
+       where
+              F: FnOnce() -> T + Send + 'scope,
+              T: Send + 'scope;
+}
+
+impl SpawnPinned for thread::Builder {
+       fn spawn_pinned<F, T>(self, core: usize, f: F) -> io::Result<Pinned<JoinHandle<Option<T>>>>
+       where
+              F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static,
+       {
+              let (sender, receiver) = channel::oneshot();
+              let handle = self.spawn(move || pinned_then(core, &sender, f))?;
+              Pinned::checked(handle, receiver.recv())
+       }
+
+       fn spawn_scoped_pinned<'scope, F, T>(
+              self,
+              scope: &'scope Scope<'scope, '_>,
+              core: usize,
+              f: F,
+       ) -> io::Result<Pinned<ScopedJoinHandle<'scope, Option<T>>>>
+       where
+              F: FnOnce() -> T + Send + 'scope,
+              T: Send + 'scope,
+       {
+              let (sender, receiver) = channel::oneshot();
+              let handle = self.spawn_scoped(scope, move || pinned_then(core, &sender, f))?;
+              Pinned::checked(handle, receiver.recv())
+       }
+}
+
+/// The spawned thread's side: pin, report, and run `f` only if pinned.
+fn pinned_then<T>(core: usize, report: &channel::oneshot::Sender<io::Result<()>>, f: impl FnOnce() -> T) -> Option<T> {
+       let pinned = pin_to_core(core);
+       let ok = pinned.is_ok();
+       report.send(pinned);
+       ok.then(f)
+}
+
+/// Handle to a thread spawned by [`SpawnPinned`]; `join` gives back what the closure returned.
+///
+/// Also [`Joinable`], for [`join_all`](crate::join_all).
+#[derive(Debug)]
+pub struct Pinned<H>(H);
+
+impl<H: Joinable> Pinned<H> {
+       /// Keep the handle if the thread reported a pin; otherwise reap the thread (it ran nothing) and pass on the error.
+       fn checked(handle: H, report: Result<io::Result<()>, RecvError>) -> io::Result<Self> {
+              match report {
+                     Ok(Ok(())) => Ok(Self(handle)),
+                     Ok(Err(error)) => {
+                            let _ = handle.join_thread();
+                            Err(error)
+                     }
+                     Err(RecvError) => Err(io::Error::other("pinned thread exited before reporting its pin")),
+              }
+       }
+}
+
+impl<H, T> Pinned<H>
+where
+       H: Joinable<Output = Option<T>>,
+{
+       /// Wait for the thread to finish.
+       ///
+       /// ## Errors
+       /// If the thread panicked; the panic payload, as from [`JoinHandle::join`].
+       pub fn join(self) -> thread::Result<T> { self.join_thread() }
+}
+
+impl<H: Joinable> crate::join::sealed::Sealed for Pinned<H> {}
+
+impl<H, T> Joinable for Pinned<H>
+where
+       H: Joinable<Output = Option<T>>,
+{
+       type Output = T;
+
+       fn thread_name(&self) -> Option<String> { self.0.thread_name() }
+
+       fn join_thread(self) -> thread::Result<T> {
+              self.0.join_thread().map(|value| value.expect("a `Pinned` handle only exists for a thread that pinned and ran"))
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_bad_core_is_reported_by_spawn() {
+              let error = thread::Builder::new().spawn_pinned(usize::MAX, || unreachable!("never runs")).unwrap_err();
+              assert!(matches!(error.kind(), io::ErrorKind::InvalidInput | io::ErrorKind::Unsupported), "{error}");
+       }
+
+       #[cfg(target_os = "linux")]
+       #[test]
+       fn test_pinned_threads_run_there() {
+              let cores: Vec<usize> = (0..affinity_cores()).collect();
+              let ran_on = thread::scope(|s| {
+                     let handles: Vec<_> =
+                            cores.iter().map(|&core| thread::Builder::new().spawn_scoped_pinned(s, core, current_core).unwrap()).collect();
+                     crate::join_all(handles).unwrap()
+              });
+              assert_eq!(ran_on, cores.into_iter().map(Some).collect::<Vec<_>>());
+       }
+
+       /// Cores 0.. this process may actually run on, assuming a contiguous set starting at 0.
+       #[cfg(target_os = "linux")]
+       fn affinity_cores() -> usize {
+              (0..core_count()).take_while(|&core| thread::spawn(move || pin_to_core(core)).join().unwrap().is_ok()).count()
+       }
+}
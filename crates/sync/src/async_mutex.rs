@@ -0,0 +1,300 @@
+//! Mutex whose `lock` is a future: waiting tasks queue a waker in the [`parking_lot`](crate::parking_lot) instead of
+//! parking a thread.
+//!
+//! The same one-byte protocol as [`ByteMutex`](crate::ByteMutex) (a `LOCKED` bit, a `PARKED` bit), and the same
+//! queues: threads calling [`lock_blocking`](AsyncMutex::lock_blocking) and tasks awaiting [`lock`](AsyncMutex::lock)
+//! wait in one FIFO line, and whichever an unlock dequeues is woken, thread or task. So one lock can guard state shared
+//! by sync and async code.
+//!
+//! The guard is `Send`: it may be held across an `.await` and released on another thread.
+//! An [`AsyncMutexLock`] future dropped while queued (a cancelled task, a lost `select!`) leaves the queue; if an
+//! unlock had already picked it, it wakes the next waiter in its place, so cancellation never strands the others.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::{AsyncMutex, block_on};
+//!
+//! let counter = AsyncMutex::new(0);
+//! thread::scope(|s| {
+//!        s.spawn(|| block_on(async { *counter.lock().await += 1 }));
+//!        s.spawn(|| *counter.lock_blocking() += 1);
+//! });
+//! assert_eq!(counter.into_inner(), 2);
+//! ```
+
+use std::{cell::UnsafeCell,
+          fmt,
+          ops::{Deref, DerefMut},
+          pin::Pin,
+          sync::Arc,
+          task::{Context, Poll}};
+
+use crate::{atomic::{AtomicU8,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn},
+            parking_lot::{self, TaskWaiter}};
+
+const LOCKED: u8 = 0b01;
+const PARKED: u8 = 0b10;
+
+/// Mutual exclusion for tasks and threads alike, queueing in the global parking lot.
+pub struct AsyncMutex<T> {
+       state: AtomicU8,
+       value: UnsafeCell<T>,
+}
+// SAFETY: the state only lets one thread at a time reach `value`.
+unsafe impl<T> Sync for AsyncMutex<T> where T: Send {}
+
+impl<T> AsyncMutex<T> {
+       loom_const_fn! {
+              pub fn new(value: T) -> Self { Self { state: AtomicU8::new(0), value: UnsafeCell::new(value) } }
+       }
+
+       /// A future that resolves once the lock is ours.
+       pub fn lock(&self) -> AsyncMutexLock<'_, T> { AsyncMutexLock { mutex: self, waiter: None, queued: false } }
+
+       /// Block the thread until the lock is ours, queueing with any tasks waiting for it.
+       pub fn lock_blocking(&self) -> AsyncMutexGuard<'_, T> {
+              loop {
+                     if let Some(guard) = self.try_lock() {
+                            return guard;
+                     }
+                     if self.mark_parked() {
+                            parking_lot::park(self.key(), || self.state.load(Relaxed) == LOCKED | PARKED, None);
+                     }
+              }
+       }
+
+       pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, T>> {
+              let mut state = self.state.load(Relaxed);
+              while state & LOCKED == 0 {
+                     // keep any PARKED bit for the other waiters
+                     match self.state.compare_exchange_weak(state, state | LOCKED, Acquire, Relaxed) {
+                            Ok(_) => return Some(AsyncMutexGuard { mutex: self }),
+                            Err(observed) => state = observed,
+                     }
+              }
+              None
+       }
+
+       pub fn get_mut(&mut self) -> &mut T { self.value.get_mut() }
+
+       pub fn into_inner(self) -> T { self.value.into_inner() }
+
+       fn key(&self) -> usize { &self.state as *const AtomicU8 as usize }
+
+       /// Set `PARKED` while the lock is held; `false` if it was released meanwhile (try again instead of queueing).
+       fn mark_parked(&self) -> bool {
+              let mut state = self.state.load(Relaxed);
+              while state == LOCKED {
+                     match self.state.compare_exchange_weak(LOCKED, LOCKED | PARKED, Relaxed, Relaxed) {
+                            Ok(_) => return true,
+                            Err(observed) => state = observed,
+                     }
+              }
+              state == LOCKED | PARKED
+       }
+
+       /// Wake the next waiter, from an unlock: the lock is released in the callback, `PARKED` kept if more wait.
+       #[cold]
+       fn unlock_slow(&self) {
+              parking_lot::unpark_one(self.key(), |result| {
+                     self.state.store(if result.have_more { PARKED } else { 0 }, Release);
+              });
+       }
+
+       /// Wake the next waiter in place of a cancelled one that had been picked. The lock may be held by now,
+       /// so only the `PARKED` bit is touched.
+       #[cold]
+       fn pass_on_wake(&self) {
+              parking_lot::unpark_one(self.key(), |result| {
+                     if !result.have_more {
+                            self.state.fetch_and(!PARKED, Relaxed);
+                     }
+              });
+       }
+}
+
+impl<T: Default> Default for AsyncMutex<T> {
+       fn default() -> Self { Self::new(T::default()) }
+}
+
+impl<T> fmt::Debug for AsyncMutex<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("AsyncMutex").field("locked", &(self.state.load(Relaxed) & LOCKED != 0)).finish_non_exhaustive()
+       }
+}
+
+/// Future returned by [`AsyncMutex::lock`].
+#[must_use = "futures do nothing unless awaited"]
+pub struct AsyncMutexLock<'a, T> {
+       mutex:  &'a AsyncMutex<T>,
+       /// This future's entry in the parking lot, made the first time it has to queue.
+       waiter: Option<Arc<TaskWaiter>>,
+       /// Whether `waiter` is queued (or was dequeued by an unlock since the last poll).
+       queued: bool,
+}
+
+impl<'a, T> Future for AsyncMutexLock<'a, T> {
+       type Output = AsyncMutexGuard<'a, T>;
+
+       fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+              let this = self.get_mut();
+              if let (true, Some(waiter)) = (this.queued, &this.waiter) {
+                     waiter.register(cx.waker()); // before checking: an unpark after this finds the current waker
+                     if !waiter.is_unparked() {
+                            return Poll::Pending;
+                     }
+                     this.queued = false;
+              }
+              // picked by an unlock (or never queued): compete for the lock like anyone else, queueing again on a loss
+              loop {
+                     if let Some(guard) = this.mutex.try_lock() {
+                            return Poll::Ready(guard);
+                     }
+                     if !this.mutex.mark_parked() {
+                            continue;
+                     }
+                     let waiter = this.waiter.get_or_insert_with(TaskWaiter::new);
+                     waiter.register(cx.waker());
+                     let mutex = this.mutex;
+                     if parking_lot::park_task(mutex.key(), || mutex.state.load(Relaxed) == LOCKED | PARKED, waiter) {
+                            this.queued = true;
+                            return Poll::Pending;
+                     }
+              }
+       }
+}
+
+impl<T> Drop for AsyncMutexLock<'_, T> {
+       fn drop(&mut self) {
+              if let (true, Some(waiter)) = (self.queued, &self.waiter)
+                     && !parking_lot::cancel_task(self.mutex.key(), waiter)
+              {
+                     self.mutex.pass_on_wake(); // an unlock picked us, and we won't take the lock
+              }
+       }
+}
+
+impl<T> fmt::Debug for AsyncMutexLock<'_, T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("AsyncMutexLock").field("queued", &self.queued).finish_non_exhaustive()
+       }
+}
+
+/// Exclusive access to an [`AsyncMutex`]'s value; unlocks on drop.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct AsyncMutexGuard<'a, T> {
+       mutex: &'a AsyncMutex<T>,
+}
+// SAFETY: the guard only hands out `&T` when shared, so `T: Sync` suffices.
+unsafe impl<T> Sync for AsyncMutexGuard<'_, T> where T: Sync {}
+
+impl<T> Deref for AsyncMutexGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: the guard's existence proves we hold the lock.
+              unsafe { &*self.mutex.value.get() }
+       }
+}
+impl<T> DerefMut for AsyncMutexGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T {
+              // SAFETY: the guard's existence proves we hold the lock.
+              unsafe { &mut *self.mutex.value.get() }
+       }
+}
+impl<T> Drop for AsyncMutexGuard<'_, T> {
+       fn drop(&mut self) {
+              if self.mutex.state.compare_exchange(LOCKED, 0, Release, Relaxed).is_err() {
+                     self.mutex.unlock_slow();
+              }
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize,
+                 task::{Wake, Waker},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::block_on;
+
+       /// Counts its wake-ups.
+       #[derive(Default)]
+       struct CountingWaker(AtomicUsize);
+       impl Wake for CountingWaker {
+              fn wake(self: Arc<Self>) { self.0.fetch_add(1, Relaxed); }
+       }
+
+       fn is_send<T: Send>(_: &T) {}
+
+       #[test]
+       fn test_tasks_and_threads_share_the_lock() {
+              const PER_WAITER: usize = if cfg!(miri) { 20 } else { 2_000 };
+              let counter = AsyncMutex::new(0);
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   block_on(async {
+                                          for _ in 0..PER_WAITER {
+                                                 let mut guard = counter.lock().await;
+                                                 *guard += 1;
+                                                 if *guard % 50 == 0 {
+                                                        thread::yield_now(); // hold it across a reschedule, so others queue
+                                                 }
+                                          }
+                                   });
+                            });
+                            s.spawn(|| (0..PER_WAITER).for_each(|_| *counter.lock_blocking() += 1));
+                     }
+              });
+              assert_eq!(counter.into_inner(), 8 * PER_WAITER);
+       }
+
+       #[test]
+       fn test_guard_held_across_an_await_is_send() {
+              let mutex = AsyncMutex::new(Vec::new());
+              let task = async {
+                     let mut guard = mutex.lock().await;
+                     guard.push(1);
+                     std::future::ready(()).await;
+                     guard.push(2);
+              };
+              is_send(&task);
+              block_on(task);
+              assert_eq!(mutex.into_inner(), [1, 2]);
+       }
+
+       #[test]
+       fn test_cancelled_waiter_passes_the_wake_on() {
+              let mutex = AsyncMutex::new(());
+              let wakes = Arc::new(CountingWaker::default());
+              let waker = Waker::from(wakes.clone());
+              let mut cx = Context::from_waker(&waker);
+              let guard = mutex.lock_blocking();
+              let (mut first, mut second) = (mutex.lock(), mutex.lock());
+              assert!(Pin::new(&mut first).poll(&mut cx).is_pending());
+              assert!(Pin::new(&mut second).poll(&mut cx).is_pending());
+              drop(guard); // picks `first`...
+              assert_eq!(wakes.0.load(Relaxed), 1);
+              drop(first); // ...which gives up, so `second` is woken instead
+              assert_eq!(wakes.0.load(Relaxed), 2);
+              assert!(Pin::new(&mut second).poll(&mut cx).is_ready());
+       }
+
+       #[test]
+       fn test_try_lock() {
+              let mutex = AsyncMutex::new(1);
+              let guard = block_on(mutex.lock());
+              assert!(mutex.try_lock().is_none());
+              drop(guard);
+              *mutex.try_lock().unwrap() += 1;
+              assert_eq!(mutex.into_inner(), 2);
+       }
+}
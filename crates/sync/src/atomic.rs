@@ -0,0 +1,82 @@
+//! The atomics the primitives are built from: std's, or under `--cfg loom` the model checker's, or with the `shuttle`
+//! feature the randomized scheduler's.
+//!
+//! Primitives import `AtomicU32`, `Ordering`, `fence`, … from here rather than `std::sync::atomic`,
+//! so one `RUSTFLAGS="--cfg loom"` swaps every one of them for a `loom` atomic and the `loom_tests` modules can explore
+//! every interleaving (and every stale read the orderings allow).
+//!
+//! Loom's search is exhaustive, so it only gets through small models: two or three threads, a few operations each.
+//! Shuttle samples instead: each `shuttle_tests` test runs a bigger scenario (more threads, a thread pool, a stream of
+//! messages) some thousands of times under a random scheduler and under PCT, which picks a few points in the run
+//! to switch thread priorities at and finds bugs that need a particular few preemptions. Its atomics are
+//! sequentially consistent: it finds orderings of operations, not stale reads. The spawning the primitives do
+//! themselves (a thread pool's workers) comes from here too, so those threads are the scheduler's as well.
+//!
+//! Spinning and parking come from here too. Loom only switches threads at its own operations, so a spin loop must
+//! yield to it or it never ends, and a parked thread must be one loom knows about or nobody can wake it.
+//! Loom has no clock: a timed park is a yield there, which callers already treat as a spurious wakeup.
+//!
+//! Loom's atomics differ from std's in ways the primitives work around:
+//! - no `const fn new`: constructors are `const` only outside loom (see [`loom_const_fn!`]), and modules that keep
+//!   atomics in a `static` aren't compiled under loom at all (a static outlives the model runs)
+//! - no `get_mut`: `&mut self` paths use a `Relaxed` load or store instead, as there's nobody to synchronize with
+//! - no `as_ptr`, so no futex: waits become yields (see [`futex`](crate::futex))
+//! - no `try_update`: see [`try_update`]
+//! - they only work inside `loom::model`: run just the loom tests (`cargo test … loom`) under `--cfg loom`
+//!
+//! Shuttle's have `const fn new` and `get_mut`, but the rest holds for them too: no futex, no `try_update`, and they
+//! only work inside `shuttle::check_*` (run just the shuttle tests). The modules left out of a loom build are left
+//! out of a shuttle one. It's a feature rather than a cfg, so switching to it doesn't change `RUSTFLAGS` and rebuild every dependency.
+
+#[cfg(all(loom, feature = "shuttle"))]
+compile_error!("`--cfg loom` and the `shuttle` feature each bring their own atomics: use one at a time");
+
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub(crate) use std::{hint::spin_loop,
+                     sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicU16, AtomicU32, AtomicU64, AtomicUsize, Ordering, fence},
+                     thread::{Builder, JoinHandle, Thread, current, park, park_timeout, yield_now}};
+
+#[cfg(loom)]
+pub(crate) use loom::{hint::spin_loop,
+                      sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicU32, AtomicU64, AtomicUsize, Ordering, fence},
+                      thread::{Builder, JoinHandle, Thread, current, park, yield_now}};
+#[cfg(feature = "shuttle")]
+pub(crate) use shuttle::{hint::spin_loop,
+                         sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, AtomicU32, AtomicU64, AtomicUsize, Ordering, fence},
+                         thread::{Builder, JoinHandle, Thread, current, park, park_timeout, yield_now}};
+
+#[cfg(loom)]
+pub(crate) fn park_timeout(_: std::time::Duration) { yield_now() }
+
+/// `AtomicU32::try_update`, which loom's and shuttle's atomics lack (they have it under its old name, `fetch_update`).
+pub(crate) fn try_update(
+       atomic: &AtomicU32,
+       set_order: Ordering,
+       fetch_order: Ordering,
+       f: impl FnMut(u32) -> Option<u32>,
+) -> Result<u32, u32> {
+       #[cfg(not(any(loom, feature = "shuttle")))]
+       return atomic.try_update(set_order, fetch_order, f);
+       #[cfg(any(loom, feature = "shuttle"))]
+       return atomic.fetch_update(set_order, fetch_order, f);
+}
+
+/// A `const fn` normally, a plain `fn` under loom (whose atomics can't be created in a const context).
+macro_rules! loom_const_fn {
+       ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+              #[cfg(not(loom))]
+              $(#[$attr])* $vis const fn $($rest)*
+              #[cfg(loom)]
+              $(#[$attr])* $vis fn $($rest)*
+       };
+}
+pub(crate) use loom_const_fn;
+
+/// Run a `shuttle_tests` scenario under shuttle's random scheduler, then under PCT, each some thousand times.
+#[cfg(all(test, feature = "shuttle"))]
+pub(crate) fn shuttle_check(scenario: impl Fn() + Clone + Send + Sync + 'static) {
+       const ITERATIONS: usize = 1_000;
+       const DEPTH: usize = 3; // PCT's priority changes: bugs needing up to 2 preemptions, at any points
+       shuttle::check_random(scenario.clone(), ITERATIONS);
+       shuttle::check_pct(scenario, ITERATIONS, DEPTH);
+}
@@ -0,0 +1,311 @@
+//! Concurrent bump arena: threads carve disjoint pieces out of a shared chunk with one `fetch_add` each.
+//!
+//! An allocation reserves `[offset, offset + n)` of the current chunk by bumping its `used` counter.
+//! No lock and no CAS loop: concurrent reservations get disjoint ranges by construction.
+//! When a reservation runs past the end, the thread allocates a fresh chunk, links the full one behind it,
+//! and CASes it in as current (losing the race just frees ours and retries on the winner's).
+//!
+//! Nothing is freed piecemeal: memory comes back all at once on [`reset`](AtomicArena::reset)
+//! (which needs `&mut self`, so no allocation can still be borrowed) or on drop.
+//! Destructors of allocated values never run: use it for plain data.
+//!
+//! ## Design
+//! - reservations are rounded up to 8 bytes, so the bump offset stays 8-aligned; over-aligned types
+//!   reserve `align - 8` bytes of slack and align the start within it
+//! - `used` is `Relaxed`: it only partitions space, no data flows through it; chunks are published with AcqRel CAS
+//! - a failed reservation has still bumped `used` past the end; the chunk is full for everyone after that
+//! - `reset` keeps the newest chunk (the arena's working size) and frees the rest
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::AtomicArena;
+//!
+//! let mut arena = AtomicArena::with_chunk_size(1024);
+//! let totals: Vec<u64> = thread::scope(|s| {
+//!        let handles: Vec<_> = (0..4_u64)
+//!               .map(|id| {
+//!                      let arena = &arena;
+//!                      s.spawn(move || {
+//!                             let squares = arena.alloc_slice_fill_with(100, |i| id * i as u64);
+//!                             squares.iter().sum()
+//!                      })
+//!               })
+//!               .collect();
+//!        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+//! });
+//! assert_eq!(totals, [0, 4950, 9900, 14850]);
+//! assert!(arena.chunk_count() > 1, "3200 bytes don't fit one 1 KiB chunk");
+//! arena.reset();
+//! assert_eq!(arena.chunk_count(), 1);
+//! ```
+
+use std::{alloc::{self, Layout},
+          fmt,
+          mem::MaybeUninit,
+          ptr::{self, NonNull},
+          slice};
+
+use crate::atomic::{AtomicPtr, AtomicUsize,
+                    Ordering::{AcqRel, Acquire, Relaxed}};
+
+/// Bump granularity: every reservation is a multiple of this, and chunks are aligned to it.
+const GRAIN: usize = 8;
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A shared bump allocator; see the [module docs](self).
+pub struct AtomicArena {
+       current:    AtomicPtr<Chunk>,
+       chunk_size: usize,
+}
+// SAFETY: the arena owns no values, only bytes; allocations are disjoint, and the chunk chain is only
+// mutated through `&mut self` (reset, drop) apart from CAS-publishing a new head.
+unsafe impl Send for AtomicArena {}
+// SAFETY: as above.
+unsafe impl Sync for AtomicArena {}
+
+struct Chunk {
+       base:     NonNull<u8>,
+       capacity: usize,
+       /// Bytes reserved so far; may overshoot `capacity` once the chunk is full.
+       used:     AtomicUsize,
+       /// The chunk that was current before this one; fixed at creation.
+       previous: *mut Chunk,
+}
+
+impl Chunk {
+       fn allocate(capacity: usize, previous: *mut Chunk) -> *mut Chunk {
+              let layout = Layout::from_size_align(capacity, GRAIN).expect("arena chunk too large");
+              // SAFETY: `capacity` is non-zero (chunk sizes and reservations both are).
+              let base = NonNull::new(unsafe { alloc::alloc(layout) }).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+              Box::into_raw(Box::new(Chunk { base, capacity, used: AtomicUsize::new(0), previous }))
+       }
+
+       /// ## Safety
+       /// `chunk` came from [`allocate`](Self::allocate), and nothing points into it any more.
+       unsafe fn free(chunk: *mut Chunk) {
+              // SAFETY: the caller promises sole ownership of a chunk from `allocate`.
+              let chunk = unsafe { Box::from_raw(chunk) };
+              // SAFETY: allocated in `allocate` with this very layout.
+              unsafe { alloc::dealloc(chunk.base.as_ptr(), Layout::from_size_align_unchecked(chunk.capacity, GRAIN)) };
+       }
+}
+
+#[expect(clippy::mut_from_ref, reason = "each allocation is a distinct region, not reused until `&mut self`")]
+impl AtomicArena {
+       /// An arena with 64 KiB chunks.
+       pub fn new() -> Self { Self::with_chunk_size(DEFAULT_CHUNK_SIZE) }
+
+       /// An arena whose chunks hold `bytes` (more for a single allocation that wouldn't fit).
+       ///
+       /// ## Panics
+       /// If `bytes` is zero.
+       pub fn with_chunk_size(bytes: usize) -> Self {
+              assert!(bytes > 0, "arena chunk size must be non-zero");
+              let chunk_size = bytes.next_multiple_of(GRAIN);
+              Self { current: AtomicPtr::new(Chunk::allocate(chunk_size, ptr::null_mut())), chunk_size }
+       }
+
+       /// Move `value` into the arena. Its destructor will never run.
+       pub fn alloc<T>(&self, value: T) -> &mut T {
+              let slot = self.alloc_layout(Layout::new::<T>()).cast::<T>();
+              // SAFETY: a fresh, suitably sized and aligned region nobody else can reach.
+              unsafe {
+                     slot.write(value);
+                     &mut *slot.as_ptr()
+              }
+       }
+
+       /// Copy `source` into the arena.
+       pub fn alloc_slice_copy<T: Copy>(&self, source: &[T]) -> &mut [T] {
+              let slots = self.alloc_uninit_slice::<T>(source.len());
+              // SAFETY: the regions can't overlap; `T: Copy`, so a bitwise copy is a copy.
+              unsafe {
+                     ptr::copy_nonoverlapping(source.as_ptr(), slots.as_mut_ptr().cast::<T>(), source.len());
+                     slice::from_raw_parts_mut(slots.as_mut_ptr().cast::<T>(), source.len())
+              }
+       }
+
+       /// A slice of `len` values, `f(i)` for each index. Destructors will never run.
+       ///
+       /// If `f` panics, the space (and any values already made) is simply abandoned.
+       pub fn alloc_slice_fill_with<T>(&self, len: usize, mut f: impl FnMut(usize) -> T) -> &mut [T] {
+              let slots = self.alloc_uninit_slice::<T>(len);
+              for (index, slot) in slots.iter_mut().enumerate() {
+                     slot.write(f(index));
+              }
+              // SAFETY: every element was just initialized.
+              unsafe { slice::from_raw_parts_mut(slots.as_mut_ptr().cast::<T>(), len) }
+       }
+
+       /// Raw space for `layout`: valid, uninitialized and unaliased until the arena is reset or dropped.
+       ///
+       /// ## Panics
+       /// If rounding `layout` up for the bump overflows `usize`.
+       pub fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+              if layout.size() == 0 {
+                     return NonNull::<u8>::dangling().with_addr(layout.align().try_into().expect("alignment is non-zero"));
+              }
+              let reserve = layout
+                     .size()
+                     .checked_next_multiple_of(GRAIN)
+                     .and_then(|size| size.checked_add(layout.align().saturating_sub(GRAIN)))
+                     .expect("arena allocation too large");
+              loop {
+                     let current = self.current.load(Acquire);
+                     // SAFETY: chunks live until `reset`/drop, which take `&mut self`.
+                     let chunk = unsafe { &*current };
+                     let offset = chunk.used.fetch_add(reserve, Relaxed);
+                     if offset.checked_add(reserve).is_some_and(|end| end <= chunk.capacity) {
+                            // SAFETY: `[offset, offset + reserve)` is within the chunk and ours alone.
+                            let start = unsafe { chunk.base.add(offset) };
+                            let padding = start.align_offset(layout.align());
+                            // SAFETY: `start` is GRAIN-aligned, so the padding fits in the slack we reserved.
+                            return unsafe { start.add(padding) };
+                     }
+                     self.grow(current, reserve);
+              }
+       }
+
+       /// Free every chunk but the newest, and start bumping from its beginning again.
+       pub fn reset(&mut self) {
+              // Relaxed loads and stores rather than `get_mut` (which loom's atomics lack): `&mut self` means nobody to synchronize with
+              let current = self.current.load(Relaxed);
+              // SAFETY: `&mut self`: no allocation can still be borrowed, and nobody else is bumping.
+              let chunk = unsafe { &mut *current };
+              chunk.used.store(0, Relaxed);
+              let mut previous = std::mem::replace(&mut chunk.previous, ptr::null_mut());
+              while !previous.is_null() {
+                     // SAFETY: as above; each older chunk is reachable only through this chain.
+                     let next = unsafe { (*previous).previous };
+                     // SAFETY: as above.
+                     unsafe { Chunk::free(previous) };
+                     previous = next;
+              }
+       }
+
+       /// How many chunks the arena holds right now. Only a snapshot.
+       pub fn chunk_count(&self) -> usize { self.chunks().count() }
+
+       /// Total bytes of all chunks (used or not). Only a snapshot.
+       pub fn capacity(&self) -> usize { self.chunks().map(|chunk| chunk.capacity).sum() }
+
+       fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+              let mut next = self.current.load(Acquire);
+              std::iter::from_fn(move || {
+                     // SAFETY: chunks live until `&mut self`; `previous` links never change once published.
+                     let chunk = unsafe { next.as_ref()? };
+                     next = chunk.previous;
+                     Some(chunk)
+              })
+       }
+
+       fn alloc_uninit_slice<T>(&self, len: usize) -> &mut [MaybeUninit<T>] {
+              let layout = Layout::array::<T>(len).expect("arena allocation too large");
+              let slots = self.alloc_layout(layout).cast::<MaybeUninit<T>>();
+              // SAFETY: fresh space for `len` `T`s; uninitialized is fine for `MaybeUninit`.
+              unsafe { slice::from_raw_parts_mut(slots.as_ptr(), len) }
+       }
+
+       /// `full` couldn't fit `reserve` bytes: chain a new chunk in front of it, unless another thread already did.
+       #[cold]
+       fn grow(&self, full: *mut Chunk, reserve: usize) {
+              let fresh = Chunk::allocate(self.chunk_size.max(reserve), full);
+              if self.current.compare_exchange(full, fresh, AcqRel, Acquire).is_err() {
+                     // SAFETY: never published, so still ours alone.
+                     unsafe { Chunk::free(fresh) };
+              }
+       }
+}
+
+impl Default for AtomicArena {
+       fn default() -> Self { Self::new() }
+}
+
+impl Drop for AtomicArena {
+       fn drop(&mut self) {
+              self.reset();
+              // SAFETY: `reset` left just this chunk, and `&mut self` means nothing borrows from it.
+              unsafe { Chunk::free(self.current.load(Relaxed)) };
+       }
+}
+
+impl fmt::Debug for AtomicArena {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("AtomicArena")
+                     .field("chunk_size", &self.chunk_size)
+                     .field("chunks", &self.chunk_count())
+                     .field("capacity", &self.capacity())
+                     .finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{collections::HashSet, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_values_and_alignment() {
+              #[repr(align(64))]
+              struct Wide(u8);
+
+              let arena = AtomicArena::with_chunk_size(256);
+              let byte = arena.alloc(1_u8);
+              let wide = arena.alloc(Wide(2));
+              let words = arena.alloc_slice_copy(&[3_u64, 4, 5]);
+              let empty = arena.alloc_slice_copy::<u32>(&[]);
+              *byte += 10;
+              assert_eq!((*byte, wide.0, &*words, empty.len()), (11, 2, &[3, 4, 5][..], 0));
+              assert_eq!(ptr::from_mut(wide).addr() % 64, 0);
+              assert_eq!(words.as_ptr().addr() % std::mem::align_of::<u64>(), 0);
+              assert_eq!(arena.alloc(()), &());
+       }
+
+       #[test]
+       fn test_chains_chunks_and_oversized() {
+              let mut arena = AtomicArena::with_chunk_size(64);
+              for i in 0..20_u64 {
+                     assert_eq!(*arena.alloc(i), i);
+              }
+              assert_eq!(arena.chunk_count(), 3, "8 words per chunk");
+              let big = arena.alloc_slice_fill_with(100, |i| i as u8);
+              assert_eq!(big[99], 99);
+              assert_eq!(arena.chunk_count(), 4);
+              assert!(arena.capacity() >= 3 * 64 + 100);
+
+              arena.reset();
+              assert_eq!(arena.chunk_count(), 1);
+              let capacity = arena.capacity();
+              let first = ptr::from_mut(arena.alloc(0_u64)).addr();
+              assert_eq!(arena.capacity(), capacity, "reuses the kept chunk");
+              arena.reset();
+              assert_eq!(ptr::from_mut(arena.alloc(0_u64)).addr(), first, "bumps from the start again");
+       }
+
+       #[test]
+       fn test_concurrent_allocations_are_disjoint() {
+              let arena = AtomicArena::with_chunk_size(512);
+              let per_thread = if cfg!(miri) { 20 } else { 2_000 };
+              let slices: Vec<Vec<&[usize]>> = thread::scope(|s| {
+                     let handles: Vec<_> = (0..4)
+                            .map(|id| {
+                                   let arena = &arena;
+                                   s.spawn(move || (0..per_thread).map(|_| &*arena.alloc_slice_fill_with(3, |_| id)).collect())
+                            })
+                            .collect();
+                     handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+              });
+              let mut addresses = HashSet::new();
+              for (id, slices) in slices.iter().enumerate() {
+                     for slice in slices {
+                            assert_eq!(*slice, [id; 3], "nobody else wrote into our slice");
+                            assert!(addresses.insert(slice.as_ptr().addr()));
+                     }
+              }
+       }
+}
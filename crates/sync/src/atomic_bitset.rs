@@ -0,0 +1,210 @@
+//! Fixed-size set of bits that any number of threads set, clear and search at once.
+//!
+//! Each bit is a flag a thread can flip with one atomic RMW on the word that holds it (`fetch_or` / `fetch_and`):
+//! - [`set`](AtomicBitSet::set) / [`clear`](AtomicBitSet::clear), and [`test_and_set`](AtomicBitSet::test_and_set) /
+//!   [`test_and_clear`](AtomicBitSet::test_and_clear), which say whether this call was the one that flipped it
+//! - [`find_first_zero`](AtomicBitSet::find_first_zero): a word at a time, skipping full words
+//! - [`claim_first_zero`](AtomicBitSet::claim_first_zero): find *and* set, retrying if another thread got there
+//!   first; the building block for handing out slots
+//! - [`iter`](AtomicBitSet::iter): the set bits, ascending
+//!
+//! Searches and iteration read one word at a time: a snapshot of each word, not of the whole set.
+//!
+//! ## Orderings
+//! Setting a bit acquires and clearing it releases (RMWs are `AcqRel`), so a bit can guard the slot it stands for:
+//! whatever a thread wrote before clearing a slot's bit is visible to the next thread to claim it.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::AtomicBitSet;
+//!
+//! // sieve of Eratosthenes: threads strike out multiples concurrently; a bit set twice is no harm
+//! let composite = AtomicBitSet::new(100);
+//! thread::scope(|s| {
+//!        for p in [2, 3, 5, 7] {
+//!               let composite = &composite;
+//!               s.spawn(move || (p * p..100).step_by(p).for_each(|n| composite.set(n)));
+//!        }
+//! });
+//! let primes: Vec<usize> = (2..100).filter(|&n| !composite.get(n)).collect();
+//! assert_eq!(primes.len(), 25);
+//! assert_eq!(composite.find_first_zero(), Some(0));
+//! ```
+
+use std::fmt;
+
+use crate::atomic::{AtomicU64,
+                    Ordering::{AcqRel, Acquire, Relaxed}};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// `len` atomic bits, all clear to start; see the [module docs](self).
+pub struct AtomicBitSet {
+       words: Box<[AtomicU64]>,
+       len:   usize,
+}
+
+impl AtomicBitSet {
+       pub fn new(len: usize) -> Self { Self { words: (0..len.div_ceil(WORD_BITS)).map(|_| AtomicU64::new(0)).collect(), len } }
+
+       pub fn len(&self) -> usize { self.len }
+
+       pub fn is_empty(&self) -> bool { self.len == 0 }
+
+       /// ## Panics
+       /// If `index >= len`, as do the other methods taking an index.
+       pub fn get(&self, index: usize) -> bool {
+              let (word, mask) = self.locate(index);
+              word.load(Acquire) & mask != 0
+       }
+
+       pub fn set(&self, index: usize) { self.test_and_set(index); }
+
+       pub fn clear(&self, index: usize) { self.test_and_clear(index); }
+
+       /// Set the bit; `true` if it was already set (so some other call set it).
+       pub fn test_and_set(&self, index: usize) -> bool {
+              let (word, mask) = self.locate(index);
+              word.fetch_or(mask, AcqRel) & mask != 0
+       }
+
+       /// Clear the bit; `true` if it was set (so this call cleared it).
+       pub fn test_and_clear(&self, index: usize) -> bool {
+              let (word, mask) = self.locate(index);
+              word.fetch_and(!mask, AcqRel) & mask != 0
+       }
+
+       /// The lowest clear bit, if any. Only a snapshot: another thread may set it at once.
+       pub fn find_first_zero(&self) -> Option<usize> {
+              self.words.iter().enumerate().find_map(|(at, word)| self.first_zero_in(at, word.load(Relaxed)))
+       }
+
+       /// Set the lowest clear bit and return its index: a free slot, now ours. `None` if every bit is set.
+       pub fn claim_first_zero(&self) -> Option<usize> {
+              for (at, word) in self.words.iter().enumerate() {
+                     let mut bits = word.load(Relaxed);
+                     while let Some(index) = self.first_zero_in(at, bits) {
+                            let mask = 1 << (index % WORD_BITS);
+                            let previous = word.fetch_or(mask, AcqRel);
+                            if previous & mask == 0 {
+                                   return Some(index);
+                            }
+                            bits = previous | mask; // lost it to another thread: look again in what we now know
+                     }
+              }
+              None
+       }
+
+       /// Set bits right now. Only a snapshot.
+       pub fn count_ones(&self) -> usize { self.words.iter().map(|word| word.load(Relaxed).count_ones() as usize).sum() }
+
+       /// Indices of the set bits, ascending; each word is read once, as the iterator reaches it.
+       pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+              self.words.iter().enumerate().flat_map(|(at, word)| {
+                     let mut bits = word.load(Acquire);
+                     std::iter::from_fn(move || {
+                            let bit = bits.trailing_zeros() as usize;
+                            (bits != 0).then(|| {
+                                   bits &= bits - 1;
+                                   at * WORD_BITS + bit
+                            })
+                     })
+              })
+       }
+
+       /// Clear every bit. Not atomic as a whole: bits set concurrently may or may not survive.
+       pub fn clear_all(&self) { self.words.iter().for_each(|word| word.store(0, Relaxed)); }
+
+       fn locate(&self, index: usize) -> (&AtomicU64, u64) {
+              assert!(index < self.len, "bit {index} out of range for a set of {}", self.len);
+              (&self.words[index / WORD_BITS], 1 << (index % WORD_BITS))
+       }
+
+       /// Index of the lowest clear bit in word `at` holding `bits`, unless it's past the end.
+       fn first_zero_in(&self, at: usize, bits: u64) -> Option<usize> {
+              let index = at * WORD_BITS + bits.trailing_ones() as usize;
+              (bits != u64::MAX && index < self.len).then_some(index)
+       }
+}
+
+impl fmt::Debug for AtomicBitSet {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_set().entries(self.iter()).finish() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{collections::HashSet, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_bits_across_words() {
+              let bits = AtomicBitSet::new(130);
+              for index in [0, 63, 64, 129] {
+                     assert!(!bits.test_and_set(index));
+              }
+              assert!(bits.test_and_set(64), "already set");
+              assert!(bits.test_and_clear(63));
+              assert!(!bits.test_and_clear(63), "already clear");
+              assert_eq!(bits.iter().collect::<Vec<_>>(), [0, 64, 129]);
+              assert_eq!(bits.find_first_zero(), Some(1));
+              assert_eq!(bits.count_ones(), 3);
+              bits.clear_all();
+              assert_eq!(bits.iter().next(), None);
+       }
+
+       #[test]
+       fn test_find_first_zero_ignores_bits_past_the_end() {
+              let bits = AtomicBitSet::new(65);
+              (0..65).for_each(|index| bits.set(index));
+              assert_eq!(bits.find_first_zero(), None, "bits 65..128 of the last word aren't in the set");
+              assert_eq!(bits.claim_first_zero(), None);
+              bits.clear(64);
+              assert_eq!(bits.claim_first_zero(), Some(64));
+       }
+
+       #[test]
+       fn test_claims_are_unique() {
+              const SLOTS: usize = 200;
+              let bits = AtomicBitSet::new(SLOTS);
+              let claimed: Vec<usize> = thread::scope(|s| {
+                     let handles: Vec<_> =
+                            (0..4).map(|_| s.spawn(|| std::iter::from_fn(|| bits.claim_first_zero()).collect::<Vec<_>>())).collect();
+                     handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+              });
+              assert_eq!(claimed.len(), SLOTS);
+              assert_eq!(claimed.into_iter().collect::<HashSet<_>>().len(), SLOTS, "a slot claimed twice");
+       }
+
+       #[test]
+       #[should_panic(expected = "bit 8 out of range for a set of 8")]
+       fn test_out_of_range_panics() { AtomicBitSet::new(8).set(8); }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+       use loom::{sync::Arc, thread};
+
+       use super::*;
+
+       /// A slot's contents, written before its bit is cleared, are visible to whoever claims it next.
+       #[test]
+       fn loom_clear_hands_over_the_slot() {
+              loom::model(|| {
+                     let bits = Arc::new(AtomicBitSet::new(1));
+                     let slot = Arc::new(AtomicU64::new(0));
+                     assert_eq!(bits.claim_first_zero(), Some(0));
+                     let other = {
+                            let (bits, slot) = (bits.clone(), slot.clone());
+                            thread::spawn(move || bits.claim_first_zero().map(|_| slot.load(Relaxed)))
+                     };
+                     slot.store(7, Relaxed);
+                     bits.clear(0);
+                     assert!(matches!(other.join().unwrap(), None | Some(7)));
+              });
+       }
+}
@@ -0,0 +1,308 @@
+//! `AtomicCell<T>`: atomic load/store/swap/CAS for any `T`, not just integers and `bool`.
+//!
+//! When `T` is a primitive a native atomic can hold (an integer, `bool`, `char`, `f32` or `f64`, of 1, 2, 4 or 8
+//! bytes, aligned like the atomic), the operations go straight to the matching `AtomicU*` and are lock-free.
+//! Anything else takes a spin lock. [`AtomicCell::<T>::is_lock_free`] says which.
+//!
+//! Breaking: that used to be the `IS_LOCK_FREE` const, and `T` had no `'static` bound. Telling the primitives apart
+//! takes a [`TypeId`], which needs `T: 'static` and can't be compared in a const.
+//!
+//! ## Design
+//! - native: `T`'s bytes are reinterpreted as the same-width integer. Only sound if every byte of `T` is part of
+//!   its value: a struct of the same size and alignment can still have padding (`#[repr(align(4))] struct X(u8)`),
+//!   and reading that as an integer reads uninitialised memory. Which types have none can't be asked of a generic
+//!   `T` on stable Rust, so the native path takes a fixed list of primitives, told apart by [`TypeId`] (hence
+//!   `T: 'static`).
+//! - fallback: a global table of cache-padded [`SpinLock`]s, picked by the cell's address; no per-cell overhead,
+//!   and critical sections are a copy of `T`
+//! - [`compare_exchange`](AtomicCell::compare_exchange) compares with `==`; the primitives with `Eq` are equal
+//!   exactly when their bytes are, so the native compare-exchange agrees
+//! - loads are Acquire; everything else AcqRel (a store is a swap, so the old value can be dropped)
+//!
+//! ## Example
+//! ```
+//! use sync::AtomicCell;
+//!
+//! #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+//! struct Point {
+//!        x: i32,
+//!        y: i32,
+//! }
+//!
+//! let origin = Point { x: 0, y: 0 };
+//! let cell = AtomicCell::new(origin);
+//! assert!(!AtomicCell::<Point>::is_lock_free()); // not a primitive: locked
+//! assert!(AtomicCell::<u64>::is_lock_free());
+//!
+//! assert_eq!(cell.swap(Point { x: 1, y: 2 }), origin);
+//! assert_eq!(cell.compare_exchange(origin, origin), Err(Point { x: 1, y: 2 }));
+//! assert_eq!(cell.load().y, 2);
+//! ```
+
+use std::{any::TypeId,
+          cell::UnsafeCell,
+          fmt,
+          mem::{self, ManuallyDrop}};
+
+#[cfg(target_has_atomic = "64")]
+use crate::atomic::AtomicU64;
+use crate::{CachePadded, SpinLock, SpinLockGuard,
+            atomic::{AtomicU8, AtomicU16, AtomicU32,
+                     Ordering::{AcqRel, Acquire}}};
+
+/// Locks for cells that can't use a native atomic; a prime count spreads neighbouring addresses.
+static LOCKS: [CachePadded<SpinLock<()>>; 61] = [const { CachePadded(SpinLock::new(())) }; 61];
+
+/// The striped lock guarding the (non-atomic) value at `address`; shared with [`AtomicDoubleWord`](crate::AtomicDoubleWord).
+pub(crate) fn lock_for<T>(address: *const T) -> SpinLockGuard<'static, ()> { LOCKS[(address as usize >> 3) % LOCKS.len()].lock() }
+
+/// A `T` that can be shared and atomically read or replaced.
+#[repr(transparent)]
+pub struct AtomicCell<T> {
+       value: UnsafeCell<T>,
+}
+// SAFETY: values only move in or out whole (atomically, or under a lock), so a shared cell is a channel for `T`s.
+unsafe impl<T: Send> Sync for AtomicCell<T> {}
+
+/// The types the native path takes: primitives with no padding, each byte part of the value.
+fn is_primitive<T: 'static>() -> bool {
+       [
+              TypeId::of::<u8>(),
+              TypeId::of::<i8>(),
+              TypeId::of::<bool>(),
+              TypeId::of::<u16>(),
+              TypeId::of::<i16>(),
+              TypeId::of::<u32>(),
+              TypeId::of::<i32>(),
+              TypeId::of::<f32>(),
+              TypeId::of::<char>(),
+              TypeId::of::<u64>(),
+              TypeId::of::<i64>(),
+              TypeId::of::<f64>(),
+              TypeId::of::<usize>(),
+              TypeId::of::<isize>(),
+       ]
+       .contains(&TypeId::of::<T>())
+}
+
+/// Run `$body` with `$atomic`/`$int` as the native atomic and integer types matching `T`, or `$fallback`.
+macro_rules! native {
+       ($t:ty, $atomic:ident, $int:ident => $body:expr, else => $fallback:expr) => {
+              match (mem::size_of::<$t>(), AtomicCell::<$t>::is_lock_free()) {
+                     (1, true) => {
+                            type $atomic = AtomicU8;
+                            type $int = u8;
+                            $body
+                     }
+                     (2, true) => {
+                            type $atomic = AtomicU16;
+                            type $int = u16;
+                            $body
+                     }
+                     (4, true) => {
+                            type $atomic = AtomicU32;
+                            type $int = u32;
+                            $body
+                     }
+                     #[cfg(target_has_atomic = "64")]
+                     (8, true) => {
+                            type $atomic = AtomicU64;
+                            type $int = u64;
+                            $body
+                     }
+                     _ => $fallback,
+              }
+       };
+}
+
+impl<T: 'static> AtomicCell<T> {
+       /// Whether operations on this `T` are native atomics (`true`) or take a lock.
+       pub fn is_lock_free() -> bool {
+              let size = mem::size_of::<T>();
+              is_primitive::<T>()
+                     && (size == 1 || size == 2 || size == 4 || (size == 8 && cfg!(target_has_atomic = "64")))
+                     // an `AtomicU*` is aligned to its size, a primitive may not be: `u64` is 4-aligned on i686
+                     && mem::align_of::<T>() >= size
+       }
+
+       pub const fn new(value: T) -> Self { Self { value: UnsafeCell::new(value) } }
+
+       pub fn into_inner(self) -> T { self.value.into_inner() }
+
+       /// Exclusive access via `&mut self` needs no atomics.
+       pub fn get_mut(&mut self) -> &mut T { self.value.get_mut() }
+
+       pub fn store(&self, value: T) { drop(self.swap(value)); }
+
+       /// Store `value`, returning the previous one.
+       pub fn swap(&self, value: T) -> T {
+              native!(T, A, I => {
+                     let value = ManuallyDrop::new(value);
+                     // SAFETY: `is_lock_free`: `T` is a primitive of `I`'s size and alignment, so its bytes can be read as one.
+                     let bits: I = unsafe { mem::transmute_copy(&*value) };
+                     // SAFETY: same size and alignment as `A`; every access to the cell goes through `A`.
+                     let previous = unsafe { self.atomic::<A>() }.swap(bits, AcqRel);
+                     // SAFETY: `previous` are the bytes of a `T` stored earlier (by `new` or a swap).
+                     unsafe { mem::transmute_copy(&previous) }
+              }, else => {
+                     let _guard = self.lock();
+                     // SAFETY: we hold this cell's lock, and every access on the fallback path does too.
+                     mem::replace(unsafe { &mut *self.value.get() }, value)
+              })
+       }
+
+       /// ## Safety
+       /// Only when `is_lock_free` and `A` is the matching atomic (see `native!`).
+       unsafe fn atomic<A>(&self) -> &A {
+              // SAFETY: passed on from the caller: same size and alignment, and no non-atomic access while shared.
+              unsafe { &*self.value.get().cast::<A>() }
+       }
+
+       fn lock(&self) -> SpinLockGuard<'static, ()> { lock_for(self.value.get()) }
+}
+
+impl<T: Copy + 'static> AtomicCell<T> {
+       pub fn load(&self) -> T {
+              native!(T, A, I => {
+                     // SAFETY: as in `swap`.
+                     let bits: I = unsafe { self.atomic::<A>() }.load(Acquire);
+                     // SAFETY: the bytes of a `T` stored earlier.
+                     unsafe { mem::transmute_copy(&bits) }
+              }, else => {
+                     let _guard = self.lock();
+                     // SAFETY: we hold this cell's lock.
+                     unsafe { *self.value.get() }
+              })
+       }
+}
+
+impl<T: Copy + Eq + 'static> AtomicCell<T> {
+       /// Replace the value with `new` if it `==` `current`; `Err` carries the value found otherwise.
+       pub fn compare_exchange(&self, current: T, new: T) -> Result<T, T> {
+              native!(T, A, I => {
+                     // SAFETY: as in `swap`.
+                     let atomic = unsafe { self.atomic::<A>() };
+                     // SAFETY: `T` is a primitive of `I`'s size.
+                     let (current, new): (I, I) = unsafe { (mem::transmute_copy(&current), mem::transmute_copy(&new)) };
+                     atomic
+                            .compare_exchange(current, new, AcqRel, Acquire)
+                            // SAFETY: the bytes of a `T` stored earlier.
+                            .map(|previous| unsafe { mem::transmute_copy(&previous) })
+                            // SAFETY: likewise.
+                            .map_err(|found| unsafe { mem::transmute_copy(&found) })
+              }, else => {
+                     let _guard = self.lock();
+                     // SAFETY: we hold this cell's lock.
+                     let value = unsafe { &mut *self.value.get() };
+                     if *value == current { Ok(mem::replace(value, new)) } else { Err(*value) }
+              })
+       }
+}
+
+impl<T: Default + 'static> Default for AtomicCell<T> {
+       fn default() -> Self { Self::new(T::default()) }
+}
+
+impl<T: 'static> From<T> for AtomicCell<T> {
+       fn from(value: T) -> Self { Self::new(value) }
+}
+
+impl<T: Copy + fmt::Debug + 'static> fmt::Debug for AtomicCell<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_tuple("AtomicCell").field(&self.load()).finish() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_lock_freedom_by_type() {
+              #[derive(Clone, Copy)]
+              #[repr(align(4))] // 4 bytes, 4-aligned, 3 of them padding
+              struct Padded(u8);
+
+              let native = [AtomicCell::<u8>::is_lock_free(), AtomicCell::<char>::is_lock_free(), AtomicCell::<f32>::is_lock_free()];
+              let locked = [
+                     AtomicCell::<Padded>::is_lock_free(),
+                     AtomicCell::<Option<&u8>>::is_lock_free(),
+                     AtomicCell::<[u8; 3]>::is_lock_free(),
+                     AtomicCell::<()>::is_lock_free(),
+              ];
+              assert_eq!((native, locked), ([true; 3], [false; 4]));
+              // a primitive less aligned than its atomic (`u64` on i686) takes the lock
+              assert_eq!(AtomicCell::<u64>::is_lock_free(), cfg!(target_has_atomic = "64") && mem::align_of::<u64>() == 8);
+
+              let padded = AtomicCell::new(Padded(7));
+              padded.store(Padded(8));
+              assert_eq!(padded.load().0, 8);
+       }
+
+       #[test]
+       fn test_native_and_locked_paths_agree() {
+              let native = AtomicCell::new(1_u32);
+              let locked = AtomicCell::new([1_u32; 3]);
+              assert_eq!(native.swap(2), 1);
+              assert_eq!(locked.swap([2; 3]), [1; 3]);
+              assert_eq!(native.compare_exchange(1, 3), Err(2));
+              assert_eq!(locked.compare_exchange([1; 3], [3; 3]), Err([2; 3]));
+              assert_eq!(native.compare_exchange(2, 3), Ok(2));
+              assert_eq!(locked.compare_exchange([2; 3], [3; 3]), Ok([2; 3]));
+              assert_eq!((native.load(), locked.load()), (3, [3; 3]));
+              assert_eq!(format!("{native:?}"), "AtomicCell(3)");
+       }
+
+       #[test]
+       fn test_compare_exchange_uses_eq_not_bytes() {
+              #[derive(Debug, Clone, Copy)]
+              struct Signed(i32);
+              impl PartialEq for Signed {
+                     fn eq(&self, other: &Self) -> bool { self.0.abs() == other.0.abs() }
+              }
+              impl Eq for Signed {}
+
+              let cell = AtomicCell::new(Signed(-5));
+              assert_eq!(cell.compare_exchange(Signed(5), Signed(7)).map(|found| found.0), Ok(-5));
+              assert_eq!(cell.load().0, 7);
+       }
+
+       #[test]
+       fn test_swaps_drop_each_value_once() {
+              let cell = AtomicCell::new(Box::new(0));
+              let large = AtomicCell::new((String::new(), 0_u64));
+              thread::scope(|s| {
+                     for t in 1..=4 {
+                            let (cell, large) = (&cell, &large);
+                            s.spawn(move || {
+                                   for i in 0..100 {
+                                          cell.store(Box::new(t * 1_000 + i));
+                                          large.store((t.to_string(), i));
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(*cell.into_inner() % 1_000, 99);
+              assert_eq!(large.into_inner().1, 99);
+       }
+
+       #[test]
+       fn test_concurrent_increments_by_cas() {
+              let cell = AtomicCell::new([0_u64; 2]);
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   for _ in 0..500 {
+                                          let mut current = cell.load();
+                                          while let Err(found) = cell.compare_exchange(current, [current[0] + 1, current[1] + 2]) {
+                                                 current = found;
+                                          }
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(cell.load(), [2_000, 4_000]);
+       }
+}
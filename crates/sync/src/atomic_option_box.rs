@@ -0,0 +1,164 @@
+//! `AtomicOptionBox<T>`: an atomic slot for an owned `Option<Box<T>>`.
+//!
+//! An `AtomicPtr<T>` where null means `None` and anything else is a `Box<T>` the slot owns.
+//! Whole boxes move in ([`store`](AtomicOptionBox::store), [`swap`](AtomicOptionBox::swap),
+//! [`store_if_empty`](AtomicOptionBox::store_if_empty)) and out ([`take`](AtomicOptionBox::take));
+//! nobody ever gets a `&T` into a shared slot, so no reclamation scheme is needed.
+//! This is the "hand a heap value to another thread" step the channel chapter keeps rebuilding by hand.
+//!
+//! ## Design
+//! - every exchange is AcqRel: Release so the box's contents are visible to whoever takes it,
+//!   Acquire so what we take is fully visible to us
+//! - whatever is left in the slot when it drops is freed
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::AtomicOptionBox;
+//!
+//! let slot = AtomicOptionBox::none();
+//! thread::scope(|s| {
+//!        s.spawn(|| slot.store(Some(Box::new(vec![1, 2, 3]))));
+//! });
+//! assert_eq!(slot.take().as_deref(), Some(&vec![1, 2, 3]));
+//! assert!(slot.take().is_none());
+//! ```
+
+use std::{fmt, marker::PhantomData, ptr};
+
+use crate::atomic::{AtomicPtr,
+                    Ordering::{AcqRel, Acquire, Relaxed},
+                    loom_const_fn};
+
+/// An `Option<Box<T>>` that can be swapped atomically.
+pub struct AtomicOptionBox<T> {
+       pointer: AtomicPtr<T>,
+       /// We own a `Box<T>` (for `Send` and drop check).
+       owned:   PhantomData<Box<T>>,
+}
+// SAFETY: shared access only moves whole `Box<T>`s between threads, never exposes a `&T`: `T: Send` suffices.
+unsafe impl<T: Send> Sync for AtomicOptionBox<T> {}
+
+impl<T> AtomicOptionBox<T> {
+       loom_const_fn! {
+              pub fn none() -> Self { Self { pointer: AtomicPtr::new(ptr::null_mut()), owned: PhantomData } }
+       }
+
+       pub fn new(value: Option<Box<T>>) -> Self { Self { pointer: AtomicPtr::new(into_raw(value)), owned: PhantomData } }
+
+       /// Put `value` in, returning what was there.
+       pub fn swap(&self, value: Option<Box<T>>) -> Option<Box<T>> {
+              // SAFETY: the slot only ever holds null or a `Box` pointer it owns; the swap hands ownership to us.
+              unsafe { from_raw(self.pointer.swap(into_raw(value), AcqRel)) }
+       }
+
+       /// Put `value` in, dropping what was there.
+       pub fn store(&self, value: Option<Box<T>>) { drop(self.swap(value)); }
+
+       pub fn take(&self) -> Option<Box<T>> { self.swap(None) }
+
+       /// Put `value` in only if the slot is empty; gives it back otherwise.
+       pub fn store_if_empty(&self, value: Box<T>) -> Result<(), Box<T>> {
+              let pointer = Box::into_raw(value);
+              match self.pointer.compare_exchange(ptr::null_mut(), pointer, AcqRel, Relaxed) {
+                     Ok(_) => Ok(()),
+                     // SAFETY: from `Box::into_raw` just above, and not published.
+                     Err(_) => Err(unsafe { Box::from_raw(pointer) }),
+              }
+       }
+
+       /// Whether the slot is full. Only a snapshot.
+       pub fn is_some(&self) -> bool { !self.pointer.load(Relaxed).is_null() }
+
+       /// Exclusive access via `&mut self`: no synchronization, and the contents can be borrowed.
+       pub fn get_mut(&mut self) -> Option<&mut T> {
+              // SAFETY: null or a `Box` we own; `&mut self` means no one else can take it meanwhile.
+              unsafe { self.pointer.load(Relaxed).as_mut() }
+       }
+
+       pub fn into_inner(self) -> Option<Box<T>> {
+              let value = self.take();
+              std::mem::forget(self);
+              value
+       }
+}
+
+fn into_raw<T>(value: Option<Box<T>>) -> *mut T { value.map_or(ptr::null_mut(), Box::into_raw) }
+
+/// ## Safety
+/// `pointer` is null or an owned pointer from `Box::into_raw`, converted back only once.
+unsafe fn from_raw<T>(pointer: *mut T) -> Option<Box<T>> {
+       // SAFETY: passed on from the caller.
+       (!pointer.is_null()).then(|| unsafe { Box::from_raw(pointer) })
+}
+
+impl<T> Drop for AtomicOptionBox<T> {
+       fn drop(&mut self) {
+              // SAFETY: null or a `Box` we own, and we're its last user.
+              drop(unsafe { from_raw(self.pointer.load(Relaxed)) });
+       }
+}
+
+impl<T> Default for AtomicOptionBox<T> {
+       fn default() -> Self { Self::none() }
+}
+
+impl<T> From<Box<T>> for AtomicOptionBox<T> {
+       fn from(value: Box<T>) -> Self { Self::new(Some(value)) }
+}
+
+impl<T> fmt::Debug for AtomicOptionBox<T> {
+       /// Only whether it's full: the contents may be taken by another thread while we'd be reading them.
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.write_str(if self.pointer.load(Acquire).is_null() { "AtomicOptionBox(None)" } else { "AtomicOptionBox(Some(..))" })
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::{AtomicUsize, Ordering::Relaxed},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::test_util::DropCounter;
+
+       #[test]
+       fn test_swap_take_store_if_empty() {
+              let slot = AtomicOptionBox::from(Box::new(1));
+              assert_eq!(format!("{slot:?}"), "AtomicOptionBox(Some(..))");
+              assert_eq!(slot.swap(Some(Box::new(2))), Some(Box::new(1)));
+              assert_eq!(slot.store_if_empty(Box::new(3)), Err(Box::new(3)));
+              assert_eq!(slot.take(), Some(Box::new(2)));
+              assert!(!slot.is_some());
+              assert_eq!(slot.store_if_empty(Box::new(4)), Ok(()));
+              let mut slot = slot;
+              *slot.get_mut().unwrap() += 1;
+              assert_eq!(slot.into_inner(), Some(Box::new(5)));
+       }
+
+       #[test]
+       fn test_every_box_dropped_once() {
+              let drops = AtomicUsize::new(0);
+              let slot = AtomicOptionBox::none();
+              let taken = AtomicUsize::new(0);
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   for _ in 0..100 {
+                                          slot.store(Some(Box::new(DropCounter(&drops))));
+                                          if slot.take().is_some() {
+                                                 taken.fetch_add(1, Relaxed);
+                                          }
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(drops.load(Relaxed), 400 - usize::from(slot.is_some()));
+              drop(slot);
+              assert_eq!(drops.into_inner(), 400);
+              assert!(taken.into_inner() > 0);
+       }
+}
@@ -0,0 +1,114 @@
+//! Which atomic widths this target supports, at compile time and (for double-word CAS) at runtime.
+//!
+//! Rust only provides an `Atomic*` type where the target can do it lock-free, so for each width
+//! `cfg(target_has_atomic = "N")` *is* "`Atomic*` exists and is always lock-free".
+//! The one thing that needs a runtime check is double-word CAS ([`AtomicDoubleWord`]).
+//!
+//! `cargo xtask atomics` prints the [`report`].
+//!
+//! ## Example
+//! ```
+//! use sync::atomic_support;
+//!
+//! let word = atomic_support::WIDTHS.iter().find(|width| width.bits == usize::BITS).unwrap();
+//! assert!(word.lock_free);
+//! println!("{}", atomic_support::report());
+//! ```
+
+use std::{fmt, mem};
+
+use crate::AtomicDoubleWord;
+
+/// What the target offers at one width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Width {
+       pub bits:      u32,
+       /// The std type at this width, if the target has one.
+       pub atomic:    Option<&'static str>,
+       /// `cfg(target_has_atomic)`: the `Atomic*` exists, and so is always lock-free.
+       pub lock_free: bool,
+       /// Alignment the atomic needs (may exceed the plain integer's, e.g. `AtomicU64` on 32-bit x86).
+       pub align:     Option<usize>,
+}
+
+macro_rules! width {
+       ($bits:literal, $cfg:literal, $atomic:ident) => {{
+              #[cfg(target_has_atomic = $cfg)]
+              let width = Width {
+                     bits:      $bits,
+                     atomic:    Some(stringify!($atomic)),
+                     lock_free: true,
+                     align:     Some(mem::align_of::<std::sync::atomic::$atomic>()),
+              };
+              #[cfg(not(target_has_atomic = $cfg))]
+              let width = Width { bits: $bits, atomic: None, lock_free: false, align: None };
+              width
+       }};
+}
+
+/// Every integer width, as compiled for this target.
+pub const WIDTHS: [Width; 4] =
+       [width!(8, "8", AtomicU8), width!(16, "16", AtomicU16), width!(32, "32", AtomicU32), width!(64, "64", AtomicU64)];
+
+/// Whether `cfg(target_has_atomic = "128")`: no stable `AtomicU128` yet, but the target could.
+pub const HAS_ATOMIC_128: bool = cfg!(target_has_atomic = "128");
+
+/// Whether pointer-width atomics (`AtomicUsize`, `AtomicPtr`) exist on this target.
+pub const HAS_ATOMIC_PTR: bool = cfg!(target_has_atomic = "ptr");
+
+/// The compile-time widths plus the runtime double-word CAS check; `Display` prints a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+       pub widths:                [Width; 4],
+       pub pointer_bits:          u32,
+       pub has_atomic_ptr:        bool,
+       pub has_atomic_128:        bool,
+       /// [`AtomicDoubleWord::is_lock_free`]
+       pub double_word_lock_free: bool,
+}
+
+pub fn report() -> Report {
+       Report {
+              widths:                WIDTHS,
+              pointer_bits:          usize::BITS,
+              has_atomic_ptr:        HAS_ATOMIC_PTR,
+              has_atomic_128:        HAS_ATOMIC_128,
+              double_word_lock_free: AtomicDoubleWord::is_lock_free(),
+       }
+}
+
+impl fmt::Display for Report {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              writeln!(f, "{:>5}  {:<10}  {:<9}  align", "bits", "type", "lock-free")?;
+              for width in &self.widths {
+                     writeln!(
+                            f,
+                            "{:>5}  {:<10}  {:<9}  {}",
+                            width.bits,
+                            width.atomic.unwrap_or("-"),
+                            width.lock_free,
+                            width.align.map_or_else(|| "-".to_string(), |align| align.to_string())
+                     )?;
+              }
+              writeln!(f, "pointer width: {} bits (AtomicUsize/AtomicPtr: {})", self.pointer_bits, self.has_atomic_ptr)?;
+              writeln!(f, "target_has_atomic = \"128\": {}", self.has_atomic_128)?;
+              write!(f, "double-word CAS (AtomicDoubleWord): {}", if self.double_word_lock_free { "native" } else { "lock fallback" })
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_report_matches_std() {
+              let report = report();
+              assert_eq!(report.widths.map(|width| width.bits), [8, 16, 32, 64]);
+              assert!(report.has_atomic_ptr);
+              let word = report.widths.iter().find(|width| width.bits == usize::BITS).unwrap();
+              assert_eq!(word.align, Some(mem::align_of::<std::sync::atomic::AtomicUsize>()));
+              assert!(report.to_string().contains("double-word CAS"));
+       }
+}
@@ -0,0 +1,120 @@
+//! Exponential backoff for spin and CAS loops.
+//!
+//! Each failed attempt escalates how politely we wait:
+//! 1. `hint::spin_loop()`, doubling the number of hints each step
+//! 2. `thread::yield_now()`, giving the core to another runnable thread
+//! 3. `thread::park_timeout(..)`, actually sleeping
+//!
+//! Spinning is cheapest when the wait is short; sleeping is cheapest when it is long.
+//! Escalating lets a loop find out which case it is in without knowing up front.
+//!
+//! ## Example
+//! ```
+//! use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+//!
+//! use sync::Backoff;
+//!
+//! fn double(n: &AtomicUsize) -> usize {
+//!        let mut backoff = Backoff::new();
+//!        let mut current = n.load(Relaxed);
+//!        loop {
+//!               match n.compare_exchange_weak(current, current * 2, Relaxed, Relaxed) {
+//!                      Ok(_) => return current * 2,
+//!                      Err(observed) => current = observed,
+//!               }
+//!               backoff.spin();
+//!        }
+//! }
+//! assert_eq!(double(&AtomicUsize::new(21)), 42);
+//! ```
+
+use std::time::Duration;
+
+use crate::atomic::{park_timeout, spin_loop, yield_now};
+
+/// Steps (of doubling spin counts) before we stop purely spinning.
+const SPIN_LIMIT: u32 = 6;
+/// Steps before we stop yielding and start parking.
+const YIELD_LIMIT: u32 = 10;
+/// How long a parking step sleeps. (Parking is timed, as nobody is obliged to unpark us.)
+const PARK_TIMEOUT: Duration = Duration::from_micros(100);
+
+/// Escalating wait strategy for retry loops.
+///
+/// Cheap to create; make one per loop (not per iteration) and [`reset`](Self::reset) it after progress is made.
+#[derive(Debug, Default, Clone)]
+pub struct Backoff {
+       step: u32,
+}
+
+impl Backoff {
+       pub const fn new() -> Self { Self { step: 0 } }
+
+       /// Start over from the cheapest waiting stage.
+       pub fn reset(&mut self) { self.step = 0; }
+
+       /// Back off after a failed CAS (or similar) where another attempt can be made right away.
+       ///
+       /// Only ever spins: the contended value is changing, so there's progress to race for.
+       pub fn spin(&mut self) {
+              for _ in 0..1 << self.step.min(SPIN_LIMIT) {
+                     spin_loop();
+              }
+              if self.step <= SPIN_LIMIT {
+                     self.step += 1;
+              }
+       }
+
+       /// Back off while waiting on another thread (e.g. for a lock to be released).
+       ///
+       /// Escalates spin → yield → park.
+       pub fn snooze(&mut self) {
+              if self.step <= SPIN_LIMIT {
+                     for _ in 0..1 << self.step {
+                            spin_loop();
+                     }
+              } else if self.step <= YIELD_LIMIT {
+                     yield_now();
+              } else {
+                     park_timeout(PARK_TIMEOUT);
+              }
+              if self.step <= YIELD_LIMIT {
+                     self.step += 1;
+              }
+       }
+
+       /// Whether [`snooze`](Self::snooze) has escalated to parking.
+       ///
+       /// Callers with a proper blocking fallback (e.g. a futex wait) should switch to it at this point.
+       pub fn is_completed(&self) -> bool { self.step > YIELD_LIMIT }
+}
+
+#[cfg(test)]
+mod tests {
+       use super::*;
+
+       #[test]
+       fn test_snooze_escalates_and_resets() {
+              let mut backoff = Backoff::new();
+              for _ in 0..=YIELD_LIMIT {
+                     assert!(!backoff.is_completed());
+                     backoff.snooze();
+              }
+              assert!(backoff.is_completed());
+              // parking stage is stable
+              backoff.snooze();
+              assert!(backoff.is_completed());
+
+              backoff.reset();
+              assert!(!backoff.is_completed());
+       }
+
+       #[test]
+       fn test_spin_never_completes() {
+              let mut backoff = Backoff::new();
+              for _ in 0..100 {
+                     backoff.spin();
+              }
+              assert!(!backoff.is_completed());
+       }
+}
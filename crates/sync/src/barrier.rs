@@ -0,0 +1,173 @@
+//! Reusable barrier on a futex generation counter.
+//!
+//! `n` threads call [`wait`](Barrier::wait); all block until the `n`th arrives, then all continue.
+//! The barrier is immediately ready for the next round, so it works inside loops (phase after phase).
+//!
+//! [`with_callback`](Barrier::with_callback) adds a step between rounds: the leader runs it before releasing
+//! anyone, so whatever it does (swap buffers, check convergence, print progress) is done, and visible, by the time
+//! every thread's `wait` returns. For a party count that changes as threads come and go, see [`Phaser`](crate::Phaser).
+//!
+//! ## Design
+//! - `arrived` counts threads in the current round
+//! - `generation` is the futex word waiters sleep on; the last arrival (the *leader*)
+//!   resets `arrived`, runs the callback, and then bumps `generation`, which releases the round
+//!
+//! A waiter reads the generation *before* arriving, and the generation can't move until every thread has arrived,
+//! so nobody can mistake the previous round's release for its own.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::Barrier;
+//!
+//! let barrier = Barrier::new(3);
+//! let leaders: usize = thread::scope(|s| {
+//!        let handles: Vec<_> = (0..3).map(|_| s.spawn(|| (0..5).filter(|_| barrier.wait().is_leader()).count())).collect();
+//!        handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+//! });
+//! assert_eq!(leaders, 5); // one per round
+//! ```
+
+use std::fmt;
+
+use crate::{atomic::{AtomicU32,
+                     Ordering::{AcqRel, Acquire, Relaxed, Release},
+                     loom_const_fn},
+            futex::{wait, wake_all}};
+
+type Callback = Box<dyn Fn(u32) + Send + Sync>;
+
+/// Rendezvous point for a fixed number of threads, reusable round after round.
+pub struct Barrier {
+       n:          u32,
+       arrived:    AtomicU32,
+       generation: AtomicU32,
+       /// Run by each round's leader, with the round's number, before the others are released.
+       callback:   Option<Callback>,
+}
+
+/// Returned by [`Barrier::wait`]; exactly one thread per round is the leader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult(bool);
+impl BarrierWaitResult {
+       /// Whether this thread was the last to arrive (and released the others).
+       pub fn is_leader(&self) -> bool { self.0 }
+}
+
+impl Barrier {
+       loom_const_fn! {
+              /// Barrier releasing every `n` threads. `n == 0` behaves like `n == 1`: `wait` never blocks.
+              pub fn new(n: u32) -> Self {
+                     Self { n: if n == 0 { 1 } else { n }, arrived: AtomicU32::new(0), generation: AtomicU32::new(0), callback: None }
+              }
+       }
+
+       /// Barrier releasing every `n` threads, once the last to arrive has run `callback(round)`
+       /// (rounds count from 0, wrapping).
+       ///
+       /// If the callback panics, the round is still released, and the panic carries on in the leader.
+       pub fn with_callback(n: u32, callback: impl Fn(u32) + Send + Sync + 'static) -> Self {
+              Self { callback: Some(Box::new(callback)), ..Self::new(n) }
+       }
+
+       /// Block until `n` threads (this one included) are waiting.
+       pub fn wait(&self) -> BarrierWaitResult {
+              let generation = self.generation.load(Acquire);
+              // AcqRel: the leader acquires everyone's pre-barrier work, then releases it all through `generation`
+              if self.arrived.fetch_add(1, AcqRel) + 1 == self.n {
+                     self.arrived.store(0, Relaxed); // published by the release below, before anyone can re-arrive
+                     let _release = ReleaseRound(self); // also if the callback unwinds: nobody is left waiting
+                     if let Some(callback) = &self.callback {
+                            callback(generation);
+                     }
+                     return BarrierWaitResult(true);
+              }
+              while self.generation.load(Acquire) == generation {
+                     wait(&self.generation, generation);
+              }
+              BarrierWaitResult(false)
+       }
+}
+
+/// Ends the round on drop: bump the generation (publishing the round, callback included) and wake everyone.
+struct ReleaseRound<'a>(&'a Barrier);
+impl Drop for ReleaseRound<'_> {
+       fn drop(&mut self) {
+              self.0.generation.fetch_add(1, Release);
+              wake_all(&self.0.generation);
+       }
+}
+
+impl fmt::Debug for Barrier {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("Barrier")
+                     .field("n", &self.n)
+                     .field("arrived", &self.arrived)
+                     .field("generation", &self.generation)
+                     .field("callback", &self.callback.is_some())
+                     .finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_rounds_stay_in_lockstep() {
+              const THREADS: usize = 4;
+              const ROUNDS: usize = 50;
+              let barrier = Barrier::new(THREADS as u32);
+              let progress = AtomicUsize::new(0);
+              let leaders = AtomicUsize::new(0);
+              thread::scope(|s| {
+                     for _ in 0..THREADS {
+                            s.spawn(|| {
+                                   for round in 0..ROUNDS {
+                                          progress.fetch_add(1, Relaxed);
+                                          if barrier.wait().is_leader() {
+                                                 leaders.fetch_add(1, Relaxed);
+                                          }
+                                          // nobody can have started the next round yet
+                                          assert_eq!(progress.load(Relaxed) / THREADS, round + 1);
+                                          barrier.wait();
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(leaders.into_inner(), ROUNDS);
+       }
+
+       #[test]
+       fn test_callback_runs_once_per_round_before_release() {
+              const THREADS: u32 = 4;
+              let rounds = std::sync::Arc::new(AtomicUsize::new(0));
+              let barrier = Barrier::with_callback(THREADS, {
+                     let rounds = rounds.clone();
+                     move |round| assert_eq!(rounds.fetch_add(1, Relaxed), round as usize)
+              });
+              thread::scope(|s| {
+                     for _ in 0..THREADS {
+                            s.spawn(|| {
+                                   for round in 0..20 {
+                                          barrier.wait();
+                                          assert!(rounds.load(Relaxed) > round, "this round's callback already ran");
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(rounds.load(Relaxed), 20);
+       }
+
+       #[test]
+       fn test_single_thread_barrier() {
+              let barrier = Barrier::new(1);
+              assert!(barrier.wait().is_leader());
+              assert!(barrier.wait().is_leader());
+       }
+}
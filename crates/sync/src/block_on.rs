@@ -0,0 +1,41 @@
+//! The smallest executor: run one future to completion on the current thread.
+//!
+//! The waker is the thread itself: waking unparks it, and between polls it parks. That's the blocking primitives'
+//! register-check-park loop with the future's `poll` as the check, which is why the async types in this crate
+//! (behind the `async` feature) need no runtime, and why they're usable from plain threads.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! let (sender, receiver) = sync::channel::async_oneshot();
+//! thread::spawn(move || sender.send("hello"));
+//! assert_eq!(sync::block_on(receiver), Ok("hello"));
+//! ```
+
+use std::{pin::pin,
+          sync::Arc,
+          task::{Context, Poll, Wake, Waker}};
+
+use crate::atomic::{Thread, current, park};
+
+/// Wakes by unparking the thread blocked in [`block_on`].
+struct ThreadWaker(Thread);
+impl Wake for ThreadWaker {
+       fn wake(self: Arc<Self>) { self.0.unpark(); }
+
+       fn wake_by_ref(self: &Arc<Self>) { self.0.unpark(); }
+}
+
+/// Poll `future` until it's ready, parking the current thread while it's pending.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+       let mut future = pin!(future);
+       let waker = Waker::from(Arc::new(ThreadWaker(current())));
+       let mut context = Context::from_waker(&waker);
+       loop {
+              match future.as_mut().poll(&mut context) {
+                     Poll::Ready(output) => return output,
+                     Poll::Pending => park(), // a spurious wakeup just polls again
+              }
+       }
+}
@@ -0,0 +1,313 @@
+//! Bounded blocking queue that measures its own backpressure.
+//!
+//! A [`BlockingChannel::bounded`](crate::channel::BlockingChannel::bounded) also makes producers wait while it's
+//! full, but says nothing about it. Here the waiting is the point: [`stats`](BoundedQueue::stats) reads gauges kept
+//! in relaxed atomics (no lock taken, so a monitoring thread can poll them freely):
+//! - depth: messages queued right now
+//! - high-water mark: the deepest the queue has been
+//! - blocked pushes, and the total time producers spent blocked on a full queue
+//!
+//! A run whose producers outpace its consumers shows up as a high-water mark at capacity and a growing blocked time,
+//! rather than as memory growing without bound.
+//!
+//! Built from this crate's [`Mutex`] and [`Condvar`]; the gauges are updated under the lock, so they never disagree
+//! with the queue by more than an in-flight push or pop.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::BoundedQueue;
+//!
+//! let queue = BoundedQueue::new(2);
+//! thread::scope(|s| {
+//!        s.spawn(|| {
+//!               for i in 0..10 {
+//!                      queue.push(i).unwrap(); // blocks while two are waiting
+//!               }
+//!               queue.close();
+//!        });
+//!        let received: Vec<_> = std::iter::from_fn(|| queue.pop().ok()).collect();
+//!        assert_eq!(received, (0..10).collect::<Vec<_>>());
+//! });
+//! let stats = queue.stats();
+//! assert_eq!((stats.depth, stats.pushes), (0, 10));
+//! assert!(stats.high_water <= 2);
+//! ```
+
+use std::{collections::VecDeque,
+          time::{Duration, Instant}};
+
+use crate::{Condvar, Mutex, MutexGuard,
+            atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed, loom_const_fn},
+            channel::{RecvError, RecvTimeoutError, SendError, SendTimeoutError},
+            deadline};
+
+/// FIFO queue of at most `capacity` messages; `push` blocks while full, `pop` while empty.
+pub struct BoundedQueue<T> {
+       state:     Mutex<State<T>>,
+       not_empty: Condvar,
+       not_full:  Condvar,
+       capacity:  usize,
+       gauges:    Gauges,
+}
+
+struct State<T> {
+       queue:  VecDeque<T>,
+       closed: bool,
+}
+
+struct Gauges {
+       depth:          AtomicUsize,
+       high_water:     AtomicUsize,
+       pushes:         AtomicU64,
+       blocked_pushes: AtomicU64,
+       blocked_nanos:  AtomicU64,
+}
+
+/// Snapshot of a [`BoundedQueue`]'s gauges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueueStats {
+       pub capacity:       usize,
+       /// Messages queued when the snapshot was taken.
+       pub depth:          usize,
+       /// Most messages ever queued at once.
+       pub high_water:     usize,
+       /// Messages accepted so far.
+       pub pushes:         u64,
+       /// Pushes that found the queue full and had to wait (including any that then timed out or saw it close).
+       pub blocked_pushes: u64,
+       /// Total time producers spent waiting on a full queue.
+       pub blocked:        Duration,
+}
+impl QueueStats {
+       /// Fraction of pushes that had to wait, in `0.0..=1.0`.
+       pub fn blocked_ratio(&self) -> f64 { if self.pushes == 0 { 0.0 } else { self.blocked_pushes as f64 / self.pushes as f64 } }
+}
+
+impl<T> BoundedQueue<T> {
+       loom_const_fn! {
+              /// Queue holding at most `capacity` messages.
+              ///
+              /// ## Panics
+              /// If `capacity` is zero.
+              pub fn new(capacity: usize) -> Self {
+                     assert!(capacity > 0, "bounded queue capacity must be non-zero");
+                     Self {
+                            state: Mutex::new(State { queue: VecDeque::new(), closed: false }),
+                            not_empty: Condvar::new(),
+                            not_full: Condvar::new(),
+                            capacity,
+                            gauges: Gauges {
+                                   depth:          AtomicUsize::new(0),
+                                   high_water:     AtomicUsize::new(0),
+                                   pushes:         AtomicU64::new(0),
+                                   blocked_pushes: AtomicU64::new(0),
+                                   blocked_nanos:  AtomicU64::new(0),
+                            },
+                     }
+              }
+       }
+
+       /// Enqueue a message, blocking while the queue is full.
+       ///
+       /// ## Errors
+       /// If the queue is closed; the message is handed back.
+       pub fn push(&self, message: T) -> Result<(), SendError<T>> {
+              self.push_deadline_inner(message, None).map_err(|error| match error {
+                     SendTimeoutError::Timeout(message) | SendTimeoutError::Disconnected(message) => SendError(message),
+              })
+       }
+
+       /// As [`push`](Self::push), giving up after `timeout`.
+       pub fn push_timeout(&self, message: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+              self.push_deadline_inner(message, deadline::after(timeout))
+       }
+
+       fn push_deadline_inner(&self, message: T, deadline: Option<Instant>) -> Result<(), SendTimeoutError<T>> {
+              let mut state = self.state.lock();
+              if !state.closed && state.queue.len() >= self.capacity {
+                     let blocked_at = Instant::now();
+                     self.gauges.blocked_pushes.fetch_add(1, Relaxed);
+                     while !state.closed && state.queue.len() >= self.capacity {
+                            let timed_out;
+                            (state, timed_out) = wait_until(&self.not_full, state, deadline);
+                            if timed_out && state.queue.len() >= self.capacity {
+                                   self.record_blocked(blocked_at);
+                                   return Err(SendTimeoutError::Timeout(message));
+                            }
+                     }
+                     self.record_blocked(blocked_at);
+              }
+              if state.closed {
+                     return Err(SendTimeoutError::Disconnected(message));
+              }
+              state.queue.push_back(message);
+              let depth = state.queue.len();
+              self.gauges.depth.store(depth, Relaxed);
+              self.gauges.high_water.fetch_max(depth, Relaxed);
+              self.gauges.pushes.fetch_add(1, Relaxed);
+              drop(state);
+              self.not_empty.notify_one();
+              Ok(())
+       }
+
+       /// Dequeue a message, blocking while the queue is empty.
+       ///
+       /// ## Errors
+       /// If the queue is closed *and* drained.
+       pub fn pop(&self) -> Result<T, RecvError> {
+              self.pop_deadline_inner(None).map_err(|_| RecvError) // no deadline: only ever `Disconnected`
+       }
+
+       /// As [`pop`](Self::pop), giving up after `timeout`.
+       pub fn pop_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> { self.pop_deadline_inner(deadline::after(timeout)) }
+
+       fn pop_deadline_inner(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+              let mut state = self.state.lock();
+              loop {
+                     if let Some(message) = state.queue.pop_front() {
+                            self.gauges.depth.store(state.queue.len(), Relaxed);
+                            drop(state);
+                            self.not_full.notify_one();
+                            return Ok(message);
+                     }
+                     if state.closed {
+                            return Err(RecvTimeoutError::Disconnected);
+                     }
+                     let timed_out;
+                     (state, timed_out) = wait_until(&self.not_empty, state, deadline);
+                     if timed_out && state.queue.is_empty() && !state.closed {
+                            return Err(RecvTimeoutError::Timeout);
+                     }
+              }
+       }
+
+       /// Refuse further pushes and wake everyone blocked on the queue.
+       ///
+       /// Messages already queued can still be popped.
+       pub fn close(&self) {
+              self.state.lock().closed = true;
+              self.not_empty.notify_all();
+              self.not_full.notify_all();
+       }
+
+       pub fn is_closed(&self) -> bool { self.state.lock().closed }
+
+       /// Number of queued messages, from the depth gauge (no lock taken).
+       pub fn len(&self) -> usize { self.gauges.depth.load(Relaxed) }
+
+       pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+       pub fn capacity(&self) -> usize { self.capacity }
+
+       /// Gauges so far.
+       pub fn stats(&self) -> QueueStats {
+              let gauges = &self.gauges;
+              QueueStats {
+                     capacity:       self.capacity,
+                     depth:          gauges.depth.load(Relaxed),
+                     high_water:     gauges.high_water.load(Relaxed),
+                     pushes:         gauges.pushes.load(Relaxed),
+                     blocked_pushes: gauges.blocked_pushes.load(Relaxed),
+                     blocked:        Duration::from_nanos(gauges.blocked_nanos.load(Relaxed)),
+              }
+       }
+
+       fn record_blocked(&self, since: Instant) {
+              let nanos = u64::try_from(since.elapsed().as_nanos()).unwrap_or(u64::MAX);
+              self.gauges.blocked_nanos.fetch_add(nanos, Relaxed);
+       }
+}
+
+/// `Condvar::wait` until `deadline` (if any); the flag is whether the deadline passed.
+fn wait_until<'a, T>(condvar: &Condvar, guard: MutexGuard<'a, T>, deadline: Option<Instant>) -> (MutexGuard<'a, T>, bool) {
+       match deadline {
+              None => (condvar.wait(guard), false),
+              Some(deadline) => {
+                     let (guard, result) = condvar.wait_until(guard, deadline);
+                     (guard, result.timed_out())
+              }
+       }
+}
+
+impl<T> std::fmt::Debug for BoundedQueue<T> {
+       fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+              f.debug_struct("BoundedQueue").field("stats", &self.stats()).finish_non_exhaustive()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_fifo_across_threads() {
+              let queue = BoundedQueue::new(4);
+              let received = thread::scope(|s| {
+                     s.spawn(|| {
+                            for i in 0..1000 {
+                                   queue.push(i).unwrap();
+                            }
+                            queue.close();
+                     });
+                     std::iter::from_fn(|| queue.pop().ok()).collect::<Vec<_>>()
+              });
+              assert_eq!(received, (0..1000).collect::<Vec<_>>());
+              let stats = queue.stats();
+              assert_eq!((stats.depth, stats.pushes), (0, 1000));
+              assert!(stats.high_water <= 4);
+       }
+
+       #[test]
+       fn test_full_queue_blocks_and_counts_it() {
+              let queue = BoundedQueue::new(2);
+              queue.push(0).unwrap();
+              queue.push(1).unwrap();
+              assert_eq!(queue.stats().blocked_pushes, 0);
+              thread::scope(|s| {
+                     let producer = s.spawn(|| queue.push(2));
+                     thread::sleep(Duration::from_millis(20));
+                     assert_eq!(queue.len(), 2, "the third push waits for room");
+                     assert_eq!(queue.pop(), Ok(0));
+                     producer.join().unwrap().unwrap();
+              });
+              let stats = queue.stats();
+              assert_eq!((stats.depth, stats.high_water, stats.pushes, stats.blocked_pushes), (2, 2, 3, 1));
+              assert!(stats.blocked >= Duration::from_millis(10), "blocked for {:?}", stats.blocked);
+       }
+
+       #[test]
+       fn test_timeouts() {
+              let queue = BoundedQueue::new(1);
+              assert_eq!(queue.pop_timeout(Duration::from_millis(5)), Err(RecvTimeoutError::Timeout));
+              queue.push("first").unwrap();
+              assert_eq!(queue.push_timeout("second", Duration::from_millis(5)), Err(SendTimeoutError::Timeout("second")));
+              let stats = queue.stats();
+              assert_eq!((stats.pushes, stats.blocked_pushes), (1, 1));
+              assert!(stats.blocked >= Duration::from_millis(5));
+       }
+
+       #[test]
+       fn test_close_wakes_blocked_producer_and_drains() {
+              let queue = BoundedQueue::new(1);
+              queue.push(1).unwrap();
+              thread::scope(|s| {
+                     let producer = s.spawn(|| queue.push(2));
+                     thread::sleep(Duration::from_millis(10));
+                     queue.close();
+                     assert_eq!(producer.join().unwrap(), Err(SendError(2)));
+              });
+              assert_eq!(queue.pop(), Ok(1));
+              assert_eq!(queue.pop(), Err(RecvError));
+              assert!(queue.is_closed() && queue.is_empty());
+       }
+
+       #[test]
+       #[should_panic(expected = "capacity must be non-zero")]
+       fn test_zero_capacity_panics() { let _ = BoundedQueue::<()>::new(0); }
+}
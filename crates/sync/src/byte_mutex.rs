@@ -0,0 +1,199 @@
+//! One-byte mutex: the lock state is a byte, the waiters live in the [`parking_lot`](crate::parking_lot).
+//!
+//! ```text
+//! LOCKED (0b01): someone holds the lock
+//! PARKED (0b10): threads may be queued in the parking lot on this lock's address
+//! ```
+//! - lock: CAS `0 → LOCKED`; otherwise spin a little while nobody's parked, then set `PARKED` and park,
+//!   validating (under the bucket lock) that the state is still `LOCKED | PARKED`
+//! - unlock: CAS `LOCKED → 0`; if `PARKED` was set, unpark one waiter and, in the callback (still under the
+//!   bucket lock), store `PARKED` or `0` depending on whether more are queued
+//!
+//! The woken thread competes for the lock like anyone else (no handoff), like the futex [`Mutex`](crate::Mutex).
+//!
+//! ## Example
+//! ```
+//! use std::{mem, thread};
+//!
+//! use sync::ByteMutex;
+//!
+//! assert_eq!(mem::size_of::<ByteMutex<()>>(), 1);
+//! let counter = ByteMutex::new(0);
+//! thread::scope(|s| {
+//!        for _ in 0..4 {
+//!               s.spawn(|| *counter.lock() += 1);
+//!        }
+//! });
+//! assert_eq!(counter.into_inner(), 4);
+//! ```
+
+use std::{cell::UnsafeCell,
+          ops::{Deref, DerefMut}};
+
+use crate::{Backoff,
+            atomic::{AtomicU8,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn},
+            parking_lot};
+
+const LOCKED: u8 = 0b01;
+const PARKED: u8 = 0b10;
+/// Spins (with [`Backoff`]) before parking.
+const SPINS: u32 = 10;
+
+/// Mutual exclusion with one byte of state, queueing in the global parking lot.
+pub struct ByteMutex<T> {
+       state: AtomicU8,
+       value: UnsafeCell<T>,
+}
+// SAFETY: the state only lets one thread at a time reach `value`.
+unsafe impl<T> Sync for ByteMutex<T> where T: Send {}
+
+impl<T> ByteMutex<T> {
+       loom_const_fn! {
+              pub fn new(value: T) -> Self { Self { state: AtomicU8::new(0), value: UnsafeCell::new(value) } }
+       }
+
+       /// Block until the lock is ours.
+       pub fn lock(&self) -> ByteMutexGuard<'_, T> {
+              if self.state.compare_exchange_weak(0, LOCKED, Acquire, Relaxed).is_err() {
+                     self.lock_slow();
+              }
+              ByteMutexGuard { mutex: self }
+       }
+
+       pub fn try_lock(&self) -> Option<ByteMutexGuard<'_, T>> {
+              let mut state = self.state.load(Relaxed);
+              while state & LOCKED == 0 {
+                     match self.state.compare_exchange_weak(state, state | LOCKED, Acquire, Relaxed) {
+                            Ok(_) => return Some(ByteMutexGuard { mutex: self }),
+                            Err(observed) => state = observed,
+                     }
+              }
+              None
+       }
+
+       pub fn get_mut(&mut self) -> &mut T { self.value.get_mut() }
+
+       pub fn into_inner(self) -> T { self.value.into_inner() }
+
+       fn key(&self) -> usize { &self.state as *const AtomicU8 as usize }
+
+       #[cold]
+       fn lock_slow(&self) {
+              let mut backoff = Backoff::new();
+              let mut spins = 0;
+              let mut state = self.state.load(Relaxed);
+              loop {
+                     // free: take it, keeping any PARKED bit for the other waiters
+                     if state & LOCKED == 0 {
+                            match self.state.compare_exchange_weak(state, state | LOCKED, Acquire, Relaxed) {
+                                   Ok(_) => return,
+                                   Err(observed) => state = observed,
+                            }
+                            continue;
+                     }
+                     // nobody parked yet: spin a while, it may be released soon
+                     if state & PARKED == 0 && spins < SPINS {
+                            spins += 1;
+                            backoff.spin();
+                            state = self.state.load(Relaxed);
+                            continue;
+                     }
+                     if state & PARKED == 0
+                            && let Err(observed) = self.state.compare_exchange_weak(state, state | PARKED, Relaxed, Relaxed)
+                     {
+                            state = observed;
+                            continue;
+                     }
+                     parking_lot::park(self.key(), || self.state.load(Relaxed) == LOCKED | PARKED, None);
+                     spins = 0;
+                     state = self.state.load(Relaxed);
+              }
+       }
+
+       #[cold]
+       fn unlock_slow(&self) {
+              parking_lot::unpark_one(self.key(), |result| {
+                     self.state.store(if result.have_more { PARKED } else { 0 }, Release);
+              });
+       }
+}
+
+impl<T: Default> Default for ByteMutex<T> {
+       fn default() -> Self { Self::new(T::default()) }
+}
+
+/// Access to the locked value; unlocks on drop.
+pub struct ByteMutexGuard<'a, T> {
+       mutex: &'a ByteMutex<T>,
+}
+// SAFETY: the guard only hands out `&T` when shared, so `T: Sync` suffices.
+unsafe impl<T> Sync for ByteMutexGuard<'_, T> where T: Sync {}
+
+impl<T> Deref for ByteMutexGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: the existence of this guard guarantees we've exclusively locked the lock.
+              unsafe { &*self.mutex.value.get() }
+       }
+}
+impl<T> DerefMut for ByteMutexGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T {
+              // SAFETY: the existence of this guard guarantees we've exclusively locked the lock.
+              unsafe { &mut *self.mutex.value.get() }
+       }
+}
+impl<T> Drop for ByteMutexGuard<'_, T> {
+       fn drop(&mut self) {
+              if self.mutex.state.compare_exchange(LOCKED, 0, Release, Relaxed).is_err() {
+                     self.mutex.unlock_slow();
+              }
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{mem, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_one_byte() {
+              assert_eq!(mem::size_of::<ByteMutex<()>>(), 1);
+              assert_eq!(mem::size_of::<ByteMutex<u8>>(), 2);
+       }
+
+       #[test]
+       fn test_try_lock() {
+              let mutex = ByteMutex::new(1);
+              let guard = mutex.lock();
+              assert!(mutex.try_lock().is_none());
+              drop(guard);
+              *mutex.try_lock().unwrap() += 1;
+              assert_eq!(mutex.into_inner(), 2);
+       }
+
+       #[test]
+       fn test_contended_increments() {
+              let mutex = ByteMutex::new(0);
+              let iterations = if cfg!(miri) { 100 } else { 10_000 };
+              thread::scope(|s| {
+                     for _ in 0..8 {
+                            s.spawn(|| {
+                                   for _ in 0..iterations {
+                                          let mut guard = mutex.lock();
+                                          *guard += 1;
+                                          if *guard % 100 == 0 {
+                                                 thread::yield_now(); // hold it across a reschedule now and then, so others park
+                                          }
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(mutex.into_inner(), 8 * iterations);
+       }
+}
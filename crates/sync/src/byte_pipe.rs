@@ -0,0 +1,346 @@
+//! Single-producer single-consumer byte pipe over a ring buffer: stream bytes between two threads without a
+//! message (and an allocation) per chunk.
+//!
+//! [`byte_pipe`] returns the two ends:
+//! - [`PipeWriter::write`] copies in as much of a slice as fits and returns how much that was; wait-free
+//! - [`PipeReader::read`] copies out as much as is buffered, up to the slice's length; wait-free
+//! - [`io::Write`] / [`io::Read`] block instead, until there's room or data: the ends plug into `BufReader`,
+//!   `io::copy`, `write!` and friends. Dropping the writer is end-of-file; dropping the reader makes writes fail
+//!   with [`BrokenPipe`](io::ErrorKind::BrokenPipe)
+//!
+//! (The inherent `read` / `write` shadow the trait methods: call through the trait, or `io::Read::read(&mut reader,
+//! buf)`, for the blocking ones.)
+//!
+//! ## Design
+//! - two ever-growing positions, `tail` (written up to) and `head` (read up to), each stored only by its own end;
+//!   `tail - head` is the buffered length, and a power-of-two capacity makes the ring index a mask
+//! - Release on a position hands the bytes (or the freed space) over to the other end's Acquire load
+//! - a blocking end raises its `waiting` flag before its last check, the other end checks the flag after moving its
+//!   position, with SeqCst fences between: one of them sees the other, so a wake-up is never lost, and the
+//!   non-blocking paths never touch a lock while nobody's waiting
+//!
+//! ## Example
+//! ```
+//! use std::{io::{Read, Write},
+//!           thread};
+//!
+//! use sync::byte_pipe;
+//!
+//! let (mut writer, mut reader) = byte_pipe(16);
+//! thread::scope(|s| {
+//!        s.spawn(move || {
+//!               for line in 0..100 {
+//!                      writeln!(writer, "line {line}").unwrap(); // blocks whenever the 16 bytes are full
+//!               }
+//!        });
+//!        let mut text = String::new();
+//!        reader.read_to_string(&mut text).unwrap(); // until the writer is dropped
+//!        assert_eq!(text.lines().count(), 100);
+//! });
+//! ```
+
+use std::{cell::UnsafeCell, fmt, io, ptr, sync::Arc};
+
+use crate::{CachePadded,
+            atomic::{AtomicBool, AtomicUsize,
+                     Ordering::{Acquire, Relaxed, Release, SeqCst},
+                     fence, park},
+            park_slot::ParkSlot};
+
+/// A pipe buffering up to `capacity` bytes (rounded up to a power of two).
+///
+/// ## Panics
+/// If `capacity` is zero.
+pub fn byte_pipe(capacity: usize) -> (PipeWriter, PipeReader) {
+       assert!(capacity > 0, "a pipe needs room for at least one byte");
+       let capacity = capacity.next_power_of_two();
+       let ring = Arc::new(Ring {
+              buffer: (0..capacity).map(|_| UnsafeCell::new(0)).collect(),
+              mask:   capacity - 1,
+              tail:   CachePadded(AtomicUsize::new(0)),
+              head:   CachePadded(AtomicUsize::new(0)),
+              writer: End::new(),
+              reader: End::new(),
+       });
+       (PipeWriter { ring: ring.clone() }, PipeReader { ring })
+}
+
+struct Ring {
+       buffer: Box<[UnsafeCell<u8>]>,
+       mask:   usize,
+       /// Bytes written, ever; stored only by the writer.
+       tail:   CachePadded<AtomicUsize>,
+       /// Bytes read, ever; stored only by the reader.
+       head:   CachePadded<AtomicUsize>,
+       writer: End,
+       reader: End,
+}
+// SAFETY: the bytes in `head..tail` belong to the reader and the rest to the writer, handed over by Release/Acquire
+// on the positions; each end is a single owner (`&mut self`), so only one thread touches each side.
+unsafe impl Sync for Ring {}
+
+/// One end's blocking state, as seen by the other end.
+struct End {
+       /// Raised while this end is about to park or parked.
+       waiting: AtomicBool,
+       slot:    ParkSlot,
+       dropped: AtomicBool,
+}
+
+impl End {
+       fn new() -> Self { Self { waiting: AtomicBool::new(false), slot: ParkSlot::new(), dropped: AtomicBool::new(false) } }
+
+       /// Park until `ready()`, which must re-read the other end's position (or drop flag) itself.
+       /// It may load `Relaxed`: the fence orders those loads after raising `waiting`.
+       fn wait_until(&self, ready: impl Fn() -> bool) {
+              self.slot.register();
+              self.waiting.store(true, Relaxed);
+              fence(SeqCst); // pairs with `notify`'s: the other end sees `waiting`, or `ready` sees its update
+              while !ready() {
+                     park();
+              }
+              self.waiting.store(false, Relaxed);
+       }
+
+       /// The other end moved: wake this one if it's waiting.
+       fn notify(&self) {
+              fence(SeqCst);
+              if self.waiting.load(Relaxed) {
+                     self.slot.wake();
+              }
+       }
+
+       /// This end is gone: the other end stops waiting for it.
+       fn hang_up(&self, other: &Self) {
+              self.dropped.store(true, Release);
+              other.notify();
+       }
+}
+
+impl Ring {
+       fn capacity(&self) -> usize { self.mask + 1 }
+
+       /// The ring as two pieces starting at position `at`, `len` bytes in all: up to the end, then from the start.
+       fn pieces(&self, at: usize, len: usize) -> [(*mut u8, usize); 2] {
+              let start = at & self.mask;
+              let first = len.min(self.capacity() - start);
+              let base = UnsafeCell::raw_get(self.buffer.as_ptr());
+              // SAFETY: `start < capacity`: in bounds.
+              [(unsafe { base.add(start) }, first), (base, len - first)]
+       }
+}
+
+/// Writing end of a [`byte_pipe`].
+pub struct PipeWriter {
+       ring: Arc<Ring>,
+}
+
+impl PipeWriter {
+       /// Copy as much of `bytes` as fits into the pipe, without waiting; returns how many bytes that was
+       /// (0 when the pipe is full, or the reader is gone).
+       pub fn write(&mut self, bytes: &[u8]) -> usize {
+              let ring = &*self.ring;
+              if ring.reader.dropped.load(Relaxed) {
+                     return 0;
+              }
+              let tail = ring.tail.0.load(Relaxed); // ours
+              let head = ring.head.0.load(Acquire); // the reader is done with the bytes before `head`
+              let len = bytes.len().min(ring.capacity() - tail.wrapping_sub(head));
+              if len == 0 {
+                     return 0;
+              }
+              let mut from = bytes.as_ptr();
+              for (to, n) in ring.pieces(tail, len) {
+                     // SAFETY: `tail..tail + len` is free space, which only the writer touches; `bytes` has `len` more.
+                     unsafe {
+                            ptr::copy_nonoverlapping(from, to, n);
+                            from = from.add(n);
+                     }
+              }
+              ring.tail.0.store(tail.wrapping_add(len), Release);
+              ring.reader.notify();
+              len
+       }
+
+       /// Room for this many bytes right now.
+       pub fn free(&self) -> usize { self.ring.capacity() - self.ring.tail.0.load(Relaxed).wrapping_sub(self.ring.head.0.load(Acquire)) }
+
+       pub fn capacity(&self) -> usize { self.ring.capacity() }
+
+       /// Whether the reader has been dropped: nothing written will ever be read.
+       pub fn is_closed(&self) -> bool { self.ring.reader.dropped.load(Relaxed) }
+}
+
+impl io::Write for PipeWriter {
+       /// Block until some of `bytes` fits (or the reader is gone: [`BrokenPipe`](io::ErrorKind::BrokenPipe)).
+       fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+              if bytes.is_empty() {
+                     return Ok(0);
+              }
+              loop {
+                     let written = PipeWriter::write(self, bytes);
+                     if written > 0 {
+                            return Ok(written);
+                     }
+                     if self.is_closed() {
+                            return Err(io::ErrorKind::BrokenPipe.into());
+                     }
+                     let ring = &*self.ring;
+                     ring.writer.wait_until(|| {
+                            ring.tail.0.load(Relaxed).wrapping_sub(ring.head.0.load(Relaxed)) < ring.capacity()
+                                   || ring.reader.dropped.load(Relaxed)
+                     });
+              }
+       }
+
+       /// Nothing to do: written bytes are already the reader's to take.
+       fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+impl Drop for PipeWriter {
+       fn drop(&mut self) { self.ring.writer.hang_up(&self.ring.reader); }
+}
+
+impl fmt::Debug for PipeWriter {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("PipeWriter").field("free", &self.free()).field("capacity", &self.capacity()).finish()
+       }
+}
+
+/// Reading end of a [`byte_pipe`].
+pub struct PipeReader {
+       ring: Arc<Ring>,
+}
+
+impl PipeReader {
+       /// Copy as many buffered bytes as fit into `bytes`, without waiting; returns how many bytes that was
+       /// (0 when the pipe is empty).
+       pub fn read(&mut self, bytes: &mut [u8]) -> usize {
+              let ring = &*self.ring;
+              let head = ring.head.0.load(Relaxed); // ours
+              let tail = ring.tail.0.load(Acquire); // the writer's bytes before `tail` are complete
+              let len = bytes.len().min(tail.wrapping_sub(head));
+              if len == 0 {
+                     return 0;
+              }
+              let mut to = bytes.as_mut_ptr();
+              for (from, n) in ring.pieces(head, len) {
+                     // SAFETY: `head..head + len` is buffered data, which only the reader touches; `bytes` has room for `len`.
+                     unsafe {
+                            ptr::copy_nonoverlapping(from, to, n);
+                            to = to.add(n);
+                     }
+              }
+              ring.head.0.store(head.wrapping_add(len), Release);
+              ring.writer.notify();
+              len
+       }
+
+       /// Bytes buffered right now.
+       pub fn len(&self) -> usize { self.ring.tail.0.load(Acquire).wrapping_sub(self.ring.head.0.load(Relaxed)) }
+
+       pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+       /// Whether the writer has been dropped: once the buffered bytes are read, that's the end.
+       pub fn is_closed(&self) -> bool { self.ring.writer.dropped.load(Relaxed) }
+}
+
+impl io::Read for PipeReader {
+       /// Block until some bytes are buffered; `Ok(0)` once the writer is gone and everything it wrote has been read.
+       fn read(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+              if bytes.is_empty() {
+                     return Ok(0);
+              }
+              loop {
+                     let read = PipeReader::read(self, bytes);
+                     if read > 0 {
+                            return Ok(read);
+                     }
+                     // Acquire: the writer's last bytes came before its drop flag, and may have landed since our read
+                     if self.ring.writer.dropped.load(Acquire) {
+                            return Ok(PipeReader::read(self, bytes));
+                     }
+                     let ring = &*self.ring;
+                     ring.reader.wait_until(|| ring.tail.0.load(Relaxed) != ring.head.0.load(Relaxed) || ring.writer.dropped.load(Relaxed));
+              }
+       }
+}
+
+impl Drop for PipeReader {
+       fn drop(&mut self) { self.ring.reader.hang_up(&self.ring.writer); }
+}
+
+impl fmt::Debug for PipeReader {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("PipeReader").field("len", &self.len()).field("capacity", &self.ring.capacity()).finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{io::{Read, Write},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_non_blocking_ends_wrap_around() {
+              let (mut writer, mut reader) = byte_pipe(6);
+              assert_eq!(writer.capacity(), 8);
+              assert_eq!(writer.write(b"abcdef"), 6);
+              let mut out = [0; 4];
+              assert_eq!(reader.read(&mut out), 4);
+              assert_eq!(&out, b"abcd");
+              assert_eq!(writer.write(b"ghijklmn"), 6, "only 6 bytes free");
+              assert_eq!(writer.write(b"x"), 0);
+              let mut out = [0; 16];
+              assert_eq!(reader.read(&mut out), 8);
+              assert_eq!(&out[..8], b"efghijkl", "in order across the wrap");
+              assert_eq!(reader.read(&mut out), 0);
+       }
+
+       #[test]
+       fn test_stream_arrives_intact() {
+              const LEN: usize = if cfg!(miri) { 1_000 } else { 1 << 20 };
+              let data: Vec<u8> = (0..LEN).map(|i| (i * 7 % 251) as u8).collect();
+              let (mut writer, mut reader) = byte_pipe(64);
+              let received = thread::scope(|s| {
+                     s.spawn(|| {
+                            // odd-sized chunks, so writes and reads straddle the wrap at different offsets
+                            for chunk in data.chunks(97) {
+                                   writer.write_all(chunk).unwrap();
+                            }
+                            drop(writer);
+                     });
+                     let mut received = Vec::new();
+                     reader.read_to_end(&mut received).unwrap();
+                     received
+              });
+              assert!(received == data, "stream corrupted");
+       }
+
+       #[test]
+       fn test_dropped_reader_breaks_the_pipe() {
+              let (mut writer, reader) = byte_pipe(4);
+              writer.write_all(b"full").unwrap();
+              thread::scope(|s| {
+                     s.spawn(move || drop(reader));
+                     let error = writer.write_all(b"more").unwrap_err(); // blocks until the reader's gone
+                     assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+              });
+              assert!(writer.is_closed());
+       }
+
+       #[test]
+       fn test_dropped_writer_is_end_of_file_after_the_data() {
+              let (mut writer, mut reader) = byte_pipe(8);
+              writer.write_all(b"last").unwrap();
+              drop(writer);
+              let mut out = String::new();
+              assert_eq!(reader.read_to_string(&mut out).unwrap(), 4);
+              assert_eq!(out, "last");
+              assert!(reader.is_closed());
+       }
+}
@@ -0,0 +1,186 @@
+//! Cancellation token: the "STOP flag" pattern, shareable, hierarchical and waitable.
+//!
+//! Clones of a [`CancellationToken`] share one flag: any of them can [`cancel`](CancellationToken::cancel),
+//! all of them observe it.
+//! A [`child`](CancellationToken::child) token is cancelled along with its parent (and the parent's ancestors),
+//! but cancelling the child leaves the parent alone: scope a sub-task's shutdown without stopping everything.
+//!
+//! Workers either poll [`is_cancelled`](CancellationToken::is_cancelled) between units of work,
+//! or block in [`wait_cancelled`](CancellationToken::wait_cancelled) (a futex wait, no spinning).
+//!
+//! ## Design
+//! - each token node holds a futex word (0 live, 1 cancelled) and weak links to its children
+//! - children hold their parent strongly, so dropping a middle token doesn't cut its descendants off
+//! - `cancel` sets the word, wakes its waiters, then cancels the children it drains from its list
+//! - `child` registers under the same lock `cancel` drains with, so a child is either drained or born cancelled
+//!
+//! ## Example
+//! ```
+//! use std::{thread, time::Duration};
+//!
+//! use sync::CancellationToken;
+//!
+//! let shutdown = CancellationToken::new();
+//! let worker = {
+//!        let token = shutdown.child();
+//!        thread::spawn(move || {
+//!               let mut ticks = 0;
+//!               while !token.wait_cancelled_timeout(Duration::from_millis(1)) {
+//!                      ticks += 1;
+//!               }
+//!               ticks
+//!        })
+//! };
+//! shutdown.cancel();
+//! worker.join().unwrap();
+//! assert!(shutdown.is_cancelled());
+//! ```
+
+use std::{fmt,
+          sync::{Arc, Weak},
+          time::Duration};
+
+use crate::{Mutex,
+            atomic::{AtomicU32,
+                     Ordering::{Acquire, Release}},
+            deadline,
+            futex::{wait, wait_until, wake_all}};
+
+const LIVE: u32 = 0;
+const CANCELLED: u32 = 1;
+
+/// Shared cancellation flag; clone it to hand out, [`child`](Self::child) it to scope.
+#[derive(Clone)]
+pub struct CancellationToken {
+       node: Arc<Node>,
+}
+
+struct Node {
+       state:    AtomicU32,
+       children: Mutex<Vec<Weak<Node>>>,
+       /// Keeps the path down to us reachable from the root.
+       _parent:  Option<Arc<Node>>,
+}
+
+impl CancellationToken {
+       pub fn new() -> Self { Self { node: Node::new(None) } }
+
+       /// A new token cancelled whenever this one is (immediately, if it already is).
+       pub fn child(&self) -> Self {
+              let child = Self { node: Node::new(Some(self.node.clone())) };
+              let mut children = self.node.children.lock();
+              if self.is_cancelled() {
+                     child.node.state.store(CANCELLED, Release);
+              } else {
+                     children.retain(|child| child.strong_count() > 0); // forget dropped children
+                     children.push(Arc::downgrade(&child.node));
+              }
+              child
+       }
+
+       /// Cancel this token, its clones and all its descendants. Idempotent.
+       pub fn cancel(&self) { self.node.cancel(); }
+
+       /// Whether [`cancel`](Self::cancel) was called on this token or an ancestor.
+       ///
+       /// Acquire: whatever the canceller did before cancelling is visible once this returns `true`.
+       pub fn is_cancelled(&self) -> bool { self.node.state.load(Acquire) == CANCELLED }
+
+       /// Block until cancelled.
+       pub fn wait_cancelled(&self) {
+              while !self.is_cancelled() {
+                     wait(&self.node.state, LIVE);
+              }
+       }
+
+       /// Block until cancelled or `timeout` elapses; returns [`is_cancelled`](Self::is_cancelled).
+       ///
+       /// Doubles as an interruptible sleep for polling workers.
+       pub fn wait_cancelled_timeout(&self, timeout: Duration) -> bool {
+              let deadline = deadline::after(timeout);
+              while !self.is_cancelled() {
+                     if !wait_until(&self.node.state, LIVE, deadline) {
+                            return false;
+                     }
+              }
+              true
+       }
+}
+
+impl Node {
+       fn new(parent: Option<Arc<Node>>) -> Arc<Self> {
+              Arc::new(Self { state: AtomicU32::new(LIVE), children: Mutex::new(Vec::new()), _parent: parent })
+       }
+
+       fn cancel(&self) {
+              if self.state.swap(CANCELLED, Release) == CANCELLED {
+                     return;
+              }
+              wake_all(&self.state);
+              let children = std::mem::take(&mut *self.children.lock());
+              for child in children.iter().filter_map(Weak::upgrade) {
+                     child.cancel();
+              }
+       }
+}
+
+impl Default for CancellationToken {
+       fn default() -> Self { Self::new() }
+}
+
+impl fmt::Debug for CancellationToken {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("CancellationToken").field("cancelled", &self.is_cancelled()).finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_clones_share_cancellation() {
+              let token = CancellationToken::new();
+              let clone = token.clone();
+              assert!(!clone.is_cancelled());
+              token.cancel();
+              token.cancel();
+              assert!(clone.is_cancelled());
+              assert_eq!(format!("{clone:?}"), "CancellationToken { cancelled: true }");
+       }
+
+       #[test]
+       fn test_children_follow_parents_only() {
+              let root = CancellationToken::new();
+              let child = root.child();
+              let grandchild = child.child();
+              let sibling = root.child();
+
+              child.cancel();
+              assert!(grandchild.is_cancelled());
+              assert!(!root.is_cancelled() && !sibling.is_cancelled());
+
+              root.cancel();
+              assert!(sibling.is_cancelled());
+              assert!(root.child().is_cancelled(), "children of a cancelled token start cancelled");
+       }
+
+       #[test]
+       fn test_wait_cancelled_wakes_blocked_threads() {
+              let root = CancellationToken::new();
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            let token = root.child().child();
+                            s.spawn(move || token.wait_cancelled());
+                     }
+                     thread::sleep(Duration::from_millis(10));
+                     root.cancel();
+              });
+              assert!(!CancellationToken::new().wait_cancelled_timeout(Duration::from_millis(5)));
+              assert!(root.wait_cancelled_timeout(Duration::ZERO));
+       }
+}
@@ -0,0 +1,77 @@
+//! Channels built from atomics and thread parking.
+//!
+//! ## [Chapter 5: Building Our Own Channels](https://marabos.nl/atomics/building-channels.html)
+//!
+//! - [`oneshot`]: a single message, runtime-checked (`&self` methods; misuse panics)
+//! - [`typed_oneshot`]: a single message, compile-time checked (by-value halves; misuse doesn't compile)
+//! - [`BlockingChannel`]: `Mutex<VecDeque<T>>` + `Condvar` queue; bounded or unbounded, closeable
+//! - [`mpsc`]: lock-free unbounded multi-producer single-consumer linked list
+//! - [`rendezvous`]: zero capacity; `send` returns only once the message has been taken
+//! - [`watch`]: latest-value broadcast; receivers block until the value changes
+//! - [`async_oneshot`] (feature `async`): a single message, received by `.await`ing a future
+//!
+//! Every blocking operation has `_timeout` and `_deadline` variants, for shutdown code that can't wait forever.
+//! [`Select`] waits on whichever of several (park-based) receivers is ready first.
+
+#[cfg(feature = "async")]
+pub mod async_oneshot;
+mod blocking;
+pub mod mpsc;
+pub mod oneshot;
+pub mod rendezvous;
+mod select;
+pub mod typed_oneshot;
+pub mod watch;
+
+#[cfg(feature = "async")]
+pub use async_oneshot::async_oneshot;
+pub use blocking::BlockingChannel;
+use derive_more::{Display, Error};
+pub use mpsc::mpsc;
+pub use oneshot::oneshot;
+pub use rendezvous::rendezvous;
+pub use select::{Select, Selectable};
+pub use typed_oneshot::typed_oneshot;
+
+/// The sending side of a channel went away; no message will ever arrive.
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[display("receiving on a channel whose senders have all been dropped")]
+pub struct RecvError;
+
+/// The channel is closed; the unsent message is handed back.
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[display("sending on a closed channel")]
+pub struct SendError<T>(#[error(not(source))] pub T);
+
+/// Why a non-blocking receive came back empty-handed.
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+       #[display("receiving on an empty channel")]
+       Empty,
+       #[display("receiving on an empty and disconnected channel")]
+       Disconnected,
+}
+
+/// Why a timed receive came back empty-handed.
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+       #[display("timed out waiting on a channel")]
+       Timeout,
+       #[display("receiving on an empty and disconnected channel")]
+       Disconnected,
+}
+impl From<RecvError> for RecvTimeoutError {
+       fn from(_: RecvError) -> Self { Self::Disconnected }
+}
+
+/// Why a timed send failed; the unsent message is handed back either way.
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+pub enum SendTimeoutError<T> {
+       #[display("timed out waiting to send on a channel")]
+       Timeout(#[error(not(source))] T),
+       #[display("sending on a closed channel")]
+       Disconnected(#[error(not(source))] T),
+}
+impl<T> From<SendError<T>> for SendTimeoutError<T> {
+       fn from(SendError(message): SendError<T>) -> Self { Self::Disconnected(message) }
+}
@@ -0,0 +1,237 @@
+//! One-shot channel whose receiver is a `Future`: the blocking [`oneshot`](super::oneshot), translated to async.
+//!
+//! The translation is mechanical:
+//! - the `ParkSlot` holding the receiving thread becomes a `WakerSlot` holding the receiving task's `Waker`
+//! - `recv`'s register-check-park loop becomes `poll`: register the waker, check the state, return `Pending`
+//!   instead of parking; the executor "unparks" by polling again once woken
+//! - the sender's publish-then-wake is unchanged
+//!
+//! No runtime needed: any executor will do, [`block_on`](crate::block_on) included.
+//!
+//! Both halves are by value, as in [`typed_oneshot`](super::typed_oneshot). Dropping the receiver closes the channel:
+//! a later `send` hands its message back.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! let (sender, receiver) = sync::channel::async_oneshot();
+//! let task = async { receiver.await.map(|n: i32| n * 2) };
+//! thread::spawn(move || sender.send(21));
+//! assert_eq!(sync::block_on(task), Ok(42));
+//! ```
+
+use std::{cell::UnsafeCell,
+          fmt,
+          mem::MaybeUninit,
+          pin::Pin,
+          sync::Arc,
+          task::{Context, Poll}};
+
+use super::{RecvError, SendError};
+use crate::{atomic::{AtomicU8,
+                     Ordering::{Acquire, Relaxed, Release}},
+            waker_slot::WakerSlot};
+
+const EMPTY: u8 = 0;
+const READY: u8 = 1;
+const TAKEN: u8 = 2;
+const DISCONNECTED: u8 = 3;
+const CLOSED: u8 = 4;
+
+/// Create a connected one-shot (`Sender`, `Receiver`) pair; the `Receiver` is a future of the message.
+pub fn async_oneshot<T>() -> (Sender<T>, Receiver<T>) {
+       let channel = Arc::new(Channel {
+              message: UnsafeCell::new(MaybeUninit::uninit()),
+              state:   AtomicU8::new(EMPTY),
+              waiter:  WakerSlot::new(),
+       });
+       (Sender { channel: channel.clone() }, Receiver { channel })
+}
+
+struct Channel<T> {
+       message: UnsafeCell<MaybeUninit<T>>,
+       state:   AtomicU8,
+       /// Receiving task, once it has been polled.
+       waiter:  WakerSlot,
+}
+// SAFETY: the by-value halves guarantee a single write, then (after a release/acquire pair) a single read of `message`.
+unsafe impl<T> Sync for Channel<T> where T: Send {}
+
+impl<T> Drop for Channel<T> {
+       fn drop(&mut self) {
+              // Relaxed (rather than `get_mut`, which loom's atomics lack): `&mut self` means nobody to synchronize with
+              if self.state.load(Relaxed) == READY {
+                     // SAFETY: READY means the message was fully written and not yet taken.
+                     unsafe { self.message.get_mut().assume_init_drop() }
+              }
+       }
+}
+
+/// Sending half of an [`async_oneshot`] channel. Sending never waits, so it's the same from sync or async code.
+pub struct Sender<T> {
+       channel: Arc<Channel<T>>,
+}
+impl<T> Sender<T> {
+       /// Send the message, consuming the sender, and wake the receiving task.
+       ///
+       /// ## Errors
+       /// If the `Receiver` was dropped; the message is handed back.
+       pub fn send(self, message: T) -> Result<(), SendError<T>> {
+              // SAFETY: `self` is the only sender and is consumed here, so this is the only write;
+              //         the receiver doesn't read before READY.
+              unsafe { (*self.channel.message.get()).write(message) };
+              // Release: publishes the message
+              if self.channel.state.compare_exchange(EMPTY, READY, Release, Relaxed).is_err() {
+                     // SAFETY: the receiver is gone (CLOSED), so the message written above is still ours alone.
+                     return Err(SendError(unsafe { (*self.channel.message.get()).assume_init_read() }));
+              }
+              self.channel.waiter.wake();
+              Ok(())
+       }
+
+       /// Whether the receiver is gone, so a `send` would fail.
+       pub fn is_closed(&self) -> bool { self.channel.state.load(Relaxed) == CLOSED }
+}
+impl<T> Drop for Sender<T> {
+       fn drop(&mut self) {
+              // only matters if nothing was ever sent
+              if self.channel.state.compare_exchange(EMPTY, DISCONNECTED, Relaxed, Relaxed).is_ok() {
+                     self.channel.waiter.wake();
+              }
+       }
+}
+
+/// Receiving half of an [`async_oneshot`] channel: `.await` it for the message.
+pub struct Receiver<T> {
+       channel: Arc<Channel<T>>,
+}
+impl<T> Receiver<T> {
+       /// Whether the message has arrived, so the next poll is `Ready`.
+       pub fn is_ready(&self) -> bool { self.channel.state.load(Relaxed) == READY }
+}
+
+impl<T> Future for Receiver<T> {
+       type Output = Result<T, RecvError>;
+
+       /// ## Panics
+       /// If polled again after returning the message.
+       fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+              // register before checking, as `recv` does before parking: a send in between still finds the waker
+              self.channel.waiter.register(cx.waker());
+              match self.channel.state.load(Acquire) {
+                     READY => {
+                            self.channel.state.store(TAKEN, Relaxed);
+                            // SAFETY: READY (acquired) means the message is fully written; we are the only receiver
+                            //         and just marked it TAKEN.
+                            Poll::Ready(Ok(unsafe { (*self.channel.message.get()).assume_init_read() }))
+                     }
+                     DISCONNECTED => Poll::Ready(Err(RecvError)),
+                     TAKEN => panic!("async oneshot polled after completion"),
+                     _ => Poll::Pending,
+              }
+       }
+}
+
+impl<T> Drop for Receiver<T> {
+       fn drop(&mut self) {
+              // tell a sender that hasn't sent yet not to bother; a message already sent is the channel's to drop
+              let _ = self.channel.state.compare_exchange(EMPTY, CLOSED, Relaxed, Relaxed);
+       }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_struct("Sender").field("closed", &self.is_closed()).finish() }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_struct("Receiver").field("ready", &self.is_ready()).finish() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize,
+                 task::{Wake, Waker},
+                 thread,
+                 time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::block_on;
+
+       /// Counts its wake-ups.
+       #[derive(Default)]
+       struct CountingWaker(AtomicUsize);
+       impl Wake for CountingWaker {
+              fn wake(self: Arc<Self>) { self.0.fetch_add(1, Relaxed); }
+       }
+
+       #[test]
+       fn test_cross_thread_handoff() {
+              let (sender, receiver) = async_oneshot();
+              let producer = thread::spawn(move || {
+                     thread::sleep(Duration::from_millis(10)); // let the receiver go pending first
+                     sender.send(vec![1, 2, 3])
+              });
+              assert_eq!(block_on(receiver), Ok(vec![1, 2, 3]));
+              assert_eq!(producer.join().unwrap(), Ok(()));
+       }
+
+       #[test]
+       fn test_pending_until_sent_and_woken_once() {
+              let (sender, mut receiver) = async_oneshot();
+              let wakes = Arc::new(CountingWaker::default());
+              let waker = Waker::from(wakes.clone());
+              let mut cx = Context::from_waker(&waker);
+              assert_eq!(Pin::new(&mut receiver).poll(&mut cx), Poll::Pending);
+              assert_eq!(Pin::new(&mut receiver).poll(&mut cx), Poll::Pending, "re-polling registers the same waker");
+              assert_eq!(wakes.0.load(Relaxed), 0);
+              sender.send('x').unwrap();
+              assert_eq!(wakes.0.load(Relaxed), 1);
+              assert!(receiver.is_ready());
+              assert_eq!(Pin::new(&mut receiver).poll(&mut cx), Poll::Ready(Ok('x')));
+       }
+
+       #[test]
+       fn test_sender_dropped_without_sending() {
+              let (sender, receiver) = async_oneshot::<()>();
+              thread::spawn(move || drop(sender));
+              assert_eq!(block_on(receiver), Err(RecvError));
+       }
+
+       #[test]
+       fn test_send_to_dropped_receiver_hands_message_back() {
+              let (sender, receiver) = async_oneshot();
+              drop(receiver);
+              assert!(sender.is_closed());
+              assert_eq!(sender.send(String::from("unread")), Err(SendError(String::from("unread"))));
+       }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+       use loom::{sync::{Arc,
+                         atomic::{AtomicUsize, Ordering::Relaxed}},
+                  thread};
+
+       use super::*;
+       use crate::block_on;
+
+       /// Whichever of `send` and the receiver's register-then-check comes first, the receiver ends up with the
+       /// message (and what was written before it), never pending forever.
+       #[test]
+       fn loom_poll_never_misses_the_send() {
+              loom::model(|| {
+                     let written = Arc::new(AtomicUsize::new(0));
+                     let (sender, receiver) = async_oneshot();
+                     let other = {
+                            let written = written.clone();
+                            thread::spawn(move || block_on(receiver).map(|()| written.load(Relaxed)))
+                     };
+                     written.store(42, Relaxed);
+                     sender.send(()).unwrap();
+                     assert_eq!(other.join().unwrap(), Ok(42));
+              });
+       }
+}
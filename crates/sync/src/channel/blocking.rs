@@ -0,0 +1,418 @@
+//! Blocking queue channel: `Mutex<VecDeque<T>>` + `Condvar`.
+//!
+//! ## [Chapter 5: A Simple Mutex-Based Channel](https://marabos.nl/atomics/building-channels.html#a-simple-mutex-based-channel)
+//!
+//! The `atomics-demos ch1 parking` example, made reusable:
+//! - unbounded or bounded (`send` blocks while full)
+//! - any number of producers (and consumers) sharing one `&BlockingChannel`
+//! - [`close`](BlockingChannel::close): later `send`s fail, `recv` drains what's left and then fails
+//!
+//! Not clever, but obviously correct; the baseline the lock-free channels get compared against.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::channel::BlockingChannel;
+//!
+//! let channel = BlockingChannel::unbounded();
+//! thread::scope(|s| {
+//!        s.spawn(|| {
+//!               for i in 0..3 {
+//!                      channel.send(i).unwrap();
+//!               }
+//!               channel.close();
+//!        });
+//!        let received: Vec<_> = std::iter::from_fn(|| channel.recv().ok()).collect();
+//!        assert_eq!(received, vec![0, 1, 2]);
+//! });
+//! ```
+
+use std::{collections::VecDeque,
+          sync::{Condvar, Mutex, MutexGuard, PoisonError},
+          time::{Duration, Instant}};
+
+use super::{RecvError, RecvTimeoutError, SendError, SendTimeoutError, TryRecvError};
+use crate::{atomic::loom_const_fn,
+            deadline,
+            histogram::{WaitSite, WaitTimer}};
+
+/// FIFO channel shared by reference; blocks on empty (and on full, if bounded).
+pub struct BlockingChannel<T> {
+       state:     Mutex<State<T>>,
+       not_empty: Condvar,
+       not_full:  Condvar,
+       capacity:  Option<usize>,
+}
+
+struct State<T> {
+       queue:  VecDeque<T>,
+       closed: bool,
+}
+
+impl<T> BlockingChannel<T> {
+       loom_const_fn! {
+              /// Channel without a capacity limit; `send` never blocks.
+              pub fn unbounded() -> Self { Self::with_capacity(None) }
+       }
+
+       loom_const_fn! {
+              /// Channel holding at most `capacity` messages; `send` blocks while full.
+              ///
+              /// ## Panics
+              /// If `capacity` is zero. (See the rendezvous channel for zero-capacity handoff.)
+              pub fn bounded(capacity: usize) -> Self {
+                     assert!(capacity > 0, "bounded channel capacity must be non-zero");
+                     Self::with_capacity(Some(capacity))
+              }
+       }
+
+       loom_const_fn! {
+              fn with_capacity(capacity: Option<usize>) -> Self {
+                     Self {
+                            state: Mutex::new(State { queue: VecDeque::new(), closed: false }),
+                            not_empty: Condvar::new(),
+                            not_full: Condvar::new(),
+                            capacity,
+                     }
+              }
+       }
+
+       /// Enqueue a message, blocking while a bounded channel is full.
+       ///
+       /// ## Errors
+       /// If the channel is closed; the message is handed back.
+       pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+              self.send_deadline_inner(message, None).map_err(|error| match error {
+                     SendTimeoutError::Timeout(message) | SendTimeoutError::Disconnected(message) => SendError(message),
+              })
+       }
+
+       /// As [`send`](Self::send), giving up after `timeout`.
+       pub fn send_timeout(&self, message: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+              self.send_deadline_inner(message, deadline::after(timeout))
+       }
+
+       /// As [`send`](Self::send), giving up at `deadline`.
+       pub fn send_deadline(&self, message: T, deadline: Instant) -> Result<(), SendTimeoutError<T>> {
+              self.send_deadline_inner(message, Some(deadline))
+       }
+
+       fn send_deadline_inner(&self, message: T, deadline: Option<Instant>) -> Result<(), SendTimeoutError<T>> {
+              let mut waited = WaitTimer::new(WaitSite::ChannelSend);
+              let mut state = self.lock();
+              while !state.closed && self.is_full(&state) {
+                     waited.start();
+                     state = match wait_until(&self.not_full, state, deadline) {
+                            Some(state) => state,
+                            None => return Err(SendTimeoutError::Timeout(message)),
+                     };
+              }
+              if state.closed {
+                     return Err(SendTimeoutError::Disconnected(message));
+              }
+              state.queue.push_back(message);
+              drop(state);
+              self.not_empty.notify_one();
+              Ok(())
+       }
+
+       /// Dequeue a message, blocking while the channel is empty.
+       ///
+       /// ## Errors
+       /// If the channel is closed *and* drained.
+       pub fn recv(&self) -> Result<T, RecvError> {
+              self.recv_deadline_inner(None).map_err(|_| RecvError) // no deadline: only ever `Disconnected`
+       }
+
+       /// As [`recv`](Self::recv), giving up after `timeout`.
+       pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> { self.recv_deadline_inner(deadline::after(timeout)) }
+
+       /// As [`recv`](Self::recv), giving up at `deadline`.
+       pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> { self.recv_deadline_inner(Some(deadline)) }
+
+       fn recv_deadline_inner(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+              let mut waited = WaitTimer::new(WaitSite::ChannelRecv);
+              let mut state = self.lock();
+              loop {
+                     if let Some(message) = state.queue.pop_front() {
+                            drop(state);
+                            self.not_full.notify_one();
+                            return Ok(message);
+                     }
+                     if state.closed {
+                            return Err(RecvTimeoutError::Disconnected);
+                     }
+                     waited.start();
+                     state = wait_until(&self.not_empty, state, deadline).ok_or(RecvTimeoutError::Timeout)?;
+              }
+       }
+
+       /// Dequeue a message if one is available, without blocking.
+       pub fn try_recv(&self) -> Result<T, TryRecvError> {
+              let mut state = self.lock();
+              match state.queue.pop_front() {
+                     Some(message) => {
+                            drop(state);
+                            self.not_full.notify_one();
+                            Ok(message)
+                     }
+                     None if state.closed => Err(TryRecvError::Disconnected),
+                     None => Err(TryRecvError::Empty),
+              }
+       }
+
+       /// Refuse further sends and wake everyone blocked on the channel.
+       ///
+       /// Messages already queued can still be received.
+       pub fn close(&self) {
+              self.lock().closed = true;
+              self.not_empty.notify_all();
+              self.not_full.notify_all();
+       }
+
+       pub fn is_closed(&self) -> bool { self.lock().closed }
+
+       /// Number of queued messages.
+       pub fn len(&self) -> usize { self.lock().queue.len() }
+
+       pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+       pub fn capacity(&self) -> Option<usize> { self.capacity }
+
+       /// Lock the state, ignoring poison: no code path panics part way through updating it.
+       fn lock(&self) -> MutexGuard<'_, State<T>> { self.state.lock().unwrap_or_else(PoisonError::into_inner) }
+
+       fn is_full(&self, state: &State<T>) -> bool { self.capacity.is_some_and(|capacity| state.queue.len() >= capacity) }
+}
+
+/// `Condvar::wait` until `deadline` (if any); `None` once the deadline has passed.
+fn wait_until<'a, T>(condvar: &Condvar, guard: MutexGuard<'a, T>, deadline: Option<Instant>) -> Option<MutexGuard<'a, T>> {
+       match deadline {
+              None => Some(condvar.wait(guard).unwrap_or_else(PoisonError::into_inner)),
+              Some(deadline) => {
+                     let remaining = deadline::remaining(deadline)?;
+                     Some(condvar.wait_timeout(guard, remaining).unwrap_or_else(PoisonError::into_inner).0)
+              }
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{thread, time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_multiple_producers() {
+              const NUM_PRODUCERS: usize = 4;
+              const PER_PRODUCER: usize = 250;
+              let channel = BlockingChannel::bounded(8);
+              let mut received = thread::scope(|s| {
+                     let producers: Vec<_> = (0..NUM_PRODUCERS)
+                            .map(|p| {
+                                   let channel = &channel;
+                                   s.spawn(move || {
+                                          for i in 0..PER_PRODUCER {
+                                                 channel.send(p * PER_PRODUCER + i).unwrap();
+                                          }
+                                   })
+                            })
+                            .collect();
+                     let consumer = s.spawn(|| std::iter::from_fn(|| channel.recv().ok()).collect::<Vec<_>>());
+                     for producer in producers {
+                            producer.join().unwrap();
+                     }
+                     channel.close();
+                     consumer.join().unwrap()
+              });
+              received.sort_unstable();
+              assert_eq!(received, (0..NUM_PRODUCERS * PER_PRODUCER).collect::<Vec<_>>());
+       }
+
+       #[test]
+       fn test_per_producer_order_preserved() {
+              let channel = BlockingChannel::unbounded();
+              thread::scope(|s| {
+                     for p in 0..3 {
+                            let channel = &channel;
+                            s.spawn(move || {
+                                   for i in 0..100 {
+                                          channel.send((p, i)).unwrap();
+                                   }
+                            });
+                     }
+              });
+              channel.close();
+              let mut last_seen = [None; 3];
+              while let Ok((p, i)) = channel.recv() {
+                     assert!(last_seen[p] < Some(i));
+                     last_seen[p] = Some(i);
+              }
+       }
+
+       #[test]
+       fn test_close_semantics() {
+              let channel = BlockingChannel::unbounded();
+              assert_eq!(channel.try_recv(), Err(TryRecvError::Empty));
+              channel.send(1).unwrap();
+              channel.close();
+              assert!(channel.is_closed());
+              assert_eq!(channel.send(2), Err(SendError(2)));
+              // queued messages survive the close
+              assert_eq!(channel.try_recv(), Ok(1));
+              assert_eq!(channel.try_recv(), Err(TryRecvError::Disconnected));
+              assert_eq!(channel.recv(), Err(RecvError));
+       }
+
+       #[test]
+       fn test_timeouts() {
+              let channel = BlockingChannel::bounded(1);
+              assert_eq!(channel.recv_timeout(Duration::from_millis(5)), Err(RecvTimeoutError::Timeout));
+              channel.send_timeout(1, Duration::from_millis(5)).unwrap();
+              assert_eq!(channel.send_timeout(2, Duration::from_millis(5)), Err(SendTimeoutError::Timeout(2)));
+              assert_eq!(channel.recv_deadline(Instant::now()), Ok(1));
+              channel.close();
+              assert_eq!(channel.recv_timeout(Duration::from_secs(60)), Err(RecvTimeoutError::Disconnected));
+              assert_eq!(channel.send_deadline(3, Instant::now()), Err(SendTimeoutError::Disconnected(3)));
+       }
+
+       #[test]
+       fn test_close_wakes_blocked_receiver() {
+              let channel = BlockingChannel::<()>::unbounded();
+              thread::scope(|s| {
+                     let consumer = s.spawn(|| channel.recv());
+                     thread::sleep(Duration::from_millis(10));
+                     channel.close();
+                     assert_eq!(consumer.join().unwrap(), Err(RecvError));
+              });
+       }
+
+       #[test]
+       fn test_bounded_send_blocks_until_space() {
+              let channel = BlockingChannel::bounded(1);
+              channel.send(1).unwrap();
+              thread::scope(|s| {
+                     let producer = s.spawn(|| channel.send(2));
+                     thread::sleep(Duration::from_millis(10));
+                     assert_eq!(channel.len(), 1);
+                     assert_eq!(channel.recv(), Ok(1));
+                     producer.join().unwrap().unwrap();
+              });
+              assert_eq!(channel.recv(), Ok(2));
+       }
+}
+
+// QuickCheck tests: random operation sequences against a `VecDeque` reference model
+#[cfg(test)]
+mod quickcheck_tests {
+       use std::{collections::VecDeque, thread, time::Duration};
+
+       use quickcheck::{Arbitrary, Gen};
+       use quickcheck_macros::quickcheck;
+
+       use super::*;
+
+       #[derive(Debug, Clone)]
+       enum Op {
+              /// Send without waiting: a full channel times out at once.
+              Send(u8),
+              TryRecv,
+              Close,
+       }
+
+       impl Arbitrary for Op {
+              fn arbitrary(g: &mut Gen) -> Self {
+                     match u8::arbitrary(g) % 10 {
+                            0..=4 => Self::Send(u8::arbitrary(g)),
+                            5..=8 => Self::TryRecv,
+                            _ => Self::Close,
+                     }
+              }
+       }
+
+       /// `capacity` 0 stands for unbounded.
+       fn channel_of<T>(capacity: u8) -> BlockingChannel<T> {
+              match capacity % 8 {
+                     0 => BlockingChannel::unbounded(),
+                     capacity => BlockingChannel::bounded(capacity.into()),
+              }
+       }
+
+       /// On one thread, the channel behaves exactly like a (possibly bounded) queue plus a closed flag.
+       #[quickcheck]
+       fn qc_test_matches_sequential_model(capacity: u8, ops: Vec<Op>) -> bool {
+              let channel = channel_of(capacity);
+              let mut model = VecDeque::new();
+              let mut closed = false;
+              ops.into_iter().all(|op| {
+                     let matches = match op {
+                            Op::Send(message) => {
+                                   let expected = if closed {
+                                          Err(SendTimeoutError::Disconnected(message))
+                                   } else if channel.capacity().is_some_and(|capacity| model.len() >= capacity) {
+                                          Err(SendTimeoutError::Timeout(message))
+                                   } else {
+                                          model.push_back(message);
+                                          Ok(())
+                                   };
+                                   channel.send_timeout(message, Duration::ZERO) == expected
+                            }
+                            Op::TryRecv => {
+                                   let expected = match model.pop_front() {
+                                          Some(message) => Ok(message),
+                                          None if closed => Err(TryRecvError::Disconnected),
+                                          None => Err(TryRecvError::Empty),
+                                   };
+                                   channel.try_recv() == expected
+                            }
+                            Op::Close => {
+                                   closed = true;
+                                   channel.close();
+                                   true
+                            }
+                     };
+                     matches && channel.len() == model.len() && channel.is_closed() == closed
+              })
+       }
+
+       /// Producers and consumers on their own threads (one producer per plan, at most 4; two consumers) interleave
+       /// arbitrarily, but every message arrives exactly once, and each consumer sees each producer's in the order sent.
+       #[quickcheck]
+       fn qc_test_concurrent_messages_arrive_once_in_order(capacity: u8, plans: Vec<Vec<u8>>) -> bool {
+              let plans = &plans[..plans.len().min(4)];
+              let channel = channel_of(capacity);
+              // message = (producer, position in its plan)
+              let per_consumer: Vec<Vec<(usize, usize)>> = thread::scope(|s| {
+                     let producers: Vec<_> = plans
+                            .iter()
+                            .enumerate()
+                            .map(|(producer, plan)| {
+                                   let channel = &channel;
+                                   s.spawn(move || (0..plan.len()).for_each(|position| channel.send((producer, position)).unwrap()))
+                            })
+                            .collect();
+                     let consumers: Vec<_> = (0..2).map(|_| s.spawn(|| std::iter::from_fn(|| channel.recv().ok()).collect())).collect();
+                     producers.into_iter().for_each(|producer| producer.join().unwrap());
+                     channel.close();
+                     consumers.into_iter().map(|consumer| consumer.join().unwrap()).collect()
+              });
+              let in_order = per_consumer.iter().all(|received| {
+                     (0..plans.len()).all(|producer| {
+                            let positions: Vec<_> =
+                                   received.iter().filter(|(from, _)| *from == producer).map(|&(_, position)| position).collect();
+                            positions.is_sorted_by(|a, b| a < b)
+                     })
+              });
+              let mut all: Vec<_> = per_consumer.concat();
+              all.sort_unstable();
+              let expected: Vec<_> = plans
+                     .iter()
+                     .enumerate()
+                     .flat_map(|(producer, plan)| (0..plan.len()).map(move |position| (producer, position)))
+                     .collect();
+              in_order && all == expected
+       }
+}
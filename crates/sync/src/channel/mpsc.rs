@@ -0,0 +1,487 @@
+//! Lock-free unbounded multi-producer single-consumer channel.
+//!
+//! A singly linked list in the style of Dmitry Vyukov's MPSC queue:
+//! ```text
+//!   tail (consumer-owned)                      head (producers swap)
+//!    │                                          │
+//!   [stub] ──next──> [msg] ──next──> [msg] ──> [msg] ──> null
+//! ```
+//! - `send`: allocate a node, `swap` it into `head`, then link the previous head's `next` to it.
+//!   One atomic swap per message; producers never wait on each other.
+//! - `recv`: follow `tail.next`; that node becomes the new stub and its message is moved out.
+//!
+//! Between a producer's `swap` and its `next` store the list is briefly disconnected.
+//! The consumer notices (`head != tail` but `tail.next` is null) and spins until the link appears.
+//!
+//! ## Blocking
+//! The consumer announces `sleeping` before its final emptiness check and producers check `sleeping` after pushing,
+//! both `SeqCst`; so either the consumer sees the message or the producer sees the sleeper.
+//! Producers only touch the park slot when somebody is actually asleep.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! let (sender, receiver) = sync::channel::mpsc();
+//! for i in 0..3 {
+//!        let sender = sender.clone();
+//!        thread::spawn(move || sender.send(i).unwrap());
+//! }
+//! drop(sender);
+//! let mut received: Vec<_> = receiver.iter().collect();
+//! received.sort();
+//! assert_eq!(received, vec![0, 1, 2]);
+//! ```
+
+use std::{cell::{Cell, UnsafeCell},
+          marker::PhantomData,
+          ptr,
+          sync::Arc,
+          time::{Duration, Instant}};
+
+use super::{RecvError, RecvTimeoutError, SendError, TryRecvError, select::sealed::SelectHandle};
+use crate::{Backoff,
+            atomic::{AtomicBool, AtomicPtr, AtomicUsize,
+                     Ordering::{AcqRel, Acquire, Relaxed, Release, SeqCst}},
+            deadline::{self, park_until},
+            histogram::{WaitSite, WaitTimer},
+            park_slot::ParkSlot};
+
+/// Create a connected (`Sender`, `Receiver`) pair. `Sender` can be cloned for more producers.
+pub fn mpsc<T>() -> (Sender<T>, Receiver<T>) {
+       let stub = Box::into_raw(Box::new(Node { next: AtomicPtr::new(ptr::null_mut()), message: None }));
+       let channel = Arc::new(Channel {
+              head:           AtomicPtr::new(stub),
+              tail:           UnsafeCell::new(stub),
+              senders:        AtomicUsize::new(1),
+              receiver_alive: AtomicBool::new(true),
+              sleeping:       AtomicBool::new(false),
+              waiter:         ParkSlot::new(),
+       });
+       (Sender { channel: channel.clone() }, Receiver { channel, _not_sync: PhantomData })
+}
+
+struct Node<T> {
+       next:    AtomicPtr<Node<T>>,
+       /// `None` only for the stub node.
+       message: Option<T>,
+}
+
+struct Channel<T> {
+       /// Most recently pushed node.
+       head:           AtomicPtr<Node<T>>,
+       /// Stub node; only ever touched by the (single) receiver.
+       tail:           UnsafeCell<*mut Node<T>>,
+       senders:        AtomicUsize,
+       receiver_alive: AtomicBool,
+       /// Receiver is (about to be) parked.
+       sleeping:       AtomicBool,
+       waiter:         ParkSlot,
+}
+// SAFETY: messages cross threads through the list (needs `T: Send`); `tail` is only accessed by the one `Receiver`.
+unsafe impl<T> Sync for Channel<T> where T: Send {}
+// SAFETY: as above; the raw pointers are owned by the channel.
+unsafe impl<T> Send for Channel<T> where T: Send {}
+
+enum Pop<T> {
+       Message(T),
+       Empty,
+       /// A producer is between its `swap` and its `next` store.
+       Inconsistent,
+}
+
+impl<T> Channel<T> {
+       fn push(&self, message: T) {
+              let node = Box::into_raw(Box::new(Node { next: AtomicPtr::new(ptr::null_mut()), message: Some(message) }));
+              let prev = self.head.swap(node, SeqCst);
+              // SAFETY: `prev` stays allocated until the receiver moves past it, which requires this very `next` store.
+              unsafe { (*prev).next.store(node, Release) };
+       }
+
+       /// ## Safety
+       /// Only the receiver may call this.
+       unsafe fn pop(&self) -> Pop<T> {
+              // SAFETY: caller is the receiver, the sole user of `tail`.
+              let tail = unsafe { *self.tail.get() };
+              // SAFETY: `tail` (the stub) is always a live node.
+              let next = unsafe { (*tail).next.load(Acquire) };
+              if !next.is_null() {
+                     // SAFETY: caller is the receiver, the sole user of `tail`.
+                     unsafe { *self.tail.get() = next };
+                     // SAFETY: `next` was fully initialized before being published (Release/Acquire);
+                     //         only the receiver reads it, and it becomes the new stub (message taken).
+                     let message = unsafe { (*next).message.take() }.expect("non-stub nodes hold a message");
+                     // SAFETY: the old stub is unreachable: producers are done with it once `next` is set.
+                     drop(unsafe { Box::from_raw(tail) });
+                     return Pop::Message(message);
+              }
+              if self.head.load(SeqCst) == tail { Pop::Empty } else { Pop::Inconsistent }
+       }
+
+       /// ## Safety
+       /// Only the receiver may call this.
+       unsafe fn pop_spinning(&self) -> Option<T> {
+              let mut backoff = Backoff::new();
+              loop {
+                     // SAFETY: forwarded caller guarantee.
+                     match unsafe { self.pop() } {
+                            Pop::Message(message) => return Some(message),
+                            Pop::Empty => return None,
+                            Pop::Inconsistent => backoff.snooze(),
+                     }
+              }
+       }
+}
+impl<T> Drop for Channel<T> {
+       fn drop(&mut self) {
+              let mut node = *self.tail.get_mut();
+              while !node.is_null() {
+                     // SAFETY: we are the last owner; every node from `tail` onward is live and owned by the list.
+                     let boxed = unsafe { Box::from_raw(node) };
+                     node = boxed.next.load(Relaxed);
+              }
+       }
+}
+
+/// Sending half of an [`mpsc`] channel. Clone it for more producers.
+pub struct Sender<T> {
+       channel: Arc<Channel<T>>,
+}
+impl<T> Sender<T> {
+       /// Enqueue a message. Never blocks.
+       ///
+       /// ## Errors
+       /// If the `Receiver` has been dropped; the message is handed back.
+       pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+              if !self.channel.receiver_alive.load(Relaxed) {
+                     return Err(SendError(message));
+              }
+              self.channel.push(message);
+              if self.channel.sleeping.load(SeqCst) {
+                     self.channel.waiter.wake();
+              }
+              Ok(())
+       }
+}
+impl<T> Clone for Sender<T> {
+       fn clone(&self) -> Self {
+              self.channel.senders.fetch_add(1, Relaxed);
+              Self { channel: self.channel.clone() }
+       }
+}
+impl<T> Drop for Sender<T> {
+       fn drop(&mut self) {
+              if self.channel.senders.fetch_sub(1, AcqRel) == 1 {
+                     self.channel.waiter.wake();
+              }
+       }
+}
+
+/// Receiving half of an [`mpsc`] channel.
+///
+/// May be moved to another thread, but not shared: the list's consumer end is single-threaded.
+pub struct Receiver<T> {
+       channel:   Arc<Channel<T>>,
+       _not_sync: PhantomData<Cell<()>>,
+}
+impl<T> Receiver<T> {
+       /// Dequeue a message, blocking while the channel is empty.
+       ///
+       /// ## Errors
+       /// If the channel is empty and all `Sender`s are gone.
+       pub fn recv(&self) -> Result<T, RecvError> {
+              self.recv_deadline_inner(None).map_err(|_| RecvError) // no deadline: only ever `Disconnected`
+       }
+
+       /// As [`recv`](Self::recv), giving up after `timeout`.
+       pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> { self.recv_deadline_inner(deadline::after(timeout)) }
+
+       /// As [`recv`](Self::recv), giving up at `deadline`.
+       pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> { self.recv_deadline_inner(Some(deadline)) }
+
+       fn recv_deadline_inner(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+              let mut waited = WaitTimer::new(WaitSite::ChannelRecv);
+              loop {
+                     match self.try_recv() {
+                            Ok(message) => return Ok(message),
+                            Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                            Err(TryRecvError::Empty) => {}
+                     }
+                     waited.start();
+                     self.channel.waiter.register();
+                     self.channel.sleeping.store(true, SeqCst);
+                     let head = self.channel.head.load(SeqCst);
+                     // SAFETY: we are the receiver, the sole user of `tail`.
+                     let tail = unsafe { *self.channel.tail.get() };
+                     let parked = head != tail || self.channel.senders.load(Acquire) == 0 || park_until(deadline);
+                     self.channel.sleeping.store(false, Relaxed);
+                     if !parked {
+                            // one last look: a message may have raced the deadline
+                            return self.try_recv().map_err(|error| match error {
+                                   TryRecvError::Empty => RecvTimeoutError::Timeout,
+                                   TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+                            });
+                     }
+              }
+       }
+
+       /// Dequeue a message if one is available, without blocking.
+       pub fn try_recv(&self) -> Result<T, TryRecvError> {
+              // senders checked first: if they're all gone, every message they sent is already in the list
+              let disconnected = self.channel.senders.load(Acquire) == 0;
+              // SAFETY: we are the receiver.
+              match unsafe { self.channel.pop_spinning() } {
+                     Some(message) => Ok(message),
+                     None if disconnected => Err(TryRecvError::Disconnected),
+                     None => Err(TryRecvError::Empty),
+              }
+       }
+
+       /// Blocking iterator over messages, ending once all `Sender`s are gone and the channel is drained.
+       pub fn iter(&self) -> impl Iterator<Item = T> + '_ { std::iter::from_fn(|| self.recv().ok()) }
+}
+impl<T> Drop for Receiver<T> {
+       fn drop(&mut self) { self.channel.receiver_alive.store(false, Relaxed); }
+}
+
+impl<T> SelectHandle for Receiver<T> {
+       fn is_ready(&self) -> bool {
+              // SAFETY: `Receiver` is `!Sync`, so `&self` means we're on the receiving thread, the sole user of `tail`.
+              let tail = unsafe { *self.channel.tail.get() };
+              self.channel.head.load(SeqCst) != tail || self.channel.senders.load(Acquire) == 0
+       }
+
+       fn watch(&self) {
+              self.channel.waiter.register();
+              self.channel.sleeping.store(true, SeqCst);
+       }
+
+       fn unwatch(&self) { self.channel.sleeping.store(false, Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::test_util::DropCounter;
+
+       /// Kept small enough for Miri: `cargo +nightly miri test --package sync mpsc`
+       const PER_PRODUCER: usize = if cfg!(miri) { 20 } else { 1_000 };
+
+       #[test]
+       fn test_multiple_producers() {
+              const NUM_PRODUCERS: usize = 4;
+              let (sender, receiver) = mpsc();
+              let producers: Vec<_> = (0..NUM_PRODUCERS)
+                     .map(|p| {
+                            let sender = sender.clone();
+                            thread::spawn(move || {
+                                   for i in 0..PER_PRODUCER {
+                                          sender.send((p, i)).unwrap();
+                                   }
+                            })
+                     })
+                     .collect();
+              drop(sender);
+              let mut last_seen = [None; NUM_PRODUCERS];
+              let mut count = 0;
+              for (p, i) in receiver.iter() {
+                     // per-producer FIFO
+                     assert!(last_seen[p] < Some(i));
+                     last_seen[p] = Some(i);
+                     count += 1;
+              }
+              for producer in producers {
+                     producer.join().unwrap();
+              }
+              assert_eq!(count, NUM_PRODUCERS * PER_PRODUCER);
+       }
+
+       #[test]
+       fn test_blocked_receiver_is_woken() {
+              let (sender, receiver) = mpsc();
+              let consumer = thread::spawn(move || receiver.recv());
+              thread::sleep(Duration::from_millis(10));
+              sender.send(5).unwrap();
+              assert_eq!(consumer.join().unwrap(), Ok(5));
+       }
+
+       #[test]
+       fn test_recv_timeout() {
+              let (sender, receiver) = mpsc();
+              assert_eq!(receiver.recv_timeout(Duration::from_millis(5)), Err(RecvTimeoutError::Timeout));
+              sender.send(1).unwrap();
+              assert_eq!(receiver.recv_deadline(Instant::now()), Ok(1));
+              drop(sender);
+              assert_eq!(receiver.recv_timeout(Duration::from_secs(60)), Err(RecvTimeoutError::Disconnected));
+       }
+
+       #[test]
+       fn test_disconnection() {
+              let (sender, receiver) = mpsc();
+              sender.send(1).unwrap();
+              drop(sender);
+              assert_eq!(receiver.recv(), Ok(1));
+              assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+              assert_eq!(receiver.recv(), Err(RecvError));
+
+              let (sender, receiver) = mpsc();
+              drop(receiver);
+              assert_eq!(sender.send(2), Err(SendError(2)));
+       }
+
+       #[test]
+       fn test_unreceived_messages_dropped() {
+              let drops = AtomicUsize::new(0);
+              let (sender, receiver) = mpsc();
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            let (sender, drops) = (sender.clone(), &drops);
+                            s.spawn(move || {
+                                   for _ in 0..PER_PRODUCER {
+                                          sender.send(DropCounter(drops)).unwrap();
+                                   }
+                            });
+                     }
+              });
+              // receive some, leave the rest in the list
+              drop(receiver.recv().unwrap());
+              drop(receiver.recv().unwrap());
+              assert_eq!(drops.load(Relaxed), 2);
+              drop((sender, receiver));
+              assert_eq!(drops.load(Relaxed), 4 * PER_PRODUCER);
+       }
+}
+
+// QuickCheck tests: random operation sequences against a `VecDeque` reference model
+#[cfg(all(test, not(miri)))]
+mod quickcheck_tests {
+       use std::{collections::VecDeque, thread};
+
+       use quickcheck::{Arbitrary, Gen};
+       use quickcheck_macros::quickcheck;
+
+       use super::*;
+
+       #[derive(Debug, Clone)]
+       enum Op {
+              /// Send through the sender at this index (modulo the live ones).
+              Send(usize, u8),
+              Clone(usize),
+              DropSender(usize),
+              TryRecv,
+              DropReceiver,
+       }
+
+       impl Arbitrary for Op {
+              fn arbitrary(g: &mut Gen) -> Self {
+                     // weighted towards sends and receives, so the queue actually fills
+                     match u8::arbitrary(g) % 10 {
+                            0..=3 => Self::Send(usize::arbitrary(g), u8::arbitrary(g)),
+                            4..=6 => Self::TryRecv,
+                            7 => Self::Clone(usize::arbitrary(g)),
+                            8 => Self::DropSender(usize::arbitrary(g)),
+                            _ => Self::DropReceiver,
+                     }
+              }
+       }
+
+       /// On one thread, the channel behaves exactly like a queue plus a sender count.
+       #[quickcheck]
+       fn qc_test_matches_sequential_model(ops: Vec<Op>) -> bool {
+              let (sender, receiver) = mpsc();
+              let mut senders = vec![sender];
+              let mut receiver = Some(receiver);
+              let mut model = VecDeque::new();
+              ops.into_iter().all(|op| match op {
+                     Op::Send(index, message) => {
+                            let Some(sender) = senders.get(index % senders.len().max(1)) else { return true };
+                            let sent = sender.send(message);
+                            match receiver {
+                                   Some(_) => {
+                                          model.push_back(message);
+                                          sent == Ok(())
+                                   }
+                                   None => sent == Err(SendError(message)),
+                            }
+                     }
+                     Op::Clone(index) => {
+                            if let Some(sender) = senders.get(index % senders.len().max(1)) {
+                                   senders.push(sender.clone());
+                            }
+                            true
+                     }
+                     Op::DropSender(index) => {
+                            if !senders.is_empty() {
+                                   senders.swap_remove(index % senders.len());
+                            }
+                            true
+                     }
+                     Op::TryRecv => receiver.as_ref().is_none_or(|receiver| {
+                            let expected = match model.pop_front() {
+                                   Some(message) => Ok(message),
+                                   None if senders.is_empty() => Err(TryRecvError::Disconnected),
+                                   None => Err(TryRecvError::Empty),
+                            };
+                            receiver.try_recv() == expected
+                     }),
+                     Op::DropReceiver => {
+                            receiver = None;
+                            true
+                     }
+              })
+       }
+
+       /// Producers on their own threads (one per plan, at most 4) interleave arbitrarily, but the receiver gets every
+       /// message exactly once and each producer's in the order sent.
+       #[quickcheck]
+       fn qc_test_concurrent_producers_keep_their_order(plans: Vec<Vec<u8>>) -> bool {
+              let plans = &plans[..plans.len().min(4)];
+              let (sender, receiver) = mpsc();
+              let received: Vec<(usize, u8)> = thread::scope(|s| {
+                     for (producer, plan) in plans.iter().enumerate() {
+                            let sender = sender.clone();
+                            s.spawn(move || plan.iter().for_each(|&message| sender.send((producer, message)).unwrap()));
+                     }
+                     drop(sender);
+                     receiver.iter().collect()
+              });
+              plans.iter().enumerate().all(|(producer, plan)| {
+                     received.iter().filter(|(from, _)| *from == producer).map(|&(_, message)| message).eq(plan.iter().copied())
+              })
+       }
+}
+
+#[cfg(all(test, feature = "shuttle"))]
+mod shuttle_tests {
+       use shuttle::thread;
+
+       use crate::atomic::shuttle_check;
+
+       /// Three producers racing their swaps into `head` against a consumer that keeps going to sleep: every message
+       /// arrives, each producer's in order, and the last sender's drop ends the stream.
+       #[test]
+       fn shuttle_producers_and_a_sleeping_consumer() {
+              shuttle_check(|| {
+                     let (sender, receiver) = super::mpsc();
+                     for producer in 0..3 {
+                            let sender = sender.clone();
+                            thread::spawn(move || {
+                                   for i in 0..3 {
+                                          sender.send((producer, i)).unwrap();
+                                   }
+                            });
+                     }
+                     drop(sender);
+                     let mut next = [0; 3];
+                     for (producer, i) in receiver.iter() {
+                            assert_eq!(i, next[producer], "producer {producer}'s messages out of order");
+                            next[producer] += 1;
+                     }
+                     assert_eq!(next, [3; 3]);
+              });
+       }
+}
@@ -0,0 +1,338 @@
+//! One-shot channel: exactly one message, from one sender to one receiver.
+//!
+//! ## [Chapter 5: Safety Through Runtime Checks](https://marabos.nl/atomics/building-channels.html#safety-through-runtime-checks)
+//!
+//! The message lives in an `UnsafeCell<MaybeUninit<T>>`; a single atomic `state` says who may touch it.
+//! ```text
+//! EMPTY ──send──> WRITING ──> READY ──recv──> TAKEN
+//!   └──sender dropped──> DISCONNECTED
+//! ```
+//! Misuse (sending twice, receiving twice) is caught at runtime and panics.
+//!
+//! ## Blocking
+//! `recv` registers the calling thread in a `ParkSlot` and parks until the sender wakes it.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! let (sender, receiver) = sync::channel::oneshot();
+//! thread::spawn(move || sender.send("hello"));
+//! assert_eq!(receiver.recv(), Ok("hello"));
+//! ```
+
+use std::{cell::{Cell, UnsafeCell},
+          marker::PhantomData,
+          mem::MaybeUninit,
+          sync::Arc,
+          time::{Duration, Instant}};
+
+use super::{RecvError, RecvTimeoutError, select::sealed::SelectHandle};
+use crate::{atomic::{AtomicU8,
+                     Ordering::{Acquire, Relaxed, Release}},
+            deadline::{self, park_until},
+            histogram::{WaitSite, WaitTimer},
+            park_slot::ParkSlot};
+
+const EMPTY: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+const TAKEN: u8 = 3;
+const DISCONNECTED: u8 = 4;
+
+/// Create a connected one-shot (`Sender`, `Receiver`) pair.
+pub fn oneshot<T>() -> (Sender<T>, Receiver<T>) {
+       let channel = Arc::new(Channel {
+              message: UnsafeCell::new(MaybeUninit::uninit()),
+              state:   AtomicU8::new(EMPTY),
+              waiter:  ParkSlot::new(),
+       });
+       (Sender { channel: channel.clone() }, Receiver { channel, _not_sync: PhantomData })
+}
+
+struct Channel<T> {
+       message: UnsafeCell<MaybeUninit<T>>,
+       state:   AtomicU8,
+       /// Receiving thread, once it has started blocking.
+       waiter:  ParkSlot,
+}
+// SAFETY: `state` guarantees a single writer, then (after a release/acquire pair) a single reader of `message`.
+unsafe impl<T> Sync for Channel<T> where T: Send {}
+
+impl<T> Drop for Channel<T> {
+       fn drop(&mut self) {
+              // a message that was sent but never received still needs dropping
+              // Relaxed (rather than `get_mut`, which loom's atomics lack): `&mut self` means nobody to synchronize with
+              if self.state.load(Relaxed) == READY {
+                     // SAFETY: READY means the message was fully written and not yet taken.
+                     unsafe { self.message.get_mut().assume_init_drop() }
+              }
+       }
+}
+
+/// Sending half of a [`oneshot`] channel.
+pub struct Sender<T> {
+       channel: Arc<Channel<T>>,
+}
+impl<T> Sender<T> {
+       /// Send the message.
+       ///
+       /// ## Panics
+       /// If called more than once.
+       pub fn send(&self, message: T) {
+              if self.channel.state.compare_exchange(EMPTY, WRITING, Relaxed, Relaxed).is_err() {
+                     panic!("can't send more than one message!");
+              }
+              // SAFETY: we won the EMPTY -> WRITING transition, so nobody else touches `message` until READY.
+              unsafe { (*self.channel.message.get()).write(message) };
+              self.channel.state.store(READY, Release);
+              self.channel.waiter.wake();
+       }
+}
+impl<T> Drop for Sender<T> {
+       fn drop(&mut self) {
+              // only matters if nothing was ever sent
+              if self.channel.state.compare_exchange(EMPTY, DISCONNECTED, Relaxed, Relaxed).is_ok() {
+                     self.channel.waiter.wake();
+              }
+       }
+}
+
+/// Receiving half of a [`oneshot`] channel.
+///
+/// May be moved to another thread, but not shared: only one thread can be registered as the waiter.
+pub struct Receiver<T> {
+       channel:   Arc<Channel<T>>,
+       _not_sync: PhantomData<Cell<()>>,
+}
+impl<T> Receiver<T> {
+       /// Whether a message is waiting to be received.
+       pub fn is_ready(&self) -> bool { self.channel.state.load(Relaxed) == READY }
+
+       /// Block until the message arrives.
+       ///
+       /// ## Errors
+       /// If the `Sender` was dropped without sending.
+       ///
+       /// ## Panics
+       /// If the message was already received.
+       pub fn recv(&self) -> Result<T, RecvError> {
+              self.recv_deadline_inner(None).map_err(|_| RecvError) // no deadline: only ever `Disconnected`
+       }
+
+       /// As [`recv`](Self::recv), giving up after `timeout`.
+       pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> { self.recv_deadline_inner(deadline::after(timeout)) }
+
+       /// As [`recv`](Self::recv), giving up at `deadline`.
+       pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> { self.recv_deadline_inner(Some(deadline)) }
+
+       fn recv_deadline_inner(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+              let mut waited = WaitTimer::new(WaitSite::ChannelRecv);
+              self.channel.waiter.register();
+              loop {
+                     match self.channel.state.load(Acquire) {
+                            READY => {
+                                   self.channel.state.store(TAKEN, Relaxed);
+                                   // SAFETY: READY (acquired) means the message is fully written;
+                                   //         we are the only receiver and just marked it TAKEN.
+                                   return Ok(unsafe { (*self.channel.message.get()).assume_init_read() });
+                            }
+                            TAKEN => panic!("message already received!"),
+                            DISCONNECTED => return Err(RecvTimeoutError::Disconnected),
+                            _ => {
+                                   waited.start();
+                                   if !park_until(deadline) {
+                                          return Err(RecvTimeoutError::Timeout);
+                                   }
+                            }
+                     }
+              }
+       }
+}
+
+impl<T> SelectHandle for Receiver<T> {
+       fn is_ready(&self) -> bool { self.channel.state.load(Relaxed) >= READY }
+
+       fn watch(&self) { self.channel.waiter.register(); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::test_util::DropCounter;
+
+       #[test]
+       fn test_cross_thread_handoff() {
+              let (sender, receiver) = oneshot();
+              let consumer = thread::spawn(move || receiver.recv());
+              thread::sleep(Duration::from_millis(10)); // let the receiver park first
+              sender.send(vec![1, 2, 3]);
+              assert_eq!(consumer.join().unwrap(), Ok(vec![1, 2, 3]));
+       }
+
+       #[test]
+       fn test_is_ready() {
+              let (sender, receiver) = oneshot();
+              assert!(!receiver.is_ready());
+              sender.send(7);
+              assert!(receiver.is_ready());
+              assert_eq!(receiver.recv(), Ok(7));
+              assert!(!receiver.is_ready());
+       }
+
+       #[test]
+       fn test_send_then_drop_drops_message() {
+              let drops = AtomicUsize::new(0);
+              let (sender, receiver) = oneshot();
+              sender.send(DropCounter(&drops));
+              drop(sender);
+              assert_eq!(drops.load(Relaxed), 0);
+              drop(receiver);
+              assert_eq!(drops.load(Relaxed), 1);
+       }
+
+       #[test]
+       fn test_received_message_dropped_once() {
+              let drops = AtomicUsize::new(0);
+              let (sender, receiver) = oneshot();
+              sender.send(DropCounter(&drops));
+              drop(receiver.recv());
+              drop((sender, receiver));
+              assert_eq!(drops.load(Relaxed), 1);
+       }
+
+       #[test]
+       fn test_sender_dropped_without_sending() {
+              let (sender, receiver) = oneshot::<()>();
+              let consumer = thread::spawn(move || receiver.recv());
+              drop(sender);
+              assert_eq!(consumer.join().unwrap(), Err(RecvError));
+       }
+
+       #[test]
+       fn test_recv_timeout() {
+              let (sender, receiver) = oneshot();
+              assert_eq!(receiver.recv_timeout(Duration::from_millis(5)), Err(RecvTimeoutError::Timeout));
+              sender.send(1);
+              assert_eq!(receiver.recv_deadline(Instant::now()), Ok(1));
+       }
+
+       #[test]
+       #[should_panic(expected = "can't send more than one message!")]
+       fn test_double_send_panics() {
+              let (sender, _receiver) = oneshot();
+              sender.send(1);
+              sender.send(2);
+       }
+}
+
+// QuickCheck tests: random operation sequences against a reference model
+#[cfg(test)]
+mod quickcheck_tests {
+       use quickcheck::{Arbitrary, Gen};
+       use quickcheck_macros::quickcheck;
+
+       use super::*;
+
+       #[derive(Debug, Clone)]
+       enum Op {
+              Send(u8),
+              DropSender,
+              /// Receive without waiting.
+              Recv,
+       }
+
+       impl Arbitrary for Op {
+              fn arbitrary(g: &mut Gen) -> Self {
+                     match u8::arbitrary(g) % 3 {
+                            0 => Self::Send(u8::arbitrary(g)),
+                            1 => Self::DropSender,
+                            _ => Self::Recv,
+                     }
+              }
+       }
+
+       /// The channel tracks the model's state machine: a message waits until received, a dropped sender disconnects
+       /// only if it never sent. Ops the model says would panic (a second send or receive) are skipped.
+       #[quickcheck]
+       fn qc_test_matches_state_model(ops: Vec<Op>) -> bool {
+              enum Model {
+                     Empty,
+                     Ready(u8),
+                     Taken,
+              }
+              let (sender, receiver) = oneshot();
+              let mut sender = Some(sender);
+              let mut model = Model::Empty;
+              ops.into_iter().all(|op| {
+                     match op {
+                            Op::Send(message) => {
+                                   if let (Some(sender), Model::Empty) = (&sender, &model) {
+                                          sender.send(message);
+                                          model = Model::Ready(message);
+                                   }
+                            }
+                            Op::DropSender => sender = None,
+                            Op::Recv => {
+                                   let expected = match model {
+                                          Model::Ready(message) => Ok(message),
+                                          Model::Empty if sender.is_none() => Err(RecvTimeoutError::Disconnected),
+                                          Model::Empty => Err(RecvTimeoutError::Timeout),
+                                          Model::Taken => return true,
+                                   };
+                                   if expected.is_ok() {
+                                          model = Model::Taken;
+                                   }
+                                   if receiver.recv_timeout(Duration::ZERO) != expected {
+                                          return false;
+                                   }
+                            }
+                     }
+                     receiver.is_ready() == matches!(model, Model::Ready(_))
+              })
+       }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+       use loom::{sync::{Arc,
+                         atomic::{AtomicUsize, Ordering::Relaxed}},
+                  thread};
+
+       use super::*;
+
+       // The receiving side runs on the spawned thread: loom's `join` waits on the same notification as `unpark`,
+       // so a late wake aimed at a joining main thread would look to it like a finished join.
+
+       /// A Relaxed write made before `send` is visible after `recv`: the channel's release/acquire pair carries it,
+       /// in every interleaving of the sender with the receiver's register-check-park loop.
+       #[test]
+       fn loom_recv_sees_what_send_published() {
+              loom::model(|| {
+                     let written = Arc::new(AtomicUsize::new(0));
+                     let (sender, receiver) = oneshot();
+                     let other = {
+                            let written = written.clone();
+                            thread::spawn(move || receiver.recv().map(|()| written.load(Relaxed)))
+                     };
+                     written.store(42, Relaxed);
+                     sender.send(());
+                     assert_eq!(other.join().unwrap(), Ok(42));
+              });
+       }
+
+       /// A sender dropped unsent always disconnects the receiver, never leaves it waiting.
+       #[test]
+       fn loom_dropped_sender_disconnects() {
+              loom::model(|| {
+                     let (sender, receiver) = oneshot::<()>();
+                     let other = thread::spawn(move || receiver.recv());
+                     drop(sender);
+                     assert_eq!(other.join().unwrap(), Err(RecvError));
+              });
+       }
+}
@@ -0,0 +1,299 @@
+//! Zero-capacity (rendezvous) channel.
+//!
+//! Nothing is ever buffered: `send` returns only once the receiver has *taken* the message,
+//! and `recv` waits for a sender to show up. Both sides meet at the handoff, which makes the channel
+//! a synchronization point as much as a transport. (e.g. "worker has definitely picked up this job")
+//!
+//! One sender, one receiver; each side parks in its own `ParkSlot` while waiting on the other.
+//! ```text
+//! EMPTY ──send places message──> FULL ──recv claims it──> TAKING ──recv takes message──> EMPTY (sender released)
+//!                                  └──send times out, or the receiver is gone: send takes it back──> EMPTY
+//! ```
+//! The claim is what keeps the two takes apart: a sender giving up and a receiver arriving both move out of FULL
+//! with a compare-exchange, and only one of them wins.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! let (sender, receiver) = sync::channel::rendezvous();
+//! let worker = thread::spawn(move || receiver.recv());
+//! sender.send(42).unwrap(); // returns once the worker holds the value
+//! assert_eq!(worker.join().unwrap(), Ok(42));
+//! ```
+
+use std::{cell::{Cell, UnsafeCell},
+          marker::PhantomData,
+          sync::Arc,
+          time::{Duration, Instant}};
+
+use super::{RecvError, RecvTimeoutError, SendError, SendTimeoutError, select::sealed::SelectHandle};
+use crate::{atomic::{AtomicBool, AtomicU8,
+                     Ordering::{Acquire, Relaxed, Release},
+                     spin_loop},
+            deadline::{self, park_until},
+            park_slot::ParkSlot};
+
+const EMPTY: u8 = 0;
+const FULL: u8 = 1;
+/// Claimed by the receiver, which is moving the message out.
+const TAKING: u8 = 2;
+
+/// Create a connected zero-capacity (`Sender`, `Receiver`) pair.
+pub fn rendezvous<T>() -> (Sender<T>, Receiver<T>) {
+       let channel = Arc::new(Channel {
+              message:         UnsafeCell::new(None),
+              state:           AtomicU8::new(EMPTY),
+              sender_alive:    AtomicBool::new(true),
+              receiver_alive:  AtomicBool::new(true),
+              sender_waiter:   ParkSlot::new(),
+              receiver_waiter: ParkSlot::new(),
+       });
+       (Sender { channel: channel.clone(), _not_sync: PhantomData }, Receiver { channel, _not_sync: PhantomData })
+}
+
+struct Channel<T> {
+       /// Written by the sender while EMPTY, taken by whoever moves it out of FULL.
+       message:         UnsafeCell<Option<T>>,
+       state:           AtomicU8,
+       sender_alive:    AtomicBool,
+       receiver_alive:  AtomicBool,
+       sender_waiter:   ParkSlot,
+       receiver_waiter: ParkSlot,
+}
+// SAFETY: `state` hands `message` back and forth with release/acquire pairs; only one side touches it at a time.
+unsafe impl<T> Sync for Channel<T> where T: Send {}
+
+/// Sending half of a [`rendezvous`] channel.
+pub struct Sender<T> {
+       channel:   Arc<Channel<T>>,
+       _not_sync: PhantomData<Cell<()>>,
+}
+impl<T> Sender<T> {
+       /// Hand over a message, blocking until the receiver has taken it.
+       ///
+       /// ## Errors
+       /// If the `Receiver` is (or, while we wait, gets) dropped; the message is handed back.
+       pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+              self.send_deadline_inner(message, None).map_err(|error| match error {
+                     SendTimeoutError::Timeout(message) | SendTimeoutError::Disconnected(message) => SendError(message),
+              })
+       }
+
+       /// As [`send`](Self::send), giving up after `timeout`.
+       ///
+       /// On timeout the message is reclaimed (the receiver never saw it) and handed back.
+       pub fn send_timeout(&self, message: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+              self.send_deadline_inner(message, deadline::after(timeout))
+       }
+
+       /// As [`send`](Self::send), giving up at `deadline`.
+       pub fn send_deadline(&self, message: T, deadline: Instant) -> Result<(), SendTimeoutError<T>> {
+              self.send_deadline_inner(message, Some(deadline))
+       }
+
+       fn send_deadline_inner(&self, message: T, deadline: Option<Instant>) -> Result<(), SendTimeoutError<T>> {
+              if !self.channel.receiver_alive.load(Acquire) {
+                     return Err(SendTimeoutError::Disconnected(message));
+              }
+              // SAFETY: state is EMPTY (our previous send completed), so the receiver isn't touching `message`.
+              unsafe { *self.channel.message.get() = Some(message) };
+              self.channel.sender_waiter.register();
+              self.channel.state.store(FULL, Release);
+              self.channel.receiver_waiter.wake();
+              loop {
+                     if self.channel.state.load(Acquire) == EMPTY {
+                            return Ok(());
+                     }
+                     let error: fn(T) -> SendTimeoutError<T> = if !self.channel.receiver_alive.load(Acquire) {
+                            SendTimeoutError::Disconnected
+                     } else if !park_until(deadline) {
+                            SendTimeoutError::Timeout
+                     } else {
+                            continue;
+                     };
+                     // reclaim the message, unless the receiver took it just now
+                     return match self.channel.state.compare_exchange(FULL, EMPTY, Acquire, Relaxed) {
+                            // SAFETY: we moved FULL -> EMPTY ourselves, so the message is ours again.
+                            Ok(_) => Err(error(unsafe { (*self.channel.message.get()).take() }.expect("FULL holds a message"))),
+                            // the receiver claimed it first: delivered, but `message` is only ours to write again at EMPTY
+                            Err(_) => {
+                                   while self.channel.state.load(Acquire) != EMPTY {
+                                          spin_loop();
+                                   }
+                                   Ok(())
+                            }
+                     };
+              }
+       }
+}
+impl<T> Drop for Sender<T> {
+       fn drop(&mut self) {
+              self.channel.sender_alive.store(false, Release);
+              self.channel.receiver_waiter.wake();
+       }
+}
+
+/// Receiving half of a [`rendezvous`] channel.
+pub struct Receiver<T> {
+       channel:   Arc<Channel<T>>,
+       _not_sync: PhantomData<Cell<()>>,
+}
+impl<T> Receiver<T> {
+       /// Block until a sender hands over a message.
+       ///
+       /// ## Errors
+       /// If the `Sender` is gone.
+       pub fn recv(&self) -> Result<T, RecvError> {
+              self.recv_deadline_inner(None).map_err(|_| RecvError) // no deadline: only ever `Disconnected`
+       }
+
+       /// As [`recv`](Self::recv), giving up after `timeout`.
+       pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> { self.recv_deadline_inner(deadline::after(timeout)) }
+
+       /// As [`recv`](Self::recv), giving up at `deadline`.
+       pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> { self.recv_deadline_inner(Some(deadline)) }
+
+       fn recv_deadline_inner(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+              self.channel.receiver_waiter.register();
+              loop {
+                     if self.channel.state.compare_exchange(FULL, TAKING, Acquire, Relaxed).is_ok() {
+                            // SAFETY: we moved FULL -> TAKING ourselves: the sender finished writing, and can't take it back.
+                            let message = unsafe { (*self.channel.message.get()).take() }.expect("FULL holds a message");
+                            self.channel.state.store(EMPTY, Release);
+                            self.channel.sender_waiter.wake();
+                            return Ok(message);
+                     }
+                     // a live sender is never mid-`send` with an EMPTY state for long; a dropped one never will be again
+                     if !self.channel.sender_alive.load(Acquire) {
+                            return Err(RecvTimeoutError::Disconnected);
+                     }
+                     if !park_until(deadline) {
+                            return Err(RecvTimeoutError::Timeout);
+                     }
+              }
+       }
+
+       /// Blocking iterator over messages, ending once the `Sender` is gone.
+       pub fn iter(&self) -> impl Iterator<Item = T> + '_ { std::iter::from_fn(|| self.recv().ok()) }
+}
+impl<T> Drop for Receiver<T> {
+       fn drop(&mut self) {
+              self.channel.receiver_alive.store(false, Release);
+              self.channel.sender_waiter.wake();
+       }
+}
+
+impl<T> SelectHandle for Receiver<T> {
+       fn is_ready(&self) -> bool { self.channel.state.load(Acquire) == FULL || !self.channel.sender_alive.load(Acquire) }
+
+       fn watch(&self) { self.channel.receiver_waiter.register(); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_send_waits_for_receiver() {
+              let (sender, receiver) = rendezvous();
+              let taken = Arc::new(AtomicUsize::new(0));
+              let consumer = thread::spawn({
+                     let taken = taken.clone();
+                     move || {
+                            thread::sleep(Duration::from_millis(20));
+                            for message in receiver.iter() {
+                                   taken.store(message, Relaxed);
+                            }
+                     }
+              });
+              for i in 1..=5 {
+                     sender.send(i).unwrap();
+                     // the receiver stores *after* taking, so it may lag by at most the message just handed over
+                     assert!(taken.load(Relaxed) >= i - 1);
+              }
+              drop(sender);
+              consumer.join().unwrap();
+              assert_eq!(taken.load(Relaxed), 5);
+       }
+
+       #[test]
+       fn test_recv_waits_for_sender() {
+              let (sender, receiver) = rendezvous();
+              let producer = thread::spawn(move || {
+                     thread::sleep(Duration::from_millis(10));
+                     sender.send("late").unwrap();
+              });
+              assert_eq!(receiver.recv(), Ok("late"));
+              producer.join().unwrap();
+              assert_eq!(receiver.recv(), Err(RecvError));
+       }
+
+       #[test]
+       fn test_timeouts() {
+              let (sender, receiver) = rendezvous();
+              // nobody takes it: the message comes back, and the receiver never sees it
+              assert_eq!(sender.send_timeout(1, Duration::from_millis(5)), Err(SendTimeoutError::Timeout(1)));
+              assert_eq!(receiver.recv_timeout(Duration::from_millis(5)), Err(RecvTimeoutError::Timeout));
+              drop(sender);
+              assert_eq!(receiver.recv_deadline(Instant::now()), Err(RecvTimeoutError::Disconnected));
+       }
+
+       #[test]
+       fn test_receiver_dropped_while_sender_waits() {
+              let (sender, receiver) = rendezvous();
+              let producer = thread::spawn(move || sender.send(String::from("unclaimed")));
+              thread::sleep(Duration::from_millis(10));
+              drop(receiver);
+              assert_eq!(producer.join().unwrap(), Err(SendError(String::from("unclaimed"))));
+       }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+       use loom::thread;
+
+       use super::*;
+
+       // Both sides run on spawned threads: each wakes the other, and loom's `join` waits on the same notification as
+       // `unpark`, so a late wake aimed at a joining main thread would look to it like a finished join.
+
+       /// A sender giving up at once races the receiver out of FULL: exactly one of them ends up with the message.
+       #[test]
+       fn loom_send_timeout_races_recv() {
+              loom::model(|| {
+                     let (sender, receiver) = rendezvous();
+                     let sending = thread::spawn(move || sender.send_timeout(7, Duration::ZERO));
+                     let receiving = thread::spawn(move || receiver.recv());
+                     match (sending.join().unwrap(), receiving.join().unwrap()) {
+                            (Ok(()), Ok(7)) | (Err(SendTimeoutError::Timeout(7)), Err(RecvError)) => {}
+                            outcome => panic!("the message went to both sides or neither: {outcome:?}"),
+                     }
+              });
+       }
+}
+
+#[cfg(all(test, feature = "shuttle"))]
+mod shuttle_tests {
+       use shuttle::thread;
+
+       use super::*;
+       use crate::atomic::shuttle_check;
+
+       /// Senders giving up at once, over and over, against a receiver taking whatever it can: every message ends
+       /// up on exactly one side, and a send that lost its reclaim never overwrites a message still being taken.
+       #[test]
+       fn shuttle_send_timeouts_race_recv() {
+              shuttle_check(|| {
+                     let (sender, receiver) = rendezvous();
+                     let other = thread::spawn(move || receiver.iter().collect::<Vec<_>>());
+                     let sent: Vec<_> = (0..3).filter(|&i| sender.send_timeout(i, Duration::ZERO).is_ok()).collect();
+                     drop(sender);
+                     assert_eq!(other.join().unwrap(), sent);
+              });
+       }
+}
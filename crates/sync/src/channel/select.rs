@@ -0,0 +1,140 @@
+//! Waiting on the first of several receivers.
+//!
+//! Each receiver here already parks its owning thread through a `ParkSlot`,
+//! so selecting is just: register the current thread with *every* receiver, check them all, park, repeat.
+//! Whichever sender fires first unparks us; we report its index and the caller receives from it.
+//!
+//! A receiver counts as ready when receiving from it won't block:
+//! a message is waiting *or* its sending side is gone (so `recv` returns an error immediately).
+//!
+//! ## Supported receivers
+//! [`oneshot`](super::oneshot), [`typed_oneshot`](super::typed_oneshot), [`mpsc`](super::mpsc), [`rendezvous`](super::rendezvous).
+//! (`BlockingChannel` waits on a std `Condvar` and `watch` on a futex, neither of which can wake a parked select.)
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::channel::{self, Select};
+//!
+//! let (_quiet_sender, quiet) = channel::mpsc::<&str>();
+//! let (sender, chatty) = channel::mpsc();
+//! thread::spawn(move || sender.send("hi").unwrap());
+//!
+//! let mut select = Select::new();
+//! let chatty_index = select.recv(&chatty);
+//! let _quiet_index = select.recv(&quiet);
+//! assert_eq!(select.select(), chatty_index);
+//! assert_eq!(chatty.recv(), Ok("hi"));
+//! ```
+
+use crate::atomic::park;
+
+/// A receiver [`Select`] can wait on. Implemented by this crate's park-based receivers.
+pub trait Selectable: sealed::SelectHandle {}
+impl<T> Selectable for T where T: sealed::SelectHandle {}
+
+pub(super) mod sealed {
+       /// Hooks a receiver provides to [`Select`](super::Select). Not implementable outside the crate.
+       pub trait SelectHandle {
+              /// Receiving right now would not block.
+              fn is_ready(&self) -> bool;
+              /// Arrange for the current thread to be unparked when the receiver becomes ready.
+              /// Called *before* the readiness check, on the receiver's own thread.
+              fn watch(&self);
+              /// Undo any bookkeeping from [`watch`](Self::watch).
+              fn unwatch(&self) {}
+       }
+}
+
+/// A set of receivers to wait on, identified by the index returned when adding them.
+#[derive(Default)]
+pub struct Select<'a> {
+       handles: Vec<&'a dyn Selectable>,
+}
+impl<'a> Select<'a> {
+       pub fn new() -> Self { Self { handles: Vec::new() } }
+
+       /// Add a receiver; returns the index [`select`](Self::select) will report for it.
+       pub fn recv(&mut self, receiver: &'a dyn Selectable) -> usize {
+              self.handles.push(receiver);
+              self.handles.len() - 1
+       }
+
+       /// Index of a ready receiver, if any, without blocking.
+       ///
+       /// Earlier-added receivers win ties.
+       pub fn try_select(&self) -> Option<usize> { self.handles.iter().position(|handle| handle.is_ready()) }
+
+       /// Block until some receiver is ready and return its index.
+       ///
+       /// ## Panics
+       /// If no receivers were added. (It would block forever.)
+       pub fn select(&self) -> usize {
+              assert!(!self.handles.is_empty(), "select with no receivers would block forever");
+              let ready = loop {
+                     self.handles.iter().for_each(|handle| handle.watch());
+                     if let Some(index) = self.try_select() {
+                            break index;
+                     }
+                     park();
+              };
+              self.handles.iter().for_each(|handle| handle.unwatch());
+              ready
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{thread, time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::channel::{self, RecvError};
+
+       #[test]
+       fn test_select_mixed_receivers() {
+              let (oneshot_sender, oneshot_receiver) = channel::oneshot::<&str>();
+              let (_mpsc_sender, mpsc_receiver) = channel::mpsc::<&str>();
+              let (rendezvous_sender, rendezvous_receiver) = channel::rendezvous::<&str>();
+
+              let mut select = Select::new();
+              let oneshot_index = select.recv(&oneshot_receiver);
+              let _mpsc_index = select.recv(&mpsc_receiver);
+              let rendezvous_index = select.recv(&rendezvous_receiver);
+              assert_eq!(select.try_select(), None);
+
+              let producer = thread::spawn(move || {
+                     thread::sleep(Duration::from_millis(10));
+                     rendezvous_sender.send("meet").unwrap();
+                     // hand the sender back, so the rendezvous doesn't read as disconnected below
+                     rendezvous_sender
+              });
+              assert_eq!(select.select(), rendezvous_index);
+              assert_eq!(rendezvous_receiver.recv(), Ok("meet"));
+              let _rendezvous_sender = producer.join().unwrap();
+
+              after_delay(move || oneshot_sender.send("once"));
+              assert_eq!(select.select(), oneshot_index);
+              assert_eq!(oneshot_receiver.recv(), Ok("once"));
+       }
+
+       #[test]
+       fn test_disconnection_counts_as_ready() {
+              let (sender, receiver) = channel::mpsc::<()>();
+              let mut select = Select::new();
+              let index = select.recv(&receiver);
+              after_delay(move || drop(sender));
+              assert_eq!(select.select(), index);
+              assert_eq!(receiver.recv(), Err(RecvError));
+       }
+
+       /// Run `f` on another thread after a short delay, so the selecting thread is (likely) parked by then.
+       fn after_delay(f: impl FnOnce() + Send + 'static) {
+              thread::spawn(move || {
+                     thread::sleep(Duration::from_millis(10));
+                     f();
+              });
+       }
+}
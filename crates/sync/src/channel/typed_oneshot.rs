@@ -0,0 +1,218 @@
+//! One-shot channel whose misuse doesn't compile.
+//!
+//! ## [Chapter 5: Safety Through Types](https://marabos.nl/atomics/building-channels.html#safety-through-types)
+//!
+//! Same storage as [`oneshot`](super::oneshot), but `send` and `receive` take their half *by value*:
+//! - sending twice: the `Sender` was moved into the first `send`
+//! - receiving twice: the `Receiver` was moved into the first `receive`
+//! - receiving before the message is ready: `receive` blocks rather than failing
+//!
+//! So the `WRITING` state and the "already received" panics of the runtime-checked variant go away.
+//!
+//! ```compile_fail
+//! let (sender, _receiver) = sync::channel::typed_oneshot::<i32>();
+//! sender.send(1);
+//! sender.send(2); // use of moved value: `sender`
+//! ```
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! let (sender, receiver) = sync::channel::typed_oneshot();
+//! thread::spawn(move || sender.send("hello"));
+//! assert_eq!(receiver.receive(), Ok("hello"));
+//! ```
+
+use std::{cell::UnsafeCell,
+          fmt,
+          mem::MaybeUninit,
+          sync::Arc,
+          time::{Duration, Instant}};
+
+use super::{RecvError, select::sealed::SelectHandle};
+use crate::{atomic::{AtomicU8,
+                     Ordering::{Acquire, Relaxed, Release}},
+            deadline::{self, park_until},
+            park_slot::ParkSlot};
+
+const EMPTY: u8 = 0;
+const READY: u8 = 1;
+const TAKEN: u8 = 2;
+const DISCONNECTED: u8 = 3;
+
+/// Create a connected one-shot (`Sender`, `Receiver`) pair with by-value halves.
+pub fn typed_oneshot<T>() -> (Sender<T>, Receiver<T>) {
+       let channel = Arc::new(Channel {
+              message: UnsafeCell::new(MaybeUninit::uninit()),
+              state:   AtomicU8::new(EMPTY),
+              waiter:  ParkSlot::new(),
+       });
+       (Sender { channel: channel.clone() }, Receiver { channel })
+}
+
+struct Channel<T> {
+       message: UnsafeCell<MaybeUninit<T>>,
+       state:   AtomicU8,
+       /// Receiving thread, once it has started blocking.
+       waiter:  ParkSlot,
+}
+// SAFETY: the by-value halves guarantee a single write, then (after a release/acquire pair) a single read of `message`.
+unsafe impl<T> Sync for Channel<T> where T: Send {}
+
+impl<T> Drop for Channel<T> {
+       fn drop(&mut self) {
+              // Relaxed (rather than `get_mut`, which loom's atomics lack): `&mut self` means nobody to synchronize with
+              if self.state.load(Relaxed) == READY {
+                     // SAFETY: READY means the message was fully written and not yet taken.
+                     unsafe { self.message.get_mut().assume_init_drop() }
+              }
+       }
+}
+
+/// Sending half of a [`typed_oneshot`] channel.
+pub struct Sender<T> {
+       channel: Arc<Channel<T>>,
+}
+impl<T> Sender<T> {
+       /// Send the message, consuming the sender.
+       pub fn send(self, message: T) {
+              // SAFETY: `self` is the only sender and is consumed here, so this is the only write.
+              unsafe { (*self.channel.message.get()).write(message) };
+              self.channel.state.store(READY, Release);
+              self.channel.waiter.wake();
+       }
+}
+impl<T> Drop for Sender<T> {
+       fn drop(&mut self) {
+              // only matters if nothing was ever sent
+              if self.channel.state.compare_exchange(EMPTY, DISCONNECTED, Relaxed, Relaxed).is_ok() {
+                     self.channel.waiter.wake();
+              }
+       }
+}
+
+/// Receiving half of a [`typed_oneshot`] channel.
+pub struct Receiver<T> {
+       channel: Arc<Channel<T>>,
+}
+impl<T> Receiver<T> {
+       /// Whether a message is waiting to be received.
+       pub fn is_ready(&self) -> bool { self.channel.state.load(Relaxed) == READY }
+
+       /// Block until the message arrives, consuming the receiver.
+       ///
+       /// ## Errors
+       /// If the `Sender` was dropped without sending.
+       pub fn receive(self) -> Result<T, RecvError> {
+              self.receive_deadline_inner(None).map_err(|_| RecvError) // no deadline: only ever `Disconnected`
+       }
+
+       /// As [`receive`](Self::receive), giving up after `timeout`.
+       ///
+       /// On timeout the receiver is handed back, so the wait can be resumed.
+       pub fn receive_timeout(self, timeout: Duration) -> Result<T, ReceiveTimeoutError<T>> {
+              self.receive_deadline_inner(deadline::after(timeout))
+       }
+
+       /// As [`receive`](Self::receive), giving up at `deadline`.
+       ///
+       /// On timeout the receiver is handed back, so the wait can be resumed.
+       pub fn receive_deadline(self, deadline: Instant) -> Result<T, ReceiveTimeoutError<T>> { self.receive_deadline_inner(Some(deadline)) }
+
+       fn receive_deadline_inner(self, deadline: Option<Instant>) -> Result<T, ReceiveTimeoutError<T>> {
+              self.channel.waiter.register();
+              loop {
+                     match self.channel.state.load(Acquire) {
+                            READY => {
+                                   self.channel.state.store(TAKEN, Relaxed);
+                                   // SAFETY: READY (acquired) means the message is fully written;
+                                   //         `self` is the only receiver and is consumed here.
+                                   return Ok(unsafe { (*self.channel.message.get()).assume_init_read() });
+                            }
+                            DISCONNECTED => return Err(ReceiveTimeoutError::Disconnected),
+                            _ => {
+                                   if !park_until(deadline) {
+                                          return Err(ReceiveTimeoutError::Timeout(self));
+                                   }
+                            }
+                     }
+              }
+       }
+}
+
+/// Why a timed [`receive`](Receiver::receive) came back empty-handed.
+pub enum ReceiveTimeoutError<T> {
+       /// Nothing yet; here's the receiver back to try again.
+       Timeout(Receiver<T>),
+       Disconnected,
+}
+impl<T> fmt::Debug for ReceiveTimeoutError<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              match self {
+                     Self::Timeout(_) => write!(f, "Timeout(..)"),
+                     Self::Disconnected => write!(f, "Disconnected"),
+              }
+       }
+}
+impl<T> fmt::Display for ReceiveTimeoutError<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              match self {
+                     Self::Timeout(_) => write!(f, "timed out waiting on a channel"),
+                     Self::Disconnected => write!(f, "receiving on an empty and disconnected channel"),
+              }
+       }
+}
+impl<T> std::error::Error for ReceiveTimeoutError<T> {}
+
+impl<T> SelectHandle for Receiver<T> {
+       fn is_ready(&self) -> bool { matches!(self.channel.state.load(Relaxed), READY | DISCONNECTED) }
+
+       fn watch(&self) { self.channel.waiter.register(); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::test_util::DropCounter;
+
+       #[test]
+       fn test_cross_thread_handoff() {
+              let (sender, receiver) = typed_oneshot();
+              let consumer = thread::spawn(move || receiver.receive());
+              thread::sleep(Duration::from_millis(10)); // let the receiver park first
+              sender.send(String::from("hi"));
+              assert_eq!(consumer.join().unwrap(), Ok(String::from("hi")));
+       }
+
+       #[test]
+       fn test_send_then_drop_drops_message() {
+              let drops = AtomicUsize::new(0);
+              let (sender, receiver) = typed_oneshot();
+              sender.send(DropCounter(&drops));
+              assert!(receiver.is_ready());
+              drop(receiver);
+              assert_eq!(drops.load(Relaxed), 1);
+       }
+
+       #[test]
+       fn test_sender_dropped_without_sending() {
+              let (sender, receiver) = typed_oneshot::<()>();
+              drop(sender);
+              assert_eq!(receiver.receive(), Err(RecvError));
+       }
+
+       #[test]
+       fn test_receive_timeout_hands_receiver_back() {
+              let (sender, receiver) = typed_oneshot();
+              let Err(ReceiveTimeoutError::Timeout(receiver)) = receiver.receive_timeout(Duration::from_millis(5)) else {
+                     panic!("nothing was sent, should time out");
+              };
+              sender.send(3);
+              assert_eq!(receiver.receive_deadline(Instant::now()).ok(), Some(3));
+       }
+}
@@ -0,0 +1,263 @@
+//! Watch channel: one sender publishing a *latest value*, any number of receivers observing it.
+//!
+//! Unlike a queue, intermediate values may be skipped: a receiver that wakes up late sees only the newest one.
+//! That is exactly what a progress display or a config reload wants,
+//! and it replaces "loop { load; compare; sleep }" polling with a blocking [`changed`](Receiver::changed).
+//!
+//! ## Design
+//! - the value sits behind an `RwLock` (readers share, the sender briefly excludes them)
+//! - an `AtomicU32` version counts sends, bumped under the write lock so a reader holding the read lock sees a value
+//!   and its own version; the top bit flags a dropped sender
+//! - receivers remember the version they last saw and futex-wait on the counter until it moves
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! let (sender, mut receiver) = sync::channel::watch::channel(0);
+//! let watcher = thread::spawn(move || {
+//!        while receiver.changed().is_ok() {
+//!               if *receiver.borrow() == 3 {
+//!                      return true;
+//!               }
+//!        }
+//!        false
+//! });
+//! for i in 1..=3 {
+//!        sender.send(i);
+//! }
+//! assert!(watcher.join().unwrap());
+//! ```
+
+use std::{ops::Deref,
+          sync::{Arc, PoisonError, RwLock, RwLockReadGuard},
+          time::{Duration, Instant}};
+
+use super::{RecvError, RecvTimeoutError};
+use crate::{atomic::{AtomicU32, AtomicUsize,
+                     Ordering::{Acquire, Relaxed, Release}},
+            deadline,
+            futex::{wait_until, wake_all}};
+
+/// Set in the version once the sender is dropped.
+const CLOSED: u32 = 1 << 31;
+const VERSION_MASK: u32 = !CLOSED;
+
+/// Create a watch channel holding `initial`.
+pub fn channel<T>(initial: T) -> (Sender<T>, Receiver<T>) {
+       let shared = Arc::new(Shared { value: RwLock::new(initial), version: AtomicU32::new(0), receivers: AtomicUsize::new(1) });
+       (Sender { shared: shared.clone() }, Receiver { shared, seen: 0 })
+}
+
+struct Shared<T> {
+       value:     RwLock<T>,
+       version:   AtomicU32,
+       receivers: AtomicUsize,
+}
+
+/// Read access to the current value. Holds a read lock: keep it short, the sender waits on it.
+pub struct Ref<'a, T> {
+       guard: RwLockReadGuard<'a, T>,
+}
+impl<T> Deref for Ref<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T { &self.guard }
+}
+
+/// Publishing half of a [`watch`](self) channel.
+pub struct Sender<T> {
+       shared: Arc<Shared<T>>,
+}
+impl<T> Sender<T> {
+       /// Replace the value and wake every waiting receiver.
+       ///
+       /// Succeeds even with no receivers left (see [`receiver_count`](Self::receiver_count));
+       /// a watch value is state, not a message that can go undelivered.
+       pub fn send(&self, value: T) { self.send_modify(|current| *current = value); }
+
+       /// Modify the value in place, then notify as for [`send`](Self::send).
+       pub fn send_modify(&self, modify: impl FnOnce(&mut T)) {
+              let mut value = self.shared.value.write().unwrap_or_else(PoisonError::into_inner);
+              modify(&mut value);
+              // bumped before the write lock goes, so whoever reads the version under the read lock reads this value's;
+              // only the sender writes the version, so load + store can't lose an update
+              let version = self.shared.version.load(Relaxed);
+              self.shared.version.store(version.wrapping_add(1) & VERSION_MASK, Release);
+              drop(value);
+              wake_all(&self.shared.version);
+       }
+
+       /// The current value.
+       pub fn borrow(&self) -> Ref<'_, T> { Ref { guard: self.shared.value.read().unwrap_or_else(PoisonError::into_inner) } }
+
+       /// A new receiver, treating the current value as already seen.
+       pub fn subscribe(&self) -> Receiver<T> {
+              self.shared.receivers.fetch_add(1, Relaxed);
+              // under the read lock, as in `Receiver::borrow_and_update`
+              let _value = self.borrow();
+              Receiver { shared: self.shared.clone(), seen: self.shared.version.load(Acquire) & VERSION_MASK }
+       }
+
+       pub fn receiver_count(&self) -> usize { self.shared.receivers.load(Relaxed) }
+}
+impl<T> Drop for Sender<T> {
+       fn drop(&mut self) {
+              self.shared.version.fetch_or(CLOSED, Release);
+              wake_all(&self.shared.version);
+       }
+}
+
+/// Observing half of a [`watch`](self) channel. Clone for more observers.
+pub struct Receiver<T> {
+       shared: Arc<Shared<T>>,
+       /// Version of the value last marked as seen.
+       seen:   u32,
+}
+impl<T> Receiver<T> {
+       /// Block until the value changes from the one last seen, and mark the new one as seen.
+       ///
+       /// ## Errors
+       /// If the sender is gone and the last value it sent has already been seen.
+       pub fn changed(&mut self) -> Result<(), RecvError> {
+              self.changed_deadline_inner(None).map_err(|_| RecvError) // no deadline: only ever `Disconnected`
+       }
+
+       /// As [`changed`](Self::changed), giving up after `timeout`.
+       pub fn changed_timeout(&mut self, timeout: Duration) -> Result<(), RecvTimeoutError> {
+              self.changed_deadline_inner(deadline::after(timeout))
+       }
+
+       /// As [`changed`](Self::changed), giving up at `deadline`.
+       pub fn changed_deadline(&mut self, deadline: Instant) -> Result<(), RecvTimeoutError> { self.changed_deadline_inner(Some(deadline)) }
+
+       fn changed_deadline_inner(&mut self, deadline: Option<Instant>) -> Result<(), RecvTimeoutError> {
+              loop {
+                     let version = self.shared.version.load(Acquire);
+                     if version & VERSION_MASK != self.seen {
+                            self.seen = version & VERSION_MASK;
+                            return Ok(());
+                     }
+                     if version & CLOSED != 0 {
+                            return Err(RecvTimeoutError::Disconnected);
+                     }
+                     if !wait_until(&self.shared.version, version, deadline) {
+                            return Err(RecvTimeoutError::Timeout);
+                     }
+              }
+       }
+
+       /// Whether a value not yet seen is available.
+       pub fn has_changed(&self) -> bool { self.shared.version.load(Acquire) & VERSION_MASK != self.seen }
+
+       /// The current value, without marking it as seen.
+       pub fn borrow(&self) -> Ref<'_, T> { Ref { guard: self.shared.value.read().unwrap_or_else(PoisonError::into_inner) } }
+
+       /// The current value, marking it as seen.
+       pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+              let guard = self.shared.value.read().unwrap_or_else(PoisonError::into_inner);
+              // read under the lock, which `send_modify` bumps the version under, so the two belong together
+              self.seen = self.shared.version.load(Acquire) & VERSION_MASK;
+              Ref { guard }
+       }
+}
+impl<T> Clone for Receiver<T> {
+       fn clone(&self) -> Self {
+              self.shared.receivers.fetch_add(1, Relaxed);
+              Self { shared: self.shared.clone(), seen: self.seen }
+       }
+}
+impl<T> Drop for Receiver<T> {
+       fn drop(&mut self) { self.shared.receivers.fetch_sub(1, Relaxed); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_all_receivers_woken() {
+              let (sender, receiver) = channel("initial");
+              let watchers: Vec<_> = (0..4)
+                     .map(|_| {
+                            let mut receiver = receiver.clone();
+                            thread::spawn(move || {
+                                   receiver.changed().unwrap();
+                                   *receiver.borrow()
+                            })
+                     })
+                     .collect();
+              thread::sleep(Duration::from_millis(10));
+              sender.send("updated");
+              for watcher in watchers {
+                     assert_eq!(watcher.join().unwrap(), "updated");
+              }
+       }
+
+       #[test]
+       fn test_intermediate_values_skipped() {
+              let (sender, mut receiver) = channel(0);
+              assert!(!receiver.has_changed());
+              sender.send(1);
+              sender.send_modify(|n| *n += 1);
+              assert!(receiver.has_changed());
+              assert_eq!(*receiver.borrow_and_update(), 2);
+              assert!(!receiver.has_changed());
+       }
+
+       /// A value `borrow_and_update` returned doesn't come back as a change: the version it marks seen is that value's.
+       #[test]
+       fn test_borrow_and_update_marks_what_it_returns() {
+              const SENDS: u32 = if cfg!(miri) { 50 } else { 100_000 };
+              let (sender, mut receiver) = channel(0);
+              thread::scope(|s| {
+                     s.spawn(|| (0..SENDS).for_each(|_| sender.send_modify(|n| *n += 1)));
+                     loop {
+                            let seen = *receiver.borrow_and_update();
+                            if receiver.has_changed() {
+                                   assert!(*receiver.borrow() > seen, "`changed` would fire again for {seen}, already seen");
+                            }
+                            if seen == SENDS {
+                                   break;
+                            }
+                     }
+              });
+       }
+
+       #[test]
+       fn test_sender_drop_ends_watch() {
+              let (sender, mut receiver) = channel(0);
+              sender.send(1);
+              drop(sender);
+              // the last value is still delivered...
+              assert_eq!(receiver.changed(), Ok(()));
+              assert_eq!(*receiver.borrow(), 1);
+              // ...then the watch is over
+              assert_eq!(receiver.changed(), Err(RecvError));
+       }
+
+       #[test]
+       fn test_changed_timeout() {
+              let (sender, mut receiver) = channel(0);
+              assert_eq!(receiver.changed_timeout(Duration::from_millis(5)), Err(RecvTimeoutError::Timeout));
+              sender.send(1);
+              assert_eq!(receiver.changed_deadline(Instant::now()), Ok(()));
+              drop(sender);
+              assert_eq!(receiver.changed_timeout(Duration::from_secs(60)), Err(RecvTimeoutError::Disconnected));
+       }
+
+       #[test]
+       fn test_subscribe_and_count() {
+              let (sender, receiver) = channel(());
+              assert_eq!(sender.receiver_count(), 1);
+              let late = sender.subscribe();
+              assert_eq!(sender.receiver_count(), 2);
+              assert!(!late.has_changed());
+              drop((receiver, late));
+              assert_eq!(sender.receiver_count(), 0);
+       }
+}
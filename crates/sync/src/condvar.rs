@@ -0,0 +1,267 @@
+//! Futex-based condition variable, paired with this crate's [`Mutex`].
+//!
+//! ## [Chapter 9: Building Our Own Locks — Condition Variable](https://marabos.nl/atomics/building-locks.html#condition-variables)
+//!
+//! A waiter can't atomically "unlock the mutex and sleep". So `counter` is bumped by every notify:
+//! the waiter reads it *before* unlocking and futex-waits on that value,
+//! so a notify landing in between changes the counter and the wait returns at once instead of missing it.
+//!
+//! `num_waiters` lets `notify_*` skip the syscall when nobody waits.
+//! As with any condvar, wakeups may be spurious: wait in a loop re-checking the condition, or let
+//! [`wait_while`](Condvar::wait_while) / [`wait_timeout_while`](Condvar::wait_timeout_while) (or
+//! [`Mutex::lock_when`](crate::Mutex::lock_when)) run that loop.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::{Condvar, Mutex};
+//!
+//! let ready = Mutex::new(false);
+//! let condvar = Condvar::new();
+//! thread::scope(|s| {
+//!        s.spawn(|| {
+//!               *ready.lock() = true;
+//!               condvar.notify_one();
+//!        });
+//!        let guard = condvar.wait_while(ready.lock(), |ready| !*ready);
+//!        assert!(*guard);
+//! });
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::{MutexGuard,
+            atomic::{AtomicU32, AtomicUsize, Ordering::Relaxed, loom_const_fn},
+            deadline,
+            futex::{wait, wait_until, wake_all, wake_one}};
+
+/// Wait for a condition protected by a [`Mutex`](crate::Mutex) to change.
+#[derive(Default)]
+pub struct Condvar {
+       counter:     AtomicU32,
+       num_waiters: AtomicUsize,
+}
+
+/// Whether [`Condvar::wait_timeout`] (or `wait_timeout_while`) returned because the time ran out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitTimeoutResult(bool);
+impl WaitTimeoutResult {
+       pub fn timed_out(&self) -> bool { self.0 }
+}
+
+impl Condvar {
+       loom_const_fn! {
+              pub fn new() -> Self { Self { counter: AtomicU32::new(0), num_waiters: AtomicUsize::new(0) } }
+       }
+
+       /// Wake one waiting thread, if any.
+       pub fn notify_one(&self) {
+              if self.num_waiters.load(Relaxed) > 0 {
+                     self.counter.fetch_add(1, Relaxed);
+                     wake_one(&self.counter);
+              }
+       }
+
+       /// Wake every waiting thread.
+       pub fn notify_all(&self) {
+              if self.num_waiters.load(Relaxed) > 0 {
+                     self.counter.fetch_add(1, Relaxed);
+                     wake_all(&self.counter);
+              }
+       }
+
+       /// Unlock, sleep until notified (or spuriously woken), re-lock.
+       pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+              // the mutex orders this increment before any notify that reads `num_waiters` after we unlock
+              self.num_waiters.fetch_add(1, Relaxed);
+              let counter_value = self.counter.load(Relaxed);
+              let mutex = guard.mutex();
+              drop(guard);
+              wait(&self.counter, counter_value);
+              self.num_waiters.fetch_sub(1, Relaxed);
+              mutex.lock()
+       }
+
+       /// As [`wait`](Self::wait), giving up after `timeout`.
+       pub fn wait_timeout<'a, T>(&self, guard: MutexGuard<'a, T>, timeout: Duration) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+              self.wait_deadline(guard, deadline::after(timeout))
+       }
+
+       /// As [`wait`](Self::wait), giving up at `deadline`.
+       ///
+       /// Loops on one absolute deadline, so a caller's own predicate loop can pass the same `Instant` every
+       /// time round without stretching the total wait.
+       pub fn wait_until<'a, T>(&self, guard: MutexGuard<'a, T>, deadline: Instant) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+              self.wait_deadline(guard, Some(deadline))
+       }
+
+       /// Wait while `condition` holds of the value, re-checking it after every wakeup (spurious or not); returns with
+       /// the lock held and the condition false.
+       pub fn wait_while<'a, T>(&self, mut guard: MutexGuard<'a, T>, mut condition: impl FnMut(&mut T) -> bool) -> MutexGuard<'a, T> {
+              while condition(&mut guard) {
+                     guard = self.wait(guard);
+              }
+              guard
+       }
+
+       /// As [`wait_while`](Self::wait_while), giving up after `timeout` in all: timed out only if the condition still
+       /// holds at the end.
+       pub fn wait_timeout_while<'a, T>(
+              &self,
+              mut guard: MutexGuard<'a, T>,
+              timeout: Duration,
+              mut condition: impl FnMut(&mut T) -> bool,
+       ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+              let deadline = deadline::after(timeout);
+              while condition(&mut guard) {
+                     let (next, result) = self.wait_deadline(guard, deadline);
+                     guard = next;
+                     if result.timed_out() {
+                            let timed_out = condition(&mut guard);
+                            return (guard, WaitTimeoutResult(timed_out));
+                     }
+              }
+              (guard, WaitTimeoutResult(false))
+       }
+
+       fn wait_deadline<'a, T>(&self, guard: MutexGuard<'a, T>, deadline: Option<Instant>) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+              self.num_waiters.fetch_add(1, Relaxed);
+              let counter_value = self.counter.load(Relaxed);
+              let mutex = guard.mutex();
+              drop(guard);
+              let mut timed_out = false;
+              // a timed futex wait may also return early (EINTR); keep waiting until notified or out of time
+              while self.counter.load(Relaxed) == counter_value {
+                     if !wait_until(&self.counter, counter_value, deadline) {
+                            timed_out = true;
+                            break;
+                     }
+              }
+              self.num_waiters.fetch_sub(1, Relaxed);
+              (mutex.lock(), WaitTimeoutResult(timed_out))
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{collections::VecDeque, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::Mutex;
+
+       /// The condvar half of the `atomics-demos ch1 parking` example, with this crate's types.
+       #[test]
+       fn test_producer_consumer() {
+              const END_VALUE: usize = 100;
+              let queue = Mutex::new(VecDeque::new());
+              let not_empty = Condvar::new();
+              let received = thread::scope(|s| {
+                     let consumer = s.spawn(|| {
+                            let mut received = Vec::new();
+                            loop {
+                                   let item = not_empty.wait_while(queue.lock(), |q| q.is_empty()).pop_front().unwrap();
+                                   received.push(item);
+                                   if item == END_VALUE {
+                                          break received;
+                                   }
+                            }
+                     });
+                     for i in 0..=END_VALUE {
+                            queue.lock().push_back(i);
+                            not_empty.notify_one();
+                     }
+                     consumer.join().unwrap()
+              });
+              assert_eq!(received, (0..=END_VALUE).collect::<Vec<_>>());
+       }
+
+       #[test]
+       fn test_notify_all_wakes_everyone() {
+              let go = Mutex::new(false);
+              let condvar = Condvar::new();
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   let mut guard = go.lock();
+                                   while !*guard {
+                                          guard = condvar.wait(guard);
+                                   }
+                            });
+                     }
+                     thread::sleep(Duration::from_millis(10));
+                     *go.lock() = true;
+                     condvar.notify_all();
+              });
+       }
+
+       #[test]
+       fn test_wait_timeout() {
+              let mutex = Mutex::new(0);
+              let condvar = Condvar::new();
+              let (guard, result) = condvar.wait_timeout(mutex.lock(), Duration::from_millis(5));
+              assert!(result.timed_out());
+              drop(guard);
+
+              thread::scope(|s| {
+                     let guard = mutex.lock();
+                     s.spawn(|| {
+                            *mutex.lock() = 1;
+                            condvar.notify_one();
+                     });
+                     let (guard, result) = condvar.wait_timeout(guard, Duration::from_secs(60));
+                     assert!(!result.timed_out());
+                     assert_eq!(*guard, 1);
+              });
+       }
+
+       #[test]
+       fn test_wait_timeout_while() {
+              let count = Mutex::new(0);
+              let condvar = Condvar::new();
+              let (guard, result) = condvar.wait_timeout_while(count.lock(), Duration::from_millis(5), |count| *count < 3);
+              assert!(result.timed_out());
+              drop(guard);
+
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            for _ in 0..3 {
+                                   *count.lock() += 1;
+                                   condvar.notify_one(); // the first two wake the waiter to a still-false condition
+                            }
+                     });
+                     let (guard, result) = condvar.wait_timeout_while(count.lock(), Duration::from_secs(60), |count| *count < 3);
+                     assert!(!result.timed_out());
+                     assert_eq!(*guard, 3);
+              });
+       }
+
+       #[test]
+       fn test_wait_until_keeps_one_deadline() {
+              let mutex = Mutex::new(false);
+              let condvar = Condvar::new();
+              let timeout = Duration::from_millis(30);
+              let deadline = Instant::now() + timeout;
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            for _ in 0..5 {
+                                   thread::sleep(Duration::from_millis(5));
+                                   condvar.notify_all(); // wakes without the condition changing
+                            }
+                     });
+                     let mut guard = mutex.lock();
+                     while !*guard {
+                            let (next, result) = condvar.wait_until(guard, deadline);
+                            guard = next;
+                            if result.timed_out() {
+                                   break;
+                            }
+                     }
+                     assert!(!*guard);
+              });
+              let overshoot = Instant::now() - deadline;
+              assert!(overshoot < Duration::from_secs(1), "gave up at the deadline, not 5 timeouts later");
+       }
+}
@@ -0,0 +1,129 @@
+//! Deadline math shared by every blocking call with a timeout.
+//!
+//! Timed calls convert their `Duration` to an absolute deadline once, up front, and loop on that:
+//! a relative timeout re-armed after each spurious wakeup would stretch the total wait a little every time.
+//! `Option<Instant>` throughout: `None` means "no deadline", which is also what a timeout too large
+//! for `Instant` becomes (rather than panicking on the addition).
+//!
+//! - [`after`]: `now + timeout`, saturating to "no deadline"
+//! - [`remaining`]: time left, or `None` once it's up (a zero remainder counts as up, so a wait can't spin on it)
+//! - [`park_until`]: one park, which may return early; for loops that re-check their own state
+//! - [`park_while`]: the whole loop, for conditions a closure can re-check
+//!
+//! The futex waits ([`Mutex`](crate::Mutex), [`Condvar::wait_until`](crate::Condvar::wait_until), ...) use the same
+//! helpers internally.
+//!
+//! ## Example
+//! ```
+//! use std::{sync::atomic::{AtomicBool, Ordering::{Acquire, Release}},
+//!           thread,
+//!           time::Duration};
+//!
+//! use sync::deadline;
+//!
+//! let done = AtomicBool::new(false);
+//! thread::scope(|s| {
+//!        let waiter = s.spawn(|| deadline::park_while(|| !done.load(Acquire), deadline::after(Duration::from_secs(10))));
+//!        done.store(true, Release);
+//!        waiter.thread().unpark();
+//!        assert!(waiter.join().unwrap());
+//! });
+//!
+//! assert!(!deadline::park_while(|| true, deadline::after(Duration::from_millis(1))), "timed out");
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::atomic::{park, park_timeout};
+
+/// `now + timeout`, or no deadline at all if that overflows `Instant`.
+pub fn after(timeout: Duration) -> Option<Instant> { Instant::now().checked_add(timeout) }
+
+/// How long until `deadline`; `None` once it has passed (or is exactly now).
+pub fn remaining(deadline: Instant) -> Option<Duration> {
+       deadline.checked_duration_since(Instant::now()).filter(|remaining| !remaining.is_zero())
+}
+
+/// Park the current thread, giving up at `deadline` (if any).
+///
+/// Returns `false` if the deadline has passed, so callers can report a timeout.
+/// May return early (spurious wakeups); callers re-check their condition in a loop.
+pub fn park_until(deadline: Option<Instant>) -> bool {
+       match deadline {
+              None => {
+                     park();
+                     true
+              }
+              Some(deadline) => match remaining(deadline) {
+                     Some(remaining) => {
+                            park_timeout(remaining);
+                            true
+                     }
+                     None => false,
+              },
+       }
+}
+
+/// Park while `blocked()` holds, until `deadline` (if any); returns `true` once it doesn't, `false` on timeout.
+///
+/// `blocked` is checked before every park and once more when time runs out, so a wake racing the deadline
+/// still counts. Whoever makes `blocked()` false must then unpark this thread.
+pub fn park_while(mut blocked: impl FnMut() -> bool, deadline: Option<Instant>) -> bool {
+       while blocked() {
+              if !park_until(deadline) {
+                     return !blocked();
+              }
+       }
+       true
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::{AtomicBool,
+                                Ordering::{Acquire, Release}},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_deadline_math() {
+              assert_eq!(after(Duration::MAX), None);
+              assert!(after(Duration::ZERO).is_some());
+              assert_eq!(remaining(Instant::now()), None);
+              assert!(remaining(Instant::now() + Duration::from_secs(60)).unwrap() > Duration::from_secs(59));
+       }
+
+       #[test]
+       fn test_park_until_past_deadline_returns_at_once() {
+              assert!(!park_until(Some(Instant::now())));
+       }
+
+       #[test]
+       fn test_park_while_waits_out_spurious_wakes() {
+              let timeout = Duration::from_millis(20);
+              let start = Instant::now();
+              let waker = thread::current();
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            for _ in 0..5 {
+                                   waker.unpark(); // wakes without a change: must park again
+                            }
+                     });
+                     assert!(!park_while(|| true, after(timeout)));
+              });
+              assert!(start.elapsed() >= timeout);
+       }
+
+       #[test]
+       fn test_park_while_returns_when_unblocked() {
+              let done = AtomicBool::new(false);
+              thread::scope(|s| {
+                     let waiter = s.spawn(|| park_while(|| !done.load(Acquire), None));
+                     done.store(true, Release);
+                     waiter.thread().unpark();
+                     assert!(waiter.join().unwrap());
+              });
+       }
+}
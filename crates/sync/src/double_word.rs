@@ -0,0 +1,159 @@
+//! Double-word compare-and-swap: two `usize`s swapped as one.
+//!
+//! A pointer plus a tag (or counter) swapped together is the classic ABA fix for lock-free structures,
+//! but a 2×64-bit CAS isn't available everywhere: [`AtomicDoubleWord`] uses x86_64's `cmpxchg16b` when the CPU has it
+//! (checked once at runtime), and falls back to the striped locks [`AtomicCell`](crate::AtomicCell) uses otherwise
+//! (other architectures, Miri). [`AtomicDoubleWord::is_lock_free`] says which;
+//! [`atomic_support`](crate::atomic_support) reports it alongside the native widths.
+//!
+//! ## Design
+//! - `#[repr(align(16))]`: `cmpxchg16b` faults on unaligned operands
+//! - `load` and `store` are CAS loops on the native path (there is no plain 128-bit atomic load on x86_64)
+//! - `lock cmpxchg16b` is a full barrier; the fallback orders like a lock (Acquire on entry, Release on exit):
+//!   rely on AcqRel
+//!
+//! ## Example
+//! ```
+//! use sync::AtomicDoubleWord;
+//!
+//! // pointer-ish value + a version tag, bumped on every change so a recycled value doesn't fool a CAS
+//! let slot = AtomicDoubleWord::new([0x1000, 0]);
+//! let [value, tag] = slot.load();
+//! assert_eq!(slot.compare_exchange([value, tag], [0x2000, tag + 1]), Ok([0x1000, 0]));
+//! assert_eq!(slot.compare_exchange([0x1000, 0], [0x1000, 9]), Err([0x2000, 1]));
+//! ```
+
+use std::{cell::UnsafeCell, fmt};
+
+use crate::atomic_cell::lock_for;
+
+/// Two words updated together atomically.
+#[repr(C, align(16))]
+pub struct AtomicDoubleWord {
+       words: UnsafeCell<[usize; 2]>,
+}
+// SAFETY: every shared access is a `cmpxchg16b` or happens under the striped lock for this address (never both:
+// the path is fixed per process).
+unsafe impl Sync for AtomicDoubleWord {}
+
+impl AtomicDoubleWord {
+       pub const fn new(words: [usize; 2]) -> Self { Self { words: UnsafeCell::new(words) } }
+
+       /// Whether this process uses the native double-word CAS (the same answer for every instance).
+       pub fn is_lock_free() -> bool {
+              #[cfg(all(target_arch = "x86_64", not(miri)))]
+              return std::arch::is_x86_feature_detected!("cmpxchg16b");
+              #[cfg(not(all(target_arch = "x86_64", not(miri))))]
+              return false;
+       }
+
+       pub fn load(&self) -> [usize; 2] {
+              match self.compare_exchange([0, 0], [0, 0]) {
+                     Ok(words) | Err(words) => words,
+              }
+       }
+
+       pub fn store(&self, words: [usize; 2]) { self.swap(words); }
+
+       /// Store `words`, returning the previous pair.
+       pub fn swap(&self, words: [usize; 2]) -> [usize; 2] {
+              let mut current = self.load();
+              loop {
+                     match self.compare_exchange(current, words) {
+                            Ok(previous) => return previous,
+                            Err(observed) => current = observed,
+                     }
+              }
+       }
+
+       /// Replace both words with `new` if both equal `current`; `Err` carries the pair found otherwise.
+       pub fn compare_exchange(&self, current: [usize; 2], new: [usize; 2]) -> Result<[usize; 2], [usize; 2]> {
+              #[cfg(all(target_arch = "x86_64", not(miri)))]
+              if Self::is_lock_free() {
+                     // SAFETY: the CPU has `cmpxchg16b`, `words` is 16-aligned, and every access to it is atomic.
+                     let (previous, swapped) = unsafe { cmpxchg16b(self.words.get(), current, new) };
+                     return if swapped { Ok(previous) } else { Err(previous) };
+              }
+              let _guard = lock_for(self.words.get());
+              // SAFETY: without `cmpxchg16b` every access holds this address's lock.
+              let words = unsafe { &mut *self.words.get() };
+              if *words == current { Ok(std::mem::replace(words, new)) } else { Err(*words) }
+       }
+
+       pub fn get_mut(&mut self) -> &mut [usize; 2] { self.words.get_mut() }
+
+       pub fn into_inner(self) -> [usize; 2] { self.words.into_inner() }
+}
+
+/// `lock cmpxchg16b`: returns the previous value and whether it matched `current` (and was replaced).
+///
+/// ## Safety
+/// The CPU supports `cmpxchg16b`; `destination` is valid, 16-aligned, and only accessed atomically.
+#[cfg(all(target_arch = "x86_64", not(miri)))]
+unsafe fn cmpxchg16b(destination: *mut [usize; 2], current: [usize; 2], new: [usize; 2]) -> ([usize; 2], bool) {
+       let (previous_low, previous_high, swapped): (usize, usize, u8);
+       // SAFETY: per the caller. `rbx` is reserved by LLVM, so `new`'s low word goes in through a scratch register
+       // and is swapped in (and `rbx` restored) around the instruction.
+       unsafe {
+              std::arch::asm!(
+                     "xchg {new_low}, rbx",
+                     "lock cmpxchg16b xmmword ptr [{destination}]",
+                     "setz {swapped}",
+                     "mov rbx, {new_low}",
+                     destination = in(reg) destination,
+                     new_low = inout(reg) new[0] => _,
+                     swapped = out(reg_byte) swapped,
+                     in("rcx") new[1],
+                     inout("rax") current[0] => previous_low,
+                     inout("rdx") current[1] => previous_high,
+                     options(nostack),
+              );
+       }
+       ([previous_low, previous_high], swapped != 0)
+}
+
+impl Default for AtomicDoubleWord {
+       fn default() -> Self { Self::new([0, 0]) }
+}
+
+impl fmt::Debug for AtomicDoubleWord {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_tuple("AtomicDoubleWord").field(&self.load()).finish() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_compare_exchange_needs_both_words() {
+              let cell = AtomicDoubleWord::new([1, 2]);
+              assert_eq!(cell.compare_exchange([1, 3], [9, 9]), Err([1, 2]));
+              assert_eq!(cell.compare_exchange([0, 2], [9, 9]), Err([1, 2]));
+              assert_eq!(cell.compare_exchange([1, 2], [3, usize::MAX]), Ok([1, 2]));
+              assert_eq!(cell.swap([0, 0]), [3, usize::MAX]);
+              assert_eq!(format!("{cell:?}"), "AtomicDoubleWord([0, 0])");
+              assert_eq!(cell.into_inner(), [0, 0]);
+       }
+
+       #[test]
+       fn test_words_move_together() {
+              let cell = AtomicDoubleWord::default();
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   for _ in 0..1_000 {
+                                          let mut current = cell.load();
+                                          while let Err(observed) = cell.compare_exchange(current, [current[0] + 1, current[1] + 2]) {
+                                                 current = observed;
+                                          }
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(cell.load(), [4_000, 8_000]);
+       }
+}
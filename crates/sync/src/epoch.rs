@@ -0,0 +1,241 @@
+//! Epoch-based reclamation: the other way to free nodes of lock-free structures safely.
+//!
+//! Instead of announcing *each pointer* it reads (as with [hazard pointers](crate::hazard)), a thread announces
+//! that it is inside a critical section: it [`pin`]s itself to the current global epoch.
+//! Anything unlinked gets [deferred](Guard::defer_destroy) into the thread's bag, tagged with the epoch at the time.
+//! - the global epoch only advances once every *pinned* thread has caught up with it
+//! - so two advances after a node was unlinked, every thread that could have seen it has unpinned since,
+//!   and the node can be freed
+//!
+//! Pinning is cheap (one fence, however many pointers are read while pinned), but a thread that stays pinned
+//! stalls reclamation for everyone: keep [`Guard`]s short-lived.
+//!
+//! ## Design
+//! - participants (one per thread that ever pinned) live in a grow-only registry, reused after a thread exits
+//! - a participant's state is its pinned epoch with the low bit set, or 0 when unpinned;
+//!   epochs count in twos to keep that bit free
+//! - every [`COLLECT_THRESHOLD`] deferrals a thread tries to advance the epoch and frees its expired garbage;
+//!   garbage left when a thread exits goes to a global orphan list that later collections pick up
+//!
+//! Same fence pairing as the hazard scheme: a pinning thread stores its epoch then fences before reading shared
+//! pointers; an advancing thread fences before reading the participants.
+//!
+//! ## Example
+//! ```
+//! use std::sync::atomic::{AtomicPtr, Ordering::{Acquire, SeqCst}};
+//!
+//! use sync::epoch;
+//!
+//! let shared = AtomicPtr::new(Box::into_raw(Box::new(1)));
+//! let guard = epoch::pin();
+//! let current = shared.load(Acquire);
+//!
+//! let old = shared.swap(Box::into_raw(Box::new(2)), SeqCst);
+//! // SAFETY: `old` came from `Box::into_raw` and is no longer reachable through `shared`.
+//! unsafe { guard.defer_destroy(old) };
+//! epoch::collect();
+//!
+//! // SAFETY: we are still pinned, so nothing deferred since can have been freed.
+//! assert_eq!(unsafe { *current }, 1);
+//! # drop(guard);
+//! # drop(unsafe { Box::from_raw(shared.into_inner()) });
+//! ```
+
+use std::{cell::{Cell, RefCell},
+          fmt,
+          marker::PhantomData,
+          mem};
+
+use crate::{Mutex,
+            atomic::{AtomicUsize,
+                     Ordering::{Acquire, Relaxed, Release, SeqCst},
+                     fence},
+            reclaim::{Entry, Registry, Retired, free_all}};
+
+/// Deferrals between attempts to advance the epoch and free expired garbage.
+pub const COLLECT_THRESHOLD: usize = 64;
+
+const PINNED: usize = 1;
+/// Epochs step by this, keeping the low bit free for [`PINNED`].
+const STEP: usize = 2;
+
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+
+/// Each participant's state: `epoch | PINNED`, or 0.
+static PARTICIPANTS: Registry<AtomicUsize> = Registry::new();
+
+/// Garbage from threads that exited before it expired.
+static ORPHANS: Mutex<Vec<Deferred>> = Mutex::new(Vec::new());
+
+thread_local! {
+       static LOCAL: Local = Local {
+              participant: PARTICIPANTS.claim(|| AtomicUsize::new(0)),
+              guards:      Cell::new(0),
+              bag:         RefCell::new(Vec::new()),
+       };
+}
+
+struct Local {
+       participant: &'static Entry<AtomicUsize>,
+       /// Live guards on this thread; pinned while non-zero.
+       guards:      Cell<usize>,
+       bag:         RefCell<Vec<Deferred>>,
+}
+
+struct Deferred {
+       epoch:   usize,
+       retired: Retired,
+}
+
+impl Deferred {
+       fn is_expired(&self, epoch: usize) -> bool { epoch.wrapping_sub(self.epoch) >= 2 * STEP }
+}
+
+/// Proof that this thread is pinned: pointers loaded while it lives stay valid until it drops.
+pub struct Guard {
+       /// Pinning is per thread.
+       not_send: PhantomData<*mut ()>,
+}
+
+/// Pin this thread (re-entrant: nested guards just count up).
+///
+/// ## Panics
+/// If called while this thread's thread-locals are being torn down.
+pub fn pin() -> Guard {
+       LOCAL.with(|local| {
+              let guards = local.guards.get();
+              local.guards.set(guards.checked_add(1).expect("guard count overflowed"));
+              if guards == 0 {
+                     local.participant.store(EPOCH.load(Relaxed) | PINNED, Relaxed);
+                     fence(SeqCst); // announce the pin before reading any shared pointer; pairs with `try_advance`
+              }
+       });
+       Guard { not_send: PhantomData }
+}
+
+impl Guard {
+       /// Free `pointer` once every thread pinned now (or earlier) has unpinned.
+       ///
+       /// ## Safety
+       /// - `pointer` came from `Box::into_raw` and is not deferred (or freed) twice
+       /// - it is unreachable: no thread can newly load it from the shared structure
+       pub unsafe fn defer_destroy<T: Send>(&self, pointer: *mut T) {
+              fence(SeqCst); // the unlink happens before reading the epoch it's tagged with
+              let deferred = Deferred { epoch: EPOCH.load(Relaxed), retired: Retired::new(pointer) };
+              let bag_full = LOCAL.with(|local| {
+                     let mut bag = local.bag.borrow_mut();
+                     bag.push(deferred);
+                     bag.len().is_multiple_of(COLLECT_THRESHOLD)
+              });
+              if bag_full {
+                     collect();
+              }
+       }
+}
+
+impl Drop for Guard {
+       fn drop(&mut self) {
+              LOCAL.with(|local| {
+                     let guards = local.guards.get() - 1;
+                     local.guards.set(guards);
+                     if guards == 0 {
+                            local.participant.store(0, Release); // our reads are done before anyone sees us unpinned
+                     }
+              });
+       }
+}
+
+impl fmt::Debug for Guard {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_struct("Guard").finish_non_exhaustive() }
+}
+
+/// Try to advance the epoch, then free this thread's (and any orphaned) garbage that has expired.
+pub fn collect() {
+       let epoch = try_advance();
+       let mut freeable: Vec<Retired> = LOCAL.with(|local| take_expired(&mut local.bag.borrow_mut(), epoch));
+       freeable.extend(take_expired(&mut ORPHANS.lock(), epoch));
+       free_all(freeable);
+}
+
+/// Advance the epoch if every pinned participant is in it; returns the epoch afterwards.
+fn try_advance() -> usize {
+       let epoch = EPOCH.load(Relaxed);
+       fence(SeqCst); // pairs with `pin`: either we see the participant pinned, or it sees the epoch we read (or later)
+       if PARTICIPANTS.entries().any(|participant| {
+              let state = participant.load(Relaxed);
+              state & PINNED != 0 && state & !PINNED != epoch
+       }) {
+              return epoch;
+       }
+       fence(Acquire); // the lagging participants' reads (before their Release unpin) happen before any freeing
+       match EPOCH.compare_exchange(epoch, epoch.wrapping_add(STEP), Release, Relaxed) {
+              Ok(_) => epoch.wrapping_add(STEP),
+              Err(current) => current,
+       }
+}
+
+fn take_expired(deferred: &mut Vec<Deferred>, epoch: usize) -> Vec<Retired> {
+       let (expired, pending) = mem::take(deferred).into_iter().partition(|deferred: &Deferred| deferred.is_expired(epoch));
+       *deferred = pending;
+       expired.into_iter().map(|deferred| deferred.retired).collect()
+}
+
+impl Drop for Local {
+       fn drop(&mut self) {
+              let epoch = try_advance();
+              let freeable = take_expired(self.bag.get_mut(), epoch);
+              ORPHANS.lock().append(self.bag.get_mut());
+              self.participant.store(0, Release);
+              self.participant.release();
+              free_all(freeable);
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{ptr,
+                 sync::atomic::{AtomicPtr, Ordering::Relaxed},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::test_util::DropCounter;
+
+       #[test]
+       fn test_pinned_thread_holds_back_reclamation() {
+              static DROPS: AtomicUsize = AtomicUsize::new(0);
+              let shared = AtomicPtr::new(Box::into_raw(Box::new(DropCounter(&DROPS))));
+              let reader = pin();
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            let guard = pin();
+                            let old = shared.swap(ptr::null_mut(), SeqCst);
+                            // SAFETY: from `Box::into_raw`, and now unreachable.
+                            unsafe { guard.defer_destroy(old) };
+                            drop(guard);
+                            for _ in 0..4 {
+                                   collect();
+                            }
+                     });
+              });
+              assert_eq!(DROPS.load(Relaxed), 0, "our pin keeps the epoch from advancing past it");
+              drop(reader);
+              // orphaned by the exiting thread; freed by our collections (or a concurrent test's)
+              while DROPS.load(Relaxed) == 0 {
+                     collect();
+                     thread::yield_now();
+              }
+              assert_eq!(DROPS.load(Relaxed), 1);
+       }
+
+       #[test]
+       fn test_nested_guards() {
+              let outer = pin();
+              let inner = pin();
+              drop(outer);
+              LOCAL.with(|local| assert_eq!(local.participant.load(Relaxed) & PINNED, PINNED));
+              drop(inner);
+              LOCAL.with(|local| assert_eq!(local.participant.load(Relaxed), 0));
+       }
+}
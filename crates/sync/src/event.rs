@@ -0,0 +1,157 @@
+//! Event: a settable flag threads can block on, in Windows' manual-reset and auto-reset flavours.
+//!
+//! - manual-reset: [`set`](Event::set) releases every waiter, now and later, until [`reset`](Event::reset)
+//! - auto-reset: each `set` releases exactly one waiter (now, or the next to arrive), which resets it on the way out;
+//!   setting an already-set event does nothing (signals don't stack, unlike a [`Semaphore`](crate::Semaphore))
+//!
+//! One futex word: 0 unset, 1 set. Manual waiters just wait for 1; auto waiters must swap it back to 0 to pass.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::Event;
+//!
+//! let started = Event::manual_reset(false);
+//! thread::scope(|s| {
+//!        for _ in 0..3 {
+//!               s.spawn(|| started.wait());
+//!        }
+//!        started.set(); // releases all three
+//! });
+//!
+//! let turn = Event::auto_reset(true);
+//! turn.wait(); // consumes the set
+//! assert!(!turn.is_set());
+//! ```
+
+use std::{fmt,
+          time::{Duration, Instant}};
+
+use crate::{atomic::{AtomicU32,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn},
+            deadline,
+            futex::{wait_until, wake_all, wake_one}};
+
+const UNSET: u32 = 0;
+const SET: u32 = 1;
+
+/// A flag to wait on; see the [module docs](self) for the two flavours.
+pub struct Event {
+       state:      AtomicU32,
+       auto_reset: bool,
+}
+
+impl Event {
+       loom_const_fn! {
+              /// Stays set, releasing all waiters, until [`reset`](Self::reset).
+              pub fn manual_reset(set: bool) -> Self { Self { state: AtomicU32::new(set as u32), auto_reset: false } }
+       }
+
+       loom_const_fn! {
+              /// Each set releases one waiter, which resets it.
+              pub fn auto_reset(set: bool) -> Self { Self { state: AtomicU32::new(set as u32), auto_reset: true } }
+       }
+
+       pub fn is_auto_reset(&self) -> bool { self.auto_reset }
+
+       /// Set the event; Release, so the setter's prior writes are visible to whoever it releases.
+       pub fn set(&self) {
+              if self.state.swap(SET, Release) == UNSET {
+                     if self.auto_reset {
+                            wake_one(&self.state);
+                     } else {
+                            wake_all(&self.state);
+                     }
+              }
+       }
+
+       pub fn reset(&self) { self.state.store(UNSET, Relaxed); }
+
+       /// Only a snapshot.
+       pub fn is_set(&self) -> bool { self.state.load(Relaxed) == SET }
+
+       /// Block until set (and, for auto-reset, claim and reset it).
+       pub fn wait(&self) { self.wait_until(None); }
+
+       /// As [`wait`](Self::wait), giving up after `timeout`; returns whether the event was (claimed) set.
+       pub fn wait_timeout(&self, timeout: Duration) -> bool { self.wait_until(deadline::after(timeout)) }
+
+       fn wait_until(&self, deadline: Option<Instant>) -> bool {
+              loop {
+                     if self.try_pass() {
+                            return true;
+                     }
+                     if !wait_until(&self.state, UNSET, deadline) {
+                            return self.try_pass();
+                     }
+              }
+       }
+
+       /// Pass if set: Acquire pairs with `set`'s Release.
+       fn try_pass(&self) -> bool {
+              if self.auto_reset {
+                     self.state.compare_exchange(SET, UNSET, Acquire, Relaxed).is_ok()
+              } else {
+                     self.state.load(Acquire) == SET
+              }
+       }
+}
+
+impl fmt::Debug for Event {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("Event").field("set", &self.is_set()).field("auto_reset", &self.auto_reset).finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_manual_reset_stays_set() {
+              let event = Event::manual_reset(false);
+              assert!(!event.wait_timeout(Duration::from_millis(1)));
+              event.set();
+              event.wait();
+              assert!(event.wait_timeout(Duration::ZERO));
+              event.reset();
+              assert!(!event.is_set());
+              assert_eq!(format!("{event:?}"), "Event { set: false, auto_reset: false }");
+       }
+
+       #[test]
+       fn test_auto_reset_releases_one_per_set() {
+              let event = Event::auto_reset(false);
+              let passed = AtomicUsize::new(0);
+              thread::scope(|s| {
+                     for _ in 0..3 {
+                            s.spawn(|| {
+                                   if event.wait_timeout(Duration::from_millis(200)) {
+                                          passed.fetch_add(1, Relaxed);
+                                   }
+                            });
+                     }
+                     for _ in 0..2 {
+                            thread::sleep(Duration::from_millis(10));
+                            event.set();
+                     }
+              });
+              assert_eq!(passed.into_inner(), 2);
+              assert!(!event.is_set());
+       }
+
+       #[test]
+       fn test_auto_reset_doesnt_stack() {
+              let event = Event::auto_reset(false);
+              event.set();
+              event.set();
+              assert!(event.wait_timeout(Duration::ZERO));
+              assert!(!event.wait_timeout(Duration::ZERO));
+       }
+}
@@ -0,0 +1,169 @@
+//! Synchronizing through `fence`s rather than through the ordering of the atomic operations themselves.
+//!
+//! ## [Chapter 3: Fences](https://marabos.nl/atomics/memory-ordering.html#fences)
+//!
+//! A `Release` store is a `Relaxed` store preceded by a release fence; an `Acquire` load is a `Relaxed` load followed
+//! by an acquire fence. Splitting them apart pays off when one side of the pair is conditional or repeated:
+//! - [`publish`]: one release fence covers any number of relaxed flag stores after it
+//! - [`is_published`]: a relaxed check, with the acquire fence only once the flag is seen set
+//!   (polling an unset flag costs nothing extra)
+//! - [`release_ref`]: the `Arc::drop` pattern: every owner decrements with `Release`, and only the one that reaches
+//!   zero pays for the acquire fence before freeing
+//!
+//! A fence synchronizes through an atomic: the acquire side must *read* a value the release side stored after its
+//! fence. A fence with no such load or store pairing it up orders nothing across threads.
+//!
+//! ## Example
+//! ```
+//! use std::{sync::atomic::{AtomicBool, AtomicU32, Ordering::Relaxed},
+//!           thread};
+//!
+//! use sync::fences::{is_published, publish};
+//!
+//! let data = [AtomicU32::new(0), AtomicU32::new(0)];
+//! let ready = [AtomicBool::new(false), AtomicBool::new(false)];
+//! thread::scope(|s| {
+//!        s.spawn(|| {
+//!               data[0].store(10, Relaxed);
+//!               data[1].store(20, Relaxed);
+//!               publish(&ready); // one fence for both flags
+//!        });
+//!        for (data, ready) in data.iter().zip(&ready) {
+//!               while !is_published(ready) {
+//!                      std::hint::spin_loop();
+//!               }
+//!               assert_ne!(data.load(Relaxed), 0);
+//!        }
+//! });
+//! ```
+
+use crate::atomic::{AtomicBool, AtomicUsize,
+                    Ordering::{Acquire, Relaxed, Release},
+                    fence};
+
+/// Set every flag, making everything this thread did before the call visible to whoever sees any of them set
+/// through [`is_published`] (or any acquire load or acquire fence).
+pub fn publish<'a>(flags: impl IntoIterator<Item = &'a AtomicBool>) {
+       fence(Release);
+       for flag in flags {
+              flag.store(true, Relaxed);
+       }
+}
+
+/// Whether `flag` is set; if so, everything the setting thread did before [`publish`] is visible to this one.
+pub fn is_published(flag: &AtomicBool) -> bool {
+       if flag.load(Relaxed) {
+              fence(Acquire); // pairs with `publish`'s release fence, through the load that saw its store
+              true
+       } else {
+              false
+       }
+}
+
+/// Give back one reference counted by `count`; `true` if it was the last.
+///
+/// When it returns `true`, every other holder's accesses before *their* `release_ref` happen-before the return,
+/// so the caller may free what `count` guards.
+pub fn release_ref(count: &AtomicUsize) -> bool {
+       if count.fetch_sub(1, Release) != 1 {
+              return false;
+       }
+       // pairs with every other holder's `Release` decrement: the release sequence ends at our read of 1
+       fence(Acquire);
+       true
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicU32, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_publish_sets_every_flag() {
+              let flags = [AtomicBool::new(false), AtomicBool::new(false), AtomicBool::new(false)];
+              assert!(!flags.iter().any(is_published));
+              publish(&flags[1..]);
+              assert_eq!(flags.each_ref().map(is_published), [false, true, true]);
+       }
+
+       #[test]
+       fn test_only_the_last_release_is_last() {
+              let count = AtomicUsize::new(8);
+              let last = thread::scope(|s| {
+                     let handles: Vec<_> = (0..8).map(|_| s.spawn(|| release_ref(&count))).collect();
+                     handles.into_iter().map(|handle| handle.join().unwrap()).filter(|&last| last).count()
+              });
+              assert_eq!((last, count.load(Relaxed)), (1, 0));
+       }
+
+       #[test]
+       fn test_published_data_is_visible() {
+              let data = AtomicU32::new(0);
+              let ready = AtomicBool::new(false);
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            data.store(42, Relaxed);
+                            publish([&ready]);
+                     });
+                     while !is_published(&ready) {
+                            std::hint::spin_loop();
+                     }
+                     assert_eq!(data.load(Relaxed), 42);
+              });
+       }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+       use loom::{sync::Arc, thread};
+
+       use super::*;
+
+       /// A reader that sees a flag set sees the data written before `publish`, for each flag of a shared fence.
+       #[test]
+       fn loom_published_data_is_visible() {
+              loom::model(|| {
+                     let shared = Arc::new(([AtomicUsize::new(0), AtomicUsize::new(0)], [AtomicBool::new(false), AtomicBool::new(false)]));
+                     let writer = {
+                            let shared = shared.clone();
+                            thread::spawn(move || {
+                                   let (data, ready) = &*shared;
+                                   data[0].store(1, Relaxed);
+                                   data[1].store(2, Relaxed);
+                                   publish(ready);
+                            })
+                     };
+                     let (data, ready) = &*shared;
+                     for (index, (data, ready)) in data.iter().zip(ready).enumerate() {
+                            if is_published(ready) {
+                                   assert_eq!(data.load(Relaxed), index + 1);
+                            }
+                     }
+                     writer.join().unwrap();
+              });
+       }
+
+       /// Both holders write, then release: whichever is last sees both writes (the `Arc::drop` pattern).
+       #[test]
+       fn loom_last_release_sees_every_write() {
+              loom::model(|| {
+                     let shared = Arc::new((AtomicUsize::new(2), AtomicUsize::new(0)));
+                     let release = |shared: &(AtomicUsize, AtomicUsize)| {
+                            let (count, writes) = shared;
+                            writes.fetch_add(1, Relaxed);
+                            if release_ref(count) {
+                                   assert_eq!(writes.load(Relaxed), 2, "the last holder missed a write");
+                            }
+                     };
+                     let other = {
+                            let shared = shared.clone();
+                            thread::spawn(move || release(&shared))
+                     };
+                     release(&shared);
+                     other.join().unwrap();
+              });
+       }
+}
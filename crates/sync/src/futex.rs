@@ -0,0 +1,80 @@
+//! Futex-style waiting on an `AtomicU32`.
+//!
+//! ## [Chapter 8: Operating System Primitives](https://marabos.nl/atomics/os-primitives.html#futex)
+//!
+//! Untimed wait/wake come straight from `atomic-wait` (Linux futex, macOS ulock, Windows `WaitOnAddress`).
+//! That crate has no timeout, so timed waits call the Linux futex syscall directly
+//! and fall back to backoff-polling the value elsewhere.
+//!
+//! Like the underlying syscalls, every wait may return spuriously: callers re-check their condition in a loop.
+//!
+//! Loom can't model a futex, so under `--cfg loom` a wait is a yield back to its scheduler and a wake does nothing:
+//! exactly a futex whose every wait returns spuriously, which callers already handle. The same goes for shuttle.
+
+use std::time::{Duration, Instant};
+
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub(crate) use atomic_wait::{wait, wake_all, wake_one};
+
+use crate::{atomic::AtomicU32, deadline};
+
+#[cfg(any(loom, feature = "shuttle"))]
+pub(crate) fn wait(_: &AtomicU32, _: u32) { crate::atomic::yield_now() }
+#[cfg(any(loom, feature = "shuttle"))]
+pub(crate) fn wake_one(_: *const AtomicU32) {}
+#[cfg(any(loom, feature = "shuttle"))]
+pub(crate) fn wake_all(_: *const AtomicU32) {}
+
+/// Wait while `atomic == expected`, giving up at `deadline` (if any).
+///
+/// Returns `false` if the deadline has passed, so callers can report a timeout.
+pub(crate) fn wait_until(atomic: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+       match deadline {
+              None => {
+                     wait(atomic, expected);
+                     true
+              }
+              Some(deadline) => match deadline::remaining(deadline) {
+                     Some(remaining) => {
+                            wait_timeout(atomic, expected, remaining);
+                            true
+                     }
+                     None => false,
+              },
+       }
+}
+
+#[cfg(all(target_os = "linux", not(any(loom, feature = "shuttle"))))]
+fn wait_timeout(atomic: &AtomicU32, expected: u32, timeout: Duration) {
+       let timespec = libc::timespec {
+              tv_sec:  timeout.as_secs().try_into().unwrap_or(libc::time_t::MAX),
+              tv_nsec: timeout.subsec_nanos() as libc::c_long, // < 1e9, fits any `c_long`
+       };
+       // SAFETY: the futex word is a live `AtomicU32` and the timespec outlives the call;
+       //         FUTEX_WAIT only reads both. (Errors such as EAGAIN/ETIMEDOUT are just early returns.)
+       unsafe {
+              libc::syscall(
+                     libc::SYS_futex,
+                     atomic.as_ptr(),
+                     libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                     expected,
+                     &raw const timespec,
+                     std::ptr::null::<u32>(),
+                     0u32,
+              );
+       }
+}
+
+#[cfg(not(any(target_os = "linux", loom, feature = "shuttle")))]
+fn wait_timeout(atomic: &AtomicU32, expected: u32, timeout: Duration) {
+       use crate::atomic::Ordering::Relaxed;
+
+       let deadline = Instant::now() + timeout;
+       let mut backoff = crate::Backoff::new();
+       while atomic.load(Relaxed) == expected && deadline::remaining(deadline).is_some() {
+              backoff.snooze();
+       }
+}
+
+#[cfg(any(loom, feature = "shuttle"))]
+fn wait_timeout(atomic: &AtomicU32, expected: u32, _: Duration) { wait(atomic, expected) }
@@ -0,0 +1,224 @@
+//! Hazard pointers: safe memory reclamation for lock-free structures.
+//!
+//! A lock-free structure can unlink a node while another thread is still reading it,
+//! so the unlinking thread can't free the node straight away. With hazard pointers:
+//! - a reader [`protect`](HazardPointer::protect)s a pointer before dereferencing it:
+//!   it publishes "I'm using this" in a hazard slot, then re-checks the pointer is still reachable
+//! - the unlinking thread [`retire`]s the node instead of freeing it; retired nodes pile up per thread,
+//!   and every so often a *scan* frees those that no hazard slot mentions
+//!
+//! That lets a structure hand out references into its nodes (see [`TreiberStack::peek`](crate::TreiberStack::peek))
+//! rather than only moving values out.
+//!
+//! ## Design
+//! - hazard slots live in a global, grow-only linked list; a [`HazardPointer`] claims a free slot
+//!   and gives it back on drop (slots are never freed, only reused)
+//! - the retire list is thread-local; a scan runs every [`SCAN_THRESHOLD`] retirements (or on [`reclaim`])
+//! - a thread that exits with nodes still protected elsewhere hands them to a global orphan list,
+//!   which the next scan on any thread adopts
+//!
+//! Protecting and scanning are a store-then-load handshake on each side
+//! (reader: publish hazard, re-read source; reclaimer: unlink, read hazards), with `SeqCst` fences between:
+//! either the reader sees the node is gone and retries, or the scan sees the hazard and keeps the node.
+//!
+//! ## Example
+//! ```
+//! use std::sync::atomic::{AtomicPtr, Ordering::SeqCst};
+//!
+//! use sync::hazard::{self, HazardPointer};
+//!
+//! let shared = AtomicPtr::new(Box::into_raw(Box::new(1)));
+//! let mut hazard = HazardPointer::new();
+//! let protected = hazard.protect(&shared);
+//!
+//! // another thread swaps in a new value and retires the old one
+//! let old = shared.swap(Box::into_raw(Box::new(2)), SeqCst);
+//! // SAFETY: `old` came from `Box::into_raw` and is no longer reachable through `shared`.
+//! unsafe { hazard::retire(old) };
+//! hazard::reclaim();
+//!
+//! // SAFETY: protected: the scan above left it alone.
+//! assert_eq!(unsafe { *protected }, 1);
+//! # drop(hazard);
+//! # hazard::reclaim();
+//! # drop(unsafe { Box::from_raw(shared.into_inner()) });
+//! ```
+
+use std::{cell::RefCell, mem, ptr};
+
+use crate::{Mutex,
+            atomic::{AtomicPtr,
+                     Ordering::{Acquire, Relaxed, Release, SeqCst},
+                     fence},
+            reclaim::{Entry, Registry, Retired, free_all}};
+
+/// Retirements between automatic scans of a thread's retire list.
+pub const SCAN_THRESHOLD: usize = 64;
+
+/// Published "in use" pointers, one per live [`HazardPointer`].
+static SLOTS: Registry<AtomicPtr<()>> = Registry::new();
+
+/// Retired nodes whose thread exited before they could be freed.
+static ORPHANS: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+thread_local! {
+       static RETIRED: RefCell<RetireList> = const { RefCell::new(RetireList(Vec::new())) };
+}
+
+/// A hazard slot owned by this handle: protects (at most) one pointer at a time.
+pub struct HazardPointer {
+       slot: &'static Entry<AtomicPtr<()>>,
+}
+
+impl HazardPointer {
+       /// Claim a free slot, or add one if all are in use.
+       pub fn new() -> Self { Self { slot: SLOTS.claim(|| AtomicPtr::new(ptr::null_mut())) } }
+
+       /// Load `source` and protect what it points to: until the next `protect` or [`reset`](Self::reset)
+       /// (or this handle drops), a retired pointee won't be freed.
+       ///
+       /// The result was reachable through `source` at a point after protection began,
+       /// so it's safe to dereference if every unlink from `source` is followed by [`retire`] (not a plain free).
+       pub fn protect<T>(&mut self, source: &AtomicPtr<T>) -> *mut T {
+              let mut pointer = source.load(Relaxed);
+              loop {
+                     self.slot.store(pointer.cast(), Relaxed);
+                     fence(SeqCst); // publish the hazard before re-checking the source; pairs with the scan's fence
+                     let reloaded = source.load(Acquire);
+                     if reloaded == pointer {
+                            return pointer;
+                     }
+                     pointer = reloaded;
+              }
+       }
+
+       /// Stop protecting anything.
+       pub fn reset(&mut self) { self.slot.store(ptr::null_mut(), Release); }
+}
+
+impl Default for HazardPointer {
+       fn default() -> Self { Self::new() }
+}
+
+impl Drop for HazardPointer {
+       fn drop(&mut self) {
+              self.reset();
+              self.slot.release();
+       }
+}
+
+impl std::fmt::Debug for HazardPointer {
+       fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+              f.debug_struct("HazardPointer").field("protected", &self.slot.load(Relaxed)).finish()
+       }
+}
+
+/// Free `pointer` once no hazard pointer protects it.
+///
+/// ## Safety
+/// - `pointer` came from `Box::into_raw` and is not retired (or freed) twice
+/// - it is unreachable: no thread can newly load it from the shared structure
+pub unsafe fn retire<T: Send>(pointer: *mut T) {
+       let mut retired = Some(Retired::new(pointer));
+       let freeable = RETIRED.try_with(|list| {
+              let mut list = list.borrow_mut();
+              list.0.extend(retired.take());
+              if list.0.len() >= SCAN_THRESHOLD { list.take_unprotected() } else { Vec::new() }
+       });
+       if let Some(retired) = retired {
+              ORPHANS.lock().push(retired); // thread-local already torn down: let another thread see to it
+       }
+       free_all(freeable.unwrap_or_default());
+}
+
+/// Scan this thread's retire list (and any orphans) now, freeing whatever is no longer protected.
+pub fn reclaim() { free_all(RETIRED.try_with(|list| list.borrow_mut().take_unprotected()).unwrap_or_default()); }
+
+struct RetireList(Vec<Retired>);
+
+impl RetireList {
+       /// Remove (for freeing) whatever no hazard slot protects, from this list and the orphans.
+       ///
+       /// The orphan lock is held throughout, so a protected orphan is back in the list before another scan looks:
+       /// once its hazard is cleared, the next scan anywhere frees it.
+       fn take_unprotected(&mut self) -> Vec<Retired> {
+              let mut orphans = ORPHANS.lock();
+              fence(SeqCst); // nodes were unlinked before this; pairs with `protect`'s fence
+              let mut hazards: Vec<_> = SLOTS.entries().map(|slot| slot.load(Acquire)).filter(|pointer| !pointer.is_null()).collect();
+              hazards.sort_unstable();
+              let mut freeable = Vec::new();
+              for list in [&mut self.0, &mut *orphans] {
+                     let (protected, unprotected) =
+                            mem::take(list).into_iter().partition(|retired| hazards.binary_search(&retired.address()).is_ok());
+                     *list = protected;
+                     freeable.extend::<Vec<_>>(unprotected);
+              }
+              freeable
+       }
+}
+
+impl Drop for RetireList {
+       fn drop(&mut self) {
+              free_all(self.take_unprotected());
+              ORPHANS.lock().append(&mut self.0);
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::{AtomicUsize, Ordering::Relaxed},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::test_util::DropCounter;
+
+       #[test]
+       fn test_protected_pointer_survives_scan() {
+              static DROPS: AtomicUsize = AtomicUsize::new(0);
+              let shared = AtomicPtr::new(Box::into_raw(Box::new(DropCounter(&DROPS))));
+              let mut hazard = HazardPointer::new();
+              let protected = hazard.protect(&shared);
+              let old = shared.swap(ptr::null_mut(), SeqCst);
+              assert_eq!(old, protected);
+              // SAFETY: from `Box::into_raw`, and now unreachable.
+              unsafe { retire(old) };
+              reclaim();
+              assert_eq!(DROPS.load(Relaxed), 0);
+              hazard.reset();
+              reclaim();
+              assert_eq!(DROPS.load(Relaxed), 1);
+       }
+
+       #[test]
+       fn test_live_handles_have_distinct_slots() {
+              let handles: Vec<_> = (0..8).map(|_| HazardPointer::new()).collect();
+              for (i, a) in handles.iter().enumerate() {
+                     assert!(handles[i + 1..].iter().all(|b| !ptr::eq(a.slot, b.slot)));
+              }
+       }
+
+       #[test]
+       fn test_exiting_thread_orphans_protected_nodes() {
+              static DROPS: AtomicUsize = AtomicUsize::new(0);
+              let shared = AtomicPtr::new(Box::into_raw(Box::new(DropCounter(&DROPS))));
+              let mut hazard = HazardPointer::new();
+              hazard.protect(&shared);
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            let old = shared.swap(ptr::null_mut(), SeqCst);
+                            // SAFETY: from `Box::into_raw`, and now unreachable.
+                            unsafe { retire(old) };
+                     });
+              });
+              assert_eq!(DROPS.load(Relaxed), 0, "still protected when its thread exited");
+              drop(hazard);
+              // our scan frees the orphan, unless a concurrent test's scan got there first
+              while DROPS.load(Relaxed) == 0 {
+                     reclaim();
+                     thread::yield_now();
+              }
+              assert_eq!(DROPS.load(Relaxed), 1);
+       }
+}
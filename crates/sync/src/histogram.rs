@@ -0,0 +1,285 @@
+//! Lock-free latency histograms, and (with the `histograms` feature) the wait times of the crate's locks and channels.
+//!
+//! Averages hide the tail: a lock whose mean wait is 200ns can still stall one caller in a thousand for a millisecond.
+//! A [`Histogram`] keeps every sample's order of magnitude instead, HDR-style: 16 linear sub-buckets per power of two,
+//! so any value is reported within 1/16 (6.25%) of what was recorded, from 1ns up to `u64::MAX` ns, in 976 counters.
+//! Recording is one relaxed `fetch_add` (plus a `fetch_max`); reading walks the counters, so a read racing recorders
+//! may miss a sample or two.
+//!
+//! With `--features histograms`, every *blocking* wait records into a process-wide histogram per [`WaitSite`]
+//! (uncontended fast paths don't touch the clock):
+//! - [`Mutex`](crate::Mutex) lock slow path
+//! - [`RwLock`](crate::RwLock) read and write waits
+//! - channel receives that park (`BlockingChannel`, `mpsc`, `oneshot`) and full `BlockingChannel` sends
+//!
+//! [`waits`] gives one site's histogram, [`summary`] a table of all of them.
+//!
+//! The counters are std atomics even under `--cfg loom`: they observe the primitives rather than being part of them.
+//!
+//! ## Example
+//! ```
+//! use std::time::Duration;
+//!
+//! use sync::histogram::Histogram;
+//!
+//! let waits = Histogram::new();
+//! for micros in 1..=1000 {
+//!        waits.record(Duration::from_micros(micros));
+//! }
+//! assert_eq!(waits.count(), 1000);
+//! let p99 = waits.p99();
+//! assert!(p99 >= Duration::from_micros(990) && p99 <= Duration::from_micros(990) * 17 / 16, "{p99:?}");
+//! ```
+
+#[cfg(feature = "histograms")]
+use std::time::Instant;
+use std::{sync::atomic::{AtomicU64, Ordering::Relaxed},
+          time::Duration};
+
+/// Linear sub-buckets per power of two (as a bit count): 4 bits, 16 sub-buckets, 1/16 relative error.
+const SUB_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+/// Values below `SUB_BUCKETS` get a bucket each; every power of two from there up to 2^63 gets `SUB_BUCKETS`.
+const BUCKETS: usize = SUB_BUCKETS + (64 - SUB_BITS as usize) * SUB_BUCKETS;
+
+/// Log-linear histogram of durations (nanosecond resolution); shareable, recorded into without locks.
+pub struct Histogram {
+       counts: [AtomicU64; BUCKETS],
+       total:  AtomicU64,
+       max:    AtomicU64,
+}
+
+impl Histogram {
+       pub const fn new() -> Self {
+              Self { counts: [const { AtomicU64::new(0) }; BUCKETS], total: AtomicU64::new(0), max: AtomicU64::new(0) }
+       }
+
+       /// Add one sample (saturating at `u64::MAX` nanoseconds, about 584 years).
+       pub fn record(&self, value: Duration) {
+              let nanos = u64::try_from(value.as_nanos()).unwrap_or(u64::MAX);
+              self.counts[bucket(nanos)].fetch_add(1, Relaxed);
+              self.total.fetch_add(1, Relaxed);
+              self.max.fetch_max(nanos, Relaxed);
+       }
+
+       /// Samples recorded so far.
+       pub fn count(&self) -> u64 { self.total.load(Relaxed) }
+
+       /// Exact largest sample (`ZERO` if none).
+       pub fn max(&self) -> Duration { Duration::from_nanos(self.max.load(Relaxed)) }
+
+       /// The value `percentile` percent of samples are at or below (`ZERO` if none), as the top of its bucket:
+       /// never below the true percentile, and at most 1/16 above it.
+       ///
+       /// ## Panics
+       /// If `percentile` isn't within `0.0..=100.0`.
+       pub fn percentile(&self, percentile: f64) -> Duration {
+              assert!((0.0..=100.0).contains(&percentile), "percentile {percentile} out of 0..=100");
+              let counts: Vec<u64> = self.counts.iter().map(|count| count.load(Relaxed)).collect();
+              let total: u64 = counts.iter().sum();
+              if total == 0 {
+                     return Duration::ZERO;
+              }
+              // rank of the sample we want, 1-based; at least the first
+              let rank = ((percentile / 100.0 * total as f64).ceil() as u64).max(1);
+              let mut seen = 0;
+              for (index, count) in counts.into_iter().enumerate() {
+                     seen += count;
+                     if seen >= rank {
+                            return Duration::from_nanos(bucket_top(index).min(self.max.load(Relaxed)));
+                     }
+              }
+              self.max()
+       }
+
+       pub fn p50(&self) -> Duration { self.percentile(50.0) }
+
+       pub fn p99(&self) -> Duration { self.percentile(99.0) }
+
+       pub fn p999(&self) -> Duration { self.percentile(99.9) }
+
+       /// Forget every sample (not atomic with respect to concurrent `record`s).
+       pub fn reset(&self) {
+              for count in &self.counts {
+                     count.store(0, Relaxed);
+              }
+              self.total.store(0, Relaxed);
+              self.max.store(0, Relaxed);
+       }
+}
+
+impl Default for Histogram {
+       fn default() -> Self { Self::new() }
+}
+
+impl std::fmt::Debug for Histogram {
+       fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+              f.debug_struct("Histogram")
+                     .field("count", &self.count())
+                     .field("p50", &self.p50())
+                     .field("p99", &self.p99())
+                     .field("p999", &self.p999())
+                     .field("max", &self.max())
+                     .finish()
+       }
+}
+
+/// Bucket for `nanos`: exact below `SUB_BUCKETS`, then `SUB_BUCKETS` per power of two.
+fn bucket(nanos: u64) -> usize {
+       if nanos < SUB_BUCKETS as u64 {
+              return nanos as usize;
+       }
+       let magnitude = nanos.ilog2(); // >= SUB_BITS
+       let sub = (nanos >> (magnitude - SUB_BITS)) as usize - SUB_BUCKETS;
+       SUB_BUCKETS + (magnitude - SUB_BITS) as usize * SUB_BUCKETS + sub
+}
+
+/// Largest value that lands in bucket `index`.
+fn bucket_top(index: usize) -> u64 {
+       if index < SUB_BUCKETS {
+              return index as u64;
+       }
+       let shift = ((index - SUB_BUCKETS) / SUB_BUCKETS) as u32;
+       let sub = ((index - SUB_BUCKETS) % SUB_BUCKETS) as u64;
+       let bottom = (SUB_BUCKETS as u64 + sub) << shift;
+       bottom + ((1 << shift) - 1)
+}
+
+/// Where a thread waited, for [`waits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WaitSite {
+       Mutex,
+       RwLockRead,
+       RwLockWrite,
+       /// A send blocked on a full bounded channel.
+       ChannelSend,
+       /// A receive blocked on an empty channel.
+       ChannelRecv,
+}
+
+impl WaitSite {
+       pub const ALL: [Self; 5] = [Self::Mutex, Self::RwLockRead, Self::RwLockWrite, Self::ChannelSend, Self::ChannelRecv];
+}
+
+#[cfg(feature = "histograms")]
+static WAITS: [Histogram; WaitSite::ALL.len()] = [const { Histogram::new() }; WaitSite::ALL.len()];
+
+/// Every blocking wait at `site` since start-up (or the last [`Histogram::reset`]).
+#[cfg(feature = "histograms")]
+pub fn waits(site: WaitSite) -> &'static Histogram { &WAITS[site as usize] }
+
+/// A table of [`waits`] at every site: count, p50, p99, p99.9 and max.
+#[cfg(feature = "histograms")]
+pub fn summary() -> String {
+       use std::fmt::Write as _;
+
+       let mut table = format!("{:<12} {:>10} {:>10} {:>10} {:>10} {:>10}\n", "site", "waits", "p50", "p99", "p99.9", "max");
+       for site in WaitSite::ALL {
+              let waits = waits(site);
+              let [p50, p99, p999, max] = [waits.p50(), waits.p99(), waits.p999(), waits.max()].map(|d| format!("{d:.1?}"));
+              let _ = writeln!(table, "{:<12} {:>10} {p50:>10} {p99:>10} {p999:>10} {max:>10}", format!("{site:?}"), waits.count());
+       }
+       table
+}
+
+/// Times one blocking wait: [`start`](Self::start) it where the caller is about to block (again is a no-op),
+/// and the time from there is recorded at `site` on drop. Does nothing without the `histograms` feature.
+pub(crate) struct WaitTimer {
+       #[cfg(feature = "histograms")]
+       site:  WaitSite,
+       #[cfg(feature = "histograms")]
+       start: Option<Instant>,
+}
+
+impl WaitTimer {
+       #[cfg_attr(not(feature = "histograms"), expect(unused_variables))]
+       pub(crate) fn new(site: WaitSite) -> Self {
+              Self {
+                     #[cfg(feature = "histograms")]
+                     site,
+                     #[cfg(feature = "histograms")]
+                     start: None,
+              }
+       }
+
+       #[inline]
+       pub(crate) fn start(&mut self) {
+              #[cfg(feature = "histograms")]
+              self.start.get_or_insert_with(Instant::now);
+       }
+}
+
+#[cfg(feature = "histograms")]
+impl Drop for WaitTimer {
+       fn drop(&mut self) {
+              if let Some(start) = self.start {
+                     waits(self.site).record(start.elapsed());
+              }
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_buckets_bound_their_values() {
+              let values = (0..20_u32).flat_map(|shift| [0, 1, 7, 15].map(|offset| (1_u64 << shift) + offset)).chain([u64::MAX]);
+              for value in values {
+                     let index = bucket(value);
+                     let top = bucket_top(index);
+                     assert!(value <= top && top - value <= value / 16, "{value} in bucket {index} topping out at {top}");
+                     assert!(index == 0 || bucket_top(index - 1) < value, "{value} belongs lower than {index}");
+              }
+              assert_eq!(bucket(u64::MAX), BUCKETS - 1);
+       }
+
+       #[test]
+       fn test_percentiles_find_the_tail() {
+              let histogram = Histogram::new();
+              assert_eq!(histogram.p99(), Duration::ZERO);
+              for _ in 0..990 {
+                     histogram.record(Duration::from_nanos(100));
+              }
+              for _ in 0..10 {
+                     histogram.record(Duration::from_millis(1));
+              }
+              assert_eq!((histogram.count(), histogram.max()), (1000, Duration::from_millis(1)));
+              assert_eq!(histogram.p50(), Duration::from_nanos(103)); // 100's bucket is 96..=103
+              assert_eq!(histogram.percentile(99.0), Duration::from_nanos(103));
+              assert_eq!(histogram.p999(), Duration::from_millis(1)); // capped at the exact max
+              histogram.reset();
+              assert_eq!(histogram.count(), 0);
+       }
+
+       #[test]
+       fn test_concurrent_records_all_land() {
+              let histogram = Histogram::new();
+              thread::scope(|s| {
+                     for t in 0..4 {
+                            let histogram = &histogram;
+                            s.spawn(move || (0..1000).for_each(|i| histogram.record(Duration::from_nanos(t * 1000 + i))));
+                     }
+              });
+              assert_eq!((histogram.count(), histogram.max()), (4000, Duration::from_nanos(3999)));
+       }
+
+       #[cfg(feature = "histograms")]
+       #[test]
+       fn test_contended_mutex_records_its_waits() {
+              let lock = crate::Mutex::new(());
+              let before = waits(WaitSite::Mutex).count();
+              thread::scope(|s| {
+                     let guard = lock.lock();
+                     s.spawn(|| drop(lock.lock()));
+                     thread::sleep(Duration::from_millis(10));
+                     drop(guard);
+              });
+              assert!(waits(WaitSite::Mutex).count() > before);
+              assert!(summary().lines().any(|line| line.starts_with("Mutex")));
+       }
+}
@@ -0,0 +1,198 @@
+//! Unique `u64` ids from many threads at once, e.g. correlation ids for tracing a request across threads.
+//!
+//! One shared counter would serialize every thread on one cache line; [`IdGen`] keeps one counter per shard
+//! (as [`ShardedCounter`](crate::ShardedCounter) does), and each thread draws from its own.
+//! Shard `s` of `n` only hands out ids `≡ s (mod n)`, so shards never collide, with no coordination between them.
+//! - [`sequential`](IdGen::sequential): the shard's count, then the shard index. Dense, and roughly ordered:
+//!   ids from different shards interleave by how far each shard has counted
+//! - [`timestamped`](IdGen::timestamped): milliseconds since [`epoch`](IdGen::epoch), then a sequence number, then
+//!   the shard (Snowflake-style). Ordered by creation time to the millisecond, across shards and across runs;
+//!   [`timestamp`](IdGen::timestamp) recovers the time
+//!
+//! ## Overflow
+//! - a timestamped shard that runs out of sequence numbers within a millisecond borrows the next millisecond
+//!   (its ids run briefly ahead of the clock, but stay unique and increasing); a clock stepping back is ignored the
+//!   same way
+//! - once a shard's counter (or the clock) no longer fits beside the shard bits, [`try_next`](IdGen::try_next)
+//!   fails rather than wrap around to ids already handed out. Sequential ids take at least 2^58 per shard to get
+//!   there, timestamped ones at least 139 years past the epoch
+//!
+//! ## Example
+//! ```
+//! use std::{collections::HashSet, thread};
+//!
+//! use sync::IdGen;
+//!
+//! let ids = IdGen::timestamped();
+//! let all: HashSet<u64> = thread::scope(|s| {
+//!        let handles: Vec<_> = (0..4).map(|_| s.spawn(|| (0..1_000).map(|_| ids.next()).collect::<Vec<_>>())).collect();
+//!        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+//! });
+//! assert_eq!(all.len(), 4_000);
+//! assert!(ids.timestamp(*all.iter().max().unwrap()).is_some());
+//! ```
+
+use std::{fmt, thread,
+          time::{Duration, SystemTime}};
+
+use derive_more::{Display, Error};
+
+use crate::{CachePadded,
+            atomic::{AtomicU64, Ordering::Relaxed},
+            sharded_counter::stripe};
+
+/// Bits of a timestamped id's sequence number: ids per millisecond per shard, before borrowing the next millisecond.
+const SEQUENCE_BITS: u32 = 16;
+const EPOCH_UNIX_SECS: u64 = 1_735_689_600;
+
+/// Generator of unique ids; see the [module docs](self).
+pub struct IdGen {
+       /// Per shard: the last count handed out (sequential), or the last `millis << SEQUENCE_BITS | sequence`.
+       shards:      Box<[CachePadded<AtomicU64>]>,
+       /// log2 of the shard count: the low bits of every id.
+       shard_bits:  u32,
+       timestamped: bool,
+}
+
+/// The generator can't hand out another id without reusing one.
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[display("id generator exhausted: the next id would not fit in 64 bits")]
+pub struct IdsExhausted;
+
+impl IdGen {
+       /// Most shards a generator uses, whatever the core count.
+       pub const MAX_SHARDS: usize = 64;
+
+       /// Sequential ids, one shard per core.
+       pub fn sequential() -> Self { Self::with_shards(Self::default_shards(), false) }
+
+       /// Timestamped ids, one shard per core.
+       pub fn timestamped() -> Self { Self::with_shards(Self::default_shards(), true) }
+
+       /// At least `shards` shards (rounded up to a power of two, at most [`MAX_SHARDS`](Self::MAX_SHARDS)).
+       pub fn with_shards(shards: usize, timestamped: bool) -> Self {
+              let shards = shards.clamp(1, Self::MAX_SHARDS).next_power_of_two();
+              Self {
+                     shards: (0..shards).map(|_| CachePadded(AtomicU64::new(0))).collect(),
+                     shard_bits: shards.trailing_zeros(),
+                     timestamped,
+              }
+       }
+
+       fn default_shards() -> usize { thread::available_parallelism().map_or(1, |cores| cores.get()) }
+
+       /// A new id, never handed out before by this generator. Never zero.
+       ///
+       /// ## Panics
+       /// If the generator is exhausted (see [`try_next`](Self::try_next)).
+       pub fn next(&self) -> u64 { self.try_next().expect("id generator exhausted") }
+
+       /// A new id, never handed out before by this generator. Never zero.
+       ///
+       /// ## Errors
+       /// Once this thread's shard can't count any further without wrapping.
+       pub fn try_next(&self) -> Result<u64, IdsExhausted> {
+              let shard_index = stripe() & (self.shards.len() - 1);
+              // the largest count that still leaves room for the shard bits
+              let max = u64::MAX >> self.shard_bits;
+              let now = if self.timestamped { millis_since_epoch() << SEQUENCE_BITS } else { 0 };
+              // the clock, unless the shard has already counted past it (a full millisecond, or a clock step back)
+              let advance = |last: u64| last.checked_add(1).map(|next| next.max(now)).filter(|&next| next <= max);
+              let last = self.shards[shard_index].try_update(Relaxed, Relaxed, advance).map_err(|_| IdsExhausted)?;
+              let count = advance(last).expect("the update just succeeded with it");
+              Ok(count << self.shard_bits | shard_index as u64)
+       }
+
+       /// When a timestamped `id` was made (to the millisecond; later, if its shard borrowed ahead of the clock).
+       /// `None` for a sequential generator.
+       pub fn timestamp(&self, id: u64) -> Option<SystemTime> {
+              self.timestamped.then(|| Self::epoch() + Duration::from_millis(id >> (self.shard_bits + SEQUENCE_BITS)))
+       }
+
+       /// Where timestamped ids count from: 2025-01-01T00:00:00Z.
+       pub fn epoch() -> SystemTime { SystemTime::UNIX_EPOCH + Duration::from_secs(EPOCH_UNIX_SECS) }
+
+       pub fn shard_count(&self) -> usize { self.shards.len() }
+
+       pub fn is_timestamped(&self) -> bool { self.timestamped }
+}
+
+/// Milliseconds since [`IdGen::epoch`]; zero for a clock set before it (counting on from the last id then).
+fn millis_since_epoch() -> u64 {
+       let since = SystemTime::now().duration_since(IdGen::epoch()).unwrap_or_default();
+       u64::try_from(since.as_millis()).unwrap_or(u64::MAX >> SEQUENCE_BITS).min(u64::MAX >> SEQUENCE_BITS)
+}
+
+impl Default for IdGen {
+       fn default() -> Self { Self::sequential() }
+}
+
+impl fmt::Debug for IdGen {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("IdGen").field("shards", &self.shards.len()).field("timestamped", &self.timestamped).finish_non_exhaustive()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::collections::HashSet;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       fn ids_from_threads(ids: &IdGen, threads: usize, per_thread: usize) -> Vec<Vec<u64>> {
+              thread::scope(|s| {
+                     let handles: Vec<_> = (0..threads).map(|_| s.spawn(move || (0..per_thread).map(|_| ids.next()).collect())).collect();
+                     handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+              })
+       }
+
+       #[test]
+       fn test_unique_and_increasing_per_thread() {
+              for ids in [IdGen::with_shards(4, false), IdGen::with_shards(4, true)] {
+                     let per_thread = ids_from_threads(&ids, 8, 2_000);
+                     for thread_ids in &per_thread {
+                            assert!(thread_ids.is_sorted_by(|a, b| a < b), "{ids:?}: one thread's ids only grow");
+                     }
+                     let unique: HashSet<_> = per_thread.iter().flatten().collect();
+                     assert_eq!(unique.len(), 16_000, "{ids:?}");
+                     assert!(!unique.contains(&0));
+              }
+       }
+
+       #[test]
+       fn test_sequential_ids_are_dense() {
+              let ids = IdGen::with_shards(1, false);
+              assert_eq!((0..4).map(|_| ids.next()).collect::<Vec<_>>(), [1, 2, 3, 4]);
+              assert_eq!(ids.timestamp(1), None);
+       }
+
+       #[test]
+       fn test_timestamps_round_trip() {
+              let ids = IdGen::with_shards(8, true);
+              let before = SystemTime::now();
+              let id = ids.next();
+              let made = ids.timestamp(id).unwrap();
+              let after = SystemTime::now();
+              assert!(made + Duration::from_millis(1) >= before && made <= after, "{made:?} not in {before:?}..={after:?}");
+       }
+
+       #[test]
+       fn test_full_millisecond_borrows_the_next() {
+              let ids = IdGen::with_shards(1, true);
+              let now = millis_since_epoch() + 1_000; // a millisecond the clock won't reach during the test
+              ids.shards[0].store(now << SEQUENCE_BITS | ((1 << SEQUENCE_BITS) - 1), Relaxed);
+              let id = ids.next();
+              assert_eq!(id, (now + 1) << SEQUENCE_BITS);
+              assert_eq!(ids.timestamp(id), Some(IdGen::epoch() + Duration::from_millis(now + 1)));
+       }
+
+       #[test]
+       fn test_exhaustion_is_an_error_not_a_wrap() {
+              let ids = IdGen::with_shards(2, false);
+              ids.shards[stripe() & 1].store((u64::MAX >> 1) - 1, Relaxed);
+              assert!(ids.try_next().is_ok());
+              assert_eq!(ids.try_next(), Err(IdsExhausted));
+       }
+}
@@ -0,0 +1,210 @@
+//! Mutex wrapper that measures itself: how often it's contended, how long lockers wait, how long holders hold.
+//!
+//! Counters are plain relaxed atomics (each one is independent; a [`stats`](InstrumentedMutex::stats) snapshot
+//! taken mid-flight may be off by an in-progress acquisition, which is fine for profiling).
+//! Each acquisition also emits `tracing` events at `TRACE` level:
+//! - `acquired` (inside a `mutex_lock` span) with the wait time and whether the lock was contended
+//! - `released` (inside a `mutex_hold` span) with the hold time
+//!
+//! With a timing layer (e.g. `tracing-timing`) on the subscriber, that's a lock-profile histogram for free.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::InstrumentedMutex;
+//!
+//! let counter = InstrumentedMutex::new("counter", 0);
+//! thread::scope(|s| {
+//!        for _ in 0..4 {
+//!               s.spawn(|| *counter.lock() += 1);
+//!        }
+//! });
+//! let stats = counter.stats();
+//! assert_eq!(stats.acquisitions, 4);
+//! assert!(stats.contended <= 4);
+//! ```
+
+use std::{ops::{Deref, DerefMut},
+          time::{Duration, Instant}};
+
+use crate::{Mutex, MutexGuard,
+            atomic::{AtomicU64, Ordering::Relaxed}};
+
+/// A [`Mutex`] that records contention, wait time, and hold time.
+pub struct InstrumentedMutex<T> {
+       name:  &'static str,
+       mutex: Mutex<T>,
+       stats: AtomicStats,
+}
+
+/// Snapshot of an [`InstrumentedMutex`]'s counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LockStats {
+       pub acquisitions: u64,
+       /// Acquisitions that found the lock taken and had to wait.
+       pub contended:    u64,
+       pub total_wait:   Duration,
+       pub max_wait:     Duration,
+       pub total_hold:   Duration,
+       pub max_hold:     Duration,
+}
+impl LockStats {
+       /// Fraction of acquisitions that had to wait, in `0.0..=1.0`.
+       pub fn contention_ratio(&self) -> f64 { if self.acquisitions == 0 { 0.0 } else { self.contended as f64 / self.acquisitions as f64 } }
+
+       pub fn mean_wait(&self) -> Duration { self.total_wait.checked_div(self.acquisitions_u32()).unwrap_or_default() }
+
+       pub fn mean_hold(&self) -> Duration { self.total_hold.checked_div(self.acquisitions_u32()).unwrap_or_default() }
+
+       fn acquisitions_u32(&self) -> u32 { self.acquisitions.try_into().unwrap_or(u32::MAX) }
+}
+
+#[derive(Default)]
+struct AtomicStats {
+       acquisitions:     AtomicU64,
+       contended:        AtomicU64,
+       total_wait_nanos: AtomicU64,
+       max_wait_nanos:   AtomicU64,
+       total_hold_nanos: AtomicU64,
+       max_hold_nanos:   AtomicU64,
+}
+
+impl<T> InstrumentedMutex<T> {
+       /// `name` labels the tracing spans.
+       pub fn new(name: &'static str, value: T) -> Self { Self { name, mutex: Mutex::new(value), stats: AtomicStats::default() } }
+
+       pub fn name(&self) -> &'static str { self.name }
+
+       /// Block until the lock is ours, recording whether (and how long) we had to wait.
+       pub fn lock(&self) -> InstrumentedMutexGuard<'_, T> {
+              let _span = tracing::trace_span!("mutex_lock", lock = self.name).entered();
+              let start = Instant::now();
+              let (guard, contended) = match self.mutex.try_lock() {
+                     Some(guard) => (guard, false),
+                     None => (self.mutex.lock(), true),
+              };
+              let acquired_at = Instant::now();
+              let wait = acquired_at - start;
+              self.stats.acquisitions.fetch_add(1, Relaxed);
+              if contended {
+                     self.stats.contended.fetch_add(1, Relaxed);
+              }
+              record(&self.stats.total_wait_nanos, &self.stats.max_wait_nanos, wait);
+              tracing::trace!(wait_ns = nanos(wait), contended, "acquired");
+              InstrumentedMutexGuard { instrumented: self, guard, acquired_at }
+       }
+
+       /// Counters so far.
+       pub fn stats(&self) -> LockStats {
+              let stats = &self.stats;
+              LockStats {
+                     acquisitions: stats.acquisitions.load(Relaxed),
+                     contended:    stats.contended.load(Relaxed),
+                     total_wait:   Duration::from_nanos(stats.total_wait_nanos.load(Relaxed)),
+                     max_wait:     Duration::from_nanos(stats.max_wait_nanos.load(Relaxed)),
+                     total_hold:   Duration::from_nanos(stats.total_hold_nanos.load(Relaxed)),
+                     max_hold:     Duration::from_nanos(stats.max_hold_nanos.load(Relaxed)),
+              }
+       }
+
+       /// Zero the counters, e.g. between benchmark phases.
+       pub fn reset_stats(&self) {
+              let stats = &self.stats;
+              for counter in [
+                     &stats.acquisitions,
+                     &stats.contended,
+                     &stats.total_wait_nanos,
+                     &stats.max_wait_nanos,
+                     &stats.total_hold_nanos,
+                     &stats.max_hold_nanos,
+              ] {
+                     counter.store(0, Relaxed);
+              }
+       }
+
+       pub fn into_inner(self) -> T { self.mutex.into_inner() }
+}
+
+/// Exclusive access to an [`InstrumentedMutex`]'s value; records the hold time on drop.
+///
+/// `Sync` only if `T` is, as sharing the guard shares `&T`:
+/// ```compile_fail
+/// fn shareable<G: Sync>() {}
+/// shareable::<sync::InstrumentedMutexGuard<'static, std::cell::Cell<u32>>>(); // error: `Cell<u32>` cannot be shared
+/// ```
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct InstrumentedMutexGuard<'a, T> {
+       instrumented: &'a InstrumentedMutex<T>,
+       guard:        MutexGuard<'a, T>,
+       acquired_at:  Instant,
+}
+// SAFETY: the guard only hands out `&T` when shared, so `T: Sync` suffices.
+unsafe impl<T> Sync for InstrumentedMutexGuard<'_, T> where T: Sync {}
+impl<T> Deref for InstrumentedMutexGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T { &self.guard }
+}
+impl<T> DerefMut for InstrumentedMutexGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T { &mut self.guard }
+}
+impl<T> Drop for InstrumentedMutexGuard<'_, T> {
+       fn drop(&mut self) {
+              // measured just before the inner guard unlocks: the unlock itself is not counted
+              let hold = self.acquired_at.elapsed();
+              let stats = &self.instrumented.stats;
+              record(&stats.total_hold_nanos, &stats.max_hold_nanos, hold);
+              tracing::trace_span!("mutex_hold", lock = self.instrumented.name)
+                     .in_scope(|| tracing::trace!(hold_ns = nanos(hold), "released"));
+       }
+}
+
+fn record(total: &AtomicU64, max: &AtomicU64, elapsed: Duration) {
+       let nanos = nanos(elapsed);
+       total.fetch_add(nanos, Relaxed);
+       max.fetch_max(nanos, Relaxed);
+}
+
+/// Saturating: 2^64 ns is ~584 years.
+fn nanos(duration: Duration) -> u64 { duration.as_nanos().try_into().unwrap_or(u64::MAX) }
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_uncontended() {
+              let mutex = InstrumentedMutex::new("quiet", 0);
+              for _ in 0..3 {
+                     *mutex.lock() += 1;
+              }
+              let stats = mutex.stats();
+              assert_eq!((stats.acquisitions, stats.contended), (3, 0));
+              assert_eq!(stats.contention_ratio(), 0.0);
+              mutex.reset_stats();
+              assert_eq!(mutex.stats(), LockStats::default());
+              assert_eq!(mutex.into_inner(), 3);
+       }
+
+       #[test]
+       fn test_contention_and_hold_time_recorded() {
+              let mutex = InstrumentedMutex::new("busy", ());
+              thread::scope(|s| {
+                     let guard = mutex.lock();
+                     let waiter = s.spawn(|| drop(mutex.lock()));
+                     thread::sleep(Duration::from_millis(20));
+                     drop(guard);
+                     waiter.join().unwrap();
+              });
+              let stats = mutex.stats();
+              assert_eq!((stats.acquisitions, stats.contended), (2, 1));
+              assert!(stats.max_hold >= Duration::from_millis(20));
+              assert!(stats.max_wait > Duration::ZERO);
+              assert!(stats.mean_hold() <= stats.max_hold);
+       }
+}
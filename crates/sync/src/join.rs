@@ -0,0 +1,227 @@
+//! Join a batch of threads, collecting every failure instead of unwrapping the first.
+//!
+//! - [`join_all`]: results in handle order, or a [`MultiError`] listing each thread that panicked
+//! - [`try_join_all`]: for threads returning `Result`; an `Err` counts as a failure alongside panics
+//!
+//! Every handle is joined either way, so no thread is left running (or detached) because an earlier one failed.
+//! Works with [`JoinHandle`]s and scoped [`ScopedJoinHandle`]s alike (see [`Joinable`]).
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! let handles: Vec<_> = (0..4)
+//!        .map(|i| {
+//!               thread::Builder::new()
+//!                      .name(format!("worker-{i}"))
+//!                      .spawn(move || {
+//!                             assert!(i != 2, "worker 2 gives up");
+//!                             i * 10
+//!                      })
+//!                      .unwrap()
+//!        })
+//!        .collect();
+//! let error = sync::join_all(handles).unwrap_err();
+//! assert_eq!(error.to_string(), "1 of 4 threads failed\n  #2 (worker-2): panicked: worker 2 gives up");
+//!
+//! let all_fine = thread::scope(|s| sync::join_all((0..3).map(|i| s.spawn(move || i))));
+//! assert_eq!(all_fine.unwrap(), [0, 1, 2]);
+//! ```
+
+use std::{any::Any,
+          convert::Infallible,
+          error::Error,
+          fmt,
+          thread::{self, JoinHandle, ScopedJoinHandle}};
+
+/// Join every handle; the results in order, or every panic.
+///
+/// ## Errors
+/// If any thread panicked.
+pub fn join_all<J: Joinable>(handles: impl IntoIterator<Item = J>) -> Result<Vec<J::Output>, MultiError> { collect(handles, Ok) }
+
+/// Join every handle of threads returning `Result`; the `Ok` values in order, or every panic and `Err`.
+///
+/// ## Errors
+/// If any thread panicked or returned `Err`.
+pub fn try_join_all<J, T, E>(handles: impl IntoIterator<Item = J>) -> Result<Vec<T>, MultiError<E>>
+where
+       J: Joinable<Output = Result<T, E>>,
+{
+       collect(handles, |output| output)
+}
+
+fn collect<J: Joinable, T, E>(
+       handles: impl IntoIterator<Item = J>,
+       mut unpack: impl FnMut(J::Output) -> Result<T, E>,
+) -> Result<Vec<T>, MultiError<E>> {
+       let mut values = Vec::new();
+       let mut failures = Vec::new();
+       let mut total = 0;
+       for (index, handle) in handles.into_iter().enumerate() {
+              total += 1;
+              let thread = handle.thread_name();
+              match handle.join_thread().map(&mut unpack) {
+                     Ok(Ok(value)) => values.push(value),
+                     Ok(Err(error)) => failures.push((index, ThreadFailure::Failed { thread, error })),
+                     Err(payload) => failures.push((index, ThreadFailure::Panicked { thread, message: panic_message(payload.as_ref()) })),
+              }
+       }
+       if failures.is_empty() { Ok(values) } else { Err(MultiError { failures, total }) }
+}
+
+/// The `&str` or `String` a `panic!` carries; anything else (`panic_any`) gets a placeholder.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+       payload.downcast_ref::<&str>()
+              .map(|message| (*message).to_string())
+              .or_else(|| payload.downcast_ref::<String>().cloned())
+              .unwrap_or_else(|| "<non-string panic payload>".to_string())
+}
+
+/// A thread handle [`join_all`] can join: [`JoinHandle`], [`ScopedJoinHandle`], or an [`affinity::Pinned`](crate::affinity::Pinned) one.
+pub trait Joinable: sealed::Sealed {
+       type Output;
+
+       /// The thread's name, if it was given one.
+       fn thread_name(&self) -> Option<String>;
+
+       /// Block until the thread finishes.
+       fn join_thread(self) -> thread::Result<Self::Output>;
+}
+
+pub(crate) mod sealed {
+       pub trait Sealed {}
+       impl<T> Sealed for super::JoinHandle<T> {}
+       impl<T> Sealed for super::ScopedJoinHandle<'_, T> {}
+}
+
+impl<T> Joinable for JoinHandle<T> {
+       type Output = T;
+
+       fn thread_name(&self) -> Option<String> { self.thread().name().map(str::to_string) }
+
+       fn join_thread(self) -> thread::Result<T> { self.join() }
+}
+
+impl<T> Joinable for ScopedJoinHandle<'_, T> {
+       type Output = T;
+
+       fn thread_name(&self) -> Option<String> { self.thread().name().map(str::to_string) }
+
+       fn join_thread(self) -> thread::Result<T> { self.join() }
+}
+
+/// How one thread failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThreadFailure<E = Infallible> {
+       Panicked {
+              thread:  Option<String>,
+              message: String,
+       },
+       /// Returned `Err` (only from [`try_join_all`]).
+       Failed {
+              thread: Option<String>,
+              error:  E,
+       },
+}
+
+impl<E> ThreadFailure<E> {
+       pub fn thread(&self) -> Option<&str> {
+              match self {
+                     Self::Panicked { thread, .. } | Self::Failed { thread, .. } => thread.as_deref(),
+              }
+       }
+
+       pub fn is_panic(&self) -> bool { matches!(self, Self::Panicked { .. }) }
+}
+
+impl<E: fmt::Display> fmt::Display for ThreadFailure<E> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              match self {
+                     Self::Panicked { message, .. } => write!(f, "panicked: {message}"),
+                     Self::Failed { error, .. } => write!(f, "failed: {error}"),
+              }
+       }
+}
+
+impl<E: Error + 'static> Error for ThreadFailure<E> {
+       fn source(&self) -> Option<&(dyn Error + 'static)> {
+              match self {
+                     Self::Panicked { .. } => None,
+                     Self::Failed { error, .. } => Some(error),
+              }
+       }
+}
+
+/// Every thread that failed in a [`join_all`] / [`try_join_all`], by position in the handle list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiError<E = Infallible> {
+       failures: Vec<(usize, ThreadFailure<E>)>,
+       total:    usize,
+}
+
+impl<E> MultiError<E> {
+       /// Number of failed threads.
+       pub fn len(&self) -> usize { self.failures.len() }
+
+       /// Always `false`: a join without failures returns `Ok`.
+       pub fn is_empty(&self) -> bool { self.failures.is_empty() }
+
+       /// How many of the failures were panics.
+       pub fn panics(&self) -> usize { self.failures.iter().filter(|(_, failure)| failure.is_panic()).count() }
+
+       pub fn failures(&self) -> &[(usize, ThreadFailure<E>)] { &self.failures }
+
+       pub fn into_failures(self) -> Vec<(usize, ThreadFailure<E>)> { self.failures }
+}
+
+impl<E: fmt::Display> fmt::Display for MultiError<E> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              write!(f, "{} of {} threads failed", self.failures.len(), self.total)?;
+              for (index, failure) in &self.failures {
+                     match failure.thread() {
+                            Some(name) => write!(f, "\n  #{index} ({name}): {failure}")?,
+                            None => write!(f, "\n  #{index}: {failure}")?,
+                     }
+              }
+              Ok(())
+       }
+}
+
+impl<E: Error + 'static> Error for MultiError<E> {
+       /// The first failure (by position).
+       fn source(&self) -> Option<&(dyn Error + 'static)> { self.failures.first().map(|(_, failure)| failure as &(dyn Error + 'static)) }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::num::ParseIntError;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_join_all_keeps_order() {
+              let handles: Vec<_> = (0..5).map(|i| thread::spawn(move || i * i)).collect();
+              assert_eq!(join_all(handles).unwrap(), [0, 1, 4, 9, 16]);
+              assert_eq!(join_all(Vec::<JoinHandle<()>>::new()).unwrap(), []);
+       }
+
+       #[test]
+       fn test_try_join_all_collects_panics_and_errors() {
+              let error = thread::scope(|s| {
+                     let handles = ["1", "x", "3", "boom"].map(|input| {
+                            s.spawn(move || {
+                                   assert!(input != "boom", "no {input}");
+                                   input.parse::<u32>()
+                            })
+                     });
+                     try_join_all(handles).unwrap_err()
+              });
+              assert_eq!((error.len(), error.panics()), (2, 1));
+              assert_eq!(error.failures().iter().map(|(index, _)| *index).collect::<Vec<_>>(), [1, 3]);
+              assert_eq!(error.to_string(), "2 of 4 threads failed\n  #1: failed: invalid digit found in string\n  #3: panicked: no boom");
+              assert!(error.source().unwrap().source().unwrap().is::<ParseIntError>());
+       }
+}
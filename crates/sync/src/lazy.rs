@@ -0,0 +1,166 @@
+//! Lazily initialized value: [`OnceLock`] plus the closure that fills it.
+//!
+//! The first access (through [`Deref`] or [`Lazy::force`]) runs the closure; concurrent first accesses
+//! wait for that one run, and every access after sees the same value. Works in a `static`.
+//!
+//! ## Panics
+//! If the closure panics, the `Lazy` is *poisoned*: the panic propagates to the thread that ran it,
+//! and every later access (from any thread, including ones that were waiting) panics too.
+//! There's no retry: the closure was consumed by the failed run, and whatever it half-did can't be trusted.
+//! std's `LazyLock` behaves the same way.
+//!
+//! ## Example
+//! ```
+//! use std::{collections::HashMap, thread};
+//!
+//! use sync::Lazy;
+//!
+//! static SQUARES: Lazy<HashMap<u32, u32>> = Lazy::new(|| (0..10).map(|n| (n, n * n)).collect());
+//! thread::scope(|s| {
+//!        for n in 0..4 {
+//!               s.spawn(move || assert_eq!(SQUARES[&n], n * n));
+//!        }
+//! });
+//! assert_eq!(Lazy::force(&SQUARES).len(), 10);
+//! ```
+
+use std::{cell::Cell, fmt, ops::Deref};
+
+use crate::{OnceLock, atomic::loom_const_fn};
+
+/// A value computed on first access, by `F`.
+pub struct Lazy<T, F = fn() -> T> {
+       cell: OnceLock<T>,
+       init: Cell<Option<F>>,
+}
+// SAFETY: `init` is only touched by the thread that won the `OnceLock`'s EMPTY -> RUNNING transition
+//         (which moves `F` out and calls it there, hence `F: Send`); everything else goes through the `OnceLock`.
+unsafe impl<T, F: Send> Sync for Lazy<T, F> where OnceLock<T>: Sync {}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+       loom_const_fn! {
+              pub fn new(init: F) -> Self { Self { cell: OnceLock::new(), init: Cell::new(Some(init)) } }
+       }
+
+       /// The value, running the closure first if nobody has yet. Same as `*this`, but reads as intent.
+       ///
+       /// ## Panics
+       /// If the closure panics now, or panicked on an earlier access (see the [module docs](self)).
+       pub fn force(this: &Self) -> &T {
+              if this.cell.is_poisoned() {
+                     panic!("Lazy instance has previously been poisoned");
+              }
+              this.cell.get_or_init(|| match this.init.take() {
+                     Some(init) => init(),
+                     None => unreachable!("the closure is only taken by the one initializing run"),
+              })
+       }
+}
+
+impl<T, F> Lazy<T, F> {
+       /// The value, if initialized. Never runs the closure or blocks.
+       pub fn get(this: &Self) -> Option<&T> { this.cell.get() }
+
+       /// Whether the closure panicked; every access will panic from now on.
+       pub fn is_poisoned(this: &Self) -> bool { this.cell.is_poisoned() }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+       type Target = T;
+
+       fn deref(&self) -> &T { Self::force(self) }
+}
+
+impl<T: Default> Default for Lazy<T> {
+       fn default() -> Self { Self::new(T::default) }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Lazy<T, F> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              match Self::get(self) {
+                     Some(value) => f.debug_tuple("Lazy").field(value).finish(),
+                     None if Self::is_poisoned(self) => f.write_str("Lazy(<poisoned>)"),
+                     None => f.write_str("Lazy(<uninit>)"),
+              }
+       }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+       use std::{panic,
+                 sync::atomic::{AtomicUsize, Ordering::Relaxed},
+                 thread,
+                 time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_initializes_once_under_contention() {
+              let calls = AtomicUsize::new(0);
+              let lazy = Lazy::new(|| {
+                     calls.fetch_add(1, Relaxed);
+                     thread::sleep(Duration::from_millis(10)); // keep the others waiting
+                     "ready".to_string()
+              });
+              assert_eq!(Lazy::get(&lazy), None);
+              thread::scope(|s| {
+                     for _ in 0..8 {
+                            s.spawn(|| assert_eq!(lazy.len(), 5));
+                     }
+              });
+              assert_eq!(calls.load(Relaxed), 1);
+              assert_eq!(Lazy::get(&lazy).map(String::as_str), Some("ready"));
+              assert_eq!(format!("{lazy:?}"), r#"Lazy("ready")"#);
+       }
+
+       #[test]
+       fn test_panic_poisons_and_repanics() {
+              let lazy: Lazy<i32> = Lazy::new(|| panic!("init failed"));
+              let first = panic::catch_unwind(panic::AssertUnwindSafe(|| *Lazy::force(&lazy))).unwrap_err();
+              assert_eq!(first.downcast_ref::<&str>(), Some(&"init failed"));
+              assert!(Lazy::is_poisoned(&lazy));
+              assert_eq!(format!("{lazy:?}"), "Lazy(<poisoned>)");
+
+              let later = panic::catch_unwind(panic::AssertUnwindSafe(|| *lazy)).unwrap_err();
+              assert_eq!(later.downcast_ref::<&str>(), Some(&"Lazy instance has previously been poisoned"));
+       }
+
+       #[test]
+       fn test_default() {
+              let lazy: Lazy<Vec<u8>> = Lazy::default();
+              assert!(lazy.is_empty());
+       }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+       use loom::{sync::{Arc,
+                         atomic::{AtomicUsize, Ordering::Relaxed}},
+                  thread};
+
+       use super::*;
+
+       /// Two threads force at once: in every interleaving the closure runs exactly once and both see its value.
+       #[test]
+       fn loom_racing_initializers() {
+              loom::model(|| {
+                     let calls = Arc::new(AtomicUsize::new(0));
+                     let lazy = Arc::new(Lazy::new({
+                            let calls = calls.clone();
+                            move || {
+                                   calls.fetch_add(1, Relaxed);
+                                   42
+                            }
+                     }));
+                     let other = {
+                            let lazy = lazy.clone();
+                            thread::spawn(move || *Lazy::force(&lazy))
+                     };
+                     assert_eq!(*Lazy::force(&lazy), 42);
+                     assert_eq!(other.join().unwrap(), 42);
+                     assert_eq!(calls.load(Relaxed), 1);
+              });
+       }
+}
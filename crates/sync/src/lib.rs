@@ -0,0 +1,156 @@
+//! # Hand-rolled synchronization primitives for [Rust Atomics and Locks](https://marabos.nl/atomics/)
+//!
+//! Library counterpart to the scratch binaries in the `threads` crate.
+//! Where the binaries demo the std types, this crate builds the equivalents from atomics.
+//!
+//! Under `--cfg loom` the atomics are loom's (see `atomic`), and the modules built on process-wide `static`s are left out:
+//! a static outlives loom's model runs, so its atomics can't be loom's. The `shuttle` feature swaps in shuttle's
+//! atomics (and threads) the same way, for its randomized-scheduling tests, and leaves out the same modules.
+//!
+//! The `async` feature adds futures built the same way, needing no runtime: [`channel::async_oneshot`], `AsyncMutex`
+//! (tasks and threads queueing for one lock), and `block_on`, the single-future executor that drives them from a
+//! plain thread.
+//!
+//! The `traced` feature adds [`traced`]: atomics that log each operation they perform, for following an example
+//! step by step.
+
+pub mod affinity;
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub mod atomic_support;
+pub mod channel;
+pub mod deadline;
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub mod epoch;
+pub mod fences;
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub mod hazard;
+pub mod histogram;
+pub mod myarc;
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub mod parallel;
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub mod parking_lot;
+pub mod priority;
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub mod shutdown;
+#[cfg(feature = "traced")]
+pub mod traced;
+
+mod adaptive_mutex;
+#[cfg(all(feature = "async", not(any(loom, feature = "shuttle"))))]
+mod async_mutex;
+mod atomic;
+mod atomic_arena;
+mod atomic_bitset;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod atomic_cell;
+mod atomic_option_box;
+mod backoff;
+mod barrier;
+#[cfg(feature = "async")]
+mod block_on;
+mod bounded_queue;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod byte_mutex;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod byte_pipe;
+mod cancellation;
+mod condvar;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod double_word;
+mod event;
+mod futex;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod id_gen;
+mod instrumented_mutex;
+mod join;
+mod lazy;
+mod mutex;
+mod once;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod ordered;
+mod park_slot;
+mod phaser;
+mod progress;
+mod rate_limiter;
+mod rcu_cell;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod reclaim;
+mod rwlock;
+mod semaphore;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod sharded_counter;
+mod shared_config;
+mod spin_lock;
+mod stats_cell;
+#[cfg(test)]
+mod test_util;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod thread_local;
+mod thread_pool;
+mod thread_registry;
+mod ticket_lock;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod tracked_mutex;
+#[cfg(not(any(loom, feature = "shuttle")))]
+mod treiber_stack;
+mod triple_buffer;
+mod wait_group;
+#[cfg(feature = "async")]
+mod waker_slot;
+mod work_queue;
+
+pub use adaptive_mutex::{AdaptiveMutex, AdaptiveMutexGuard};
+#[cfg(all(feature = "async", not(any(loom, feature = "shuttle"))))]
+pub use async_mutex::{AsyncMutex, AsyncMutexGuard, AsyncMutexLock};
+pub use atomic_arena::AtomicArena;
+pub use atomic_bitset::AtomicBitSet;
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use atomic_cell::AtomicCell;
+pub use atomic_option_box::AtomicOptionBox;
+pub use backoff::Backoff;
+pub use barrier::{Barrier, BarrierWaitResult};
+#[cfg(feature = "async")]
+pub use block_on::block_on;
+pub use bounded_queue::{BoundedQueue, QueueStats};
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use byte_mutex::{ByteMutex, ByteMutexGuard};
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use byte_pipe::{PipeReader, PipeWriter, byte_pipe};
+pub use cancellation::CancellationToken;
+pub use condvar::{Condvar, WaitTimeoutResult};
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use double_word::AtomicDoubleWord;
+pub use event::Event;
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use id_gen::{IdGen, IdsExhausted};
+pub use instrumented_mutex::{InstrumentedMutex, InstrumentedMutexGuard, LockStats};
+pub use join::{Joinable, MultiError, ThreadFailure, join_all, panic_message, try_join_all};
+pub use lazy::Lazy;
+pub use mutex::{MappedMutexGuard, Mutex, MutexGuard};
+pub use once::{Once, OnceLock};
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use ordered::{LockLevel, Lockable, Ordered};
+pub use phaser::Phaser;
+pub use progress::{Progress, ProgressReporter, ProgressWatcher};
+pub use rate_limiter::RateLimiter;
+pub use rcu_cell::RcuCell;
+pub use rwlock::{MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::{Semaphore, SemaphorePermit};
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use sharded_counter::{CachePadded, ShardedCounter};
+pub use shared_config::{SharedConfig, SubscriptionId};
+pub use spin_lock::{SpinLock, SpinLockGuard};
+pub use stats_cell::{StatsCell, StatsSnapshot};
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use thread_local::ThreadLocal;
+pub use thread_pool::{JobPanic, PanicPolicy, PoolScope, ThreadPool, ThreadPoolBuilder};
+pub use thread_registry::{RegisteredBuilder, Snapshot, ThreadInfo, ThreadRegistry, ThreadState};
+pub use ticket_lock::{TicketLock, TicketLockGuard};
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use tracked_mutex::{OnViolation, TrackedMutex, TrackedMutexGuard};
+#[cfg(not(any(loom, feature = "shuttle")))]
+pub use treiber_stack::{StackRef, TreiberStack};
+pub use triple_buffer::{TripleBuffer, TripleBufferReader, TripleBufferWriter};
+pub use wait_group::WaitGroup;
+pub use work_queue::WorkQueue;
@@ -0,0 +1,403 @@
+//! Futex-based mutex.
+//!
+//! ## [Chapter 9: Building Our Own Locks — Mutex](https://marabos.nl/atomics/building-locks.html#mutex)
+//!
+//! One `AtomicU32` with three states, so an unlock only makes a syscall when someone might be asleep:
+//! ```text
+//! 0: unlocked
+//! 1: locked, no waiters
+//! 2: locked, (possibly) waiters
+//! ```
+//! Contended lockers spin briefly (the lock is often released within nanoseconds), then set 2 and futex-wait.
+//!
+//! No poisoning: a panic while holding the guard simply unlocks.
+//!
+//! [`MutexGuard::map`] narrows a guard to part of the value (a field, an element), still holding the whole lock:
+//! hand out access to one field without exposing the rest.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::Mutex;
+//!
+//! let counter = Mutex::new(0);
+//! thread::scope(|s| {
+//!        for _ in 0..4 {
+//!               s.spawn(|| *counter.lock() += 1);
+//!        }
+//! });
+//! assert_eq!(counter.into_inner(), 4);
+//! ```
+
+use std::{cell::UnsafeCell,
+          marker::PhantomData,
+          mem,
+          ops::{Deref, DerefMut},
+          ptr::NonNull,
+          time::{Duration, Instant}};
+
+use crate::{Condvar,
+            atomic::{AtomicU32,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn, spin_loop},
+            deadline,
+            futex::{wait_until, wake_one},
+            histogram::{WaitSite, WaitTimer}};
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+const CONTENDED: u32 = 2;
+
+/// Mutual exclusion lock that sleeps (futex) rather than spins when contended.
+pub struct Mutex<T> {
+       state: AtomicU32,
+       value: UnsafeCell<T>,
+}
+// SAFETY: the state only lets one thread at a time reach `value`.
+unsafe impl<T> Sync for Mutex<T> where T: Send {}
+
+impl<T> Mutex<T> {
+       loom_const_fn! {
+              pub fn new(value: T) -> Self { Self { state: AtomicU32::new(UNLOCKED), value: UnsafeCell::new(value) } }
+       }
+
+       /// Block until the lock is ours.
+       pub fn lock(&self) -> MutexGuard<'_, T> {
+              if self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_err() {
+                     self.lock_contended(None);
+              }
+              MutexGuard { mutex: self }
+       }
+
+       /// Lock once `ready` holds of the value, waiting on `condvar` in between: whoever makes it true must notify it.
+       pub fn lock_when(&self, condvar: &Condvar, mut ready: impl FnMut(&mut T) -> bool) -> MutexGuard<'_, T> {
+              condvar.wait_while(self.lock(), |value| !ready(value))
+       }
+
+       /// As [`lock`](Self::lock), giving up after `timeout`.
+       pub fn try_lock_for(&self, timeout: Duration) -> Option<MutexGuard<'_, T>> { self.lock_until(deadline::after(timeout)) }
+
+       /// As [`lock`](Self::lock), giving up at `deadline`.
+       pub fn try_lock_until(&self, deadline: Instant) -> Option<MutexGuard<'_, T>> { self.lock_until(Some(deadline)) }
+
+       fn lock_until(&self, deadline: Option<Instant>) -> Option<MutexGuard<'_, T>> {
+              let locked = self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_ok() || self.lock_contended(deadline);
+              locked.then(|| MutexGuard { mutex: self }) // lazily: a guard built and dropped would unlock
+       }
+
+       /// Take the lock only if it's free right now.
+       pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+              self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).ok().map(|_| MutexGuard { mutex: self })
+       }
+
+       /// No locking needed: `&mut self` proves exclusive access.
+       pub fn get_mut(&mut self) -> &mut T { self.value.get_mut() }
+
+       pub fn into_inner(self) -> T { self.value.into_inner() }
+
+       /// Slow path of locking; `false` if `deadline` passed first.
+       #[cold]
+       fn lock_contended(&self, deadline: Option<Instant>) -> bool {
+              let mut waited = WaitTimer::new(WaitSite::Mutex);
+              waited.start();
+              let mut spin_count = 0;
+              // spin only while merely locked: if others already sleep, queue up behind them
+              while self.state.load(Relaxed) == LOCKED && spin_count < 100 {
+                     spin_count += 1;
+                     spin_loop();
+              }
+              if self.state.compare_exchange(UNLOCKED, LOCKED, Acquire, Relaxed).is_ok() {
+                     return true;
+              }
+              // we can't tell whether other waiters remain after we wake, so always lock as CONTENDED
+              // (giving up leaves it CONTENDED too: at worst the next unlock makes one needless wake call)
+              while self.state.swap(CONTENDED, Acquire) != UNLOCKED {
+                     if !wait_until(&self.state, CONTENDED, deadline) {
+                            return false;
+                     }
+              }
+              true
+       }
+
+       /// Release the lock, waking one sleeper if there may be any.
+       fn unlock(&self) {
+              if self.state.swap(UNLOCKED, Release) == CONTENDED {
+                     wake_one(&self.state);
+              }
+       }
+}
+
+impl<T: Default> Default for Mutex<T> {
+       fn default() -> Self { Self::new(T::default()) }
+}
+
+/// Exclusive access to a [`Mutex`]'s value; unlocks on drop.
+///
+/// Shared between threads, a guard hands each of them `&T`, so it's `Sync` only if `T` is:
+/// ```compile_fail
+/// fn shareable<G: Sync>() {}
+/// shareable::<sync::MutexGuard<'static, std::cell::Cell<u32>>>(); // error: `Cell<u32>` cannot be shared between threads
+/// ```
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct MutexGuard<'a, T> {
+       mutex: &'a Mutex<T>,
+}
+// SAFETY: the guard only hands out `&T` when shared, so `T: Sync` suffices (and is needed: `&Mutex<T>` alone would
+// make it `Sync` for any `T: Send`).
+unsafe impl<T> Sync for MutexGuard<'_, T> where T: Sync {}
+impl<'a, T> MutexGuard<'a, T> {
+       /// The mutex this guard holds, so [`Condvar`](crate::Condvar) can re-lock it after waiting.
+       pub(crate) fn mutex(&self) -> &'a Mutex<T> { self.mutex }
+
+       /// A guard for the part of the value `f` picks, holding the lock until it's dropped.
+       /// (An associated function, so it can't shadow a method of `T`: `MutexGuard::map(guard, |v| &mut v.field)`.)
+       pub fn map<U: ?Sized>(mut this: Self, f: impl FnOnce(&mut T) -> &mut U) -> MappedMutexGuard<'a, T, U> {
+              let value = NonNull::from(f(&mut this)); // if `f` panics, `this` unlocks as it unwinds
+              let mutex = this.mutex;
+              mem::forget(this); // the mapped guard unlocks instead
+              MappedMutexGuard { mutex, value, _borrow: PhantomData }
+       }
+}
+impl<T> Deref for MutexGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: the guard's existence proves we hold the lock.
+              unsafe { &*self.mutex.value.get() }
+       }
+}
+impl<T> DerefMut for MutexGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T {
+              // SAFETY: the guard's existence proves we hold the lock.
+              unsafe { &mut *self.mutex.value.get() }
+       }
+}
+impl<T> Drop for MutexGuard<'_, T> {
+       fn drop(&mut self) { self.mutex.unlock(); }
+}
+
+/// Exclusive access to part of a [`Mutex`]'s value, from [`MutexGuard::map`]; unlocks on drop.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct MappedMutexGuard<'a, T, U: ?Sized> {
+       mutex:   &'a Mutex<T>,
+       value:   NonNull<U>,
+       _borrow: PhantomData<&'a mut U>,
+}
+// SAFETY: as `&mut U` would be: the lock we hold makes the pointer an exclusive borrow.
+unsafe impl<T, U: ?Sized + Sync> Sync for MappedMutexGuard<'_, T, U> {}
+// SAFETY: as `MutexGuard` (unlocking from another thread is fine for this mutex), plus `&mut U` moving with it.
+unsafe impl<T: Send, U: ?Sized + Send> Send for MappedMutexGuard<'_, T, U> {}
+
+impl<'a, T, U: ?Sized> MappedMutexGuard<'a, T, U> {
+       /// Narrow further.
+       pub fn map<V: ?Sized>(mut this: Self, f: impl FnOnce(&mut U) -> &mut V) -> MappedMutexGuard<'a, T, V> {
+              let value = NonNull::from(f(&mut this));
+              let mutex = this.mutex;
+              mem::forget(this);
+              MappedMutexGuard { mutex, value, _borrow: PhantomData }
+       }
+}
+impl<T, U: ?Sized> Deref for MappedMutexGuard<'_, T, U> {
+       type Target = U;
+
+       fn deref(&self) -> &U {
+              // SAFETY: points into the value, which the lock we hold keeps ours.
+              unsafe { self.value.as_ref() }
+       }
+}
+impl<T, U: ?Sized> DerefMut for MappedMutexGuard<'_, T, U> {
+       fn deref_mut(&mut self) -> &mut U {
+              // SAFETY: points into the value, which the lock we hold keeps ours.
+              unsafe { self.value.as_mut() }
+       }
+}
+impl<T, U: ?Sized> Drop for MappedMutexGuard<'_, T, U> {
+       fn drop(&mut self) { self.mutex.unlock(); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_contended_counter() {
+              const NUM_THREADS: usize = 8;
+              const PER_THREAD: usize = 10_000;
+              let counter = Mutex::new(0);
+              thread::scope(|s| {
+                     for _ in 0..NUM_THREADS {
+                            s.spawn(|| {
+                                   for _ in 0..PER_THREAD {
+                                          *counter.lock() += 1;
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(counter.into_inner(), NUM_THREADS * PER_THREAD);
+       }
+
+       #[test]
+       fn test_try_lock() {
+              let mutex = Mutex::new(());
+              let guard = mutex.lock();
+              assert!(mutex.try_lock().is_none());
+              assert!(mutex.try_lock().is_none(), "a failed try_lock leaves the holder's lock alone");
+              drop(guard);
+              assert!(mutex.try_lock().is_some());
+       }
+
+       #[test]
+       fn test_try_lock_for() {
+              let mutex = Mutex::new(0);
+              thread::scope(|s| {
+                     let guard = mutex.lock();
+                     let waiter = s.spawn(|| mutex.try_lock_for(Duration::from_millis(5)).is_some());
+                     assert!(!waiter.join().unwrap());
+                     // the waiter left the state CONTENDED behind; unlocking still works
+                     drop(guard);
+                     let waiter = s.spawn(|| mutex.try_lock_for(Duration::from_secs(60)).map(|mut guard| *guard += 1));
+                     waiter.join().unwrap().expect("lock is free");
+              });
+              assert_eq!(mutex.try_lock_until(Instant::now()).map(|guard| *guard), Some(1));
+       }
+
+       #[test]
+       fn test_mapped_guard_holds_the_lock() {
+              let mutex = Mutex::new((String::from("name"), vec![1]));
+              let mut items = MutexGuard::map(mutex.lock(), |(_, items)| items);
+              items.push(2);
+              assert!(mutex.try_lock().is_none(), "the whole value stays locked");
+              let mut first = MappedMutexGuard::map(items, |items| &mut items[0]);
+              *first += 10;
+              drop(first);
+              assert_eq!(mutex.into_inner(), (String::from("name"), vec![11, 2]));
+       }
+
+       #[test]
+       fn test_panicking_map_unlocks() {
+              let mutex = Mutex::new(0);
+              let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                     MutexGuard::map(mutex.lock(), |_| -> &mut i32 { panic!("no field") })
+              }));
+              assert!(result.is_err());
+              assert!(mutex.try_lock().is_some());
+       }
+
+       #[test]
+       fn test_lock_when() {
+              let batch = Mutex::new(Vec::new());
+              let filled = Condvar::new();
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            for i in 0..10 {
+                                   batch.lock().push(i);
+                                   filled.notify_all();
+                            }
+                     });
+                     let guard = batch.lock_when(&filled, |batch| batch.len() >= 5);
+                     assert!(guard.len() >= 5);
+              });
+       }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+       use loom::{sync::{Arc,
+                         atomic::{AtomicUsize, Ordering::Relaxed}},
+                  thread};
+
+       use super::*;
+
+       /// The count is bumped with a Relaxed load then store, so only the lock orders the two critical sections:
+       /// if unlock didn't release (or lock didn't acquire), some interleaving would read a stale 0 and lose an update.
+       #[test]
+       fn loom_lock_orders_critical_sections() {
+              fn increment(mutex: &Mutex<()>, count: &AtomicUsize) {
+                     let _guard = mutex.lock();
+                     count.store(count.load(Relaxed) + 1, Relaxed);
+              }
+              loom::model(|| {
+                     let mutex = Arc::new(Mutex::new(()));
+                     let count = Arc::new(AtomicUsize::new(0));
+                     let other = {
+                            let (mutex, count) = (mutex.clone(), count.clone());
+                            thread::spawn(move || increment(&mutex, &count))
+                     };
+                     increment(&mutex, &count);
+                     other.join().unwrap();
+                     assert_eq!(count.load(Relaxed), 2);
+              });
+       }
+
+       /// `try_lock` never succeeds while the other thread holds the lock.
+       #[test]
+       fn loom_try_lock_excludes() {
+              loom::model(|| {
+                     let mutex = Arc::new(Mutex::new(()));
+                     let inside = Arc::new(AtomicUsize::new(0));
+                     let other = {
+                            let (mutex, inside) = (mutex.clone(), inside.clone());
+                            thread::spawn(move || {
+                                   let _guard = mutex.lock();
+                                   inside.store(1, Relaxed);
+                                   inside.store(0, Relaxed);
+                            })
+                     };
+                     if let Some(_guard) = mutex.try_lock() {
+                            assert_eq!(inside.load(Relaxed), 0);
+                     }
+                     other.join().unwrap();
+              });
+       }
+}
+
+#[cfg(all(test, feature = "shuttle"))]
+mod shuttle_tests {
+       use std::sync::Arc;
+
+       use shuttle::thread;
+
+       use super::*;
+       use crate::atomic::{AtomicUsize, Ordering::Relaxed, shuttle_check};
+
+       /// Four threads locking three times each, some with `try_lock`: more than loom gets through. Every step inside
+       /// the lock is a point the scheduler can switch at, so a second thread getting in would be seen.
+       #[test]
+       fn shuttle_lock_excludes_under_contention() {
+              shuttle_check(|| {
+                     let mutex = Arc::new(Mutex::new(0));
+                     let inside = Arc::new(AtomicUsize::new(0));
+                     let threads: Vec<_> = (0..4)
+                            .map(|t| {
+                                   let (mutex, inside) = (mutex.clone(), inside.clone());
+                                   thread::spawn(move || {
+                                          for i in 0..3 {
+                                                 let mut guard = if (t + i) % 3 == 0 {
+                                                        loop {
+                                                               match mutex.try_lock() {
+                                                                      Some(guard) => break guard,
+                                                                      None => thread::yield_now(),
+                                                               }
+                                                        }
+                                                 } else {
+                                                        mutex.lock()
+                                                 };
+                                                 assert_eq!(inside.fetch_add(1, Relaxed), 0, "two threads hold the lock");
+                                                 *guard += 1;
+                                                 inside.fetch_sub(1, Relaxed);
+                                          }
+                                   })
+                            })
+                            .collect();
+                     for thread in threads {
+                            thread.join().unwrap();
+                     }
+                     assert_eq!(*mutex.lock(), 12);
+              });
+       }
+}
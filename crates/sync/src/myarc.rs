@@ -0,0 +1,335 @@
+//! Reference-counted shared ownership, from scratch.
+//!
+//! ## [Chapter 6: Building Our Own "Arc"](https://marabos.nl/atomics/building-arc.html)
+//!
+//! One heap allocation holds the count next to the value; every `Arc` is a pointer to it.
+//! - `clone`: `Relaxed` increment (we already hold a reference, so nothing can be freed under us)
+//! - `drop`: `Release` decrement; whoever takes the count to zero runs an `Acquire` fence before freeing,
+//!   so every other owner's last use of the value *happens-before* the drop of it (see [`release_ref`])
+//!
+//! Exclusive access (`get_mut`, `try_unwrap`, `into_inner`, `make_mut`) hinges on seeing a count of 1.
+//! Since we hold one of the references, nobody can *add* one behind our back;
+//! the `Acquire` on that observation pairs with the `Release` decrements of the owners that left,
+//! so their last accesses happen-before ours. (Same reasoning as the fence in `drop`.)
+//!
+//! Named `myarc` so it never gets confused with `std::sync::Arc` in a `use` list.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::myarc::Arc;
+//!
+//! let shared = Arc::new(vec![1, 2, 3]);
+//! let worker = thread::spawn({
+//!        let shared = shared.clone();
+//!        move || shared.iter().sum::<i32>()
+//! });
+//! assert_eq!(worker.join().unwrap(), 6);
+//! assert_eq!(Arc::strong_count(&shared), 1);
+//! ```
+
+use std::{fmt, ops::Deref, process, ptr::NonNull};
+
+use crate::{atomic::{AtomicUsize,
+                     Ordering::{Acquire, Relaxed}},
+            fences::release_ref};
+
+struct ArcData<T> {
+       ref_count: AtomicUsize,
+       data:      T,
+}
+
+/// Thread-safe reference-counted pointer to a `T` on the heap.
+pub struct Arc<T> {
+       ptr: NonNull<ArcData<T>>,
+}
+// SAFETY: sending an `Arc` shares the `T` (needs `Sync`) and may drop it on the other thread (needs `Send`).
+unsafe impl<T: Send + Sync> Send for Arc<T> {}
+// SAFETY: as above; `&Arc` can be cloned into an owned `Arc` on another thread.
+unsafe impl<T: Send + Sync> Sync for Arc<T> {}
+
+impl<T> Arc<T> {
+       pub fn new(data: T) -> Self { Self { ptr: NonNull::from(Box::leak(Box::new(ArcData { ref_count: AtomicUsize::new(1), data }))) } }
+
+       /// Number of `Arc`s pointing at this allocation. Only a snapshot: other threads may clone or drop meanwhile.
+       ///
+       /// Associated function (not a method) so it can't shadow a method of `T` through `Deref`.
+       pub fn strong_count(this: &Self) -> usize { this.data().ref_count.load(Relaxed) }
+
+       /// Mutable access, if this is the only `Arc` to the value.
+       pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+              if this.data().ref_count.load(Acquire) == 1 {
+                     // SAFETY: count 1 and we hold `&mut` to that one `Arc`: no other access exists or can be created.
+                     Some(unsafe { &mut this.ptr.as_mut().data })
+              } else {
+                     None
+              }
+       }
+
+       /// The value, if this is the only `Arc` to it; otherwise the `Arc` back, untouched.
+       ///
+       /// ## Errors
+       /// If other `Arc`s to the value exist.
+       pub fn try_unwrap(this: Self) -> Result<T, Self> {
+              if this.data().ref_count.compare_exchange(1, 0, Acquire, Relaxed).is_err() {
+                     return Err(this);
+              }
+              // SAFETY: we took the count from 1 to 0, so we own the allocation outright.
+              Ok(unsafe { Self::take_data(this) })
+       }
+
+       /// The value, if this was the last `Arc` to it. Unlike [`try_unwrap`](Self::try_unwrap) the `Arc` is always
+       /// consumed, so when several owners race to call this, exactly one of them gets the value.
+       pub fn into_inner(this: Self) -> Option<T> {
+              if !release_ref(&this.data().ref_count) {
+                     std::mem::forget(this); // our reference is already given back
+                     return None;
+              }
+              // SAFETY: that was the last reference, as in `drop`.
+              Some(unsafe { Self::take_data(this) })
+       }
+
+       /// Move the value out and free the allocation, without running `Drop for Arc`.
+       ///
+       /// ## Safety
+       /// The count must have reached 0 through `this`, with an acquire on the way.
+       unsafe fn take_data(this: Self) -> T {
+              let this = std::mem::ManuallyDrop::new(this);
+              // SAFETY: per the caller, nobody else can reach the allocation; the box is never touched again.
+              let data = unsafe { Box::from_raw(this.ptr.as_ptr()) };
+              data.data
+       }
+
+       /// Whether both point at the same allocation.
+       pub fn ptr_eq(this: &Self, other: &Self) -> bool { this.ptr == other.ptr }
+
+       fn data(&self) -> &ArcData<T> {
+              // SAFETY: the allocation lives as long as any `Arc` (such as `self`) points at it.
+              unsafe { self.ptr.as_ref() }
+       }
+}
+
+impl<T: Clone> Arc<T> {
+       /// Mutable access, cloning the value into a fresh allocation first if it is shared (clone-on-write).
+       ///
+       /// Other `Arc`s keep the old value; `this` no longer points at it.
+       pub fn make_mut(this: &mut Self) -> &mut T {
+              if this.data().ref_count.load(Acquire) != 1 {
+                     *this = Self::new((**this).clone());
+              }
+              // SAFETY: either the count was 1 (as in `get_mut`) or `this` is a fresh allocation nobody else has seen.
+              unsafe { &mut this.ptr.as_mut().data }
+       }
+}
+
+impl<T> Deref for Arc<T> {
+       type Target = T;
+
+       fn deref(&self) -> &T { &self.data().data }
+}
+
+impl<T> Clone for Arc<T> {
+       fn clone(&self) -> Self {
+              // a leaked-`Arc` loop could overflow the count and cause a use-after-free; abort instead (as std does)
+              if self.data().ref_count.fetch_add(1, Relaxed) > usize::MAX / 2 {
+                     process::abort();
+              }
+              Self { ptr: self.ptr }
+       }
+}
+
+impl<T> Drop for Arc<T> {
+       fn drop(&mut self) {
+              if release_ref(&self.data().ref_count) {
+                     // SAFETY: that was the last reference; nobody else can reach the allocation any more.
+                     drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+              }
+       }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Arc<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { fmt::Debug::fmt(&**self, f) }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::{Barrier, atomic::AtomicUsize},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::test_util::DropCounter;
+
+       /// Kept small enough for Miri: `cargo +nightly miri test --package sync myarc`
+       const CLONES: usize = if cfg!(miri) { 10 } else { 1_000 };
+
+       #[test]
+       fn test_dropped_once_after_last_reference() {
+              let drops = AtomicUsize::new(0);
+              let a = Arc::new(("hello", DropCounter(&drops)));
+              let b = a.clone();
+              assert_eq!(Arc::strong_count(&a), 2);
+              assert!(Arc::ptr_eq(&a, &b));
+              drop(a);
+              assert_eq!(drops.load(Relaxed), 0);
+              assert_eq!(b.0, "hello");
+              drop(b);
+              assert_eq!(drops.load(Relaxed), 1);
+       }
+
+       #[test]
+       fn test_concurrent_clone_and_drop() {
+              let drops = AtomicUsize::new(0);
+              let shared = Arc::new(DropCounter(&drops));
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            let shared = shared.clone();
+                            s.spawn(move || {
+                                   let clones: Vec<_> = (0..CLONES).map(|_| shared.clone()).collect();
+                                   drop(clones);
+                            });
+                     }
+              });
+              assert_eq!(Arc::strong_count(&shared), 1);
+              assert_eq!(drops.load(Relaxed), 0);
+              drop(shared);
+              assert_eq!(drops.load(Relaxed), 1);
+       }
+
+       #[test]
+       fn test_get_mut_and_try_unwrap_detect_sharing() {
+              let mut a = Arc::new(1);
+              *Arc::get_mut(&mut a).unwrap() += 1;
+              let b = a.clone();
+              assert!(Arc::get_mut(&mut a).is_none());
+              let a = Arc::try_unwrap(a).unwrap_err();
+              drop(b);
+              assert_eq!(Arc::try_unwrap(a).ok(), Some(2));
+       }
+
+       #[test]
+       fn test_make_mut_clones_only_when_shared() {
+              let mut a = Arc::new(vec![1]);
+              let before = a.ptr;
+              Arc::make_mut(&mut a).push(2);
+              assert_eq!(a.ptr, before); // unique: modified in place
+
+              let b = a.clone();
+              Arc::make_mut(&mut a).push(3);
+              assert!(!Arc::ptr_eq(&a, &b));
+              assert_eq!((*a).clone(), vec![1, 2, 3]);
+              assert_eq!((*b).clone(), vec![1, 2]);
+              assert_eq!(Arc::strong_count(&b), 1);
+       }
+
+       #[test]
+       fn test_uniqueness_under_concurrent_clones() {
+              const WORKERS: usize = 4;
+              let mut shared = Arc::new(0);
+              let (started, finished) = (Barrier::new(WORKERS + 1), Barrier::new(WORKERS + 1));
+              thread::scope(|s| {
+                     for _ in 0..WORKERS {
+                            let shared = shared.clone();
+                            let (started, finished) = (&started, &finished);
+                            s.spawn(move || {
+                                   started.wait();
+                                   for _ in 0..CLONES {
+                                          drop(shared.clone());
+                                   }
+                                   finished.wait();
+                            });
+                     }
+                     started.wait();
+                     // the count swings up and down meanwhile, but never to 1
+                     for _ in 0..CLONES {
+                            assert!(Arc::get_mut(&mut shared).is_none());
+                     }
+                     finished.wait();
+              });
+              *Arc::get_mut(&mut shared).expect("all workers are done") += 1;
+              assert_eq!(Arc::try_unwrap(shared).ok(), Some(1));
+       }
+
+       #[test]
+       fn test_into_inner_exactly_one_winner() {
+              let drops = AtomicUsize::new(0);
+              let shared = Arc::new(DropCounter(&drops));
+              let winners = thread::scope(|s| {
+                     let handles: Vec<_> = (0..4)
+                            .map(|_| {
+                                   let shared = shared.clone();
+                                   s.spawn(move || Arc::into_inner(shared).is_some())
+                            })
+                            .collect();
+                     let main_won = Arc::into_inner(shared).is_some();
+                     handles.into_iter().map(|handle| handle.join().unwrap()).filter(|&won| won).count() + usize::from(main_won)
+              });
+              assert_eq!(winners, 1);
+              assert_eq!(drops.load(Relaxed), 1);
+       }
+
+       #[test]
+       fn test_last_drop_on_other_thread() {
+              let drops = AtomicUsize::new(0);
+              thread::scope(|s| {
+                     let shared = Arc::new(DropCounter(&drops));
+                     let handles: Vec<_> = (0..4)
+                            .map(|_| {
+                                   let shared = shared.clone();
+                                   s.spawn(move || drop(shared))
+                            })
+                            .collect();
+                     drop(shared);
+                     handles.into_iter().for_each(|handle| handle.join().unwrap());
+              });
+              assert_eq!(drops.load(Relaxed), 1);
+       }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+       use loom::{sync::atomic::{AtomicUsize, Ordering::Relaxed},
+                  thread};
+
+       use super::*;
+
+       /// Checks, when dropped, that both owners' writes are visible.
+       struct Written(AtomicUsize);
+
+       impl Drop for Written {
+              fn drop(&mut self) {
+                     assert_eq!(self.0.load(Relaxed), 2, "the dropping thread missed a write");
+              }
+       }
+
+       /// Each owner writes with Relaxed then drops its `Arc`: whichever drops last frees the value, and must see the
+       /// other's write (the release decrements and the acquire fence); a weaker drop would let it read a stale count.
+       #[test]
+       fn loom_last_drop_sees_every_write() {
+              loom::model(|| {
+                     let mine = Arc::new(Written(AtomicUsize::new(0)));
+                     let theirs = mine.clone();
+                     let other = thread::spawn(move || {
+                            theirs.0.fetch_add(1, Relaxed);
+                            drop(theirs);
+                     });
+                     mine.0.fetch_add(1, Relaxed);
+                     drop(mine);
+                     other.join().unwrap();
+              });
+       }
+
+       /// Two owners race `into_inner`: exactly one gets the value.
+       #[test]
+       fn loom_into_inner_race() {
+              loom::model(|| {
+                     let mine = Arc::new(7);
+                     let theirs = mine.clone();
+                     let other = thread::spawn(move || Arc::into_inner(theirs));
+                     let got = [Arc::into_inner(mine), other.join().unwrap()];
+                     assert_eq!(got.iter().flatten().count(), 1);
+              });
+       }
+}
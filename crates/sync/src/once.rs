@@ -0,0 +1,279 @@
+//! One-time initialization: [`Once`] runs a closure exactly once, [`OnceLock`] stores what it produced.
+//!
+//! ## States
+//! ```text
+//! EMPTY ──first caller──> RUNNING ──closure returns──> READY
+//!   ^                        │
+//!   └──closure fails (Err)───┤
+//!                            └──closure panics──> POISONED
+//! ```
+//! Everyone else arriving during `RUNNING` futex-waits on the state word.
+//! A failed `get_or_try_init` puts the state back to `EMPTY`, so the next caller tries again;
+//! a *panic* poisons it, and every later initialization attempt panics too (the half-done work can't be trusted).
+//!
+//! The `atomics-demos ch1 interior-mut` example demos std's `OnceLock`; this is the same thing from an `AtomicU32`.
+//!
+//! Under `--cfg loom` the state word is a `loom` atomic (see [`atomic`](crate::atomic)),
+//! so the model checker can explore racing initializers (see [`Lazy`](crate::Lazy)'s loom test).
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::OnceLock;
+//!
+//! static CONFIG: OnceLock<String> = OnceLock::new();
+//! let lengths: Vec<usize> = thread::scope(|s| {
+//!        let handles: Vec<_> = (0..4).map(|_| s.spawn(|| CONFIG.get_or_init(|| "loaded once".to_string()).len())).collect();
+//!        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+//! });
+//! assert_eq!(lengths, [11; 4]);
+//! ```
+
+use std::{cell::UnsafeCell,
+          convert::Infallible,
+          fmt,
+          mem::{self, MaybeUninit}};
+
+use crate::{atomic::{AtomicU32,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn},
+            futex::{wait, wake_all}};
+
+const EMPTY: u32 = 0;
+const RUNNING: u32 = 1;
+const READY: u32 = 2;
+const POISONED: u32 = 3;
+
+/// Run a closure at most once (successfully), however many threads ask.
+pub struct Once {
+       state: AtomicU32,
+}
+
+impl Once {
+       loom_const_fn! {
+              pub fn new() -> Self { Self { state: AtomicU32::new(EMPTY) } }
+       }
+
+       /// Whether a closure has run to completion.
+       pub fn is_completed(&self) -> bool { self.state.load(Acquire) == READY }
+
+       /// Whether a closure panicked part way through.
+       pub fn is_poisoned(&self) -> bool { self.state.load(Acquire) == POISONED }
+
+       /// Run `f` if no closure has completed yet; otherwise wait for the one running, or return at once.
+       ///
+       /// ## Panics
+       /// If the `Once` is poisoned. If `f` panics, the panic propagates and the `Once` becomes poisoned.
+       pub fn call_once(&self, f: impl FnOnce()) {
+              let result: Result<(), Infallible> = self.try_call_once(|| {
+                     f();
+                     Ok(())
+              });
+              let Ok(()) = result;
+       }
+
+       /// As [`call_once`](Self::call_once), but an `Err` from `f` resets the `Once` for the next caller.
+       fn try_call_once<E>(&self, f: impl FnOnce() -> Result<(), E>) -> Result<(), E> {
+              loop {
+                     match self.state.compare_exchange(EMPTY, RUNNING, Acquire, Acquire) {
+                            Ok(_) => {
+                                   let poison_on_unwind = PoisonOnUnwind(&self.state);
+                                   let result = f();
+                                   mem::forget(poison_on_unwind);
+                                   self.state.store(if result.is_ok() { READY } else { EMPTY }, Release);
+                                   wake_all(&self.state);
+                                   return result;
+                            }
+                            Err(READY) => return Ok(()),
+                            Err(POISONED) => panic!("Once instance has previously been poisoned"),
+                            Err(running) => wait(&self.state, running),
+                     }
+              }
+       }
+}
+
+impl Default for Once {
+       fn default() -> Self { Self::new() }
+}
+
+impl fmt::Debug for Once {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("Once").field("state", &self.state.load(Acquire)).finish()
+       }
+}
+
+/// Poisons the state if the initializing closure unwinds.
+struct PoisonOnUnwind<'a>(&'a AtomicU32);
+impl Drop for PoisonOnUnwind<'_> {
+       fn drop(&mut self) {
+              self.0.store(POISONED, Release);
+              wake_all(self.0);
+       }
+}
+
+/// A value written at most once, then shared by reference.
+pub struct OnceLock<T> {
+       once:  Once,
+       value: UnsafeCell<MaybeUninit<T>>,
+}
+// SAFETY: the value is written once (by the thread that won EMPTY -> RUNNING) before READY is released,
+//         and only read after READY is acquired; sharing hands out `&T` across threads, hence `Sync + Send`.
+unsafe impl<T> Sync for OnceLock<T> where T: Sync + Send {}
+// SAFETY: owning a `OnceLock<T>` is owning (at most) a `T`.
+unsafe impl<T> Send for OnceLock<T> where T: Send {}
+
+impl<T> OnceLock<T> {
+       loom_const_fn! {
+              pub fn new() -> Self { Self { once: Once::new(), value: UnsafeCell::new(MaybeUninit::uninit()) } }
+       }
+
+       /// The value, if initialized. Never blocks.
+       pub fn get(&self) -> Option<&T> {
+              // SAFETY: READY (acquired by `is_completed`) means the value was fully written and is never written again.
+              self.once.is_completed().then(|| unsafe { (*self.value.get()).assume_init_ref() })
+       }
+
+       /// Store `value` if still empty (waiting out a running initializer).
+       ///
+       /// ## Errors
+       /// If the lock was already initialized; `value` is handed back.
+       pub fn set(&self, value: T) -> Result<(), T> {
+              let mut value = Some(value);
+              self.get_or_init(|| value.take().expect("only taken here"));
+              value.map_or(Ok(()), Err)
+       }
+
+       /// The value, initializing it with `f` if nobody has yet. Concurrent callers wait for the one running `f`.
+       ///
+       /// ## Panics
+       /// If the lock is poisoned, or `f` panics (which poisons it).
+       pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+              let result: Result<&T, Infallible> = self.get_or_try_init(|| Ok(f()));
+              let Ok(value) = result;
+              value
+       }
+
+       /// As [`get_or_init`](Self::get_or_init), but a failing `f` leaves the lock empty for the next caller.
+       ///
+       /// ## Errors
+       /// Whatever `f` returned.
+       pub fn get_or_try_init<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+              if let Some(value) = self.get() {
+                     return Ok(value);
+              }
+              self.once.try_call_once(|| {
+                     let value = f()?;
+                     // SAFETY: we hold RUNNING, so nobody else reads or writes the slot.
+                     unsafe { (*self.value.get()).write(value) };
+                     Ok(())
+              })?;
+              Ok(self.get().expect("initialized by us or the thread we waited for"))
+       }
+
+       pub fn is_poisoned(&self) -> bool { self.once.is_poisoned() }
+
+       /// The value, if initialized.
+       pub fn into_inner(mut self) -> Option<T> { self.take() }
+
+       /// Take the value out, leaving the lock empty. `&mut self` rules out concurrent access.
+       pub fn take(&mut self) -> Option<T> {
+              // Relaxed (rather than `get_mut`, which loom's atomics lack): `&mut self` means nobody to synchronize with
+              if self.once.state.load(Relaxed) != READY {
+                     return None;
+              }
+              self.once.state.store(EMPTY, Relaxed);
+              // SAFETY: it was READY, and is now EMPTY so the value won't be read or dropped again.
+              Some(unsafe { self.value.get_mut().assume_init_read() })
+       }
+}
+
+impl<T> Default for OnceLock<T> {
+       fn default() -> Self { Self::new() }
+}
+
+impl<T> Drop for OnceLock<T> {
+       fn drop(&mut self) {
+              if self.once.state.load(Relaxed) == READY {
+                     // SAFETY: READY means the value was fully written.
+                     unsafe { self.value.get_mut().assume_init_drop() }
+              }
+       }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceLock<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              match self.get() {
+                     Some(value) => f.debug_tuple("OnceLock").field(value).finish(),
+                     None => f.write_str("OnceLock(<uninit>)"),
+              }
+       }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+       use std::{panic, sync::atomic::AtomicUsize, thread, time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_contended_initialization_runs_once() {
+              let calls = AtomicUsize::new(0);
+              let lock = OnceLock::new();
+              let values: Vec<_> = thread::scope(|s| {
+                     let handles: Vec<_> = (0..8)
+                            .map(|i| {
+                                   let (lock, calls) = (&lock, &calls);
+                                   s.spawn(move || {
+                                          *lock.get_or_init(|| {
+                                                 calls.fetch_add(1, Relaxed);
+                                                 thread::sleep(Duration::from_millis(10)); // keep the others waiting
+                                                 i
+                                          })
+                                   })
+                            })
+                            .collect();
+                     handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+              });
+              assert_eq!(calls.into_inner(), 1);
+              assert!(values.iter().all(|&value| value == values[0]));
+              assert_eq!(lock.set(99), Err(99));
+       }
+
+       #[test]
+       fn test_failed_try_init_allows_retry() {
+              let lock = OnceLock::new();
+              assert_eq!(lock.get_or_try_init(|| Err("not yet")), Err("not yet"));
+              assert_eq!(lock.get(), None);
+              assert_eq!(lock.get_or_try_init(|| Ok::<_, &str>(5)), Ok(&5));
+              assert_eq!(lock.into_inner(), Some(5));
+       }
+
+       #[test]
+       fn test_panic_poisons() {
+              let lock = OnceLock::<i32>::new();
+              assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| lock.get_or_init(|| panic!("init failed")))).is_err());
+              assert!(lock.is_poisoned());
+              assert_eq!(lock.get(), None);
+              assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| lock.get_or_init(|| 1))).is_err());
+       }
+
+       #[test]
+       fn test_once_call_once() {
+              let once = Once::new();
+              let calls = AtomicUsize::new(0);
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   once.call_once(|| {
+                                          calls.fetch_add(1, Relaxed);
+                                   })
+                            });
+                     }
+              });
+              assert!(once.is_completed());
+              assert_eq!(calls.into_inner(), 1);
+       }
+}
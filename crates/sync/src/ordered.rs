@@ -0,0 +1,199 @@
+//! Lock ordering checked at compile time: every lock gets a level, and a thread only ever locks upwards.
+//!
+//! [`TrackedMutex`](crate::TrackedMutex) reports an inversion once some run has taken two locks both ways round.
+//! Here the order is in the types, so code that inverts it doesn't build:
+//! - [`Ordered<L, LEVEL>`](Ordered) wraps any [`Lockable`] lock (`Mutex`, `RwLock`, `SpinLock`, ...) with a level
+//! - locking it takes a [`LockLevel<HELD>`](LockLevel) token, proof that the thread holds nothing at or above `HELD`
+//!   besides level `HELD` itself, and returns the guard plus a token for `LEVEL`; `LEVEL <= HELD` is a compile error
+//! - both borrow the token passed in, so until the guard *and* the new token are gone, the thread can only lock
+//!   above `LEVEL`
+//! - [`LockLevel::unlocked`] makes a thread's first token, at level 0 (debug builds check it's the thread's only one)
+//!
+//! Two locks at one level can't be held together: give each its own level, or put both values behind one lock.
+//!
+//! ## Design
+//! The check is a `const { assert!(HELD < LEVEL) }` inside the generic [`Ordered::lock`], as stable Rust can't put
+//! `HELD < LEVEL` in a `where` clause. It fires as the call is monomorphized: at `cargo build` (and in doctests), not
+//! at `cargo check`.
+//!
+//! ## Example
+//! ```
+//! use sync::{LockLevel, Mutex, Ordered};
+//!
+//! // TrackedMutex's example, with the order fixed in the types: accounts (1) before audit_log (2)
+//! let accounts = Ordered::<_, 1>::new(Mutex::new(100));
+//! let audit_log = Ordered::<_, 2>::new(Mutex::new(Vec::new()));
+//!
+//! let mut unlocked = LockLevel::unlocked();
+//! let (mut balance, mut level) = accounts.lock(&mut unlocked);
+//! let (mut log, _) = audit_log.lock(&mut level);
+//! *balance -= 30;
+//! log.push(format!("withdrew 30, balance {}", *balance));
+//! ```
+//! The other way round doesn't compile:
+//! ```compile_fail
+//! # use sync::{LockLevel, Mutex, Ordered};
+//! # let accounts = Ordered::<_, 1>::new(Mutex::new(100));
+//! # let audit_log = Ordered::<_, 2>::new(Mutex::new(Vec::<String>::new()));
+//! let mut unlocked = LockLevel::unlocked();
+//! let (_log, mut level) = audit_log.lock(&mut unlocked);
+//! let (_balance, _) = accounts.lock(&mut level); // error: lock levels must increase
+//! ```
+
+use std::{cell::Cell, marker::PhantomData};
+
+use crate::{AdaptiveMutex, AdaptiveMutexGuard, ByteMutex, ByteMutexGuard, InstrumentedMutex, InstrumentedMutexGuard, Mutex, MutexGuard,
+            RwLock, RwLockReadGuard, RwLockWriteGuard, SpinLock, SpinLockGuard, TicketLock, TicketLockGuard, TrackedMutex,
+            TrackedMutexGuard};
+
+thread_local! {
+       /// Whether this thread has a live [`LockLevel::unlocked`] token (tracked with `debug_assertions` only).
+       static HAS_UNLOCKED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A lock with a blocking, exclusive `lock`: what [`Ordered`] can wrap.
+pub trait Lockable {
+       type Guard<'a>
+       where
+              Self: 'a;
+
+       fn acquire(&self) -> Self::Guard<'_>;
+}
+
+/// This thread's place in the lock order: it holds no [`Ordered`] lock above `LEVEL`; see the [module docs](self).
+///
+/// Not `Send`: the token speaks for the thread that made it.
+#[must_use = "a lock level is only good for taking locks above it"]
+pub struct LockLevel<'a, const LEVEL: u8> {
+       _held: PhantomData<(&'a mut (), *const ())>,
+}
+
+impl LockLevel<'static, 0> {
+       /// The token for a thread holding no ordered locks: where its locking starts. Pass it (or the tokens it leads
+       /// to) down rather than making another.
+       ///
+       /// ## Panics
+       /// With `debug_assertions`, if this thread already has one: a second would let it lock out of order.
+       pub fn unlocked() -> Self {
+              if cfg!(debug_assertions) {
+                     assert!(!HAS_UNLOCKED.replace(true), "this thread already has a LockLevel::unlocked token; pass that one down");
+              }
+              Self { _held: PhantomData }
+       }
+}
+
+impl<const LEVEL: u8> Drop for LockLevel<'_, LEVEL> {
+       fn drop(&mut self) {
+              // level 0 is only ever `unlocked`: every lock's level is above some token's
+              if cfg!(debug_assertions) && LEVEL == 0 {
+                     let _ = HAS_UNLOCKED.try_with(|has| has.set(false));
+              }
+       }
+}
+
+/// A lock that may only be taken while the thread holds nothing at `LEVEL` or above.
+#[derive(Debug, Default)]
+pub struct Ordered<L, const LEVEL: u8> {
+       lock: L,
+}
+
+impl<L, const LEVEL: u8> Ordered<L, LEVEL> {
+       pub const fn new(lock: L) -> Self { Self { lock } }
+
+       /// No ordering needed: `&mut self` proves nobody holds the lock.
+       pub fn get_mut(&mut self) -> &mut L { &mut self.lock }
+
+       pub fn into_inner(self) -> L { self.lock }
+}
+
+impl<L: Lockable, const LEVEL: u8> Ordered<L, LEVEL> {
+       /// Block until the lock is ours; also returns the token for locking further, above `LEVEL`.
+       ///
+       /// Doesn't compile unless `HELD < LEVEL`.
+       pub fn lock<'a, const HELD: u8>(&'a self, _level: &'a mut LockLevel<'_, HELD>) -> (L::Guard<'a>, LockLevel<'a, LEVEL>) {
+              const { assert!(HELD < LEVEL, "lock levels must increase: this lock's level isn't above the one held") };
+              (self.lock.acquire(), LockLevel { _held: PhantomData })
+       }
+}
+
+impl<T, const LEVEL: u8> Ordered<RwLock<T>, LEVEL> {
+       /// As [`lock`](Self::lock) (which takes the write lock), for a read lock.
+       pub fn read<'a, const HELD: u8>(&'a self, _level: &'a mut LockLevel<'_, HELD>) -> (RwLockReadGuard<'a, T>, LockLevel<'a, LEVEL>) {
+              const { assert!(HELD < LEVEL, "lock levels must increase: this lock's level isn't above the one held") };
+              (self.lock.read(), LockLevel { _held: PhantomData })
+       }
+}
+
+macro_rules! lockable {
+       ($($lock:ident => $guard:ident),* $(,)?) => {$(
+              impl<T> Lockable for $lock<T> {
+                     type Guard<'a> = $guard<'a, T> where T: 'a;
+
+                     fn acquire(&self) -> $guard<'_, T> { self.lock() }
+              }
+       )*};
+}
+
+lockable! {
+       AdaptiveMutex => AdaptiveMutexGuard,
+       ByteMutex => ByteMutexGuard,
+       InstrumentedMutex => InstrumentedMutexGuard,
+       Mutex => MutexGuard,
+       SpinLock => SpinLockGuard,
+       TicketLock => TicketLockGuard,
+       TrackedMutex => TrackedMutexGuard,
+}
+
+/// The write lock.
+impl<T> Lockable for RwLock<T> {
+       type Guard<'a>
+              = RwLockWriteGuard<'a, T>
+       where
+              T: 'a;
+
+       fn acquire(&self) -> RwLockWriteGuard<'_, T> { self.write() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_locking_upwards() {
+              let (config, cache, stats) = (
+                     Ordered::<_, 1>::new(RwLock::new(3)),
+                     Ordered::<_, 2>::new(Mutex::new(Vec::new())),
+                     Ordered::<_, 5>::new(SpinLock::new(0)),
+              );
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   let mut unlocked = LockLevel::unlocked();
+                                   let (size, mut level) = config.read(&mut unlocked);
+                                   let (mut cache, mut level) = cache.lock(&mut level);
+                                   cache.push(*size);
+                                   *stats.lock(&mut level).0 += 1;
+                            });
+                     }
+              });
+              // levels may be skipped, and a token released by its guard can be reused
+              let mut unlocked = LockLevel::unlocked();
+              assert_eq!(*stats.lock(&mut unlocked).0, 4);
+              *config.lock(&mut unlocked).0 += 1;
+              assert_eq!(*config.read(&mut unlocked).0, 4);
+              assert_eq!(cache.into_inner().into_inner(), [3; 4]);
+       }
+
+       #[test]
+       #[cfg_attr(not(debug_assertions), ignore = "only checked with debug_assertions")]
+       fn test_one_unlocked_token_per_thread() {
+              let unlocked = LockLevel::unlocked();
+              assert!(std::panic::catch_unwind(LockLevel::unlocked).is_err(), "a second token would allow any order");
+              drop(unlocked);
+              let _again = LockLevel::unlocked();
+       }
+}
@@ -0,0 +1,155 @@
+//! Data-parallel helpers: split a slice into chunks and process them on a shared [`ThreadPool`].
+//!
+//! - [`map`]: results in input order, however the chunks interleave
+//! - [`for_each`]: the same, for side effects
+//! - [`try_map`]: fallible `f`; runs every item and aggregates *all* failures into one [`ParallelErrors`]
+//!   (an `Error`, so `?` turns it into the binaries' `ErrWrapper`)
+//!
+//! Chunks run on a process-wide pool (one worker per available core, named `parallel-N`), created on first use.
+//! `chunk_size` trades scheduling overhead (small chunks) against load balance (big chunks).
+//! Don't call these from inside the pool's own jobs: see [`ThreadPool::scope`].
+//!
+//! ## Example
+//! ```
+//! use sync::parallel;
+//!
+//! let numbers: Vec<u64> = (1..=1_000).collect();
+//! let squares = parallel::map(&numbers, 100, |n| n * n);
+//! assert_eq!(squares[..3], [1, 4, 9]);
+//!
+//! let parsed = parallel::try_map(&["1", "two", "3", "four"], 1, |s| s.parse::<u8>());
+//! let errors = parsed.unwrap_err();
+//! assert_eq!(errors.len(), 2);
+//! assert_eq!(errors.indices().collect::<Vec<_>>(), [1, 3]);
+//! ```
+
+use std::{error::Error, fmt};
+
+use crate::{Lazy, ThreadPool};
+
+static POOL: Lazy<ThreadPool> =
+       Lazy::new(|| ThreadPool::builder().name("parallel").build().expect("failed to spawn the parallel helpers' thread pool"));
+
+/// `f` applied to every item, results in input order.
+///
+/// ## Panics
+/// If `chunk_size` is zero, or `f` panics (re-raised once every chunk is done).
+pub fn map<T: Sync, U: Send>(items: &[T], chunk_size: usize, f: impl Fn(&T) -> U + Sync) -> Vec<U> {
+       assert!(chunk_size > 0, "chunk size must be non-zero");
+       let f = &f;
+       POOL.scope(|s| {
+              for chunk in items.chunks(chunk_size) {
+                     s.spawn(move || chunk.iter().map(f).collect::<Vec<_>>());
+              }
+       })
+       .into_iter()
+       .flatten()
+       .collect()
+}
+
+/// Run `f` on every item.
+///
+/// ## Panics
+/// As [`map`].
+pub fn for_each<T: Sync>(items: &[T], chunk_size: usize, f: impl Fn(&T) + Sync) { map(items, chunk_size, f); }
+
+/// `f` applied to every item; if any fail, all their errors (not just the first).
+///
+/// ## Errors
+/// Every `Err` returned by `f`, with the index of the item that produced it.
+///
+/// ## Panics
+/// As [`map`].
+pub fn try_map<T: Sync, U: Send, E: Send>(
+       items: &[T],
+       chunk_size: usize,
+       f: impl Fn(&T) -> Result<U, E> + Sync,
+) -> Result<Vec<U>, ParallelErrors<E>> {
+       let results = map(items, chunk_size, f);
+       let total = results.len();
+       let mut values = Vec::with_capacity(total);
+       let mut errors = Vec::new();
+       for (index, result) in results.into_iter().enumerate() {
+              match result {
+                     Ok(value) => values.push(value),
+                     Err(error) => errors.push((index, error)),
+              }
+       }
+       if errors.is_empty() { Ok(values) } else { Err(ParallelErrors { errors, total }) }
+}
+
+/// Every failure from a [`try_map`], in input order.
+#[derive(Debug)]
+pub struct ParallelErrors<E> {
+       /// `(item index, error)`, sorted by index; never empty.
+       errors: Vec<(usize, E)>,
+       total:  usize,
+}
+
+impl<E> ParallelErrors<E> {
+       /// Number of failed items.
+       pub fn len(&self) -> usize { self.errors.len() }
+
+       /// Always `false`: a `try_map` without failures returns `Ok`.
+       pub fn is_empty(&self) -> bool { self.errors.is_empty() }
+
+       /// Indices of the failed items, ascending.
+       pub fn indices(&self) -> impl Iterator<Item = usize> + '_ { self.errors.iter().map(|(index, _)| *index) }
+
+       pub fn errors(&self) -> &[(usize, E)] { &self.errors }
+
+       pub fn into_errors(self) -> Vec<(usize, E)> { self.errors }
+}
+
+impl<E: fmt::Display> fmt::Display for ParallelErrors<E> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              write!(f, "{} of {} items failed", self.errors.len(), self.total)?;
+              for (index, error) in &self.errors {
+                     write!(f, "\n  item {index}: {error}")?;
+              }
+              Ok(())
+       }
+}
+
+impl<E: Error + 'static> Error for ParallelErrors<E> {
+       /// The first failure (by index).
+       fn source(&self) -> Option<&(dyn Error + 'static)> { self.errors.first().map(|(_, error)| error as &(dyn Error + 'static)) }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{num::ParseIntError,
+                 sync::atomic::{AtomicUsize, Ordering::Relaxed}};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_map_preserves_order() {
+              let items: Vec<usize> = (0..1_000).collect();
+              for chunk_size in [1, 7, 1_000, 5_000] {
+                     assert_eq!(map(&items, chunk_size, |n| n * 2), items.iter().map(|n| n * 2).collect::<Vec<_>>());
+              }
+              assert_eq!(map(&[] as &[u8], 4, |n| *n), []);
+       }
+
+       #[test]
+       fn test_for_each_visits_every_item() {
+              let sum = AtomicUsize::new(0);
+              for_each(&[1, 2, 3, 4, 5], 2, |n| {
+                     sum.fetch_add(*n, Relaxed);
+              });
+              assert_eq!(sum.into_inner(), 15);
+       }
+
+       #[test]
+       fn test_try_map_aggregates_every_error() {
+              assert_eq!(try_map(&["1", "2"], 1, |s| s.parse::<i32>()).unwrap(), [1, 2]);
+
+              let errors: ParallelErrors<ParseIntError> = try_map(&["x", "2", "y", "z"], 3, |s| s.parse::<i32>()).unwrap_err();
+              assert_eq!(errors.indices().collect::<Vec<_>>(), [0, 2, 3]);
+              assert!(errors.to_string().starts_with("3 of 4 items failed\n  item 0: invalid digit"));
+              assert!(errors.source().is_some());
+       }
+}
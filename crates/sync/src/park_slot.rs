@@ -0,0 +1,28 @@
+//! Slot for the thread a blocking primitive should unpark.
+//!
+//! Shared by the channels: the waiting side [`register`](ParkSlot::register)s itself *then* re-checks its condition
+//! before parking; the notifying side publishes its change *then* [`wake`](ParkSlot::wake)s.
+//! The spin lock orders the two, so either the waiter sees the change or the notifier sees the waiter.
+
+use crate::{SpinLock,
+            atomic::{Thread, current, loom_const_fn}};
+
+/// At most one registered (parked or about-to-park) thread.
+pub(crate) struct ParkSlot {
+       thread: SpinLock<Option<Thread>>,
+}
+impl ParkSlot {
+       loom_const_fn! {
+              pub(crate) fn new() -> Self { Self { thread: SpinLock::new(None) } }
+       }
+
+       /// Make the current thread the one to be woken.
+       pub(crate) fn register(&self) { *self.thread.lock() = Some(current()); }
+
+       /// Unpark the registered thread, if any.
+       pub(crate) fn wake(&self) {
+              if let Some(thread) = self.thread.lock().as_ref() {
+                     thread.unpark();
+              }
+       }
+}
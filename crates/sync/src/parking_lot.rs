@@ -0,0 +1,260 @@
+//! Global parking lot: wait queues keyed by address, so a lock's own state can shrink to a byte.
+//!
+//! A futex lets the kernel keep the wait queue for a 32-bit word. Without one (or to go smaller than a word),
+//! keep the queues yourself: one process-wide hash table from an address (any `usize` key) to the threads
+//! waiting on it. A primitive then needs only enough state to know *whether* to look in the table
+//! (see [`ByteMutex`](crate::ByteMutex): a locked bit and a parked bit).
+//!
+//! - [`park`]: under the key's bucket lock, `validate` re-checks the primitive's state; if it still says "wait",
+//!   the thread is queued and sleeps until unparked (or the deadline)
+//! - [`unpark_one`] / [`unpark_all`]: dequeue waiters for a key and wake them; `unpark_one`'s callback runs under
+//!   the bucket lock, told whether more threads wait, so the primitive can update its "parked" bit atomically with
+//!   the queue
+//!
+//! Validating under the bucket lock is what makes this race-free: an unparker changes the state *then* takes the
+//! bucket lock, so either the parker's validation sees the change, or the parker is already queued.
+//!
+//! With the `async` feature, tasks queue here too, alongside threads ([`AsyncMutex`](crate::AsyncMutex) is the
+//! user): [`park_task`] queues a [`TaskWaiter`] and returns, and the unpark that dequeues it wakes the task's
+//! `Waker` instead of a thread. A future dropped while queued must [`cancel_task`], and pass on a wake it was
+//! already handed.
+//!
+//! ## Design
+//! - fixed table of cache-padded buckets (spin-locked: the critical sections are a few pointer moves);
+//!   keys hash with a Fibonacci multiply
+//! - each thread has one parker (a futex word) reused for every park; waiters queue FIFO per bucket
+//! - a timed-out parker that finds itself already dequeued was unparked concurrently, and waits for that wake
+
+#[cfg(feature = "async")]
+use std::task::Waker;
+use std::{collections::VecDeque, sync::Arc, time::Instant};
+
+use crate::{CachePadded, SpinLock,
+            atomic::{AtomicU32,
+                     Ordering::{Acquire, Release}},
+            futex::{wait_until, wake_one}};
+#[cfg(feature = "async")]
+use crate::{atomic::AtomicBool, waker_slot::WakerSlot};
+
+const BUCKETS: usize = 64;
+
+static TABLE: [CachePadded<SpinLock<VecDeque<Waiter>>>; BUCKETS] = [const { CachePadded(SpinLock::new(VecDeque::new())) }; BUCKETS];
+
+thread_local! {
+       static PARKER: Arc<Parker> = Arc::new(Parker { unparked: AtomicU32::new(0) });
+}
+
+struct Parker {
+       /// 1 once an unparker has dequeued us and handed over the wake.
+       unparked: AtomicU32,
+}
+
+/// A task's place in the queues: flagged, and its waker woken, by the unpark that dequeues it.
+/// One per future, reused for each time it queues.
+#[cfg(feature = "async")]
+pub struct TaskWaiter {
+       unparked: AtomicBool,
+       waker:    WakerSlot,
+}
+
+struct Waiter {
+       key:    usize,
+       wakeup: Wakeup,
+}
+
+/// Who a dequeued waiter is, and so how to wake it.
+enum Wakeup {
+       Thread(Arc<Parker>),
+       #[cfg(feature = "async")]
+       Task(Arc<TaskWaiter>),
+}
+
+/// How a [`park`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParkResult {
+       /// Woken by an `unpark_*`.
+       Unparked,
+       /// `validate` returned `false`; never slept.
+       Invalid,
+       /// The deadline passed first (and we're no longer queued).
+       TimedOut,
+}
+
+/// What [`unpark_one`] found, as passed to its callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnparkResult {
+       /// Whether a thread was dequeued (and will be woken).
+       pub unparked:  bool,
+       /// Whether threads are still queued on the key after this one.
+       pub have_more: bool,
+}
+
+fn bucket(key: usize) -> &'static SpinLock<VecDeque<Waiter>> {
+       let hash = (key as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - BUCKETS.trailing_zeros());
+       &TABLE[hash as usize]
+}
+
+/// Queue the current thread on `key` if `validate()` (run under the key's bucket lock) says so, then sleep until
+/// unparked or `deadline`.
+///
+/// ## Panics
+/// If called while this thread's thread-locals are being torn down.
+pub fn park(key: usize, validate: impl FnOnce() -> bool, deadline: Option<Instant>) -> ParkResult {
+       let parker = PARKER.with(Arc::clone);
+       {
+              let mut queue = bucket(key).lock();
+              if !validate() {
+                     return ParkResult::Invalid;
+              }
+              parker.unparked.store(0, Release);
+              queue.push_back(Waiter { key, wakeup: Wakeup::Thread(parker.clone()) });
+       }
+       loop {
+              // Acquire: what the unparker did before waking us is visible once we see the flag
+              if parker.unparked.load(Acquire) == 1 {
+                     return ParkResult::Unparked;
+              }
+              if !wait_until(&parker.unparked, 0, deadline) {
+                     break;
+              }
+       }
+       // timed out, unless an unparker got to us first
+       let mut queue = bucket(key).lock();
+       if let Some(position) =
+              queue.iter().position(|waiter| matches!(&waiter.wakeup, Wakeup::Thread(queued) if Arc::ptr_eq(queued, &parker)))
+       {
+              queue.remove(position);
+              return ParkResult::TimedOut;
+       }
+       drop(queue);
+       // dequeued: the wake is on its way, and we mustn't park again before it lands
+       while parker.unparked.load(Acquire) == 0 {
+              wait_until(&parker.unparked, 0, None);
+       }
+       ParkResult::Unparked
+}
+
+/// Wake the longest-waiting thread parked on `key`, if any.
+///
+/// `callback` runs under the bucket lock, after the dequeue and before the wake:
+/// the place to update the primitive's state so it agrees with the queue.
+pub fn unpark_one(key: usize, callback: impl FnOnce(UnparkResult)) -> UnparkResult {
+       let mut queue = bucket(key).lock();
+       let wakeup =
+              queue.iter().position(|waiter| waiter.key == key).and_then(|position| queue.remove(position)).map(|waiter| waiter.wakeup);
+       let result = UnparkResult { unparked: wakeup.is_some(), have_more: queue.iter().any(|waiter| waiter.key == key) };
+       callback(result);
+       drop(queue);
+       if let Some(wakeup) = wakeup {
+              wakeup.wake();
+       }
+       result
+}
+
+/// Wake every thread parked on `key`; returns how many.
+pub fn unpark_all(key: usize) -> usize {
+       let wakeups: Vec<Wakeup> = {
+              let mut queue = bucket(key).lock();
+              let (ours, others): (VecDeque<Waiter>, VecDeque<Waiter>) = queue.drain(..).partition(|waiter| waiter.key == key);
+              *queue = others;
+              ours.into_iter().map(|waiter| waiter.wakeup).collect()
+       };
+       let count = wakeups.len();
+       wakeups.into_iter().for_each(Wakeup::wake);
+       count
+}
+
+impl Wakeup {
+       fn wake(self) {
+              match self {
+                     Self::Thread(parker) => {
+                            parker.unparked.store(1, Release);
+                            wake_one(&parker.unparked);
+                     }
+                     #[cfg(feature = "async")]
+                     Self::Task(waiter) => {
+                            // flag first: a re-poll that registers after this wake then sees the flag
+                            waiter.unparked.store(true, Release);
+                            waiter.waker.wake();
+                     }
+              }
+       }
+}
+
+/// Queue `waiter` on `key` if `validate()` (run under the key's bucket lock) says so; `false` if it didn't.
+///
+/// Unlike [`park`], returns at once: the caller returns `Pending`, having registered its waker with `waiter`
+/// beforehand, and checks [`TaskWaiter::is_unparked`] when polled again.
+#[cfg(feature = "async")]
+pub fn park_task(key: usize, validate: impl FnOnce() -> bool, waiter: &Arc<TaskWaiter>) -> bool {
+       let mut queue = bucket(key).lock();
+       if !validate() {
+              return false;
+       }
+       waiter.unparked.store(false, Release);
+       queue.push_back(Waiter { key, wakeup: Wakeup::Task(waiter.clone()) });
+       true
+}
+
+/// Take a queued `waiter` off `key`'s queue; `false` if an unpark dequeued it first (its wake is then the caller's
+/// to pass on).
+#[cfg(feature = "async")]
+pub fn cancel_task(key: usize, waiter: &Arc<TaskWaiter>) -> bool {
+       let mut queue = bucket(key).lock();
+       let position = queue.iter().position(|queued| matches!(&queued.wakeup, Wakeup::Task(task) if Arc::ptr_eq(task, waiter)));
+       position.and_then(|position| queue.remove(position)).is_some()
+}
+
+#[cfg(feature = "async")]
+impl TaskWaiter {
+       pub fn new() -> Arc<Self> { Arc::new(Self { unparked: AtomicBool::new(false), waker: WakerSlot::new() }) }
+
+       /// Make `waker` the one an unpark wakes. Register before checking [`is_unparked`](Self::is_unparked).
+       pub fn register(&self, waker: &Waker) { self.waker.register(waker); }
+
+       /// Whether an unpark has dequeued this waiter since it last queued.
+       pub fn is_unparked(&self) -> bool { self.unparked.load(Acquire) }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::{AtomicBool, Ordering::Relaxed},
+                 thread,
+                 time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_invalid_park_returns_at_once() {
+              assert_eq!(park(1, || false, None), ParkResult::Invalid);
+       }
+
+       #[test]
+       fn test_park_times_out_and_dequeues() {
+              let key = 2;
+              assert_eq!(park(key, || true, Some(Instant::now() + Duration::from_millis(5))), ParkResult::TimedOut);
+              assert!(!unpark_one(key, |_| {}).unparked);
+       }
+
+       #[test]
+       fn test_unpark_wakes_parked_threads() {
+              let ready = AtomicBool::new(false);
+              let key = &ready as *const _ as usize;
+              thread::scope(|s| {
+                     for _ in 0..3 {
+                            s.spawn(|| {
+                                   while !ready.load(Acquire) {
+                                          park(key, || !ready.load(Relaxed), None);
+                                   }
+                            });
+                     }
+                     thread::sleep(Duration::from_millis(10));
+                     ready.store(true, Release);
+                     let first = unpark_one(key, |_| {});
+                     let rest = unpark_all(key);
+                     assert!(usize::from(first.unparked) + rest <= 3, "only threads that validated before `ready` queued");
+              });
+       }
+}
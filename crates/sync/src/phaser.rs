@@ -0,0 +1,266 @@
+//! Reusable barrier whose party count can change between (and during) phases, after Java's `Phaser`.
+//!
+//! A [`Barrier`](crate::Barrier) is for a fixed team. With a [`Phaser`], parties come and go:
+//! - [`register`](Phaser::register): one more party, expected from the current phase on
+//! - [`arrive_and_wait`](Phaser::arrive_and_wait): the barrier step; the phase advances once every registered party
+//!   has arrived
+//! - [`arrive`](Phaser::arrive): arrive without waiting (a producer signalling "my part of this phase is done")
+//! - [`arrive_and_deregister`](Phaser::arrive_and_deregister): arrive and leave; the others no longer wait for it
+//!
+//! ## Design
+//! One `AtomicU64` holds `phase (32) | parties (16) | arrived (16)`, so an arrival, a registration and the advance
+//! are each a single CAS: no state in which the count and the phase disagree. Waiters sleep on a second, 32-bit
+//! word mirroring the phase (a futex needs one), which the advancing party moves forward before waking them.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::Phaser;
+//!
+//! let phaser = &Phaser::new(1); // the main thread
+//! thread::scope(|s| {
+//!        for rounds in [1, 2, 3] {
+//!               phaser.register(); // before spawning: the worker takes part from this phase on
+//!               s.spawn(move || {
+//!                      for _ in 0..rounds {
+//!                             phaser.arrive_and_wait();
+//!                      }
+//!                      phaser.arrive_and_deregister(); // done early: the rest carry on without us
+//!               });
+//!        }
+//!        phaser.arrive_and_deregister();
+//! });
+//! assert_eq!((phaser.phase(), phaser.parties()), (4, 0));
+//! ```
+
+use std::fmt;
+
+use crate::{atomic::{AtomicU32, AtomicU64,
+                     Ordering::{AcqRel, Acquire, Relaxed, Release},
+                     loom_const_fn, try_update},
+            futex::{wait, wake_all}};
+
+const COUNT_BITS: u32 = 16;
+const COUNT_MASK: u64 = (1 << COUNT_BITS) - 1;
+const PARTIES_SHIFT: u32 = COUNT_BITS;
+const PHASE_SHIFT: u32 = 2 * COUNT_BITS;
+
+/// Barrier for a changing number of parties; see the [module docs](self).
+pub struct Phaser {
+       /// `phase << 32 | parties << 16 | arrived`.
+       state:      AtomicU64,
+       /// The phase again, as a futex word for waiters.
+       phase_word: AtomicU32,
+}
+
+/// The fields of a `state` word.
+#[derive(Clone, Copy)]
+struct State {
+       phase:   u32,
+       parties: u64,
+       arrived: u64,
+}
+
+impl State {
+       fn unpack(word: u64) -> Self {
+              Self { phase: (word >> PHASE_SHIFT) as u32, parties: (word >> PARTIES_SHIFT) & COUNT_MASK, arrived: word & COUNT_MASK }
+       }
+
+       fn pack(self) -> u64 { u64::from(self.phase) << PHASE_SHIFT | self.parties << PARTIES_SHIFT | self.arrived }
+
+       /// Everyone's in: the next phase, nobody arrived yet.
+       fn settle(self) -> Self {
+              if self.arrived == self.parties { Self { phase: self.phase.wrapping_add(1), arrived: 0, ..self } } else { self }
+       }
+}
+
+impl Phaser {
+       /// Most parties registered at once.
+       pub const MAX_PARTIES: u32 = COUNT_MASK as u32;
+
+       loom_const_fn! {
+              /// A phaser at phase 0 with `parties` registered.
+              ///
+              /// ## Panics
+              /// If `parties` exceeds [`MAX_PARTIES`](Self::MAX_PARTIES).
+              pub fn new(parties: u32) -> Self {
+                     assert!(parties <= Self::MAX_PARTIES, "too many parties");
+                     Self { state: AtomicU64::new((parties as u64) << PARTIES_SHIFT), phase_word: AtomicU32::new(0) }
+              }
+       }
+
+       /// Add a party, which must arrive for the current phase too. Returns the current phase.
+       ///
+       /// ## Panics
+       /// If [`MAX_PARTIES`](Self::MAX_PARTIES) are already registered.
+       pub fn register(&self) -> u32 {
+              self.update(|state| {
+                     assert!(state.parties < COUNT_MASK, "too many parties");
+                     State { parties: state.parties + 1, ..state }
+              })
+              .phase
+       }
+
+       /// Arrive at the current phase without waiting for the others. Returns the phase arrived at.
+       ///
+       /// ## Panics
+       /// If every registered party has already arrived (or none is registered).
+       pub fn arrive(&self) -> u32 { self.arrive_as(false) }
+
+       /// Arrive, and leave: later phases don't wait for this party. Returns the phase arrived at.
+       ///
+       /// ## Panics
+       /// As [`arrive`](Self::arrive).
+       pub fn arrive_and_deregister(&self) -> u32 { self.arrive_as(true) }
+
+       /// Arrive, and block until every other party has too. Returns the new phase.
+       ///
+       /// ## Panics
+       /// As [`arrive`](Self::arrive).
+       pub fn arrive_and_wait(&self) -> u32 {
+              let phase = self.arrive();
+              self.wait_for_phase(phase)
+       }
+
+       /// Block while the phase is still `phase`. Returns the current phase (at once, if it's already moved on).
+       pub fn wait_for_phase(&self, phase: u32) -> u32 {
+              loop {
+                     let current = self.phase();
+                     if current != phase {
+                            return current;
+                     }
+                     wait(&self.phase_word, phase); // returns at once if the advancer has already stored the new phase
+              }
+       }
+
+       /// The current phase, counting from 0 (wrapping).
+       pub fn phase(&self) -> u32 { State::unpack(self.state.load(Acquire)).phase }
+
+       /// Parties registered. Only a snapshot.
+       pub fn parties(&self) -> u32 { State::unpack(self.state.load(Relaxed)).parties as u32 }
+
+       /// Parties that have arrived at the current phase. Only a snapshot.
+       pub fn arrived(&self) -> u32 { State::unpack(self.state.load(Relaxed)).arrived as u32 }
+
+       fn arrive_as(&self, deregister: bool) -> u32 {
+              self.update(|state| {
+                     assert!(state.arrived < state.parties, "more arrivals than registered parties");
+                     if deregister { State { parties: state.parties - 1, ..state } } else { State { arrived: state.arrived + 1, ..state } }
+              })
+              .phase
+       }
+
+       /// Apply `f` to the state (advancing the phase if that completes it), waking waiters on an advance.
+       /// Returns the state `f` was applied to.
+       fn update(&self, f: impl Fn(State) -> State) -> State {
+              let mut word = self.state.load(Relaxed);
+              loop {
+                     let old = State::unpack(word);
+                     let new = f(old).settle();
+                     // AcqRel: each arrival releases its party's work; the advancing CAS acquires all of it (through the
+                     // chain of RMWs), and waiters acquire it from the advance
+                     match self.state.compare_exchange_weak(word, new.pack(), AcqRel, Relaxed) {
+                            Ok(_) => {
+                                   if new.phase != old.phase {
+                                          // only ever forward: a party that deregistered as it advanced may get here after
+                                          // the next phase's advancer
+                                          let newer = |word: u32| (new.phase.wrapping_sub(word) as i32 > 0).then_some(new.phase);
+                                          let _ = try_update(&self.phase_word, Release, Relaxed, newer);
+                                          wake_all(&self.phase_word);
+                                   }
+                                   return old;
+                            }
+                            Err(observed) => word = observed,
+                     }
+              }
+       }
+}
+
+impl Default for Phaser {
+       fn default() -> Self { Self::new(0) }
+}
+
+impl fmt::Debug for Phaser {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              let state = State::unpack(self.state.load(Relaxed));
+              f.debug_struct("Phaser")
+                     .field("phase", &state.phase)
+                     .field("parties", &state.parties)
+                     .field("arrived", &state.arrived)
+                     .finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_phases_stay_in_lockstep() {
+              const THREADS: usize = 4;
+              const PHASES: u32 = 50;
+              let phaser = Phaser::new(THREADS as u32);
+              let progress = AtomicUsize::new(0);
+              thread::scope(|s| {
+                     for _ in 0..THREADS {
+                            s.spawn(|| {
+                                   for phase in 0..PHASES {
+                                          progress.fetch_add(1, Relaxed);
+                                          assert_eq!(phaser.arrive_and_wait(), phase + 1);
+                                          // everyone finished this phase's work before anyone got here
+                                          assert!(progress.load(Relaxed) >= THREADS * (phase as usize + 1));
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(phaser.phase(), PHASES);
+       }
+
+       #[test]
+       fn test_deregistered_parties_are_not_waited_for() {
+              let phaser = Phaser::new(3);
+              assert_eq!(phaser.arrive(), 0);
+              assert_eq!(phaser.arrive_and_deregister(), 0);
+              assert_eq!((phaser.phase(), phaser.parties(), phaser.arrived()), (0, 2, 1));
+              assert_eq!(phaser.arrive(), 0, "the last one in advances the phase");
+              assert_eq!((phaser.phase(), phaser.parties(), phaser.arrived()), (1, 2, 0));
+              assert_eq!(phaser.register(), 1);
+              assert_eq!(phaser.arrive_and_deregister(), 1);
+              assert_eq!(phaser.arrive_and_deregister(), 1);
+              assert_eq!(phaser.arrive_and_deregister(), 1);
+              assert_eq!((phaser.phase(), phaser.parties()), (2, 0));
+       }
+
+       #[test]
+       fn test_parties_join_mid_run() {
+              let phaser = Phaser::new(1);
+              let arrivals = AtomicUsize::new(0);
+              thread::scope(|s| {
+                     for joined in 0..4_u32 {
+                            let phase = phaser.register();
+                            assert_eq!(phase, joined);
+                            let (phaser, arrivals) = (&phaser, &arrivals);
+                            s.spawn(move || {
+                                   while phaser.phase() < 6 {
+                                          arrivals.fetch_add(1, Relaxed);
+                                          phaser.arrive_and_wait();
+                                   }
+                                   phaser.arrive_and_deregister();
+                            });
+                            phaser.arrive_and_wait();
+                     }
+                     phaser.arrive_and_deregister(); // phase 4 on, the workers alone
+              });
+              // phases 0..4 had 1, 2, 3, 4 workers; phases 4 and 5 had all 4
+              assert_eq!(arrivals.load(Relaxed), 1 + 2 + 3 + 4 + 4 + 4);
+       }
+
+       #[test]
+       #[should_panic(expected = "more arrivals than registered parties")]
+       fn test_arrival_without_a_party_panics() { Phaser::new(0).arrive(); }
+}
@@ -0,0 +1,229 @@
+//! Thread scheduling priority, coarsely and portably: enough to set up a priority inversion on purpose.
+//!
+//! [`ThreadPriority`] has five levels, mapped onto whatever the platform has for ordinary (non-realtime) threads:
+//!
+//! | level     | Linux (nice) | macOS (`SCHED_OTHER` param)  | Windows                         |
+//! |-----------|--------------|------------------------------|---------------------------------|
+//! | `Lowest`  | 19           | min                          | `THREAD_PRIORITY_LOWEST`        |
+//! | `Low`     | 10           | between min and default      | `THREAD_PRIORITY_BELOW_NORMAL`  |
+//! | `Normal`  | 0            | default                      | `THREAD_PRIORITY_NORMAL`        |
+//! | `High`    | -10          | between default and max      | `THREAD_PRIORITY_ABOVE_NORMAL`  |
+//! | `Highest` | -20          | max                          | `THREAD_PRIORITY_HIGHEST`       |
+//!
+//! Linux keeps a nice value per thread, which is what this sets; lowering priority is always allowed,
+//! raising it above `Normal` needs `CAP_SYS_NICE` (else `PermissionDenied`).
+//! These are weights, not strict priorities: a low thread still gets a trickle of CPU, so an inversion shows up as a
+//! long stall rather than a hang.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::priority::{self, SpawnWithPriority, ThreadPriority};
+//!
+//! let handle = thread::Builder::new().spawn_with_priority(ThreadPriority::Lowest, || 6 * 7).unwrap();
+//! assert_eq!(handle.join().unwrap(), 42);
+//! // raising needs privileges on Linux; lowering never does
+//! let _ = priority::set_priority(ThreadPriority::Highest);
+//! ```
+
+use std::{io,
+          thread::{self, JoinHandle, Scope, ScopedJoinHandle}};
+
+/// Portable priority levels; see the [module docs](self) for what each maps to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum ThreadPriority {
+       Lowest,
+       Low,
+       #[default]
+       Normal,
+       High,
+       Highest,
+}
+
+/// Set the calling thread's priority.
+///
+/// ## Errors
+/// - `PermissionDenied` raising priority without the privilege to
+/// - `Unsupported` on platforms without an implementation
+/// - the OS error otherwise
+pub fn set_priority(priority: ThreadPriority) -> io::Result<()> { imp::set_priority(priority) }
+
+#[cfg(target_os = "linux")]
+mod imp {
+       use std::io;
+
+       use super::ThreadPriority;
+
+       pub(super) fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+              let nice = match priority {
+                     ThreadPriority::Lowest => 19,
+                     ThreadPriority::Low => 10,
+                     ThreadPriority::Normal => 0,
+                     ThreadPriority::High => -10,
+                     ThreadPriority::Highest => -20,
+              };
+              // SAFETY: no arguments, no memory touched.
+              let tid = unsafe { libc::gettid() };
+              // SAFETY: as above; on Linux `PRIO_PROCESS` with a thread id applies to just that thread.
+              match unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } {
+                     0 => Ok(()),
+                     _ => Err(io::Error::last_os_error()),
+              }
+       }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+       use std::{io, mem};
+
+       use super::ThreadPriority;
+
+       pub(super) fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+              // SAFETY: no memory touched.
+              let (min, max) =
+                     unsafe { (libc::sched_get_priority_min(libc::SCHED_OTHER), libc::sched_get_priority_max(libc::SCHED_OTHER)) };
+              let default = (min + max) / 2;
+              let level = match priority {
+                     ThreadPriority::Lowest => min,
+                     ThreadPriority::Low => (min + default) / 2,
+                     ThreadPriority::Normal => default,
+                     ThreadPriority::High => (default + max) / 2,
+                     ThreadPriority::Highest => max,
+              };
+              // SAFETY: `sched_param` is plain data.
+              let mut param: libc::sched_param = unsafe { mem::zeroed() };
+              param.sched_priority = level;
+              // SAFETY: `pthread_self` is always valid and `param` is live for the call.
+              match unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_OTHER, &raw const param) } {
+                     0 => Ok(()),
+                     code => Err(io::Error::from_raw_os_error(code)),
+              }
+       }
+}
+
+#[cfg(windows)]
+mod imp {
+       use std::{ffi::c_void, io};
+
+       use super::ThreadPriority;
+
+       #[link(name = "kernel32")]
+       unsafe extern "system" {
+              fn GetCurrentThread() -> *mut c_void;
+              fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+       }
+
+       pub(super) fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+              let level = match priority {
+                     ThreadPriority::Lowest => -2,
+                     ThreadPriority::Low => -1,
+                     ThreadPriority::Normal => 0,
+                     ThreadPriority::High => 1,
+                     ThreadPriority::Highest => 2,
+              };
+              // SAFETY: the pseudo-handle for the current thread is always valid, and needs no closing.
+              match unsafe { SetThreadPriority(GetCurrentThread(), level) } {
+                     0 => Err(io::Error::last_os_error()),
+                     _ => Ok(()),
+              }
+       }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod imp {
+       use std::io;
+
+       use super::ThreadPriority;
+
+       pub(super) fn set_priority(_priority: ThreadPriority) -> io::Result<()> {
+              Err(io::Error::new(io::ErrorKind::Unsupported, "thread priority not supported on this platform"))
+       }
+}
+
+/// Spawn threads at a given priority.
+///
+/// Unlike [`SpawnPinned`](crate::affinity::SpawnPinned), a refused priority doesn't fail the spawn:
+/// raising priority is routinely refused to unprivileged processes, and the thread is still useful at `Normal`.
+/// The refusal is logged (`tracing` `WARN`); call [`set_priority`] inside the closure to handle it yourself.
+pub trait SpawnWithPriority {
+       /// ## Errors
+       /// If the thread can't be created.
+       fn spawn_with_priority<F, T>(self, priority: ThreadPriority, f: F) -> io::Result<JoinHandle<T>>
+       where
+              F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static;
+
+       /// As [`spawn_with_priority`](Self::spawn_with_priority), into a [`thread::scope`].
+       ///
+       /// ## Errors
+       /// If the thread can't be created.
+       fn spawn_scoped_with_priority<'scope, F, T>(
+              self,
+              scope: &'scope Scope<'scope, '_>,
+              priority: ThreadPriority,
+              f: F,
+       ) -> io::Result<ScopedJoinHandle<'scope, T>>
+       where
+              F: FnOnce() -> T + Send + 'scope,
+              T: Send + 'scope;
+}
+
+impl SpawnWithPriority for thread::Builder {
+       fn spawn_with_priority<F, T>(self, priority: ThreadPriority, f: F) -> io::Result<JoinHandle<T>>
+       where
+              F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static,
+       {
+              self.spawn(move || {
+                     try_set_priority(priority);
+                     f()
+              })
+       }
+
+       fn spawn_scoped_with_priority<'scope, F, T>(
+              self,
+              scope: &'scope Scope<'scope, '_>,
+              priority: ThreadPriority,
+              f: F,
+       ) -> io::Result<ScopedJoinHandle<'scope, T>>
+       where
+              F: FnOnce() -> T + Send + 'scope,
+              T: Send + 'scope,
+       {
+              self.spawn_scoped(scope, move || {
+                     try_set_priority(priority);
+                     f()
+              })
+       }
+}
+
+fn try_set_priority(priority: ThreadPriority) {
+       if let Err(error) = set_priority(priority) {
+              tracing::warn!(?priority, %error, thread = thread::current().name(), "couldn't set thread priority; running at the default");
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use super::*;
+
+       #[test]
+       fn test_lowering_is_always_allowed() {
+              let result = thread::spawn(|| set_priority(ThreadPriority::Low).and_then(|()| set_priority(ThreadPriority::Lowest)));
+              let result = result.join().unwrap();
+              assert!(result.is_ok() || cfg!(not(any(target_os = "linux", target_os = "macos", windows))), "{result:?}");
+       }
+
+       #[cfg(target_os = "linux")]
+       #[test]
+       fn test_sets_this_threads_nice_only() {
+              fn nice() -> i32 {
+                     // SAFETY: no memory touched; `gettid` is the calling thread.
+                     unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t) }
+              }
+              let before = nice();
+              let inside = thread::Builder::new().spawn_with_priority(ThreadPriority::Lowest, nice).unwrap().join().unwrap();
+              assert_eq!((inside, nice()), (19, before));
+       }
+}
@@ -0,0 +1,261 @@
+//! Progress reporting: worker threads count, one watching thread sleeps until there's something new to show.
+//!
+//! The "atomic counter + unpark the main thread" pattern, packaged:
+//! - [`ProgressWatcher::new`] on the thread that will display progress
+//! - hand a [`ProgressReporter`] (cheap to clone) to each worker; [`inc`](ProgressReporter::inc) per item done
+//! - the watcher loops on [`wait_for_update`](ProgressWatcher::wait_for_update), which parks until a reporter
+//!   counts (or the last reporter is dropped), and gets a [`Progress`] snapshot with rate and ETA
+//!
+//! ## Design
+//! - counters are `Relaxed`: they're statistics, and no other data is published through them
+//! - an update counter tells the watcher whether anything happened since it last looked,
+//!   so a park that races with an unpark (or wakes spuriously) never loses or invents an update
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::ProgressWatcher;
+//!
+//! let watcher = ProgressWatcher::new(400);
+//! thread::scope(|s| {
+//!        for _ in 0..4 {
+//!               let reporter = watcher.reporter();
+//!               s.spawn(move || (0..100).for_each(|_| {
+//!                      reporter.inc();
+//!               }));
+//!        }
+//!        while !watcher.wait_for_update().is_finished() {}
+//! });
+//! assert_eq!(watcher.snapshot().done, 400);
+//! ```
+
+use std::{cell::Cell,
+          fmt,
+          marker::PhantomData,
+          sync::Arc,
+          time::{Duration, Instant}};
+
+use crate::atomic::{AtomicUsize,
+                    Ordering::{Acquire, Relaxed, Release},
+                    Thread, current, park};
+
+struct Shared {
+       done:      AtomicUsize,
+       total:     AtomicUsize,
+       /// Bumped after every change the watcher should see.
+       updates:   AtomicUsize,
+       reporters: AtomicUsize,
+       watcher:   Thread,
+       started:   Instant,
+}
+
+impl Shared {
+       fn notify(&self) {
+              self.updates.fetch_add(1, Release);
+              self.watcher.unpark();
+       }
+}
+
+/// The displaying side; stays on the thread that created it (that's the thread reporters unpark).
+pub struct ProgressWatcher {
+       shared:   Arc<Shared>,
+       /// `updates` as of our last look.
+       seen:     Cell<usize>,
+       not_send: PhantomData<*const ()>,
+}
+
+/// The counting side: clone one per worker.
+pub struct ProgressReporter {
+       shared: Arc<Shared>,
+}
+
+/// Progress at one instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+       pub done:    usize,
+       pub total:   usize,
+       pub elapsed: Duration,
+}
+
+impl ProgressWatcher {
+       /// Watch `total` items of work from the current thread; the clock starts now.
+       pub fn new(total: usize) -> Self {
+              Self {
+                     shared:   Arc::new(Shared {
+                            done:      AtomicUsize::new(0),
+                            total:     AtomicUsize::new(total),
+                            updates:   AtomicUsize::new(0),
+                            reporters: AtomicUsize::new(0),
+                            watcher:   current(),
+                            started:   Instant::now(),
+                     }),
+                     seen:     Cell::new(0),
+                     not_send: PhantomData,
+              }
+       }
+
+       pub fn reporter(&self) -> ProgressReporter {
+              self.shared.reporters.fetch_add(1, Relaxed);
+              ProgressReporter { shared: self.shared.clone() }
+       }
+
+       pub fn snapshot(&self) -> Progress {
+              Progress {
+                     done:    self.shared.done.load(Relaxed),
+                     total:   self.shared.total.load(Relaxed),
+                     elapsed: self.shared.started.elapsed(),
+              }
+       }
+
+       /// Park until something was reported since the last call, then snapshot.
+       ///
+       /// Returns at once if the work is finished or no reporters are left, so a `while` loop on
+       /// [`is_finished`](Progress::is_finished) can't hang once the workers are gone.
+       pub fn wait_for_update(&self) -> Progress {
+              loop {
+                     let updates = self.shared.updates.load(Acquire);
+                     if updates != self.seen.replace(updates) {
+                            break;
+                     }
+                     let snapshot = self.snapshot();
+                     if snapshot.is_finished() || self.shared.reporters.load(Relaxed) == 0 {
+                            return snapshot;
+                     }
+                     park();
+              }
+              self.snapshot()
+       }
+}
+
+impl ProgressReporter {
+       /// Count one item done; returns the count before it.
+       pub fn inc(&self) -> usize { self.inc_by(1) }
+
+       /// Count `n` items done; returns the count before them.
+       pub fn inc_by(&self, n: usize) -> usize {
+              let previous = self.shared.done.fetch_add(n, Relaxed);
+              self.shared.notify();
+              previous
+       }
+
+       /// Change the amount of work expected (e.g. once it's been discovered).
+       pub fn set_total(&self, total: usize) {
+              self.shared.total.store(total, Relaxed);
+              self.shared.notify();
+       }
+}
+
+impl Clone for ProgressReporter {
+       fn clone(&self) -> Self {
+              self.shared.reporters.fetch_add(1, Relaxed);
+              Self { shared: self.shared.clone() }
+       }
+}
+
+impl Drop for ProgressReporter {
+       /// The last reporter leaving is news too: the watcher stops waiting.
+       fn drop(&mut self) {
+              if self.shared.reporters.fetch_sub(1, Relaxed) == 1 {
+                     self.shared.watcher.unpark();
+              }
+       }
+}
+
+impl Progress {
+       pub fn is_finished(&self) -> bool { self.done >= self.total }
+
+       /// Done over total, in `0.0..=1.0` (`1.0` for no work at all).
+       pub fn fraction(&self) -> f64 { if self.total == 0 { 1.0 } else { (self.done as f64 / self.total as f64).min(1.0) } }
+
+       /// Items per second so far.
+       pub fn rate(&self) -> f64 {
+              let seconds = self.elapsed.as_secs_f64();
+              if seconds > 0.0 { self.done as f64 / seconds } else { 0.0 }
+       }
+
+       /// Time left at the rate so far; `None` until something is done.
+       pub fn eta(&self) -> Option<Duration> {
+              let remaining = self.total.saturating_sub(self.done);
+              if remaining == 0 {
+                     return Some(Duration::ZERO);
+              }
+              let rate = self.rate();
+              (rate > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / rate))
+       }
+}
+
+impl fmt::Display for Progress {
+       /// `done/total (pct%)`
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              write!(f, "{}/{} ({:.0}%)", self.done, self.total, self.fraction() * 100.0)
+       }
+}
+
+impl fmt::Debug for ProgressWatcher {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_tuple("ProgressWatcher").field(&self.snapshot()).finish() }
+}
+
+impl fmt::Debug for ProgressReporter {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_struct("ProgressReporter").finish_non_exhaustive() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_watcher_sees_every_increment() {
+              let watcher = ProgressWatcher::new(1_000);
+              thread::scope(|s| {
+                     for _ in 0..10 {
+                            let reporter = watcher.reporter();
+                            s.spawn(move || {
+                                   for _ in 0..100 {
+                                          reporter.inc();
+                                   }
+                            });
+                     }
+                     let mut last = 0;
+                     loop {
+                            let progress = watcher.wait_for_update();
+                            assert!(progress.done >= last, "counts only go up");
+                            last = progress.done;
+                            if progress.is_finished() {
+                                   break;
+                            }
+                     }
+              });
+              assert_eq!(watcher.snapshot().done, 1_000);
+       }
+
+       #[test]
+       fn test_wait_returns_once_reporters_are_gone() {
+              let watcher = ProgressWatcher::new(10);
+              let reporter = watcher.reporter();
+              thread::scope(|s| {
+                     s.spawn(move || {
+                            reporter.set_total(20);
+                            reporter.inc_by(5);
+                     });
+              });
+              watcher.wait_for_update();
+              let progress = watcher.wait_for_update();
+              assert_eq!((progress.done, progress.total), (5, 20));
+              assert!(!progress.is_finished());
+       }
+
+       #[test]
+       fn test_rate_and_eta() {
+              let progress = Progress { done: 25, total: 100, elapsed: Duration::from_secs(5) };
+              assert_eq!(progress.rate(), 5.0);
+              assert_eq!(progress.eta(), Some(Duration::from_secs(15)));
+              assert_eq!(progress.to_string(), "25/100 (25%)");
+              assert_eq!(Progress { done: 0, ..progress }.eta(), None);
+              assert_eq!(Progress { done: 100, ..progress }.eta(), Some(Duration::ZERO));
+       }
+}
@@ -0,0 +1,218 @@
+//! Token-bucket rate limiter in a single atomic word.
+//!
+//! A bucket holds up to `burst` tokens and refills at a steady rate; taking a token is permission to act.
+//! Short bursts go through at once, sustained load is held to the rate: the shape for throttling a log or print
+//! storm, or retries against a struggling service.
+//!
+//! The whole state, token count and refill timestamp, packs into one `u64`, so every operation is a single CAS loop:
+//! read the word, credit the tokens earned since the timestamp, take what's asked for, and publish the new word.
+//! A racing thread just recomputes from the word it lost to. There's no lock, and no background refill thread.
+//! ```text
+//! 63             16 15      0
+//! [ refilled at µs ][ tokens ]
+//! ```
+//! - the timestamp counts microseconds since the limiter was made (48 bits: 8.9 years)
+//! - it only moves forward by whole tokens' worth of time, so partial progress toward the next token isn't lost
+//!   (rounded to the microsecond, in the limiter's favour)
+//!
+//! ## Example
+//! ```
+//! use std::time::Duration;
+//!
+//! use sync::RateLimiter;
+//!
+//! let limiter = RateLimiter::new(10, 3); // 10 per second, bursts of up to 3
+//! assert_eq!((0..5).filter(|_| limiter.try_acquire()).count(), 3, "the burst, then nothing");
+//! std::thread::sleep(Duration::from_millis(110));
+//! assert!(limiter.try_acquire(), "a token refilled");
+//! ```
+
+use std::{fmt, thread,
+          time::{Duration, Instant}};
+
+use crate::atomic::{AtomicU64, Ordering::Relaxed};
+
+const TOKEN_BITS: u32 = 16;
+const TOKEN_MASK: u64 = (1 << TOKEN_BITS) - 1;
+/// Latest representable timestamp, in microseconds.
+const MAX_STAMP: u64 = u64::MAX >> TOKEN_BITS;
+
+/// Token bucket: `burst` tokens, one more every `interval`; see the [module docs](self).
+pub struct RateLimiter {
+       /// `refilled_at_micros << 16 | tokens`.
+       state:    AtomicU64,
+       /// Time to earn one token, in nanoseconds.
+       interval: u64,
+       burst:    u64,
+       started:  Instant,
+}
+
+impl RateLimiter {
+       /// Largest burst a limiter can hold.
+       pub const MAX_BURST: u32 = TOKEN_MASK as u32;
+
+       /// `per_second` tokens a second, up to `burst` at once. Starts full.
+       ///
+       /// ## Panics
+       /// If `per_second` is zero, or `burst` is zero or over [`MAX_BURST`](Self::MAX_BURST).
+       pub fn new(per_second: u32, burst: u32) -> Self {
+              assert!(per_second > 0, "a rate limiter needs a non-zero rate");
+              Self::with_interval(Duration::from_secs(1) / per_second, burst)
+       }
+
+       /// One token every `interval`, up to `burst` at once. Starts full.
+       ///
+       /// ## Panics
+       /// If `interval` is zero, or `burst` is zero or over [`MAX_BURST`](Self::MAX_BURST).
+       pub fn with_interval(interval: Duration, burst: u32) -> Self {
+              assert!(!interval.is_zero(), "a rate limiter needs a non-zero interval");
+              assert!((1..=Self::MAX_BURST).contains(&burst), "burst must be 1..={}", Self::MAX_BURST);
+              Self {
+                     state:    AtomicU64::new(u64::from(burst)),
+                     interval: u64::try_from(interval.as_nanos()).unwrap_or(u64::MAX),
+                     burst:    u64::from(burst),
+                     started:  Instant::now(),
+              }
+       }
+
+       /// Take a token if one is available right now.
+       pub fn try_acquire(&self) -> bool { self.try_acquire_n(1) }
+
+       /// Take `n` tokens if they're all available right now; otherwise take none.
+       pub fn try_acquire_n(&self, n: u32) -> bool { self.take(u64::from(n)).is_ok() }
+
+       /// Take a token, sleeping until one is available.
+       pub fn acquire(&self) { self.acquire_n(1); }
+
+       /// Take `n` tokens, sleeping until they're all available (others may take tokens meanwhile: no fairness).
+       ///
+       /// ## Panics
+       /// If `n` exceeds the burst: the bucket can never hold that many.
+       pub fn acquire_n(&self, n: u32) {
+              assert!(u64::from(n) <= self.burst, "can't acquire {n} tokens from a bucket of {}", self.burst);
+              while let Err(wait) = self.take(u64::from(n)) {
+                     thread::sleep(wait);
+              }
+       }
+
+       /// Tokens available right now. Only a snapshot.
+       pub fn available(&self) -> u32 {
+              let (tokens, stamp) = unpack(self.state.load(Relaxed));
+              self.refill(tokens, stamp, self.now()).0 as u32
+       }
+
+       pub fn burst(&self) -> u32 { self.burst as u32 }
+
+       /// Time to earn one token.
+       pub fn interval(&self) -> Duration { Duration::from_nanos(self.interval) }
+
+       /// Take `n` tokens, or say how long until there will be enough.
+       fn take(&self, n: u64) -> Result<(), Duration> {
+              let now = self.now();
+              let mut word = self.state.load(Relaxed);
+              loop {
+                     let (tokens, stamp) = unpack(word);
+                     let (tokens, stamp) = self.refill(tokens, stamp, now);
+                     if tokens < n {
+                            return Err(self.time_to_earn(n - tokens, stamp, now));
+                     }
+                     // Relaxed: the word guards only itself; a limiter publishes no other data
+                     match self.state.compare_exchange_weak(word, pack(tokens - n, stamp), Relaxed, Relaxed) {
+                            Ok(_) => return Ok(()),
+                            Err(observed) => word = observed,
+                     }
+              }
+       }
+
+       /// Credit the tokens earned between `stamp` and `now`: the new count, and the time they're counted up to.
+       fn refill(&self, tokens: u64, stamp: u64, now: u64) -> (u64, u64) {
+              let elapsed = u128::from(now.saturating_sub(stamp)) * 1_000; // another thread may have a later `now`
+              let earned = u64::try_from(elapsed / u128::from(self.interval)).unwrap_or(u64::MAX);
+              if earned >= self.burst - tokens {
+                     return (self.burst, now); // full: time spent full earns nothing
+              }
+              // forward by exactly the time those tokens took (rounded up): the rest counts toward the next one
+              let spent = (u128::from(earned) * u128::from(self.interval)).div_ceil(1_000) as u64;
+              (tokens + earned, stamp + spent)
+       }
+
+       /// How long from `now` until `missing` more tokens have been earned since `stamp`.
+       fn time_to_earn(&self, missing: u64, stamp: u64, now: u64) -> Duration {
+              let due = Duration::from_micros(stamp) + Duration::from_nanos(self.interval).saturating_mul(missing as u32);
+              due.saturating_sub(Duration::from_micros(now)).max(Duration::from_micros(1))
+       }
+
+       /// Microseconds since the limiter was made.
+       fn now(&self) -> u64 { u64::try_from(self.started.elapsed().as_micros()).unwrap_or(MAX_STAMP).min(MAX_STAMP) }
+}
+
+fn unpack(word: u64) -> (u64, u64) { (word & TOKEN_MASK, word >> TOKEN_BITS) }
+
+fn pack(tokens: u64, stamp: u64) -> u64 { stamp << TOKEN_BITS | tokens }
+
+impl fmt::Debug for RateLimiter {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("RateLimiter")
+                     .field("available", &self.available())
+                     .field("burst", &self.burst)
+                     .field("interval", &self.interval())
+                     .finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::sync::atomic::AtomicUsize;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_burst_then_rate() {
+              let limiter = RateLimiter::with_interval(Duration::from_millis(20), 4);
+              assert!(limiter.try_acquire_n(3));
+              assert!(!limiter.try_acquire_n(2), "all or nothing");
+              assert!(limiter.try_acquire());
+              assert!(!limiter.try_acquire());
+              thread::sleep(Duration::from_millis(50));
+              assert_eq!(limiter.available(), 2);
+       }
+
+       #[test]
+       fn test_refill_keeps_partial_progress() {
+              let limiter = RateLimiter::with_interval(Duration::from_millis(40), 1);
+              assert!(limiter.try_acquire());
+              // 20ms: no token yet, and asking mustn't throw away the 20ms already earned toward one
+              thread::sleep(Duration::from_millis(20));
+              assert!(!limiter.try_acquire());
+              let started = Instant::now();
+              limiter.acquire();
+              assert!(started.elapsed() < Duration::from_millis(35), "waited {:?}: only the remaining ~20ms", started.elapsed());
+       }
+
+       #[test]
+       fn test_threads_share_the_rate() {
+              const PER_SECOND: u32 = 1_000;
+              let limiter = RateLimiter::new(PER_SECOND, 10);
+              let taken = AtomicUsize::new(0);
+              let started = Instant::now();
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   for _ in 0..25 {
+                                          limiter.acquire();
+                                          taken.fetch_add(1, Relaxed);
+                                   }
+                            });
+                     }
+              });
+              // 100 tokens: 10 from the initial burst, 90 earned at 1ms each
+              assert_eq!(taken.into_inner(), 100);
+              assert!(started.elapsed() >= Duration::from_millis(89), "too fast: {:?}", started.elapsed());
+       }
+
+       #[test]
+       #[should_panic(expected = "can't acquire 5 tokens from a bucket of 4")]
+       fn test_acquiring_more_than_the_burst_panics() { RateLimiter::new(1, 4).acquire_n(5); }
+}
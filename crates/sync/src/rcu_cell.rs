@@ -0,0 +1,226 @@
+//! Read-copy-update cell: wait-free `Arc` snapshots for readers, CAS-published versions for writers.
+//!
+//! Readers [`load`](RcuCell::load) an `Arc<T>` of the current version and keep using it as long as they like,
+//! even after a writer has replaced it. Writers build a whole new `T` (copy, then update) and publish it with
+//! one pointer swap or CAS; the old version is freed when the last snapshot of it drops.
+//! Fits read-mostly data, e.g. configuration that is hot-reloaded while worker threads keep reading it.
+//!
+//! ## Design
+//! The cell owns one strong count of the current version, kept as a raw pointer in an `AtomicPtr`.
+//! The hard part is a reader's *load pointer, then bump its count*: in between, a writer could swap the pointer
+//! and drop the cell's count, freeing the version under the reader. So:
+//! - a reader announces itself in one of two counters (picked by the current `epoch`) around those two steps
+//! - a writer, after swapping the pointer out, waits for a *grace period* before giving up the old count:
+//!   it flips the epoch (new readers go to the other counter) and waits for the old counter to drain, twice,
+//!   so both counters have been seen empty since the swap
+//!
+//! Any reader that could have seen the old pointer announced itself before the swap, so it's done by then.
+//! Reads are a fixed handful of atomic ops (wait-free); only writers wait, and only for readers in flight.
+//! Grace periods are serialized with a [`Mutex`], so concurrent writers don't flip the epoch under each other.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::RcuCell;
+//!
+//! let config = RcuCell::new(vec!["alpha"]);
+//! let snapshot = config.load();
+//! thread::scope(|s| {
+//!        s.spawn(|| config.update(|names| [names.as_slice(), &["beta"]].concat()));
+//! });
+//! assert_eq!(*snapshot, ["alpha"]); // old snapshots are unaffected
+//! assert_eq!(*config.load(), ["alpha", "beta"]);
+//! ```
+
+use std::{fmt, sync::Arc};
+
+use crate::{Backoff, Mutex,
+            atomic::{AtomicPtr, AtomicUsize,
+                     Ordering::{Acquire, Relaxed, Release, SeqCst}}};
+
+/// Shared, replaceable `Arc<T>`; see the [module docs](self).
+pub struct RcuCell<T> {
+       /// From `Arc::into_raw`; the cell owns one strong count.
+       current: AtomicPtr<T>,
+       epoch:   AtomicUsize,
+       readers: [AtomicUsize; 2],
+       grace:   Mutex<()>,
+}
+// SAFETY: the cell hands out `Arc<T>`s across threads, so it needs what `Arc<T>` needs to be shared or sent.
+unsafe impl<T: Send + Sync> Sync for RcuCell<T> {}
+// SAFETY: as above.
+unsafe impl<T: Send + Sync> Send for RcuCell<T> {}
+
+impl<T> RcuCell<T> {
+       pub fn new(value: T) -> Self { Self::from_arc(Arc::new(value)) }
+
+       pub fn from_arc(value: Arc<T>) -> Self {
+              Self {
+                     current: AtomicPtr::new(Arc::into_raw(value).cast_mut()),
+                     epoch:   AtomicUsize::new(0),
+                     readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+                     grace:   Mutex::new(()),
+              }
+       }
+
+       /// Snapshot of the current version. Wait-free.
+       pub fn load(&self) -> Arc<T> {
+              let readers = &self.readers[self.epoch.load(SeqCst) % 2];
+              readers.fetch_add(1, SeqCst);
+              let current = self.current.load(SeqCst);
+              // SAFETY: `current` came from `Arc::into_raw`, and the cell's count on it is only given up after a
+              //         grace period that waits for our announcement (made before the load) to be withdrawn.
+              let snapshot = unsafe {
+                     Arc::increment_strong_count(current);
+                     Arc::from_raw(current)
+              };
+              readers.fetch_sub(1, Release);
+              snapshot
+       }
+
+       /// Publish `value`, dropping the cell's hold on the previous version.
+       pub fn store(&self, value: T) { drop(self.swap(Arc::new(value))); }
+
+       /// Publish `new`, returning the previous version. Waits for a grace period.
+       pub fn swap(&self, new: Arc<T>) -> Arc<T> {
+              let previous = self.current.swap(Arc::into_raw(new).cast_mut(), SeqCst);
+              self.synchronize();
+              // SAFETY: swapped out, and past the grace period: the cell's count on `previous` is ours to take.
+              unsafe { Arc::from_raw(previous) }
+       }
+
+       /// Publish `new` only if `current` is still the current version (by pointer).
+       ///
+       /// ## Errors
+       /// If another version was published since `current` was loaded; `new` is handed back.
+       pub fn compare_and_swap(&self, current: &Arc<T>, new: Arc<T>) -> Result<Arc<T>, Arc<T>> {
+              let new = Arc::into_raw(new).cast_mut();
+              match self.current.compare_exchange(Arc::as_ptr(current).cast_mut(), new, SeqCst, SeqCst) {
+                     Ok(previous) => {
+                            self.synchronize();
+                            // SAFETY: as in `swap`.
+                            Ok(unsafe { Arc::from_raw(previous) })
+                     }
+                     // SAFETY: `new` was never published, so we still own its count.
+                     Err(_) => Err(unsafe { Arc::from_raw(new) }),
+              }
+       }
+
+       /// Replace the value with `f(current)`, retrying if another writer got in first. Returns the new version.
+       ///
+       /// `f` may run more than once, so it should be free of side effects.
+       pub fn update(&self, mut f: impl FnMut(&T) -> T) -> Arc<T> {
+              let mut current = self.load();
+              loop {
+                     let new = Arc::new(f(&current));
+                     match self.compare_and_swap(&current, new.clone()) {
+                            Ok(_) => return new,
+                            Err(_) => current = self.load(),
+                     }
+              }
+       }
+
+       /// Wait until every reader that might still be between loading the old pointer and counting it is done.
+       fn synchronize(&self) {
+              let _grace = self.grace.lock();
+              let mut backoff = Backoff::new();
+              for _ in 0..2 {
+                     let draining = &self.readers[self.epoch.fetch_add(1, SeqCst) % 2];
+                     // Acquire: pairs with each reader's Release, so its count bump happened before we move on
+                     while draining.load(Acquire) != 0 {
+                            backoff.snooze();
+                     }
+              }
+       }
+}
+
+impl<T> Drop for RcuCell<T> {
+       fn drop(&mut self) {
+              // SAFETY: `&mut self`: no readers in flight, so the cell's count can go at once.
+              drop(unsafe { Arc::from_raw(self.current.load(Relaxed)) });
+       }
+}
+
+impl<T: Default> Default for RcuCell<T> {
+       fn default() -> Self { Self::new(T::default()) }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RcuCell<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_tuple("RcuCell").field(&self.load()).finish() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::Ordering::Relaxed, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_readers_see_whole_versions() {
+              const WRITES: usize = if cfg!(miri) { 20 } else { 2_000 };
+              // each version is internally consistent; a torn or freed one would break the invariant
+              let cell = RcuCell::new((0, 0));
+              let done = AtomicUsize::new(0);
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   let mut last = 0;
+                                   while done.load(Relaxed) == 0 {
+                                          let snapshot = cell.load();
+                                          assert_eq!(snapshot.1, snapshot.0 * 2);
+                                          assert!(snapshot.0 >= last, "versions go forward");
+                                          last = snapshot.0;
+                                   }
+                            });
+                     }
+                     s.spawn(|| {
+                            for _ in 0..WRITES {
+                                   cell.update(|&(n, _)| (n + 1, (n + 1) * 2));
+                            }
+                            done.store(1, Relaxed);
+                     });
+              });
+              assert_eq!(*cell.load(), (WRITES, WRITES * 2));
+       }
+
+       #[test]
+       fn test_concurrent_updates_all_land() {
+              let cell = RcuCell::new(0);
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   for _ in 0..100 {
+                                          cell.update(|n| n + 1);
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(*cell.load(), 400);
+       }
+
+       #[test]
+       fn test_old_versions_reclaimed_with_last_snapshot() {
+              let first = Arc::new("first");
+              let cell = RcuCell::from_arc(first.clone());
+              let snapshot = cell.load();
+              assert_eq!(Arc::strong_count(&first), 3); // ours, the cell's, the snapshot's
+              cell.store("second");
+              assert_eq!(Arc::strong_count(&first), 2);
+              drop(snapshot);
+              assert_eq!(Arc::strong_count(&first), 1);
+       }
+
+       #[test]
+       fn test_compare_and_swap() {
+              let cell = RcuCell::new(1);
+              let stale = cell.load();
+              assert_eq!(cell.swap(Arc::new(2)), stale);
+              assert_eq!(cell.compare_and_swap(&stale, Arc::new(3)).unwrap_err(), Arc::new(3));
+              let current = cell.load();
+              assert_eq!(cell.compare_and_swap(&current, Arc::new(4)).ok(), Some(Arc::new(2)));
+              assert_eq!(format!("{cell:?}"), "RcuCell(4)");
+       }
+}
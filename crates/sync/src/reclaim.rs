@@ -0,0 +1,161 @@
+//! Memory reclamation for the lock-free structures, and the plumbing its backends share.
+//!
+//! [`TreiberStack`](crate::TreiberStack) reads and retires nodes through a [`Shield`]:
+//! [hazard pointers](crate::hazard) by default, [epochs](crate::epoch) with the `epoch` feature.
+//! Both backends are always built (`benches/reclaim.rs` compares them); the feature only picks the one `Shield` wraps.
+//! - hazard pointers: a protect costs a `SeqCst` fence per pointer, but garbage is bounded
+//!   (only what's currently protected can't be freed)
+//! - epochs: a pin costs one fence per critical section however many pointers it reads,
+//!   but one stalled pinned thread holds up *all* reclamation
+
+use std::{ops::Deref, ptr};
+
+use crate::atomic::{AtomicBool, AtomicPtr,
+                    Ordering::{Acquire, Relaxed, Release}};
+#[cfg(feature = "epoch")]
+use crate::epoch;
+#[cfg(not(feature = "epoch"))]
+use crate::hazard::{self, HazardPointer};
+
+/// Keeps what it loads alive until dropped; see the [module docs](self) for which backend.
+#[cfg(not(feature = "epoch"))]
+#[derive(Debug)]
+pub(crate) struct Shield(HazardPointer);
+
+#[cfg(not(feature = "epoch"))]
+impl Shield {
+       pub(crate) fn new() -> Self { Self(HazardPointer::new()) }
+
+       /// Load `source`; the pointee stays valid until the next `protect` or the shield drops.
+       pub(crate) fn protect<T>(&mut self, source: &AtomicPtr<T>) -> *mut T { self.0.protect(source) }
+
+       /// Free `pointer` once no shield can still be reading it.
+       ///
+       /// ## Safety
+       /// As [`hazard::retire`]: a `Box` pointer, unreachable, retired once.
+       pub(crate) unsafe fn retire<T: Send>(&self, pointer: *mut T) {
+              // SAFETY: passed on from the caller.
+              unsafe { hazard::retire(pointer) }
+       }
+}
+
+/// Keeps what it loads alive until dropped; see the [module docs](self) for which backend.
+#[cfg(feature = "epoch")]
+#[derive(Debug)]
+pub(crate) struct Shield(epoch::Guard);
+
+#[cfg(feature = "epoch")]
+impl Shield {
+       pub(crate) fn new() -> Self { Self(epoch::pin()) }
+
+       /// Load `source`; the pointee stays valid until the shield drops (we're pinned all along).
+       pub(crate) fn protect<T>(&mut self, source: &AtomicPtr<T>) -> *mut T { source.load(Acquire) }
+
+       /// Free `pointer` once no shield can still be reading it.
+       ///
+       /// ## Safety
+       /// As [`Guard::defer_destroy`](epoch::Guard::defer_destroy): a `Box` pointer, unreachable, retired once.
+       pub(crate) unsafe fn retire<T: Send>(&self, pointer: *mut T) {
+              // SAFETY: passed on from the caller.
+              unsafe { self.0.defer_destroy(pointer) }
+       }
+}
+
+/// Free whatever the backend can right now (tests use it to observe reclamation).
+#[cfg(test)]
+pub(crate) fn collect() {
+       #[cfg(not(feature = "epoch"))]
+       hazard::reclaim();
+       #[cfg(feature = "epoch")]
+       epoch::collect();
+}
+
+/// A retired allocation and how to free it.
+pub(crate) struct Retired {
+       pointer: *mut (),
+       free:    unsafe fn(*mut ()),
+}
+// SAFETY: `new` requires `T: Send`, so freeing (dropping the `T`) on another thread is fine.
+unsafe impl Send for Retired {}
+
+impl Retired {
+       /// `pointer` must come from `Box::into_raw`; it is freed as a `Box<T>` by [`free_all`].
+       pub(crate) fn new<T: Send>(pointer: *mut T) -> Self { Self { pointer: pointer.cast(), free: free_box::<T> } }
+
+       pub(crate) fn address(&self) -> *mut () { self.pointer }
+}
+
+/// ## Safety
+/// `pointer` is a `Box<T>` from `Box::into_raw`, freed only here.
+unsafe fn free_box<T>(pointer: *mut ()) {
+       // SAFETY: per the contract above.
+       drop(unsafe { Box::from_raw(pointer.cast::<T>()) });
+}
+
+/// Free retired allocations the backend has proven unreachable.
+///
+/// Call outside any borrow or lock: dropping a `T` may retire more.
+pub(crate) fn free_all(freeable: impl IntoIterator<Item = Retired>) {
+       for retired in freeable {
+              // SAFETY: retired (so unreachable) and, per the backend's scan, read by no one.
+              unsafe { (retired.free)(retired.pointer) };
+       }
+}
+
+/// Grow-only, lock-free list of per-thread records (hazard slots, epoch participants).
+///
+/// Entries are leaked and never freed; a thread claims a free one and releases it for reuse when done.
+pub(crate) struct Registry<R: 'static> {
+       head: AtomicPtr<Entry<R>>,
+}
+
+pub(crate) struct Entry<R> {
+       record:  R,
+       claimed: AtomicBool,
+       next:    *const Entry<R>,
+}
+
+impl<R> Registry<R> {
+       pub(crate) const fn new() -> Self { Self { head: AtomicPtr::new(ptr::null_mut()) } }
+
+       /// Claim a released entry, or add a new one made by `record`.
+       pub(crate) fn claim(&self, record: impl FnOnce() -> R) -> &'static Entry<R> {
+              if let Some(entry) = self
+                     .entries()
+                     .find(|entry| !entry.claimed.load(Relaxed) && entry.claimed.compare_exchange(false, true, Acquire, Relaxed).is_ok())
+              {
+                     return entry;
+              }
+              let entry = Box::leak(Box::new(Entry { record: record(), claimed: AtomicBool::new(true), next: ptr::null() }));
+              let mut head = self.head.load(Relaxed);
+              loop {
+                     entry.next = head;
+                     match self.head.compare_exchange_weak(head, entry, Release, Relaxed) {
+                            Ok(_) => return entry,
+                            Err(observed) => head = observed,
+                     }
+              }
+       }
+
+       /// Every entry, claimed or not.
+       pub(crate) fn entries(&self) -> impl Iterator<Item = &'static Entry<R>> {
+              let mut current = self.head.load(Acquire);
+              std::iter::from_fn(move || {
+                     // SAFETY: entries are leaked (never freed) and fully initialized before being published with Release.
+                     let entry = unsafe { current.as_ref() }?;
+                     current = entry.next.cast_mut();
+                     Some(entry)
+              })
+       }
+}
+
+impl<R> Entry<R> {
+       /// Hand the entry back for another thread to claim.
+       pub(crate) fn release(&self) { self.claimed.store(false, Release); }
+}
+
+impl<R> Deref for Entry<R> {
+       type Target = R;
+
+       fn deref(&self) -> &R { &self.record }
+}
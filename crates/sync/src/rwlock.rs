@@ -0,0 +1,396 @@
+//! Futex-based reader-writer lock that doesn't starve writers.
+//!
+//! ## [Chapter 9: Building Our Own Locks — Reader-Writer Lock](https://marabos.nl/atomics/building-locks.html#reader-writer-lock)
+//!
+//! `state` counts read locks in steps of two, leaving the low bit as a "writer waiting" flag:
+//! ```text
+//! 2 * readers      : read-locked (or unlocked at 0), new readers welcome
+//! 2 * readers + 1  : a writer is waiting; new readers queue up behind it
+//! u32::MAX         : write-locked
+//! ```
+//! Readers futex-wait on `state`; writers on a separate `writer_wake_counter`,
+//! so a read unlock doesn't have to wake every reader just to reach one writer.
+//!
+//! A writer that gives up waiting (timed acquisition) clears the flag and wakes the other writers,
+//! which set it again if they're still there. Otherwise readers would queue behind a writer that has left.
+//!
+//! Either guard can be narrowed to part of the value with [`RwLockReadGuard::map`] / [`RwLockWriteGuard::map`].
+//!
+//! ## Example
+//! ```
+//! use sync::RwLock;
+//!
+//! let lock = RwLock::new(vec![1]);
+//! {
+//!        let (a, b) = (lock.read(), lock.read());
+//!        assert_eq!(a.len() + b.len(), 2);
+//! }
+//! lock.write().push(2);
+//! assert_eq!(*lock.read(), [1, 2]);
+//! ```
+
+use std::{cell::UnsafeCell,
+          marker::PhantomData,
+          mem,
+          ops::{Deref, DerefMut},
+          ptr::NonNull,
+          time::{Duration, Instant}};
+
+use crate::{atomic::{AtomicU32,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn, try_update},
+            deadline,
+            futex::{wait_until, wake_all, wake_one},
+            histogram::{WaitSite, WaitTimer}};
+
+const WRITE_LOCKED: u32 = u32::MAX;
+
+/// Many readers or one writer; waiting writers block new readers.
+pub struct RwLock<T> {
+       state:               AtomicU32,
+       /// Bumped to wake writers.
+       writer_wake_counter: AtomicU32,
+       value:               UnsafeCell<T>,
+}
+// SAFETY: readers share `&T` across threads (needs `Sync`); a writer may be on any thread (needs `Send`).
+unsafe impl<T> Sync for RwLock<T> where T: Send + Sync {}
+
+impl<T> RwLock<T> {
+       loom_const_fn! {
+              pub fn new(value: T) -> Self {
+                     Self {
+                            state:               AtomicU32::new(0),
+                            writer_wake_counter: AtomicU32::new(0),
+                            value:               UnsafeCell::new(value),
+                     }
+              }
+       }
+
+       /// Block until a read lock is ours.
+       pub fn read(&self) -> RwLockReadGuard<'_, T> { self.read_until(None).expect("no deadline to miss") }
+
+       /// Take a read lock only if no writer holds or waits for the lock.
+       pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> { self.read_until(Some(Instant::now())) }
+
+       /// As [`read`](Self::read), giving up after `timeout`.
+       pub fn try_read_for(&self, timeout: Duration) -> Option<RwLockReadGuard<'_, T>> { self.read_until(deadline::after(timeout)) }
+
+       /// As [`read`](Self::read), giving up at `deadline`.
+       pub fn try_read_until(&self, deadline: Instant) -> Option<RwLockReadGuard<'_, T>> { self.read_until(Some(deadline)) }
+
+       fn read_until(&self, deadline: Option<Instant>) -> Option<RwLockReadGuard<'_, T>> {
+              let mut waited = WaitTimer::new(WaitSite::RwLockRead);
+              let mut s = self.state.load(Relaxed);
+              loop {
+                     if s.is_multiple_of(2) {
+                            assert!(s < WRITE_LOCKED - 2, "too many readers");
+                            match self.state.compare_exchange_weak(s, s + 2, Acquire, Relaxed) {
+                                   Ok(_) => return Some(RwLockReadGuard { rwlock: self }),
+                                   Err(e) => s = e,
+                            }
+                     }
+                     if !s.is_multiple_of(2) {
+                            waited.start();
+                            if !wait_until(&self.state, s, deadline) {
+                                   return None;
+                            }
+                            s = self.state.load(Relaxed);
+                     }
+              }
+       }
+
+       /// Block until the write lock is ours.
+       pub fn write(&self) -> RwLockWriteGuard<'_, T> { self.write_until(None).expect("no deadline to miss") }
+
+       /// Take the write lock only if nobody holds the lock.
+       pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+              // a waiting writer (odd state) hasn't got the lock yet, so we may barge past it
+              let s = self.state.load(Relaxed);
+              (s <= 1 && self.state.compare_exchange(s, WRITE_LOCKED, Acquire, Relaxed).is_ok()).then(|| RwLockWriteGuard { rwlock: self }) // lazily: a guard built and dropped would unlock
+       }
+
+       /// As [`write`](Self::write), giving up after `timeout`.
+       pub fn try_write_for(&self, timeout: Duration) -> Option<RwLockWriteGuard<'_, T>> { self.write_until(deadline::after(timeout)) }
+
+       /// As [`write`](Self::write), giving up at `deadline`.
+       pub fn try_write_until(&self, deadline: Instant) -> Option<RwLockWriteGuard<'_, T>> { self.write_until(Some(deadline)) }
+
+       fn write_until(&self, deadline: Option<Instant>) -> Option<RwLockWriteGuard<'_, T>> {
+              let mut waited = WaitTimer::new(WaitSite::RwLockWrite);
+              let mut s = self.state.load(Relaxed);
+              loop {
+                     if s <= 1 {
+                            match self.state.compare_exchange(s, WRITE_LOCKED, Acquire, Relaxed) {
+                                   Ok(_) => return Some(RwLockWriteGuard { rwlock: self }),
+                                   Err(e) => {
+                                          s = e;
+                                          continue;
+                                   }
+                            }
+                     }
+                     // block new readers by making the state odd
+                     if s.is_multiple_of(2)
+                            && let Err(e) = self.state.compare_exchange(s, s + 1, Relaxed, Relaxed)
+                     {
+                            s = e;
+                            continue;
+                     }
+                     let w = self.writer_wake_counter.load(Acquire);
+                     s = self.state.load(Relaxed);
+                     if s >= 2 {
+                            waited.start();
+                            if !wait_until(&self.writer_wake_counter, w, deadline) {
+                                   self.abandon_write();
+                                   return None;
+                            }
+                            s = self.state.load(Relaxed);
+                     }
+              }
+       }
+
+       /// A timed-out writer withdraws its "writer waiting" flag; any remaining writers re-raise it.
+       #[cold]
+       fn abandon_write(&self) {
+              let _ = try_update(&self.state, Relaxed, Relaxed, |s| (s != WRITE_LOCKED && !s.is_multiple_of(2)).then(|| s - 1));
+              self.writer_wake_counter.fetch_add(1, Release);
+              wake_all(&self.writer_wake_counter);
+              wake_all(&self.state);
+       }
+
+       /// Drop one read lock (from a read guard, mapped or not).
+       fn read_unlock(&self) {
+              // 3 -> 1: last reader out, and a writer is waiting
+              if self.state.fetch_sub(2, Release) == 3 {
+                     self.writer_wake_counter.fetch_add(1, Release);
+                     wake_one(&self.writer_wake_counter);
+              }
+       }
+
+       /// Drop the write lock (from a write guard, mapped or not).
+       fn write_unlock(&self) {
+              self.state.store(0, Release);
+              self.writer_wake_counter.fetch_add(1, Release);
+              wake_one(&self.writer_wake_counter);
+              wake_all(&self.state);
+       }
+
+       /// No locking needed: `&mut self` proves exclusive access.
+       pub fn get_mut(&mut self) -> &mut T { self.value.get_mut() }
+
+       pub fn into_inner(self) -> T { self.value.into_inner() }
+}
+
+impl<T: Default> Default for RwLock<T> {
+       fn default() -> Self { Self::new(T::default()) }
+}
+
+/// Shared access to an [`RwLock`]'s value.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct RwLockReadGuard<'a, T> {
+       rwlock: &'a RwLock<T>,
+}
+impl<T> Deref for RwLockReadGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: a read lock excludes writers.
+              unsafe { &*self.rwlock.value.get() }
+       }
+}
+impl<'a, T> RwLockReadGuard<'a, T> {
+       /// A guard for the part of the value `f` picks, holding the read lock until it's dropped.
+       pub fn map<U: ?Sized>(this: Self, f: impl FnOnce(&T) -> &U) -> MappedRwLockReadGuard<'a, T, U> {
+              let value = NonNull::from(f(&this)); // if `f` panics, `this` unlocks as it unwinds
+              let rwlock = this.rwlock;
+              mem::forget(this); // the mapped guard unlocks instead
+              MappedRwLockReadGuard { rwlock, value, _borrow: PhantomData }
+       }
+}
+impl<T> Drop for RwLockReadGuard<'_, T> {
+       fn drop(&mut self) { self.rwlock.read_unlock(); }
+}
+
+/// Exclusive access to an [`RwLock`]'s value.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct RwLockWriteGuard<'a, T> {
+       rwlock: &'a RwLock<T>,
+}
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: the write lock excludes everyone else.
+              unsafe { &*self.rwlock.value.get() }
+       }
+}
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T {
+              // SAFETY: the write lock excludes everyone else.
+              unsafe { &mut *self.rwlock.value.get() }
+       }
+}
+impl<'a, T> RwLockWriteGuard<'a, T> {
+       /// A guard for the part of the value `f` picks, holding the write lock until it's dropped.
+       pub fn map<U: ?Sized>(mut this: Self, f: impl FnOnce(&mut T) -> &mut U) -> MappedRwLockWriteGuard<'a, T, U> {
+              let value = NonNull::from(f(&mut this));
+              let rwlock = this.rwlock;
+              mem::forget(this);
+              MappedRwLockWriteGuard { rwlock, value, _borrow: PhantomData }
+       }
+}
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+       fn drop(&mut self) { self.rwlock.write_unlock(); }
+}
+
+/// Shared access to part of an [`RwLock`]'s value, from [`RwLockReadGuard::map`].
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct MappedRwLockReadGuard<'a, T, U: ?Sized> {
+       rwlock:  &'a RwLock<T>,
+       value:   NonNull<U>,
+       _borrow: PhantomData<&'a U>,
+}
+// SAFETY: as `&U` would be: the read lock keeps writers out while we point into the value.
+unsafe impl<T, U: ?Sized + Sync> Sync for MappedRwLockReadGuard<'_, T, U> {}
+// SAFETY: as `&U` would be (and a read lock may be released from any thread).
+unsafe impl<T, U: ?Sized + Sync> Send for MappedRwLockReadGuard<'_, T, U> {}
+
+impl<'a, T, U: ?Sized> MappedRwLockReadGuard<'a, T, U> {
+       /// Narrow further.
+       pub fn map<V: ?Sized>(this: Self, f: impl FnOnce(&U) -> &V) -> MappedRwLockReadGuard<'a, T, V> {
+              let value = NonNull::from(f(&this));
+              let rwlock = this.rwlock;
+              mem::forget(this);
+              MappedRwLockReadGuard { rwlock, value, _borrow: PhantomData }
+       }
+}
+impl<T, U: ?Sized> Deref for MappedRwLockReadGuard<'_, T, U> {
+       type Target = U;
+
+       fn deref(&self) -> &U {
+              // SAFETY: points into the value; the read lock we hold excludes writers.
+              unsafe { self.value.as_ref() }
+       }
+}
+impl<T, U: ?Sized> Drop for MappedRwLockReadGuard<'_, T, U> {
+       fn drop(&mut self) { self.rwlock.read_unlock(); }
+}
+
+/// Exclusive access to part of an [`RwLock`]'s value, from [`RwLockWriteGuard::map`].
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct MappedRwLockWriteGuard<'a, T, U: ?Sized> {
+       rwlock:  &'a RwLock<T>,
+       value:   NonNull<U>,
+       _borrow: PhantomData<&'a mut U>,
+}
+// SAFETY: as `&mut U` would be: the write lock makes the pointer an exclusive borrow.
+unsafe impl<T, U: ?Sized + Sync> Sync for MappedRwLockWriteGuard<'_, T, U> {}
+// SAFETY: as `&mut U` would be (and the write lock may be released from any thread).
+unsafe impl<T, U: ?Sized + Send> Send for MappedRwLockWriteGuard<'_, T, U> {}
+
+impl<'a, T, U: ?Sized> MappedRwLockWriteGuard<'a, T, U> {
+       /// Narrow further.
+       pub fn map<V: ?Sized>(mut this: Self, f: impl FnOnce(&mut U) -> &mut V) -> MappedRwLockWriteGuard<'a, T, V> {
+              let value = NonNull::from(f(&mut this));
+              let rwlock = this.rwlock;
+              mem::forget(this);
+              MappedRwLockWriteGuard { rwlock, value, _borrow: PhantomData }
+       }
+}
+impl<T, U: ?Sized> Deref for MappedRwLockWriteGuard<'_, T, U> {
+       type Target = U;
+
+       fn deref(&self) -> &U {
+              // SAFETY: points into the value; the write lock we hold excludes everyone else.
+              unsafe { self.value.as_ref() }
+       }
+}
+impl<T, U: ?Sized> DerefMut for MappedRwLockWriteGuard<'_, T, U> {
+       fn deref_mut(&mut self) -> &mut U {
+              // SAFETY: points into the value; the write lock we hold excludes everyone else.
+              unsafe { self.value.as_mut() }
+       }
+}
+impl<T, U: ?Sized> Drop for MappedRwLockWriteGuard<'_, T, U> {
+       fn drop(&mut self) { self.rwlock.write_unlock(); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_readers_and_writers() {
+              const NUM_WRITERS: usize = 4;
+              const PER_WRITER: usize = 1_000;
+              let lock = RwLock::new((0, 0));
+              thread::scope(|s| {
+                     for _ in 0..NUM_WRITERS {
+                            s.spawn(|| {
+                                   for _ in 0..PER_WRITER {
+                                          let mut guard = lock.write();
+                                          guard.0 += 1;
+                                          guard.1 += 1;
+                                   }
+                            });
+                            s.spawn(|| {
+                                   for _ in 0..PER_WRITER {
+                                          let guard = lock.read();
+                                          assert_eq!(guard.0, guard.1, "a reader saw a half-finished write");
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(lock.into_inner(), (NUM_WRITERS * PER_WRITER, NUM_WRITERS * PER_WRITER));
+       }
+
+       #[test]
+       fn test_waiting_writer_blocks_new_readers() {
+              let lock = RwLock::new(());
+              thread::scope(|s| {
+                     let reader = lock.read();
+                     let writer = s.spawn(|| drop(lock.write()));
+                     while lock.state.load(Relaxed).is_multiple_of(2) {
+                            thread::yield_now();
+                     }
+                     assert!(lock.try_read().is_none());
+                     drop(reader);
+                     writer.join().unwrap();
+              });
+              assert!(lock.try_read().is_some());
+       }
+
+       #[test]
+       fn test_mapped_guards() {
+              let lock = RwLock::new((1, vec![String::from("a")]));
+              {
+                     let count = RwLockReadGuard::map(lock.read(), |(count, _)| count);
+                     let name = MappedRwLockReadGuard::map(RwLockReadGuard::map(lock.read(), |(_, names)| names), |names| &names[0]);
+                     assert_eq!((*count, name.as_str()), (1, "a"));
+                     assert!(lock.try_write().is_none(), "mapped read guards still hold read locks");
+              }
+              let mut names = RwLockWriteGuard::map(lock.write(), |(_, names)| names);
+              names.push(String::from("b"));
+              assert!(lock.try_read().is_none());
+              drop(names);
+              assert_eq!(lock.read().1, ["a", "b"]);
+       }
+
+       #[test]
+       fn test_timed_out_writer_lets_readers_back_in() {
+              let lock = RwLock::new(());
+              let reader = lock.read();
+              assert!(lock.try_write_for(Duration::from_millis(5)).is_none());
+              // the flag it raised while waiting is gone again
+              assert!(lock.try_read_until(Instant::now()).is_some());
+              drop(reader);
+              assert!(lock.try_write_for(Duration::from_secs(60)).is_some());
+
+              let writer = lock.write();
+              assert!(lock.try_read_for(Duration::from_millis(5)).is_none());
+              drop(writer);
+       }
+}
@@ -0,0 +1,203 @@
+//! Futex-based counting semaphore.
+//!
+//! A pool of permits: [`acquire`](Semaphore::acquire) takes one (blocking while none are left),
+//! dropping the returned [`SemaphorePermit`] gives it back. Bounds how many threads do something at once,
+//! e.g. a thread pool's queue depth or the number of open connections.
+//!
+//! ## Design
+//! - `permits` is the futex word: waiters sleep on the value they saw, so *any* release wakes them
+//! - `waiters` lets a release skip the wake syscall when nobody sleeps
+//! - a release wakes *all* waiters: with `acquire_many` the first sleeper may need more than was returned,
+//!   while a later one would fit
+//!
+//! The waiter-count check is a Dekker-style handshake (waiter: count self, then read permits;
+//! releaser: add permits, then read waiters), so both sides use `SeqCst`: at least one sees the other.
+//!
+//! ## Example
+//! ```
+//! use std::{sync::atomic::{AtomicUsize, Ordering::Relaxed},
+//!           thread};
+//!
+//! use sync::Semaphore;
+//!
+//! let (semaphore, active, max_active) = (Semaphore::new(2), AtomicUsize::new(0), AtomicUsize::new(0));
+//! thread::scope(|s| {
+//!        for _ in 0..8 {
+//!               s.spawn(|| {
+//!                      let _permit = semaphore.acquire();
+//!                      max_active.fetch_max(active.fetch_add(1, Relaxed) + 1, Relaxed);
+//!                      active.fetch_sub(1, Relaxed);
+//!               });
+//!        }
+//! });
+//! assert!(max_active.into_inner() <= 2);
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::{atomic::{AtomicU32,
+                     Ordering::{Acquire, Relaxed, SeqCst},
+                     loom_const_fn, try_update},
+            deadline,
+            futex::{wait_until, wake_all}};
+
+/// Counting semaphore; permits are handed out as RAII guards.
+pub struct Semaphore {
+       permits: AtomicU32,
+       waiters: AtomicU32,
+}
+
+impl Semaphore {
+       loom_const_fn! {
+              pub fn new(permits: u32) -> Self { Self { permits: AtomicU32::new(permits), waiters: AtomicU32::new(0) } }
+       }
+
+       /// Permits not currently handed out. Only a snapshot.
+       pub fn available_permits(&self) -> u32 { self.permits.load(Relaxed) }
+
+       /// Block until a permit is ours.
+       pub fn acquire(&self) -> SemaphorePermit<'_> { self.acquire_many(1) }
+
+       /// Block until `n` permits are ours, taken all at once.
+       ///
+       /// Blocks forever if `n` exceeds what the semaphore will ever hold.
+       /// (No up-front check: permits can be [added](Self::add_permits) later.)
+       pub fn acquire_many(&self, n: u32) -> SemaphorePermit<'_> { self.acquire_until(n, None).expect("no deadline to miss") }
+
+       /// Take a permit only if one is available right now.
+       pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> { self.try_acquire_many(1) }
+
+       /// Take `n` permits only if that many are available right now.
+       pub fn try_acquire_many(&self, n: u32) -> Option<SemaphorePermit<'_>> {
+              try_update(&self.permits, Acquire, Relaxed, |permits| permits.checked_sub(n))
+                     .ok()
+                     .map(|_| SemaphorePermit { semaphore: self, count: n })
+       }
+
+       /// As [`acquire_many`](Self::acquire_many), giving up after `timeout`.
+       pub fn try_acquire_for(&self, n: u32, timeout: Duration) -> Option<SemaphorePermit<'_>> {
+              self.acquire_until(n, deadline::after(timeout))
+       }
+
+       /// As [`acquire_many`](Self::acquire_many), giving up at `deadline`.
+       pub fn try_acquire_until(&self, n: u32, deadline: Instant) -> Option<SemaphorePermit<'_>> { self.acquire_until(n, Some(deadline)) }
+
+       fn acquire_until(&self, n: u32, deadline: Option<Instant>) -> Option<SemaphorePermit<'_>> {
+              loop {
+                     if let Some(permit) = self.try_acquire_many(n) {
+                            return Some(permit);
+                     }
+                     self.waiters.fetch_add(1, SeqCst);
+                     let permits = self.permits.load(SeqCst);
+                     let in_time = permits >= n || wait_until(&self.permits, permits, deadline);
+                     self.waiters.fetch_sub(1, Relaxed);
+                     if !in_time {
+                            return self.try_acquire_many(n); // one last try: permits may have raced the deadline
+                     }
+              }
+       }
+
+       /// Hand out `n` more permits, beyond those returned by dropped [`SemaphorePermit`]s.
+       pub fn add_permits(&self, n: u32) {
+              let previous = self.permits.fetch_add(n, SeqCst);
+              assert!(previous.checked_add(n).is_some(), "semaphore permit count overflowed");
+              if self.waiters.load(SeqCst) > 0 {
+                     wake_all(&self.permits);
+              }
+       }
+}
+
+/// Permits held from a [`Semaphore`]; returned on drop.
+#[must_use = "the permits are returned as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct SemaphorePermit<'a> {
+       semaphore: &'a Semaphore,
+       count:     u32,
+}
+impl SemaphorePermit<'_> {
+       pub fn count(&self) -> u32 { self.count }
+
+       /// Keep the permits taken for good: the semaphore shrinks by `count`.
+       pub fn forget(mut self) { self.count = 0; }
+}
+impl Drop for SemaphorePermit<'_> {
+       fn drop(&mut self) {
+              if self.count > 0 {
+                     self.semaphore.add_permits(self.count);
+              }
+       }
+}
+
+impl std::fmt::Debug for Semaphore {
+       fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+              f.debug_struct("Semaphore").field("available_permits", &self.available_permits()).finish_non_exhaustive()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_bounds_concurrency() {
+              const PERMITS: u32 = 3;
+              let semaphore = Semaphore::new(PERMITS);
+              let (active, max_active) = (AtomicUsize::new(0), AtomicUsize::new(0));
+              thread::scope(|s| {
+                     for _ in 0..16 {
+                            s.spawn(|| {
+                                   for _ in 0..100 {
+                                          let _permit = semaphore.acquire();
+                                          max_active.fetch_max(active.fetch_add(1, Relaxed) + 1, Relaxed);
+                                          thread::yield_now();
+                                          active.fetch_sub(1, Relaxed);
+                                   }
+                            });
+                     }
+              });
+              assert!(max_active.into_inner() <= PERMITS as usize);
+              assert_eq!(semaphore.available_permits(), PERMITS);
+       }
+
+       #[test]
+       fn test_acquire_many_waits_for_enough() {
+              let semaphore = Semaphore::new(3);
+              let first = semaphore.acquire_many(2);
+              assert!(semaphore.try_acquire_many(2).is_none());
+              thread::scope(|s| {
+                     let big = s.spawn(|| semaphore.acquire_many(3).count());
+                     thread::sleep(Duration::from_millis(10));
+                     drop(first);
+                     assert_eq!(big.join().unwrap(), 3);
+              });
+              assert_eq!(semaphore.available_permits(), 3);
+       }
+
+       #[test]
+       fn test_try_acquire_and_forget() {
+              let semaphore = Semaphore::new(1);
+              let permit = semaphore.try_acquire().unwrap();
+              assert!(semaphore.try_acquire().is_none());
+              permit.forget();
+              assert_eq!(semaphore.available_permits(), 0);
+              semaphore.add_permits(1);
+              assert!(semaphore.try_acquire().is_some());
+       }
+
+       #[test]
+       fn test_try_acquire_for() {
+              let semaphore = Semaphore::new(1);
+              let held = semaphore.acquire();
+              assert!(semaphore.try_acquire_for(1, Duration::from_millis(5)).is_none());
+              thread::scope(|s| {
+                     let waiter = s.spawn(|| semaphore.try_acquire_for(1, Duration::from_secs(60)).is_some());
+                     drop(held);
+                     assert!(waiter.join().unwrap());
+              });
+              assert!(semaphore.try_acquire_until(1, Instant::now()).is_some());
+       }
+}
@@ -0,0 +1,141 @@
+//! Striped counter for write-heavy, read-rarely statistics.
+//!
+//! A single `AtomicUsize` hit by many cores bounces one cache line between them; every `fetch_add` waits its turn.
+//! [`ShardedCounter`] gives each thread its own shard (on its own cache line) to add to,
+//! and only [`sum`](ShardedCounter::sum) touches them all.
+//! - increments scale with cores; a read costs one load per shard
+//! - the sum isn't a snapshot: increments racing with it may or may not be counted
+//!
+//! ## Design
+//! - shards: a power of two, at least the number of cores (so threads rarely share one)
+//! - each thread takes a stripe index once, round-robin, and keeps it: a cheap stand-in for "the current core"
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::ShardedCounter;
+//!
+//! let hits = ShardedCounter::new();
+//! thread::scope(|s| {
+//!        for _ in 0..8 {
+//!               s.spawn(|| (0..1_000).for_each(|_| hits.inc()));
+//!        }
+//! });
+//! assert_eq!(hits.sum(), 8_000);
+//! ```
+
+use std::{fmt,
+          ops::{Deref, DerefMut},
+          thread};
+
+use crate::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// Aligns (and so pads) `T` to its own cache line, keeping neighbours from false sharing.
+///
+/// 128 bytes: adjacent-line prefetching pulls in pairs of 64-byte lines on x86_64, and some ARM cores use 128-byte lines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(align(128))]
+pub struct CachePadded<T>(pub T);
+
+impl<T> Deref for CachePadded<T> {
+       type Target = T;
+
+       fn deref(&self) -> &T { &self.0 }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+       fn deref_mut(&mut self) -> &mut T { &mut self.0 }
+}
+
+/// Next thread's stripe index.
+static NEXT_STRIPE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+       static STRIPE: usize = NEXT_STRIPE.fetch_add(1, Relaxed);
+}
+
+/// This thread's stripe index: taken round-robin on first use, then kept.
+///
+/// Once thread-locals are gone (a destructor running late), every such caller shares stripe 0.
+pub(crate) fn stripe() -> usize { STRIPE.try_with(|stripe| *stripe).unwrap_or(0) }
+
+/// Counter whose increments are spread over per-thread, cache-padded shards.
+pub struct ShardedCounter {
+       shards: Box<[CachePadded<AtomicUsize>]>,
+}
+
+impl ShardedCounter {
+       /// One shard per core (rounded up to a power of two).
+       pub fn new() -> Self { Self::with_shards(thread::available_parallelism().map_or(1, |cores| cores.get())) }
+
+       /// At least `shards` shards (rounded up to a power of two; zero counts as one).
+       pub fn with_shards(shards: usize) -> Self {
+              Self { shards: (0..shards.max(1).next_power_of_two()).map(|_| CachePadded(AtomicUsize::new(0))).collect() }
+       }
+
+       pub fn inc(&self) { self.add(1); }
+
+       /// Add `n` (wrapping) to this thread's shard.
+       pub fn add(&self, n: usize) { self.shard().fetch_add(n, Relaxed); }
+
+       /// Total over all shards; increments concurrent with the call may be missed.
+       pub fn sum(&self) -> usize { self.shards.iter().fold(0, |sum, shard| sum.wrapping_add(shard.load(Relaxed))) }
+
+       /// Reset to zero, returning the total. Exact: `&mut` means no one is adding.
+       pub fn take(&mut self) -> usize { self.shards.iter_mut().fold(0, |sum, shard| sum.wrapping_add(std::mem::take(shard.get_mut()))) }
+
+       pub fn shard_count(&self) -> usize { self.shards.len() }
+
+       fn shard(&self) -> &AtomicUsize { &self.shards[stripe() & (self.shards.len() - 1)] }
+}
+
+impl Default for ShardedCounter {
+       fn default() -> Self { Self::new() }
+}
+
+impl fmt::Debug for ShardedCounter {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_tuple("ShardedCounter").field(&self.sum()).finish() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::mem;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_concurrent_increments_all_counted() {
+              let counter = ShardedCounter::with_shards(4);
+              thread::scope(|s| {
+                     for t in 0..16 {
+                            let counter = &counter;
+                            s.spawn(move || {
+                                   for _ in 0..1_000 {
+                                          counter.inc();
+                                   }
+                                   counter.add(t);
+                            });
+                     }
+              });
+              assert_eq!(counter.sum(), 16_000 + (0..16).sum::<usize>());
+       }
+
+       #[test]
+       fn test_take_resets() {
+              let mut counter = ShardedCounter::with_shards(3);
+              assert_eq!(counter.shard_count(), 4);
+              counter.add(7);
+              assert_eq!(format!("{counter:?}"), "ShardedCounter(7)");
+              assert_eq!(counter.take(), 7);
+              assert_eq!(counter.sum(), 0);
+       }
+
+       #[test]
+       fn test_cache_padding() {
+              assert_eq!(mem::align_of::<CachePadded<AtomicUsize>>(), 128);
+              assert_eq!(mem::size_of::<CachePadded<u8>>(), 128);
+       }
+}
@@ -0,0 +1,187 @@
+//! Hot-reloadable settings: wait-free reads of the current version, with change notification.
+//!
+//! An [`RcuCell`] plus what a service's configuration wants on top of it:
+//! - [`load`](SharedConfig::load): the current `Arc<T>`, wait-free; no lock on the read path
+//! - [`store`](SharedConfig::store) / [`update`](SharedConfig::update): publish a new version
+//! - [`version`](SharedConfig::version): a counter bumped by every publish, for a cheap "has it changed?" check
+//! - [`subscribe`](SharedConfig::subscribe): a hook called with every new version, e.g. to resize a pool
+//!
+//! Writers are serialized, and each runs the subscribers for its version before the next writer publishes,
+//! so every subscriber sees every version, in order. Subscribers run on the writing thread, holding the writers' lock:
+//! keep them short, and don't publish or (un)subscribe from inside one (that deadlocks).
+//!
+//! ## Example
+//! ```
+//! use std::sync::{Arc, Mutex};
+//!
+//! use sync::SharedConfig;
+//!
+//! let config = SharedConfig::new(4_usize); // e.g. worker count
+//! let seen = Arc::new(Mutex::new(Vec::new()));
+//! let subscription = config.subscribe({
+//!        let seen = seen.clone();
+//!        move |workers: &Arc<usize>| seen.lock().unwrap().push(**workers)
+//! });
+//! config.store(8);
+//! config.update(|workers| workers * 2);
+//! config.unsubscribe(subscription);
+//! config.store(1);
+//! assert_eq!((*config.load(), config.version()), (1, 3));
+//! assert_eq!(*seen.lock().unwrap(), [8, 16]);
+//! ```
+
+use std::{fmt, sync::Arc};
+
+use crate::{Mutex, RcuCell,
+            atomic::{AtomicU64,
+                     Ordering::{Acquire, Release}}};
+
+type Subscriber<T> = Box<dyn Fn(&Arc<T>) + Send + Sync>;
+
+/// Read-mostly, replaceable settings; see the [module docs](self).
+pub struct SharedConfig<T> {
+       current:     RcuCell<T>,
+       version:     AtomicU64,
+       /// Serializes writers; the subscribers, tagged for [`unsubscribe`](SharedConfig::unsubscribe).
+       subscribers: Mutex<Subscribers<T>>,
+}
+
+struct Subscribers<T> {
+       next_id: u64,
+       list:    Vec<(SubscriptionId, Subscriber<T>)>,
+}
+
+/// Names a subscription, to [`unsubscribe`](SharedConfig::unsubscribe) it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+impl<T> SharedConfig<T> {
+       pub fn new(value: T) -> Self {
+              Self {
+                     current:     RcuCell::new(value),
+                     version:     AtomicU64::new(0),
+                     subscribers: Mutex::new(Subscribers { next_id: 0, list: Vec::new() }),
+              }
+       }
+
+       /// The current settings. Wait-free; keep the `Arc` as long as needed, a later publish doesn't affect it.
+       pub fn load(&self) -> Arc<T> { self.current.load() }
+
+       /// How many versions have been published since [`new`](Self::new).
+       ///
+       /// Seeing a new number means [`load`](Self::load) returns that version (or a later one).
+       pub fn version(&self) -> u64 { self.version.load(Acquire) }
+
+       /// Publish `value` and notify the subscribers. Returns the new version.
+       pub fn store(&self, value: T) -> Arc<T> { self.publish(|_| value) }
+
+       /// Publish `f(current)` and notify the subscribers. Returns the new version.
+       ///
+       /// Writers are serialized, so `f` runs exactly once, on the latest version.
+       pub fn update(&self, f: impl FnOnce(&T) -> T) -> Arc<T> { self.publish(f) }
+
+       fn publish(&self, f: impl FnOnce(&T) -> T) -> Arc<T> {
+              let subscribers = self.subscribers.lock();
+              let new = Arc::new(f(&self.current.load()));
+              drop(self.current.swap(new.clone()));
+              self.version.fetch_add(1, Release); // after the swap: a reader seeing the new number loads `new`
+              for (_, subscriber) in &subscribers.list {
+                     subscriber(&new);
+              }
+              new
+       }
+
+       /// Call `subscriber` with every version published from now on.
+       pub fn subscribe(&self, subscriber: impl Fn(&Arc<T>) + Send + Sync + 'static) -> SubscriptionId {
+              let mut subscribers = self.subscribers.lock();
+              let id = SubscriptionId(subscribers.next_id);
+              subscribers.next_id += 1;
+              subscribers.list.push((id, Box::new(subscriber)));
+              id
+       }
+
+       /// Stop calling a subscriber; `false` if it was already gone.
+       pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+              let mut subscribers = self.subscribers.lock();
+              let before = subscribers.list.len();
+              subscribers.list.retain(|(subscribed, _)| *subscribed != id);
+              subscribers.list.len() < before
+       }
+}
+
+impl<T: Default> Default for SharedConfig<T> {
+       fn default() -> Self { Self::new(T::default()) }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SharedConfig<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("SharedConfig").field("version", &self.version()).field("current", &self.load()).finish_non_exhaustive()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::{AtomicUsize, Ordering::Relaxed},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_subscribers_see_every_version_in_order() {
+              const WRITERS: usize = 4;
+              const PER_WRITER: usize = if cfg!(miri) { 10 } else { 250 };
+              let config = SharedConfig::new(0_usize);
+              let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+              config.subscribe({
+                     let seen = seen.clone();
+                     move |value: &Arc<usize>| seen.lock().unwrap().push(**value)
+              });
+              thread::scope(|s| {
+                     for _ in 0..WRITERS {
+                            s.spawn(|| (0..PER_WRITER).for_each(|_| drop(config.update(|n| n + 1))));
+                     }
+                     s.spawn(|| {
+                            let mut last = 0;
+                            while last < WRITERS * PER_WRITER {
+                                   let version = config.version();
+                                   let value = *config.load();
+                                   assert!(value as u64 >= version, "a reader that saw version {version} loaded {value}");
+                                   assert!(value >= last, "versions go forward");
+                                   last = value;
+                            }
+                     });
+              });
+              assert_eq!(*seen.lock().unwrap(), (1..=WRITERS * PER_WRITER).collect::<Vec<_>>());
+              assert_eq!(config.version(), (WRITERS * PER_WRITER) as u64);
+       }
+
+       #[test]
+       fn test_unsubscribe() {
+              let config = SharedConfig::new("initial");
+              let calls = Arc::new(AtomicUsize::new(0));
+              let [first, second] = [(); 2].map(|()| {
+                     let calls = calls.clone();
+                     config.subscribe(move |_| {
+                            calls.fetch_add(1, Relaxed);
+                     })
+              });
+              config.store("both");
+              assert!(config.unsubscribe(first));
+              assert!(!config.unsubscribe(first), "already gone");
+              config.store("second only");
+              assert!(config.unsubscribe(second));
+              config.store("nobody");
+              assert_eq!((calls.load(Relaxed), *config.load()), (3, "nobody"));
+       }
+
+       #[test]
+       fn test_old_snapshots_survive_a_publish() {
+              let config = SharedConfig::new(vec![1, 2]);
+              let snapshot = config.load();
+              config.update(|values| values.iter().map(|v| v * 10).collect());
+              assert_eq!(*snapshot, [1, 2]);
+              assert_eq!(*config.load(), [10, 20]);
+       }
+}
@@ -0,0 +1,124 @@
+//! Shutdown coordinator: turn Ctrl-C (`SIGINT`) and `SIGTERM` into a [`CancellationToken`].
+//!
+//! A signal handler may do almost nothing: no locks, no allocation, so no `cancel()` (which takes the token's lock
+//! and wakes its children). The handler here only bumps a static futex word and wakes it; a watcher thread, waiting
+//! on that word, does the cancelling. Workers hold the token (or [`child`](CancellationToken::child)ren of it, to
+//! also stop for reasons of their own) and wind down as they would for any cancellation.
+//!
+//! - [`on_signals`]: install the handlers (once per process) and get the token
+//! - a *second* signal exits at once, with status 130: a shutdown that hangs can still be interrupted
+//! - [`signals_received`]: how many have arrived
+//!
+//! Unix only (Linux, macOS); elsewhere [`on_signals`] is `Unsupported`, and Ctrl-C keeps its default.
+//!
+//! ## Example
+//! ```no_run
+//! use std::{thread, time::Duration};
+//!
+//! use sync::shutdown;
+//!
+//! let stop = shutdown::on_signals().expect("signal handlers").child();
+//! let worker = thread::spawn(move || {
+//!        while !stop.wait_cancelled_timeout(Duration::from_millis(100)) {
+//!               // a tick of work
+//!        }
+//! });
+//! worker.join().unwrap(); // until Ctrl-C
+//! ```
+
+use std::io;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::thread;
+
+use crate::{CancellationToken, OnceLock,
+            atomic::{AtomicU32, Ordering::Relaxed}};
+
+/// Signals received so far; the watcher thread's futex word.
+static SIGNALS: AtomicU32 = AtomicU32::new(0);
+/// The token, or the OS error that stopped the handlers being installed.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+static COORDINATOR: OnceLock<Result<CancellationToken, i32>> = OnceLock::new();
+
+/// The process's shutdown token: cancelled on the first `SIGINT` or `SIGTERM`. The first call installs the
+/// handlers and starts the watcher thread; later calls return the same token.
+///
+/// ## Errors
+/// - `Unsupported` off Linux and macOS
+/// - the OS error if a handler couldn't be installed (every later call reports it too)
+pub fn on_signals() -> io::Result<CancellationToken> {
+       #[cfg(any(target_os = "linux", target_os = "macos"))]
+       return COORDINATOR.get_or_init(install).clone().map_err(io::Error::from_raw_os_error);
+       #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+       Err(io::ErrorKind::Unsupported.into())
+}
+
+/// `SIGINT`s and `SIGTERM`s received since [`on_signals`] installed the handlers.
+pub fn signals_received() -> u32 { SIGNALS.load(Relaxed) }
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn install() -> Result<CancellationToken, i32> {
+       use crate::{atomic::Ordering::Acquire, futex::wait};
+
+       let token = CancellationToken::new();
+       {
+              let token = token.clone();
+              thread::Builder::new()
+                     .name("shutdown".into())
+                     .spawn(move || {
+                            while SIGNALS.load(Acquire) == 0 {
+                                   wait(&SIGNALS, 0);
+                            }
+                            token.cancel();
+                     })
+                     .map_err(|error| error.raw_os_error().unwrap_or(libc::EAGAIN))?;
+       }
+       for signal in [libc::SIGINT, libc::SIGTERM] {
+              // SAFETY: a zeroed `sigaction` is a valid empty one; `handle` is async-signal-safe (an atomic RMW, a
+              // futex wake and, at worst, `_exit`).
+              unsafe {
+                     let mut action: libc::sigaction = std::mem::zeroed();
+                     action.sa_sigaction = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                     action.sa_flags = libc::SA_RESTART; // don't fail blocking reads (the stdin loop) with EINTR
+                     libc::sigemptyset(&mut action.sa_mask);
+                     if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                            return Err(io::Error::last_os_error().raw_os_error().unwrap_or(libc::EINVAL));
+                     }
+              }
+       }
+       Ok(token)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+extern "C" fn handle(_: libc::c_int) {
+       use crate::{atomic::Ordering::Release, futex::wake_all};
+
+       if SIGNALS.fetch_add(1, Release) > 0 {
+              // SAFETY: `_exit` is async-signal-safe; skipping destructors is the point of a second Ctrl-C.
+              unsafe { libc::_exit(130) };
+       }
+       wake_all(&SIGNALS);
+}
+
+#[cfg(test)]
+mod tests {
+       use std::time::Duration;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       /// The only test that raises a signal: a second one, from anywhere in the test binary, would exit it.
+       #[test]
+       #[cfg(any(target_os = "linux", target_os = "macos"))]
+       #[cfg_attr(miri, ignore = "miri can't install signal handlers")]
+       fn test_sigterm_cancels_the_token() {
+              let token = on_signals().unwrap();
+              let child = token.child();
+              assert!(!token.is_cancelled());
+              // SAFETY: plain FFI call; our handler is installed, so this doesn't terminate the process.
+              assert_eq!(unsafe { libc::raise(libc::SIGTERM) }, 0);
+              assert!(child.wait_cancelled_timeout(Duration::from_secs(10)), "the watcher cancelled the token");
+              assert_eq!(signals_received(), 1);
+              assert!(on_signals().unwrap().is_cancelled(), "one token per process");
+       }
+}
@@ -0,0 +1,118 @@
+//! Basic (unfair) spin lock.
+//!
+//! ## [Chapter 4: Building Our Own Spin Lock](https://marabos.nl/atomics/building-spinlock.html)
+//!
+//! A single `AtomicBool`: `compare_exchange(false, true, Acquire, ..)` to take, `store(false, Release)` to give back.
+//! Whichever waiting thread happens to win the exchange after a release gets the lock,
+//! so under contention a thread can be starved indefinitely. (See [`TicketLock`](crate::TicketLock) for the fair variant.)
+
+use std::{cell::UnsafeCell,
+          ops::{Deref, DerefMut}};
+
+use crate::{Backoff,
+            atomic::{AtomicBool,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn}};
+
+/// Mutual exclusion by busy-waiting.
+///
+/// Only sensible for very short critical sections: waiters burn a core the whole time.
+pub struct SpinLock<T> {
+       locked: AtomicBool,
+       value:  UnsafeCell<T>,
+}
+// SAFETY: the lock hands out at most one `&mut T` at a time, so sharing the lock only requires `T` be sendable.
+unsafe impl<T> Sync for SpinLock<T> where T: Send {}
+
+impl<T> SpinLock<T> {
+       loom_const_fn! {
+              pub fn new(value: T) -> Self { Self { locked: AtomicBool::new(false), value: UnsafeCell::new(value) } }
+       }
+
+       /// Spin until the lock is acquired.
+       pub fn lock(&self) -> SpinLockGuard<'_, T> {
+              let mut backoff = Backoff::new();
+              while self.try_take().is_err() {
+                     // wait on plain loads so we aren't bouncing the cache line with writes
+                     while self.locked.load(Relaxed) {
+                            backoff.snooze();
+                     }
+              }
+              SpinLockGuard { lock: self }
+       }
+
+       /// Acquire the lock only if it is currently free.
+       pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+              if self.try_take().is_err() { None } else { Some(SpinLockGuard { lock: self }) }
+       }
+
+       /// A compare-exchange rather than a `swap`: a failed attempt then writes nothing, so waiters don't dirty the
+       /// holder's cache line, and the release is the latest store they can read (which loom needs to see them progress).
+       fn try_take(&self) -> Result<bool, bool> { self.locked.compare_exchange(false, true, Acquire, Relaxed) }
+
+       /// Exclusive access via `&mut self` needs no locking.
+       pub fn get_mut(&mut self) -> &mut T { self.value.get_mut() }
+
+       pub fn into_inner(self) -> T { self.value.into_inner() }
+}
+
+/// Access to the locked value; unlocks on drop.
+pub struct SpinLockGuard<'a, T> {
+       lock: &'a SpinLock<T>,
+}
+// SAFETY: the guard only hands out `&T` when shared, so `T: Sync` suffices.
+unsafe impl<T> Sync for SpinLockGuard<'_, T> where T: Sync {}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: the existence of this guard guarantees we've exclusively locked the lock.
+              unsafe { &*self.lock.value.get() }
+       }
+}
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T {
+              // SAFETY: the existence of this guard guarantees we've exclusively locked the lock.
+              unsafe { &mut *self.lock.value.get() }
+       }
+}
+impl<T> Drop for SpinLockGuard<'_, T> {
+       fn drop(&mut self) { self.lock.locked.store(false, Release); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_contended_increments() {
+              const NUM_THREADS: usize = 8;
+              const ADDS_PER_THREAD: usize = 1_000;
+              let lock = SpinLock::new(0);
+              thread::scope(|s| {
+                     for _ in 0..NUM_THREADS {
+                            s.spawn(|| {
+                                   for _ in 0..ADDS_PER_THREAD {
+                                          *lock.lock() += 1;
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(lock.into_inner(), NUM_THREADS * ADDS_PER_THREAD);
+       }
+
+       #[test]
+       fn test_try_lock_while_held() {
+              let lock = SpinLock::new(vec![1]);
+              let mut guard = lock.lock();
+              assert!(lock.try_lock().is_none());
+              guard.push(2);
+              drop(guard);
+              assert_eq!(*lock.try_lock().unwrap(), vec![1, 2]);
+       }
+}
@@ -0,0 +1,180 @@
+//! Running count / sum / min / max of `u64` samples, recorded from many threads without a lock.
+//!
+//! Each statistic is its own atomic, updated by one RMW: `fetch_add` for the count, `fetch_min` / `fetch_max` for
+//! the extremes, and a CAS loop for the sum, which saturates at `u64::MAX` rather than wrap to a small number.
+//! Recording never waits.
+//!
+//! Reading four separate atomics could catch a sample half-recorded (counted, but not yet in the sum, say), so
+//! [`snapshot`](StatsCell::snapshot) works like a seqlock: recorders count themselves in flight before touching
+//! the statistics, and out again (bumping a generation) after; a snapshot only counts if nobody was in flight and the
+//! generation didn't change across its reads. It retries otherwise, so it reflects whole samples only:
+//! `min <= mean <= max`, always. A snapshot waits only on records in flight, each a handful of RMWs.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::StatsCell;
+//!
+//! let latencies = StatsCell::new();
+//! thread::scope(|s| {
+//!        for t in 0..4 {
+//!               let latencies = &latencies;
+//!               s.spawn(move || (1..=100).for_each(|micros| latencies.record(micros * (t + 1))));
+//!        }
+//! });
+//! let stats = latencies.snapshot();
+//! assert_eq!((stats.count, stats.min, stats.max), (400, Some(1), Some(400)));
+//! assert_eq!(stats.mean(), Some(126.25));
+//! ```
+
+use std::fmt;
+
+use crate::{Backoff,
+            atomic::{AtomicU64,
+                     Ordering::{Acquire, Relaxed, Release},
+                     fence, loom_const_fn}};
+
+/// Records in flight, in the low half of `records`.
+const IN_FLIGHT: u64 = (1 << 32) - 1;
+/// Start of a record: one more in flight.
+const BEGIN: u64 = 1;
+/// End of a record, in one add: one fewer in flight, and the next generation in the high half (wrapping off the top).
+const END: u64 = (1 << 32) - 1;
+
+/// Concurrent accumulator of count, sum, min and max; see the [module docs](self).
+pub struct StatsCell {
+       /// `generation << 32 | in flight`.
+       records: AtomicU64,
+       count:   AtomicU64,
+       sum:     AtomicU64,
+       min:     AtomicU64,
+       max:     AtomicU64,
+}
+
+/// The statistics at one moment, as taken by [`StatsCell::snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatsSnapshot {
+       pub count: u64,
+       /// Saturates at `u64::MAX`.
+       pub sum:   u64,
+       /// `None` until a sample is recorded.
+       pub min:   Option<u64>,
+       pub max:   Option<u64>,
+}
+
+impl StatsSnapshot {
+       /// `None` with no samples; skewed low once the sum has saturated.
+       pub fn mean(&self) -> Option<f64> { (self.count > 0).then(|| self.sum as f64 / self.count as f64) }
+}
+
+impl StatsCell {
+       loom_const_fn! {
+              pub fn new() -> Self {
+                     Self {
+                            records: AtomicU64::new(0),
+                            count:   AtomicU64::new(0),
+                            sum:     AtomicU64::new(0),
+                            min:     AtomicU64::new(u64::MAX),
+                            max:     AtomicU64::new(0),
+                     }
+              }
+       }
+
+       /// Add a sample.
+       pub fn record(&self, value: u64) {
+              self.records.fetch_add(BEGIN, Relaxed);
+              fence(Release); // a snapshot that sees any of the statistics below sees this record in flight
+              self.count.fetch_add(1, Relaxed);
+              let mut sum = self.sum.load(Relaxed);
+              while let Err(observed) = self.sum.compare_exchange_weak(sum, sum.saturating_add(value), Relaxed, Relaxed) {
+                     sum = observed;
+              }
+              self.min.fetch_min(value, Relaxed);
+              self.max.fetch_max(value, Relaxed);
+              self.records.fetch_add(END, Release);
+       }
+
+       /// The statistics over whole samples: waits out records in flight (briefly; each is a few RMWs).
+       pub fn snapshot(&self) -> StatsSnapshot {
+              let mut backoff = Backoff::new();
+              loop {
+                     let before = self.records.load(Acquire); // every finished record's statistics are visible
+                     let snapshot = self.read();
+                     fence(Acquire); // pairs with `record`'s: saw any of a record's statistics => sees it in flight
+                     if before & IN_FLIGHT == 0 && self.records.load(Relaxed) == before {
+                            return snapshot;
+                     }
+                     backoff.snooze();
+              }
+       }
+
+       /// Samples recorded so far. Only a snapshot, of the count alone.
+       pub fn count(&self) -> u64 { self.count.load(Relaxed) }
+
+       /// Reset to no samples; `&mut`, so no record can be in flight.
+       pub fn reset(&mut self) { *self = Self::new(); }
+
+       fn read(&self) -> StatsSnapshot {
+              let count = self.count.load(Relaxed);
+              let (min, max) = (self.min.load(Relaxed), self.max.load(Relaxed));
+              StatsSnapshot { count, sum: self.sum.load(Relaxed), min: (count > 0).then_some(min), max: (count > 0).then_some(max) }
+       }
+}
+
+impl Default for StatsCell {
+       fn default() -> Self { Self::new() }
+}
+
+impl fmt::Debug for StatsCell {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_tuple("StatsCell").field(&self.snapshot()).finish() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicBool, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_empty_and_single() {
+              let mut stats = StatsCell::new();
+              assert_eq!(stats.snapshot(), StatsSnapshot::default());
+              assert_eq!(stats.snapshot().mean(), None);
+              stats.record(7);
+              assert_eq!(stats.snapshot(), StatsSnapshot { count: 1, sum: 7, min: Some(7), max: Some(7) });
+              stats.reset();
+              assert_eq!(stats.count(), 0);
+       }
+
+       #[test]
+       fn test_sum_saturates() {
+              let stats = StatsCell::new();
+              stats.record(u64::MAX - 1);
+              stats.record(5);
+              let snapshot = stats.snapshot();
+              assert_eq!((snapshot.count, snapshot.sum, snapshot.min), (2, u64::MAX, Some(5)));
+       }
+
+       #[test]
+       fn test_snapshots_see_whole_samples() {
+              const PER_THREAD: u64 = if cfg!(miri) { 100 } else { 20_000 };
+              let stats = StatsCell::new();
+              let done = AtomicBool::new(false);
+              thread::scope(|s| {
+                     let recorders: Vec<_> = (0..3).map(|_| s.spawn(|| (0..PER_THREAD).for_each(|_| stats.record(10)))).collect();
+                     s.spawn(|| {
+                            while !done.load(Relaxed) {
+                                   // every sample is 10: any torn read would show a sum that isn't 10 × count
+                                   let snapshot = stats.snapshot();
+                                   assert_eq!(snapshot.sum, 10 * snapshot.count);
+                            }
+                     });
+                     recorders.into_iter().for_each(|recorder| recorder.join().unwrap());
+                     done.store(true, Relaxed);
+              });
+              assert_eq!(stats.snapshot().count, 3 * PER_THREAD);
+       }
+}
@@ -0,0 +1,11 @@
+//! Fixtures the unit tests share.
+
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// Counts its own drops.
+#[derive(Debug)]
+pub(crate) struct DropCounter<'a>(pub(crate) &'a AtomicUsize);
+
+impl Drop for DropCounter<'_> {
+       fn drop(&mut self) { self.0.fetch_add(1, Relaxed); }
+}
@@ -0,0 +1,325 @@
+//! Per-object thread-local storage: each thread gets its own `T`, and the owner can visit every thread's.
+//!
+//! `thread_local!` gives one value per thread per *static*; [`ThreadLocal`] gives one per thread per *instance*,
+//! and unlike a static it can be iterated from any thread: the shape of per-thread counters or histograms that
+//! threads record into without sharing a cache line, and a reader sums now and then.
+//! - [`get_or`](ThreadLocal::get_or): this thread's value, made on first use. Lock-free: a thread-local id lookup,
+//!   an atomic load, and (first use only) an allocation
+//! - [`iter`](ThreadLocal::iter): every thread's value, from any thread (so `T: Sync`); values made concurrently
+//!   may or may not be visited
+//! - [`iter_mut`](ThreadLocal::iter_mut) / [`into_iter`](IntoIterator::into_iter): exclusive, so exact
+//!
+//! ## Design
+//! - each thread holds a small id, taken on first use and returned to a pool when it exits; ids are reused
+//!   smallest-first, so they stay dense however many threads come and go
+//! - slots live in buckets of doubling size (1, 2, 4, …), allocated on demand and never moved: id `n` is in bucket
+//!   `log2(n + 1)`, so `&T`s stay valid as more threads arrive, and 64 bucket pointers cover every id
+//! - a value outlives its thread: it's dropped with the `ThreadLocal`, and a later thread given the same id takes
+//!   it over (a per-thread counter keeps counting, so sums still include exited threads)
+//!
+//! ## Example
+//! ```
+//! use std::{sync::atomic::{AtomicUsize, Ordering::Relaxed},
+//!           thread};
+//!
+//! use sync::ThreadLocal;
+//!
+//! let hits = ThreadLocal::new();
+//! thread::scope(|s| {
+//!        for _ in 0..4 {
+//!               s.spawn(|| {
+//!                      let mine = hits.get_or(|| AtomicUsize::new(0)); // uncontended: only this thread adds to it
+//!                      (0..1_000).for_each(|_| _ = mine.fetch_add(1, Relaxed));
+//!               });
+//!        }
+//!        // meanwhile, any thread can read a running total
+//!        assert!(hits.iter().map(|count| count.load(Relaxed)).sum::<usize>() <= 4_000);
+//! });
+//! assert_eq!(hits.into_iter().map(AtomicUsize::into_inner).sum::<usize>(), 4_000);
+//! ```
+
+use std::{cell::UnsafeCell, cmp::Reverse, collections::BinaryHeap, fmt, marker::PhantomData, mem::MaybeUninit, ptr};
+
+use crate::{Mutex,
+            atomic::{AtomicBool, AtomicPtr,
+                     Ordering::{AcqRel, Acquire, Relaxed, Release}}};
+
+/// One bucket per bit of an id: bucket `b` holds ids `2^b - 1 ..= 2^(b+1) - 2`.
+const BUCKETS: usize = usize::BITS as usize;
+
+/// One value per thread, per instance; see the [module docs](self).
+pub struct ThreadLocal<T: Send> {
+       buckets: [AtomicPtr<Slot<T>>; BUCKETS],
+       /// Owns `T`s (and so is `Send` only if they are).
+       _values: PhantomData<T>,
+}
+// SAFETY: a thread only writes the slot of its own id, and others only read a slot once it's `present`;
+//         reading another thread's value needs `T: Sync`, which `iter` demands.
+unsafe impl<T: Send> Sync for ThreadLocal<T> {}
+
+struct Slot<T> {
+       present: AtomicBool,
+       value:   UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T: Send> ThreadLocal<T> {
+       pub const fn new() -> Self { Self { buckets: [const { AtomicPtr::new(ptr::null_mut()) }; BUCKETS], _values: PhantomData } }
+
+       /// This thread's value, if it has made one.
+       ///
+       /// ## Panics
+       /// If called while this thread's thread-locals are being torn down.
+       pub fn get(&self) -> Option<&T> {
+              let slot = self.slot(thread_id(), false)?;
+              // Acquire: pairs with the Release that published the value (ours, or an exited thread's with our id)
+              // SAFETY: `present` means the value was fully written, and it's only dropped by `&mut self` methods.
+              slot.present.load(Acquire).then(|| unsafe { (*slot.value.get()).assume_init_ref() })
+       }
+
+       /// This thread's value, made with `init` if it has none yet.
+       ///
+       /// ## Panics
+       /// If `init` panics, or itself calls `get_or` on this `ThreadLocal`; or if called while this thread's
+       /// thread-locals are being torn down.
+       pub fn get_or(&self, init: impl FnOnce() -> T) -> &T {
+              if let Some(value) = self.get() {
+                     return value;
+              }
+              let value = init();
+              let slot = self.slot(thread_id(), true).expect("just allocated");
+              assert!(!slot.present.load(Relaxed), "ThreadLocal::get_or called from its own `init`");
+              // SAFETY: only the thread holding this id writes the slot, and it isn't `present`, so nobody reads it.
+              unsafe { (*slot.value.get()).write(value) };
+              slot.present.store(true, Release);
+              // SAFETY: just written and published.
+              unsafe { (*slot.value.get()).assume_init_ref() }
+       }
+
+       /// This thread's value, defaulted if it has none yet.
+       pub fn get_or_default(&self) -> &T
+       where
+              T: Default,
+       {
+              self.get_or(T::default)
+       }
+
+       /// Every thread's value (in id order, not by age). Values made during the iteration may be missed.
+       pub fn iter(&self) -> impl Iterator<Item = &T>
+       where
+              T: Sync,
+       {
+              self.bucket_slices().flatten().filter(|slot| slot.present.load(Acquire)).map(|slot| {
+                     // SAFETY: as in `get`: `present` (acquired) means fully written, and nothing drops it meanwhile.
+                     unsafe { (*slot.value.get()).assume_init_ref() }
+              })
+       }
+
+       /// Every thread's value, mutably: `&mut self` means no thread is using its own.
+       pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+              self.bucket_slices().flatten().filter(|slot| slot.present.load(Relaxed)).map(|slot| {
+                     // SAFETY: present, and `&mut self` rules out any other reference to it.
+                     unsafe { (*slot.value.get()).assume_init_mut() }
+              })
+       }
+
+       /// The slot for thread `id`, allocating its bucket if `allocate` (else `None` if there's no bucket yet).
+       fn slot(&self, id: usize, allocate: bool) -> Option<&Slot<T>> {
+              let bucket = (usize::BITS - 1 - (id + 1).leading_zeros()) as usize;
+              let index = id + 1 - (1 << bucket);
+              let mut slots = self.buckets[bucket].load(Acquire);
+              if slots.is_null() {
+                     if !allocate {
+                            return None;
+                     }
+                     slots = self.allocate(bucket);
+              }
+              // SAFETY: bucket `b` holds `2^b` slots, and `index < 2^b`; buckets live as long as `self`.
+              Some(unsafe { &*slots.add(index) })
+       }
+
+       /// Install bucket `bucket`, or return the one another thread installed first.
+       #[cold]
+       fn allocate(&self, bucket: usize) -> *mut Slot<T> {
+              let slots: Box<[Slot<T>]> = (0..1_usize << bucket)
+                     .map(|_| Slot { present: AtomicBool::new(false), value: UnsafeCell::new(MaybeUninit::uninit()) })
+                     .collect();
+              let ours = Box::into_raw(slots).cast::<Slot<T>>();
+              match self.buckets[bucket].compare_exchange(ptr::null_mut(), ours, AcqRel, Acquire) {
+                     Ok(_) => ours,
+                     Err(theirs) => {
+                            // SAFETY: `ours` came from `Box::into_raw` just above, with this length, and was never shared.
+                            drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ours, 1 << bucket)) });
+                            theirs
+                     }
+              }
+       }
+
+       /// Each allocated bucket's slots.
+       fn bucket_slices(&self) -> impl Iterator<Item = &[Slot<T>]> {
+              self.buckets.iter().enumerate().filter_map(|(bucket, slots)| {
+                     let slots = slots.load(Acquire);
+                     // SAFETY: a non-null bucket pointer is a live allocation of `2^bucket` slots.
+                     (!slots.is_null()).then(|| unsafe { &*ptr::slice_from_raw_parts(slots, 1 << bucket) })
+              })
+       }
+}
+
+impl<T: Send> Default for ThreadLocal<T> {
+       fn default() -> Self { Self::new() }
+}
+
+impl<T: Send> Drop for ThreadLocal<T> {
+       fn drop(&mut self) {
+              for (bucket, slots) in self.buckets.iter_mut().enumerate() {
+                     let slots = slots.load(Relaxed); // `&mut self`: nobody to synchronize with
+                     if !slots.is_null() {
+                            // SAFETY: allocated by `allocate` with this length, and `&mut self` means no `&T` into it remains.
+                            drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(slots, 1 << bucket)) });
+                     }
+              }
+       }
+}
+
+impl<T> Drop for Slot<T> {
+       fn drop(&mut self) {
+              if self.present.load(Relaxed) {
+                     // SAFETY: present means fully written, and the slot is going away.
+                     unsafe { self.value.get_mut().assume_init_drop() }
+              }
+       }
+}
+
+impl<T: Send> IntoIterator for ThreadLocal<T> {
+       type IntoIter = std::vec::IntoIter<T>;
+       type Item = T;
+
+       /// Every thread's value, by value.
+       fn into_iter(self) -> Self::IntoIter {
+              let values: Vec<T> = self
+                     .bucket_slices()
+                     .flatten()
+                     .filter(|slot| slot.present.swap(false, Relaxed)) // un-present: `Drop` won't drop it again
+                     // SAFETY: it was present, so fully written; marking it absent made this the only read.
+                     .map(|slot| unsafe { (*slot.value.get()).assume_init_read() })
+                     .collect();
+              values.into_iter()
+       }
+}
+
+impl<T: Send + fmt::Debug> fmt::Debug for ThreadLocal<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("ThreadLocal").field("local", &self.get()).finish_non_exhaustive()
+       }
+}
+
+/// Ids in use count up from zero; an exited thread's id goes back in the pool, and the smallest free one is reused.
+struct IdPool {
+       next: usize,
+       free: BinaryHeap<Reverse<usize>>,
+}
+
+static IDS: Mutex<IdPool> = Mutex::new(IdPool { next: 0, free: BinaryHeap::new() });
+
+/// A thread's id, returned to the pool when the thread exits.
+struct ThreadId(usize);
+impl ThreadId {
+       fn take() -> Self {
+              let mut ids = IDS.lock();
+              let id = ids.free.pop().map_or_else(
+                     || {
+                            ids.next += 1;
+                            ids.next - 1
+                     },
+                     |Reverse(id)| id,
+              );
+              Self(id)
+       }
+}
+impl Drop for ThreadId {
+       fn drop(&mut self) { IDS.lock().free.push(Reverse(self.0)); }
+}
+
+thread_local! {
+       static THREAD_ID: ThreadId = ThreadId::take();
+}
+
+fn thread_id() -> usize { THREAD_ID.with(|id| id.0) }
+
+#[cfg(test)]
+mod tests {
+       use std::{cell::Cell,
+                 sync::{Barrier,
+                        atomic::{AtomicUsize, Ordering::Relaxed}},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::test_util::DropCounter;
+
+       #[test]
+       fn test_each_thread_its_own_value() {
+              let local = ThreadLocal::new();
+              assert_eq!(local.get(), None);
+              assert_eq!(*local.get_or(|| 1), 1);
+              assert_eq!(*local.get_or(|| 2), 1, "made once");
+              let all_alive = Barrier::new(19); // no thread exits (freeing its id for another) before all have one
+              thread::scope(|s| {
+                     for t in 2..=20 {
+                            let (local, all_alive) = (&local, &all_alive);
+                            s.spawn(move || {
+                                   assert_eq!(*local.get_or(|| t), t);
+                                   all_alive.wait();
+                            });
+                     }
+              });
+              assert_eq!(local.get(), Some(&1));
+              let mut all: Vec<_> = local.iter().copied().collect();
+              all.sort_unstable();
+              assert_eq!(all, (1..=20).collect::<Vec<_>>());
+       }
+
+       #[test]
+       fn test_exited_threads_id_is_reused_with_its_value() {
+              let local = ThreadLocal::new();
+              thread::scope(|s| s.spawn(|| local.get_or(|| Cell::new(1)).set(2)).join().unwrap());
+              // the next thread may take the exited one's id (and its value), or another free one
+              let seen = thread::scope(|s| s.spawn(|| local.get_or(|| Cell::new(0)).get()).join().unwrap());
+              assert!(seen == 2 || seen == 0, "{seen}");
+              assert!(local.into_iter().map(Cell::into_inner).any(|value| value == 2), "an exited thread's value is kept");
+       }
+
+       #[test]
+       fn test_per_thread_counters_sum_up() {
+              let counters: ThreadLocal<Cell<usize>> = ThreadLocal::new();
+              thread::scope(|s| {
+                     for _ in 0..8 {
+                            s.spawn(|| {
+                                   let mine = counters.get_or_default();
+                                   (0..500).for_each(|_| mine.set(mine.get() + 1)); // no atomics: nobody else touches it
+                            });
+                     }
+              });
+              let mut counters = counters;
+              assert_eq!(counters.iter_mut().map(|count| count.get()).sum::<usize>(), 4_000);
+       }
+
+       #[test]
+       fn test_values_dropped_once() {
+              let drops = AtomicUsize::new(0);
+              let local = ThreadLocal::new();
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   local.get_or(|| DropCounter(&drops));
+                            });
+                     }
+              });
+              let made = local.iter().count();
+              assert_eq!(drops.load(Relaxed), 0, "values outlive their threads");
+              let taken: Vec<_> = local.into_iter().collect();
+              assert_eq!((taken.len(), drops.load(Relaxed)), (made, 0));
+              drop(taken);
+              assert_eq!(drops.load(Relaxed), made);
+       }
+}
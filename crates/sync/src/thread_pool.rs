@@ -0,0 +1,723 @@
+//! Fixed-size thread pool: a shared job queue and named worker threads.
+//!
+//! [`execute`](ThreadPool::execute) queues a boxed closure; whichever worker is free picks it up.
+//! [`join`](ThreadPool::join) waits for everything queued so far, leaving the pool ready for more.
+//!
+//! [`scope`](ThreadPool::scope) is the pooled take on `std::thread::scope`: tasks spawned in it may borrow from
+//! the caller's stack, `scope` returns once they've all finished, and it hands back their results in submission order.
+//!
+//! ## Shutdown
+//! - graceful ([`shutdown`](ThreadPool::shutdown), or dropping the pool): stop accepting work,
+//!   let the workers drain the queue, then join them
+//! - immediate ([`shutdown_now`](ThreadPool::shutdown_now)): discard queued jobs; jobs already running still finish
+//!   (there's no safe way to interrupt a thread); workers drop what's left of a batch they've taken
+//!
+//! ## Design
+//! - the queue is a [`WorkQueue`]: multi-consumer, and closing it is exactly the workers' exit signal
+//! - `pending` counts queued + running jobs; it's a futex word, so `join` sleeps until the last job wakes it
+//! - while more than `BATCH_ABOVE` jobs per worker are pending, a worker takes a batch of them per trip to the queue
+//!   (a fair share, at most `MAX_BATCH`), so a deep queue isn't drained one lock acquisition at a time
+//!
+//! ## Panics
+//! What a panicking job does to its worker is the pool's [`PanicPolicy`] ([`on_panic`](ThreadPoolBuilder::on_panic)):
+//! - [`Restart`](PanicPolicy::Restart) (the default): the panic unwinds and ends the worker thread, and a fresh one,
+//!   with the same name, takes its place
+//! - [`Abort`](PanicPolicy::Abort): abort the process, once the panic hook has reported the panic
+//! - [`Capture`](PanicPolicy::Capture): catch it and send it down a channel as a [`JobPanic`]; the worker carries on
+//!
+//! Either way the job is counted as finished, so `join` doesn't hang on it, and in
+//! [`panicked`](ThreadPool::panicked). Scoped tasks are different: their panics are caught, and re-raised by `scope`
+//! once every task is done.
+//!
+//! Don't call `scope` (or `join`) from inside one of the pool's own jobs: with every worker waiting, nobody is left
+//! to run what they wait for.
+//!
+//! ## Example
+//! ```
+//! use std::sync::{Arc,
+//!                 atomic::{AtomicUsize, Ordering::Relaxed}};
+//!
+//! use sync::ThreadPool;
+//!
+//! let pool = ThreadPool::new(4);
+//! let done = Arc::new(AtomicUsize::new(0));
+//! for _ in 0..100 {
+//!        let done = done.clone();
+//!        pool.execute(move || {
+//!               done.fetch_add(1, Relaxed);
+//!        });
+//! }
+//! pool.join();
+//! assert_eq!(done.load(Relaxed), 100);
+//! ```
+
+use std::{any::Any,
+          error::Error,
+          fmt, io,
+          marker::PhantomData,
+          mem,
+          num::NonZeroUsize,
+          panic::{self, AssertUnwindSafe},
+          process, ptr,
+          sync::Arc,
+          thread};
+
+use crate::{Mutex,
+            atomic::{AtomicBool, AtomicU32, Builder, JoinHandle,
+                     Ordering::{AcqRel, Acquire, Relaxed, Release},
+                     current},
+            channel::mpsc,
+            futex::{wait, wake_all},
+            panic_message,
+            work_queue::WorkQueue};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Pending jobs per worker past which workers take jobs in batches.
+const BATCH_ABOVE: u32 = 4;
+/// Most jobs a worker takes in one batch.
+const MAX_BATCH: u32 = 16;
+
+/// Worker threads sharing one job queue; see the [module docs](self).
+pub struct ThreadPool {
+       shared: Arc<Shared>,
+}
+
+struct Shared {
+       queue:      WorkQueue<Job>,
+       /// Jobs queued or running.
+       pending:    AtomicU32,
+       workers:    u32,
+       /// Set by `shutdown_now`: workers stop running the jobs they've taken, and count them here instead.
+       discarding: AtomicBool,
+       discarded:  AtomicU32,
+       panicked:   AtomicU32,
+       on_panic:   PanicPolicy,
+       name:       String,
+       stack_size: Option<usize>,
+       /// Every worker thread spawned, restarts included; `stop` joins them.
+       handles:    Mutex<Vec<JoinHandle<()>>>,
+}
+
+/// Configures a [`ThreadPool`]: worker count, thread names, stack size, panic policy.
+#[derive(Debug, Clone)]
+pub struct ThreadPoolBuilder {
+       size:       usize,
+       name:       String,
+       stack_size: Option<usize>,
+       on_panic:   PanicPolicy,
+}
+
+/// What a worker does when a job it runs panics; see the [module docs](self#panics).
+#[derive(Clone, Default)]
+pub enum PanicPolicy {
+       /// Let the panic end the worker thread, and spawn a replacement. If that fails, the pool carries on with one
+       /// worker fewer (logging the error).
+       #[default]
+       Restart,
+       /// Abort the process.
+       Abort,
+       /// Send the panic to the channel's receiver, if it's still there, and carry on with the next job.
+       Capture(mpsc::Sender<JobPanic>),
+}
+
+impl fmt::Debug for PanicPolicy {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              match self {
+                     Self::Restart => f.write_str("Restart"),
+                     Self::Abort => f.write_str("Abort"),
+                     Self::Capture(_) => f.write_str("Capture(..)"),
+              }
+       }
+}
+
+/// A job's panic, caught under [`PanicPolicy::Capture`].
+pub struct JobPanic {
+       /// The worker that ran the job.
+       pub worker:  String,
+       /// What the job panicked with; see [`message`](Self::message).
+       pub payload: Box<dyn Any + Send>,
+}
+
+impl JobPanic {
+       /// The panic's message, if it was a string (as `panic!`'s are).
+       pub fn message(&self) -> String { panic_message(self.payload.as_ref()) }
+}
+
+impl fmt::Display for JobPanic {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "job panicked on {}: {}", self.worker, self.message()) }
+}
+
+impl fmt::Debug for JobPanic {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("JobPanic").field("worker", &self.worker).field("message", &self.message()).finish()
+       }
+}
+
+impl Error for JobPanic {}
+
+impl ThreadPoolBuilder {
+       /// Number of worker threads. Defaults to the available parallelism.
+       ///
+       /// ## Panics
+       /// If `size` is zero.
+       pub fn size(mut self, size: usize) -> Self {
+              assert!(size > 0, "thread pool size must be non-zero");
+              self.size = size;
+              self
+       }
+
+       /// Workers are named `{name}-{index}`. Defaults to `pool`.
+       pub fn name(mut self, name: impl Into<String>) -> Self {
+              self.name = name.into();
+              self
+       }
+
+       pub fn stack_size(mut self, bytes: usize) -> Self {
+              self.stack_size = Some(bytes);
+              self
+       }
+
+       /// What a panicking job does to its worker. Defaults to [`PanicPolicy::Restart`].
+       pub fn on_panic(mut self, policy: PanicPolicy) -> Self {
+              self.on_panic = policy;
+              self
+       }
+
+       /// Spawn the workers.
+       ///
+       /// ## Errors
+       /// If the OS refuses to spawn a thread; workers spawned so far are shut down again.
+       pub fn build(self) -> io::Result<ThreadPool> {
+              let pool = ThreadPool {
+                     shared: Arc::new(Shared {
+                            queue:      WorkQueue::new(),
+                            pending:    AtomicU32::new(0),
+                            workers:    u32::try_from(self.size).expect("fewer than 2^32 workers"),
+                            discarding: AtomicBool::new(false),
+                            discarded:  AtomicU32::new(0),
+                            panicked:   AtomicU32::new(0),
+                            on_panic:   self.on_panic,
+                            name:       self.name,
+                            stack_size: self.stack_size,
+                            handles:    Mutex::new(Vec::with_capacity(self.size)),
+                     }),
+              };
+              for index in 0..self.size {
+                     let worker = Shared::spawn_worker(&pool.shared, index)?; // on error, dropping `pool` joins the rest
+                     pool.shared.handles.lock().push(worker);
+              }
+              Ok(pool)
+       }
+}
+
+impl Default for ThreadPoolBuilder {
+       fn default() -> Self {
+              Self {
+                     size:       thread::available_parallelism().map_or(1, NonZeroUsize::get),
+                     name:       "pool".into(),
+                     stack_size: None,
+                     on_panic:   PanicPolicy::default(),
+              }
+       }
+}
+
+impl ThreadPool {
+       /// Pool of `size` workers named `pool-0`, `pool-1`, ...
+       ///
+       /// ## Panics
+       /// If `size` is zero, or a worker thread can't be spawned.
+       pub fn new(size: usize) -> Self { Self::builder().size(size).build().expect("failed to spawn thread pool workers") }
+
+       pub fn builder() -> ThreadPoolBuilder { ThreadPoolBuilder::default() }
+
+       /// Number of worker threads.
+       pub fn size(&self) -> usize { self.shared.workers as usize }
+
+       /// Queue `job` for the next free worker.
+       pub fn execute(&self, job: impl FnOnce() + Send + 'static) { self.shared.submit(Box::new(job)); }
+
+       /// Run `f` with a [`PoolScope`] for spawning tasks that borrow from the current stack frame.
+       /// Returns once every spawned task has finished, with their results in the order they were spawned.
+       ///
+       /// ## Panics
+       /// If a task panicked: the first such panic (in submission order) is resumed, after all tasks are done.
+       /// A panic in `f` itself also waits for the tasks before unwinding.
+       ///
+       /// ## Example
+       /// ```
+       /// use sync::ThreadPool;
+       ///
+       /// let pool = ThreadPool::new(2);
+       /// let words = ["pooled", "scoped", "tasks"];
+       /// let lengths = pool.scope(|s| {
+       ///        for word in &words {
+       ///               s.spawn(|| word.len());
+       ///        }
+       /// });
+       /// assert_eq!(lengths, [6, 6, 5]);
+       /// ```
+       pub fn scope<'env, T: Send>(&self, f: impl for<'scope> FnOnce(&'scope PoolScope<'scope, 'env, T>)) -> Vec<T> {
+              let scope = PoolScope {
+                     shared:    self.shared.clone(),
+                     results:   Mutex::new(Vec::new()),
+                     remaining: AtomicU32::new(0),
+                     scope:     PhantomData,
+                     env:       PhantomData,
+              };
+              {
+                     let _wait = WaitForTasks(&scope.remaining); // also on unwind: tasks may borrow what `f` unwinds past
+                     f(&scope);
+              }
+              let results = mem::take(&mut *scope.results.lock());
+              results.into_iter()
+                     .map(|result| match result.expect("every task stored its result before finishing") {
+                            Ok(value) => value,
+                            Err(payload) => panic::resume_unwind(payload),
+                     })
+                     .collect()
+       }
+
+       /// Jobs queued or running. Only a snapshot.
+       pub fn pending(&self) -> u32 { self.shared.pending.load(Relaxed) }
+
+       /// Jobs that have panicked so far (scoped tasks aside: `scope` reports those itself).
+       pub fn panicked(&self) -> u32 { self.shared.panicked.load(Relaxed) }
+
+       /// Block until every job executed so far has finished. The pool stays usable.
+       pub fn join(&self) {
+              loop {
+                     // Acquire: pairs with each job's AcqRel decrement, so the jobs' effects are visible after `join`
+                     let pending = self.shared.pending.load(Acquire);
+                     if pending == 0 {
+                            return;
+                     }
+                     wait(&self.shared.pending, pending);
+              }
+       }
+
+       /// Finish every queued job, then stop the workers.
+       pub fn shutdown(mut self) { self.stop(); }
+
+       /// Discard queued jobs (those already running still finish), then stop the workers.
+       /// Returns how many jobs were discarded.
+       pub fn shutdown_now(mut self) -> usize {
+              self.shared.discarding.store(true, Relaxed);
+              let mut discarded = 0;
+              while let Ok(job) = self.shared.queue.try_pop() {
+                     drop(job);
+                     self.shared.finish_job();
+                     discarded += 1;
+              }
+              self.stop();
+              // after the join: every worker has counted what it dropped from its batch
+              discarded + self.shared.discarded.load(Relaxed) as usize
+       }
+
+       fn stop(&mut self) {
+              self.shared.queue.close();
+              // a worker restarting adds its replacement's handle before it exits, so the joins can't miss one
+              loop {
+                     let workers = mem::take(&mut *self.shared.handles.lock());
+                     if workers.is_empty() {
+                            return;
+                     }
+                     for worker in workers {
+                            let _ = worker.join(); // a panicked worker already reported through the panic hook
+                     }
+              }
+       }
+}
+
+impl Drop for ThreadPool {
+       fn drop(&mut self) { self.stop(); }
+}
+
+impl fmt::Debug for ThreadPool {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("ThreadPool").field("size", &self.size()).field("pending", &self.pending()).finish()
+       }
+}
+
+impl Shared {
+       fn submit(&self, job: Job) {
+              self.pending.fetch_add(1, Relaxed);
+              if self.queue.push(job).is_err() {
+                     unreachable!("the queue is only closed by shutdown, which consumes the pool");
+              }
+       }
+
+       fn spawn_worker(shared: &Arc<Self>, index: usize) -> io::Result<JoinHandle<()>> {
+              let mut builder = Builder::new().name(format!("{}-{index}", shared.name));
+              if let Some(bytes) = shared.stack_size {
+                     builder = builder.stack_size(bytes);
+              }
+              let shared = shared.clone();
+              builder.spawn(move || {
+                     let _restart = Restart { shared: &shared, index };
+                     shared.work();
+              })
+       }
+
+       /// Worker loop: run jobs until the queue is closed and drained.
+       fn work(&self) {
+              while let Ok(jobs) = self.queue.pop_batch(self.batch_size()) {
+                     let mut batch = Batch { shared: self, jobs: jobs.into_iter() };
+                     while !self.discarding.load(Relaxed)
+                            && let Some(job) = batch.jobs.next()
+                     {
+                            let _finished = FinishJob(self); // counted even if `job` unwinds
+                            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                                   self.panicked.fetch_add(1, Relaxed);
+                                   match &self.on_panic {
+                                          PanicPolicy::Restart => panic::resume_unwind(payload),
+                                          PanicPolicy::Abort => process::abort(),
+                                          PanicPolicy::Capture(panics) => {
+                                                 let worker = current().name().unwrap_or_default().to_string();
+                                                 let _ = panics.send(JobPanic { worker, payload }); // nobody listening: drop it
+                                          }
+                                   }
+                            }
+                     }
+              }
+       }
+
+       /// How many jobs a worker should take at once: one, unless the queue is deep enough to share out.
+       fn batch_size(&self) -> usize {
+              let per_worker = self.pending.load(Relaxed) / self.workers;
+              let batch = if per_worker > BATCH_ABOVE { (per_worker / 2).min(MAX_BATCH) } else { 1 };
+              batch as usize
+       }
+
+       fn finish_job(&self) {
+              if self.pending.fetch_sub(1, AcqRel) == 1 {
+                     wake_all(&self.pending);
+              }
+       }
+}
+
+/// A worker's claimed jobs. If one of them unwinds the worker, the rest go back on the queue;
+/// if the pool is shutting down now (or the queue is closed by then), they're counted finished, as discarded.
+struct Batch<'a> {
+       shared: &'a Shared,
+       jobs:   std::vec::IntoIter<Job>,
+}
+impl Drop for Batch<'_> {
+       fn drop(&mut self) {
+              let unrun: Vec<Job> = self.jobs.by_ref().collect();
+              let count = unrun.len();
+              let requeued = if self.shared.discarding.load(Relaxed) { 0 } else { self.shared.queue.push_batch(unrun) };
+              if requeued < count {
+                     self.shared.discarded.fetch_add(count as u32, Relaxed);
+                     (0..count).for_each(|_| self.shared.finish_job());
+              }
+       }
+}
+
+/// Spawns a replacement for a worker a job's panic is unwinding.
+struct Restart<'a> {
+       shared: &'a Arc<Shared>,
+       index:  usize,
+}
+impl Drop for Restart<'_> {
+       fn drop(&mut self) {
+              if !thread::panicking() {
+                     return;
+              }
+              match Shared::spawn_worker(self.shared, self.index) {
+                     Ok(worker) => self.shared.handles.lock().push(worker),
+                     Err(error) => tracing::error!(%error, worker = self.index, "couldn't restart a thread pool worker"),
+              }
+       }
+}
+
+/// Marks a job finished on drop, unwinding included.
+struct FinishJob<'a>(&'a Shared);
+impl Drop for FinishJob<'_> {
+       fn drop(&mut self) { self.0.finish_job(); }
+}
+
+/// Spawns tasks onto a [`ThreadPool`] that may borrow anything outliving `'env`; see [`ThreadPool::scope`].
+pub struct PoolScope<'scope, 'env: 'scope, T> {
+       shared:    Arc<Shared>,
+       /// One slot per spawned task, in submission order.
+       results:   Mutex<Vec<Option<thread::Result<T>>>>,
+       /// Tasks not yet finished; `scope` waits on it.
+       remaining: AtomicU32,
+       /// Invariant lifetimes, as in `std::thread::Scope`.
+       scope:     PhantomData<&'scope mut &'scope ()>,
+       env:       PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope, T: Send> PoolScope<'scope, '_, T> {
+       /// Queue `task` on the pool; its result lands at this call's position in `scope`'s output.
+       pub fn spawn(&'scope self, task: impl FnOnce() -> T + Send + 'scope) {
+              let index = {
+                     let mut results = self.results.lock();
+                     results.push(None);
+                     results.len() - 1
+              };
+              self.remaining.fetch_add(1, Relaxed);
+              let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+                     let result = panic::catch_unwind(AssertUnwindSafe(task));
+                     self.results.lock()[index] = Some(result);
+                     let remaining = ptr::from_ref(&self.remaining);
+                     // Release: publishes the result; once this hits zero, `scope` may return and free `self`
+                     if self.remaining.fetch_sub(1, Release) == 1 {
+                            wake_all(remaining); // only the address is used: fine even if `self` is gone by now
+                     }
+              });
+              // SAFETY: only the lifetime changes. `scope` doesn't return (or unwind) until `remaining` is zero,
+              //         i.e. until this job has run and touched `self` for the last time, so nothing it borrows expires first.
+              let job: Job = unsafe { mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Job>(job) };
+              self.shared.submit(job);
+       }
+}
+
+impl<T> fmt::Debug for PoolScope<'_, '_, T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("PoolScope").field("remaining", &self.remaining.load(Relaxed)).finish_non_exhaustive()
+       }
+}
+
+/// Blocks on drop until a scope's tasks have all finished.
+struct WaitForTasks<'a>(&'a AtomicU32);
+impl Drop for WaitForTasks<'_> {
+       fn drop(&mut self) {
+              loop {
+                     // Acquire: pairs with each task's Release, so their results are visible
+                     let remaining = self.0.load(Acquire);
+                     if remaining == 0 {
+                            return;
+                     }
+                     wait(self.0, remaining);
+              }
+       }
+}
+
+#[cfg(all(test, not(any(loom, feature = "shuttle"))))]
+mod tests {
+       use std::{sync::{Mutex,
+                        atomic::{AtomicUsize, Ordering::Relaxed}},
+                 time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::WaitGroup;
+
+       #[test]
+       fn test_runs_every_job_on_named_workers() {
+              let pool = ThreadPool::builder().size(3).name("worker").build().unwrap();
+              let names = Arc::new(Mutex::new(Vec::new()));
+              for _ in 0..30 {
+                     let names = names.clone();
+                     pool.execute(move || names.lock().unwrap().push(thread::current().name().unwrap().to_string()));
+              }
+              pool.join();
+              let names = names.lock().unwrap();
+              assert_eq!(names.len(), 30);
+              assert!(names.iter().all(|name| ["worker-0", "worker-1", "worker-2"].contains(&name.as_str())));
+       }
+
+       #[test]
+       fn test_join_is_reusable() {
+              let pool = ThreadPool::new(2);
+              let done = Arc::new(AtomicUsize::new(0));
+              for round in 1..=3 {
+                     for _ in 0..10 {
+                            let done = done.clone();
+                            pool.execute(move || {
+                                   done.fetch_add(1, Relaxed);
+                            });
+                     }
+                     pool.join();
+                     assert_eq!(done.load(Relaxed), round * 10);
+              }
+              assert_eq!(pool.pending(), 0);
+       }
+
+       #[test]
+       fn test_graceful_shutdown_drains_queue() {
+              let pool = ThreadPool::new(1);
+              let done = Arc::new(AtomicUsize::new(0));
+              for _ in 0..5 {
+                     let done = done.clone();
+                     pool.execute(move || {
+                            thread::sleep(Duration::from_millis(2));
+                            done.fetch_add(1, Relaxed);
+                     });
+              }
+              pool.shutdown();
+              assert_eq!(done.load(Relaxed), 5);
+       }
+
+       #[test]
+       fn test_shutdown_now_discards_queued() {
+              let pool = ThreadPool::new(1);
+              let (started, done) = (WaitGroup::new(), Arc::new(AtomicUsize::new(0)));
+              let blocker = started.clone();
+              pool.execute(move || {
+                     drop(blocker); // signal: the worker is busy with us
+                     thread::sleep(Duration::from_millis(20));
+              });
+              for _ in 0..5 {
+                     let done = done.clone();
+                     pool.execute(move || {
+                            done.fetch_add(1, Relaxed);
+                     });
+              }
+              started.wait();
+              assert_eq!(pool.shutdown_now(), 5);
+              assert_eq!(done.load(Relaxed), 0);
+       }
+
+       #[test]
+       fn test_scope_borrows_and_keeps_order() {
+              let pool = ThreadPool::new(3);
+              let inputs: Vec<u64> = (0..50).collect();
+              let mut total = 0;
+              let squares = pool.scope(|s| {
+                     for n in &inputs {
+                            s.spawn(move || {
+                                   thread::sleep(Duration::from_micros(50 - n)); // finish out of order
+                                   n * n
+                            });
+                     }
+                     total = inputs.len(); // `f` can use the stack too
+              });
+              assert_eq!(squares, inputs.iter().map(|n| n * n).collect::<Vec<_>>());
+              assert_eq!(total, 50);
+              pool.join(); // a worker may still be finishing the job wrapper around the last task
+              assert_eq!(pool.pending(), 0);
+       }
+
+       #[test]
+       fn test_scope_resumes_task_panic_after_all_finish() {
+              let pool = ThreadPool::new(2);
+              let finished = AtomicUsize::new(0);
+              let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                     pool.scope(|s| {
+                            s.spawn(|| panic!("task failed"));
+                            for _ in 0..4 {
+                                   s.spawn(|| {
+                                          thread::sleep(Duration::from_millis(2));
+                                          finished.fetch_add(1, Relaxed);
+                                   });
+                            }
+                     })
+              }));
+              assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"task failed"));
+              assert_eq!(finished.load(Relaxed), 4);
+              assert_eq!(pool.size(), 2, "scoped panics don't take down workers");
+       }
+
+       #[test]
+       fn test_panicking_job_still_counted() {
+              let pool = ThreadPool::new(2);
+              pool.execute(|| panic!("job failed"));
+              pool.join();
+              let done = Arc::new(AtomicUsize::new(0));
+              let counter = done.clone();
+              pool.execute(move || {
+                     counter.fetch_add(1, Relaxed);
+              });
+              pool.join();
+              assert_eq!(done.load(Relaxed), 1, "the surviving worker picks up later jobs");
+              assert_eq!(pool.panicked(), 1);
+       }
+
+       #[test]
+       fn test_restart_replaces_the_worker() {
+              let pool = ThreadPool::builder().size(1).name("solo").build().unwrap();
+              pool.execute(|| panic!("job failed"));
+              let (sender, receiver) = std::sync::mpsc::channel();
+              pool.execute(move || sender.send(thread::current().name().map(String::from)).unwrap());
+              assert_eq!(receiver.recv_timeout(Duration::from_secs(10)).unwrap().as_deref(), Some("solo-0"));
+              pool.shutdown(); // joins the replacement as well as the original
+       }
+
+       #[test]
+       fn test_capture_sends_the_panic_and_keeps_the_worker() {
+              let (panics, caught) = crate::channel::mpsc();
+              let pool = ThreadPool::builder().size(1).name("capture").on_panic(PanicPolicy::Capture(panics)).build().unwrap();
+              for n in 0..3 {
+                     pool.execute(move || assert!(n != 1, "job {n} failed"));
+              }
+              pool.join();
+              let panic = caught.try_recv().unwrap();
+              assert_eq!(panic.to_string(), "job panicked on capture-0: job 1 failed");
+              assert!(caught.try_recv().is_err(), "one panic");
+              assert_eq!(pool.panicked(), 1);
+              let worker = pool.shared.handles.lock().pop().unwrap();
+              assert!(!worker.is_finished(), "the worker carried on");
+              pool.shared.handles.lock().push(worker);
+       }
+
+       #[test]
+       fn test_panic_mid_batch_requeues_the_rest() {
+              let pool = ThreadPool::new(2);
+              let (started, gate) = (WaitGroup::new(), Arc::new(std::sync::Barrier::new(3)));
+              for _ in 0..2 {
+                     let (started, gate) = (started.clone(), gate.clone());
+                     pool.execute(move || {
+                            drop(started);
+                            gate.wait(); // both workers busy while the queue fills up
+                     });
+              }
+              started.wait();
+              let done = Arc::new(AtomicUsize::new(0));
+              pool.execute(|| panic!("job failed"));
+              for _ in 0..40 {
+                     let done = done.clone();
+                     pool.execute(move || {
+                            done.fetch_add(1, Relaxed);
+                     });
+              }
+              assert!(pool.shared.batch_size() > 1, "deep enough to batch");
+              gate.wait();
+              pool.join();
+              assert_eq!((done.load(Relaxed), pool.pending()), (40, 0));
+       }
+}
+
+#[cfg(all(test, feature = "shuttle"))]
+mod shuttle_tests {
+       use std::sync::Arc;
+
+       use super::*;
+       use crate::atomic::{AtomicUsize, shuttle_check};
+
+       /// Jobs from two threads at once, onto a pool of two: `join` waits for them all, a scope in the middle gets its
+       /// results in order, and shutting down joins the workers (which would hang the run, if one were stuck).
+       #[test]
+       fn shuttle_jobs_scopes_and_shutdown() {
+              shuttle_check(|| {
+                     let pool = Arc::new(ThreadPool::new(2));
+                     let done = Arc::new(AtomicUsize::new(0));
+                     let submitter = {
+                            let (pool, done) = (pool.clone(), done.clone());
+                            shuttle::thread::spawn(move || {
+                                   for _ in 0..3 {
+                                          let done = done.clone();
+                                          pool.execute(move || _ = done.fetch_add(1, Relaxed));
+                                   }
+                            })
+                     };
+                     for _ in 0..3 {
+                            let done = done.clone();
+                            pool.execute(move || _ = done.fetch_add(1, Relaxed));
+                     }
+                     let squares = pool.scope(|s| {
+                            for n in 0..3 {
+                                   s.spawn(move || n * n);
+                            }
+                     });
+                     assert_eq!(squares, [0, 1, 4]);
+                     submitter.join().unwrap();
+                     pool.join();
+                     assert_eq!(done.load(Relaxed), 6);
+                     Arc::into_inner(pool).expect("the submitter is done with it").shutdown();
+              });
+       }
+}
@@ -0,0 +1,295 @@
+//! Registry of the threads a program spawns: who they are, where they came from, and whether they're still going.
+//!
+//! Spawn through [`ThreadRegistry::builder`] instead of `thread::Builder` and each thread gets a record
+//! (name, `ThreadId`, the `file:line` that spawned it, state, age) plus `tracing` events at each step:
+//! - `DEBUG` `thread started` when it begins running
+//! - `DEBUG` `thread finished` with its run time, or `WARN` `thread panicked`
+//!
+//! [`snapshot`](ThreadRegistry::snapshot) copies the records out at any moment; its `Display` is a table,
+//! for dumping when a demo hangs or prints something unexpected.
+//!
+//! ## Design
+//! - the record is created before the spawn, so a thread that finishes instantly still has somewhere to report to
+//! - the thread updates its own record: a drop guard marks it finished, or panicked if unwinding
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::{ThreadRegistry, ThreadState};
+//!
+//! let registry = ThreadRegistry::new();
+//! thread::scope(|s| {
+//!        for i in 0..3 {
+//!               registry.builder(format!("worker-{i}")).spawn_scoped(s, move || i * 2).unwrap();
+//!        }
+//! });
+//! let snapshot = registry.snapshot();
+//! assert_eq!(snapshot.threads().len(), 3);
+//! assert!(snapshot.threads().iter().all(|thread| thread.state == ThreadState::Finished));
+//! assert_eq!(snapshot.live().count(), 0);
+//! println!("{snapshot}");
+//! ```
+
+use std::{collections::BTreeMap,
+          fmt, io,
+          panic::Location,
+          sync::Arc,
+          thread::{self, JoinHandle, Scope, ScopedJoinHandle, ThreadId},
+          time::{Duration, Instant}};
+
+use crate::Mutex;
+
+/// Records threads spawned through it; cheap to clone (clones share the records).
+#[derive(Clone, Default)]
+pub struct ThreadRegistry {
+       records: Arc<Mutex<Records>>,
+}
+
+/// Keyed by spawn sequence number: spawn order, and stable while other records come and go.
+#[derive(Default)]
+struct Records {
+       next:    u64,
+       threads: BTreeMap<u64, ThreadInfo>,
+}
+
+/// Where a registered thread is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+       /// Spawned, not yet running.
+       Starting,
+       Running,
+       Finished,
+       Panicked,
+}
+
+impl ThreadState {
+       /// Starting or running.
+       pub fn is_live(&self) -> bool { matches!(self, Self::Starting | Self::Running) }
+}
+
+/// One thread's record, as of a [`snapshot`](ThreadRegistry::snapshot).
+#[derive(Debug, Clone)]
+pub struct ThreadInfo {
+       pub name:       String,
+       /// Known once the thread has started.
+       pub id:         Option<ThreadId>,
+       pub spawned_at: &'static Location<'static>,
+       pub state:      ThreadState,
+       pub spawned:    Instant,
+       /// Run time, once finished (or panicked).
+       pub ran_for:    Option<Duration>,
+}
+
+impl ThreadRegistry {
+       pub fn new() -> Self { Self::default() }
+
+       /// A builder for a thread called `name`, registered here when spawned.
+       pub fn builder(&self, name: impl Into<String>) -> RegisteredBuilder<'_> {
+              let name = name.into();
+              RegisteredBuilder { registry: self, builder: thread::Builder::new().name(name.clone()), name }
+       }
+
+       /// A copy of every record, in spawn order.
+       pub fn snapshot(&self) -> Snapshot {
+              Snapshot { threads: self.records.lock().threads.values().cloned().collect(), taken: Instant::now() }
+       }
+
+       /// Drop the records of threads that are no longer running.
+       pub fn forget_finished(&self) { self.records.lock().threads.retain(|_, record| record.state.is_live()); }
+
+       fn register(&self, name: String, spawned_at: &'static Location<'static>) -> Lifecycle {
+              let info = ThreadInfo { name, id: None, spawned_at, state: ThreadState::Starting, spawned: Instant::now(), ran_for: None };
+              let mut records = self.records.lock();
+              let key = records.next;
+              records.next += 1;
+              records.threads.insert(key, info);
+              Lifecycle { registry: self.clone(), key, started: None }
+       }
+
+       /// Drop the record of a thread that never spawned.
+       fn unregister(&self, key: u64) { self.records.lock().threads.remove(&key); }
+
+       /// Update a thread's record (if it hasn't been forgotten).
+       fn update(&self, key: u64, f: impl FnOnce(&mut ThreadInfo)) {
+              if let Some(record) = self.records.lock().threads.get_mut(&key) {
+                     f(record);
+              }
+       }
+}
+
+impl fmt::Debug for ThreadRegistry {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("ThreadRegistry").field("threads", &self.records.lock().threads.len()).finish()
+       }
+}
+
+/// [`thread::Builder`] that registers the thread it spawns; from [`ThreadRegistry::builder`].
+#[derive(Debug)]
+pub struct RegisteredBuilder<'r> {
+       registry: &'r ThreadRegistry,
+       builder:  thread::Builder,
+       name:     String,
+}
+
+impl RegisteredBuilder<'_> {
+       pub fn stack_size(self, size: usize) -> Self { Self { builder: self.builder.stack_size(size), ..self } }
+
+       /// Spawn and register; the record's spawn site is the caller of this.
+       ///
+       /// ## Errors
+       /// If the OS fails to create the thread (which is then not registered).
+       #[track_caller]
+       pub fn spawn<F, T>(self, f: F) -> io::Result<JoinHandle<T>>
+       where
+              F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static,
+       {
+              let mut lifecycle = self.registry.register(self.name, Location::caller());
+              let key = lifecycle.key;
+              self.builder
+                     .spawn(move || {
+                            lifecycle.start();
+                            f()
+                     })
+                     .inspect_err(|_| self.registry.unregister(key))
+       }
+
+       /// As [`spawn`](Self::spawn), into a [`thread::scope`].
+       ///
+       /// ## Errors
+       /// If the OS fails to create the thread (which is then not registered).
+       #[track_caller]
+       pub fn spawn_scoped<'scope, F, T>(self, scope: &'scope Scope<'scope, '_>, f: F) -> io::Result<ScopedJoinHandle<'scope, T>>
+       where
+              F: FnOnce() -> T + Send + 'scope,
+              T: Send + 'scope,
+       {
+              let mut lifecycle = self.registry.register(self.name, Location::caller());
+              let key = lifecycle.key;
+              self.builder
+                     .spawn_scoped(scope, move || {
+                            lifecycle.start();
+                            f()
+                     })
+                     .inspect_err(|_| self.registry.unregister(key))
+       }
+}
+
+/// Moved into the spawned thread; reports start on [`start`](Self::start), and the outcome on drop.
+struct Lifecycle {
+       registry: ThreadRegistry,
+       key:      u64,
+       started:  Option<Instant>,
+}
+
+impl Lifecycle {
+       fn start(&mut self) {
+              self.started = Some(Instant::now());
+              self.registry.update(self.key, |record| {
+                     record.id = Some(thread::current().id());
+                     record.state = ThreadState::Running;
+                     tracing::debug!(thread = %record.name, spawned_at = %record.spawned_at, "thread started");
+              });
+       }
+}
+
+impl Drop for Lifecycle {
+       fn drop(&mut self) {
+              // not started: the closure was dropped unrun (the spawn failed), and the record is already gone
+              let Some(started) = self.started else { return };
+              let ran_for = started.elapsed();
+              self.registry.update(self.key, |record| {
+                     record.ran_for = Some(ran_for);
+                     if thread::panicking() {
+                            record.state = ThreadState::Panicked;
+                            tracing::warn!(thread = %record.name, spawned_at = %record.spawned_at, ?ran_for, "thread panicked");
+                     } else {
+                            record.state = ThreadState::Finished;
+                            tracing::debug!(thread = %record.name, ?ran_for, "thread finished");
+                     }
+              });
+       }
+}
+
+/// The registry's records at one moment; `Display` prints a table.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+       threads: Vec<ThreadInfo>,
+       taken:   Instant,
+}
+
+impl Snapshot {
+       /// Every record, in spawn order.
+       pub fn threads(&self) -> &[ThreadInfo] { &self.threads }
+
+       /// The records of threads still starting or running.
+       pub fn live(&self) -> impl Iterator<Item = &ThreadInfo> { self.threads.iter().filter(|thread| thread.state.is_live()) }
+}
+
+impl fmt::Display for Snapshot {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              write!(f, "{} threads, {} live", self.threads.len(), self.live().count())?;
+              for thread in &self.threads {
+                     let id = thread.id.map_or_else(|| "-".to_string(), |id| format!("{id:?}"));
+                     let age = self.taken.saturating_duration_since(thread.spawned);
+                     write!(
+                            f,
+                            "\n  {:<16} {:<14} {:<9} {:>10.1?}  {}",
+                            thread.name,
+                            id,
+                            format!("{:?}", thread.state),
+                            age,
+                            thread.spawned_at
+                     )?;
+              }
+              Ok(())
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::sync::mpsc;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_records_lifecycle() {
+              let registry = ThreadRegistry::new();
+              let (release, released) = mpsc::channel::<()>();
+              let (started, wait_started) = mpsc::channel();
+              let waiting = registry
+                     .builder("waiting")
+                     .spawn(move || {
+                            started.send(thread::current().id()).unwrap();
+                            released.recv().unwrap();
+                     })
+                     .unwrap();
+              let id = wait_started.recv().unwrap();
+
+              let snapshot = registry.snapshot();
+              let [record] = snapshot.threads() else { panic!("one record") };
+              assert_eq!((record.name.as_str(), record.id, record.state), ("waiting", Some(id), ThreadState::Running));
+              assert_eq!(record.spawned_at.file(), file!());
+              assert_eq!(snapshot.live().count(), 1);
+              assert!(snapshot.to_string().starts_with("1 threads, 1 live\n  waiting"));
+
+              release.send(()).unwrap();
+              waiting.join().unwrap();
+              let [record] = registry.snapshot().threads().to_vec().try_into().unwrap();
+              assert_eq!(record.state, ThreadState::Finished);
+              assert!(record.ran_for.is_some());
+              registry.forget_finished();
+              assert!(registry.snapshot().threads().is_empty());
+       }
+
+       #[test]
+       fn test_records_panics() {
+              let registry = ThreadRegistry::new();
+              let result = thread::scope(|s| registry.builder("doomed").spawn_scoped(s, || panic!("on purpose")).unwrap().join());
+              assert!(result.is_err());
+              assert_eq!(registry.snapshot().threads()[0].state, ThreadState::Panicked);
+       }
+}
@@ -0,0 +1,139 @@
+//! FIFO-fair ticket spin lock.
+//!
+//! Two counters: `next_ticket` (taken with `fetch_add` by each arriving thread)
+//! and `now_serving` (bumped by the holder on unlock).
+//! A thread owns the lock once `now_serving` reaches its ticket, so acquisition order is arrival order.
+//!
+//! ## Fairness vs Throughput
+//! The unfair [`SpinLock`](crate::SpinLock) lets whichever core already has the cache line re-take the lock immediately;
+//! that is fast but can starve other threads.
+//! The ticket lock instead hands the lock to the *next* waiter, which costs a cache-line transfer on every handoff
+//! and means one descheduled waiter stalls everyone queued behind it.
+//! (`cargo bench --package sync` compares the two under contention.)
+
+use std::{cell::UnsafeCell,
+          ops::{Deref, DerefMut}};
+
+use crate::{Backoff,
+            atomic::{AtomicUsize,
+                     Ordering::{Acquire, Relaxed, Release},
+                     loom_const_fn}};
+
+/// Mutual exclusion by busy-waiting, granted in arrival order.
+pub struct TicketLock<T> {
+       next_ticket: AtomicUsize,
+       now_serving: AtomicUsize,
+       value:       UnsafeCell<T>,
+}
+// SAFETY: the lock hands out at most one `&mut T` at a time, so sharing the lock only requires `T` be sendable.
+unsafe impl<T> Sync for TicketLock<T> where T: Send {}
+
+impl<T> TicketLock<T> {
+       loom_const_fn! {
+              pub fn new(value: T) -> Self {
+                     Self { next_ticket: AtomicUsize::new(0), now_serving: AtomicUsize::new(0), value: UnsafeCell::new(value) }
+              }
+       }
+
+       /// Take a ticket and spin until it is served.
+       pub fn lock(&self) -> TicketLockGuard<'_, T> {
+              // **NOTE**: counters wrap; only equality is ever compared, so wrapping is harmless
+              //           (short of `usize::MAX` simultaneous waiters)
+              let ticket = self.next_ticket.fetch_add(1, Relaxed);
+              let mut backoff = Backoff::new();
+              while self.now_serving.load(Acquire) != ticket {
+                     backoff.snooze();
+              }
+              TicketLockGuard { lock: self }
+       }
+
+       /// Acquire the lock only if nobody holds it or is queued for it.
+       pub fn try_lock(&self) -> Option<TicketLockGuard<'_, T>> {
+              let serving = self.now_serving.load(Acquire);
+              self.next_ticket
+                     .compare_exchange(serving, serving.wrapping_add(1), Relaxed, Relaxed)
+                     .ok()
+                     .map(|_| TicketLockGuard { lock: self })
+       }
+
+       /// Exclusive access via `&mut self` needs no locking.
+       pub fn get_mut(&mut self) -> &mut T { self.value.get_mut() }
+
+       pub fn into_inner(self) -> T { self.value.into_inner() }
+}
+
+/// Access to the locked value; serves the next ticket on drop.
+pub struct TicketLockGuard<'a, T> {
+       lock: &'a TicketLock<T>,
+}
+// SAFETY: the guard only hands out `&T` when shared, so `T: Sync` suffices.
+unsafe impl<T> Sync for TicketLockGuard<'_, T> where T: Sync {}
+
+impl<T> Deref for TicketLockGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: the existence of this guard guarantees our ticket is being served.
+              unsafe { &*self.lock.value.get() }
+       }
+}
+impl<T> DerefMut for TicketLockGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T {
+              // SAFETY: the existence of this guard guarantees our ticket is being served.
+              unsafe { &mut *self.lock.value.get() }
+       }
+}
+impl<T> Drop for TicketLockGuard<'_, T> {
+       fn drop(&mut self) { self.lock.now_serving.fetch_add(1, Release); }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::Mutex, thread, time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_contended_increments() {
+              const NUM_THREADS: usize = 8;
+              const ADDS_PER_THREAD: usize = 1_000;
+              let lock = TicketLock::new(0);
+              thread::scope(|s| {
+                     for _ in 0..NUM_THREADS {
+                            s.spawn(|| {
+                                   for _ in 0..ADDS_PER_THREAD {
+                                          *lock.lock() += 1;
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(lock.into_inner(), NUM_THREADS * ADDS_PER_THREAD);
+       }
+
+       /// Waiters queued one at a time must be served in the order they queued.
+       #[test]
+       fn test_fifo_order() {
+              const NUM_WAITERS: usize = 4;
+              let lock = TicketLock::new(());
+              let order = Mutex::new(Vec::new());
+              thread::scope(|s| {
+                     let guard = lock.lock();
+                     for i in 0..NUM_WAITERS {
+                            let (lock, order) = (&lock, &order);
+                            s.spawn(move || {
+                                   let _guard = lock.lock();
+                                   order.lock().unwrap().push(i);
+                            });
+                            // wait until waiter `i` has taken its ticket before spawning the next one
+                            while lock.next_ticket.load(Relaxed) != i + 2 {
+                                   thread::sleep(Duration::from_millis(1));
+                            }
+                     }
+                     assert!(lock.try_lock().is_none());
+                     drop(guard);
+              });
+              assert_eq!(order.into_inner().unwrap(), (0..NUM_WAITERS).collect::<Vec<_>>());
+       }
+}
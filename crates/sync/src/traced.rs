@@ -0,0 +1,204 @@
+//! Atomics that narrate themselves: every operation logged, with its ordering, through `tracing` (the `traced` feature).
+//!
+//! [`TracedAtomicUsize`] and [`TracedAtomicBool`] have the std types' methods, and do the same thing; each call also
+//! emits an `INFO` event (target `sync::traced`) naming the atomic, the operation, its ordering(s), the values it
+//! read and wrote, and the calling thread. Swapped in for the plain atomics of an example, they turn it into a
+//! step-by-step account of which thread did what to which atomic, in the order the operations took effect on it.
+//!
+//! The log is for following along, not for measuring: emitting an event is far slower than the operation it
+//! reports (and takes the subscriber's locks), which serializes threads a real run would let race. Interleavings the
+//! narration shows are ones that *can* happen; plenty that can won't show up while it's on.
+//!
+//! ## Example
+//! ```
+//! use std::{sync::atomic::Ordering::{Acquire, Relaxed, Release},
+//!           thread};
+//!
+//! use sync::traced::{TracedAtomicBool, TracedAtomicUsize};
+//!
+//! let data = TracedAtomicUsize::new("data", 0);
+//! let ready = TracedAtomicBool::new("ready", false);
+//! thread::scope(|s| {
+//!        s.spawn(|| {
+//!               data.store(42, Relaxed); // logs "data: store", with order=Relaxed value=42
+//!               ready.store(true, Release);
+//!        });
+//!        while !ready.load(Acquire) {
+//!               std::hint::spin_loop();
+//!        }
+//!        assert_eq!(data.load(Relaxed), 42);
+//! });
+//! ```
+
+use std::{fmt, thread};
+
+use crate::atomic::{AtomicBool, AtomicUsize, Ordering, loom_const_fn};
+
+/// Log one operation on the atomic `$name`: its ordering(s) and values as `tracing` fields.
+macro_rules! narrate {
+       ($name:expr, $op:literal $(, $($field:tt)*)?) => {
+              tracing::info!(atomic = $name, op = $op, thread = thread::current().name().unwrap_or("<unnamed>") $(, $($field)*)?, "{}: {}", $name, $op)
+       };
+}
+
+/// The operations both types share, with the same signatures as std's.
+macro_rules! traced_common {
+       ($value:ty) => {
+              /// The name the log gives this atomic.
+              pub fn name(&self) -> &'static str { self.name }
+
+              pub fn load(&self, order: Ordering) -> $value {
+                     let value = self.inner.load(order);
+                     narrate!(self.name, "load", ?order, value);
+                     value
+              }
+
+              pub fn store(&self, value: $value, order: Ordering) {
+                     self.inner.store(value, order);
+                     narrate!(self.name, "store", ?order, value);
+              }
+
+              pub fn swap(&self, value: $value, order: Ordering) -> $value {
+                     let previous = self.inner.swap(value, order);
+                     narrate!(self.name, "swap", ?order, value, previous);
+                     previous
+              }
+
+              pub fn compare_exchange(
+                     &self,
+                     current: $value,
+                     new: $value,
+                     success: Ordering,
+                     failure: Ordering,
+              ) -> Result<$value, $value> {
+                     let result = self.inner.compare_exchange(current, new, success, failure);
+                     narrate!(self.name, "compare_exchange", ?success, ?failure, current, new, ?result);
+                     result
+              }
+
+              /// Like [`compare_exchange`](Self::compare_exchange), but may fail spuriously; the log shows which
+              /// failures were (an `Err` holding the very value it expected).
+              pub fn compare_exchange_weak(
+                     &self,
+                     current: $value,
+                     new: $value,
+                     success: Ordering,
+                     failure: Ordering,
+              ) -> Result<$value, $value> {
+                     let result = self.inner.compare_exchange_weak(current, new, success, failure);
+                     narrate!(self.name, "compare_exchange_weak", ?success, ?failure, current, new, ?result);
+                     result
+              }
+
+              /// Unlike the other operations, not logged: nobody else can be looking.
+              pub fn into_inner(self) -> $value { self.inner.into_inner() }
+       };
+}
+
+/// An `AtomicUsize` logging every operation; see the [module docs](self).
+pub struct TracedAtomicUsize {
+       name:  &'static str,
+       inner: AtomicUsize,
+}
+
+impl TracedAtomicUsize {
+       loom_const_fn! {
+              pub fn new(name: &'static str, value: usize) -> Self { Self { name, inner: AtomicUsize::new(value) } }
+       }
+
+       traced_common!(usize);
+
+       pub fn fetch_add(&self, value: usize, order: Ordering) -> usize {
+              let previous = self.inner.fetch_add(value, order);
+              narrate!(self.name, "fetch_add", ?order, value, previous);
+              previous
+       }
+
+       pub fn fetch_sub(&self, value: usize, order: Ordering) -> usize {
+              let previous = self.inner.fetch_sub(value, order);
+              narrate!(self.name, "fetch_sub", ?order, value, previous);
+              previous
+       }
+
+       pub fn fetch_max(&self, value: usize, order: Ordering) -> usize {
+              let previous = self.inner.fetch_max(value, order);
+              narrate!(self.name, "fetch_max", ?order, value, previous);
+              previous
+       }
+}
+
+/// An `AtomicBool` logging every operation; see the [module docs](self).
+pub struct TracedAtomicBool {
+       name:  &'static str,
+       inner: AtomicBool,
+}
+
+impl TracedAtomicBool {
+       loom_const_fn! {
+              pub fn new(name: &'static str, value: bool) -> Self { Self { name, inner: AtomicBool::new(value) } }
+       }
+
+       traced_common!(bool);
+
+       pub fn fetch_and(&self, value: bool, order: Ordering) -> bool {
+              let previous = self.inner.fetch_and(value, order);
+              narrate!(self.name, "fetch_and", ?order, value, previous);
+              previous
+       }
+
+       pub fn fetch_or(&self, value: bool, order: Ordering) -> bool {
+              let previous = self.inner.fetch_or(value, order);
+              narrate!(self.name, "fetch_or", ?order, value, previous);
+              previous
+       }
+}
+
+/// Like the std atomics' `Debug`, a `Relaxed` load; not logged.
+impl fmt::Debug for TracedAtomicUsize {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { fmt::Debug::fmt(&self.inner, f) }
+}
+
+/// Like the std atomics' `Debug`, a `Relaxed` load; not logged.
+impl fmt::Debug for TracedAtomicBool {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { fmt::Debug::fmt(&self.inner, f) }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release, SeqCst},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_behaves_like_the_std_atomics() {
+              let counter = TracedAtomicUsize::new("counter", 5);
+              assert_eq!(counter.fetch_add(3, Relaxed), 5);
+              assert_eq!(counter.fetch_sub(1, Release), 8);
+              assert_eq!(counter.fetch_max(4, AcqRel), 7);
+              assert_eq!(counter.swap(10, SeqCst), 7);
+              assert_eq!(counter.compare_exchange(9, 11, SeqCst, Relaxed), Err(10));
+              assert_eq!(counter.compare_exchange(10, 11, SeqCst, Relaxed), Ok(10));
+              assert_eq!(counter.load(Acquire), 11);
+              assert_eq!(counter.into_inner(), 11);
+
+              let flag = TracedAtomicBool::new("flag", false);
+              assert!(!flag.fetch_or(true, AcqRel));
+              assert!(flag.fetch_and(false, AcqRel));
+              assert!(!flag.swap(true, Relaxed));
+              assert_eq!(format!("{flag:?} {}", flag.name()), "true flag");
+       }
+
+       #[test]
+       fn test_counts_across_threads() {
+              let counter = TracedAtomicUsize::new("counter", 0);
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| (0..100).for_each(|_| _ = counter.fetch_add(1, Relaxed)));
+                     }
+              });
+              assert_eq!(counter.into_inner(), 400);
+       }
+}
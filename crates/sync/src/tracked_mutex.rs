@@ -0,0 +1,254 @@
+//! Mutex wrapper that catches lock-order inversions *before* they deadlock.
+//!
+//! Every thread keeps a stack of the [`TrackedMutex`]es it holds. Taking lock `B` while holding `A`
+//! records the edge `A → B` in one process-wide graph. If `B` can already reach `A` in that graph,
+//! some thread has taken them the other way round at some point: the two code paths *can* deadlock,
+//! even if this run got lucky. That's reported on the spot, naming the cycle, instead of hanging later.
+//!
+//! Re-locking a mutex the thread already holds (a guaranteed self-deadlock) is reported the same way.
+//!
+//! Tracking only runs with `debug_assertions`; release builds get a plain [`Mutex`] plus a name. For an order fixed
+//! up front and checked at compile time instead, see [`Ordered`](crate::Ordered).
+//!
+//! ## Example
+//! ```should_panic
+//! use sync::TrackedMutex;
+//!
+//! let (accounts, audit_log) = (TrackedMutex::new("accounts", ()), TrackedMutex::new("audit_log", ()));
+//! {
+//!        let _a = accounts.lock();
+//!        let _b = audit_log.lock(); // records accounts → audit_log
+//! }
+//! let _b = audit_log.lock();
+//! let _a = accounts.lock(); // panics: lock order violation: accounts → audit_log → accounts
+//! ```
+
+use std::{cell::RefCell,
+          collections::{HashMap, HashSet},
+          ops::{Deref, DerefMut},
+          sync::{self, LazyLock, PoisonError}};
+
+use crate::{Mutex, MutexGuard,
+            atomic::{AtomicUsize, Ordering::Relaxed}};
+
+/// What to do when an acquisition would violate the established lock order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnViolation {
+       /// Panic with the offending cycle (before blocking on the lock).
+       #[default]
+       Panic,
+       /// Emit a `tracing` error with the cycle and carry on. (A re-lock still deadlocks right after.)
+       Log,
+}
+
+/// A [`Mutex`] that participates in lock-order validation.
+pub struct TrackedMutex<T> {
+       id:           usize,
+       name:         &'static str,
+       on_violation: OnViolation,
+       mutex:        Mutex<T>,
+}
+
+impl<T> TrackedMutex<T> {
+       /// Tracked mutex that panics on an order violation. `name` appears in the report.
+       pub fn new(name: &'static str, value: T) -> Self { Self::with_policy(name, value, OnViolation::Panic) }
+
+       pub fn with_policy(name: &'static str, value: T, on_violation: OnViolation) -> Self {
+              static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+              Self { id: NEXT_ID.fetch_add(1, Relaxed), name, on_violation, mutex: Mutex::new(value) }
+       }
+
+       pub fn name(&self) -> &'static str { self.name }
+
+       /// Validate the acquisition against the lock graph, then block until the lock is ours.
+       ///
+       /// ## Panics
+       /// On a lock-order violation, if the policy is [`OnViolation::Panic`].
+       pub fn lock(&self) -> TrackedMutexGuard<'_, T> {
+              if cfg!(debug_assertions) {
+                     self.check_and_record();
+              }
+              let guard = self.mutex.lock();
+              if cfg!(debug_assertions) {
+                     HELD.with_borrow_mut(|held| held.push(self.id));
+              }
+              TrackedMutexGuard { tracked: self, guard }
+       }
+
+       fn check_and_record(&self) {
+              let held = HELD.with_borrow(Clone::clone);
+              let cycle = {
+                     let mut graph = GRAPH.lock().unwrap_or_else(PoisonError::into_inner);
+                     graph.names.insert(self.id, self.name);
+                     if held.contains(&self.id) {
+                            Some(vec![self.id, self.id])
+                     } else {
+                            let cycle = held.iter().find_map(|&from| graph.path(self.id, from).map(|path| [&[from][..], &path].concat()));
+                            if cycle.is_none() {
+                                   for &from in &held {
+                                          graph.edges.entry(from).or_default().insert(self.id);
+                                   }
+                            }
+                            cycle
+                     }
+                     .map(|cycle| cycle.iter().map(|id| graph.names.get(id).copied().unwrap_or("?")).collect::<Vec<_>>().join(" → "))
+              };
+              let Some(cycle) = cycle else { return };
+              match self.on_violation {
+                     OnViolation::Panic => panic!("lock order violation: {cycle}"),
+                     OnViolation::Log => tracing::error!(lock = self.name, %cycle, "lock order violation"),
+              }
+       }
+}
+
+impl<T> Drop for TrackedMutex<T> {
+       fn drop(&mut self) {
+              if cfg!(debug_assertions) {
+                     GRAPH.lock().unwrap_or_else(PoisonError::into_inner).forget(self.id);
+              }
+       }
+}
+
+/// Exclusive access to a [`TrackedMutex`]'s value; unlocks (and leaves the held-lock stack) on drop.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct TrackedMutexGuard<'a, T> {
+       tracked: &'a TrackedMutex<T>,
+       guard:   MutexGuard<'a, T>,
+}
+// SAFETY: the guard only hands out `&T` when shared, so `T: Sync` suffices. (`Ordered` over a `TrackedMutex` hands out this guard too.)
+unsafe impl<T> Sync for TrackedMutexGuard<'_, T> where T: Sync {}
+impl<T> Deref for TrackedMutexGuard<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T { &self.guard }
+}
+impl<T> DerefMut for TrackedMutexGuard<'_, T> {
+       fn deref_mut(&mut self) -> &mut T { &mut self.guard }
+}
+impl<T> Drop for TrackedMutexGuard<'_, T> {
+       fn drop(&mut self) {
+              if cfg!(debug_assertions) {
+                     // guards needn't be dropped in reverse order
+                     HELD.with_borrow_mut(|held| {
+                            if let Some(position) = held.iter().rposition(|&id| id == self.tracked.id) {
+                                   held.remove(position);
+                            }
+                     });
+              }
+       }
+}
+
+thread_local! {
+       /// Ids of the tracked mutexes this thread holds, in acquisition order.
+       static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// "Acquired `to` while holding `from`" edges, across all threads.
+static GRAPH: LazyLock<sync::Mutex<LockGraph>> = LazyLock::new(Default::default);
+
+#[derive(Default)]
+struct LockGraph {
+       edges: HashMap<usize, HashSet<usize>>,
+       names: HashMap<usize, &'static str>,
+}
+impl LockGraph {
+       /// Path of ids from `from` to `to` (inclusive), if `to` is reachable.
+       fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+              let mut visited = HashSet::new();
+              let mut stack = vec![vec![from]];
+              while let Some(path) = stack.pop() {
+                     let last = *path.last().expect("paths are never empty");
+                     if last == to {
+                            return Some(path);
+                     }
+                     if !visited.insert(last) {
+                            continue;
+                     }
+                     for &next in self.edges.get(&last).into_iter().flatten() {
+                            stack.push([&path[..], &[next]].concat());
+                     }
+              }
+              None
+       }
+
+       fn forget(&mut self, id: usize) {
+              self.edges.remove(&id);
+              self.edges.values_mut().for_each(|targets| {
+                     targets.remove(&id);
+              });
+              self.names.remove(&id);
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{panic, thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_consistent_order_is_fine() {
+              let (a, b, c) = (TrackedMutex::new("a", 0), TrackedMutex::new("b", 0), TrackedMutex::new("c", 0));
+              thread::scope(|s| {
+                     for _ in 0..4 {
+                            s.spawn(|| {
+                                   for _ in 0..100 {
+                                          let (mut a, mut b) = (a.lock(), b.lock());
+                                          let mut c = c.lock();
+                                          *a += 1;
+                                          *b += 1;
+                                          *c += 1;
+                                   }
+                            });
+                     }
+              });
+              assert_eq!(*a.lock() + *b.lock() + *c.lock(), 3 * 400);
+       }
+
+       #[test]
+       fn test_inversion_across_threads_detected() {
+              let (a, b, c) = (TrackedMutex::new("a", ()), TrackedMutex::new("b", ()), TrackedMutex::new("c", ()));
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            let _a = a.lock();
+                            let _b = b.lock();
+                     })
+                     .join()
+                     .unwrap();
+                     s.spawn(|| {
+                            let _b = b.lock();
+                            let _c = c.lock();
+                     })
+                     .join()
+                     .unwrap();
+                     // a → b → c is established; c then a closes the cycle, though no thread ever blocked
+                     let error = s
+                            .spawn(|| {
+                                   let _c = c.lock();
+                                   let _a = a.lock();
+                            })
+                            .join()
+                            .unwrap_err();
+                     assert_eq!(error.downcast_ref::<String>().map(String::as_str), Some("lock order violation: c → a → b → c"));
+              });
+       }
+
+       #[test]
+       fn test_relock_detected() {
+              let mutex = TrackedMutex::new("reentrant", ());
+              let _guard = mutex.lock();
+              let error = panic::catch_unwind(panic::AssertUnwindSafe(|| drop(mutex.lock()))).unwrap_err();
+              assert_eq!(error.downcast_ref::<String>().map(String::as_str), Some("lock order violation: reentrant → reentrant"));
+       }
+
+       #[test]
+       fn test_log_policy_carries_on() {
+              let a = TrackedMutex::with_policy("a", (), OnViolation::Log);
+              let b = TrackedMutex::with_policy("b", (), OnViolation::Log);
+              drop((a.lock(), b.lock()));
+              let _b = b.lock();
+              let _a = a.lock(); // logged, not fatal
+       }
+}
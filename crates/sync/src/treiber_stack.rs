@@ -0,0 +1,213 @@
+//! Lock-free stack (Treiber's), with nodes reclaimed through [hazard pointers](crate::hazard)
+//! (or [epochs](crate::epoch), with the `epoch` feature).
+//!
+//! `push` and `pop` are single CAS loops on `head`. The catch in any lock-free linked structure is
+//! that a `pop` reads `head.next` while another thread may pop and free `head`; the reclamation scheme makes that
+//! read safe (and rules out ABA: a node still being read can't be freed, so its address can't come back as a new node).
+//!
+//! Because nodes outlive their unlinking, [`peek`](TreiberStack::peek) and [`pop`](TreiberStack::pop)
+//! can hand out references ([`StackRef`]) instead of only moving values out.
+//! A popped value may still be read by a thread that peeked it, so `pop` shares it too;
+//! clone it to keep it past the `StackRef`.
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::TreiberStack;
+//!
+//! let stack = TreiberStack::new();
+//! thread::scope(|s| {
+//!        for i in 0..4 {
+//!               let stack = &stack;
+//!               s.spawn(move || stack.push(i));
+//!        }
+//! });
+//! assert!(stack.peek().is_some_and(|top| *top < 4));
+//! let mut popped: Vec<i32> = std::iter::from_fn(|| stack.pop().map(|value| *value)).collect();
+//! popped.sort();
+//! assert_eq!(popped, [0, 1, 2, 3]);
+//! ```
+
+use std::{fmt,
+          marker::PhantomData,
+          ops::Deref,
+          ptr::{self, NonNull}};
+
+use crate::{atomic::{AtomicPtr,
+                     Ordering::{AcqRel, Relaxed, Release}},
+            reclaim::Shield};
+
+/// Lock-free LIFO; see the [module docs](self).
+pub struct TreiberStack<T> {
+       head: AtomicPtr<Node<T>>,
+}
+// SAFETY: values are pushed from one thread and read (or dropped) from others.
+unsafe impl<T: Send + Sync> Sync for TreiberStack<T> {}
+// SAFETY: as above.
+unsafe impl<T: Send> Send for TreiberStack<T> {}
+
+struct Node<T> {
+       value: T,
+       next:  *mut Node<T>,
+}
+// SAFETY: a retired node is freed (dropping its `T`) on whichever thread scans; `next` is just an address.
+unsafe impl<T: Send> Send for Node<T> {}
+
+impl<T: Send + Sync> TreiberStack<T> {
+       pub const fn new() -> Self { Self { head: AtomicPtr::new(ptr::null_mut()) } }
+
+       pub fn push(&self, value: T) {
+              let node = Box::into_raw(Box::new(Node { value, next: ptr::null_mut() }));
+              let mut head = self.head.load(Relaxed);
+              loop {
+                     // SAFETY: not published yet, so still exclusively ours.
+                     unsafe { (*node).next = head };
+                     match self.head.compare_exchange_weak(head, node, Release, Relaxed) {
+                            Ok(_) => return,
+                            Err(observed) => head = observed,
+                     }
+              }
+       }
+
+       /// Unlink the top value. It stays readable through the returned reference (concurrent peekers may share it).
+       pub fn pop(&self) -> Option<StackRef<'_, T>> {
+              let mut shield = Shield::new();
+              loop {
+                     let node = NonNull::new(shield.protect(&self.head))?;
+                     // SAFETY: protected, so not freed even if someone else pops it first.
+                     let next = unsafe { node.as_ref().next };
+                     if self.head.compare_exchange(node.as_ptr(), next, AcqRel, Relaxed).is_ok() {
+                            // SAFETY: from `Box::into_raw`; only the CAS winner retires it, and it's no longer reachable.
+                            unsafe { shield.retire(node.as_ptr()) };
+                            return Some(StackRef { shield, node, stack: PhantomData });
+                     }
+              }
+       }
+
+       /// The top value, left on the stack.
+       pub fn peek(&self) -> Option<StackRef<'_, T>> {
+              let mut shield = Shield::new();
+              let node = NonNull::new(shield.protect(&self.head))?;
+              Some(StackRef { shield, node, stack: PhantomData })
+       }
+
+       /// Only a snapshot.
+       pub fn is_empty(&self) -> bool { self.head.load(Relaxed).is_null() }
+}
+
+impl<T: Send + Sync> Default for TreiberStack<T> {
+       fn default() -> Self { Self::new() }
+}
+
+impl<T> Drop for TreiberStack<T> {
+       fn drop(&mut self) {
+              let mut current = *self.head.get_mut();
+              while !current.is_null() {
+                     // SAFETY: `&mut self`: the remaining nodes are reachable only from here, and outstanding
+                     //         `StackRef`s borrow the stack, so there are none.
+                     let node = unsafe { Box::from_raw(current) };
+                     current = node.next;
+              }
+       }
+}
+
+impl<T> fmt::Debug for TreiberStack<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_struct("TreiberStack").finish_non_exhaustive() }
+}
+
+/// Shared access to a stack value, kept alive by a hazard pointer (or epoch pin).
+pub struct StackRef<'a, T> {
+       shield: Shield,
+       node:   NonNull<Node<T>>,
+       stack:  PhantomData<&'a TreiberStack<T>>,
+}
+
+impl<T> Deref for StackRef<'_, T> {
+       type Target = T;
+
+       fn deref(&self) -> &T {
+              // SAFETY: `shield` protects the node for as long as we live, retired or not.
+              unsafe { &self.node.as_ref().value }
+       }
+}
+
+impl<T: fmt::Debug> fmt::Debug for StackRef<'_, T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_tuple("StackRef").field(&**self).field(&self.shield).finish() }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{collections::HashSet,
+                 sync::atomic::{AtomicUsize, Ordering::Relaxed},
+                 thread};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+       use crate::reclaim;
+
+       #[test]
+       fn test_lifo() {
+              let stack = TreiberStack::new();
+              assert!(stack.pop().is_none());
+              stack.push("a");
+              stack.push("b");
+              assert_eq!(*stack.peek().unwrap(), "b");
+              assert_eq!(*stack.pop().unwrap(), "b");
+              assert_eq!(*stack.pop().unwrap(), "a");
+              assert!(stack.is_empty());
+       }
+
+       #[test]
+       fn test_concurrent_push_pop_loses_nothing() {
+              const PER_THREAD: usize = if cfg!(miri) { 50 } else { 5_000 };
+              let stack = TreiberStack::new();
+              let popped: Vec<Vec<usize>> = thread::scope(|s| {
+                     let handles: Vec<_> = (0..4)
+                            .map(|t| {
+                                   let stack = &stack;
+                                   s.spawn(move || {
+                                          let mut popped = Vec::new();
+                                          for i in 0..PER_THREAD {
+                                                 stack.push(t * PER_THREAD + i);
+                                                 popped.extend(stack.pop().map(|value| *value));
+                                          }
+                                          popped
+                                   })
+                            })
+                            .collect();
+                     handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+              });
+              let all: HashSet<usize> = popped.into_iter().flatten().collect();
+              assert_eq!(all.len(), 4 * PER_THREAD);
+       }
+
+       #[test]
+       fn test_peeked_value_outlives_pop() {
+              static DROPS: AtomicUsize = AtomicUsize::new(0);
+              struct CountDrops(u32);
+              impl Drop for CountDrops {
+                     fn drop(&mut self) { DROPS.fetch_add(1, Relaxed); }
+              }
+
+              let stack = TreiberStack::new();
+              stack.push(CountDrops(7));
+              let peeked = stack.peek().unwrap();
+              thread::scope(|s| {
+                     s.spawn(|| {
+                            drop(stack.pop());
+                            reclaim::collect();
+                     });
+              });
+              assert_eq!(peeked.0, 7);
+              assert_eq!(DROPS.load(Relaxed), 0);
+              drop(peeked);
+              // the popping thread orphaned the node; our scan frees it, unless a concurrent test's scan got there first
+              while DROPS.load(Relaxed) == 0 {
+                     reclaim::collect();
+                     thread::yield_now();
+              }
+              assert_eq!(DROPS.load(Relaxed), 1);
+       }
+}
@@ -0,0 +1,168 @@
+//! Triple buffer: a writer publishes snapshots, a reader always gets the freshest complete one; neither ever waits.
+//!
+//! Three slots of `T`: the writer owns one (filling it), the reader owns one (reading it),
+//! and the third is the "back" slot in between. Publishing swaps the writer's slot with the back slot;
+//! reading, if something new was published, swaps the reader's slot with it.
+//! Both swaps are a single atomic `swap` on one byte: wait-free, and a slow reader just skips intermediate values.
+//! (Compare [`channel::watch`](crate::channel::watch): blocking, many readers, one shared value behind a lock.)
+//!
+//! ## Design
+//! - `back`: index of the back slot, plus a `FRESH` bit set by the writer and cleared by the reader
+//! - AcqRel swaps: Release hands our slot's contents over, Acquire takes in the other side's
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::TripleBuffer;
+//!
+//! let (mut writer, mut reader) = TripleBuffer::new(0_u64).split();
+//! thread::scope(|s| {
+//!        s.spawn(move || {
+//!               for frame in 1..=1_000 {
+//!                      writer.write(frame);
+//!               }
+//!        });
+//!        let mut last = 0;
+//!        while last < 1_000 {
+//!               let frame = *reader.read();
+//!               assert!(frame >= last, "never goes back in time");
+//!               last = frame;
+//!        }
+//! });
+//! ```
+
+use std::{cell::UnsafeCell, fmt, sync::Arc};
+
+use crate::atomic::{AtomicU8,
+                    Ordering::{AcqRel, Relaxed}};
+
+const INDEX: u8 = 0b011;
+const FRESH: u8 = 0b100;
+
+/// Three slots for a single writer and a single reader; [`split`](Self::split) into the two halves.
+pub struct TripleBuffer<T> {
+       slots: [UnsafeCell<T>; 3],
+       /// Back slot index, `| FRESH` when it holds a value the reader hasn't taken yet.
+       back:  AtomicU8,
+}
+// SAFETY: each slot is owned by exactly one of writer / back / reader at a time, handed over by AcqRel swaps of `back`.
+unsafe impl<T: Send> Sync for TripleBuffer<T> {}
+
+impl<T: Clone> TripleBuffer<T> {
+       /// Every slot starts as `initial`, which the reader sees until the first publish.
+       pub fn new(initial: T) -> Self {
+              Self {
+                     slots: [UnsafeCell::new(initial.clone()), UnsafeCell::new(initial.clone()), UnsafeCell::new(initial)],
+                     back:  AtomicU8::new(1),
+              }
+       }
+}
+
+impl<T> TripleBuffer<T> {
+       pub fn split(self) -> (TripleBufferWriter<T>, TripleBufferReader<T>) {
+              let buffer = Arc::new(self);
+              (TripleBufferWriter { buffer: buffer.clone(), slot: 0 }, TripleBufferReader { buffer, slot: 2 })
+       }
+
+       /// Only dereference a slot you own (as writer or reader).
+       fn slot(&self, index: u8) -> *mut T { self.slots[usize::from(index)].get() }
+}
+
+/// Publishing half of a [`TripleBuffer`].
+pub struct TripleBufferWriter<T> {
+       buffer: Arc<TripleBuffer<T>>,
+       slot:   u8,
+}
+
+/// Reading half of a [`TripleBuffer`].
+pub struct TripleBufferReader<T> {
+       buffer: Arc<TripleBuffer<T>>,
+       slot:   u8,
+}
+
+impl<T> TripleBufferWriter<T> {
+       /// Publish `value`.
+       pub fn write(&mut self, value: T) {
+              *self.input() = value;
+              self.publish();
+       }
+
+       /// The slot being filled, to update in place (it holds whatever was published two swaps ago, not the latest).
+       pub fn input(&mut self) -> &mut T {
+              // SAFETY: we own `self.slot`; `&mut self` keeps the borrow from outliving the next `publish`.
+              unsafe { &mut *self.buffer.slot(self.slot) }
+       }
+
+       /// Publish the input slot as it is, taking the back slot to fill next.
+       pub fn publish(&mut self) { self.slot = self.buffer.back.swap(self.slot | FRESH, AcqRel) & INDEX; }
+}
+
+impl<T> TripleBufferReader<T> {
+       /// The latest published value (or the one last read, if nothing new was published).
+       pub fn read(&mut self) -> &T {
+              if self.has_update() {
+                     self.slot = self.buffer.back.swap(self.slot, AcqRel) & INDEX;
+              }
+              // SAFETY: we own `self.slot`; `&mut self` keeps the borrow from outliving the next swap.
+              unsafe { &*self.buffer.slot(self.slot) }
+       }
+
+       /// Whether something was published since the last [`read`](Self::read). Only a snapshot.
+       pub fn has_update(&self) -> bool { self.buffer.back.load(Relaxed) & FRESH != 0 }
+}
+
+impl<T> fmt::Debug for TripleBufferWriter<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.debug_struct("TripleBufferWriter").finish_non_exhaustive() }
+}
+
+impl<T> fmt::Debug for TripleBufferReader<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("TripleBufferReader").field("has_update", &self.has_update()).finish_non_exhaustive()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_reader_gets_latest_only() {
+              let (mut writer, mut reader) = TripleBuffer::new(String::from("initial")).split();
+              assert_eq!(reader.read(), "initial");
+              assert!(!reader.has_update());
+              writer.write("first".into());
+              writer.write("second".into());
+              assert!(reader.has_update());
+              assert_eq!(reader.read(), "second");
+              assert_eq!(reader.read(), "second");
+
+              writer.input().push_str(" (edited)"); // the slot left from two publishes ago
+              writer.publish();
+              assert_eq!(reader.read(), "first (edited)");
+       }
+
+       #[test]
+       fn test_snapshots_are_whole_and_in_order() {
+              let (mut writer, mut reader) = TripleBuffer::new([0_u64; 8]).split();
+              let frames = if cfg!(miri) { 100 } else { 100_000 };
+              thread::scope(|s| {
+                     s.spawn(move || {
+                            for frame in 1..=frames {
+                                   writer.write([frame; 8]);
+                            }
+                     });
+                     let mut last = 0;
+                     while last < frames {
+                            let snapshot = *reader.read();
+                            assert!(snapshot.iter().all(|&part| part == snapshot[0]), "torn snapshot: {snapshot:?}");
+                            assert!(snapshot[0] >= last);
+                            last = snapshot[0];
+                     }
+              });
+       }
+}
@@ -0,0 +1,124 @@
+//! Go-style wait group: wait for a dynamic number of tasks to finish.
+//!
+//! Every clone of a [`WaitGroup`] is one outstanding task; dropping it marks the task done.
+//! [`wait`](WaitGroup::wait) gives up the caller's own handle, then blocks until no handles remain.
+//! Unlike joining, the waiter doesn't need the `JoinHandle`s (or even to know how many tasks there are),
+//! and unlike a [`Barrier`](crate::Barrier) the count isn't fixed up front.
+//!
+//! One futex word counts live handles; the drop that takes it to zero wakes the waiters.
+//!
+//! ## Example
+//! ```
+//! use std::{sync::atomic::{AtomicUsize, Ordering::Relaxed},
+//!           thread};
+//!
+//! use sync::WaitGroup;
+//!
+//! static DONE: AtomicUsize = AtomicUsize::new(0);
+//! let wait_group = WaitGroup::new();
+//! for _ in 0..4 {
+//!        let task = wait_group.clone();
+//!        thread::spawn(move || {
+//!               DONE.fetch_add(1, Relaxed);
+//!               drop(task);
+//!        });
+//! }
+//! wait_group.wait();
+//! assert_eq!(DONE.load(Relaxed), 4);
+//! ```
+
+use std::sync::Arc;
+
+use crate::{atomic::{AtomicU32,
+                     Ordering::{Acquire, Relaxed, Release}},
+            futex::{wait, wake_all}};
+
+/// Handle to a group of tasks; clone one per task and drop it when the task is done.
+pub struct WaitGroup {
+       handles: Arc<AtomicU32>,
+}
+
+impl WaitGroup {
+       /// A group with a single handle (this one).
+       pub fn new() -> Self { Self { handles: Arc::new(AtomicU32::new(1)) } }
+
+       /// Number of live handles, this one included. Only a snapshot.
+       pub fn count(&self) -> u32 { self.handles.load(Relaxed) }
+
+       /// Drop this handle, then block until every other handle is dropped too.
+       pub fn wait(self) {
+              let handles = self.handles.clone();
+              drop(self);
+              loop {
+                     // Acquire: pairs with each dropping task's Release, so their work is visible after `wait`
+                     let count = handles.load(Acquire);
+                     if count == 0 {
+                            return;
+                     }
+                     wait(&handles, count);
+              }
+       }
+}
+
+impl Default for WaitGroup {
+       fn default() -> Self { Self::new() }
+}
+
+impl Clone for WaitGroup {
+       fn clone(&self) -> Self {
+              self.handles.fetch_add(1, Relaxed);
+              Self { handles: self.handles.clone() }
+       }
+}
+
+impl Drop for WaitGroup {
+       fn drop(&mut self) {
+              if self.handles.fetch_sub(1, Release) == 1 {
+                     wake_all(&*self.handles);
+              }
+       }
+}
+
+impl std::fmt::Debug for WaitGroup {
+       fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+              f.debug_struct("WaitGroup").field("count", &self.count()).finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::{sync::atomic::AtomicUsize, thread, time::Duration};
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_waits_for_nested_tasks() {
+              let done = Arc::new(AtomicUsize::new(0));
+              let wait_group = WaitGroup::new();
+              for _ in 0..4 {
+                     let (task, done) = (wait_group.clone(), done.clone());
+                     thread::spawn(move || {
+                            // tasks may spawn more tasks: the count is dynamic
+                            let (subtask, subdone) = (task.clone(), done.clone());
+                            thread::spawn(move || {
+                                   thread::sleep(Duration::from_millis(5));
+                                   subdone.fetch_add(1, Relaxed);
+                                   drop(subtask);
+                            });
+                            done.fetch_add(1, Relaxed);
+                            drop(task);
+                     });
+              }
+              wait_group.wait();
+              assert_eq!(done.load(Relaxed), 8);
+       }
+
+       #[test]
+       fn test_lone_wait_returns_immediately() {
+              let wait_group = WaitGroup::new();
+              assert_eq!(wait_group.count(), 1);
+              wait_group.wait();
+       }
+}
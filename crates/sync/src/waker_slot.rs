@@ -0,0 +1,36 @@
+//! Slot for the task a future should wake: [`ParkSlot`](crate::park_slot::ParkSlot) with a `Waker` for the thread.
+//!
+//! Same protocol: the polling side [`register`](WakerSlot::register)s its waker *then* re-checks its condition before
+//! returning `Pending`; the notifying side publishes its change *then* [`wake`](WakerSlot::wake)s.
+//! The spin lock orders the two, so either the poll sees the change or the notifier sees the waker.
+//! (`futures`' `AtomicWaker` does the same with a state word instead of a lock, never blocking either side.)
+
+use std::task::Waker;
+
+use crate::{SpinLock, atomic::loom_const_fn};
+
+/// At most one registered waker: the last task to poll.
+pub(crate) struct WakerSlot {
+       waker: SpinLock<Option<Waker>>,
+}
+impl WakerSlot {
+       loom_const_fn! {
+              pub(crate) fn new() -> Self { Self { waker: SpinLock::new(None) } }
+       }
+
+       /// Make `waker` the one to be woken, replacing any other (a future may move between tasks).
+       pub(crate) fn register(&self, waker: &Waker) {
+              let mut slot = self.waker.lock();
+              if !slot.as_ref().is_some_and(|registered| registered.will_wake(waker)) {
+                     *slot = Some(waker.clone());
+              }
+       }
+
+       /// Wake the registered task, if any. Woken outside the lock: a waker may run arbitrary executor code.
+       pub(crate) fn wake(&self) {
+              let waker = self.waker.lock().take();
+              if let Some(waker) = waker {
+                     waker.wake();
+              }
+       }
+}
@@ -0,0 +1,227 @@
+//! Multi-producer, multi-consumer work queue whose consumers can take a whole batch per lock acquisition.
+//!
+//! With many consumers each taking one item at a time, the queue's lock is taken once per item, and under load the
+//! consumers spend their time queueing for it. [`pop_batch`](WorkQueue::pop_batch) takes up to `n` items with one
+//! acquisition, dividing that cost by the batch size; the catch is that a batch is claimed by one consumer while
+//! others may sit idle. Small batches win while the lock is contended; large ones only once there's plenty queued.
+//! `cargo bench --package sync --bench channels -- work_queue` shows where the crossover falls on a given machine.
+//!
+//! Built from this crate's [`Mutex`] and [`Condvar`]; unbounded, and closeable like
+//! [`BlockingChannel`](crate::channel::BlockingChannel).
+//!
+//! ## Example
+//! ```
+//! use std::thread;
+//!
+//! use sync::WorkQueue;
+//!
+//! let queue = WorkQueue::new();
+//! queue.push_batch(0..100);
+//! queue.close();
+//! let sums: Vec<i32> = thread::scope(|s| {
+//!        let consumers: Vec<_> = (0..4)
+//!               .map(|_| s.spawn(|| std::iter::from_fn(|| queue.pop_batch(8).ok()).flatten().sum()))
+//!               .collect();
+//!        consumers.into_iter().map(|consumer| consumer.join().unwrap()).collect()
+//! });
+//! assert_eq!(sums.iter().sum::<i32>(), (0..100).sum());
+//! ```
+
+use std::{collections::VecDeque, fmt};
+
+use crate::{Condvar, Mutex, MutexGuard,
+            atomic::loom_const_fn,
+            channel::{RecvError, SendError, TryRecvError}};
+
+/// Unbounded FIFO queue for any number of producers and consumers; see the [module docs](self).
+pub struct WorkQueue<T> {
+       state:     Mutex<State<T>>,
+       not_empty: Condvar,
+}
+
+struct State<T> {
+       queue:  VecDeque<T>,
+       closed: bool,
+}
+
+impl<T> WorkQueue<T> {
+       loom_const_fn! {
+              pub fn new() -> Self {
+                     Self { state: Mutex::new(State { queue: VecDeque::new(), closed: false }), not_empty: Condvar::new() }
+              }
+       }
+
+       /// Enqueue one item.
+       ///
+       /// ## Errors
+       /// If the queue is closed; the item is handed back.
+       pub fn push(&self, item: T) -> Result<(), SendError<T>> {
+              let mut state = self.state.lock();
+              if state.closed {
+                     return Err(SendError(item));
+              }
+              state.queue.push_back(item);
+              drop(state);
+              self.not_empty.notify_one();
+              Ok(())
+       }
+
+       /// Enqueue every item, with one lock acquisition; returns how many. Nothing is queued on a closed queue.
+       pub fn push_batch(&self, items: impl IntoIterator<Item = T>) -> usize {
+              let mut state = self.state.lock();
+              if state.closed {
+                     return 0;
+              }
+              let before = state.queue.len();
+              state.queue.extend(items);
+              let pushed = state.queue.len() - before;
+              drop(state);
+              match pushed {
+                     0 => {}
+                     1 => self.not_empty.notify_one(),
+                     _ => self.not_empty.notify_all(),
+              }
+              pushed
+       }
+
+       /// Dequeue one item, blocking while the queue is empty.
+       ///
+       /// ## Errors
+       /// If the queue is closed *and* drained.
+       pub fn pop(&self) -> Result<T, RecvError> {
+              let mut state = self.wait_for_items()?;
+              Ok(state.queue.pop_front().expect("waited for an item"))
+       }
+
+       /// Dequeue up to `max` items (at least one), blocking while the queue is empty. One lock acquisition,
+       /// however many are taken.
+       ///
+       /// ## Errors
+       /// If the queue is closed *and* drained.
+       ///
+       /// ## Panics
+       /// If `max` is zero.
+       pub fn pop_batch(&self, max: usize) -> Result<Vec<T>, RecvError> {
+              assert!(max > 0, "a batch takes at least one item");
+              let mut state = self.wait_for_items()?;
+              let take = max.min(state.queue.len());
+              let batch = state.queue.drain(..take).collect();
+              let more = !state.queue.is_empty();
+              drop(state);
+              if more {
+                     self.not_empty.notify_one(); // a push's wake-up may have gone to us; pass it on
+              }
+              Ok(batch)
+       }
+
+       /// Dequeue an item if one is available, without blocking.
+       pub fn try_pop(&self) -> Result<T, TryRecvError> {
+              let mut state = self.state.lock();
+              match state.queue.pop_front() {
+                     Some(item) => Ok(item),
+                     None if state.closed => Err(TryRecvError::Disconnected),
+                     None => Err(TryRecvError::Empty),
+              }
+       }
+
+       /// Lock the state once it holds an item; `Err` once it's closed and drained.
+       fn wait_for_items(&self) -> Result<MutexGuard<'_, State<T>>, RecvError> {
+              let state = self.state.lock_when(&self.not_empty, |state| !state.queue.is_empty() || state.closed);
+              if state.queue.is_empty() { Err(RecvError) } else { Ok(state) }
+       }
+
+       /// Refuse further pushes and wake every waiting consumer. Items already queued can still be popped.
+       pub fn close(&self) {
+              self.state.lock().closed = true;
+              self.not_empty.notify_all();
+       }
+
+       pub fn is_closed(&self) -> bool { self.state.lock().closed }
+
+       /// Number of queued items. Only a snapshot.
+       pub fn len(&self) -> usize { self.state.lock().queue.len() }
+
+       pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+impl<T> Default for WorkQueue<T> {
+       fn default() -> Self { Self::new() }
+}
+
+impl<T> fmt::Debug for WorkQueue<T> {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("WorkQueue").field("len", &self.len()).finish_non_exhaustive()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use std::thread;
+
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       #[test]
+       fn test_batches_take_what_is_there() {
+              let queue = WorkQueue::new();
+              assert_eq!(queue.push_batch(0..5), 5);
+              assert_eq!(queue.pop_batch(3), Ok(vec![0, 1, 2]));
+              assert_eq!(queue.pop_batch(10), Ok(vec![3, 4]), "fewer than asked: no waiting for more");
+              queue.push(5).unwrap();
+              queue.close();
+              assert_eq!(queue.push(6), Err(SendError(6)));
+              assert_eq!(queue.push_batch([7, 8]), 0);
+              assert_eq!(queue.pop(), Ok(5));
+              assert_eq!(queue.pop_batch(1), Err(RecvError));
+              assert_eq!(queue.try_pop(), Err(TryRecvError::Disconnected));
+       }
+
+       #[test]
+       fn test_every_item_taken_exactly_once() {
+              const PRODUCERS: usize = 4;
+              const PER_PRODUCER: usize = 2_500;
+              let queue = WorkQueue::new();
+              let mut taken: Vec<usize> = thread::scope(|s| {
+                     let consumers: Vec<_> = [1, 4, 16]
+                            .into_iter()
+                            .map(|batch| {
+                                   let queue = &queue;
+                                   s.spawn(move || std::iter::from_fn(|| queue.pop_batch(batch).ok()).flatten().collect::<Vec<_>>())
+                            })
+                            .collect();
+                     let producers: Vec<_> = (0..PRODUCERS)
+                            .map(|p| {
+                                   let queue = &queue;
+                                   s.spawn(move || {
+                                          for chunk in (p * PER_PRODUCER..(p + 1) * PER_PRODUCER).collect::<Vec<_>>().chunks(7) {
+                                                 if chunk.len() % 2 == 0 {
+                                                        queue.push_batch(chunk.iter().copied());
+                                                 } else {
+                                                        chunk.iter().for_each(|&item| queue.push(item).unwrap());
+                                                 }
+                                          }
+                                   })
+                            })
+                            .collect();
+                     crate::join_all(producers).unwrap();
+                     queue.close();
+                     consumers.into_iter().flat_map(|consumer| consumer.join().unwrap()).collect()
+              });
+              taken.sort_unstable();
+              assert_eq!(taken, (0..PRODUCERS * PER_PRODUCER).collect::<Vec<_>>());
+       }
+
+       #[test]
+       fn test_close_wakes_waiting_consumers() {
+              let queue = WorkQueue::<()>::new();
+              thread::scope(|s| {
+                     let waiters: Vec<_> = (0..3).map(|_| s.spawn(|| queue.pop_batch(4))).collect();
+                     thread::sleep(std::time::Duration::from_millis(10));
+                     queue.close();
+                     for waiter in waiters {
+                            assert_eq!(waiter.join().unwrap(), Err(RecvError));
+                     }
+              });
+       }
+}
@@ -0,0 +1,260 @@
+//! Every allocation the `Arc`, the channels and the lock-free structures make is freed again.
+//!
+//! `cargo test --package sync --test leaks`
+//!
+//! A counting global allocator prefixes each block with the *site* (scenario) that was running when it was allocated,
+//! and keeps live block and byte counts per site; a block freed later (on any thread) is subtracted from the site
+//! that allocated it. Each scenario runs twice: once as warm-up (untracked, so lazily created statics and thread-locals
+//! are in place), then tracked. Whatever the tracked run leaves live is a leak, reported per site.
+//!
+//! Drop-path bugs in lock-free code (a node never retired, a message left in a dropped channel) are otherwise
+//! invisible short of Miri. No libtest harness (`harness = false`): its own threads would allocate under our sites.
+//!
+//! Under the `shuttle` feature it only builds (shuttle's atomics run inside its scheduler alone), leaving out the
+//! hazard pointers, epochs and `TreiberStack` that lib.rs leaves out there.
+
+use std::{alloc::{GlobalAlloc, Layout, System},
+          process::ExitCode,
+          sync::atomic::{AtomicIsize, AtomicUsize, Ordering::Relaxed},
+          thread};
+
+#[cfg(not(feature = "shuttle"))]
+use sync::TreiberStack;
+use sync::{AtomicOptionBox, Mutex, RcuCell, TripleBuffer, channel, myarc::Arc};
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Site 0 is "untracked": the runtime, warm-ups, the report itself.
+static SITE: AtomicUsize = AtomicUsize::new(0);
+const MAX_SITES: usize = 32;
+static LIVE_BLOCKS: [AtomicIsize; MAX_SITES] = [const { AtomicIsize::new(0) }; MAX_SITES];
+static LIVE_BYTES: [AtomicIsize; MAX_SITES] = [const { AtomicIsize::new(0) }; MAX_SITES];
+
+/// `System`, with each block's site stored just in front of it.
+struct Counting;
+
+impl Counting {
+       /// Room for the site in front of a block, keeping the block's alignment.
+       fn header(layout: Layout) -> usize { layout.align().max(size_of::<usize>()) }
+
+       fn padded(layout: Layout) -> Layout {
+              Layout::from_size_align(layout.size() + Self::header(layout), layout.align()).expect("padded layout overflowed")
+       }
+}
+
+// SAFETY: every block comes from `System` with a layout at least as aligned as asked, and goes back to it with the
+//         same padded layout; the caller's part of the block starts `header` bytes in and is `layout.size()` long.
+unsafe impl GlobalAlloc for Counting {
+       unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+              // SAFETY: `padded` is non-zero-sized (the header alone is).
+              let block = unsafe { System.alloc(Self::padded(layout)) };
+              if block.is_null() {
+                     return block;
+              }
+              let site = SITE.load(Relaxed);
+              LIVE_BLOCKS[site].fetch_add(1, Relaxed);
+              LIVE_BYTES[site].fetch_add(layout.size() as isize, Relaxed);
+              // SAFETY: the header is at least one aligned `usize` wide, and the site goes in its last `usize`.
+              unsafe {
+                     let user = block.add(Self::header(layout));
+                     user.cast::<usize>().sub(1).write(site);
+                     user
+              }
+       }
+
+       unsafe fn dealloc(&self, user: *mut u8, layout: Layout) {
+              // SAFETY: `user` came from `alloc` with this `layout`, so the site sits in front of it and the block
+              //         starts `header` bytes earlier.
+              unsafe {
+                     let site = user.cast::<usize>().sub(1).read();
+                     LIVE_BLOCKS[site].fetch_sub(1, Relaxed);
+                     LIVE_BYTES[site].fetch_sub(layout.size() as isize, Relaxed);
+                     System.dealloc(user.sub(Self::header(layout)), Self::padded(layout));
+              }
+       }
+}
+
+/// Free whatever the reclamation schemes are still holding back, so it doesn't read as a leak.
+#[cfg(not(feature = "shuttle"))]
+fn reclaim_deferred() {
+       sync::hazard::reclaim();
+       for _ in 0..3 {
+              sync::epoch::collect(); // garbage is freed two epoch advances after it was retired
+       }
+}
+
+/// Claim as many hazard slots and epoch participants as the scenarios can hold at once (one per thread, main included),
+/// so the tracked runs reuse them rather than growing the registries: a registry entry is never freed, by design.
+#[cfg(not(feature = "shuttle"))]
+fn prime_registries() {
+       let barrier = std::sync::Barrier::new(THREADS + 1);
+       let hold = || {
+              let (_hazard, _pin) = (sync::hazard::HazardPointer::new(), sync::epoch::pin());
+              barrier.wait();
+       };
+       on_threads(|_| hold(), hold);
+}
+
+const THREADS: usize = 4;
+const PER_THREAD: usize = 200;
+
+/// Run `work(thread index)` on `THREADS` scoped threads, alongside `main` on this one; joins each thread explicitly,
+/// since only a join waits for a thread's thread-locals to be torn down (a scope's end doesn't).
+fn on_threads<R>(work: impl Fn(usize) + Sync, main: impl FnOnce() -> R) -> R {
+       thread::scope(|s| {
+              let work = &work;
+              let handles: Vec<_> = (0..THREADS).map(|index| s.spawn(move || work(index))).collect();
+              let result = main();
+              sync::join_all(handles).unwrap();
+              result
+       })
+}
+
+fn arc() {
+       let shared = Arc::new(vec![String::from("shared"); 8]);
+       on_threads(|_| (0..PER_THREAD).for_each(|_| drop(shared.clone())), || ());
+       let mut copy = shared.clone();
+       Arc::make_mut(&mut copy).push(String::from("cloned on write"));
+       assert!(Arc::try_unwrap(shared).is_ok());
+       let racers: Vec<_> = (0..THREADS).map(|_| Mutex::new(Some(copy.clone()))).collect();
+       drop(copy);
+       let winners = AtomicUsize::new(0);
+       on_threads(
+              |index| {
+                     let racer = racers[index].lock().take().unwrap();
+                     if Arc::into_inner(racer).is_some() {
+                            winners.fetch_add(1, Relaxed);
+                     }
+              },
+              || (),
+       );
+       assert_eq!(winners.into_inner(), 1);
+}
+
+fn mpsc() {
+       let (sender, receiver) = channel::mpsc();
+       let senders: Vec<_> = (0..THREADS).map(|_| Mutex::new(Some(sender.clone()))).collect();
+       on_threads(
+              |producer| {
+                     let sender = senders[producer].lock().take().unwrap();
+                     (0..PER_THREAD).for_each(|i| sender.send(format!("{producer}:{i}")).unwrap());
+              },
+              || receiver.iter().take(PER_THREAD).for_each(drop), // the rest are left for the channel's drop
+       );
+       drop((sender, receiver));
+}
+
+fn blocking() {
+       let channel = channel::BlockingChannel::bounded(16);
+       on_threads(
+              |producer| (0..PER_THREAD).for_each(|i| channel.send(format!("{producer}:{i}")).unwrap()),
+              || (0..THREADS * PER_THREAD - 10).for_each(|_| drop(channel.recv().unwrap())),
+       );
+}
+
+fn oneshots() {
+       let (sender, receiver) = channel::oneshot();
+       sender.send(String::from("never received"));
+       drop((sender, receiver));
+       let (sender, receiver) = channel::oneshot::<String>();
+       let sender = Mutex::new(Some(sender));
+       on_threads(|_| drop(sender.lock().take()), || assert!(receiver.recv().is_err()));
+       let (sender, receiver) = channel::typed_oneshot();
+       sender.send(String::from("never received"));
+       drop(receiver);
+}
+
+fn rendezvous() {
+       let (sender, receiver) = channel::rendezvous();
+       let sender = Mutex::new(Some(sender));
+       let received = on_threads(
+              |index| {
+                     if index == 0 {
+                            let sender = sender.lock().take().unwrap();
+                            (0..PER_THREAD).for_each(|i| sender.send(i.to_string()).unwrap());
+                     }
+              },
+              || receiver.iter().count(),
+       );
+       assert_eq!(received, PER_THREAD);
+}
+
+#[cfg(not(feature = "shuttle"))]
+fn treiber_stack() {
+       let stack = TreiberStack::new();
+       on_threads(
+              |_| {
+                     for i in 0..PER_THREAD {
+                            stack.push(i.to_string());
+                            if i % 2 == 0 {
+                                   drop(stack.pop());
+                            }
+                     }
+              },
+              || (),
+       );
+       drop(stack); // half the values still on it
+       reclaim_deferred();
+}
+
+fn cells() {
+       let slot = AtomicOptionBox::new(Some(Box::new(String::from("first"))));
+       let rcu = RcuCell::new(String::from("first"));
+       on_threads(
+              |_| {
+                     for i in 0..PER_THREAD {
+                            drop(slot.swap(Some(Box::new(i.to_string()))));
+                            rcu.store(i.to_string());
+                            drop(rcu.load());
+                     }
+              },
+              || (),
+       );
+       let (writer, mut reader) = TripleBuffer::new(String::new()).split();
+       let writer = Mutex::new(Some(writer));
+       on_threads(
+              |index| {
+                     if index == 0 {
+                            let mut writer = writer.lock().take().unwrap();
+                            (0..PER_THREAD).for_each(|i| writer.write(i.to_string()));
+                     }
+              },
+              || (0..PER_THREAD).for_each(|_| drop(reader.read().clone())),
+       );
+}
+
+const SCENARIOS: &[(&str, fn())] = &[
+       ("myarc::Arc", arc),
+       ("channel::mpsc", mpsc),
+       ("channel::BlockingChannel", blocking),
+       ("channel::oneshot", oneshots),
+       ("channel::rendezvous", rendezvous),
+       #[cfg(not(feature = "shuttle"))]
+       ("TreiberStack", treiber_stack),
+       ("AtomicOptionBox/RcuCell/TripleBuffer", cells),
+];
+
+fn main() -> ExitCode {
+       const { assert!(SCENARIOS.len() < MAX_SITES) };
+       #[cfg(not(feature = "shuttle"))]
+       prime_registries();
+       let mut leaks = Vec::new();
+       for (index, &(name, scenario)) in SCENARIOS.iter().enumerate() {
+              scenario(); // warm-up, untracked
+              let site = index + 1;
+              SITE.store(site, Relaxed);
+              scenario();
+              SITE.store(0, Relaxed);
+              let (blocks, bytes) = (LIVE_BLOCKS[site].load(Relaxed), LIVE_BYTES[site].load(Relaxed));
+              println!("{name:<40} {}", if blocks == 0 { "ok" } else { "LEAKED" });
+              if blocks != 0 {
+                     leaks.push(format!("{name}: {blocks} blocks, {bytes} bytes still live"));
+              }
+       }
+       if leaks.is_empty() {
+              return ExitCode::SUCCESS;
+       }
+       eprintln!("\nleaked sites:\n{}", leaks.join("\n"));
+       ExitCode::FAILURE
+}
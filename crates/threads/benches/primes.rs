@@ -0,0 +1,61 @@
+//! The `primes` crate's sieves against each other, and the thread pool's sieve against the thread count.
+//!
+//! `cargo bench --package threads --bench primes`
+//!
+//! The `item/s` columns are numbers sieved per second.
+//! - `naive` / `bit_packed` / `segmented` / `wheel`: every prime up to `max`, single-threaded; a flag per number, a
+//!   bit per odd number, a segment's worth of flags at a time, and flags for only the 8-in-30 numbers coprime to 2·3·5
+//! - `count_only`: the segmented sieves counting instead of collecting (no `Vec` of primes to grow)
+//! - `parallel`: segments as `sync::ThreadPool` tasks, at 1/2/4/8 workers
+//! - `iter`: `Primes::iter()` up to `max`, for what laziness costs over `primes_in`
+//! - `is_prime_each` / `is_prime_sieved`: a block of large numbers, tested one by one with Miller–Rabin, or sieved in
+//!   one pass
+
+use divan::{Bencher, counter::ItemsCount};
+use primes::{Primes, bit_sieve, count_primes, is_prime, parallel_sieve, prime_sieve, primes_in, wheel_sieve};
+
+fn main() { divan::main(); }
+
+const MAX: &[usize] = &[100_000, 10_000_000];
+const THREADS: &[usize] = &[1, 2, 4, 8];
+/// Where the `is_prime` block starts, and how long it is.
+const BLOCK: (usize, usize) = (1 << 40, 10_000);
+
+#[divan::bench(args = MAX)]
+fn naive(bencher: Bencher, max: usize) { bencher.counter(ItemsCount::new(max)).bench(|| prime_sieve(None, max)); }
+
+#[divan::bench(args = MAX)]
+fn bit_packed(bencher: Bencher, max: usize) { bencher.counter(ItemsCount::new(max)).bench(|| bit_sieve(0, max)); }
+
+#[divan::bench(args = MAX)]
+fn segmented(bencher: Bencher, max: usize) { bencher.counter(ItemsCount::new(max)).bench(|| primes_in(..=max)); }
+
+#[divan::bench(args = MAX)]
+fn wheel(bencher: Bencher, max: usize) { bencher.counter(ItemsCount::new(max)).bench(|| wheel_sieve(0, max)); }
+
+#[divan::bench(args = [false, true])]
+fn count_only(bencher: Bencher, wheel: bool) {
+       let max = MAX[MAX.len() - 1];
+       bencher.counter(ItemsCount::new(max)).bench(|| count_primes(0, max, wheel));
+}
+
+#[divan::bench(args = THREADS)]
+fn parallel(bencher: Bencher, threads: usize) {
+       let max = MAX[MAX.len() - 1];
+       bencher.counter(ItemsCount::new(max)).bench(|| parallel_sieve(0, max, threads, true, false).count);
+}
+
+#[divan::bench(args = MAX)]
+fn iter(bencher: Bencher, max: usize) { bencher.counter(ItemsCount::new(max)).bench(|| Primes::iter().take_while(|&p| p <= max).count()); }
+
+#[divan::bench]
+fn is_prime_each(bencher: Bencher) {
+       let (low, len) = BLOCK;
+       bencher.counter(ItemsCount::new(len)).bench(|| (low..low + len).filter(|&n| is_prime(n)).count());
+}
+
+#[divan::bench]
+fn is_prime_sieved(bencher: Bencher) {
+       let (low, len) = BLOCK;
+       bencher.counter(ItemsCount::new(len)).bench(|| primes_in(low..low + len).len());
+}
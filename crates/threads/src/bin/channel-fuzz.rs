@@ -0,0 +1,566 @@
+//! # Randomized stress test for the channels
+//! ## [Chapter 5: Building Our Own Channels](https://marabos.nl/atomics/building-channels.html)
+//!
+//! Round after round, for `--duration` seconds: pick a channel, a few producers and consumers, and let each thread
+//! loose on a random (seeded) sequence of operations:
+//! - producers: `send`, `send` with a timeout, pause; some drop their sender early, and on the channels that have
+//!   one, a producer may `close` the channel for everyone
+//! - consumers: `recv`, `recv` with a timeout, `try_recv`, pause; some drop their receiver early (never the last one
+//!   of a shared channel, which has no other way to unblock full-channel senders)
+//!
+//! Every message is a (producer, sequence number) pair, and every thread logs what it sent or received, so after a
+//! round:
+//! - nothing was received twice, and nothing that wasn't sent (a send that *failed* counts as not sent)
+//! - each consumer saw each producer's messages in order
+//! - conservation: every message sent was received, unless the receiver was dropped early and took some down with it
+//!   (except on `rendezvous`, where a successful send means the message was taken)
+//!
+//! A watchdog fails the run if no thread completes an operation for `--watchdog-ms`: a deadlock, or a wakeup lost.
+//! Any failure prints what went wrong and the round's seed, and exits non-zero; `--seed <seed> --channels <channel>`
+//! replays that round's operations (not, alas, its thread interleaving).
+//!
+//! Channels: `blocking`, `bounded` (`BlockingChannel` of `--capacity`), `queue` (`BoundedQueue`), `mpsc`,
+//! `rendezvous`. `oneshot`/`typed_oneshot` carry a single message and `watch` only the latest value, so they don't
+//! conserve a stream.
+
+use std::{collections::{HashMap, HashSet},
+          fmt, process,
+          sync::{Arc,
+                 atomic::{AtomicU8, AtomicU64, AtomicUsize, Ordering::Relaxed}},
+          thread,
+          time::{Duration, Instant}};
+
+use clap::{Parser, ValueEnum};
+use owo_colors::OwoColorize;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use sync::{BoundedQueue, channel};
+
+/// interface for scratch code for use with [Rust Atomics and Locks](https://marabos.nl/atomics/)
+#[derive(Parser, Debug)]
+#[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "input source")]
+struct Args {
+       /// channels to fuzz, taking turns (default: all)
+       #[arg(short, long, value_enum, value_delimiter = ',')]
+       channels:    Vec<Kind>,
+       /// how long to keep starting rounds, in seconds
+       #[arg(short, long, default_value = "10")]
+       duration:    u64,
+       /// seed of the first round (default: random); round `n` uses `seed + n`
+       #[arg(short, long)]
+       seed:        Option<u64>,
+       /// most operations per producer per round
+       #[arg(short, long, default_value = "2000")]
+       ops:         u64,
+       /// capacity of the `bounded` channel and the `queue`; small, so senders block
+       #[arg(long, default_value = "4")]
+       capacity:    usize,
+       /// fail if no thread completes an operation for this long, in milliseconds
+       #[arg(short, long, default_value = "5000")]
+       watchdog_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+enum Kind {
+       Blocking,
+       Bounded,
+       Queue,
+       Mpsc,
+       Rendezvous,
+}
+
+impl Kind {
+       const ALL: [Self; 5] = [Self::Blocking, Self::Bounded, Self::Queue, Self::Mpsc, Self::Rendezvous];
+
+       /// Most (producers, consumers) the channel supports.
+       fn max_ends(self) -> (usize, usize) {
+              match self {
+                     Self::Blocking | Self::Bounded | Self::Queue => (4, 4),
+                     Self::Mpsc => (4, 1),
+                     Self::Rendezvous => (1, 1),
+              }
+       }
+
+       /// Shared by reference: it only ends when closed, and a consumer dropping out doesn't disconnect anything.
+       fn is_shared(self) -> bool { matches!(self, Self::Blocking | Self::Bounded | Self::Queue) }
+}
+
+/// A message: `producer << 32 | sequence`.
+type Id = u64;
+
+fn id(producer: usize, seq: u64) -> Id { (producer as u64) << 32 | seq }
+
+/// `producer:sequence`, for reports.
+struct Shown(Id);
+impl fmt::Display for Shown {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}:{}", self.0 >> 32, self.0 & u64::from(u32::MAX)) }
+}
+
+enum Sent {
+       Ok,
+       TimedOut,
+       Closed,
+}
+
+enum Got {
+       Message(Id),
+       /// Nothing yet: a `try_recv` or a timeout.
+       Empty,
+       Closed,
+}
+
+/// A producer's end of the channel.
+trait Tx: Send {
+       fn send(&self, message: Id) -> Sent;
+       fn send_timeout(&self, message: Id, timeout: Duration) -> Sent;
+       /// Close the channel for everyone; `false` if it can't be (only dropping every sender ends it).
+       fn close(&self) -> bool { false }
+}
+/// A consumer's end.
+trait Rx: Send {
+       fn recv(&self) -> Got;
+       fn recv_timeout(&self, timeout: Duration) -> Got;
+       fn try_recv(&self) -> Got;
+}
+
+fn sent<E>(result: Result<(), E>) -> Sent { if result.is_ok() { Sent::Ok } else { Sent::Closed } }
+
+fn sent_within(result: Result<(), channel::SendTimeoutError<Id>>) -> Sent {
+       match result {
+              Ok(()) => Sent::Ok,
+              Err(channel::SendTimeoutError::Timeout(_)) => Sent::TimedOut,
+              Err(channel::SendTimeoutError::Disconnected(_)) => Sent::Closed,
+       }
+}
+
+fn got<E>(result: Result<Id, E>) -> Got { result.map_or(Got::Closed, Got::Message) }
+
+fn got_within(result: Result<Id, channel::RecvTimeoutError>) -> Got {
+       match result {
+              Ok(message) => Got::Message(message),
+              Err(channel::RecvTimeoutError::Timeout) => Got::Empty,
+              Err(channel::RecvTimeoutError::Disconnected) => Got::Closed,
+       }
+}
+
+fn got_now(result: Result<Id, channel::TryRecvError>) -> Got {
+       match result {
+              Ok(message) => Got::Message(message),
+              Err(channel::TryRecvError::Empty) => Got::Empty,
+              Err(channel::TryRecvError::Disconnected) => Got::Closed,
+       }
+}
+
+impl Tx for Arc<channel::BlockingChannel<Id>> {
+       fn send(&self, message: Id) -> Sent { sent(channel::BlockingChannel::send(self, message)) }
+
+       fn send_timeout(&self, message: Id, timeout: Duration) -> Sent {
+              sent_within(channel::BlockingChannel::send_timeout(self, message, timeout))
+       }
+
+       fn close(&self) -> bool {
+              channel::BlockingChannel::close(self);
+              true
+       }
+}
+impl Rx for Arc<channel::BlockingChannel<Id>> {
+       fn recv(&self) -> Got { got(channel::BlockingChannel::recv(self)) }
+
+       fn recv_timeout(&self, timeout: Duration) -> Got { got_within(channel::BlockingChannel::recv_timeout(self, timeout)) }
+
+       fn try_recv(&self) -> Got { got_now(channel::BlockingChannel::try_recv(self)) }
+}
+impl Tx for Arc<BoundedQueue<Id>> {
+       fn send(&self, message: Id) -> Sent { sent(self.push(message)) }
+
+       fn send_timeout(&self, message: Id, timeout: Duration) -> Sent { sent_within(self.push_timeout(message, timeout)) }
+
+       fn close(&self) -> bool {
+              BoundedQueue::close(self);
+              true
+       }
+}
+impl Rx for Arc<BoundedQueue<Id>> {
+       fn recv(&self) -> Got { got(self.pop()) }
+
+       fn recv_timeout(&self, timeout: Duration) -> Got { got_within(self.pop_timeout(timeout)) }
+
+       fn try_recv(&self) -> Got { got_within(self.pop_timeout(Duration::ZERO)) }
+}
+impl Tx for channel::mpsc::Sender<Id> {
+       fn send(&self, message: Id) -> Sent { sent(Self::send(self, message)) }
+
+       /// Never blocks anyway.
+       fn send_timeout(&self, message: Id, _: Duration) -> Sent { sent(Self::send(self, message)) }
+}
+impl Rx for channel::mpsc::Receiver<Id> {
+       fn recv(&self) -> Got { got(Self::recv(self)) }
+
+       fn recv_timeout(&self, timeout: Duration) -> Got { got_within(Self::recv_timeout(self, timeout)) }
+
+       fn try_recv(&self) -> Got { got_now(Self::try_recv(self)) }
+}
+impl Tx for channel::rendezvous::Sender<Id> {
+       fn send(&self, message: Id) -> Sent { sent(Self::send(self, message)) }
+
+       fn send_timeout(&self, message: Id, timeout: Duration) -> Sent { sent_within(Self::send_timeout(self, message, timeout)) }
+}
+impl Rx for channel::rendezvous::Receiver<Id> {
+       fn recv(&self) -> Got { got(Self::recv(self)) }
+
+       fn recv_timeout(&self, timeout: Duration) -> Got { got_within(Self::recv_timeout(self, timeout)) }
+
+       /// No `try_recv`: a zero timeout is the nearest thing.
+       fn try_recv(&self) -> Got { got_within(Self::recv_timeout(self, Duration::ZERO)) }
+}
+
+/// Both ends of one channel, plus how to end it once every producer is done.
+struct Endpoints {
+       txs:    Vec<Box<dyn Tx>>,
+       rxs:    Vec<Box<dyn Rx>>,
+       finish: Box<dyn FnOnce() + Send>,
+}
+
+impl Endpoints {
+       fn new(kind: Kind, producers: usize, consumers: usize, capacity: usize) -> Self {
+              match kind {
+                     Kind::Blocking | Kind::Bounded => {
+                            let channel = Arc::new(match kind {
+                                   Kind::Bounded => channel::BlockingChannel::bounded(capacity),
+                                   _ => channel::BlockingChannel::unbounded(),
+                            });
+                            Self::shared(&channel, producers, consumers, {
+                                   let channel = channel.clone();
+                                   move || channel::BlockingChannel::close(&channel)
+                            })
+                     }
+                     Kind::Queue => {
+                            let queue = Arc::new(BoundedQueue::new(capacity));
+                            Self::shared(&queue, producers, consumers, {
+                                   let queue = queue.clone();
+                                   move || BoundedQueue::close(&queue)
+                            })
+                     }
+                     Kind::Mpsc => {
+                            let (sender, receiver) = channel::mpsc();
+                            Self::split((0..producers).map(|_| sender.clone()), receiver)
+                     }
+                     Kind::Rendezvous => {
+                            let (sender, receiver) = channel::rendezvous();
+                            Self::split([sender], receiver)
+                     }
+              }
+       }
+
+       fn shared<C: Tx + Rx + Clone + 'static>(
+              channel: &C,
+              producers: usize,
+              consumers: usize,
+              close: impl FnOnce() + Send + 'static,
+       ) -> Self {
+              Self {
+                     txs:    (0..producers).map(|_| Box::new(channel.clone()) as Box<dyn Tx>).collect(),
+                     rxs:    (0..consumers).map(|_| Box::new(channel.clone()) as Box<dyn Rx>).collect(),
+                     finish: Box::new(close),
+              }
+       }
+
+       /// Channels that disconnect once their senders drop: nothing to do at the end.
+       fn split<T: Tx + 'static>(senders: impl IntoIterator<Item = T>, receiver: impl Rx + 'static) -> Self {
+              Self {
+                     txs:    senders.into_iter().map(|sender| Box::new(sender) as Box<dyn Tx>).collect(),
+                     rxs:    vec![Box::new(receiver)],
+                     finish: Box::new(|| ()),
+              }
+       }
+}
+
+/// What a thread is doing, for the watchdog's report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Doing {
+       Starting,
+       Send,
+       SendTimeout,
+       Recv,
+       RecvTimeout,
+       TryRecv,
+       Pausing,
+       Done,
+}
+
+impl Doing {
+       const ALL: [Self; 8] =
+              [Self::Starting, Self::Send, Self::SendTimeout, Self::Recv, Self::RecvTimeout, Self::TryRecv, Self::Pausing, Self::Done];
+}
+
+/// Shared by a round's threads and its watchdog.
+struct Round {
+       /// Operations completed, by anyone.
+       progress: AtomicU64,
+       /// Per thread, producers first.
+       doing:    Vec<AtomicU8>,
+}
+
+impl Round {
+       fn set(&self, thread: usize, doing: Doing) {
+              self.doing[thread].store(doing as u8, Relaxed);
+              self.progress.fetch_add(1, Relaxed);
+       }
+
+       fn doing(&self, thread: usize) -> Doing { Doing::ALL[usize::from(self.doing[thread].load(Relaxed))] }
+}
+
+/// What a round's threads did.
+#[derive(Default)]
+struct Log {
+       /// Per producer, the messages it sent successfully.
+       sent:     Vec<Vec<Id>>,
+       /// Per consumer, the messages it received, in order.
+       received: Vec<Vec<Id>>,
+       /// Some receiver was dropped while messages might still arrive.
+       dropped:  bool,
+       closed:   bool,
+}
+
+fn produce(tx: Box<dyn Tx>, producer: usize, ops: u64, mut rng: StdRng, round: &Round) -> (Vec<Id>, bool) {
+       let leave_at = if rng.random_bool(0.3) { rng.random_range(0..ops) } else { ops };
+       let close_at = rng.random_bool(0.1).then(|| rng.random_range(0..ops));
+       let mut sent = Vec::new();
+       for seq in 0..leave_at {
+              if close_at == Some(seq) && tx.close() {
+                     return (sent, true);
+              }
+              let outcome = match rng.random_range(0..10) {
+                     0..6 => {
+                            round.set(producer, Doing::Send);
+                            tx.send(id(producer, seq))
+                     }
+                     6..9 => {
+                            round.set(producer, Doing::SendTimeout);
+                            tx.send_timeout(id(producer, seq), Duration::from_micros(rng.random_range(0..200)))
+                     }
+                     _ => {
+                            round.set(producer, Doing::Pausing);
+                            thread::sleep(Duration::from_micros(rng.random_range(0..200)));
+                            continue;
+                     }
+              };
+              match outcome {
+                     Sent::Ok => sent.push(id(producer, seq)),
+                     Sent::TimedOut => (),
+                     Sent::Closed => break,
+              }
+       }
+       (sent, false)
+}
+
+/// Receive until the channel ends, or until this consumer leaves early (if `may_leave`).
+fn consume(rx: Box<dyn Rx>, thread: usize, may_leave: bool, mut rng: StdRng, round: &Round) -> (Vec<Id>, bool) {
+       let leave_after = (may_leave && rng.random_bool(0.3)).then(|| rng.random_range(0..1000));
+       let mut received = Vec::new();
+       for op in 0_u32.. {
+              if leave_after == Some(op) {
+                     return (received, true);
+              }
+              let got = match rng.random_range(0..10) {
+                     0..5 => {
+                            round.set(thread, Doing::Recv);
+                            rx.recv()
+                     }
+                     5..8 => {
+                            round.set(thread, Doing::RecvTimeout);
+                            rx.recv_timeout(Duration::from_micros(rng.random_range(0..500)))
+                     }
+                     8 => {
+                            round.set(thread, Doing::TryRecv);
+                            rx.try_recv()
+                     }
+                     _ => {
+                            round.set(thread, Doing::Pausing);
+                            thread::sleep(Duration::from_micros(rng.random_range(0..100)));
+                            continue;
+                     }
+              };
+              match got {
+                     Got::Message(message) => received.push(message),
+                     Got::Empty => (),
+                     Got::Closed => break,
+              }
+       }
+       (received, false)
+}
+
+/// One round on `kind`, everything derived from `seed`. Exits the process if the watchdog fires or a thread panics.
+fn run_round(kind: Kind, seed: u64, args: &Args) -> Log {
+       let mut rng = StdRng::seed_from_u64(seed);
+       let (max_producers, max_consumers) = kind.max_ends();
+       let (producers, consumers) = (rng.random_range(1..=max_producers), rng.random_range(1..=max_consumers));
+       let Endpoints { txs, rxs, finish } = Endpoints::new(kind, producers, consumers, args.capacity);
+       let round = Round { progress: AtomicU64::new(0), doing: (0..producers + consumers).map(|_| AtomicU8::new(0)).collect() };
+       let (producers_left, consumers_left) = (AtomicUsize::new(producers), AtomicUsize::new(consumers));
+       let watchdog = Duration::from_millis(args.watchdog_ms);
+       let fail = |what: &str| -> ! {
+              let who = (0..producers + consumers)
+                     .map(|thread| {
+                            let name = if thread < producers {
+                                   format!("producer {thread}")
+                            } else {
+                                   format!("consumer {}", thread - producers)
+                            };
+                            format!("  {name}: {:?}", round.doing(thread))
+                     })
+                     .collect::<Vec<_>>()
+                     .join("\n");
+              failure(kind, seed, &format!("{what} ({producers} producers, {consumers} consumers)\n{who}"))
+       };
+       thread::scope(|s| {
+              let consumers: Vec<_> = rxs
+                     .into_iter()
+                     .enumerate()
+                     .map(|(consumer, rx)| {
+                            // a shared channel's last consumer must stay: full-channel senders have nobody else to unblock them
+                            let may_leave = !kind.is_shared() || consumer > 0;
+                            let (rng, round, left) = (StdRng::seed_from_u64(rng.random()), &round, &consumers_left);
+                            s.spawn(move || {
+                                   let thread = producers + consumer;
+                                   let received = consume(rx, thread, may_leave, rng, round);
+                                   round.set(thread, Doing::Done);
+                                   left.fetch_sub(1, Relaxed);
+                                   received
+                            })
+                     })
+                     .collect();
+              let producers: Vec<_> = txs
+                     .into_iter()
+                     .enumerate()
+                     .map(|(producer, tx)| {
+                            let ops = rng.random_range(1..=args.ops);
+                            let (rng, round, left) = (StdRng::seed_from_u64(rng.random()), &round, &producers_left);
+                            s.spawn(move || {
+                                   let sent = produce(tx, producer, ops, rng, round);
+                                   round.set(producer, Doing::Done);
+                                   left.fetch_sub(1, Relaxed);
+                                   sent
+                            })
+                     })
+                     .collect();
+
+              let (mut finish, mut seen, mut since) = (Some(finish), round.progress.load(Relaxed), Instant::now());
+              while producers_left.load(Relaxed) + consumers_left.load(Relaxed) > 0 {
+                     thread::sleep(Duration::from_millis(5));
+                     if producers_left.load(Relaxed) == 0
+                            && let Some(finish) = finish.take()
+                     {
+                            finish();
+                     }
+                     let progress = round.progress.load(Relaxed);
+                     if progress != seen {
+                            (seen, since) = (progress, Instant::now());
+                     } else if since.elapsed() > watchdog {
+                            fail(&format!("deadlock: no operation completed for {watchdog:?}"));
+                     }
+              }
+              let mut log = Log::default();
+              for producer in producers {
+                     let (sent, closed) = producer.join().unwrap_or_else(|_| fail("a producer panicked"));
+                     log.sent.push(sent);
+                     log.closed |= closed;
+              }
+              if let Some(finish) = finish.take() {
+                     finish(); // everyone was done before the watchdog looked (a producer closed the channel)
+              }
+              for consumer in consumers {
+                     let (received, left) = consumer.join().unwrap_or_else(|_| fail("a consumer panicked"));
+                     log.received.push(received);
+                     log.dropped |= left;
+              }
+              log
+       })
+}
+
+/// The round's invariants; the first broken one, described.
+fn check(kind: Kind, log: &Log) -> Result<(), String> {
+       let sent: HashSet<Id> = log.sent.iter().flatten().copied().collect();
+       let mut received = HashSet::new();
+       for (consumer, messages) in log.received.iter().enumerate() {
+              let mut last: HashMap<Id, Id> = HashMap::new();
+              for &message in messages {
+                     if !received.insert(message) {
+                            return Err(format!("{} received twice", Shown(message)));
+                     }
+                     if !sent.contains(&message) {
+                            return Err(format!("{} received, but never (successfully) sent", Shown(message)));
+                     }
+                     if let Some(previous) = last.insert(message >> 32, message)
+                            && previous > message
+                     {
+                            return Err(format!("consumer {consumer} received {} after {}: out of order", Shown(message), Shown(previous)));
+                     }
+              }
+       }
+       // a shared channel's other consumers drain what one leaving didn't take
+       let conserved = !log.dropped || kind.is_shared() || kind == Kind::Rendezvous;
+       match sent.difference(&received).min() {
+              Some(&lost) if conserved => {
+                     Err(format!("{} of {} sent messages never received, {} first", sent.len() - received.len(), sent.len(), Shown(lost)))
+              }
+              _ => Ok(()),
+       }
+}
+
+/// Report a broken invariant and exit.
+fn failure(kind: Kind, seed: u64, what: &str) -> ! {
+       println!("\n{}: {kind:?}, seed {}: {what}", "FAILED".red().bold(), seed.yellow());
+       println!("replay the round's operations: channel-fuzz --seed {seed} --channels {}", format!("{kind:?}").to_lowercase());
+       process::exit(1);
+}
+
+/// Per channel: rounds run, messages delivered, rounds with a receiver dropped early or the channel closed.
+#[derive(Default)]
+struct Tally {
+       rounds:    u64,
+       delivered: u64,
+       dropped:   u64,
+       closed:    u64,
+}
+
+fn main() {
+       let _tracing_writer_worker_guard = utilities::activate_global_default_tracing_subscriber().call().expect("tracing subscriber");
+       let mut args = Args::parse();
+       println!("\n-----{}-----", "Channel Fuzz".bold().purple());
+       if args.channels.is_empty() {
+              args.channels = Kind::ALL.to_vec();
+       }
+       let seed = args.seed.unwrap_or_else(rand::random);
+       println!("seed {}", seed.yellow());
+
+       let mut tallies: HashMap<Kind, Tally> = HashMap::new();
+       let (start, run_for) = (Instant::now(), Duration::from_secs(args.duration));
+       let mut last_report = start;
+       for (round, kind) in (0_u64..).zip(args.channels.iter().copied().cycle()) {
+              if start.elapsed() >= run_for && round > 0 {
+                     break;
+              }
+              let round_seed = seed.wrapping_add(round);
+              let log = run_round(kind, round_seed, &args);
+              if let Err(what) = check(kind, &log) {
+                     failure(kind, round_seed, &what);
+              }
+              let tally = tallies.entry(kind).or_default();
+              tally.rounds += 1;
+              tally.delivered += log.received.iter().map(|received| received.len() as u64).sum::<u64>();
+              tally.dropped += u64::from(log.dropped);
+              tally.closed += u64::from(log.closed);
+              if last_report.elapsed() >= Duration::from_secs(1) {
+                     println!("{:>6.1?}  {} rounds", start.elapsed(), (round + 1).cyan());
+                     last_report = Instant::now();
+              }
+       }
+
+       println!("\n-----{}-----", format!("passed after {:.1?}", start.elapsed()).bold().green());
+       println!("{:<12} {:>8} {:>12} {:>14} {:>8}", "channel", "rounds", "delivered", "early drops", "closed");
+       for kind in Kind::ALL.into_iter().filter(|kind| tallies.contains_key(kind)) {
+              let tally = &tallies[&kind];
+              println!("{:<12} {:>8} {:>12} {:>14} {:>8}", format!("{kind:?}"), tally.rounds, tally.delivered, tally.dropped, tally.closed);
+       }
+}
@@ -0,0 +1,195 @@
+//! # Memory-ordering litmus tests, on this machine
+//! ## [Chapter 3: Memory Ordering](https://marabos.nl/atomics/memory-ordering.html)
+//!
+//! Two threads run a tiny program over two fresh locations, millions of times; we count what they read.
+//! - **store buffer** (SB): each thread stores to one location, then loads the other.
+//!   Both loads seeing `0` means each store was still "in flight" when the other thread loaded.
+//!   Only `SeqCst` forbids it; x86's store buffers produce it readily even so (for `Relaxed` and release/acquire).
+//! - **message passing** (MP): one thread writes `data` then raises `flag`; the other reads `flag` then `data`.
+//!   `flag == 1, data == 0` means the message arrived before its payload.
+//!   Release/acquire forbids it; `Relaxed` allows it, though x86 (which never reorders stores with stores,
+//!   or loads with loads) won't show it. ARM and POWER can.
+//!
+//! Outcomes are (a's load, b's load) for SB, and (flag, data) as b read them for MP.
+//! "Allowed" is what the Rust/C++ model permits; "seen" is what this hardware (and compiler) actually did.
+//! A forbidden outcome that shows up is a bug; an allowed one that never does just isn't exercised here.
+//!
+//! ## **NOTE**
+//! The threads rendezvous before every round, so each round is a fresh race. On a single core the threads only
+//! interleave at preemption points, so weak outcomes are rare to absent: run on 2+ cores.
+
+use std::{collections::BTreeMap,
+          hint,
+          sync::atomic::{AtomicU32, AtomicUsize,
+                         Ordering::{self, Acquire, Relaxed, Release, SeqCst}},
+          thread,
+          time::Instant};
+
+use clap::{Parser, ValueEnum};
+use owo_colors::OwoColorize;
+use sync::Backoff;
+
+/// interface for scratch code for use with [Rust Atomics and Locks](https://marabos.nl/atomics/)
+#[derive(Parser, Debug)]
+#[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "input source")]
+struct Args {
+       /// rounds per test and ordering
+       #[arg(short, long, default_value = "1000000")]
+       iterations: usize,
+       /// only run this test (default: both)
+       #[arg(short, long)]
+       test:       Option<Test>,
+       /// only run under this ordering (default: all three)
+       #[arg(short, long)]
+       ordering:   Option<Model>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Test {
+       StoreBuffer,
+       MessagePassing,
+}
+
+/// The orderings each test is run under: (stores, loads).
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Model {
+       Relaxed,
+       #[value(name = "acqrel")]
+       ReleaseAcquire,
+       #[value(name = "seqcst")]
+       SeqCst,
+}
+
+impl Model {
+       const ALL: [Self; 3] = [Self::Relaxed, Self::ReleaseAcquire, Self::SeqCst];
+
+       fn orderings(self) -> (Ordering, Ordering) {
+              match self {
+                     Self::Relaxed => (Relaxed, Relaxed),
+                     Self::ReleaseAcquire => (Release, Acquire),
+                     Self::SeqCst => (SeqCst, SeqCst),
+              }
+       }
+}
+
+impl Test {
+       /// The "impossible" outcome: (a's load, b's load) for SB, (flag, data) as b read them for MP.
+       fn weak_outcome(self) -> (u32, u32) {
+              match self {
+                     Self::StoreBuffer => (0, 0),
+                     Self::MessagePassing => (1, 0),
+              }
+       }
+
+       /// Whether the language's memory model permits the weak outcome under `model`.
+       fn allows_weak(self, model: Model) -> bool {
+              match (self, model) {
+                     (Self::StoreBuffer, Model::SeqCst) => false,
+                     (Self::StoreBuffer, _) => true,
+                     (Self::MessagePassing, Model::Relaxed) => true,
+                     (Self::MessagePassing, _) => false,
+              }
+       }
+}
+
+fn main() {
+       let _tracing_writer_worker_guard = utilities::activate_global_default_tracing_subscriber().call().expect("tracing subscriber");
+       let args = Args::parse();
+       println!("\n-----{}-----", "Memory-ordering litmus tests".bold().purple());
+       println!("{} cores available", thread::available_parallelism().map_or(1, usize::from).cyan());
+
+       let tests = args.test.map_or(vec![Test::StoreBuffer, Test::MessagePassing], |test| vec![test]);
+       for test in tests {
+              println!("\n-----{}-----", format!("{test:?}").bold().purple());
+              println!("{:<16} {:>8} {:>12} {:>10}  outcomes", "ordering", "allowed", "weak seen", "time");
+              for model in args.ordering.map_or(Model::ALL.to_vec(), |model| vec![model]) {
+                     let start = Instant::now();
+                     let outcomes = run(test, model, args.iterations);
+                     let weak = outcomes.get(&test.weak_outcome()).copied().unwrap_or(0);
+                     let allowed = test.allows_weak(model);
+                     let weak_cell = format!("{weak:>12}");
+                     let weak_cell = match (weak > 0, allowed) {
+                            (true, false) => weak_cell.red().bold().to_string(),
+                            (true, true) => weak_cell.yellow().to_string(),
+                            (false, _) => weak_cell.green().to_string(),
+                     };
+                     println!(
+                            "{:<16} {:>8} {weak_cell} {:>10}  {outcomes:?}",
+                            format!("{model:?}"),
+                            if allowed { "yes" } else { "no" },
+                            format!("{:.1?}", start.elapsed())
+                     );
+              }
+       }
+}
+
+/// Run `test` under `model` for `iterations` rounds; how often each (a, b) outcome came up.
+fn run(test: Test, model: Model, iterations: usize) -> BTreeMap<(u32, u32), usize> {
+       let (store, load) = model.orderings();
+       let x = AtomicU32::new(0);
+       let y = AtomicU32::new(0);
+       // rendezvous: a resets the locations and opens round `i`; b reports its read and closes it
+       let opened = AtomicUsize::new(usize::MAX);
+       let closed = AtomicUsize::new(usize::MAX);
+       let b_reads = [AtomicU32::new(0), AtomicU32::new(0)];
+       let mut outcomes = BTreeMap::new();
+
+       thread::scope(|s| {
+              s.spawn(|| {
+                     for i in 0..iterations {
+                            wait_for(&opened, i);
+                            let reads = match test {
+                                   Test::StoreBuffer => {
+                                          y.store(1, store);
+                                          [x.load(load), 0]
+                                   }
+                                   Test::MessagePassing => {
+                                          let flag = y.load(load);
+                                          [flag, x.load(Relaxed)]
+                                   }
+                            };
+                            for (slot, read) in b_reads.iter().zip(reads) {
+                                   slot.store(read, Relaxed);
+                            }
+                            closed.store(i, Release);
+                     }
+              });
+
+              for i in 0..iterations {
+                     x.store(0, Relaxed);
+                     y.store(0, Relaxed);
+                     opened.store(i, Release);
+                     // vary a's head start, so the race isn't decided the same way every round
+                     for _ in 0..i % 8 {
+                            hint::spin_loop();
+                     }
+                     let a = match test {
+                            Test::StoreBuffer => {
+                                   x.store(1, store);
+                                   y.load(load)
+                            }
+                            Test::MessagePassing => {
+                                   x.store(1, Relaxed);
+                                   y.store(1, store);
+                                   0 // a only writes
+                            }
+                     };
+                     wait_for(&closed, i);
+                     let [b, b_data] = b_reads.each_ref().map(|slot| slot.load(Relaxed));
+                     let outcome = match test {
+                            Test::StoreBuffer => (a, b),
+                            Test::MessagePassing => (b, b_data),
+                     };
+                     *outcomes.entry(outcome).or_default() += 1;
+              }
+       });
+       outcomes
+}
+
+/// Spin (politely: one core is enough to run this) until `round` reaches `i`.
+fn wait_for(round: &AtomicUsize, i: usize) {
+       let mut backoff = Backoff::new();
+       while round.load(Acquire) != i {
+              backoff.snooze();
+       }
+}
@@ -0,0 +1,233 @@
+//! # Spinning vs. sleeping: the locks under tunable contention
+//! ## [Chapter 4: Building Our Own Spin Lock](https://marabos.nl/atomics/building-spinlock.html)
+//! ## [Chapter 9: Building Our Own Locks](https://marabos.nl/atomics/building-locks.html)
+//!
+//! Every thread loops for `--duration` ms: take the lock, hold it for the critical section (busy for `--critical`
+//! ns, plus one increment), release it, then stay busy outside it for `--idle` ns. Each combination of lock, thread
+//! count, critical section, and idle time gets a run of its own, and a row in the table (markdown, or `--csv`):
+//! - acquires per second, all threads together
+//! - p50 / p99 time to acquire: from calling `lock` to holding the guard
+//! - fairness: the fewest acquires any thread got over the most (1 is perfectly even; near 0, a thread starved)
+//!
+//! Locks:
+//! - `spin`: `sync::SpinLock`, test-and-test-and-set; unfair, the releasing core often takes it straight back
+//! - `ticket`: `sync::TicketLock`, spinning in FIFO order
+//! - `mutex`: `sync::Mutex`, the three-state futex mutex: spins briefly, then sleeps in the kernel
+//! - `std`: `std::sync::Mutex`, for reference
+//!
+//! The book's rule of thumb: spinning wins when critical sections are short and there are no more threads than
+//! cores, and loses badly once a spinner burns the time slice the holder needed. `--threads` past the core count,
+//! and a long `--critical`, show the second half.
+//!
+//! ## **NOTE**
+//! Busy time is measured by reading the clock in a loop (a few dozen ns a read), so critical sections and idle times
+//! below ~100ns are approximate; `0` skips them. The table goes to stdout, everything else to stderr: redirect it
+//! to a file to keep just the table.
+
+use std::{hint, sync as std_sync, thread,
+          time::{Duration, Instant}};
+
+use clap::{Parser, ValueEnum};
+use owo_colors::OwoColorize;
+use sync::{CancellationToken, Mutex, SpinLock, TicketLock, histogram::Histogram};
+
+/// interface for scratch code for use with [Rust Atomics and Locks](https://marabos.nl/atomics/)
+#[derive(Parser, Debug)]
+#[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "input source")]
+struct Args {
+       /// locks to measure (default: all)
+       #[arg(short, long, value_enum, value_delimiter = ',')]
+       locks:    Vec<Kind>,
+       /// thread counts to measure
+       #[arg(short, long, value_delimiter = ',', default_value = "1,2,4,8")]
+       threads:  Vec<usize>,
+       /// time held inside the critical section, in nanoseconds
+       #[arg(short, long, value_delimiter = ',', default_value = "0,1000")]
+       critical: Vec<u64>,
+       /// time spent outside the lock between acquires, in nanoseconds
+       #[arg(short, long, value_delimiter = ',', default_value = "0,1000")]
+       idle:     Vec<u64>,
+       /// how long each combination runs, in milliseconds
+       #[arg(short, long, default_value = "500")]
+       duration: u64,
+       /// CSV instead of a markdown table
+       #[arg(long)]
+       csv:      bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Kind {
+       Spin,
+       Ticket,
+       Mutex,
+       Std,
+}
+
+impl Kind {
+       const ALL: [Self; 4] = [Self::Spin, Self::Ticket, Self::Mutex, Self::Std];
+}
+
+/// The lock under test, around the shared count its critical sections increment.
+trait Lock: Sync {
+       fn new() -> Self;
+       /// Run `critical` holding the lock, returning when the lock was acquired.
+       fn locked(&self, critical: impl FnOnce(&mut u64)) -> Instant;
+       fn into_count(self) -> u64;
+}
+
+/// `Lock` for the crate's locks, which share their method names.
+macro_rules! lock {
+       ($($lock:ident),*) => {$(
+              impl Lock for $lock<u64> {
+                     fn new() -> Self { Self::new(0) }
+
+                     fn locked(&self, critical: impl FnOnce(&mut u64)) -> Instant {
+                            let mut guard = self.lock();
+                            let acquired = Instant::now();
+                            critical(&mut guard);
+                            acquired
+                     }
+
+                     fn into_count(self) -> u64 { self.into_inner() }
+              }
+       )*};
+}
+lock!(SpinLock, TicketLock, Mutex);
+
+impl Lock for std_sync::Mutex<u64> {
+       fn new() -> Self { Self::new(0) }
+
+       fn locked(&self, critical: impl FnOnce(&mut u64)) -> Instant {
+              let mut guard = self.lock().unwrap_or_else(std_sync::PoisonError::into_inner);
+              let acquired = Instant::now();
+              critical(&mut guard);
+              acquired
+       }
+
+       fn into_count(self) -> u64 { self.into_inner().unwrap_or_else(std_sync::PoisonError::into_inner) }
+}
+
+/// One combination of lock, threads, critical section and idle time.
+#[derive(Debug, Clone, Copy)]
+struct Case {
+       kind:     Kind,
+       threads:  usize,
+       critical: Duration,
+       idle:     Duration,
+}
+
+/// What a [`Case`] measured.
+struct Measured {
+       acquires_per_sec: f64,
+       p50:              Duration,
+       p99:              Duration,
+       fairness:         f64,
+}
+
+/// Stay busy (on this core, not sleeping) for `duration`.
+fn busy_for(duration: Duration) {
+       if duration.is_zero() {
+              return;
+       }
+       let until = Instant::now() + duration;
+       while Instant::now() < until {
+              hint::spin_loop();
+       }
+}
+
+fn measure<L: Lock>(case: Case, run_for: Duration) -> Measured {
+       let lock = L::new();
+       let waits = Histogram::new();
+       let stop = CancellationToken::new();
+       let start = Instant::now();
+       let per_thread = thread::scope(|s| {
+              let (lock, waits, stop) = (&lock, &waits, &stop);
+              let workers: Vec<_> = (0..case.threads)
+                     .map(|_| {
+                            s.spawn(move || {
+                                   let mut acquires = 0_u64;
+                                   while !stop.is_cancelled() {
+                                          let called = Instant::now();
+                                          let acquired = lock.locked(|count| {
+                                                 busy_for(case.critical);
+                                                 *count += 1;
+                                          });
+                                          waits.record(acquired - called);
+                                          acquires += 1;
+                                          busy_for(case.idle);
+                                   }
+                                   acquires
+                            })
+                     })
+                     .collect();
+              thread::sleep(run_for);
+              stop.cancel();
+              sync::join_all(workers).expect("bench threads")
+       });
+       let elapsed = start.elapsed();
+       let total: u64 = per_thread.iter().sum();
+       let (fewest, most) = (per_thread.iter().min().copied().unwrap_or(0), per_thread.iter().max().copied().unwrap_or(0));
+       // the lock's own count, every increment made under it: a lock that let two threads in would come up short
+       assert_eq!(lock.into_count(), total, "{:?} lost increments: mutual exclusion broken", case.kind);
+       Measured {
+              acquires_per_sec: total as f64 / elapsed.as_secs_f64(),
+              p50:              waits.p50(),
+              p99:              waits.p99(),
+              fairness:         if most == 0 { 0.0 } else { fewest as f64 / most as f64 },
+       }
+}
+
+fn main() {
+       let _tracing_writer_worker_guard = utilities::activate_global_default_tracing_subscriber().call().expect("tracing subscriber");
+       let mut args = Args::parse();
+       eprintln!("\n-----{}-----", "Lock Bench".bold().purple());
+       if args.locks.is_empty() {
+              args.locks = Kind::ALL.to_vec();
+       }
+       eprintln!("{} cores available", thread::available_parallelism().map_or(1, usize::from).cyan());
+
+       let columns = ["lock", "threads", "critical ns", "idle ns", "acquires/s", "p50 wait ns", "p99 wait ns", "fairness"];
+       if args.csv {
+              println!("{}", columns.join(","));
+       } else {
+              println!("| {} |", columns.join(" | "));
+              println!("|{}", "---|".repeat(columns.len()));
+       }
+       let run_for = Duration::from_millis(args.duration);
+       for &kind in &args.locks {
+              for &threads in &args.threads {
+                     for &critical in &args.critical {
+                            for &idle in &args.idle {
+                                   let case = Case {
+                                          kind,
+                                          threads,
+                                          critical: Duration::from_nanos(critical),
+                                          idle: Duration::from_nanos(idle),
+                                   };
+                                   eprintln!("{case:?}");
+                                   let measured = match kind {
+                                          Kind::Spin => measure::<SpinLock<u64>>(case, run_for),
+                                          Kind::Ticket => measure::<TicketLock<u64>>(case, run_for),
+                                          Kind::Mutex => measure::<Mutex<u64>>(case, run_for),
+                                          Kind::Std => measure::<std_sync::Mutex<u64>>(case, run_for),
+                                   };
+                                   let row = [
+                                          format!("{kind:?}").to_lowercase(),
+                                          threads.to_string(),
+                                          critical.to_string(),
+                                          idle.to_string(),
+                                          format!("{:.0}", measured.acquires_per_sec),
+                                          measured.p50.as_nanos().to_string(),
+                                          measured.p99.as_nanos().to_string(),
+                                          format!("{:.2}", measured.fairness),
+                                   ];
+                                   if args.csv {
+                                          println!("{}", row.join(","));
+                                   } else {
+                                          println!("| {} |", row.join(" | "));
+                                   }
+                            }
+                     }
+              }
+       }
+}
@@ -0,0 +1,238 @@
+//! # Dining philosophers, four ways
+//! ## [Chapter 1: Locking: Mutexes and RwLocks](https://marabos.nl/atomics/basics.html#locking)
+//!
+//! `n` philosophers sit around a table with one fork (a `sync::Mutex`) between each pair; eating takes both.
+//! - **naive**: left fork, then right. Once everyone holds a left fork, nobody ever gets a right one: deadlock.
+//! - **ordered**: lower-numbered fork first. The last philosopher reaches "backwards", so the wait-for cycle can't close.
+//! - **backoff**: left fork, then `try_lock` the right; on failure put the left back down and back off.
+//!   No deadlock, but livelock-prone and unfair under contention.
+//! - **waiter**: a `Semaphore` with `n - 1` seats; at most `n - 1` philosophers reach for forks at once,
+//!   so at least one of them gets both.
+//!
+//! Meals and time spent hungry are reported live; a table that stops eating with everyone stuck on their second fork
+//! is reported as deadlocked, and the process exits (the deadlocked threads can't be joined).
+//!
+//! ## **NOTE**
+//! `--gap-us` pauses between the two forks, which makes the naive deadlock show up within moments rather than by luck.
+
+use std::{fmt, process,
+          sync::atomic::{AtomicU8, AtomicU64, Ordering::Relaxed},
+          thread,
+          time::{Duration, Instant}};
+
+use clap::{Parser, ValueEnum};
+use owo_colors::OwoColorize;
+use sync::{Backoff, CancellationToken, Mutex, Semaphore};
+
+/// interface for scratch code for use with [Rust Atomics and Locks](https://marabos.nl/atomics/)
+#[derive(Parser, Debug)]
+#[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "input source")]
+struct Args {
+       /// how philosophers pick up their forks
+       #[arg(short, long, value_enum, default_value = "ordered")]
+       strategy:     Strategy,
+       /// philosophers (and forks) at the table
+       #[arg(short, long, default_value = "5")]
+       philosophers: usize,
+       /// how long to run, in seconds
+       #[arg(short = 't', long, default_value = "3")]
+       seconds:      u64,
+       /// time spent eating (holding both forks), in microseconds
+       #[arg(short, long, default_value = "100")]
+       eat_us:       u64,
+       /// time spent thinking between meals, in microseconds
+       #[arg(long, default_value = "100")]
+       think_us:     u64,
+       /// pause between picking up the first fork and reaching for the second, in microseconds
+       #[arg(short, long, default_value = "50")]
+       gap_us:       u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Strategy {
+       Naive,
+       Ordered,
+       Backoff,
+       Waiter,
+}
+
+/// What a philosopher is doing, for the live report and the deadlock check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Doing {
+       Thinking,
+       Hungry,
+       /// Holding one fork, waiting for the other.
+       OneFork,
+       Eating,
+}
+
+impl Doing {
+       fn from_u8(value: u8) -> Self { [Self::Thinking, Self::Hungry, Self::OneFork, Self::Eating][value as usize] }
+}
+
+/// One philosopher's counters, written by them and read by the reporter.
+#[derive(Default)]
+struct Stats {
+       doing:        AtomicU8,
+       meals:        AtomicU64,
+       hungry_nanos: AtomicU64,
+       /// Longest single wait from hungry to eating.
+       worst_nanos:  AtomicU64,
+}
+
+impl Stats {
+       fn set(&self, doing: Doing) { self.doing.store(doing as u8, Relaxed); }
+
+       fn doing(&self) -> Doing { Doing::from_u8(self.doing.load(Relaxed)) }
+
+       fn record_meal(&self, hungry: Duration) {
+              let nanos = u64::try_from(hungry.as_nanos()).unwrap_or(u64::MAX);
+              self.meals.fetch_add(1, Relaxed);
+              self.hungry_nanos.fetch_add(nanos, Relaxed);
+              self.worst_nanos.fetch_max(nanos, Relaxed);
+       }
+}
+
+impl fmt::Display for Doing {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              let symbol = match self {
+                     Self::Thinking => "T",
+                     Self::Hungry => "H",
+                     Self::OneFork => "1",
+                     Self::Eating => "E",
+              };
+              f.write_str(symbol)
+       }
+}
+
+struct Table {
+       forks:    Vec<Mutex<()>>,
+       /// Seats for [`Strategy::Waiter`]: one fewer than philosophers.
+       waiter:   Semaphore,
+       stats:    Vec<Stats>,
+       strategy: Strategy,
+       gap:      Duration,
+}
+
+impl Table {
+       fn new(args: &Args) -> Self {
+              let seats = u32::try_from(args.philosophers - 1).expect("fewer than 2^32 philosophers");
+              Self {
+                     forks:    (0..args.philosophers).map(|_| Mutex::new(())).collect(),
+                     waiter:   Semaphore::new(seats),
+                     stats:    (0..args.philosophers).map(|_| Stats::default()).collect(),
+                     strategy: args.strategy,
+                     gap:      Duration::from_micros(args.gap_us),
+              }
+       }
+
+       /// Philosopher `seat` picks up both forks, by the table's strategy, and eats for `eat_for`.
+       fn dine(&self, seat: usize, eat_for: Duration) {
+              let (left, right) = (seat, (seat + 1) % self.forks.len());
+              let stats = &self.stats[seat];
+              let hungry = Instant::now();
+              stats.set(Doing::Hungry);
+              let eat = || {
+                     stats.set(Doing::Eating);
+                     stats.record_meal(hungry.elapsed());
+                     thread::sleep(eat_for);
+              };
+              match self.strategy {
+                     Strategy::Naive => self.both(stats, left, right, eat),
+                     Strategy::Ordered => self.both(stats, left.min(right), left.max(right), eat),
+                     Strategy::Backoff => {
+                            let mut backoff = Backoff::new();
+                            loop {
+                                   let first = self.forks[left].lock();
+                                   stats.set(Doing::OneFork);
+                                   thread::sleep(self.gap);
+                                   if let Some(_second) = self.forks[right].try_lock() {
+                                          eat();
+                                          break;
+                                   }
+                                   stats.set(Doing::Hungry);
+                                   drop(first);
+                                   backoff.snooze();
+                            }
+                     }
+                     Strategy::Waiter => {
+                            let _seat = self.waiter.acquire();
+                            self.both(stats, left, right, eat);
+                     }
+              }
+       }
+
+       /// Lock fork `first` then `second` (blocking on each), and `eat`.
+       fn both(&self, stats: &Stats, first: usize, second: usize, eat: impl FnOnce()) {
+              let _first = self.forks[first].lock();
+              stats.set(Doing::OneFork);
+              thread::sleep(self.gap);
+              let _second = self.forks[second].lock();
+              eat();
+       }
+}
+
+fn main() {
+       let _tracing_writer_worker_guard = utilities::activate_global_default_tracing_subscriber().call().expect("tracing subscriber");
+       let args = Args::parse();
+       println!("\n-----{}-----", "Dining Philosophers".bold().purple());
+       assert!(args.philosophers >= 2, "the table needs at least two philosophers (and forks)");
+
+       let table = Table::new(&args);
+       let stop = CancellationToken::new();
+       let (eat, think) = (Duration::from_micros(args.eat_us), Duration::from_micros(args.think_us));
+       let start = Instant::now();
+       thread::scope(|s| {
+              for seat in 0..args.philosophers {
+                     let (table, stop) = (&table, &stop);
+                     thread::Builder::new()
+                            .name(format!("philosopher-{seat}"))
+                            .spawn_scoped(s, move || {
+                                   while !stop.is_cancelled() {
+                                          table.stats[seat].set(Doing::Thinking);
+                                          thread::sleep(think);
+                                          table.dine(seat, eat);
+                                   }
+                            })
+                            .expect("spawn philosopher");
+              }
+              report(&table, &stop, start, Duration::from_secs(args.seconds));
+       });
+       summary(&table, start.elapsed());
+}
+
+/// Print the table every half second until `run_for` is up (then stop the philosophers), or until it deadlocks.
+fn report(table: &Table, stop: &CancellationToken, start: Instant, run_for: Duration) {
+       const EVERY: Duration = Duration::from_millis(500);
+       let total_meals = || table.stats.iter().map(|stats| stats.meals.load(Relaxed)).sum::<u64>();
+       let mut last_meals = total_meals();
+       while start.elapsed() < run_for {
+              thread::sleep(EVERY.min(run_for.saturating_sub(start.elapsed())));
+              let meals = total_meals();
+              let doing: Vec<_> = table.stats.iter().map(Stats::doing).collect();
+              let line: String = doing.iter().map(ToString::to_string).collect();
+              println!("{:>6.1?}  [{line}]  meals {:>8} (+{})", start.elapsed(), meals.cyan(), (meals - last_meals).green());
+              if meals == last_meals && doing.iter().all(|doing| *doing == Doing::OneFork) {
+                     println!("\n{}: everyone holds one fork and waits for the next; nobody will ever eat again", "deadlock".red().bold());
+                     summary(table, start.elapsed());
+                     process::exit(1);
+              }
+              last_meals = meals;
+       }
+       stop.cancel();
+}
+
+/// Per-philosopher meals and hunger, plus how fairly the meals were shared.
+fn summary(table: &Table, elapsed: Duration) {
+       println!("\n-----{}-----", format!("{:?} after {elapsed:.1?}", table.strategy).bold().purple());
+       println!("{:<6} {:>8} {:>14} {:>14}", "seat", "meals", "mean hungry", "worst hungry");
+       let meals: Vec<u64> = table.stats.iter().map(|stats| stats.meals.load(Relaxed)).collect();
+       for (seat, stats) in table.stats.iter().enumerate() {
+              let mean = Duration::from_nanos(stats.hungry_nanos.load(Relaxed).checked_div(meals[seat]).unwrap_or(0));
+              let worst = Duration::from_nanos(stats.worst_nanos.load(Relaxed));
+              println!("{seat:<6} {:>8} {:>14} {:>14}", meals[seat], format!("{mean:.1?}"), format!("{worst:.1?}"));
+       }
+       let (fewest, most) = (meals.iter().min().copied().unwrap_or(0), meals.iter().max().copied().unwrap_or(0));
+       println!("total {} meals; fewest/most {}", meals.iter().sum::<u64>().cyan(), format!("{fewest}/{most}").yellow());
+}
@@ -0,0 +1,157 @@
+//! # Priority inversion, on purpose
+//!
+//! Three kinds of thread share one core:
+//! - **low** takes a lock and does a fixed amount of CPU work while holding it
+//! - **medium** threads spin, never touching the lock, until high gets it
+//! - **high** wants the lock
+//!
+//! Run once with every thread at the same priority, then again with low at the lowest priority.
+//! The second time, the mediums outrank low, so low (and the lock it holds) barely gets the core:
+//! high ends up waiting on threads that have nothing to do with its lock. That's the inversion.
+//!
+//! ## **NOTE**
+//! Raising priority needs privileges on Linux (`CAP_SYS_NICE`), so this only *lowers* low; the effect is the same.
+//! Linux nice levels are weights, so low still gets a sliver of the core and the inversion is a stall, not a hang.
+//!
+//! `sync::Mutex` is a plain futex lock with no priority inheritance; the fix is a lock that lends the waiter's
+//! priority to the holder while it holds (`PTHREAD_PRIO_INHERIT`, `FUTEX_LOCK_PI`), so low outranks the mediums
+//! just long enough to get out of high's way.
+
+use std::{hint,
+          sync::atomic::{AtomicU64, Ordering::Relaxed},
+          thread,
+          time::{Duration, Instant}};
+
+use clap::Parser;
+use owo_colors::OwoColorize;
+use sync::{CancellationToken, Mutex, affinity,
+           priority::{SpawnWithPriority, ThreadPriority}};
+
+/// interface for scratch code for use with [Rust Atomics and Locks](https://marabos.nl/atomics/)
+#[derive(Parser, Debug)]
+#[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "input source")]
+struct Args {
+       /// CPU time low spends holding the lock, in milliseconds (measured alone)
+       #[arg(short, long, default_value = "10")]
+       work_ms: u64,
+       /// number of medium-priority spinning threads
+       #[arg(short, long, default_value = "2")]
+       mediums: usize,
+       /// core to put every thread on
+       #[arg(short, long, default_value = "0")]
+       core:    usize,
+}
+
+/// Priorities for one run.
+#[derive(Debug, Clone, Copy)]
+struct Priorities {
+       low:    ThreadPriority,
+       medium: ThreadPriority,
+       high:   ThreadPriority,
+}
+
+fn main() {
+       let _tracing_writer_worker_guard = utilities::activate_global_default_tracing_subscriber().call().expect("tracing subscriber");
+       let args = Args::parse();
+       println!("\n-----{}-----", "Priority Inversion".bold().purple());
+       let work = Work::calibrate(Duration::from_millis(args.work_ms));
+       println!("low's work: {} spins ({}ms alone)", work.spins.cyan(), args.work_ms);
+
+       let same = Priorities { low: ThreadPriority::Normal, medium: ThreadPriority::Normal, high: ThreadPriority::Normal };
+       let inverted = Priorities { low: ThreadPriority::Lowest, medium: ThreadPriority::Normal, high: ThreadPriority::Normal };
+       for (title, priorities) in [("all equal", same), ("low at lowest", inverted)] {
+              println!("\n-----{}-----", title.bold().purple());
+              let (waited, medium_spins) = run(&args, work, priorities);
+              println!(
+                     "high waited {} for the lock; the mediums spun {} times meanwhile",
+                     format!("{waited:.1?}").yellow(),
+                     medium_spins.blue()
+              );
+       }
+
+       println!("\n-----{}-----", "priority inheritance".bold().purple());
+       println!(
+              "{}: a priority-inheriting lock would have run low at high's priority while high waited on it,\nso the second wait would look like the first. `sync::Mutex` doesn't (see the module docs).",
+              "note".green()
+       );
+}
+
+/// One scenario: low holds the lock, the mediums pile on, high waits; how long high waited, and how much the mediums spun.
+fn run(args: &Args, work: Work, priorities: Priorities) -> (Duration, u64) {
+       let lock = Mutex::new(());
+       let high_has_lock = CancellationToken::new();
+       let medium_spins = AtomicU64::new(0);
+       let (lock, high_has_lock, medium_spins, core) = (&lock, &high_has_lock, &medium_spins, args.core);
+       thread::scope(|s| {
+              let (holding, low_holds) = sync::channel::oneshot();
+              let low = thread::Builder::new().name("low".into());
+              low.spawn_scoped_with_priority(s, priorities.low, move || {
+                     pin(core);
+                     let _guard = lock.lock();
+                     holding.send(());
+                     work.run();
+              })
+              .expect("spawn low");
+              low_holds.recv().expect("low takes the lock");
+
+              for i in 0..args.mediums {
+                     let medium = thread::Builder::new().name(format!("medium-{i}"));
+                     medium.spawn_scoped_with_priority(s, priorities.medium, move || {
+                            pin(core);
+                            while !high_has_lock.is_cancelled() {
+                                   Work { spins: 1_000 }.run();
+                                   medium_spins.fetch_add(1, Relaxed);
+                            }
+                     })
+                     .expect("spawn medium");
+              }
+
+              let high = thread::Builder::new().name("high".into());
+              let high = high
+                     .spawn_scoped_with_priority(s, priorities.high, move || {
+                            pin(core);
+                            let start = Instant::now();
+                            let _guard = lock.lock();
+                            let waited = start.elapsed();
+                            high_has_lock.cancel();
+                            waited
+                     })
+                     .expect("spawn high");
+              (high.join().expect("high doesn't panic"), medium_spins.load(Relaxed))
+       })
+}
+
+/// Put the calling thread on `core`; without a shared core there's nothing to fight over, so say so.
+fn pin(core: usize) {
+       if let Err(error) = affinity::pin_to_core(core) {
+              eprintln!(
+                     "{}: {} not pinned ({error}); the threads may not share a core",
+                     "warning".yellow(),
+                     thread::current().name().unwrap_or("?")
+              );
+       }
+}
+
+/// A fixed amount of CPU work: the same spins take longer only if the thread gets less of the core.
+#[derive(Debug, Clone, Copy)]
+struct Work {
+       spins: u64,
+}
+
+impl Work {
+       /// Spins taking about `target` on this thread, running alone.
+       fn calibrate(target: Duration) -> Self {
+              let probe = Self { spins: 1_000_000 };
+              let start = Instant::now();
+              probe.run();
+              let per_spin = start.elapsed().as_secs_f64() / probe.spins as f64;
+              Self { spins: (target.as_secs_f64() / per_spin) as u64 }
+       }
+
+       fn run(self) {
+              let mut x = 0_u64;
+              for i in 0..self.spins {
+                     x = hint::black_box(x.wrapping_add(i));
+              }
+       }
+}
@@ -0,0 +1,255 @@
+//! # Producer-consumer testbed for the channels
+//! ## [Chapter 5: Building Our Own Channels](https://marabos.nl/atomics/building-channels.html)
+//!
+//! Producers send timestamped messages as fast as the channel takes them, for a fixed time; consumers receive and
+//! record each message's latency (send to receive) in a `sync::histogram::Histogram`.
+//! At the end: messages per second, and latency percentiles.
+//!
+//! Channels, and the producer/consumer counts they allow:
+//! - `blocking`: `BlockingChannel`, unbounded (any number of each)
+//! - `bounded`: `BlockingChannel` of `--capacity` (any number of each; producers block while full)
+//! - `queue`: `BoundedQueue` of `--capacity` (any number of each; as `bounded`, and reports its backpressure gauges:
+//!   high-water mark, how many sends blocked and for how long)
+//! - `mpsc`: the lock-free `channel::mpsc` (any number of producers, one consumer)
+//! - `rendezvous`: `channel::rendezvous` (one of each; every send waits for its receive)
+//! - `std`: `std::sync::mpsc`, for reference (any number of producers, one consumer)
+//!
+//! `oneshot`/`typed_oneshot` carry a single message and `watch` only the latest value, so they aren't streams to
+//! measure here.
+//!
+//! ## **NOTE**
+//! With unbounded channels and slow consumers, latency measures queueing: it grows for as long as the run lasts.
+
+use std::{sync::{Arc, mpsc as std_mpsc},
+          thread,
+          time::{Duration, Instant}};
+
+use clap::{CommandFactory, Parser, ValueEnum, error::ErrorKind};
+use owo_colors::OwoColorize;
+use sync::{BoundedQueue, CancellationToken, channel, histogram::Histogram};
+
+/// interface for scratch code for use with [Rust Atomics and Locks](https://marabos.nl/atomics/)
+#[derive(Parser, Debug)]
+#[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "input source")]
+struct Args {
+       /// channel implementation
+       #[arg(short, long, value_enum, default_value = "blocking")]
+       channel:   Kind,
+       /// producer threads
+       #[arg(short, long, default_value = "1")]
+       producers: usize,
+       /// consumer threads
+       #[arg(short = 'n', long, default_value = "1")]
+       consumers: usize,
+       /// bytes of payload per message (heap-allocated, as a `Vec<u8>`)
+       #[arg(short = 'b', long, default_value = "0")]
+       payload:   usize,
+       /// capacity of the `bounded` channel and the `queue`
+       #[arg(long, default_value = "64")]
+       capacity:  usize,
+       /// how long producers keep sending, in milliseconds
+       #[arg(short, long, default_value = "2000")]
+       duration:  u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Kind {
+       Blocking,
+       Bounded,
+       Queue,
+       Mpsc,
+       Rendezvous,
+       Std,
+}
+
+impl Kind {
+       /// Most (producers, consumers) the channel supports; `None` for any number.
+       fn max_ends(self) -> (Option<usize>, Option<usize>) {
+              match self {
+                     Self::Blocking | Self::Bounded | Self::Queue => (None, None),
+                     Self::Mpsc | Self::Std => (None, Some(1)),
+                     Self::Rendezvous => (Some(1), Some(1)),
+              }
+       }
+}
+
+struct Message {
+       sent:     Instant,
+       _payload: Vec<u8>,
+}
+
+/// A producer's end of the channel; `false` once the consumers are gone.
+trait Tx: Send {
+       fn send(&self, message: Message) -> bool;
+}
+/// A consumer's end; `None` once the channel is closed and drained.
+trait Rx: Send {
+       fn recv(&self) -> Option<Message>;
+}
+
+impl Tx for Arc<channel::BlockingChannel<Message>> {
+       fn send(&self, message: Message) -> bool { channel::BlockingChannel::send(self, message).is_ok() }
+}
+impl Rx for Arc<channel::BlockingChannel<Message>> {
+       fn recv(&self) -> Option<Message> { channel::BlockingChannel::recv(self).ok() }
+}
+impl Tx for Arc<BoundedQueue<Message>> {
+       fn send(&self, message: Message) -> bool { self.push(message).is_ok() }
+}
+impl Rx for Arc<BoundedQueue<Message>> {
+       fn recv(&self) -> Option<Message> { self.pop().ok() }
+}
+impl Tx for channel::mpsc::Sender<Message> {
+       fn send(&self, message: Message) -> bool { Self::send(self, message).is_ok() }
+}
+impl Rx for channel::mpsc::Receiver<Message> {
+       fn recv(&self) -> Option<Message> { Self::recv(self).ok() }
+}
+impl Tx for channel::rendezvous::Sender<Message> {
+       fn send(&self, message: Message) -> bool { Self::send(self, message).is_ok() }
+}
+impl Rx for channel::rendezvous::Receiver<Message> {
+       fn recv(&self) -> Option<Message> { Self::recv(self).ok() }
+}
+impl Tx for std_mpsc::Sender<Message> {
+       fn send(&self, message: Message) -> bool { Self::send(self, message).is_ok() }
+}
+impl Rx for std_mpsc::Receiver<Message> {
+       fn recv(&self) -> Option<Message> { Self::recv(self).ok() }
+}
+
+/// Both ends of one channel, plus what to do once every producer is done (close it, if dropping senders doesn't).
+struct Endpoints {
+       txs:    Vec<Box<dyn Tx>>,
+       rxs:    Vec<Box<dyn Rx>>,
+       finish: Box<dyn FnOnce()>,
+       /// The `queue`, kept for its gauges.
+       queue:  Option<Arc<BoundedQueue<Message>>>,
+}
+
+impl Endpoints {
+       fn new(args: &Args) -> Self {
+              let (producers, consumers) = (args.producers, args.consumers);
+              match args.channel {
+                     Kind::Blocking | Kind::Bounded => {
+                            let channel = Arc::new(match args.channel {
+                                   Kind::Bounded => channel::BlockingChannel::bounded(args.capacity),
+                                   _ => channel::BlockingChannel::unbounded(),
+                            });
+                            Self {
+                                   txs:    (0..producers).map(|_| Box::new(channel.clone()) as Box<dyn Tx>).collect(),
+                                   rxs:    (0..consumers).map(|_| Box::new(channel.clone()) as Box<dyn Rx>).collect(),
+                                   finish: Box::new(move || channel.close()),
+                                   queue:  None,
+                            }
+                     }
+                     Kind::Queue => {
+                            let queue = Arc::new(BoundedQueue::new(args.capacity));
+                            let closer = queue.clone();
+                            Self {
+                                   txs:    (0..producers).map(|_| Box::new(queue.clone()) as Box<dyn Tx>).collect(),
+                                   rxs:    (0..consumers).map(|_| Box::new(queue.clone()) as Box<dyn Rx>).collect(),
+                                   finish: Box::new(move || closer.close()),
+                                   queue:  Some(queue),
+                            }
+                     }
+                     Kind::Mpsc => {
+                            let (sender, receiver) = channel::mpsc();
+                            Self::split((0..producers).map(|_| sender.clone()), receiver)
+                     }
+                     Kind::Rendezvous => {
+                            let (sender, receiver) = channel::rendezvous();
+                            Self::split([sender], receiver)
+                     }
+                     Kind::Std => {
+                            let (sender, receiver) = std_mpsc::channel();
+                            Self::split((0..producers).map(|_| sender.clone()), receiver)
+                     }
+              }
+       }
+
+       /// Channels that disconnect once their senders drop: nothing to do at the end.
+       fn split<T: Tx + 'static>(senders: impl IntoIterator<Item = T>, receiver: impl Rx + 'static) -> Self {
+              Self {
+                     txs:    senders.into_iter().map(|sender| Box::new(sender) as Box<dyn Tx>).collect(),
+                     rxs:    vec![Box::new(receiver)],
+                     finish: Box::new(|| ()),
+                     queue:  None,
+              }
+       }
+}
+
+fn main() {
+       let _tracing_writer_worker_guard = utilities::activate_global_default_tracing_subscriber().call().expect("tracing subscriber");
+       let args = Args::parse();
+       println!("\n-----{}-----", "Producer-Consumer".bold().purple());
+       let (max_producers, max_consumers) = args.channel.max_ends();
+       for (ends, count, max) in [("producers", args.producers, max_producers), ("consumers", args.consumers, max_consumers)] {
+              if count == 0 || max.is_some_and(|max| count > max) {
+                     let allowed = max.map_or_else(|| "1 or more".to_string(), |max| format!("at most {max}"));
+                     Args::command().error(ErrorKind::ArgumentConflict, format!("`{:?}` takes {allowed} {ends}", args.channel)).exit();
+              }
+       }
+
+       let Endpoints { txs, rxs, finish, queue } = Endpoints::new(&args);
+       let latency = Histogram::new();
+       let stop = CancellationToken::new();
+       let start = Instant::now();
+       let (sent, received) = thread::scope(|s| {
+              let (latency, stop, payload) = (&latency, &stop, args.payload);
+              let consumers: Vec<_> = rxs
+                     .into_iter()
+                     .map(|rx| {
+                            s.spawn(move || {
+                                   let mut received = 0_u64;
+                                   while let Some(message) = rx.recv() {
+                                          latency.record(message.sent.elapsed());
+                                          received += 1;
+                                   }
+                                   received
+                            })
+                     })
+                     .collect();
+              let producers: Vec<_> = txs
+                     .into_iter()
+                     .map(|tx| {
+                            s.spawn(move || {
+                                   let mut sent = 0_u64;
+                                   while !stop.is_cancelled() && tx.send(Message { sent: Instant::now(), _payload: vec![0; payload] }) {
+                                          sent += 1;
+                                   }
+                                   sent
+                            })
+                     })
+                     .collect();
+              thread::sleep(Duration::from_millis(args.duration));
+              stop.cancel();
+              let sent: u64 = sync::join_all(producers).expect("producers").into_iter().sum();
+              finish();
+              let received: u64 = sync::join_all(consumers).expect("consumers").into_iter().sum();
+              (sent, received)
+       });
+       let elapsed = start.elapsed();
+
+       println!("\n-----{}-----", format!("{:?}: {} -> {}", args.channel, args.producers, args.consumers).bold().purple());
+       println!("sent {} / received {} in {elapsed:.1?}", sent.cyan(), received.cyan());
+       println!("throughput: {} msg/s", format!("{:.0}", received as f64 / elapsed.as_secs_f64()).green());
+       println!(
+              "latency: p50 {}  p99 {}  p99.9 {}  max {}",
+              format!("{:.1?}", latency.p50()).yellow(),
+              format!("{:.1?}", latency.p99()).yellow(),
+              format!("{:.1?}", latency.p999()).yellow(),
+              format!("{:.1?}", latency.max()).red()
+       );
+       if let Some(queue) = queue {
+              let stats = queue.stats();
+              println!(
+                     "backpressure: high water {}/{}  blocked sends {} ({:.1}%)  blocked for {}",
+                     stats.high_water.cyan(),
+                     stats.capacity,
+                     stats.blocked_pushes.cyan(),
+                     100.0 * stats.blocked_ratio(),
+                     format!("{:.1?}", stats.blocked).yellow()
+              );
+       }
+}
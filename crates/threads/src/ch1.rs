@@ -0,0 +1,44 @@
+//! [Chapter 1: Basics of Rust Concurrency](https://marabos.nl/atomics/basics.html)
+
+mod closures;
+mod interior_mut;
+mod parking;
+mod scoped_threads;
+mod shared_refs;
+mod spawn_captured;
+mod threads;
+
+use clap::Subcommand;
+
+use crate::Result;
+
+#[derive(Subcommand, Debug)]
+pub enum Demo {
+       /// threads spawned, optionally joined, tracked by a `ThreadRegistry`
+       Threads(threads::Args),
+       /// scoped threads borrowing from the spawning stack frame
+       ScopedThreads,
+       /// `move` closures, and detached threads waited on with a `WaitGroup`
+       Closures,
+       /// sharing through statics, leaks and `Arc`
+       SharedRefs,
+       /// `Cell`, `RefCell`, `UnsafeCell`, `Mutex`, `RwLock`, `OnceLock`, atomics
+       InteriorMut,
+       /// thread parking, and condition variables
+       Parking,
+       /// `thread::Builder` spawns whose panics come back from `join` as errors
+       SpawnCaptured,
+}
+
+pub fn run(demo: &Demo) -> Result<()> {
+       match demo {
+              Demo::Threads(args) => threads::run(args),
+              Demo::ScopedThreads => scoped_threads::run(),
+              Demo::Closures => closures::run(),
+              Demo::SharedRefs => shared_refs::run(),
+              Demo::InteriorMut => interior_mut::run(),
+              Demo::Parking => parking::run(),
+              Demo::SpawnCaptured => return spawn_captured::run(),
+       }
+       Ok(())
+}
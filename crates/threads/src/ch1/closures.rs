@@ -10,8 +10,9 @@
 use std::thread;
 
 use owo_colors::OwoColorize;
+use sync::WaitGroup;
 
-fn main() {
+pub fn run() {
        println!("\n-----{}-----", "Thread Closures".bold().purple());
        let to_sum = Vec::from_iter(0..=1000);
        let t = thread::spawn(move || {
@@ -21,26 +22,34 @@ fn main() {
        let sum = t.join().unwrap();
        println!("The sum of 0 to 1000 is: {}", sum.green());
 
+       // the detached threads below each hold a `WaitGroup` handle, so main can wait for them without joining
+       let wait_group = WaitGroup::new();
        let numbers_1 = vec![0, 1, 2, 3, 4];
+       let task = wait_group.clone();
        thread::spawn(move || {
               for n in &numbers_1 {
                      println!("number: {}", n.green());
               }
+              drop(task);
        });
 
        let numbers_2: Vec<i32> = (20..29).collect();
+       let task = wait_group.clone();
        thread::spawn(move || {
               for n in &numbers_2 {
                      println!("number: {}", n.blue());
               }
+              drop(task);
        });
 
        let numbers_3: Vec<i32> = (30..39).collect();
+       let task = wait_group.clone();
        thread::spawn(move || {
               for n in &numbers_3 {
                      println!("number: {}", n.yellow());
               }
+              drop(task);
        });
-       // std::thread::sleep(std::time::Duration::from_millis(1));
        println!("{} from {}", "hi there".purple(), "main".blue());
+       wait_group.wait();
 }
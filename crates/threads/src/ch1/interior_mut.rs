@@ -34,7 +34,7 @@ use std::{thread, time::Duration};
 
 use owo_colors::OwoColorize as _;
 
-fn main() {
+pub fn run() {
        // Cell
        {
               use std::cell::Cell;
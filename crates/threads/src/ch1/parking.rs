@@ -10,7 +10,7 @@
 use std::{collections::VecDeque, sync::Mutex, thread, time::Duration};
 
 use owo_colors::OwoColorize;
-fn main() {
+pub fn run() {
        {
               println!("\n-----{}-----", "Thread Parking".bold().purple());
               const END_VALUE: usize = 12;
@@ -15,7 +15,7 @@ use std::thread;
 
 use owo_colors::OwoColorize;
 
-fn main() {
+pub fn run() {
        println!("\n-----{}-----", "Scoped Threads".bold().purple());
        let numbers = [0, 1, 2, 3, 4];
        // `scope(f)` takes a function with a `Scope` object as its argument.   This allows the compiler to infer the type of of `s` as `Scope`.
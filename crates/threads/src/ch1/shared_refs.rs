@@ -8,59 +8,88 @@
 //!   - **NOTE** 2: `mov(e)`ing a reference `cop(y)`ies it
 //! - ref counting
 //!   - **NOTE**: `Arc` variant is needed for our purposes (vs `Rc`)
+//! - a `WaitGroup` handle per thread, so each section waits for its threads to finish (rather than sleeping)
 
-use std::{sync::Arc, thread, time::Duration};
+use std::{sync::Arc, thread};
 
 use owo_colors::OwoColorize;
+use sync::WaitGroup;
 
-fn main() {
+pub fn run() {
        {
+              let wait_group = WaitGroup::new();
               println!("\n-----{}-----", "statics & constants for multithread use".magenta());
               // static variable initialized with const variables
               static STATOS_VAROS: [i32; 7] = [0, 1, 2, 3, 4, 5, 6];
-              thread::spawn(|| println!("STATOS_VAROS: {:?}", STATOS_VAROS));
-              thread::spawn(|| println!("STATOS_VAROS: {:?}", STATOS_VAROS));
+              for _ in 0..2 {
+                     let task = wait_group.clone();
+                     thread::spawn(move || {
+                            println!("STATOS_VAROS: {:?}", STATOS_VAROS);
+                            drop(task);
+                     });
+              }
 
               const CONSTOS_VAROS: [i32; 7] = [10, 11, 12, 13, 14, 15, 16];
-              thread::spawn(|| println!("CONSTOS_VAROS: {:?}", CONSTOS_VAROS));
-              thread::spawn(|| println!("CONSTOS_VAROS: {:?}", CONSTOS_VAROS));
+              for _ in 0..2 {
+                     let task = wait_group.clone();
+                     thread::spawn(move || {
+                            println!("CONSTOS_VAROS: {:?}", CONSTOS_VAROS);
+                            drop(task);
+                     });
+              }
+              wait_group.wait();
        }
-       thread::sleep(Duration::from_millis(100));
        {
+              let wait_group = WaitGroup::new();
               println!("\n-----{}-----", "leak for multithread use".magenta());
               // leak
               // **NOTE** 1: we need to explicitly note the type as `&'static` -- by default it is `&'static mut`, which can't be shared
               // **NOTE** 2: `mov(e)`ing a reference `cop(y)`ies it
               let vectoros = vec![20, 21, 22];
               let vectoros_leaked: &'static _ = Vec::leak(vectoros);
-              thread::spawn(move || println!("vectoros: {:?}", vectoros_leaked));
-              thread::spawn(move || println!("vectoros: {:?}", vectoros_leaked));
+              for _ in 0..2 {
+                     let task = wait_group.clone();
+                     thread::spawn(move || {
+                            println!("vectoros: {:?}", vectoros_leaked);
+                            drop(task);
+                     });
+              }
 
               let boxos = Box::new([30, 31, 32]);
               let boxos_leaked: &'static _ = Box::leak(boxos);
-              thread::spawn(move || println!("boxos_leaked: {:?}", boxos_leaked));
-              thread::spawn(move || println!("boxos_leaked: {:?}", boxos_leaked));
+              for _ in 0..2 {
+                     let task = wait_group.clone();
+                     thread::spawn(move || {
+                            println!("boxos_leaked: {:?}", boxos_leaked);
+                            drop(task);
+                     });
+              }
+              wait_group.wait();
        }
-       thread::sleep(Duration::from_millis(100));
        {
+              let wait_group = WaitGroup::new();
               println!("\n-----{}-----", "refcounting for multithread use".magenta());
               // ref counting
               // **NOTE**: `Arc` variant is needed for our purposes (vs `Rc`)
               let arc_count = Arc::new([50, 51]);
               thread::spawn({
                      let arc_count = arc_count.clone();
+                     let task = wait_group.clone();
                      move || {
                             println!("arc-count: {:?}", arc_count);
+                            drop(task);
                      }
               });
               thread::spawn({
                      let arc_count = arc_count.clone();
+                     let task = wait_group.clone();
                      move || {
                             println!("arc-count: {:?}", arc_count);
+                            drop(task);
                      }
               });
+              // wait (rather than sleep) for the other threads to run
+              wait_group.wait();
        }
-       // sleep to let other threads run
-       thread::sleep(Duration::from_millis(100));
        println!("----");
 }
@@ -0,0 +1,34 @@
+//! # Scratch code for [Rust Atomics and Locks](https://marabos.nl/atomics/)
+//!
+//! ## [Chapter 1: Basics of Rust Concurrency](https://marabos.nl/atomics/basics.html#threads)
+//!
+//! Threads spawned through `thread::Builder` (which, unlike `thread::spawn`, reports a failure to spawn),
+//! with panics captured as errors by [`SpawnExt`].
+
+use std::thread;
+
+use owo_colors::OwoColorize;
+
+use crate::{Result, spawn::SpawnExt};
+
+pub fn run() -> Result<()> {
+       println!("\n-----{}-----", "Spawn, Captured".bold().purple());
+       let first = thread::Builder::new().stack_size(1024).spawn_captured("First non-main", f)?; // Note: this spawn allows error handling unlike default thread::spawn
+       println!("{} from the {} thread.", "Hello".cyan(), "main".blue());
+       first.join()?;
+
+       // a panic comes back from `join` as an error (after the panic hook has printed it)
+       let panicky = thread::Builder::new().spawn_captured("Panicky", || panic!("on purpose"))?;
+       let error = panicky.join().expect_err("the closure always panics");
+       println!("{} {}", "Captured:".red(), error);
+
+       Ok(())
+}
+
+fn f() {
+       println!("{} from {} thread!", "Hello".cyan(), "another".green());
+       let id = thread::current().id();
+       println!("This is my thread id: {:?}", id.purple());
+       let name = thread::current().name().unwrap().to_string();
+       println!("This is my thread name: {:?}", name.purple());
+}
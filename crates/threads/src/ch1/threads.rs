@@ -12,13 +12,11 @@
 
 use std::thread;
 
-use clap::Parser;
 use owo_colors::OwoColorize;
+use sync::ThreadRegistry;
 
-/// interface for scratch code for use with [Rust Atomics and Locks](https://marabos.nl/atomics/)
-#[derive(Parser, Debug)]
-#[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "input source")]
-struct Args {
+#[derive(clap::Args, Debug)]
+pub struct Args {
        /// number of threads to spawn
        #[arg(default_value = "3")]
        threads: usize,
@@ -29,38 +27,38 @@ struct Args {
        #[arg(short, long, default_value = "0")]
        repeats: usize,
 }
-fn main() {
-       let args = Args::parse();
+pub fn run(args: &Args) {
        println!("\n-----{}-----", "Simple Threads".bold().purple());
-       dbg!(&args);
        for _ in 0..1 + args.repeats {
-              main_core(&args);
+              main_core(args);
        }
 }
 
-/// Effectively `main()`, but dropped in a function so we can easily repeat it.
+/// The demo proper, in a function so we can easily repeat it.
 ///
 /// **Note**: threads don't drop on function end as they would with `main()`-proper end.
 fn main_core(args: &Args) {
        println!("--------------------------");
+       let registry = ThreadRegistry::new();
        let mut handles = vec![];
-       for _ in 0..args.threads {
-              let h = thread::spawn(f);
+       for i in 0..args.threads {
+              let h = registry.builder(format!("simple-{i}")).spawn(f).expect("failed to spawn thread");
               handles.push(h);
        }
        println!("{} from the {} thread.", "Hello".cyan(), "main".blue());
-       if args.wait_on {
-              for h in handles {
-                     h.join().unwrap();
-              }
+       if args.wait_on
+              && let Err(errors) = sync::join_all(handles)
+       {
+              eprintln!("{}", errors.red());
        }
+       println!("{}", registry.snapshot().dimmed());
 }
 
 /// Print, then get thread id and print again with it.
 ///
 /// **Note**: `println!()` uses `std::io::Stdout::lock()`, which prevents interleaving of within-`println!()` output.
 fn f() {
-       println!("{} from {} thread!", "Hello".cyan(), "another".green());
+       println!("{} from {} thread!", "Hello".cyan(), thread::current().name().unwrap_or("another").green());
        let id = thread::current().id();
        println!("This is my thread id: {:?}", id.purple());
 }
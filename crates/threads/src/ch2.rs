@@ -0,0 +1,164 @@
+//! [Chapter 2: Atomics](https://marabos.nl/atomics/atomics.html)
+
+mod atomics;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize,
+                        Ordering::{self, AcqRel, Acquire, Relaxed, Release, SeqCst}};
+
+use clap::{Args, Subcommand, ValueEnum, error::ErrorKind::ArgumentConflict};
+use sync::traced::{TracedAtomicBool, TracedAtomicUsize};
+
+use crate::Result;
+
+#[derive(Args, Debug)]
+pub struct Ch2 {
+       /// the orderings the demo's own atomics use (`progress`'s counter is `ProgressWatcher`'s, always `Relaxed`: it refuses the others)
+       #[arg(long, global = true, value_enum, default_value_t)]
+       ordering: Model,
+       /// narrate: the demo's own atomics log every operation (`sync::traced`); best with `RUST_LOG=sync::traced` (`progress` has none)
+       #[arg(long, global = true)]
+       explain:  bool,
+       #[command(subcommand)]
+       demo:     Demo,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Demo {
+       /// load and store: a stop flag (reads commands from stdin until `stop`, or Ctrl-C)
+       StopFlag,
+       /// fetch-and-modify: 50 threads reporting progress through one counter
+       Progress,
+       /// compare-and-exchange: an increment as a CAS loop
+       CompareExchange,
+}
+
+/// A memory ordering picked on the command line, spread out to the load, store, and read-modify-write orderings
+/// it means for each kind of operation.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Model {
+       /// everything `Relaxed`
+       #[default]
+       Relaxed,
+       /// `Acquire` loads, `Release` stores, `AcqRel` read-modify-writes
+       #[value(name = "acqrel")]
+       AcqRel,
+       /// everything `SeqCst`
+       #[value(name = "seqcst")]
+       SeqCst,
+}
+
+impl Model {
+       pub fn load(self) -> Ordering {
+              match self {
+                     Self::Relaxed => Relaxed,
+                     Self::AcqRel => Acquire,
+                     Self::SeqCst => SeqCst,
+              }
+       }
+
+       pub fn store(self) -> Ordering {
+              match self {
+                     Self::Relaxed => Relaxed,
+                     Self::AcqRel => Release,
+                     Self::SeqCst => SeqCst,
+              }
+       }
+
+       pub fn rmw(self) -> Ordering {
+              match self {
+                     Self::Relaxed => Relaxed,
+                     Self::AcqRel => AcqRel,
+                     Self::SeqCst => SeqCst,
+              }
+       }
+}
+
+/// The `usize` atomic operations the demos use: std's `AtomicUsize`, or with `--explain` a `TracedAtomicUsize`.
+pub trait Counter: Send + Sync {
+       fn named(name: &'static str, value: usize) -> Self;
+       fn load(&self, order: Ordering) -> usize;
+       fn fetch_add(&self, value: usize, order: Ordering) -> usize;
+       fn compare_exchange_weak(
+              &self,
+              current: usize,
+              new: usize,
+              success: Ordering,
+              failure: Ordering,
+       ) -> std::result::Result<usize, usize>;
+}
+
+/// The `bool` atomic operations the demos use: std's `AtomicBool`, or with `--explain` a `TracedAtomicBool`.
+pub trait Flag: Send + Sync {
+       fn named(name: &'static str, value: bool) -> Self;
+       fn load(&self, order: Ordering) -> bool;
+       fn store(&self, value: bool, order: Ordering);
+       fn swap(&self, value: bool, order: Ordering) -> bool;
+}
+
+/// Forwards a trait's methods to the type's inherent ones, which have the same signatures.
+macro_rules! forward {
+       ($($fn:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+              $(fn $fn(&self, $($arg: $ty),*) -> $ret { Self::$fn(self, $($arg),*) })*
+       };
+}
+
+impl Counter for AtomicUsize {
+       forward! {
+              load(order: Ordering) -> usize;
+              fetch_add(value: usize, order: Ordering) -> usize;
+              compare_exchange_weak(current: usize, new: usize, success: Ordering, failure: Ordering) -> std::result::Result<usize, usize>;
+       }
+
+       fn named(_: &'static str, value: usize) -> Self { Self::new(value) }
+}
+
+impl Counter for TracedAtomicUsize {
+       forward! {
+              load(order: Ordering) -> usize;
+              fetch_add(value: usize, order: Ordering) -> usize;
+              compare_exchange_weak(current: usize, new: usize, success: Ordering, failure: Ordering) -> std::result::Result<usize, usize>;
+       }
+
+       fn named(name: &'static str, value: usize) -> Self { Self::new(name, value) }
+}
+
+impl Flag for AtomicBool {
+       forward! {
+              load(order: Ordering) -> bool;
+              store(value: bool, order: Ordering) -> ();
+              swap(value: bool, order: Ordering) -> bool;
+       }
+
+       fn named(_: &'static str, value: bool) -> Self { Self::new(value) }
+}
+
+impl Flag for TracedAtomicBool {
+       forward! {
+              load(order: Ordering) -> bool;
+              store(value: bool, order: Ordering) -> ();
+              swap(value: bool, order: Ordering) -> bool;
+       }
+
+       fn named(name: &'static str, value: bool) -> Self { Self::new(name, value) }
+}
+
+pub fn run(Ch2 { ordering, explain, demo }: &Ch2) -> Result<()> {
+       match (demo, explain) {
+              (Demo::StopFlag, false) => return atomics::stop_flag::<AtomicBool, AtomicUsize>(*ordering),
+              (Demo::StopFlag, true) => return atomics::stop_flag::<TracedAtomicBool, TracedAtomicUsize>(*ordering),
+              (Demo::Progress, _) if !matches!(ordering, Model::Relaxed) => clap::Error::raw(
+                     ArgumentConflict,
+                     "`progress`'s counter is `ProgressWatcher`'s, always `Relaxed`: `--ordering` can't change it\n",
+              )
+              .exit(),
+              (Demo::Progress, true) => clap::Error::raw(
+                     ArgumentConflict,
+                     "`progress`'s counter is `ProgressWatcher`'s, not one of the demo's own atomics: `--explain` has none to narrate\n",
+              )
+              .exit(),
+              (Demo::Progress, false) => return atomics::progress(),
+              (Demo::CompareExchange, false) => atomics::compare_exchange::<AtomicBool, AtomicUsize>(*ordering),
+              (Demo::CompareExchange, true) => atomics::compare_exchange::<TracedAtomicBool, TracedAtomicUsize>(*ordering),
+       }
+       Ok(())
+}
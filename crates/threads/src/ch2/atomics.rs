@@ -0,0 +1,232 @@
+//! # Scratch code for [Rust Atomics and Locks](https://marabos.nl/atomics/)
+//! ## [Chapter 2: Atomics](https://marabos.nl/atomics/atomics.html#example-stop-flag)
+//!
+//! - Load, Store
+//! - Fetch_&_Modify
+//! - Compare_&_Exchange
+
+use std::{io, sync::Arc, thread, time::Duration};
+
+use owo_colors::{OwoColorize as _, XtermColors};
+use sync::{Backoff, CancellationToken, ProgressWatcher, StatsCell, shutdown};
+use utilities::MultiProgress;
+
+use super::{Counter, Flag, Model};
+use crate::{Result,
+            error::{self, ErrKind}};
+
+/// Load, Store: a stop flag, generalized to a `CancellationToken`, plus flags the command loop and worker share
+/// (under `ordering`).
+///
+/// Stops on `stop`, or on Ctrl-C / SIGTERM through the process's shutdown token (a second Ctrl-C exits at once).
+pub fn stop_flag<F: Flag + 'static, C: Counter + 'static>(ordering: Model) -> Result<()> {
+       println!("\n-----{}-----", "Load, Store: STOP signal.".bold().purple());
+       println!("ordering: {}", format!("{ordering:?}").cyan());
+       let (reporter, mut errors) = error::collector();
+       // the STOP `AtomicBool`, generalized: clonable, with child tokens, and waitable (a futex instead of sleep-polling);
+       // a child of the shutdown token, so a signal cancels it too
+       let stop = match shutdown::on_signals() {
+              Ok(signals) => signals.child(),
+              Err(error) => {
+                     println!("No signal handling ({error}): only \"{}\" stops it", "stop".green());
+                     CancellationToken::new()
+              }
+       };
+       // more load/store: the command loop writes `paused` and reads `ticks`, the worker the other way round
+       let paused = Arc::new(F::named("paused", false));
+       let ticks = Arc::new(C::named("ticks", 0));
+       // work 'till it sees the token cancelled
+       let background_thread = thread::Builder::new().name("worker".into()).spawn({
+              let (stop, paused, ticks) = (stop.clone(), paused.clone(), ticks.clone());
+              move || {
+                     // "work" in 100ms ticks (unless paused); cancelling wakes us mid-tick
+                     while !stop.wait_cancelled_timeout(Duration::from_millis(100)) {
+                            if !paused.load(ordering.load()) {
+                                   ticks.fetch_add(1, ordering.rmw());
+                            }
+                     }
+                     println!("`{}` observed. Background thread stopping.", "cancel()".red());
+              }
+       })?;
+
+       // the command loop gets a thread of its own: a signal can't interrupt its blocking read, so it's left there
+       // (and ends with the process) if the stop comes from a signal
+       thread::Builder::new().name("commands".into()).spawn({
+              let stop = stop.clone();
+              move || {
+                     println!("Type \"{}\" for a list of commands", "help".green());
+                     for line in std::io::stdin().lines() {
+                            let line = match line {
+                                   Ok(line) => line,
+                                   Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                                          reporter.report(error); // not UTF-8: skip the line
+                                          continue;
+                                   }
+                                   Err(error) => {
+                                          reporter.fatal(error); // stdin is gone: nobody can type `stop` now
+                                          break;
+                                   }
+                            };
+                            match line.as_str() {
+                                   "help" => println!(
+                                          "Available commands: {}, {}, {}, {}, {} (or Ctrl-C)",
+                                          "help".green(),
+                                          "status".green(),
+                                          "pause".green(),
+                                          "resume".green(),
+                                          "stop".green()
+                                   ),
+                                   "status" => println!(
+                                          "ticks: {}, {}",
+                                          ticks.load(ordering.load()).cyan(),
+                                          if paused.load(ordering.load()) {
+                                                 "paused".yellow().to_string()
+                                          } else {
+                                                 "running".green().to_string()
+                                          }
+                                   ),
+                                   "pause" if paused.swap(true, ordering.rmw()) => println!("already paused"),
+                                   "resume" if !paused.swap(false, ordering.rmw()) => println!("not paused"),
+                                   "pause" | "resume" => {}
+                                   "stop" => break,
+                                   cmd => println!("Unknown command: {:?}\ntry: \"{}\"", cmd.blue(), "help".green()),
+                            }
+                     }
+                     stop.cancel(); // on `stop`, or stdin closing
+              }
+       })?;
+       stop.wait_cancelled();
+       if shutdown::signals_received() > 0 {
+              println!("{} received.", "signal".red());
+       }
+       background_thread.join().expect("the background thread doesn't panic");
+       let fatal = errors.first_fatal();
+       errors.drain().iter().for_each(|reported| println!("{}", reported.yellow()));
+       match fatal {
+              Some(reported) => Err(reported.error),
+              None => Ok(()),
+       }
+}
+
+/// Fetch-and-Modify: threads counting their progress into one atomic.
+///
+/// Each thread also counts into its own bar of a `MultiProgress`, whose render thread does all the drawing.
+pub fn progress() -> Result<()> {
+       println!("\n-----{}-----", "Fetch_&_Modify: Synchronization".bold().purple());
+       const NUM_THREADS: usize = 50;
+       const ADDS_PER_THREAD: usize = 100;
+
+       // the shared atomic `done` counter every thread `fetch_add`s into
+       let progress = ProgressWatcher::new(NUM_THREADS * ADDS_PER_THREAD);
+       let bars = MultiProgress::builder().refresh(Duration::from_millis(50)).build();
+       // every thread's gaps between its own observations of the counter: how far the others got in between
+       let diffs = &StatsCell::new();
+       let (errors, mut collected) = error::collector();
+       thread::scope(|s| {
+              // 'background thread' processing 100 items
+              for t in 0..NUM_THREADS {
+                     let reporter = progress.reporter();
+                     let bar = bars.add(format!("thread {t:>2}"), ADDS_PER_THREAD as u64);
+                     let errors = errors.clone();
+                     s.spawn(move || {
+                            let mut last_counter_value = 0;
+
+                            for _ in t..(t + ADDS_PER_THREAD) {
+                                   thread::sleep(std::time::Duration::from_millis(2)); // fake processing
+                                   // fetch_add & get current value of counter
+                                   let incoming_counter_value = reporter.inc();
+                                   bar.inc();
+
+                                   // the diff between this and our last observation of the `num_done` counter
+                                   let Some(curr_diff) = incoming_counter_value.checked_sub(last_counter_value) else {
+                                          let source_string =
+                                                 format!("counter went backwards: {last_counter_value} -> {incoming_counter_value}");
+                                          return errors.fatal(ErrKind::OtherErrorString { source_string });
+                                   };
+                                   diffs.record(curr_diff as u64);
+                                   last_counter_value = incoming_counter_value;
+                            }
+                     });
+              }
+       });
+       let summary = bars.finish();
+       let current = progress.snapshot();
+       println!("Processed {}/{} items -- {summary}", current.done.to_string().blue(), current.total);
+       if current.is_finished() {
+              println!("{}", "All items processed".green());
+       }
+       let diffs = diffs.snapshot();
+       println!(
+              "Diffs: max {}, mean {:.1} over {} observations",
+              diffs.max.unwrap_or_default().green().bold(),
+              diffs.mean().unwrap_or_default(),
+              diffs.count
+       );
+       match collected.first_fatal() {
+              Some(reported) => Err(reported.error),
+              None => Ok(()),
+       }
+}
+
+/// Compare-and-Exchange: an increment as a CAS loop, under `ordering`.
+pub fn compare_exchange<F: Flag, C: Counter>(ordering: Model) {
+       println!("\n-----{}-----", "Compare_&_Exchange: Is really odd in its use...".bold().purple());
+       println!("ordering: {}", format!("{ordering:?}").cyan());
+       /// Increments the atomic number by one using compare_exchange.
+       /// Loads, creates new value from it, then non-atomically moves to a loop.
+       /// (I'm uncertain what the advantage would be over the stricter behavior coming from a mutex.)
+       fn plus_just_one(atomic_num: &impl Counter, ordering: Model) -> (usize, usize) {
+              // back off between failed attempts rather than immediately re-hammering the contended value
+              let mut backoff = Backoff::new();
+              let mut current = atomic_num.load(ordering.load());
+              // things could change here; if so we try again
+              // **NOTE**: we're not guaranteed that no change happened between last call and next, only that value is the same.
+              loop {
+                     let new_value = current + 1;
+                     // we use `_weak` as our loop allows for "spurious failures" and the op may be more efficient (potentially platform dependent)
+                     match atomic_num.compare_exchange_weak(current, new_value, ordering.rmw(), ordering.load()) {
+                            Ok(previous_value) => {
+                                   if previous_value != current {
+                                          unreachable!("");
+                                   }
+                                   return (previous_value, new_value);
+                            }
+                            Err(observed_val) => current = observed_val,
+                     }
+                     backoff.spin();
+              }
+       }
+
+       let atomic_num = &C::named("atomic_num", 0);
+       let no_non_one_diffs = &F::named("no_non_one_diffs", true);
+       thread::scope(|s| {
+              for t in 0..10 {
+                     // named, for `--explain`'s narration
+                     let thread = thread::Builder::new().name(format!("incrementer {t}"));
+                     thread.spawn_scoped(s, move || {
+                            for _ in 0..10 {
+                                   let thread_color = XtermColors::from(t as u8);
+                                   let (previous_value, new_value) = plus_just_one(atomic_num, ordering);
+                                   let diff = new_value - previous_value;
+                                   print!(
+                                          "diff: {} ({}-{}), ",
+                                          diff.color(thread_color),
+                                          new_value.color(thread_color),
+                                          previous_value.color(thread_color)
+                                   );
+                                   if diff != 1 {
+                                          no_non_one_diffs.store(false, ordering.store());
+                                   }
+                            }
+                     })
+                     .expect("spawning a thread");
+              }
+       });
+       println!();
+       if no_non_one_diffs.load(ordering.load()) {
+              println!("{}", "All diffs were 1.".blue());
+       } else {
+              println!("{}", "Some diffs were not 1!!!".red().bold().italic());
+              unreachable!("All diffs should be 1");
+       }
+}
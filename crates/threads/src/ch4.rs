@@ -0,0 +1,17 @@
+//! [Chapter 4: Building Our Own Spin Lock](https://marabos.nl/atomics/building-spinlock.html)
+
+mod spin_lock;
+
+use clap::Subcommand;
+
+#[derive(Subcommand, Debug)]
+pub enum Demo {
+       /// `sync::SpinLock` guarding a `Vec` pushed to from two threads
+       SpinLock,
+}
+
+pub fn run(demo: &Demo) {
+       match demo {
+              Demo::SpinLock => spin_lock::run(),
+       }
+}
@@ -0,0 +1,28 @@
+//! # Scratch code for [Rust Atomics and Locks](https://marabos.nl/atomics/)
+//!
+//! ## [Chapter 4: Building Our Own Spin Lock](https://marabos.nl/atomics/building-spinlock.html#using-a-lock-guard)
+//!
+//! The chapter's closing example, on `sync::SpinLock`: two threads push onto one locked `Vec` while the main thread
+//! pushes too; the guard derefs to the `Vec` and unlocks on drop.
+//! The order of the pushes varies from run to run, the count never does.
+
+use std::thread;
+
+use owo_colors::OwoColorize;
+use sync::SpinLock;
+
+pub fn run() {
+       println!("\n-----{}-----", "Spin Lock".bold().purple());
+       let x = SpinLock::new(Vec::new());
+       thread::scope(|s| {
+              s.spawn(|| x.lock().push(1));
+              s.spawn(|| {
+                     let mut g = x.lock();
+                     g.push(2);
+                     g.push(2);
+              });
+       });
+       let g = x.lock();
+       println!("pushed: {:?}", g.as_slice().cyan());
+       assert!(g.as_slice() == [1, 2, 2] || g.as_slice() == [2, 2, 1]);
+}
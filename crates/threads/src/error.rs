@@ -1,13 +1,16 @@
 //! Error & Result type for Day07 of Advent of Code 2024.
 //!
+//! Worker threads report into a [`collector`] rather than `unwrap()`, so their failures outlive them.
+//!
 //! ## Utility reference
 //! For adding backtrace to errors:
 //! `#![feature(error_generic_member_access)]`
 //! `use std::backtrace;`
 
-use std::io;
+use std::{fmt, io, thread};
 
 use derive_more::{Display, Error};
+use sync::channel::{SendError, mpsc};
 use tracing::{instrument, subscriber::SetGlobalDefaultError};
 
 // use derive_more::{Display, Error, derive::From};
@@ -31,6 +34,12 @@ pub enum ErrKind {
        TracingSubscriber {
               source: SetGlobalDefaultError,
        },
+       #[from(ignore)] // built by `spawn::SpawnExt`, from the caught panic payload
+       #[display("thread {thread:?} panicked: {message}")]
+       ThreadPanic {
+              thread:  String,
+              message: String,
+       },
        #[from(ignore)] // use `make_dyn_error` instead; would conflict with auto-derives
        #[display("Uncategorized Error (dyn error object): {}", source)]
        OtherErrorDyn {
@@ -57,6 +66,22 @@ impl ErrKind {
        }
 }
 
+/// Lets `?` take every failure from a `sync::parallel::try_map` at once.
+impl<E> From<sync::parallel::ParallelErrors<E>> for ErrKind
+where
+       E: std::error::Error + Send + Sync + 'static,
+{
+       fn from(errors: sync::parallel::ParallelErrors<E>) -> Self { Self::into_dyn_error(errors) }
+}
+
+/// Lets `?` take every failed thread from a `sync::join_all` / `sync::try_join_all` at once.
+impl<E> From<sync::MultiError<E>> for ErrKind
+where
+       E: std::error::Error + Send + Sync + 'static,
+{
+       fn from(errors: sync::MultiError<E>) -> Self { Self::into_dyn_error(errors) }
+}
+
 #[derive(Display, Error)]
 #[display(
         "error: {:#}\n\n\nspantrace capture: {:?}\n\n\nspantrace: {:#}",
@@ -72,7 +97,6 @@ pub struct ErrWrapper {
 // Using custom display as debug so we can get SpanTrace auto printed.
 impl std::fmt::Debug for ErrWrapper {
        #[instrument(skip_all)]
-       #[expect(unused_braces)]
        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}", self) }
 }
 impl<E> From<E> for ErrWrapper
@@ -88,3 +112,76 @@ where
               }
        }
 }
+
+/// An [`ErrReporter`] to hand (cloned) to worker threads, and the [`ErrCollector`] their reports end up in.
+///
+/// In place of an `unwrap()` in a spawned thread, which takes the thread down and leaves only the panic hook's
+/// output: the thread reports the error (as an [`ErrKind`], with the spantrace where it was reported) and decides
+/// for itself whether to carry on. After joining, the owner [`drain`](ErrCollector::drain)s every report, or picks
+/// out the [`first_fatal`](ErrCollector::first_fatal) one to return with `?`.
+pub fn collector() -> (ErrReporter, ErrCollector) {
+       let (sender, receiver) = mpsc::mpsc();
+       (ErrReporter { sender }, ErrCollector { receiver, pending: Vec::new() })
+}
+
+/// One error a worker reported.
+pub struct Reported {
+       /// The reporting thread's name.
+       pub thread: String,
+       /// Reported with [`ErrReporter::fatal`]: the thread gave up.
+       pub fatal:  bool,
+       pub error:  ErrWrapper,
+}
+
+impl fmt::Display for Reported {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              let severity = if self.fatal { "fatal" } else { "error" };
+              write!(f, "{severity} in thread {:?}: {}", self.thread, self.error.source)
+       }
+}
+
+/// Worker threads' handle for reporting errors to the [`ErrCollector`]; clone one per thread.
+#[derive(Clone)]
+pub struct ErrReporter {
+       sender: mpsc::Sender<Reported>,
+}
+
+impl ErrReporter {
+       /// Report an error the thread carries on after.
+       pub fn report(&self, error: impl Into<ErrKind>) { self.send(error.into(), false); }
+
+       /// Report the error the thread is giving up on.
+       pub fn fatal(&self, error: impl Into<ErrKind>) { self.send(error.into(), true); }
+
+       fn send(&self, error: ErrKind, fatal: bool) {
+              let thread = thread::current().name().unwrap_or("<unnamed>").to_string();
+              // the spantrace is captured here, in the reporting thread
+              if let Err(SendError(unheard)) = self.sender.send(Reported { thread, fatal, error: error.into() }) {
+                     tracing::error!(%unheard, "error reported after its collector was dropped");
+              }
+       }
+}
+
+/// Where the [`ErrReporter`]s' errors collect, for the thread that spawned the workers.
+pub struct ErrCollector {
+       receiver: mpsc::Receiver<Reported>,
+       /// Received, but not yet handed out.
+       pending:  Vec<Reported>,
+}
+
+impl ErrCollector {
+       /// Every error reported so far and not yet taken, oldest first.
+       pub fn drain(&mut self) -> Vec<Reported> {
+              self.receive();
+              std::mem::take(&mut self.pending)
+       }
+
+       /// Take the earliest fatal error, leaving the rest for [`drain`](Self::drain).
+       pub fn first_fatal(&mut self) -> Option<Reported> {
+              self.receive();
+              let at = self.pending.iter().position(|reported| reported.fatal)?;
+              Some(self.pending.remove(at))
+       }
+
+       fn receive(&mut self) { self.pending.extend(std::iter::from_fn(|| self.receiver.try_recv().ok())); }
+}
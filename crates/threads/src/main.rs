@@ -1,30 +1,49 @@
 //! # Scratch code for [Rust Atomics and Locks](https://marabos.nl/atomics/)
+//!
+//! The chapter examples, one subcommand per chapter and one sub-subcommand per example:
+//! `atomics-demos ch1 threads 5 --wait-on`, `atomics-demos ch2 stop-flag --ordering acqrel`,
+//! `atomics-demos ch4 spin-lock`, … (`atomics-demos --help`, or `atomics-demos ch1 --help`, lists them).
+//!
+//! The bigger experiments (`litmus`, `philosophers`, `prodcon`, `lock-bench`, …) stay binaries of their own.
 
+mod ch1;
+mod ch2;
+mod ch4;
 mod error;
+mod spawn;
+
+use clap::{Parser, Subcommand};
 
 use crate::error::ErrWrapper;
 pub type Result<T> = std::result::Result<T, ErrWrapper>;
 
-use std::thread;
+/// interface for scratch code for use with [Rust Atomics and Locks](https://marabos.nl/atomics/)
+#[derive(Parser, Debug)]
+#[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "chapter")]
+struct Args {
+       #[command(subcommand)]
+       chapter: Chapter,
+}
 
-use owo_colors::OwoColorize;
+#[derive(Subcommand, Debug)]
+enum Chapter {
+       /// Basics of Rust Concurrency
+       #[command(subcommand)]
+       Ch1(ch1::Demo),
+       /// Atomics
+       Ch2(ch2::Ch2),
+       /// Building Our Own Spin Lock
+       #[command(subcommand)]
+       Ch4(ch4::Demo),
+}
 
 fn main() -> Result<()> {
-       thread::Builder::new()
-              .name("First non-main".into())
-              .stack_size(1024)
-              // .no_hooks()
-              .spawn(f)?; // Note: this spawn allows error handling unlike default thread::spawn
-       std::thread::sleep(std::time::Duration::from_secs(1));
-       println!("{} from the {} thread.", "Hello".cyan(), "main".blue());
-
+       let _tracing_writer_worker_guard = utilities::activate_global_default_tracing_subscriber().call()?; // for panics' spantraces
+       let args = Args::parse();
+       match &args.chapter {
+              Chapter::Ch1(demo) => ch1::run(demo)?,
+              Chapter::Ch2(ch2) => ch2::run(ch2)?,
+              Chapter::Ch4(demo) => ch4::run(demo),
+       }
        Ok(())
 }
-
-fn f() {
-       println!("{} from {} thread!", "Hello".cyan(), "another".green());
-       let id = thread::current().id();
-       println!("This is my thread id: {:?}", id.purple());
-       let name = thread::current().name().unwrap().to_string();
-       println!("This is my thread name: {:?}", name.purple());
-}
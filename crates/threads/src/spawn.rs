@@ -0,0 +1,69 @@
+//! Spawning threads whose panics come back from `join` as an [`ErrWrapper`] instead of an opaque `Box<dyn Any>`.
+//!
+//! [`SpawnExt::spawn_captured`] names the thread, runs the closure inside a span named after it
+//! (a child of the spawner's current span), and catches any panic there:
+//! the payload message, the thread name and the `SpanTrace` at the point of the panic become an
+//! [`ErrKind::ThreadPanic`] that [`CapturedHandle::join`] hands back, ready for `?`.
+//!
+//! The panic hook still runs (and prints) as usual; this only changes what `join` returns.
+
+use std::{io,
+          panic::{self, AssertUnwindSafe},
+          thread::{self, JoinHandle}};
+
+use sync::panic_message;
+use tracing::{Span, info_span};
+
+use crate::error::{ErrKind, ErrWrapper};
+
+/// Panic-capturing spawn for [`thread::Builder`].
+pub trait SpawnExt {
+       /// Spawn `f` on a thread called `name`; a panic in `f` comes back from [`join`](CapturedHandle::join) as an error.
+       ///
+       /// ## Errors
+       /// If the OS fails to create the thread.
+       fn spawn_captured<F, T>(self, name: impl Into<String>, f: F) -> io::Result<CapturedHandle<T>>
+       where
+              F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static;
+}
+
+impl SpawnExt for thread::Builder {
+       fn spawn_captured<F, T>(self, name: impl Into<String>, f: F) -> io::Result<CapturedHandle<T>>
+       where
+              F: FnOnce() -> T + Send + 'static,
+              T: Send + 'static,
+       {
+              let name = name.into();
+              let parent = Span::current();
+              let inner = self.name(name.clone()).spawn(move || {
+                     let span = info_span!(parent: &parent, "spawn_captured", thread = %name);
+                     let _entered = span.enter();
+                     // AssertUnwindSafe: nothing `f` touched is looked at again after a panic, only the payload
+                     panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+                            ErrWrapper::from(ErrKind::ThreadPanic { thread: name, message: panic_message(payload.as_ref()) })
+                     })
+              })?;
+              Ok(CapturedHandle { inner })
+       }
+}
+
+/// Owned permission to join a [`spawn_captured`](SpawnExt::spawn_captured) thread.
+#[derive(Debug)]
+pub struct CapturedHandle<T> {
+       inner: JoinHandle<Result<T, ErrWrapper>>,
+}
+
+impl<T> CapturedHandle<T> {
+       /// Wait for the thread; its panic, if any, as an [`ErrKind::ThreadPanic`].
+       ///
+       /// ## Errors
+       /// If the thread's closure panicked.
+       pub fn join(self) -> Result<T, ErrWrapper> {
+              let thread = self.inner.thread().name().unwrap_or("<unnamed>").to_string();
+              // the closure's panic is already caught; this only fails if dropping its result panicked
+              self.inner
+                     .join()
+                     .unwrap_or_else(|payload| Err(ErrKind::ThreadPanic { thread, message: panic_message(payload.as_ref()) }.into()))
+       }
+}
@@ -1,7 +1,9 @@
 //! Utility code for other Workspace Crates
 
 mod hidden_value;
+mod multi_progress;
 mod subscriber;
 
 pub use hidden_value::{HiddenValue, HiddenValueError};
+pub use multi_progress::{BarSummary, MultiProgress, ProgressBar, Summary};
 pub use subscriber::activate_global_default_tracing_subscriber;
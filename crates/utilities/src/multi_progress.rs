@@ -0,0 +1,285 @@
+//! Terminal progress bars for many threads at once, drawn by a single render thread.
+//!
+//! Workers never touch the terminal: each holds a [`ProgressBar`] (a relaxed atomic counter) and counts into it.
+//! One render thread redraws every bar at a fixed rate, in place, and a final frame plus a [`Summary`] once
+//! [`finish`](MultiProgress::finish)ed. Output stays readable however many threads are counting, and costs the workers
+//! one uncontended `fetch_add` per item rather than a write to a shared, locked stdout.
+//!
+//! Bars can be added at any time, from any thread.
+//! Drawing to stdout redraws live only on a terminal; piped, only the final frame is written.
+//!
+//! ## Example
+//! ```
+//! use std::{thread, time::Duration};
+//!
+//! use utilities::MultiProgress;
+//!
+//! let progress = MultiProgress::builder().refresh(Duration::from_millis(20)).writer(Box::new(std::io::sink())).build();
+//! thread::scope(|s| {
+//!        for t in 0..4 {
+//!               let bar = progress.add(format!("worker {t}"), 100);
+//!               s.spawn(move || (0..100).for_each(|_| bar.inc()));
+//!        }
+//! });
+//! let summary = progress.finish();
+//! assert_eq!((summary.done(), summary.total()), (400, 400));
+//! ```
+
+use std::{fmt,
+          io::{self, IsTerminal, Write},
+          sync::{Arc, Mutex, PoisonError,
+                 atomic::{AtomicBool, AtomicU64,
+                          Ordering::{Acquire, Relaxed, Release}}},
+          thread::{self, JoinHandle},
+          time::{Duration, Instant}};
+
+use bon::bon;
+
+const BAR_WIDTH: usize = 30;
+
+/// Owns the render thread; [`add`](Self::add) hands out bars, [`finish`](Self::finish) stops and summarizes.
+pub struct MultiProgress {
+       shared:   Arc<Shared>,
+       renderer: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+       bars:    Mutex<Vec<Arc<Bar>>>,
+       stop:    AtomicBool,
+       started: Instant,
+}
+
+struct Bar {
+       label: String,
+       total: u64,
+       done:  AtomicU64,
+}
+
+/// One worker's counter; cheap to clone, `Send`, and never blocks.
+#[derive(Clone)]
+pub struct ProgressBar {
+       bar: Arc<Bar>,
+}
+
+/// Final counts of every bar, and how long the whole run took.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+       pub bars:    Vec<BarSummary>,
+       pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BarSummary {
+       pub label: String,
+       pub done:  u64,
+       pub total: u64,
+}
+
+#[bon]
+impl MultiProgress {
+       /// Start the render thread, redrawing every `refresh` (default 100ms) to `writer` (default stdout).
+       #[builder]
+       pub fn new(#[builder(default = Duration::from_millis(100))] refresh: Duration, writer: Option<Box<dyn Write + Send>>) -> Self {
+              let live = writer.is_some() || io::stdout().is_terminal();
+              let writer = writer.unwrap_or_else(|| Box::new(io::stdout()));
+              let shared = Arc::new(Shared { bars: Mutex::new(Vec::new()), stop: AtomicBool::new(false), started: Instant::now() });
+              let renderer = thread::Builder::new()
+                     .name("progress-render".to_string())
+                     .spawn({
+                            let shared = shared.clone();
+                            move || render(&shared, writer, refresh, live)
+                     })
+                     .expect("spawn progress render thread");
+              Self { shared, renderer: Some(renderer) }
+       }
+}
+
+impl MultiProgress {
+       /// A new bar counting up to `total`, drawn below the existing ones.
+       pub fn add(&self, label: impl Into<String>, total: u64) -> ProgressBar {
+              let bar = Arc::new(Bar { label: label.into(), total, done: AtomicU64::new(0) });
+              self.shared.bars.lock().unwrap_or_else(PoisonError::into_inner).push(bar.clone());
+              ProgressBar { bar }
+       }
+
+       /// Stop the render thread after one last frame, and summarize.
+       pub fn finish(mut self) -> Summary {
+              self.stop_renderer();
+              let bars = self.shared.bars.lock().unwrap_or_else(PoisonError::into_inner);
+              Summary {
+                     bars:    bars.iter().map(|bar| BarSummary { label: bar.label.clone(), done: bar.done(), total: bar.total }).collect(),
+                     elapsed: self.shared.started.elapsed(),
+              }
+       }
+
+       fn stop_renderer(&mut self) {
+              if let Some(renderer) = self.renderer.take() {
+                     self.shared.stop.store(true, Release); // everything counted before `finish` makes the last frame
+                     renderer.thread().unpark();
+                     renderer.join().expect("progress render thread panicked");
+              }
+       }
+}
+
+impl Drop for MultiProgress {
+       fn drop(&mut self) { self.stop_renderer(); }
+}
+
+impl ProgressBar {
+       pub fn inc(&self) { self.inc_by(1); }
+
+       pub fn inc_by(&self, n: u64) { self.bar.done.fetch_add(n, Relaxed); }
+
+       pub fn done(&self) -> u64 { self.bar.done() }
+}
+
+impl Bar {
+       fn done(&self) -> u64 { self.done.load(Relaxed) }
+
+       fn line(&self, label_width: usize) -> String {
+              let done = self.done();
+              let fraction = if self.total == 0 { 1.0 } else { (done as f64 / self.total as f64).min(1.0) };
+              let filled = (fraction * BAR_WIDTH as f64) as usize;
+              format!(
+                     "{:<label_width$} [{}{}] {done:>8}/{:<8} {:>5.1}%",
+                     self.label,
+                     "=".repeat(filled),
+                     " ".repeat(BAR_WIDTH - filled),
+                     self.total,
+                     100.0 * fraction
+              )
+       }
+}
+
+/// The render thread: a frame every `refresh` (only if `live`), and a last one on stop.
+///
+/// Each frame moves the cursor back up over the previous one and overwrites it line by line.
+fn render(shared: &Shared, mut writer: Box<dyn Write + Send>, refresh: Duration, live: bool) {
+       let mut drawn = 0;
+       loop {
+              // read `stop` before drawing, so the frame drawn after seeing it is complete
+              let stopping = shared.stop.load(Acquire);
+              if live || stopping {
+                     drawn = draw(shared, &mut writer, drawn, live).unwrap_or(drawn); // a closed terminal isn't the workers' problem
+              }
+              if stopping {
+                     return;
+              }
+              thread::park_timeout(refresh);
+       }
+}
+
+/// Overwrite the `drawn` lines of the last frame with the current one; how many lines that was.
+/// Not `live`, there's no frame to overwrite, and no escape codes to litter a pipe with.
+fn draw(shared: &Shared, writer: &mut dyn Write, drawn: usize, live: bool) -> io::Result<usize> {
+       let bars: Vec<_> = shared.bars.lock().unwrap_or_else(PoisonError::into_inner).clone();
+       let label_width = bars.iter().map(|bar| bar.label.len()).max().unwrap_or(0);
+       let mut frame = String::new();
+       if drawn > 0 {
+              frame.push_str(&format!("\x1b[{drawn}A"));
+       }
+       for bar in &bars {
+              if live {
+                     frame.push_str("\x1b[2K");
+              }
+              frame.push_str(&bar.line(label_width));
+              frame.push('\n');
+       }
+       writer.write_all(frame.as_bytes())?;
+       writer.flush()?;
+       Ok(bars.len())
+}
+
+impl Summary {
+       /// Items counted across every bar.
+       pub fn done(&self) -> u64 { self.bars.iter().map(|bar| bar.done).sum() }
+
+       pub fn total(&self) -> u64 { self.bars.iter().map(|bar| bar.total).sum() }
+
+       /// Items per second, over the whole run.
+       pub fn rate(&self) -> f64 { self.done() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON) }
+}
+
+impl fmt::Display for Summary {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              let finished = self.bars.iter().filter(|bar| bar.done >= bar.total).count();
+              write!(
+                     f,
+                     "{}/{} items, {finished}/{} bars finished, in {:.1?} ({:.0} items/s)",
+                     self.done(),
+                     self.total(),
+                     self.bars.len(),
+                     self.elapsed,
+                     self.rate()
+              )
+       }
+}
+
+impl fmt::Debug for MultiProgress {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              let bars = self.shared.bars.lock().unwrap_or_else(PoisonError::into_inner).len();
+              f.debug_struct("MultiProgress").field("bars", &bars).finish_non_exhaustive()
+       }
+}
+
+impl fmt::Debug for ProgressBar {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              f.debug_struct("ProgressBar")
+                     .field("label", &self.bar.label)
+                     .field("done", &self.done())
+                     .field("total", &self.bar.total)
+                     .finish()
+       }
+}
+
+#[cfg(test)]
+mod tests {
+       use pretty_assertions::assert_eq;
+
+       use super::*;
+
+       /// A writer whose output the test can still read once the render thread is done with it.
+       #[derive(Clone, Default)]
+       struct Captured(Arc<Mutex<Vec<u8>>>);
+       impl Write for Captured {
+              fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                     self.0.lock().unwrap().extend_from_slice(buf);
+                     Ok(buf.len())
+              }
+
+              fn flush(&mut self) -> io::Result<()> { Ok(()) }
+       }
+       impl Captured {
+              fn text(&self) -> String { String::from_utf8(self.0.lock().unwrap().clone()).unwrap() }
+       }
+
+       #[test]
+       fn test_counts_from_many_threads() {
+              let progress = MultiProgress::builder().writer(Box::new(io::sink())).build();
+              thread::scope(|s| {
+                     for t in 0..8 {
+                            let bar = progress.add(format!("thread {t}"), 500);
+                            s.spawn(move || (0..500).for_each(|_| bar.inc()));
+                     }
+              });
+              let summary = progress.finish();
+              assert_eq!((summary.done(), summary.total(), summary.bars.len()), (4000, 4000, 8));
+              assert!(summary.bars.iter().all(|bar| bar.done == 500));
+       }
+
+       #[test]
+       fn test_final_frame_shows_every_bar() {
+              let captured = Captured::default();
+              let progress = MultiProgress::builder().refresh(Duration::from_millis(1)).writer(Box::new(captured.clone())).build();
+              let (a, b) = (progress.add("a", 10), progress.add("bee", 4));
+              a.inc_by(10);
+              b.inc();
+              thread::sleep(Duration::from_millis(20)); // some live frames
+              let summary = progress.finish();
+              let text = captured.text();
+              let last_frame = &text[text.rfind("\x1b[2A").expect("frames after the first move back up")..];
+              assert!(last_frame.contains("a   [=============================="), "{last_frame:?}");
+              assert!(last_frame.contains("100.0%") && last_frame.contains(" 25.0%"), "{last_frame:?}");
+              assert_eq!(summary.to_string().split(", in ").next(), Some("11/14 items, 1/2 bars finished"));
+       }
+}
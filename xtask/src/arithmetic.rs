@@ -0,0 +1,272 @@
+//! Integer arithmetic as a chosen type does it, overflow and all.
+//!
+//! Each integer type's `checked_`, `wrapping_`, `saturating_` and `overflowing_` methods are the same question
+//! asked four ways; `Mode` picks one, and the answer always says whether the plain operator would have overflowed
+//! (and so panicked, in a debug build).
+
+use std::{error::Error,
+          fmt,
+          ops::{BitAnd, BitOr, BitXor}};
+
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+
+use crate::types_manual::TypesManual;
+
+/// What to do when a result doesn't fit the type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+       /// No result at all (`None`)
+       #[default]
+       Checked,
+       /// Wrap around, keeping the low bits
+       Wrapping,
+       /// Stop at the type's min or max
+       Saturating,
+       /// Wrap around, and say so (`(value, true)`)
+       Overflowing,
+}
+
+/// The integer primitives, for arithmetic generic over them.
+pub trait Int:
+       Copy
+       + Default
+       + PartialEq
+       + fmt::Display
+       + fmt::LowerHex
+       + fmt::Octal
+       + fmt::Binary
+       + BitAnd<Output = Self>
+       + BitOr<Output = Self>
+       + BitXor<Output = Self>
+{
+       const BITS: u32;
+       fn from_str_radix(digits: &str, radix: u32) -> Result<Self, std::num::ParseIntError>;
+       /// As a shift amount: the low 32 bits, and whether that lost anything (a negative amount, say).
+       fn shift_amount(self) -> (u32, bool);
+       fn overflowing_add(self, rhs: Self) -> (Self, bool);
+       fn overflowing_sub(self, rhs: Self) -> (Self, bool);
+       fn overflowing_mul(self, rhs: Self) -> (Self, bool);
+       fn overflowing_div(self, rhs: Self) -> (Self, bool);
+       fn overflowing_rem(self, rhs: Self) -> (Self, bool);
+       fn overflowing_shl(self, rhs: u32) -> (Self, bool);
+       fn overflowing_shr(self, rhs: u32) -> (Self, bool);
+       fn saturating_add(self, rhs: Self) -> Self;
+       fn saturating_sub(self, rhs: Self) -> Self;
+       fn saturating_mul(self, rhs: Self) -> Self;
+       fn saturating_div(self, rhs: Self) -> Self;
+}
+
+macro_rules! impl_int {
+       ($($t:ty),*) => {$(
+              impl Int for $t {
+                     const BITS: u32 = <$t>::BITS;
+                     fn from_str_radix(digits: &str, radix: u32) -> Result<Self, std::num::ParseIntError> { <$t>::from_str_radix(digits, radix) }
+                     // `try_from` fails wherever `as` loses bits
+                     fn shift_amount(self) -> (u32, bool) { (self as u32, u32::try_from(self).is_err()) }
+                     fn overflowing_add(self, rhs: Self) -> (Self, bool) { self.overflowing_add(rhs) }
+                     fn overflowing_sub(self, rhs: Self) -> (Self, bool) { self.overflowing_sub(rhs) }
+                     fn overflowing_mul(self, rhs: Self) -> (Self, bool) { self.overflowing_mul(rhs) }
+                     fn overflowing_div(self, rhs: Self) -> (Self, bool) { self.overflowing_div(rhs) }
+                     fn overflowing_rem(self, rhs: Self) -> (Self, bool) { self.overflowing_rem(rhs) }
+                     fn overflowing_shl(self, rhs: u32) -> (Self, bool) { self.overflowing_shl(rhs) }
+                     fn overflowing_shr(self, rhs: u32) -> (Self, bool) { self.overflowing_shr(rhs) }
+                     fn saturating_add(self, rhs: Self) -> Self { self.saturating_add(rhs) }
+                     fn saturating_sub(self, rhs: Self) -> Self { self.saturating_sub(rhs) }
+                     fn saturating_mul(self, rhs: Self) -> Self { self.saturating_mul(rhs) }
+                     fn saturating_div(self, rhs: Self) -> Self { self.saturating_div(rhs) }
+              }
+       )*};
+}
+impl_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// The binary operators, each with its integer types' overflow behavior.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+       Add,
+       Sub,
+       Mul,
+       Div,
+       Rem,
+       Shl,
+       Shr,
+       And,
+       Or,
+       Xor,
+}
+
+impl Op {
+       pub fn symbol(self) -> &'static str {
+              match self {
+                     Op::Add => "+",
+                     Op::Sub => "-",
+                     Op::Mul => "*",
+                     Op::Div => "/",
+                     Op::Rem => "%",
+                     Op::Shl => "<<",
+                     Op::Shr => ">>",
+                     Op::And => "&",
+                     Op::Or => "|",
+                     Op::Xor => "^",
+              }
+       }
+
+       /// `a op b` in `mode`, and whether the plain operator would have overflowed (`None`: checked, and it did).
+       ///
+       /// Dividing by zero is an error in every mode: it isn't overflow, and nothing wraps or saturates it.
+       /// Neither is there a saturating `%`, `<<` or `>>` in `std`; those are errors only if they overflow.
+       pub fn apply<T: Int>(self, a: T, b: T, mode: Mode) -> Result<(Option<T>, bool), Box<dyn Error>> {
+              if matches!(self, Op::Div | Op::Rem) && b == T::default() {
+                     Err(format!("Error: `{a} {} 0`: division by zero.", self.symbol()))?
+              }
+              let (wrapped, overflowed) = match self {
+                     Op::Add => a.overflowing_add(b),
+                     Op::Sub => a.overflowing_sub(b),
+                     Op::Mul => a.overflowing_mul(b),
+                     Op::Div => a.overflowing_div(b),
+                     Op::Rem => a.overflowing_rem(b),
+                     Op::Shl | Op::Shr => {
+                            let (amount, lost) = b.shift_amount();
+                            let (shifted, too_far) = if self == Op::Shl { a.overflowing_shl(amount) } else { a.overflowing_shr(amount) };
+                            (shifted, lost || too_far)
+                     }
+                     Op::And => (a & b, false),
+                     Op::Or => (a | b, false),
+                     Op::Xor => (a ^ b, false),
+              };
+              let value = match mode {
+                     Mode::Checked => (!overflowed).then_some(wrapped),
+                     Mode::Wrapping | Mode::Overflowing => Some(wrapped),
+                     Mode::Saturating if !overflowed => Some(wrapped),
+                     Mode::Saturating => Some(match self {
+                            Op::Add => a.saturating_add(b),
+                            Op::Sub => a.saturating_sub(b),
+                            Op::Mul => a.saturating_mul(b),
+                            Op::Div => a.saturating_div(b),
+                            _ => Err(format!(
+                                   "Error: `{a} {} {b}` overflows, and there's no saturating `{}`.",
+                                   self.symbol(),
+                                   self.symbol()
+                            ))?,
+                     }),
+              };
+              Ok((value, overflowed))
+       }
+}
+
+/// Parse `s` as a `T`: decimal, or `0x`/`0o`/`0b` for the other bases, with an optional `-` and `_` separators.
+pub fn parse<T: Int>(s: &str) -> Result<T, Box<dyn Error>> {
+       let digits = s.replace('_', "");
+       let (sign, unsigned) = digits.strip_prefix('-').map_or(("", digits.as_str()), |rest| ("-", rest));
+       let (radix, unsigned) = [("0x", 16), ("0o", 8), ("0b", 2)]
+              .into_iter()
+              .find_map(|(prefix, radix)| unsigned.strip_prefix(prefix).map(|rest| (radix, rest)))
+              .unwrap_or((10, unsigned));
+       T::from_str_radix(&format!("{sign}{unsigned}"), radix)
+              .map_err(|e| format!("Error: `{s}` as {}: {e}", std::any::type_name::<T>()).into())
+}
+
+/// A value written out in each base, the non-decimal ones at the type's full width (two's complement, if negative).
+#[derive(Debug)]
+pub struct Bases {
+       pub dec: String,
+       pub hex: String,
+       pub oct: String,
+       pub bin: String,
+}
+
+impl Bases {
+       pub fn new<T: Int>(value: T) -> Self {
+              let bits = T::BITS as usize;
+              Self {
+                     dec: value.to_string(),
+                     hex: format!("{value:#0w$x}", w = bits / 4 + 2),
+                     oct: format!("{value:#0w$o}", w = bits.div_ceil(3) + 2),
+                     bin: format!("{value:#0w$b}", w = bits + 2),
+              }
+       }
+}
+
+/// An operation's operands and result (`None`: checked, and it overflowed), and whether it overflowed.
+#[derive(Debug)]
+pub struct Outcome {
+       pub mode:       Mode,
+       pub operands:   Vec<Bases>,
+       pub result:     Option<Bases>,
+       pub overflowed: bool,
+}
+
+/// `a + b` as a `T` would have it, in `mode`.
+fn add<T: Int>(a: &str, b: &str, mode: Mode) -> Result<Outcome, Box<dyn Error>> {
+       let (a, b) = (parse::<T>(a)?, parse::<T>(b)?);
+       let (result, overflowed) = Op::Add.apply(a, b, mode)?;
+       Ok(Outcome { mode, operands: vec![Bases::new(a), Bases::new(b)], result: result.map(Bases::new), overflowed })
+}
+
+/// `$body`, with `$t` the integer type `$ty` (a `TypesManual`) names; an error for the other types.
+macro_rules! with_int {
+       ($ty:expr, $t:ident => $body:expr) => {
+              match $ty {
+                     TypesManual::U8 => { type $t = u8; $body }
+                     TypesManual::U16 => { type $t = u16; $body }
+                     TypesManual::U32 => { type $t = u32; $body }
+                     TypesManual::U64 => { type $t = u64; $body }
+                     TypesManual::U128 => { type $t = u128; $body }
+                     TypesManual::USize => { type $t = usize; $body }
+                     TypesManual::I8 => { type $t = i8; $body }
+                     TypesManual::I16 => { type $t = i16; $body }
+                     TypesManual::I32 => { type $t = i32; $body }
+                     TypesManual::I64 => { type $t = i64; $body }
+                     TypesManual::I128 => { type $t = i128; $body }
+                     TypesManual::ISize => { type $t = isize; $body }
+                     other => Err(format!("Error: arithmetic is for the plain integer types, not {}.", other.details().name))?,
+              }
+       };
+}
+pub(crate) use with_int;
+
+impl TypesManual {
+       /// `a + b` under this type's rules.
+       pub fn add(self, a: &str, b: &str, mode: Mode) -> Result<Outcome, Box<dyn Error>> { with_int!(self, T => add::<T>(a, b, mode)) }
+}
+
+impl Outcome {
+       /// The operands (named `labels`) and result, a column each, a row per base; then whether it overflowed.
+       pub fn table(&self, labels: &[&str]) -> String {
+              let cells = |b: &Bases| [b.dec.clone(), b.hex.clone(), b.oct.clone(), b.bin.clone()];
+              let mut columns: Vec<(&str, [String; 4])> = labels.iter().copied().zip(self.operands.iter().map(cells)).collect();
+              columns.push((
+                     "result",
+                     self.result.as_ref().map_or_else(|| ["None".into(), "None".into(), "None".into(), "None".into()], cells),
+              ));
+              let widths: Vec<usize> =
+                     columns.iter().map(|(label, col)| col.iter().map(String::len).chain([label.len()]).max().unwrap_or(0)).collect();
+
+              let mut out = format!("{:<3}", "");
+              for ((label, _), w) in columns.iter().zip(&widths) {
+                     out += &format!("  {label:>w$}");
+              }
+              out.push('\n');
+              for (row, base) in ["dec", "hex", "oct", "bin"].into_iter().enumerate() {
+                     out += &format!("{}", base.cyan());
+                     for (i, ((_, col), w)) in columns.iter().zip(&widths).enumerate() {
+                            let cell = format!("{:>w$}", col[row]);
+                            match (i + 1 == columns.len(), &self.result) {
+                                   (true, None) => out += &format!("  {}", cell.red()),
+                                   (true, Some(_)) => out += &format!("  {}", cell.green().bold()),
+                                   (false, _) => out += &format!("  {cell}"),
+                            }
+                     }
+                     out.push('\n');
+              }
+              let overflow = match (self.overflowed, self.mode) {
+                     (false, _) => format!("{}", "no".green()),
+                     (true, Mode::Checked) => format!("{} (checked: no result)", "yes".red()),
+                     (true, Mode::Wrapping) => format!("{} (wrapped)", "yes".red()),
+                     (true, Mode::Saturating) => format!("{} (saturated)", "yes".red()),
+                     (true, Mode::Overflowing) => format!("{} (wrapped, and flagged: `(result, true)`)", "yes".red()),
+              };
+              out += &format!("overflow: {overflow}\n");
+              out
+       }
+}
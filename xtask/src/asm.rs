@@ -0,0 +1,222 @@
+//! `xtask asm`: the assembly rustc makes of our functions, with the atomic instructions picked out.
+//!
+//! [Chapter 7](https://marabos.nl/atomics/hardware.html) is about what each ordering costs on x86-64 and ARM64;
+//! this shows it for the code in this repo. The assembly is of a release build (a debug build's is mostly
+//! stack traffic), emitted by `cargo rustc -- --emit asm`, and for the host unless given a `--target` whose
+//! standard library is installed.
+//!
+//! A library's assembly holds only the functions it codegens itself: nothing generic and nothing `#[inline]`,
+//! which are compiled where they're used. For `SpinLock<T>::lock`, look at a binary that locks one.
+
+use std::{error::Error, fmt::Write as _, fs, path::PathBuf};
+
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+
+use crate::run_all::{cargo, target_dir};
+
+/// Which functions (and lines) to show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Filter {
+       /// Only functions with atomic instructions (or fences), and only the lines around them
+       Atomics,
+}
+
+/// What to compile: a binary, or a library.
+#[derive(Debug)]
+pub enum Unit<'a> {
+       Bin { package: &'a str, bin: &'a str },
+       Lib { package: &'a str },
+}
+
+impl Unit<'_> {
+       /// The stem of the files rustc writes for it (`prodcon-<hash>.s`, `sync-<hash>.s`).
+       fn stem(&self) -> String {
+              match self {
+                     Unit::Bin { bin: name, .. } | Unit::Lib { package: name } => name.replace('-', "_"),
+              }
+       }
+}
+
+/// Compile `unit` (release) with `--emit asm`, returning the assembly file's path.
+pub fn emit(unit: &Unit, triple: Option<&str>) -> Result<PathBuf, Box<dyn Error>> {
+       let mut rustc = cargo();
+       rustc.arg("rustc").arg("--release");
+       match unit {
+              Unit::Bin { package, bin } => rustc.args(["--package", package, "--bin", bin]),
+              Unit::Lib { package } => rustc.args(["--package", package, "--lib"]),
+       };
+       if let Some(triple) = triple {
+              rustc.args(["--target", triple]);
+       }
+       if !rustc.args(["--", "--emit", "asm"]).status()?.success() {
+              Err("Error: `cargo rustc` failed.")?
+       }
+
+       // the newest: earlier builds (other flags, other hashes) leave theirs behind
+       let deps = target_dir()?.join(triple.unwrap_or_default()).join("release").join("deps");
+       let prefix = format!("{}-", unit.stem());
+       fs::read_dir(&deps)?
+              .filter_map(Result::ok)
+              .filter(|entry| {
+                     let name = entry.file_name();
+                     let name = name.to_string_lossy();
+                     name.starts_with(&prefix) && name.ends_with(".s")
+              })
+              .max_by_key(|entry| entry.metadata().and_then(|m| m.modified()).ok())
+              .map(|entry| entry.path())
+              .ok_or_else(|| format!("Error: no `{prefix}*.s` in {}.", deps.display()).into())
+}
+
+/// A function's labels and instructions (no directives), by its demangled name.
+#[derive(Debug)]
+pub struct Function {
+       pub name:  String,
+       pub lines: Vec<String>,
+}
+
+/// The functions in `asm`, in order.
+pub fn functions(asm: &str) -> Vec<Function> {
+       let mut functions = Vec::new();
+       let mut declared = None; // from `.type NAME,@function`, until its label comes along
+       let mut current: Option<Function> = None;
+       for line in asm.lines() {
+              let trimmed = line.trim();
+              if let Some(rest) = trimmed.strip_prefix(".type")
+                     && rest.trim_end().ends_with("@function")
+              {
+                     declared = rest.split(',').next().map(|name| name.trim().to_string());
+              } else if !line.starts_with(char::is_whitespace) && trimmed.ends_with(':') {
+                     let label = &trimmed[..trimmed.len() - 1];
+                     if declared.as_deref() == Some(label) {
+                            declared = None;
+                            functions.extend(current.replace(Function { name: demangle(label), lines: Vec::new() }));
+                     } else if label.starts_with(".Lfunc_end") {
+                            functions.extend(current.take());
+                     } else if let Some(function) = &mut current
+                            && label.starts_with(".LBB")
+                     {
+                            function.lines.push(trimmed.to_string());
+                     }
+              } else if let Some(function) = &mut current
+                     && !trimmed.is_empty()
+                     && !trimmed.starts_with(['.', '#'])
+              {
+                     function.lines.push(instruction(trimmed));
+              }
+       }
+       functions.extend(current);
+       functions
+}
+
+/// `mnemonic operands`, spaced evenly (rustc separates them with tabs), with symbols demangled.
+fn instruction(line: &str) -> String {
+       let mut words = line.split_whitespace();
+       let mut mnemonic = words.next().unwrap_or_default().to_string();
+       if mnemonic == "lock" {
+              mnemonic = format!("lock {}", words.next().unwrap_or_default());
+       }
+       let operands: Vec<String> = words.map(demangle_operand).collect();
+       format!("{mnemonic:<14} {}", operands.join(" ")).trim_end().to_string()
+}
+
+fn demangle(symbol: &str) -> String { rustc_demangle::try_demangle(symbol).map_or_else(|_| symbol.to_string(), |name| format!("{name:#}")) }
+
+/// An operand with any symbol in it demangled: `_RNv...IDS+32(%rip)` to `sync::thread_local::IDS+32(%rip)`.
+fn demangle_operand(operand: &str) -> String {
+       let Some(start) = operand.find("_R").or_else(|| operand.find("_ZN")) else { return operand.to_string() };
+       let len = operand[start..]
+              .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.'))
+              .unwrap_or(operand.len() - start);
+       format!("{}{}{}", &operand[..start], demangle(&operand[start..start + len]), &operand[start + len..])
+}
+
+/// What an atomic instruction (or fence, or spin hint) is doing there; `None` for the rest.
+///
+/// x86-64 and ARM64 (both the LL/SC loops and the LSE single instructions), plus RISC-V's `amo`s, `lr`/`sc` and fences.
+pub fn annotate(instruction: &str) -> Option<&'static str> {
+       let mut words = instruction.split_whitespace();
+       let mnemonic = words.next()?;
+       let operands = words.collect::<Vec<_>>().join(" ");
+       let arm_suffixless = mnemonic.trim_end_matches(['b', 'h']);
+       Some(match mnemonic {
+              // x86-64
+              "lock" => match operands.split_whitespace().next()? {
+                     op if op.starts_with("cmpxchg") => "compare-exchange (lock-prefixed: a full barrier, whatever the ordering)",
+                     op if op.starts_with("xadd") => "fetch_add/fetch_sub (lock-prefixed: a full barrier)",
+                     _ => "read-modify-write whose old value goes unused, e.g. fetch_or (lock-prefixed: a full barrier)",
+              },
+              m if m.starts_with("xchg") && operands.split(',').any(|operand| !operand.trim().starts_with('%')) => {
+                     "swap, or a SeqCst store (xchg with memory is implicitly locked)"
+              }
+              "mfence" => "full fence: fence(SeqCst)",
+              "lfence" | "sfence" => "load/store fence (not what Rust's atomics emit: x86 orders those already)",
+              "pause" => "spin-loop hint",
+              // ARM64
+              _ if arm_suffixless == "ldar" => "load-acquire (Acquire or SeqCst load)",
+              m if m.starts_with("ldapr") => "load-acquire, RCpc (an Acquire load that needn't wait for earlier release stores)",
+              _ if arm_suffixless == "stlr" => "store-release (Release or SeqCst store)",
+              _ if arm_suffixless == "ldxr" || arm_suffixless == "ldaxr" => "load-exclusive: an LL/SC loop starts (`a`: acquire)",
+              _ if arm_suffixless == "stxr" || arm_suffixless == "stlxr" => {
+                     "store-exclusive: fails, and loops, if anyone else wrote (`l`: release)"
+              }
+              "clrex" => "abandon an exclusive monitor (an LL/SC loop giving up)",
+              m if ["cas", "swp", "ldadd", "ldclr", "ldeor", "ldset", "ldsmax", "ldsmin", "ldumax", "ldumin"]
+                     .iter()
+                     .any(|lse| m.starts_with(lse)) =>
+              {
+                     "LSE atomic read-modify-write (`a`: acquire, `l`: release, `al`: both)"
+              }
+              "dmb" if operands.starts_with("ishld") => "barrier for earlier loads: an Acquire fence",
+              "dmb" if operands.starts_with("ishst") => "barrier between stores",
+              "dmb" => "full barrier: a SeqCst (or AcqRel/Release) fence",
+              "isb" => "instruction barrier",
+              "yield" | "wfe" | "sev" => "spin-loop hint / event wait",
+              // RISC-V
+              m if m.starts_with("amo") => "atomic read-modify-write (`.aq`/`.rl`: acquire/release)",
+              m if m.starts_with("lr.") => "load-reserved: an LR/SC loop starts",
+              m if m.starts_with("sc.") => "store-conditional: fails, and loops, if anyone else wrote",
+              "fence" | "fence.tso" => "fence",
+              _ => return None,
+       })
+}
+
+/// `functions` as text, atomic lines highlighted and annotated; with `filter`, only functions with atomics,
+/// and only `context` lines either side of them.
+pub fn render(functions: &[&Function], filter: Option<Filter>, context: usize) -> String {
+       let mut out = String::new();
+       for function in functions {
+              let notes: Vec<Option<&str>> = function.lines.iter().map(|line| annotate(line)).collect();
+              let atomics = notes.iter().flatten().count();
+              if filter == Some(Filter::Atomics) && atomics == 0 {
+                     continue;
+              }
+              let instructions = function.lines.iter().filter(|line| !line.ends_with(':')).count();
+              _ = writeln!(out, "{} ({} instructions, {} atomic)", function.name.bold().purple(), instructions, atomics.yellow());
+              let shown = |i: usize| {
+                     filter.is_none() || notes[i.saturating_sub(context)..=(i + context).min(notes.len() - 1)].iter().any(Option::is_some)
+              };
+              let mut skipped = false;
+              for (i, (line, note)) in function.lines.iter().zip(&notes).enumerate() {
+                     if !shown(i) {
+                            skipped = true;
+                            continue;
+                     }
+                     if std::mem::take(&mut skipped) {
+                            _ = writeln!(out, "        {}", "…".dimmed());
+                     }
+                     match note {
+                            _ if line.ends_with(':') => _ = writeln!(out, "    {}", line.cyan()),
+                            Some(note) => {
+                                   _ = writeln!(out, "  {} {:<60} {}", "▶".yellow(), line.yellow().bold(), format!("; {note}").green())
+                            }
+                            None => _ = writeln!(out, "        {line}"),
+                     }
+              }
+              if skipped {
+                     _ = writeln!(out, "        {}", "…".dimmed());
+              }
+              out.push('\n');
+       }
+       out
+}
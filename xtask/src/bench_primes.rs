@@ -0,0 +1,104 @@
+//! `xtask bench-primes`: the `primes` crate's sieves, timed over the same ranges, side by side.
+
+use std::{error::Error, fmt::Write as _};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::timing::sampled;
+
+/// The sieves `bench-primes` can time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sieve {
+       /// a `bool` per number
+       Naive,
+       /// a bit per odd number
+       BitPacked,
+       /// a cache-sized window of flags at a time
+       Segmented,
+       /// flags only for numbers coprime to 2·3·5
+       Wheel,
+       /// segmented, on a `sync::ThreadPool`
+       Parallel,
+}
+
+impl Sieve {
+       pub const ALL: [Self; 5] = [Self::Naive, Self::BitPacked, Self::Segmented, Self::Wheel, Self::Parallel];
+
+       fn primes(self, min: usize, max: usize, threads: usize) -> Vec<usize> {
+              match self {
+                     Self::Naive => primes::prime_sieve(Some(min), max),
+                     Self::BitPacked => primes::bit_sieve(min, max),
+                     Self::Segmented => primes::primes_in(min..=max),
+                     Self::Wheel => primes::wheel_sieve(min, max),
+                     Self::Parallel => primes::parallel_sieve(min, max, threads, false, false).primes,
+              }
+       }
+}
+
+/// One sieve's times over one range.
+#[derive(Debug, Serialize)]
+pub struct BenchRow {
+       pub sieve:      Sieve,
+       pub min:        usize,
+       pub max:        usize,
+       pub primes:     usize,
+       pub fastest_ms: f64,
+       pub median_ms:  f64,
+       pub mean_ms:    f64,
+       /// Median over the first sieve's median, on the same range (below 1: faster).
+       pub relative:   f64,
+}
+
+/// Time each of `sieves` `runs` times over `min..=max`, checking they all find the same primes.
+pub fn bench_range(sieves: &[Sieve], min: usize, max: usize, threads: usize, runs: usize) -> Result<Vec<BenchRow>, Box<dyn Error>> {
+       let mut rows: Vec<BenchRow> = Vec::with_capacity(sieves.len());
+       let mut expected: Option<Vec<usize>> = None;
+       for &sieve in sieves {
+              let (primes, samples) = sampled(runs, || sieve.primes(min, max, threads));
+              match &expected {
+                     Some(expected) if *expected != primes => Err(format!(
+                            "{sieve:?} found {} primes in ({min}..={max}), {:?} found {}",
+                            primes.len(),
+                            sieves[0],
+                            expected.len()
+                     ))?,
+                     Some(_) => {}
+                     None => expected = Some(primes.clone()),
+              }
+              let median_ms = samples.median().as_secs_f64() * 1e3;
+              rows.push(BenchRow {
+                     sieve,
+                     min,
+                     max,
+                     primes: primes.len(),
+                     fastest_ms: samples.fastest().as_secs_f64() * 1e3,
+                     median_ms,
+                     mean_ms: samples.mean().as_secs_f64() * 1e3,
+                     relative: rows.first().map_or(1., |first| median_ms / first.median_ms),
+              });
+       }
+       Ok(rows)
+}
+
+/// `rows` as a markdown table.
+pub fn table(rows: &[BenchRow]) -> String {
+       let columns = ["sieve", "min", "max", "primes", "fastest ms", "median ms", "mean ms", "relative"];
+       let mut text = format!("| {} |\n|{}\n", columns.join(" | "), "---|".repeat(columns.len()));
+       for row in rows {
+              _ = writeln!(
+                     text,
+                     "| {} | {} | {} | {} | {:.3} | {:.3} | {:.3} | {:.2}x |",
+                     row.sieve.to_possible_value().expect("no skipped variants").get_name(),
+                     row.min,
+                     row.max,
+                     row.primes,
+                     row.fastest_ms,
+                     row.median_ms,
+                     row.mean_ms,
+                     row.relative
+              );
+       }
+       text
+}
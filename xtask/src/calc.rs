@@ -0,0 +1,160 @@
+//! `xtask calc`: an integer expression, evaluated as one integer type would, an operation at a time.
+//!
+//! Precedence and associativity are Rust's: unary `-` binds tightest, then `* / %`, `+ -`, `<< >>`, `&`, `^`, `|`,
+//! each left-associative. Every intermediate result is the chosen type too, so as a `u8`, `(200 + 100) / 2`
+//! overflows at the `+`, just as it would in Rust.
+
+use std::{error::Error, fmt, marker::PhantomData};
+
+use crate::{arithmetic::{Bases, Int, Mode, Op, Outcome, parse, with_int},
+            types_manual::TypesManual};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+       /// As written, prefix and separators included: only the chosen type can say whether it's valid.
+       Num(String),
+       Op(Op),
+       Open,
+       Close,
+}
+
+impl fmt::Display for Token {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              match self {
+                     Token::Num(n) => write!(f, "`{n}`"),
+                     Token::Op(op) => write!(f, "`{}`", op.symbol()),
+                     Token::Open => write!(f, "`(`"),
+                     Token::Close => write!(f, "`)`"),
+              }
+       }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+       let mut tokens = Vec::new();
+       let mut chars = expr.char_indices().peekable();
+       while let Some((at, c)) = chars.next() {
+              let token = match c {
+                     c if c.is_whitespace() => continue,
+                     '0'..='9' => {
+                            let mut end = at + 1;
+                            while let Some(&(i, c)) = chars.peek()
+                                   && (c.is_ascii_alphanumeric() || c == '_')
+                            {
+                                   end = i + 1;
+                                   chars.next();
+                            }
+                            Token::Num(expr[at..end].to_string())
+                     }
+                     '(' => Token::Open,
+                     ')' => Token::Close,
+                     '+' => Token::Op(Op::Add),
+                     '-' => Token::Op(Op::Sub),
+                     '*' => Token::Op(Op::Mul),
+                     '/' => Token::Op(Op::Div),
+                     '%' => Token::Op(Op::Rem),
+                     '&' => Token::Op(Op::And),
+                     '|' => Token::Op(Op::Or),
+                     '^' => Token::Op(Op::Xor),
+                     '<' | '>' if chars.next_if(|&(_, next)| next == c).is_some() => Token::Op(if c == '<' { Op::Shl } else { Op::Shr }),
+                     c => Err(format!("Error: unexpected `{c}` at column {}.", at + 1))?,
+              };
+              tokens.push(token);
+       }
+       Ok(tokens)
+}
+
+/// The binary operators, loosest-binding first.
+const LEVELS: [&[Op]; 6] = [&[Op::Or], &[Op::Xor], &[Op::And], &[Op::Shl, Op::Shr], &[Op::Add, Op::Sub], &[Op::Mul, Op::Div, Op::Rem]];
+
+/// Recursive descent, evaluating as it goes; `None` is a checked overflow's (lack of a) value, passed on up.
+struct Parser<'a, T> {
+       tokens:    &'a [Token],
+       pos:       usize,
+       mode:      Mode,
+       /// Each operation that overflowed, as written with its operands' values.
+       overflows: Vec<String>,
+       _type:     PhantomData<T>,
+}
+
+impl<T: Int> Parser<'_, T> {
+       fn binary(&mut self, level: usize) -> Result<Option<T>, Box<dyn Error>> {
+              let Some(ops) = LEVELS.get(level) else { return self.unary() };
+              let mut lhs = self.binary(level + 1)?;
+              while let Some(Token::Op(op)) = self.tokens.get(self.pos)
+                     && ops.contains(op)
+              {
+                     self.pos += 1;
+                     let rhs = self.binary(level + 1)?;
+                     lhs = match (lhs, rhs) {
+                            (Some(a), Some(b)) => self.step(format!("{a} {} {b}", op.symbol()), op.apply(a, b, self.mode)?),
+                            _ => None,
+                     };
+              }
+              Ok(lhs)
+       }
+
+       fn unary(&mut self) -> Result<Option<T>, Box<dyn Error>> {
+              let token = self.tokens.get(self.pos).ok_or("Error: the expression ends too soon.")?;
+              self.pos += 1;
+              match token {
+                     Token::Num(n) => Ok(Some(parse(n)?)),
+                     Token::Open => {
+                            let value = self.binary(0)?;
+                            match self.tokens.get(self.pos) {
+                                   Some(Token::Close) => {
+                                          self.pos += 1;
+                                          Ok(value)
+                                   }
+                                   Some(other) => Err(format!("Error: expected `)`, found {other}."))?,
+                                   None => Err("Error: a `(` is never closed.")?,
+                            }
+                     }
+                     Token::Op(Op::Sub) => {
+                            // a negative literal is a value in its own right (`-128` is an `i8`), unless the type has none
+                            if let Some(Token::Num(n)) = self.tokens.get(self.pos)
+                                   && let Ok(value) = parse::<T>(&format!("-{n}"))
+                            {
+                                   self.pos += 1;
+                                   return Ok(Some(value));
+                            }
+                            Ok(match self.unary()? {
+                                   Some(x) => self.step(format!("-({x})"), Op::Sub.apply(T::default(), x, self.mode)?),
+                                   None => None,
+                            })
+                     }
+                     other => Err(format!("Error: expected a number, `(` or `-`, found {other}."))?,
+              }
+       }
+
+       fn step(&mut self, written: String, (value, overflowed): (Option<T>, bool)) -> Option<T> {
+              if overflowed {
+                     self.overflows.push(written);
+              }
+              value
+       }
+}
+
+/// An expression's value and whether (and where) it overflowed.
+#[derive(Debug)]
+pub struct Calculated {
+       pub outcome:   Outcome,
+       pub overflows: Vec<String>,
+}
+
+fn calc<T: Int>(expr: &str, mode: Mode) -> Result<Calculated, Box<dyn Error>> {
+       let tokens = tokenize(expr)?;
+       let mut parser = Parser::<T> { tokens: &tokens, pos: 0, mode, overflows: Vec::new(), _type: PhantomData };
+       let value = parser.binary(0)?;
+       if let Some(extra) = tokens.get(parser.pos) {
+              Err(format!("Error: unexpected {extra} after a complete expression."))?
+       }
+       Ok(Calculated {
+              outcome:   Outcome { mode, operands: Vec::new(), result: value.map(Bases::new), overflowed: !parser.overflows.is_empty() },
+              overflows: parser.overflows,
+       })
+}
+
+impl TypesManual {
+       /// Evaluate `expr` under this type's rules.
+       pub fn calc(self, expr: &str, mode: Mode) -> Result<Calculated, Box<dyn Error>> { with_int!(self, T => calc::<T>(expr, mode)) }
+}
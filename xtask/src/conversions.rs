@@ -0,0 +1,158 @@
+//! Which numeric types convert into which: losslessly (`From`), checked (`TryFrom`), or neither.
+//!
+//! Nothing here is written out by hand: each cell asks the compiler whether the impl exists, by autoref-based
+//! specialization. `(&&&Probe::<S, D>).conversion()` finds a method on the first of `&&Probe`, `&Probe`, `Probe`
+//! (in that order) whose impl applies, and each level's impl needs a weaker bound than the one before:
+//! `D: From<S>`, then `D: TryFrom<S>`, then nothing. (This works only with concrete types, as in the macros below;
+//! in a generic function, the bounds would have to be known up front.)
+
+use std::{any::{Any, type_name},
+          marker::PhantomData,
+          num::NonZero,
+          sync::atomic::{AtomicBool, AtomicI8, AtomicI16, AtomicI32, AtomicI64, AtomicIsize, AtomicPtr, AtomicU8, AtomicU16, AtomicU32,
+                         AtomicU64, AtomicUsize}};
+
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::types_manual::TypesManual;
+
+/// How a value converts from one type to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Conversion {
+       /// `From`: every value fits.
+       Lossless,
+       /// `TryFrom` (but not `From`): some values don't fit, and say so.
+       Fallible,
+       /// Neither: between the primitive numbers (or from `char`, `bool`, or a raw pointer to an integer), only `as`
+       /// (which rounds, truncates, or saturates without a word); otherwise, nothing direct.
+       Neither,
+}
+
+/// A conversion from `S` to `D`, for the three levels below to bid on.
+struct Probe<S, D>(PhantomData<(S, D)>);
+
+trait ViaFrom {
+       fn conversion(&self) -> Conversion { Conversion::Lossless }
+}
+impl<S, D: From<S>> ViaFrom for &&Probe<S, D> {}
+
+trait ViaTryFrom {
+       fn conversion(&self) -> Conversion { Conversion::Fallible }
+}
+impl<S, D: TryFrom<S>> ViaTryFrom for &Probe<S, D> {}
+
+trait ViaNothing {
+       fn conversion(&self) -> Conversion { Conversion::Neither }
+}
+impl<S, D> ViaNothing for Probe<S, D> {}
+
+/// `$s`'s conversion to each of `$d`, by name.
+macro_rules! row {
+       ($s:ty; $($d:ty),*) => {
+              vec![$((short_name(type_name::<$d>()), (&&&Probe::<$s, $d>(PhantomData)).conversion())),*]
+       };
+}
+
+/// `$s`'s conversion to every type `TypesManual` names.
+macro_rules! conversions_from {
+       ($s:ty) => {
+              row!($s; u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64,
+                     NonZero<u8>, NonZero<u16>, NonZero<u32>, NonZero<u64>, NonZero<u128>, NonZero<usize>,
+                     NonZero<i8>, NonZero<i16>, NonZero<i32>, NonZero<i64>, NonZero<i128>, NonZero<isize>)
+       };
+}
+
+/// `core::num::nonzero::NonZero<u8>` as `NonZero<u8>`, `core::sync::atomic::Atomic<u8>` as `Atomic<u8>`.
+pub fn short_name(name: &'static str) -> &'static str {
+       ["core::num::nonzero::", "core::sync::atomic::"].iter().find_map(|path| name.strip_prefix(path)).unwrap_or(name)
+}
+
+impl TypesManual {
+       /// How this type converts to each type `TypesManual` names (itself included), in declaration order.
+       pub fn conversions(&self) -> Vec<(&'static str, Conversion)> {
+              match self {
+                     TypesManual::U8 => conversions_from!(u8),
+                     TypesManual::U16 => conversions_from!(u16),
+                     TypesManual::U32 => conversions_from!(u32),
+                     TypesManual::U64 => conversions_from!(u64),
+                     TypesManual::U128 => conversions_from!(u128),
+                     TypesManual::USize => conversions_from!(usize),
+                     TypesManual::I8 => conversions_from!(i8),
+                     TypesManual::I16 => conversions_from!(i16),
+                     TypesManual::I32 => conversions_from!(i32),
+                     TypesManual::I64 => conversions_from!(i64),
+                     TypesManual::I128 => conversions_from!(i128),
+                     TypesManual::ISize => conversions_from!(isize),
+                     TypesManual::F32 => conversions_from!(f32),
+                     TypesManual::F64 => conversions_from!(f64),
+                     TypesManual::NonZeroU8 => conversions_from!(NonZero<u8>),
+                     TypesManual::NonZeroU16 => conversions_from!(NonZero<u16>),
+                     TypesManual::NonZeroU32 => conversions_from!(NonZero<u32>),
+                     TypesManual::NonZeroU64 => conversions_from!(NonZero<u64>),
+                     TypesManual::NonZeroU128 => conversions_from!(NonZero<u128>),
+                     TypesManual::NonZeroUsize => conversions_from!(NonZero<usize>),
+                     TypesManual::NonZeroI8 => conversions_from!(NonZero<i8>),
+                     TypesManual::NonZeroI16 => conversions_from!(NonZero<i16>),
+                     TypesManual::NonZeroI32 => conversions_from!(NonZero<i32>),
+                     TypesManual::NonZeroI64 => conversions_from!(NonZero<i64>),
+                     TypesManual::NonZeroI128 => conversions_from!(NonZero<i128>),
+                     TypesManual::NonZeroIsize => conversions_from!(NonZero<isize>),
+                     TypesManual::AtomicU8 => conversions_from!(AtomicU8),
+                     TypesManual::AtomicU16 => conversions_from!(AtomicU16),
+                     TypesManual::AtomicU32 => conversions_from!(AtomicU32),
+                     TypesManual::AtomicU64 => conversions_from!(AtomicU64),
+                     TypesManual::AtomicUsize => conversions_from!(AtomicUsize),
+                     TypesManual::AtomicI8 => conversions_from!(AtomicI8),
+                     TypesManual::AtomicI16 => conversions_from!(AtomicI16),
+                     TypesManual::AtomicI32 => conversions_from!(AtomicI32),
+                     TypesManual::AtomicI64 => conversions_from!(AtomicI64),
+                     TypesManual::AtomicIsize => conversions_from!(AtomicIsize),
+                     TypesManual::AtomicBool => conversions_from!(AtomicBool),
+                     TypesManual::AtomicPtr => conversions_from!(AtomicPtr<()>),
+                     TypesManual::Char => conversions_from!(char),
+                     TypesManual::Bool => conversions_from!(bool),
+                     TypesManual::Pointer => conversions_from!(*const ()),
+                     TypesManual::Ref => conversions_from!(&u8),
+                     TypesManual::SliceRef => conversions_from!(&[u8]),
+                     TypesManual::StrRef => conversions_from!(&str),
+                     TypesManual::DynRef => conversions_from!(&dyn Any),
+              }
+       }
+}
+
+/// Whether `as` casts `from` to `to` (both as [`short_name`]s): between the primitive numbers, or from a `char`,
+/// `bool` or raw pointer to an integer.
+fn casts(from: &str, to: &str) -> bool {
+       const INTEGERS: [&str; 12] = ["u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize"];
+       let is_number = |name: &str| INTEGERS.contains(&name) || name == "f32" || name == "f64";
+       match from {
+              "char" | "bool" | "*const ()" => INTEGERS.contains(&to),
+              from => is_number(from) && is_number(to),
+       }
+}
+
+/// The conversions from `name`, grouped by kind, one line each.
+pub fn render(name: &str, conversions: &[(&'static str, Conversion)]) -> String {
+       let targets = |keep: &dyn Fn(&str, Conversion) -> bool| {
+              let targets: Vec<&str> = conversions
+                     .iter()
+                     .filter(|&&(target, conversion)| target != name && keep(target, conversion))
+                     .map(|&(target, _)| target)
+                     .collect();
+              if targets.is_empty() { "-".to_string() } else { targets.join(", ") }
+       };
+       let neither = |target: &str, conversion| conversion == Conversion::Neither && casts(name, target);
+       let nothing = |target: &str, conversion| conversion == Conversion::Neither && !casts(name, target);
+       let mut text = format!("{} {}\n", "converts from".yellow().italic(), name.bold().cyan());
+       for (label, targets) in [
+              ("losslessly (From)", targets(&|_, conversion| conversion == Conversion::Lossless).green().to_string()),
+              ("checked (TryFrom)", targets(&|_, conversion| conversion == Conversion::Fallible).yellow().to_string()),
+              ("only with `as`", targets(&neither).red().to_string()),
+              ("not directly", targets(&nothing).red().to_string()),
+       ] {
+              text += &format!(" {:<18} {targets}\n", format!("{label}:"));
+       }
+       text
+}
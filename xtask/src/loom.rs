@@ -0,0 +1,114 @@
+//! `xtask loom`: the `loom_*` model tests under `--cfg loom`, and for each one that fails, the interleaving that did it.
+//!
+//! Loom runs a model once per interleaving of its threads, up to `LOOM_MAX_PREEMPTIONS` forced switches per run
+//! (2 or 3 finds most bugs; each one more multiplies the time). The `--cfg loom` build goes to `target/loom`,
+//! so switching `RUSTFLAGS` back and forth doesn't rebuild the normal one each time.
+//!
+//! A failing test is run twice more, one test at a time: first writing each interleaving to a checkpoint file
+//! before trying it (`LOOM_CHECKPOINT_INTERVAL=1`), so the file is left holding the failing one; then starting
+//! from that checkpoint with `LOOM_LOG=trace` and `LOOM_LOCATION`, replaying it with every operation logged,
+//! and where in the source it happened. (The checkpoint needs loom's `checkpoint` feature, which `sync` enables.)
+
+use std::{env,
+          error::Error,
+          fmt::Write as _,
+          fs,
+          path::{Path, PathBuf},
+          process::Command};
+
+use owo_colors::OwoColorize;
+
+use crate::run_all::{cargo, stream, target_dir};
+
+/// How to run the loom tests.
+#[derive(Debug)]
+pub struct Options<'a> {
+       pub package:          &'a str,
+       /// Only tests whose names contain this.
+       pub filter:           &'a str,
+       pub preemption_bound: usize,
+       pub max_branches:     Option<usize>,
+}
+
+/// How the loom tests went, and where each failure's replay can be picked up again.
+#[derive(Debug, Default)]
+pub struct Report {
+       pub results: Vec<String>,
+       /// The failed tests, each with its checkpoint file (if the rerun left one).
+       pub failed:  Vec<(String, Option<PathBuf>)>,
+}
+
+impl Options<'_> {
+       /// `cargo test` of the package's library, release (loom's runs are many), under `--cfg loom`.
+       fn test(&self, dir: &Path) -> Command {
+              let rustflags = format!("{} --cfg loom", env::var("RUSTFLAGS").unwrap_or_default());
+              let mut test = cargo();
+              test.args(["test", "--package", self.package, "--release", "--lib"])
+                     .env("RUSTFLAGS", rustflags.trim())
+                     .env("CARGO_TARGET_DIR", dir)
+                     .env("LOOM_MAX_PREEMPTIONS", self.preemption_bound.to_string());
+              if let Some(branches) = self.max_branches {
+                     test.env("LOOM_MAX_BRANCHES", branches.to_string());
+              }
+              test
+       }
+}
+
+/// Run the loom tests (streaming their output); for each failure, find and replay the failing interleaving.
+pub fn run(options: &Options) -> Result<Report, Box<dyn Error>> {
+       let dir = target_dir()?.join("loom");
+       eprintln!("{} (preemption bound {}), building in {}", "RUSTFLAGS=--cfg loom".cyan(), options.preemption_bound.cyan(), dir.display());
+       let (status, lines) = stream(options.test(&dir).arg(options.filter))?;
+       let mut report =
+              Report { results: lines.iter().filter(|line| line.starts_with("test result:")).cloned().collect(), ..Report::default() };
+       let failed: Vec<&str> = lines.iter().filter_map(|line| line.strip_prefix("test ")?.strip_suffix(" ... FAILED")).collect();
+       if !status.success() && failed.is_empty() {
+              Err("Error: the loom build (or test binary) failed; see above.")?
+       }
+
+       for name in failed {
+              let checkpoint = dir.join(format!("{}.checkpoint.json", name.replace("::", "-")));
+              _ = fs::remove_file(&checkpoint);
+              eprintln!("\n{} failed; finding the interleaving...", name.red().bold());
+              options.test(&dir)
+                     .args([name, "--", "--exact"])
+                     .env("LOOM_CHECKPOINT_FILE", &checkpoint)
+                     .env("LOOM_CHECKPOINT_INTERVAL", "1")
+                     .output()?;
+              if !checkpoint.exists() {
+                     eprintln!("{}", "It passed this time (or failed before loom began), so there's nothing to replay.".yellow());
+                     report.failed.push((name.to_string(), None));
+                     continue;
+              }
+              eprintln!("{}", "Replaying it, logging every operation:".bold());
+              stream(options
+                     .test(&dir)
+                     .args([name, "--", "--exact", "--nocapture"])
+                     .env("LOOM_CHECKPOINT_FILE", &checkpoint)
+                     .env("LOOM_CHECKPOINT_INTERVAL", "1")
+                     .env("LOOM_LOG", "trace")
+                     .env("LOOM_LOCATION", "1"))?;
+              report.failed.push((name.to_string(), Some(checkpoint)));
+       }
+       Ok(report)
+}
+
+impl Report {
+       pub fn render(&self) -> String {
+              let mut out = format!("{}\n", "Loom summary".bold().purple());
+              for result in &self.results {
+                     _ = writeln!(out, "{result}");
+              }
+              for (name, checkpoint) in &self.failed {
+                     _ = writeln!(out, "{} {}", "FAILED".red().bold(), name);
+                     if let Some(checkpoint) = checkpoint {
+                            _ = writeln!(
+                                   out,
+                                   "    interleaving above; to replay it again: LOOM_CHECKPOINT_FILE={}",
+                                   checkpoint.display().cyan()
+                            );
+                     }
+              }
+              out
+       }
+}
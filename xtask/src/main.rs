@@ -14,32 +14,89 @@
 //! with similar performance and (needs-specific) utility suggests that this may be a nice
 //! future direction.  (And in said future just may or may not remain as a discoverability or unifying facade.)
 
+mod arithmetic;
+mod asm;
+mod bench_primes;
+mod calc;
+mod conversions;
+mod loom;
+mod miri;
+mod report;
+mod run_all;
+mod stress;
+mod struct_info;
+mod timing;
 mod types_manual;
 
-use std::{error::Error, result::Result};
+use std::{error::Error, fs, path::PathBuf, result::Result, thread, time::Duration};
 
 use clap::Parser;
 use owo_colors::OwoColorize;
+use primes::{Sieved, count_primes, nth_prime, parallel_sieve, prime_sieve, wheel_sieve};
 
-use crate::types_manual::*;
+use crate::{arithmetic::Mode,
+            bench_primes::Sieve,
+            report::{ConversionRow, Format, NthPrime, PrimeStats, PrimesReport, TypeReport},
+            timing::{Timed, timed},
+            types_manual::*};
+
+/// Progress notes: to stdout for `--format human`, to stderr otherwise (leaving stdout to the data).
+macro_rules! note {
+       ($format:expr, $($arg:tt)*) => {
+              if $format == Format::Human { println!($($arg)*) } else { eprintln!($($arg)*) }
+       };
+}
 
 /// xtasks, repo convenience tasks
 #[derive(Parser, Debug)]
 #[command(version, about, long_about, disable_help_subcommand = true, subcommand_help_heading = "input source")]
 enum Args {
-       /// add two numbers
+       /// Add two numbers as some integer type would: checked, wrapping, saturating or overflowing
        Add {
-              /// i32
-              a: i32,
-              /// i32
-              b: i32,
+              /// Decimal, or `0x`/`0o`/`0b` for the other bases (`_` separators are fine)
+              #[arg(allow_hyphen_values = true)]
+              a:    String,
+              /// Decimal, or `0x`/`0o`/`0b` for the other bases (`_` separators are fine)
+              #[arg(allow_hyphen_values = true)]
+              b:    String,
+              /// The integer type to add them as
+              #[arg(short, long = "type", default_value = "i32")]
+              ty:   TypesManual,
+              /// What happens if the sum doesn't fit
+              #[arg(short, long, value_enum, default_value_t)]
+              mode: Mode,
+       },
+
+       /// Evaluate an integer expression (`+ - * / % << >> & | ^`, parentheses) as some integer type would
+       Calc {
+              /// The expression: numbers in decimal, or `0x`/`0o`/`0b` for the other bases (`_` separators are fine)
+              #[arg(allow_hyphen_values = true)]
+              expr: String,
+              /// The integer type to evaluate it as (every intermediate result included)
+              #[arg(short, long = "type", default_value = "i32")]
+              ty:   TypesManual,
+              /// What happens when a result doesn't fit
+              #[arg(short, long, value_enum, default_value_t)]
+              mode: Mode,
        },
 
+       /// Report which atomic widths (and double-word CAS) this target supports
+       Atomics,
+
        /// List prime components of a rust std type
        // #[arg[(value_enum = "TypesManual")]]
        TypeInfo {
-              /// Numeric type to give information about.
-              t: TypesManual,
+              /// Type to give information about (mostly numeric).
+              t:           TypesManual,
+              /// Also draw how the type's bits are laid out, with some values in binary and hex
+              #[arg(short, long)]
+              layout:      bool,
+              /// Also list the types it converts to losslessly (`From`), checked (`TryFrom`), or neither
+              #[arg(short, long)]
+              conversions: bool,
+              /// How to write the details (`json`: all of them, whatever the other flags, bar `--conversions`)
+              #[arg(short, long, value_enum, default_value_t)]
+              format:      Format,
        },
 
        /// Calculate prime numbers in a range. (In debug mode slows down by 100 million.)
@@ -52,28 +109,225 @@ enum Args {
               /// Show all primes found
               #[arg(short, long)]
               show:         bool,
+              /// Sieve in segments across this many threads (a `sync::ThreadPool`), reporting each one's share
+              #[arg(short, long)]
+              threads:      Option<usize>,
+              /// Sieve only numbers coprime to 2·3·5, and time the plain sieve too, for comparison
+              #[arg(short, long)]
+              wheel:        bool,
+              /// How to write the results
+              #[arg(short, long, value_enum, default_value_t)]
+              format:       Format,
+              /// Write the results to this file instead of stdout (`human`: uncolored)
+              #[arg(short, long)]
+              output:       Option<PathBuf>,
+              /// Only count the primes, never collecting them (a segment's flags in memory at a time)
+              #[arg(short, long, conflicts_with = "show")]
+              count_only:   bool,
+              /// Report the gaps between primes, twin primes, and the density in each tenth of the range
+              #[arg(long, conflicts_with = "count_only")]
+              stats:        bool,
+              /// Find the Nth prime instead (the 1st is 2)
+              #[arg(long, value_name = "N", conflicts_with_all = ["primes_until", "primes_from", "show", "threads", "wheel", "count_only", "stats"])]
+              nth:          Option<usize>,
+       },
+
+       /// Time the sieves against each other over the same ranges, as a markdown table (or JSON)
+       BenchPrimes {
+              /// Upper ends of the ranges to sieve
+              #[arg(short, long, value_delimiter = ',', default_value = "1000000,10000000")]
+              max:     Vec<usize>,
+              /// Lower end of every range
+              #[arg(short = 'n', long, default_value_t = 0)]
+              min:     usize,
+              /// Sieves to time, in this order; `relative` is against the first (default: all)
+              #[arg(short, long, value_enum, value_delimiter = ',')]
+              sieves:  Vec<Sieve>,
+              /// Workers for the parallel sieve (default: available cores)
+              #[arg(short, long)]
+              threads: Option<usize>,
+              /// Timed runs per sieve and range
+              #[arg(short, long, default_value_t = 5)]
+              runs:    usize,
+              /// JSON instead of a markdown table
+              #[arg(long)]
+              json:    bool,
+       },
+
+       /// Run every binary in the `threads` crate, each with a time limit, and tabulate how they went
+       RunAll {
+              /// Seconds each run gets before it's killed
+              #[arg(short, long, default_value_t = 30)]
+              timeout:          u64,
+              /// Build and run the release binaries
+              #[arg(short, long)]
+              release:          bool,
+              /// Arguments for a binary, as `BIN=ARGS`; repeat for more runs of it (`--args 'atomics-demos=ch4 spin-lock'`)
+              #[arg(short, long = "args", value_name = "BIN=ARGS")]
+              args:             Vec<String>,
+              /// Binaries not to run at all: ones that wait on input no one will give, say
+              #[arg(short, long, value_delimiter = ',', value_name = "BIN")]
+              skip_interactive: Vec<String>,
+              /// Feed each binary this on stdin (`\n` for a newline), instead of closing it
+              #[arg(long)]
+              stdin:            Option<String>,
+              /// Print every run's full output, not just the last lines of the ones that failed
+              #[arg(long)]
+              show_output:      bool,
+       },
+
+       /// Run a `threads` binary across thread counts (and argument sets), several times each, and tabulate min/median/max
+       Stress {
+              /// The `threads` binary to run
+              bin:        String,
+              /// Thread counts to sweep
+              #[arg(short, long, value_delimiter = ',', default_value = "1,2,4,8")]
+              threads:    Vec<usize>,
+              /// How the binary takes the thread count, `{}` standing for it (`-p {} -n {}` for prodcon)
+              #[arg(long, default_value = "--threads {}", allow_hyphen_values = true)]
+              thread_arg: String,
+              /// More arguments; repeat for more sets, each run with every thread count (`--args '-c blocking'`)
+              #[arg(short, long = "args", value_name = "ARGS", allow_hyphen_values = true)]
+              args:       Vec<String>,
+              /// Runs of each combination
+              #[arg(short, long, default_value_t = 5)]
+              repeats:    usize,
+              /// Only the metrics whose names contain one of these (wall-clock time is always shown)
+              #[arg(short, long, value_delimiter = ',')]
+              metrics:    Vec<String>,
+              /// Seconds each run gets before it's killed
+              #[arg(long, default_value_t = 60)]
+              timeout:    u64,
+              /// Build and run the debug binary instead (release is what's worth timing)
+              #[arg(long)]
+              debug:      bool,
+              /// JSON instead of a markdown table
+              #[arg(long)]
+              json:       bool,
+       },
+
+       /// Show the assembly of a `threads` binary, or of the functions in a library matching a name, atomics annotated
+       Asm {
+              /// A `threads` binary (`prodcon`), or part of a function's path (`ticket_lock::TicketLock::lock`)
+              #[arg(value_name = "SYMBOL_OR_BIN")]
+              item:    String,
+              /// Only the functions (and lines) with atomic instructions
+              #[arg(short, long, value_enum)]
+              filter:  Option<asm::Filter>,
+              /// For a binary: only functions whose path contains this (`sync::`, `Mutex`)
+              #[arg(short, long)]
+              symbol:  Option<String>,
+              /// The library to look for functions in
+              #[arg(short = 'p', long = "crate", default_value = "sync")]
+              krate:   String,
+              /// Compile for this target triple instead (its standard library must be installed)
+              #[arg(long = "target", value_name = "TRIPLE")]
+              triple:  Option<String>,
+              /// With `--filter`: lines to show either side of each atomic one
+              #[arg(short = 'C', long, default_value_t = 3)]
+              context: usize,
+       },
+
+       /// Run a crate's tests under Miri (several seeds, strict provenance) and summarize what it reports
+       Miri {
+              /// The crate whose tests to run
+              #[arg(short = 'p', long = "crate", default_value = "sync")]
+              krate:                 String,
+              /// Only tests whose names contain this
+              #[arg(short, long)]
+              filter:                Option<String>,
+              /// Run each test with this many scheduling seeds (`-Zmiri-many-seeds`)
+              #[arg(short, long, default_value_t = 8)]
+              seeds:                 u32,
+              /// Allow integer-to-pointer casts, rather than strict provenance
+              #[arg(long)]
+              permissive_provenance: bool,
+              /// Tree Borrows instead of Stacked Borrows
+              #[arg(long)]
+              tree_borrows:          bool,
+              /// The (nightly) toolchain with Miri installed
+              #[arg(long, default_value = "nightly")]
+              toolchain:             String,
+       },
+
+       /// Run the loom model tests (`--cfg loom`), replaying any failure's interleaving with every operation logged
+       Loom {
+              /// The crate whose loom tests to run
+              #[arg(short = 'p', long = "crate", default_value = "sync")]
+              krate:            String,
+              /// Only tests whose names contain this
+              #[arg(short, long, default_value = "loom_")]
+              filter:           String,
+              /// Forced thread switches per interleaving (`LOOM_MAX_PREEMPTIONS`): each one more multiplies the time
+              #[arg(short = 'b', long, default_value_t = 2)]
+              preemption_bound: usize,
+              /// Branches per run before loom gives up on it (`LOOM_MAX_BRANCHES`; loom's default: 1000)
+              #[arg(long)]
+              max_branches:     Option<usize>,
+       },
+
+       /// Lay out a struct from a source file: each field's offset, size and cache line, and the padding between
+       StructInfo {
+              /// Rust source file the struct is defined in
+              path:       PathBuf,
+              /// The struct, with type arguments if it's generic (`CachePadded<u64>`)
+              name:       String,
+              /// Cache line size, in bytes
+              #[arg(short, long, default_value_t = 64)]
+              cache_line: usize,
        },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
        match Args::parse() {
-              Args::Add { a, b } => {
-                     let sum = a + b;
-                     let sum = sum.green();
-                     let a = a.red();
-                     let b = b.blue();
-                     println!("The (hex) sum of {a:>16x}  and {b:>16x} is {sum:>16x}");
-                     println!("The (dec) sum of {a:>16}  and {b:>16} is {sum:>16}");
-                     println!("The (oct) sum of {a:>16o}  and {b:>16o} is {sum:>16o}");
-                     println!("The (bin) sum of {a:>16b}  and {b:>16b} is {sum:>16b}");
+              Args::Add { a, b, ty, mode } => {
+                     let outcome = ty.add(&a, &b, mode)?;
+                     println!(
+                            "{} {} {}, as {} ({})",
+                            a.blue(),
+                            "+".bold(),
+                            b.blue(),
+                            ty.details().name.green(),
+                            format!("{mode:?}").to_lowercase().purple()
+                     );
+                     print!("{}", outcome.table(&["a", "b"]));
+              }
+              Args::Calc { expr, ty, mode } => {
+                     let calculated = ty.calc(&expr, mode)?;
+                     println!("{}, as {} ({})", expr.blue(), ty.details().name.green(), format!("{mode:?}").to_lowercase().purple());
+                     print!("{}", calculated.outcome.table(&[]));
+                     for step in &calculated.overflows {
+                            println!("  at {}", step.red());
+                     }
+              }
+              Args::Atomics => {
+                     println!("{}", "Atomic support".bold().purple());
+                     println!("{}", sync::atomic_support::report());
               }
-              Args::TypeInfo { t } => {
+              Args::TypeInfo { t, layout, conversions, format } => {
                      const MAX_PRIME_TILL: usize = 10_000_000;
-                     let t_deets = t.get_details_as_strings();
+                     let t_deets = t.details();
+                     if format != Format::Human {
+                            let conversions = conversions
+                                   .then(|| t.conversions().into_iter().map(|(to, conversion)| ConversionRow { to, conversion }).collect());
+                            print!("{}", TypeReport { details: &t_deets, conversions }.render(format)?);
+                            return Ok(());
+                     }
                      println!("{}", t_deets);
+                     if layout {
+                            print!("{}", t_deets.layout);
+                     }
+                     if conversions {
+                            print!("{}", conversions::render(conversions::short_name(t_deets.name), &t.conversions()));
+                     }
+                     if !t.is_integer() {
+                            // primes are for the integers; a float's max isn't one `usize` can hold anyway
+                            return Ok(());
+                     }
+                     let max = t_deets.fact("max").expect("every integer has a max");
                      // What follows is a bit silly (with current primes implementation, but I'll keep around for now.)
                      type TForPrimes = usize;
-                     let upper_bound = match t_deets.max.parse::<TForPrimes>() {
+                     let upper_bound = match max.parse::<TForPrimes>() {
                             Ok(n) => {
                                    if n <= MAX_PRIME_TILL {
                                           n
@@ -81,7 +335,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                                           eprintln!(
                                                  "Primes not listed.  {}'s max value ({}) will take a long time for us to calculate with the current method.",
                                                  t_deets.name.green(),
-                                                 t_deets.max.blue(),
+                                                 max.blue(),
                                           );
                                           eprintln!("We're going to skip prime calculation.");
                                           eprintln!(
@@ -94,7 +348,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                             Err(e) => Err(format!(
                                    "Error parsing {}'s max value ({}) as {}: {}",
                                    t_deets.name,
-                                   t_deets.max,
+                                   max,
                                    std::any::type_name::<TForPrimes>(),
                                    e
                             ))?,
@@ -109,68 +363,267 @@ fn main() -> Result<(), Box<dyn Error>> {
                             100. * (found_primes.len() as f32) / (upper_bound as f32 + 2.)
                      );
               }
-              Args::Primes { primes_until: primes_till, primes_from, show } => {
+              Args::Primes { primes_until: primes_till, primes_from, show, threads, wheel, format, output, count_only, stats, nth } => {
+                     if let Some(n) = nth {
+                            let Timed { value: prime, elapsed } = timed(|| nth_prime(n));
+                            let prime = prime.ok_or("Error: primes count from the 1st (which is 2); there's no 0th.")?;
+                            note!(format, "Sieved in {}", format!("{elapsed:.1?}").yellow());
+                            let report = NthPrime { n, prime };
+                            match &output {
+                                   Some(path) => {
+                                          fs::write(path, report.render(format)?)?;
+                                          note!(format, "Results written to {}", path.display().green());
+                                   }
+                                   None if format != Format::Human => print!("{}", report.render(format)?),
+                                   None => println!("Prime #{}: {}", n.blue(), prime.green().bold()),
+                            }
+                            return Ok(());
+                     }
                      const DEFAULT_PRIMES_TILL: usize = 12_345;
                      let primes_from_or_default = primes_from.unwrap_or(0);
                      let primes_till_or_default = match primes_till {
                             None => {
-                                   println!("No `{}` input given, defaulting to : {}", "primes_until".green(), DEFAULT_PRIMES_TILL.cyan());
+                                   note!(
+                                          format,
+                                          "No `{}` input given, defaulting to : {}",
+                                          "primes_until".green(),
+                                          DEFAULT_PRIMES_TILL.cyan()
+                                   );
                                    DEFAULT_PRIMES_TILL
                             }
                             Some(p) => {
-                                   println!("You requested primes up to: {}", p.blue());
+                                   note!(format, "You requested primes up to: {}", p.blue());
                                    p
                             }
                      };
-                     println!("Calculating primes from ({}..={})...", primes_from_or_default.blue(), primes_till_or_default.blue());
+                     note!(format, "Calculating primes from ({}..={})...", primes_from_or_default.blue(), primes_till_or_default.blue());
                      if primes_from_or_default > primes_till_or_default {
                             Err("Error: your minimum is larger than your maximum.  Cancelling search.")?
                      };
 
-                     let found_primes = prime_sieve(primes_from, primes_till_or_default);
-                     println!("Number of primes found <= {}: {}", primes_till_or_default.blue(), found_primes.len().green().bold());
-                     println!(
-                            "which makes the range ({}..={}) {:.1}% prime.",
-                            primes_from_or_default.blue(),
-                            primes_till_or_default.blue(),
-                            (100. * (found_primes.len() as f32) / ((primes_till_or_default - primes_from_or_default) as f32 + 2.))
-                                   .cyan()
-                                   .bold()
+                     if threads == Some(0) {
+                            Err("Error: `--threads` must be at least 1.")?
+                     }
+                     // the primes (unless only counting) and their count, and how the pool's workers did, if any
+                     let sieve = |wheel| match threads {
+                            None if count_only => Sieved {
+                                   count: count_primes(primes_from_or_default, primes_till_or_default, wheel),
+                                   ..Sieved::default()
+                            },
+                            None => {
+                                   let primes = if wheel {
+                                          wheel_sieve(primes_from_or_default, primes_till_or_default)
+                                   } else {
+                                          prime_sieve(primes_from, primes_till_or_default)
+                                   };
+                                   Sieved { count: primes.len(), primes, ..Sieved::default() }
+                            }
+                            Some(threads) => parallel_sieve(primes_from_or_default, primes_till_or_default, threads, wheel, count_only),
+                     };
+
+                     let Timed { value: found, elapsed } = timed(|| sieve(wheel));
+                     if !found.timings.is_empty() {
+                            note!(format, "{:<10} {:>9} {:>10} {:>12}", "thread", "segments", "primes", "busy");
+                            for (worker, timing) in &found.timings {
+                                   note!(
+                                          format,
+                                          "{:<10} {:>9} {:>10} {:>12}",
+                                          format!("{worker:<10}").cyan(),
+                                          timing.segments,
+                                          timing.primes,
+                                          format!("{:.1?}", timing.busy)
+                                   );
+                            }
+                     }
+                     note!(format, "Sieved in {}", format!("{elapsed:.1?}").yellow());
+                     if wheel {
+                            let Timed { value: plain, elapsed: plain_elapsed } = timed(|| sieve(false));
+                            assert_eq!(
+                                   (plain.count, &plain.primes),
+                                   (found.count, &found.primes),
+                                   "the wheel and the plain sieve disagree"
+                            );
+                            note!(
+                                   format,
+                                   "Plain sieve: {} (the wheel took {:.2}x as long, with 8/30 the flags)",
+                                   format!("{plain_elapsed:.1?}").yellow(),
+                                   (elapsed.as_secs_f64() / plain_elapsed.as_secs_f64()).cyan()
+                            );
+                     }
+                     let stats = stats.then(|| PrimeStats::new(primes_from_or_default, primes_till_or_default, &found.primes));
+                     let report = PrimesReport::new(
+                            primes_from_or_default,
+                            primes_till_or_default,
+                            found.count,
+                            show.then_some(found.primes),
+                            stats,
                      );
-                     if show {
-                            println!("{:?}", found_primes.magenta());
+                     if let Some(path) = &output {
+                            fs::write(path, report.render(format)?)?;
+                            note!(format, "Results written to {}", path.display().green());
+                     } else if format != Format::Human {
+                            print!("{}", report.render(format)?);
+                     } else {
+                            println!("Number of primes found <= {}: {}", report.max.blue(), report.count.green().bold());
+                            println!(
+                                   "which makes the range ({}..={}) {:.1}% prime.",
+                                   report.min.blue(),
+                                   report.max.blue(),
+                                   report.density.cyan().bold()
+                            );
+                            if let Some(stats) = &report.stats {
+                                   print!("{}", stats.table());
+                            }
+                            if let Some(primes) = &report.primes {
+                                   println!("{:?}", primes.magenta());
+                            }
                      }
               }
-       }
-       Ok(())
-}
-
-/// I'll be surprised if this works efficiently as a mechanical, literal, procedure.
-fn prime_sieve(min: Option<usize>, max: usize) -> Vec<usize> {
-       // buncha default yes's
-       let mut primes = vec![true; max + 1];
-       primes[0] = false;
-       primes[1] = false;
-       // no need to go past sqrt(n).floor()
-       for i in 2..=max.isqrt() {
-              // skip if index was marked as multiple of preceding num
-              if primes[i] {
-                     // first value that's not been sieved would require p >= us, which would be us
-                     let mut index = i.pow(2);
-                     // false for al p * n indices
-                     while index <= max {
-                            primes[index] = false;
-                            index += i;
+              Args::BenchPrimes { max, min, mut sieves, threads, runs, json } => {
+                     if sieves.is_empty() {
+                            sieves = Sieve::ALL.to_vec();
+                     }
+                     let threads = threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from));
+                     if threads == 0 {
+                            Err("Error: `--threads` must be at least 1.")?
+                     }
+                     eprintln!("{}", "Sieve bench".bold().purple());
+                     eprintln!("{} runs per sieve and range; parallel sieve on {} threads", runs.cyan(), threads.cyan());
+                     let mut rows = Vec::new();
+                     for &max in &max {
+                            if min > max {
+                                   Err(format!("Error: `--min` {min} is larger than `--max` {max}."))?
+                            }
+                            eprintln!("sieving ({}..={})...", min.blue(), max.blue());
+                            rows.extend(bench_primes::bench_range(&sieves, min, max, threads, runs)?);
+                     }
+                     if json {
+                            print!("{}", report::json(&rows)?);
+                     } else {
+                            print!("{}", bench_primes::table(&rows));
                      }
               }
-       }
-       let min = min.unwrap_or(0);
-       // collect unsieved bits
-       let mut result = vec![];
-       for (i, b) in primes.iter().enumerate().skip(min) {
-              if *b {
-                     result.push(i);
+              Args::RunAll { timeout, release, args, skip_interactive, stdin, show_output } => {
+                     const TAIL_LINES: usize = 10;
+                     let mut extra_runs = run_all::parse_args(&args)?;
+                     let bins = run_all::threads_bins(release)?;
+                     if let Some(unknown) = extra_runs.keys().chain(&skip_interactive).find(|bin| !bins.names.contains(bin)) {
+                            Err(format!("Error: `threads` has no binary `{unknown}` (it has: {}).", bins.names.join(", ")))?
+                     }
+                     run_all::build_threads_bins(release)?;
+                     let stdin = stdin.map(|text| text.replace("\\n", "\n"));
+                     let mut runs = Vec::new();
+                     for bin in &bins.names {
+                            for args in extra_runs.remove(bin).unwrap_or_else(|| vec![Vec::new()]) {
+                                   if skip_interactive.contains(bin) {
+                                          runs.push(run_all::Run::skipped(bin, args));
+                                          continue;
+                                   }
+                                   eprintln!("running {}...", format!("{bin} {}", args.join(" ")).trim_end().blue());
+                                   runs.push(run_all::run(&bins.dir, bin, args, stdin.as_deref(), Duration::from_secs(timeout))?);
+                            }
+                     }
+                     print!("{}", run_all::table(&runs));
+                     let failed: Vec<_> =
+                            runs.iter().filter(|run| matches!(run.status, run_all::Status::Fail | run_all::Status::Timeout)).collect();
+                     if show_output {
+                            for run in runs.iter().filter(|run| run.status != run_all::Status::Skipped) {
+                                   println!("\n### `{}`\n```\n{}{}```", run.command(), run.stdout, run.stderr);
+                            }
+                     } else {
+                            for run in &failed {
+                                   println!("\n### `{}` (last lines)\n```\n{}\n```", run.command(), run_all::tail(run, TAIL_LINES));
+                            }
+                     }
+                     if !failed.is_empty() {
+                            Err(format!("Error: {} of {} runs failed or timed out.", failed.len(), runs.len()))?
+                     }
+              }
+              Args::Stress { bin, threads, thread_arg, args, repeats, metrics, timeout, debug, json } => {
+                     const TAIL_LINES: usize = 10;
+                     if threads.contains(&0) || repeats == 0 {
+                            Err("Error: `--threads` and `--repeats` must be at least 1.")?
+                     }
+                     let bins = run_all::threads_bins(!debug)?;
+                     if !bins.names.contains(&bin) {
+                            Err(format!("Error: `threads` has no binary `{bin}` (it has: {}).", bins.names.join(", ")))?
+                     }
+                     let cases = stress::cases(&threads, &thread_arg, &args)?;
+                     run_all::build_threads_bins(!debug)?;
+                     eprintln!("{}", "Stress".bold().purple());
+                     eprintln!("{} combinations, {} runs each", cases.len().cyan(), repeats.cyan());
+                     let runs = stress::sweep(&bins.dir, &bin, &cases, repeats, Duration::from_secs(timeout))?;
+                     let rows = stress::rows(&bin, &cases, &runs, &metrics);
+                     if json {
+                            print!("{}", report::json(&rows)?);
+                     } else {
+                            print!("{}", stress::table(&rows));
+                     }
+                     // the first failure of each combination
+                     let failed: Vec<_> =
+                            runs.iter().filter_map(|runs| runs.iter().find(|run| run.status != run_all::Status::Pass)).collect();
+                     for run in &failed {
+                            eprintln!(
+                                   "\n### `{}` ({:?}, last lines)\n```\n{}\n```",
+                                   run.command(),
+                                   run.status,
+                                   run_all::tail(run, TAIL_LINES)
+                            );
+                     }
+                     if !failed.is_empty() {
+                            Err(format!("Error: {} of {} combinations had runs that failed or timed out.", failed.len(), cases.len()))?
+                     }
+              }
+              Args::Asm { item, filter, symbol, krate, triple, context } => {
+                     let bins = run_all::threads_bins(true)?;
+                     let (unit, symbol) = if bins.names.contains(&item) {
+                            (asm::Unit::Bin { package: "threads", bin: &item }, symbol.as_deref())
+                     } else {
+                            (asm::Unit::Lib { package: &krate }, Some(item.as_str()))
+                     };
+                     let path = asm::emit(&unit, triple.as_deref())?;
+                     let functions = asm::functions(&fs::read_to_string(&path)?);
+                     let matching: Vec<_> = functions.iter().filter(|f| symbol.is_none_or(|symbol| f.name.contains(symbol))).collect();
+                     if matching.is_empty() {
+                            Err(format!(
+                                   "Error: no function in {} matches `{}`. (Generic and `#[inline]` functions are compiled where \
+                                    they're used: try a binary that calls it.)",
+                                   path.display(),
+                                   symbol.unwrap_or_default()
+                            ))?
+                     }
+                     print!("{}", asm::render(&matching, filter, context));
+                     eprintln!("{} of {} functions, from {}", matching.len().cyan(), functions.len(), path.display().green());
+              }
+              Args::Miri { krate, filter, seeds, permissive_provenance, tree_borrows, toolchain } => {
+                     let options = miri::Options {
+                            toolchain: &toolchain,
+                            package: &krate,
+                            filter: filter.as_deref(),
+                            seeds,
+                            permissive_provenance,
+                            tree_borrows,
+                     };
+                     let (passed, summary) = miri::run(&options)?;
+                     print!("{}", summary.render());
+                     if !passed {
+                            Err("Error: Miri run failed.")?
+                     }
+              }
+              Args::Loom { krate, filter, preemption_bound, max_branches } => {
+                     let report = loom::run(&loom::Options { package: &krate, filter: &filter, preemption_bound, max_branches })?;
+                     print!("{}", report.render());
+                     if !report.failed.is_empty() {
+                            Err(format!("Error: {} loom test(s) failed.", report.failed.len()))?
+                     }
+              }
+              Args::StructInfo { path, name, cache_line } => {
+                     if cache_line == 0 {
+                            Err("Error: `--cache-line` must be at least 1.")?
+                     }
+                     let layout = struct_info::struct_layout(&path, &name)?;
+                     print!("{}", struct_info::table(&layout, cache_line));
               }
        }
-       result
+       Ok(())
 }
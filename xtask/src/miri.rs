@@ -0,0 +1,165 @@
+//! `xtask miri`: a crate's tests under Miri, with the flags that catch the most, and a summary of what it found.
+//!
+//! Miri runs each test under several scheduling seeds (`-Zmiri-many-seeds`: data races and weak-memory
+//! outcomes depend on the interleaving) and with strict provenance (`-Zmiri-strict-provenance`: no
+//! integer-to-pointer casts; a tagged pointer keeps its provenance with `map_addr`). Whatever `MIRIFLAGS` the
+//! environment has go after ours, so they win.
+//!
+//! The tests keep their counts small under `cfg(miri)`; interpreting is some thousand times slower.
+
+use std::{collections::BTreeMap, error::Error, fmt::Write as _, process::Command};
+
+use owo_colors::OwoColorize;
+
+use crate::run_all::stream;
+
+/// How to run Miri.
+#[derive(Debug)]
+pub struct Options<'a> {
+       pub toolchain:             &'a str,
+       pub package:               &'a str,
+       pub filter:                Option<&'a str>,
+       /// Run every test with seeds `0..seeds` (1: just the one run).
+       pub seeds:                 u32,
+       /// Allow integer-to-pointer casts (with a warning each) instead of rejecting them.
+       pub permissive_provenance: bool,
+       /// Check references with Tree Borrows rather than Stacked Borrows.
+       pub tree_borrows:          bool,
+}
+
+impl Options<'_> {
+       pub fn miriflags(&self) -> String {
+              let mut flags =
+                     vec![if self.permissive_provenance { "-Zmiri-permissive-provenance" } else { "-Zmiri-strict-provenance" }.to_string()];
+              if self.seeds > 1 {
+                     flags.push(format!("-Zmiri-many-seeds=0..{}", self.seeds));
+              }
+              if self.tree_borrows {
+                     flags.push("-Zmiri-tree-borrows".into());
+              }
+              flags.extend(std::env::var("MIRIFLAGS").ok().filter(|extra| !extra.trim().is_empty()));
+              flags.join(" ")
+       }
+}
+
+/// `cargo +toolchain ...`: the rustup proxy, not the `cargo` running us (which would ignore the `+toolchain`),
+/// and without the `RUSTC` it may have set.
+pub fn cargo_with(toolchain: &str) -> Command {
+       let mut cargo = Command::new("cargo");
+       cargo.arg(format!("+{toolchain}")).env_remove("RUSTC").env_remove("RUSTUP_TOOLCHAIN");
+       cargo
+}
+
+/// Run the tests under Miri (streaming its output), then summarize; whether they passed, and the summary.
+pub fn run(options: &Options) -> Result<(bool, Summary), Box<dyn Error>> {
+       let installed = cargo_with(options.toolchain).args(["miri", "--version"]).output();
+       if !installed.is_ok_and(|output| output.status.success()) {
+              Err(format!(
+                     "Error: no Miri for the `{0}` toolchain. (`rustup toolchain install {0} --component miri`, or \
+                      `rustup +{0} component add miri`)",
+                     options.toolchain
+              ))?
+       }
+       let miriflags = options.miriflags();
+       eprintln!("MIRIFLAGS={}", miriflags.cyan());
+       let mut test = cargo_with(options.toolchain);
+       test.args(["miri", "test", "--package", options.package]).env("MIRIFLAGS", &miriflags);
+       if let Some(filter) = options.filter {
+              test.arg(filter);
+       }
+       let (status, lines) = stream(&mut test)?;
+       Ok((status.success(), Summary::new(&lines)))
+}
+
+/// One thing Miri reported.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+       /// `Undefined Behavior`, `memory leaked`, `deadlock`, ... (the text before the first `:`), or `error`.
+       pub kind:     String,
+       pub message:  String,
+       /// `path:line:column`, when Miri gave one.
+       pub location: Option<String>,
+       /// The test that was running.
+       pub test:     Option<String>,
+}
+
+/// What a Miri run's output comes to.
+#[derive(Debug, Default)]
+pub struct Summary {
+       /// Each distinct diagnostic, and how many times (seeds, mostly) it came up.
+       pub diagnostics:   BTreeMap<Diagnostic, usize>,
+       pub failing_seeds: Vec<String>,
+       /// `test result:` lines, and how many times each (once per seed, for the same result).
+       pub results:       BTreeMap<String, usize>,
+}
+
+/// Miri's kinds of error, as it starts their messages.
+const KINDS: [&str; 6] =
+       ["Undefined Behavior", "unsupported operation", "memory leaked", "deadlock", "abnormal termination", "resource exhaustion"];
+
+impl Summary {
+       pub fn new(lines: &[String]) -> Self {
+              let mut summary = Self::default();
+              let mut test = None;
+              for (i, line) in lines.iter().enumerate() {
+                     if let Some(rest) = line.strip_prefix("test ")
+                            && let Some((name, outcome)) = rest.split_once(" ... ")
+                     {
+                            // a test that Miri stops doesn't get its `ok`
+                            test = (!outcome.starts_with("ok") && !outcome.starts_with("ignored")).then(|| name.to_string());
+                     } else if line.starts_with("test result:") {
+                            *summary.results.entry(line.clone()).or_default() += 1;
+                     } else if line.to_lowercase().contains("failing seed") {
+                            summary.failing_seeds.push(line.trim().to_string());
+                     } else if let Some(message) = line.strip_prefix("error: ") {
+                            // cargo's own, not Miri's
+                            if ["test failed", "could not compile", "aborting due to"].iter().any(|cargo| message.starts_with(cargo)) {
+                                   continue;
+                            }
+                            let (kind, message) = KINDS
+                                   .iter()
+                                   .find_map(|kind| {
+                                          message.strip_prefix(kind)
+                                                 .map(|rest| (kind.to_string(), rest.trim_start_matches(':').trim().to_string()))
+                                   })
+                                   .unwrap_or_else(|| ("error".into(), message.to_string()));
+                            let location = lines[i + 1..]
+                                   .iter()
+                                   .take(3)
+                                   .find_map(|next| next.trim_start().strip_prefix("--> "))
+                                   .map(|location| location.trim().to_string());
+                            *summary.diagnostics.entry(Diagnostic { kind, message, location, test: test.clone() }).or_default() += 1;
+                     }
+              }
+              summary
+       }
+
+       pub fn render(&self) -> String {
+              let mut out = format!("{}\n", "Miri summary".bold().purple());
+              for (result, times) in &self.results {
+                     _ = writeln!(out, "{result}{}", if *times > 1 { format!(" (x{times})") } else { String::new() });
+              }
+              if self.diagnostics.is_empty() {
+                     _ = writeln!(out, "{}", "no diagnostics".green());
+              }
+              for (diagnostic, times) in &self.diagnostics {
+                     _ = writeln!(
+                            out,
+                            "{}: {}{}",
+                            diagnostic.kind.red().bold(),
+                            diagnostic.message,
+                            if *times > 1 { format!(" (x{times})") } else { String::new() }
+                     );
+                     if let Some(test) = &diagnostic.test {
+                            _ = writeln!(out, "    in {}", test.yellow());
+                     }
+                     if let Some(location) = &diagnostic.location {
+                            _ = writeln!(out, "    at {}", location.cyan());
+                     }
+              }
+              for seed in &self.failing_seeds {
+                     _ = writeln!(out, "{}", seed.red());
+              }
+              out
+       }
+}
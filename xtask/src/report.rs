@@ -0,0 +1,249 @@
+//! `xtask primes` and `xtask type-info` results as data: for scripts, rather than the colored terminal text.
+
+use std::{error::Error, fmt::Write as _};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{conversions::Conversion, types_manual::TypeDetails};
+
+/// How `xtask primes` and `xtask type-info` write their results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+       /// colored text, with progress notes
+       #[default]
+       Human,
+       /// one object: the summary, `primes` with `--show`, and `stats` with `--stats` (`--nth`: `n` and `prime`;
+       /// `type-info`: the details, with `conversions` if asked for)
+       Json,
+       /// a header and one summary row (`--stats` adds the gap and twin columns, but not the deciles); with `--show`,
+       /// a `prime` column instead, one prime per row (`--nth`: `n,prime`)
+       Csv,
+}
+
+/// What a `primes` run found.
+#[derive(Debug, Serialize)]
+pub struct PrimesReport {
+       pub min:     usize,
+       pub max:     usize,
+       pub count:   usize,
+       /// Percent of the numbers in `min..=max` that are prime.
+       pub density: f64,
+       #[serde(skip_serializing_if = "Option::is_none")]
+       pub primes:  Option<Vec<usize>>,
+       #[serde(skip_serializing_if = "Option::is_none")]
+       pub stats:   Option<PrimeStats>,
+}
+
+/// How the primes of a `primes --stats` run are spread out.
+#[derive(Debug, Serialize)]
+pub struct PrimeStats {
+       /// The widest gap between consecutive primes, and the prime it follows (the first, if several tie); `None`
+       /// with fewer than two primes.
+       pub max_gap:  Option<Gap>,
+       /// The average gap between consecutive primes.
+       pub mean_gap: Option<f64>,
+       /// Pairs of primes 2 apart, both in the range.
+       pub twins:    usize,
+       /// The range cut into ten parts, as equal as they come (fewer, if there are under ten numbers).
+       pub deciles:  Vec<Decile>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Gap {
+       pub after: usize,
+       pub gap:   usize,
+}
+
+/// One tenth of the range, and how many of its numbers are prime.
+#[derive(Debug, Serialize)]
+pub struct Decile {
+       pub low:     usize,
+       pub high:    usize,
+       pub count:   usize,
+       /// Percent of the numbers in `low..=high` that are prime.
+       pub density: f64,
+}
+
+/// The summary's CSV row.
+#[derive(Serialize)]
+struct SummaryRow {
+       min:     usize,
+       max:     usize,
+       count:   usize,
+       density: f64,
+}
+
+/// The summary's CSV row, with `--stats`: the gaps and twins, flattened (the deciles don't fit a row).
+#[derive(Serialize)]
+struct StatsRow {
+       min:           usize,
+       max:           usize,
+       count:         usize,
+       density:       f64,
+       max_gap:       Option<usize>,
+       max_gap_after: Option<usize>,
+       mean_gap:      Option<f64>,
+       twins:         usize,
+}
+
+/// The CSV row per prime, with `--show`.
+#[derive(Serialize)]
+struct PrimeRow {
+       prime: usize,
+}
+
+/// What `primes --nth` found.
+#[derive(Debug, Serialize)]
+pub struct NthPrime {
+       pub n:     usize,
+       pub prime: usize,
+}
+
+/// What `type-info` found: every detail, whatever flags asked to display.
+#[derive(Debug, Serialize)]
+pub struct TypeReport<'a> {
+       #[serde(flatten)]
+       pub details:     &'a TypeDetails,
+       /// Each target type, and how this one converts to it.
+       #[serde(skip_serializing_if = "Option::is_none")]
+       pub conversions: Option<Vec<ConversionRow>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConversionRow {
+       pub to:         &'static str,
+       pub conversion: Conversion,
+}
+
+impl TypeReport<'_> {
+       pub fn render(&self, format: Format) -> Result<String, Box<dyn Error>> {
+              match format {
+                     Format::Json => json(self),
+                     Format::Human | Format::Csv => Err("type-info writes `--format json` only (or colored text, by default)")?,
+              }
+       }
+}
+
+impl NthPrime {
+       /// The report as text in `format`; `Human` is plain (uncolored), for files.
+       pub fn render(&self, format: Format) -> Result<String, Box<dyn Error>> {
+              match format {
+                     Format::Human => Ok(format!("prime #{}: {}\n", self.n, self.prime)),
+                     Format::Json => json(self),
+                     Format::Csv => csv([self]),
+              }
+       }
+}
+
+impl PrimesReport {
+       /// `primes`, if kept, are the `count` primes found.
+       pub fn new(min: usize, max: usize, count: usize, primes: Option<Vec<usize>>, stats: Option<PrimeStats>) -> Self {
+              let density = 100. * count as f64 / (max - min + 1) as f64;
+              Self { min, max, count, density, primes, stats }
+       }
+
+       /// The report as text in `format`; `Human` is plain (uncolored), for files.
+       pub fn render(&self, format: Format) -> Result<String, Box<dyn Error>> {
+              Ok(match format {
+                     Format::Human => {
+                            let mut text = format!("{} primes in ({}..={}): {:.1}% prime\n", self.count, self.min, self.max, self.density);
+                            if let Some(stats) = &self.stats {
+                                   text += &stats.table();
+                            }
+                            if let Some(primes) = &self.primes {
+                                   writeln!(text, "{primes:?}")?;
+                            }
+                            text
+                     }
+                     Format::Json => json(self)?,
+                     Format::Csv => match (&self.primes, &self.stats) {
+                            (Some(primes), _) => csv(primes.iter().map(|&prime| PrimeRow { prime }))?,
+                            (None, Some(stats)) => csv([StatsRow {
+                                   min:           self.min,
+                                   max:           self.max,
+                                   count:         self.count,
+                                   density:       self.density,
+                                   max_gap:       stats.max_gap.map(|max| max.gap),
+                                   max_gap_after: stats.max_gap.map(|max| max.after),
+                                   mean_gap:      stats.mean_gap,
+                                   twins:         stats.twins,
+                            }])?,
+                            (None, None) => {
+                                   csv([SummaryRow { min: self.min, max: self.max, count: self.count, density: self.density }])?
+                            }
+                     },
+              })
+       }
+}
+
+impl PrimeStats {
+       /// The statistics of `primes`, every prime in `min..=max`, in order.
+       pub fn new(min: usize, max: usize, primes: &[usize]) -> Self {
+              let gaps = primes.windows(2).map(|pair| Gap { after: pair[0], gap: pair[1] - pair[0] });
+              // `max_by_key` keeps the last of equal maxima; the first is the one anyone would look up
+              let max_gap = gaps.clone().fold(None, |widest: Option<Gap>, gap| match widest {
+                     Some(widest) if widest.gap >= gap.gap => Some(widest),
+                     _ => Some(gap),
+              });
+              let mean_gap = match primes {
+                     [first, .., last] => Some((last - first) as f64 / (primes.len() - 1) as f64),
+                     _ => None,
+              };
+              let twins = gaps.filter(|gap| gap.gap == 2).count();
+              // the `i`th tenth starts `i/10` of the way in (in u128: `len * i` can overflow)
+              let len = (max - min) as u128 + 1;
+              let start = |i: u128| min + (len * i / 10) as usize;
+              let deciles = (0..10)
+                     .filter(|&i| start(i) < start(i + 1))
+                     .map(|i| {
+                            let (low, high) = (start(i), start(i + 1) - 1);
+                            let count = primes.partition_point(|&p| p <= high) - primes.partition_point(|&p| p < low);
+                            Decile { low, high, count, density: 100. * count as f64 / (high - low + 1) as f64 }
+                     })
+                     .collect();
+              Self { max_gap, mean_gap, twins, deciles }
+       }
+
+       /// The gaps and twins, then the deciles as a table, each with a bar scaled to the densest.
+       pub fn table(&self) -> String {
+              const BAR: usize = 40;
+              let mut text = String::new();
+              match self.max_gap {
+                     Some(Gap { after, gap }) => _ = writeln!(text, "widest gap: {gap} (after {after})"),
+                     None => text += "widest gap: none (fewer than two primes)\n",
+              }
+              if let Some(mean_gap) = self.mean_gap {
+                     _ = writeln!(text, "mean gap:   {mean_gap:.2}");
+              }
+              _ = writeln!(text, "twin pairs: {}", self.twins);
+              let densest = self.deciles.iter().map(|decile| decile.density).fold(0., f64::max);
+              let high_width = self.deciles.last().map_or(1, |decile| decile.high.to_string().len());
+              _ = writeln!(text, "{:>w$}  {:>10} {:>8}", "range", "primes", "density", w = 2 * high_width + 3);
+              for decile in &self.deciles {
+                     let bar = if densest > 0. { (decile.density / densest * BAR as f64).round() as usize } else { 0 };
+                     _ = writeln!(
+                            text,
+                            "{:>w$}..={:<w$}  {:>10} {:>7.2}% {}",
+                            decile.low,
+                            decile.high,
+                            decile.count,
+                            decile.density,
+                            "█".repeat(bar),
+                            w = high_width
+                     );
+              }
+              text
+       }
+}
+
+pub fn json(value: &impl Serialize) -> Result<String, Box<dyn Error>> { Ok(serde_json::to_string_pretty(value)? + "\n") }
+
+/// A header (the rows' field names), then the rows.
+fn csv(rows: impl IntoIterator<Item = impl Serialize>) -> Result<String, Box<dyn Error>> {
+       let mut csv = csv::Writer::from_writer(Vec::new());
+       for row in rows {
+              csv.serialize(row)?;
+       }
+       Ok(String::from_utf8(csv.into_inner()?)?)
+}
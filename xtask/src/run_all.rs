@@ -0,0 +1,226 @@
+//! `xtask run-all`: every binary in the `threads` crate, run once each with a time limit, summarized in a table.
+//!
+//! The binaries are whatever `cargo metadata` lists, so a new one is picked up without touching this.
+//! Each runs with its output captured and, unless given `--stdin`, its stdin closed: the interactive demos
+//! (`atomics-demos ch2 stop-flag`, reading commands until `stop`) see end of input and wind down, and the time
+//! limit catches any that don't.
+
+use std::{collections::HashMap,
+          env,
+          error::Error,
+          fmt::Write as _,
+          io::{self, BufRead, BufReader, Read, Write},
+          path::{Path, PathBuf},
+          process::{Command, ExitStatus, Stdio},
+          sync::mpsc,
+          thread,
+          time::Duration};
+
+use crate::timing::{Timed, timed, wait_timeout};
+
+/// The `threads` crate's binaries, and the directory a build (of the chosen profile) puts them in.
+#[derive(Debug)]
+pub struct Bins {
+       pub dir:   PathBuf,
+       pub names: Vec<String>,
+}
+
+/// The cargo running us (or the one on the path).
+pub fn cargo() -> Command { Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".into())) }
+
+/// The workspace's packages, without their dependencies.
+fn metadata() -> Result<serde_json::Value, Box<dyn Error>> {
+       let output = cargo().args(["metadata", "--format-version", "1", "--no-deps"]).output()?;
+       if !output.status.success() {
+              Err(format!("Error: `cargo metadata` failed.\n{}", String::from_utf8_lossy(&output.stderr)))?
+       }
+       Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Where builds go (before the profile: `target`, not `target/debug`).
+pub fn target_dir() -> Result<PathBuf, Box<dyn Error>> {
+       let metadata = metadata()?;
+       let dir = metadata["target_directory"].as_str().ok_or("Error: `cargo metadata` gave no target directory.")?;
+       Ok(PathBuf::from(dir))
+}
+
+/// The `threads` crate's binaries, as `cargo metadata` lists them.
+pub fn threads_bins(release: bool) -> Result<Bins, Box<dyn Error>> {
+       let metadata = metadata()?;
+       let package = metadata["packages"]
+              .as_array()
+              .and_then(|packages| packages.iter().find(|p| p["name"] == "threads"))
+              .ok_or("Error: no `threads` package in the workspace.")?;
+       let names = package["targets"]
+              .as_array()
+              .into_iter()
+              .flatten()
+              .filter(|target| target["kind"].as_array().is_some_and(|kinds| kinds.iter().any(|k| k == "bin")))
+              .filter_map(|target| target["name"].as_str().map(String::from))
+              .collect();
+       Ok(Bins { dir: target_dir()?.join(if release { "release" } else { "debug" }), names })
+}
+
+/// Build all of the `threads` crate's binaries, in one cargo invocation.
+pub fn build_threads_bins(release: bool) -> Result<(), Box<dyn Error>> {
+       let mut build = cargo();
+       build.args(["build", "--package", "threads", "--bins"]);
+       if release {
+              build.arg("--release");
+       }
+       if !build.status()?.success() {
+              Err("Error: building the `threads` binaries failed.")?
+       }
+       Ok(())
+}
+
+/// How a run went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+       Pass,
+       Fail,
+       Timeout,
+       Skipped,
+}
+
+/// One binary's run: what it was given, how it went, and what it wrote.
+#[derive(Debug)]
+pub struct Run {
+       pub bin:     String,
+       pub args:    Vec<String>,
+       pub status:  Status,
+       /// `None` if it didn't exit by itself (timed out, or killed by a signal), or never ran.
+       pub code:    Option<i32>,
+       pub elapsed: Duration,
+       pub stdout:  String,
+       pub stderr:  String,
+}
+
+impl Run {
+       pub fn skipped(bin: &str, args: Vec<String>) -> Self {
+              Self {
+                     bin: bin.to_string(),
+                     args,
+                     status: Status::Skipped,
+                     code: None,
+                     elapsed: Duration::ZERO,
+                     stdout: String::new(),
+                     stderr: String::new(),
+              }
+       }
+
+       /// The command line, as typed.
+       pub fn command(&self) -> String {
+              [self.bin.as_str()].into_iter().chain(self.args.iter().map(String::as_str)).collect::<Vec<_>>().join(" ")
+       }
+}
+
+/// Run `dir/bin args`, feeding it `stdin` (closed, if `None`), and killing it after `limit`.
+pub fn run(dir: &Path, bin: &str, args: Vec<String>, stdin: Option<&str>, limit: Duration) -> io::Result<Run> {
+       let mut child = Command::new(dir.join(bin))
+              .args(&args)
+              .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+              .stdout(Stdio::piped())
+              .stderr(Stdio::piped())
+              .spawn()?;
+       // drained on threads of their own, so a chatty binary never blocks on a full pipe
+       let drain = |pipe: Option<Box<dyn Read + Send>>| {
+              thread::spawn(move || {
+                     let mut text = Vec::new();
+                     if let Some(mut pipe) = pipe {
+                            _ = pipe.read_to_end(&mut text);
+                     }
+                     String::from_utf8_lossy(&text).into_owned()
+              })
+       };
+       let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+       let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
+       if let (Some(mut pipe), Some(text)) = (child.stdin.take(), stdin) {
+              let text = text.to_string();
+              // a binary that never reads it mustn't hold us up either; dropping the pipe closes it
+              thread::spawn(move || _ = pipe.write_all(text.as_bytes()));
+       }
+
+       let Timed { value: status, elapsed } = timed(|| wait_timeout(&mut child, limit));
+       let (status, code) = match status? {
+              Some(exit) if exit.success() => (Status::Pass, exit.code()),
+              Some(exit) => (Status::Fail, exit.code()),
+              None => {
+                     child.kill()?;
+                     child.wait()?;
+                     (Status::Timeout, None)
+              }
+       };
+       Ok(Run {
+              bin: bin.to_string(),
+              args,
+              status,
+              code,
+              elapsed,
+              stdout: stdout.join().unwrap_or_default(),
+              stderr: stderr.join().unwrap_or_default(),
+       })
+}
+
+/// Argument lists, by binary.
+pub type RunArgs = HashMap<String, Vec<Vec<String>>>;
+
+/// `--args` entries (`bin=arg arg ...`), by binary: a binary named more than once runs once per entry.
+pub fn parse_args(entries: &[String]) -> Result<RunArgs, Box<dyn Error>> {
+       let mut by_bin = RunArgs::new();
+       for entry in entries {
+              let (bin, args) = entry.split_once('=').ok_or_else(|| format!("Error: `--args {entry}`: expected `BIN=ARGS`."))?;
+              by_bin.entry(bin.trim().to_string()).or_default().push(args.split_whitespace().map(String::from).collect());
+       }
+       Ok(by_bin)
+}
+
+/// `runs` as a markdown table.
+pub fn table(runs: &[Run]) -> String {
+       let columns = ["command", "result", "exit", "time"];
+       let mut text = format!("| {} |\n|{}\n", columns.join(" | "), "---|".repeat(columns.len()));
+       for run in runs {
+              let result = match run.status {
+                     Status::Pass => "pass",
+                     Status::Fail => "FAIL",
+                     Status::Timeout => "TIMEOUT",
+                     Status::Skipped => "skipped",
+              };
+              let code = run.code.map_or_else(|| "-".into(), |code| code.to_string());
+              let time = if run.status == Status::Skipped { "-".into() } else { format!("{:.2?}", run.elapsed) };
+              _ = writeln!(text, "| `{}` | {} | {} | {} |", run.command(), result, code, time);
+       }
+       text
+}
+
+/// The last `lines` lines of what `run` wrote: stderr, or stdout if that's empty.
+pub fn tail(run: &Run, lines: usize) -> String {
+       let text = if run.stderr.trim().is_empty() { &run.stdout } else { &run.stderr };
+       let all: Vec<&str> = text.lines().collect();
+       all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+/// Run `command`, echoing its stdout and stderr to our stderr as they come, and returning its exit status and
+/// every line it wrote (both streams, interleaved as they arrived): for the long runs worth watching and then
+/// summarizing.
+pub fn stream(command: &mut Command) -> io::Result<(ExitStatus, Vec<String>)> {
+       let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+       let (tx, rx) = mpsc::channel();
+       let forward = |pipe: Box<dyn Read + Send>, tx: mpsc::Sender<String>| {
+              thread::spawn(move || {
+                     for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                            _ = tx.send(line);
+                     }
+              })
+       };
+       let readers = [
+              child.stdout.take().map(|pipe| forward(Box::new(pipe), tx.clone())),
+              child.stderr.take().map(|pipe| forward(Box::new(pipe), tx)),
+       ];
+       // ends once both readers are done, and their senders with them
+       let lines: Vec<String> = rx.into_iter().inspect(|line| eprintln!("{line}")).collect();
+       for reader in readers.into_iter().flatten() {
+              _ = reader.join();
+       }
+       Ok((child.wait()?, lines))
+}
@@ -0,0 +1,236 @@
+//! `xtask stress`: one `threads` binary swept across thread counts (and argument sets), each combination run
+//! several times, summarized as min/median/max.
+//!
+//! The thread count goes in through an argument template (`--threads {}` by default; `-p {} -n {}` for
+//! `prodcon`'s producers and consumers), since each binary names it its own way. The runs go round by round,
+//! every combination once a round, so a slow spell on the machine lands on all of them rather than on one.
+//!
+//! Besides each run's wall-clock time, whatever numbers the binary prints in a form a program can read are
+//! collected from its stdout (colors stripped):
+//! - a line that's a JSON object: each numeric field;
+//! - a table, markdown or CSV (a header of names, then rows with as many cells): each numeric cell, named by
+//!   its column and the row's other cells (`spin acquires/s`);
+//! - a `name: number [unit]` line (`throughput: 1599999 msg/s`).
+
+use std::{collections::{BTreeMap, HashMap},
+          error::Error,
+          fmt::Write as _,
+          io,
+          path::Path,
+          time::Duration};
+
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::run_all::{self, Run, Status};
+
+/// One combination to run: a thread count, and the arguments it makes with an argument set.
+#[derive(Debug)]
+pub struct Case {
+       pub threads: usize,
+       pub args:    Vec<String>,
+}
+
+/// Every argument set (`["--args"]`, or none) with every thread count, `{}` in `template` replaced by the count.
+pub fn cases(threads: &[usize], template: &str, arg_sets: &[String]) -> Result<Vec<Case>, Box<dyn Error>> {
+       if !template.contains("{}") {
+              Err(format!("Error: `--thread-arg {template}` has no `{{}}` for the thread count."))?
+       }
+       let arg_sets = if arg_sets.is_empty() { &[String::new()][..] } else { arg_sets };
+       Ok(arg_sets
+              .iter()
+              .flat_map(|set| {
+                     threads.iter().map(move |&n| Case {
+                            threads: n,
+                            args:    template
+                                   .split_whitespace()
+                                   .map(|word| word.replace("{}", &n.to_string()))
+                                   .chain(set.split_whitespace().map(String::from))
+                                   .collect(),
+                     })
+              })
+              .collect())
+}
+
+/// Run each case `repeats` times, round by round, killing any run after `limit`; each case's runs, in order.
+pub fn sweep(dir: &Path, bin: &str, cases: &[Case], repeats: usize, limit: Duration) -> io::Result<Vec<Vec<Run>>> {
+       let mut runs: Vec<Vec<Run>> = cases.iter().map(|_| Vec::with_capacity(repeats)).collect();
+       for round in 1..=repeats {
+              for (case, case_runs) in cases.iter().zip(&mut runs) {
+                     eprintln!("[{round}/{repeats}] {} {}", bin.blue(), case.args.join(" ").blue());
+                     let run = run_all::run(dir, bin, case.args.clone(), None, limit)?;
+                     if run.status != Status::Pass {
+                            eprintln!("  {}", format!("{:?}", run.status).to_lowercase().red());
+                     }
+                     case_runs.push(run);
+              }
+       }
+       Ok(runs)
+}
+
+/// `text` without its terminal escapes (`owo-colors` colors whether or not it's writing to a terminal).
+fn plain(text: &str) -> String {
+       let mut plain = String::with_capacity(text.len());
+       let mut chars = text.chars();
+       while let Some(c) = chars.next() {
+              if c == '\x1b' {
+                     // `ESC [ params letter`: up to and including the letter
+                     _ = chars.find(char::is_ascii_alphabetic);
+              } else {
+                     plain.push(c);
+              }
+       }
+       plain
+}
+
+fn number(text: &str) -> Option<f64> { text.parse::<f64>().ok().filter(|n| n.is_finite()) }
+
+/// A line's cells, if it's a markdown table row (`| a | b |`) or has commas (`a,b`): at least two.
+fn cells(line: &str) -> Option<Vec<String>> {
+       let cells: Vec<String> = if let Some(inner) = line.strip_prefix('|').and_then(|line| line.strip_suffix('|')) {
+              inner.split('|').map(|cell| cell.trim().to_string()).collect()
+       } else {
+              line.split(',').map(|cell| cell.trim().to_string()).collect()
+       };
+       (cells.len() > 1).then_some(cells)
+}
+
+/// The numbers in a run's output, by name (the last, for a name that comes up more than once).
+pub fn metrics(output: &str) -> BTreeMap<String, f64> {
+       let mut metrics = BTreeMap::new();
+       let mut header: Option<Vec<String>> = None;
+       let mut labels = HashMap::<String, usize>::new(); // rows seen, by label, so two `spin` rows stay apart
+       for line in plain(output).lines().map(str::trim) {
+              let row = cells(line);
+              if let (Some(columns), Some(row)) = (&header, &row)
+                     && columns.len() == row.len()
+              {
+                     if row.iter().all(|cell| !cell.is_empty() && cell.chars().all(|c| c == '-' || c == ':')) {
+                            continue; // markdown's line under the header
+                     }
+                     let label = row.iter().filter(|cell| number(cell).is_none()).cloned().collect::<Vec<_>>().join(" ");
+                     let seen = labels.entry(label.clone()).or_default();
+                     *seen += 1;
+                     let label = if *seen > 1 { format!("{label} #{seen}") } else { label };
+                     for (column, cell) in columns.iter().zip(row) {
+                            if let Some(value) = number(cell) {
+                                   metrics.insert(format!("{label} {column}").trim().to_string(), value);
+                            }
+                     }
+                     continue;
+              }
+              header = row.filter(|row| row.iter().all(|cell| !cell.is_empty() && number(cell).is_none()));
+              if header.is_some() {
+                     labels.clear();
+              } else if line.starts_with('{')
+                     && let Ok(serde_json::Value::Object(object)) = serde_json::from_str(line)
+              {
+                     metrics.extend(object.iter().filter_map(|(name, value)| Some((name.clone(), value.as_f64()?))));
+              } else if let Some((name, rest)) = line.split_once(':')
+                     && !name.trim().is_empty()
+              {
+                     let mut words = rest.split_whitespace();
+                     if let (Some(value), unit, None) = (words.next().and_then(number), words.next(), words.next()) {
+                            let name = name.trim();
+                            metrics.insert(unit.map_or_else(|| name.to_string(), |unit| format!("{name} ({unit})")), value);
+                     }
+              }
+       }
+       metrics
+}
+
+/// One line of the summary: a measure (wall-clock time, or a metric) over a case's runs.
+#[derive(Debug, Serialize)]
+pub struct StressRow {
+       pub command: String,
+       pub threads: usize,
+       /// `wall ms`, or a metric's name.
+       pub measure: String,
+       /// The runs that passed (and for a metric, reported it).
+       pub runs:    usize,
+       /// The case's runs that failed or timed out.
+       pub failed:  usize,
+       pub min:     f64,
+       pub median:  f64,
+       pub max:     f64,
+}
+
+/// The smallest, median and largest of `values` (not-a-number, if there are none).
+fn spread(mut values: Vec<f64>) -> (f64, f64, f64) {
+       if values.is_empty() {
+              return (f64::NAN, f64::NAN, f64::NAN);
+       }
+       values.sort_unstable_by(f64::total_cmp);
+       let mid = values.len() / 2;
+       let median = if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2. } else { values[mid] };
+       (values[0], median, values[values.len() - 1])
+}
+
+/// Each case's wall-clock time (not-a-number if no run passed), then each metric whose name contains one of `only` (all of them, if it's empty),
+/// over the runs that passed.
+pub fn rows(bin: &str, cases: &[Case], runs: &[Vec<Run>], only: &[String]) -> Vec<StressRow> {
+       let mut rows = Vec::new();
+       for (case, runs) in cases.iter().zip(runs) {
+              let passed: Vec<&Run> = runs.iter().filter(|run| run.status == Status::Pass).collect();
+              let failed = runs.len() - passed.len();
+              let row = |measure: String, values: Vec<f64>| {
+                     let runs = values.len();
+                     let (min, median, max) = spread(values);
+                     StressRow {
+                            command: format!("{bin} {}", case.args.join(" ")).trim_end().to_string(),
+                            threads: case.threads,
+                            measure,
+                            runs,
+                            failed,
+                            min,
+                            median,
+                            max,
+                     }
+              };
+              rows.push(row("wall ms".into(), passed.iter().map(|run| run.elapsed.as_secs_f64() * 1e3).collect()));
+              let mut by_name = BTreeMap::<String, Vec<f64>>::new();
+              for run in &passed {
+                     for (name, value) in metrics(&run.stdout) {
+                            by_name.entry(name).or_default().push(value);
+                     }
+              }
+              for (name, values) in by_name {
+                     if only.is_empty() || only.iter().any(|wanted| name.contains(wanted.as_str())) {
+                            rows.push(row(name, values));
+                     }
+              }
+       }
+       rows
+}
+
+/// A number as it reads best: whole ones without decimals, the rest to three places.
+fn show(value: f64) -> String {
+       if value.is_nan() {
+              "-".into()
+       } else if value.fract() == 0. && value.abs() < 1e15 {
+              format!("{value:.0}")
+       } else {
+              format!("{value:.3}")
+       }
+}
+
+/// `rows` as a markdown table.
+pub fn table(rows: &[StressRow]) -> String {
+       let columns = ["command", "threads", "measure", "runs", "failed", "min", "median", "max"];
+       let mut text = format!("| {} |\n|{}\n", columns.join(" | "), "---|".repeat(columns.len()));
+       for row in rows {
+              _ = writeln!(
+                     text,
+                     "| `{}` | {} | {} | {} | {} | {} | {} | {} |",
+                     row.command,
+                     row.threads,
+                     row.measure,
+                     row.runs,
+                     row.failed,
+                     show(row.min),
+                     show(row.median),
+                     show(row.max)
+              );
+       }
+       text
+}
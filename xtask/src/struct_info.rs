@@ -0,0 +1,312 @@
+//! `xtask struct-info`: where a struct's fields actually land — offsets, padding, and cache lines.
+//!
+//! The struct is found in its source file with `syn`, then asked about rather than worked out:
+//! it's written into a small probe program (with whatever else in the file it names), compiled with `rustc`,
+//! and run, printing `size_of`/`align_of` and each field's `offset_of!`.
+//! The default `repr(Rust)` is free to reorder fields, so only the compiler knows; this way `repr(C)`,
+//! `align(N)` and `packed` come out right with no rules of our own to get wrong.
+//!
+//! The probe sees `std` and the file, not the file's crate: a field typed with something from elsewhere in
+//! the crate (or another crate) won't resolve, and rustc's error says which.
+//! Layout is the host's, as the probe runs here.
+
+use std::{collections::{BTreeSet, HashMap},
+          error::Error,
+          fmt::Write as _,
+          fs,
+          path::Path,
+          process::{self, Command}};
+
+use quote::ToTokens;
+use syn::{Fields, Item};
+
+/// `std` items a field type can name without a path (globs: the file's own `std` imports come after, and win).
+const PRELUDE: &str = "use std::{cell::*, marker::*, mem::*, num::*, ptr::*, sync::{atomic::*, *}, time::*};";
+
+/// One field, where the compiler put it.
+#[derive(Debug)]
+pub struct FieldLayout {
+       pub name:   String,
+       pub ty:     String,
+       pub offset: usize,
+       pub size:   usize,
+       pub align:  usize,
+}
+
+/// A struct's size and alignment, and its fields in memory order.
+#[derive(Debug)]
+pub struct StructLayout {
+       pub name:   String,
+       pub size:   usize,
+       pub align:  usize,
+       pub fields: Vec<FieldLayout>,
+}
+
+/// Lay out `ty` (a struct in `path`, with type arguments if it's generic: `CachePadded<u8>`).
+pub fn struct_layout(path: &Path, ty: &str) -> Result<StructLayout, Box<dyn Error>> {
+       let ty: syn::Type = syn::parse_str(ty).map_err(|e| format!("Error: `{ty}` isn't a type: {e}"))?;
+       let syn::Type::Path(type_path) = &ty else { Err(format!("Error: `{}` isn't a struct's name.", tidy(&ty)))? };
+       let last = type_path.path.segments.last().ok_or("Error: empty type name.")?;
+       let name = last.ident.to_string();
+
+       let source = fs::read_to_string(path).map_err(|e| format!("Error reading {}: {e}", path.display()))?;
+       let file = syn::parse_file(&source).map_err(|e| format!("Error parsing {}: {e}", path.display()))?;
+       let mut items = HashMap::new();
+       collect_items(&file.items, &mut items);
+       let Some(Item::Struct(target)) = items.get(&name) else { Err(format!("Error: no struct `{name}` in {}.", path.display()))? };
+       let mut ty = tidy(&ty);
+       if last.arguments.is_none() && !target.generics.params.is_empty() {
+              // some stand-in for each parameter (`'static` for a lifetime, all any lifetime-only struct needs)
+              let args: Vec<_> = target
+                     .generics
+                     .params
+                     .iter()
+                     .map(|param| match param {
+                            syn::GenericParam::Lifetime(_) => "'static",
+                            syn::GenericParam::Type(_) => "u8",
+                            syn::GenericParam::Const(_) => "1",
+                     })
+                     .collect();
+              let example = format!("{name}<{}>", args.join(", "));
+              if target.generics.lifetimes().count() < args.len() {
+                     Err(format!("Error: `{name}` is generic; give it arguments, e.g. `{example}`."))?
+              }
+              ty = example;
+       }
+       let fields: Vec<(String, String)> = match &target.fields {
+              Fields::Named(named) => named.named.iter().map(|f| (f.ident.as_ref().expect("named").to_string(), tidy(&f.ty))).collect(),
+              Fields::Unnamed(unnamed) => unnamed.unnamed.iter().enumerate().map(|(i, f)| (i.to_string(), tidy(&f.ty))).collect(),
+              Fields::Unit => Vec::new(),
+       };
+
+       // the file's own `std` imports: `Any`, `JoinHandle`, ... as it names them
+       let uses: Vec<String> = file
+              .items
+              .iter()
+              .filter_map(|item| match item {
+                     Item::Use(u) => match &u.tree {
+                            syn::UseTree::Path(p) if ["std", "core", "alloc"].iter().any(|root| p.ident == root) => {
+                                   Some(format!("use {};", tidy(&u.tree)))
+                            }
+                            _ => None,
+                     },
+                     _ => None,
+              })
+              .collect();
+       let output = run_probe(&probe_source(&name, &ty, &fields, &items, &uses))?;
+       let mut lines = output.lines().map(|line| line.split(' ').map(str::parse::<usize>).collect::<Result<Vec<_>, _>>());
+       let [size, align] = lines.next().ok_or("Error: the probe printed nothing.")??[..] else {
+              Err("Error: the probe's output doesn't make sense.")?
+       };
+       let mut laid_out = Vec::with_capacity(fields.len());
+       for ((name, ty), line) in fields.into_iter().zip(lines) {
+              let [offset, size, align] = line?[..] else { Err("Error: the probe's output doesn't make sense.")? };
+              laid_out.push(FieldLayout { name, ty, offset, size, align });
+       }
+       // zero-sized fields first among those at the same offset: they take no room there
+       laid_out.sort_by_key(|f| (f.offset, f.size));
+       Ok(StructLayout { name: ty, size, align, fields: laid_out })
+}
+
+/// Every struct, enum, union, type alias and const in `items`, by name, inline modules included.
+fn collect_items(items: &[Item], out: &mut HashMap<String, Item>) {
+       for item in items {
+              let name = match item {
+                     Item::Struct(s) => &s.ident,
+                     Item::Enum(e) => &e.ident,
+                     Item::Union(u) => &u.ident,
+                     Item::Type(t) => &t.ident,
+                     Item::Const(c) => &c.ident,
+                     Item::Mod(m) => {
+                            if let Some((_, items)) = &m.content {
+                                   collect_items(items, out);
+                            }
+                            continue;
+                     }
+                     _ => continue,
+              };
+              out.entry(name.to_string()).or_insert_with(|| without_attributes(item.clone()));
+       }
+}
+
+/// `item` with only its `#[repr]` kept: derives and the like may need what the probe hasn't got.
+fn without_attributes(mut item: Item) -> Item {
+       let keep = |attrs: &mut Vec<syn::Attribute>| attrs.retain(|a| a.path().is_ident("repr"));
+       let strip_fields = |fields: &mut Fields| fields.iter_mut().for_each(|f| f.attrs.clear());
+       match &mut item {
+              Item::Struct(s) => {
+                     keep(&mut s.attrs);
+                     strip_fields(&mut s.fields);
+              }
+              Item::Enum(e) => {
+                     keep(&mut e.attrs);
+                     for variant in &mut e.variants {
+                            variant.attrs.clear();
+                            strip_fields(&mut variant.fields);
+                     }
+              }
+              Item::Union(u) => {
+                     keep(&mut u.attrs);
+                     u.fields.named.iter_mut().for_each(|f| f.attrs.clear());
+              }
+              Item::Type(t) => keep(&mut t.attrs),
+              Item::Const(c) => keep(&mut c.attrs),
+              _ => {}
+       }
+       item
+}
+
+/// The probe: `uses`, the target and everything in the file it (transitively) names, and a `main` printing
+/// the size and alignment, then each field's offset, size and alignment, a line each.
+fn probe_source(name: &str, ty: &str, fields: &[(String, String)], items: &HashMap<String, Item>, uses: &[String]) -> String {
+       let mut needed = BTreeSet::from([name.to_string()]);
+       let mut queue = vec![name.to_string()];
+       while let Some(next) = queue.pop() {
+              let tokens = items[&next].to_token_stream().to_string();
+              for ident in tokens.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+                     if items.contains_key(ident) && needed.insert(ident.to_string()) {
+                            queue.push(ident.to_string());
+                     }
+              }
+       }
+
+       let mut source = format!("#![allow(warnings)]\n{PRELUDE}\n{}\n", uses.join("\n"));
+       for item in &needed {
+              writeln!(source, "{}", tidy(&items[item])).expect("writing to a String");
+       }
+       source.push_str("fn field<T, F>(_: fn(&T) -> &F) -> (usize, usize) { (size_of::<F>(), align_of::<F>()) }\n");
+       writeln!(source, "fn main() {{\n    println!(\"{{}} {{}}\", size_of::<{ty}>(), align_of::<{ty}>());").expect("writing to a String");
+       for (field, _) in fields {
+              writeln!(
+                     source,
+                     "    let (size, align) = field(|s: &{ty}| &s.{field});\n    println!(\"{{}} {{size}} {{align}}\", \
+                      std::mem::offset_of!({ty}, {field}));"
+              )
+              .expect("writing to a String");
+       }
+       source.push_str("}\n");
+       source
+}
+
+/// Compile and run `source`, returning what it printed.
+fn run_probe(source: &str) -> Result<String, Box<dyn Error>> {
+       let dir = std::env::temp_dir().join(format!("xtask-struct-info-{}", process::id()));
+       fs::create_dir_all(&dir)?;
+       let run = || -> Result<String, Box<dyn Error>> {
+              let (src, bin) = (dir.join("probe.rs"), dir.join("probe"));
+              fs::write(&src, source)?;
+              let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+              let compiled =
+                     Command::new(&rustc).args(["--edition", "2024", "--crate-name", "probe", "-o"]).arg(&bin).arg(&src).output()?;
+              if !compiled.status.success() {
+                     eprint!("{}", String::from_utf8_lossy(&compiled.stderr));
+                     Err(
+                            "Error: the probe didn't compile (it only knows `std` and this file: a type from elsewhere in the crate won't resolve).",
+                     )?
+              }
+              let ran = Command::new(&bin).output()?;
+              if !ran.status.success() {
+                     eprint!("{}", String::from_utf8_lossy(&ran.stderr));
+                     Err("Error: the probe failed.")?
+              }
+              Ok(String::from_utf8(ran.stdout)?)
+       };
+       let output = run();
+       fs::remove_dir_all(&dir)?;
+       output
+}
+
+/// Tokens as they'd be written, near enough: `Box<[u8; 4]>`, not `Box < [u8 ; 4] >`.
+fn tidy(tokens: &impl ToTokens) -> String {
+       let mut s = tokens.to_token_stream().to_string();
+       for (from, to) in [
+              (" :: ", "::"),
+              (":: ", "::"),
+              (" < ", "<"),
+              (" <", "<"),
+              ("< ", "<"),
+              (" >", ">"),
+              (" ,", ","),
+              (" ;", ";"),
+              ("& ", "&"),
+              ("* ", "*"),
+       ] {
+              s = s.replace(from, to);
+       }
+       s
+}
+
+/// The cache line(s) `len` bytes at `offset` touch: `2`, or `1-2` when they straddle a boundary.
+fn lines(offset: usize, len: usize, line: usize) -> String {
+       let (first, last) = (offset / line, (offset + len.max(1) - 1) / line);
+       if first == last { first.to_string() } else { format!("{first}-{last}") }
+}
+
+/// The layout as a table in memory order, padding included, with the cache line(s) of each row.
+pub fn table(layout: &StructLayout, line: usize) -> String {
+       let mut rows = Vec::new();
+       let mut end = 0;
+       let mut padding = 0;
+       for f in &layout.fields {
+              if f.offset > end {
+                     rows.push([
+                            end.to_string(),
+                            (f.offset - end).to_string(),
+                            String::new(),
+                            lines(end, f.offset - end, line),
+                            "(padding)".into(),
+                            String::new(),
+                     ]);
+                     padding += f.offset - end;
+              }
+              rows.push([
+                     f.offset.to_string(),
+                     f.size.to_string(),
+                     f.align.to_string(),
+                     lines(f.offset, f.size, line),
+                     f.name.clone(),
+                     f.ty.clone(),
+              ]);
+              end = end.max(f.offset + f.size);
+       }
+       if layout.size > end {
+              rows.push([
+                     end.to_string(),
+                     (layout.size - end).to_string(),
+                     String::new(),
+                     lines(end, layout.size - end, line),
+                     "(padding)".into(),
+                     String::new(),
+              ]);
+              padding += layout.size - end;
+       }
+
+       let headers = ["offset", "size", "align", "line", "field", "type"];
+       let widths: Vec<usize> =
+              (0..headers.len()).map(|i| rows.iter().map(|r| r[i].len()).chain([headers[i].len()]).max().unwrap_or(0)).collect();
+       let mut out = String::new();
+       let mut write_row = |cells: [&str; 6]| {
+              let [offset, size, align, cache_line, field, ty] = cells;
+              let w = &widths;
+              let row = format!("{offset:>0$}  {size:>1$}  {align:>2$}  {cache_line:>3$}  {field:<4$}  {ty}", w[0], w[1], w[2], w[3], w[4]);
+              writeln!(out, "{}", row.trim_end()).expect("writing to a String");
+       };
+       write_row(headers);
+       for r in &rows {
+              write_row([&r[0], &r[1], &r[2], &r[3], &r[4], &r[5]]);
+       }
+       let spans = layout.size.div_ceil(line).max(1);
+       writeln!(
+              out,
+              "{}: {} bytes, {}-aligned, {} of them padding; {} {}-byte cache line{} (when it starts one)",
+              layout.name,
+              layout.size,
+              layout.align,
+              padding,
+              spans,
+              line,
+              if spans == 1 { "" } else { "s" }
+       )
+       .expect("writing to a String");
+       out
+}
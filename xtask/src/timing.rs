@@ -0,0 +1,74 @@
+//! Timing for xtask's measurements: one run ([`timed`]), or several, summarized ([`sampled`]); and time limits
+//! ([`wait_timeout`]).
+//!
+//! The one place xtask reads the clock, so every figure it prints is measured the same way.
+
+use std::{hint::black_box,
+          io,
+          process::{Child, ExitStatus},
+          thread,
+          time::{Duration, Instant}};
+
+/// What a closure returned, and how long it took.
+#[derive(Debug)]
+pub struct Timed<T> {
+       pub value:   T,
+       pub elapsed: Duration,
+}
+
+/// Run `f` once, timing it.
+pub fn timed<T>(f: impl FnOnce() -> T) -> Timed<T> {
+       let start = Instant::now();
+       let value = f();
+       Timed { value, elapsed: start.elapsed() }
+}
+
+/// The times of several runs of the same closure.
+#[derive(Debug, Clone)]
+pub struct Samples {
+       /// Sorted, fastest first; never empty.
+       runs: Vec<Duration>,
+}
+
+impl Samples {
+       pub fn fastest(&self) -> Duration { self.runs[0] }
+
+       pub fn median(&self) -> Duration {
+              let mid = self.runs.len() / 2;
+              if !self.runs.len().is_multiple_of(2) { self.runs[mid] } else { (self.runs[mid - 1] + self.runs[mid]) / 2 }
+       }
+
+       pub fn mean(&self) -> Duration { self.runs.iter().sum::<Duration>() / self.runs.len() as u32 }
+}
+
+/// Run `f` `runs` times (at least once), timing each; the last run's value, and the times.
+///
+/// The values before the last are passed through [`black_box`], so the work isn't optimized away, and dropped
+/// outside the timed span, so freeing them isn't counted.
+pub fn sampled<T>(runs: usize, mut f: impl FnMut() -> T) -> (T, Samples) {
+       let mut times = Vec::with_capacity(runs.max(1));
+       let mut last = None;
+       for _ in 0..runs.max(1) {
+              let Timed { value, elapsed } = timed(&mut f);
+              times.push(elapsed);
+              black_box(last.replace(value));
+       }
+       times.sort_unstable();
+       (last.expect("ran at least once"), Samples { runs: times })
+}
+
+/// Wait for `child` to exit, but no longer than `limit`: its status, or `None` if it's still running.
+///
+/// Polls (every few milliseconds) rather than blocking, as `std` has no wait with a timeout.
+pub fn wait_timeout(child: &mut Child, limit: Duration) -> io::Result<Option<ExitStatus>> {
+       let deadline = Instant::now() + limit;
+       loop {
+              if let Some(status) = child.try_wait()? {
+                     return Ok(Some(status));
+              }
+              if Instant::now() >= deadline {
+                     return Ok(None);
+              }
+              thread::sleep(Duration::from_millis(5));
+       }
+}
@@ -1,10 +1,15 @@
 //! Interface to allow getting information about Types
-use std::{fmt, num::NonZero};
+use std::{any::{Any, type_name},
+          fmt,
+          num::NonZero,
+          sync::atomic::{AtomicBool, AtomicI8, AtomicI16, AtomicI32, AtomicI64, AtomicIsize, AtomicPtr, AtomicU8, AtomicU16, AtomicU32,
+                         AtomicU64, AtomicUsize}};
 
 use clap::ValueEnum;
 use owo_colors::OwoColorize;
+use serde::{Serialize, Serializer, ser::SerializeStruct};
 
-/// Manual Enumeration of some (std, numeric) rust types.
+/// Manual Enumeration of some (std, mostly numeric) rust types.
 /// Mostly here to act as a handle/interface to extract other type information
 ///
 /// ## Limitations
@@ -59,139 +64,506 @@ pub enum TypesManual {
        NonZeroI64,
        NonZeroI128,
        NonZeroIsize,
+       // atomic
+       AtomicU8,
+       AtomicU16,
+       AtomicU32,
+       AtomicU64,
+       AtomicUsize,
+       AtomicI8,
+       AtomicI16,
+       AtomicI32,
+       AtomicI64,
+       AtomicIsize,
+       AtomicBool,
+       AtomicPtr,
+       // other primitives
+       Char,
+       Bool,
+       /// `*const ()`: a thin raw pointer
+       Pointer,
+       /// `&u8`: a thin reference
+       Ref,
+       /// `&[u8]`: a fat reference (address and length)
+       SliceRef,
+       /// `&str`: a fat reference (address and length in bytes)
+       StrRef,
+       /// `&dyn Any`: a fat reference (address and vtable)
+       DynRef,
 }
 impl TypesManual {
        /// Get info about type indicatd by type handle (`TypesManual` variant)
-       pub fn get_details_as_strings(&self) -> TypeDetails<String> {
+       pub fn details(&self) -> TypeDetails {
               match self {
-                     TypesManual::U8 => get_type_details::<u8>().as_strings(),
-                     TypesManual::U16 => get_type_details::<u16>().as_strings(),
-                     TypesManual::U32 => get_type_details::<u32>().as_strings(),
-                     TypesManual::U64 => get_type_details::<u64>().as_strings(),
-                     TypesManual::U128 => get_type_details::<u128>().as_strings(),
-                     TypesManual::USize => get_type_details::<usize>().as_strings(),
-                     TypesManual::I8 => get_type_details::<i8>().as_strings(),
-                     TypesManual::I16 => get_type_details::<i16>().as_strings(),
-                     TypesManual::I32 => get_type_details::<i32>().as_strings(),
-                     TypesManual::I64 => get_type_details::<i64>().as_strings(),
-                     TypesManual::I128 => get_type_details::<i128>().as_strings(),
-                     TypesManual::ISize => get_type_details::<isize>().as_strings(),
-                     TypesManual::F32 => get_type_details::<f32>().as_strings(),
-                     TypesManual::F64 => get_type_details::<f64>().as_strings(),
-                     TypesManual::NonZeroU8 => get_type_details::<NonZero<u8>>().as_strings(),
-                     TypesManual::NonZeroU16 => get_type_details::<NonZero<u16>>().as_strings(),
-                     TypesManual::NonZeroU32 => get_type_details::<NonZero<u32>>().as_strings(),
-                     TypesManual::NonZeroU64 => get_type_details::<NonZero<u64>>().as_strings(),
-                     TypesManual::NonZeroU128 => get_type_details::<NonZero<u128>>().as_strings(),
-                     TypesManual::NonZeroUsize => get_type_details::<NonZero<usize>>().as_strings(),
-                     TypesManual::NonZeroI8 => get_type_details::<NonZero<i8>>().as_strings(),
-                     TypesManual::NonZeroI16 => get_type_details::<NonZero<i16>>().as_strings(),
-                     TypesManual::NonZeroI32 => get_type_details::<NonZero<i32>>().as_strings(),
-                     TypesManual::NonZeroI64 => get_type_details::<NonZero<i64>>().as_strings(),
-                     TypesManual::NonZeroI128 => get_type_details::<NonZero<i128>>().as_strings(),
-                     TypesManual::NonZeroIsize => get_type_details::<NonZero<isize>>().as_strings(),
+                     TypesManual::U8 => get_type_details::<u8>(),
+                     TypesManual::U16 => get_type_details::<u16>(),
+                     TypesManual::U32 => get_type_details::<u32>(),
+                     TypesManual::U64 => get_type_details::<u64>(),
+                     TypesManual::U128 => get_type_details::<u128>(),
+                     TypesManual::USize => get_type_details::<usize>(),
+                     TypesManual::I8 => get_type_details::<i8>(),
+                     TypesManual::I16 => get_type_details::<i16>(),
+                     TypesManual::I32 => get_type_details::<i32>(),
+                     TypesManual::I64 => get_type_details::<i64>(),
+                     TypesManual::I128 => get_type_details::<i128>(),
+                     TypesManual::ISize => get_type_details::<isize>(),
+                     TypesManual::F32 => get_type_details::<f32>(),
+                     TypesManual::F64 => get_type_details::<f64>(),
+                     TypesManual::NonZeroU8 => get_type_details::<NonZero<u8>>(),
+                     TypesManual::NonZeroU16 => get_type_details::<NonZero<u16>>(),
+                     TypesManual::NonZeroU32 => get_type_details::<NonZero<u32>>(),
+                     TypesManual::NonZeroU64 => get_type_details::<NonZero<u64>>(),
+                     TypesManual::NonZeroU128 => get_type_details::<NonZero<u128>>(),
+                     TypesManual::NonZeroUsize => get_type_details::<NonZero<usize>>(),
+                     TypesManual::NonZeroI8 => get_type_details::<NonZero<i8>>(),
+                     TypesManual::NonZeroI16 => get_type_details::<NonZero<i16>>(),
+                     TypesManual::NonZeroI32 => get_type_details::<NonZero<i32>>(),
+                     TypesManual::NonZeroI64 => get_type_details::<NonZero<i64>>(),
+                     TypesManual::NonZeroI128 => get_type_details::<NonZero<i128>>(),
+                     TypesManual::NonZeroIsize => get_type_details::<NonZero<isize>>(),
+                     TypesManual::AtomicU8 => atomic_details::<AtomicU8, u8>(cfg!(target_has_atomic = "8")),
+                     TypesManual::AtomicU16 => atomic_details::<AtomicU16, u16>(cfg!(target_has_atomic = "16")),
+                     TypesManual::AtomicU32 => atomic_details::<AtomicU32, u32>(cfg!(target_has_atomic = "32")),
+                     TypesManual::AtomicU64 => atomic_details::<AtomicU64, u64>(cfg!(target_has_atomic = "64")),
+                     TypesManual::AtomicUsize => atomic_details::<AtomicUsize, usize>(cfg!(target_has_atomic = "ptr")),
+                     TypesManual::AtomicI8 => atomic_details::<AtomicI8, i8>(cfg!(target_has_atomic = "8")),
+                     TypesManual::AtomicI16 => atomic_details::<AtomicI16, i16>(cfg!(target_has_atomic = "16")),
+                     TypesManual::AtomicI32 => atomic_details::<AtomicI32, i32>(cfg!(target_has_atomic = "32")),
+                     TypesManual::AtomicI64 => atomic_details::<AtomicI64, i64>(cfg!(target_has_atomic = "64")),
+                     TypesManual::AtomicIsize => atomic_details::<AtomicIsize, isize>(cfg!(target_has_atomic = "ptr")),
+                     TypesManual::AtomicBool => atomic_details::<AtomicBool, bool>(cfg!(target_has_atomic = "8")),
+                     TypesManual::AtomicPtr => atomic_details::<AtomicPtr<()>, *mut ()>(cfg!(target_has_atomic = "ptr")),
+                     TypesManual::Char => get_type_details::<char>(),
+                     TypesManual::Bool => get_type_details::<bool>(),
+                     TypesManual::Pointer => get_type_details::<*const ()>(),
+                     TypesManual::Ref => get_type_details::<&u8>(),
+                     TypesManual::SliceRef => get_type_details::<&[u8]>(),
+                     TypesManual::StrRef => get_type_details::<&str>(),
+                     TypesManual::DynRef => get_type_details::<&dyn Any>(),
               }
        }
+
+       /// The plain and non-zero integers: the types with a `MAX` to count primes up to.
+       pub fn is_integer(&self) -> bool {
+              matches!(
+                     self,
+                     TypesManual::U8
+                            | TypesManual::U16
+                            | TypesManual::U32
+                            | TypesManual::U64
+                            | TypesManual::U128
+                            | TypesManual::USize
+                            | TypesManual::I8
+                            | TypesManual::I16
+                            | TypesManual::I32
+                            | TypesManual::I64
+                            | TypesManual::I128
+                            | TypesManual::ISize
+                            | TypesManual::NonZeroU8
+                            | TypesManual::NonZeroU16
+                            | TypesManual::NonZeroU32
+                            | TypesManual::NonZeroU64
+                            | TypesManual::NonZeroU128
+                            | TypesManual::NonZeroUsize
+                            | TypesManual::NonZeroI8
+                            | TypesManual::NonZeroI16
+                            | TypesManual::NonZeroI32
+                            | TypesManual::NonZeroI64
+                            | TypesManual::NonZeroI128
+                            | TypesManual::NonZeroIsize
+              )
+       }
 }
 
-/// Trait for extracting useful info about various (std, numeric) rust types.
-pub trait TypeInfo {
-       fn min_value() -> Self;
-       fn max_value() -> Self;
-       fn type_name() -> &'static str;
+/// Trait for extracting useful info about various (std, mostly numeric) rust types.
+pub trait TypeInfo: Sized {
+       /// Bytes a value takes up (`size_of`).
+       const SIZE: usize = size_of::<Self>();
+       /// Address multiple a value must sit at (`align_of`).
+       const ALIGN: usize = align_of::<Self>();
+       /// Bits a value takes up: `SIZE * 8`.
+       const BITS: usize = Self::SIZE * 8;
+
+       fn type_name() -> &'static str { std::any::type_name::<Self>() }
+       /// What's worth knowing about this type in particular, as labelled values, in display order
+       /// (`min` and `max`, for the numbers).
+       fn facts() -> Vec<(&'static str, String)>;
+       fn layout() -> Layout;
 }
 
 /// Convenience macro to implement `TypeInfo` for various types with informally common methods.
+/// One arm per kind of type (`unsigned:`, `signed:`, `nonzero:` taking the inner types, `float:`), as each lays
+/// out its bits differently, and floats have facts of their own.
 macro_rules! impl_type_info {
-       ($($t:ty),*) => {
-                $(
-                    impl TypeInfo for $t {
-                        fn min_value() -> Self {
-                            <$t>::MIN
-                        }
-                        fn max_value() -> Self {
-                            <$t>::MAX
-                        }
-                        fn type_name() -> &'static str {
-                            std::any::type_name::<$t>()
-                        }
-                    }
-                )*
-            };
+       (@common $t:ty) => {
+              fn facts() -> Vec<(&'static str, String)> {
+                     vec![("min", <$t>::MIN.to_string()), ("max", <$t>::MAX.to_string())]
+              }
+       };
+       (unsigned: $($t:ty),*) => {
+              $(
+                     impl TypeInfo for $t {
+                            impl_type_info!(@common $t);
+
+                            fn layout() -> Layout {
+                                   Layout::new(
+                                          vec![("value", <Self as TypeInfo>::BITS)],
+                                          [("MIN", <$t>::MIN as u128), ("1", 1), ("MAX", <$t>::MAX as u128)],
+                                          None,
+                                   )
+                            }
+                     }
+              )*
+       };
+       (signed: $($t:ty),*) => {
+              $(
+                     impl TypeInfo for $t {
+                            impl_type_info!(@common $t);
+
+                            fn layout() -> Layout {
+                                   // `as u128` sign-extends; `Layout::new` masks the extension off
+                                   Layout::new(
+                                          vec![("sign", 1), ("magnitude", <Self as TypeInfo>::BITS - 1)],
+                                          [("MIN", <$t>::MIN as u128), ("-1", -1 as $t as u128), ("0", 0), ("1", 1), ("MAX", <$t>::MAX as u128)],
+                                          Some(SIGNED_NOTE),
+                                   )
+                            }
+                     }
+              )*
+       };
+       (nonzero: $($inner:ty),*) => {
+              $(
+                     impl TypeInfo for NonZero<$inner> {
+                            impl_type_info!(@common NonZero<$inner>);
+
+                            fn layout() -> Layout {
+                                   let inner = <$inner as TypeInfo>::layout();
+                                   let examples = inner.examples.iter().filter(|&&(_, bits)| bits != 0).copied();
+                                   Layout::new(inner.fields, examples, Some(NICHE_NOTE))
+                            }
+                     }
+              )*
+       };
+       (float: $($t:ty),*) => {
+              $(
+                     impl TypeInfo for $t {
+                            fn facts() -> Vec<(&'static str, String)> {
+                                   vec![
+                                          ("min", <$t>::MIN.to_string()),
+                                          ("max", <$t>::MAX.to_string()),
+                                          // gap between 1.0 and the next float up
+                                          ("epsilon", format!("{:e}", <$t>::EPSILON)),
+                                          // significand bits, counting the implicit leading 1
+                                          ("mantissa digits", <$t>::MANTISSA_DIGITS.to_string()),
+                                          // smallest positive normal value (subnormals go lower, losing precision)
+                                          ("min positive", format!("{:e}", <$t>::MIN_POSITIVE)),
+                                          ("infinity", format!("{} ({:#x})", <$t>::INFINITY, <$t>::INFINITY.to_bits())),
+                                          // the bit pattern of std's `NAN`, one of many NaNs
+                                          ("NaN", format!("{} ({:#x})", <$t>::NAN, <$t>::NAN.to_bits())),
+                                          // every integer up to it is exact; past it, the odd ones start to round off
+                                          ("largest exact integer", (1_u128 << <$t>::MANTISSA_DIGITS).to_string()),
+                                   ]
+                            }
+
+                            fn layout() -> Layout {
+                                   let mantissa = <$t>::MANTISSA_DIGITS as usize - 1;
+                                   Layout::new(
+                                          vec![("sign", 1), ("exponent", <Self as TypeInfo>::BITS - 1 - mantissa), ("mantissa", mantissa)],
+                                          [
+                                                 ("MIN", <$t>::MIN.to_bits().into()),
+                                                 ("-1", (-1.0 as $t).to_bits().into()),
+                                                 ("0", (0.0 as $t).to_bits().into()),
+                                                 ("1", (1.0 as $t).to_bits().into()),
+                                                 ("MAX", <$t>::MAX.to_bits().into()),
+                                          ],
+                                          Some(FLOAT_NOTE),
+                                   )
+                            }
+                     }
+              )*
+       };
 }
 // NOTE: cannot do (i|u)size statically.
-impl_type_info!(
-       u8,
-       u16,
-       u32,
-       u64,
-       u128,
-       usize,
-       i8,
-       i16,
-       i32,
-       i64,
-       i128,
-       isize,
-       f32,
-       f64,
-       NonZero<i8>,
-       NonZero<i16>,
-       NonZero<i32>,
-       NonZero<i64>,
-       NonZero<i128>,
-       NonZero<isize>,
-       NonZero<u8>,
-       NonZero<u16>,
-       NonZero<u32>,
-       NonZero<u64>,
-       NonZero<u128>,
-       NonZero<usize>
-);
+impl_type_info!(unsigned: u8, u16, u32, u64, u128, usize);
+impl_type_info!(signed: i8, i16, i32, i64, i128, isize);
+impl_type_info!(nonzero: u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_type_info!(float: f32, f64);
 
-/// Convenience wrapper for usefil information about types.
+const CHAR_NOTE: &str = "a Unicode scalar value; the top 11 bits are always 0, and they and the surrogate gap are niches";
+const REFERENCE_NOTE: &str =
+       "never null (nor dangling, nor misaligned): null is the niche `Option<&_>` stores `None` in, keeping it the same size";
+const BOOL_NOTE: &str = "a whole byte, of which only 0 and 1 are valid: the other 254 patterns are niches";
+const POINTER_NOTE: &str = "an address; on most 64-bit targets only the low 48 (or 57) bits can be set in practice";
+const SIGNED_NOTE: &str = "two's complement: a set sign bit is worth -2^(bits-1), so -1 is all ones and MIN a lone sign bit";
+const NICHE_NOTE: &str = "all zeros is never a valid value: the niche `Option<NonZero<_>>` stores `None` in, keeping it the same size";
+const FLOAT_NOTE: &str = "value = (-1)^sign × 1.mantissa × 2^(exponent - bias); an all-ones exponent is inf (mantissa 0) or NaN";
+
+/// How a type's bits are split up, and some values' bit patterns.
 #[derive(Debug, Clone)]
-pub struct TypeDetails<T>
-where
-       T: std::fmt::Display,
-{
-       pub name: &'static str,
-       pub min:  T,
-       pub max:  T,
-}
-impl<T> TypeDetails<T>
-where
-       T: std::fmt::Display,
-{
-       /// Convert the `TypeDetails` to a `TypeDetails` with `String` fields.
-       /// This allows all `TypeDetails<T>` to ~~> `TypeDetails<String>`
-       pub fn as_strings(&self) -> TypeDetails<String> {
-              TypeDetails { name: self.name, min: self.min.to_string(), max: self.max.to_string() }
+pub struct Layout {
+       /// Named runs of bits, most significant first.
+       pub fields:   Vec<(&'static str, usize)>,
+       /// Values' bit patterns, in the low bits of a `u128`.
+       pub examples: Vec<(&'static str, u128)>,
+       /// What to know to read the bits.
+       pub note:     Option<&'static str>,
+}
+
+impl Layout {
+       /// `examples` may have bits set above the fields' (from sign-extending); they're masked off.
+       fn new(
+              fields: Vec<(&'static str, usize)>,
+              examples: impl IntoIterator<Item = (&'static str, u128)>,
+              note: Option<&'static str>,
+       ) -> Self {
+              let bits: usize = fields.iter().map(|&(_, width)| width).sum();
+              let mask = u128::MAX >> (128 - bits);
+              Self { fields, examples: examples.into_iter().map(|(label, value)| (label, value & mask)).collect(), note }
+       }
+
+       /// Total bits across the fields.
+       pub fn bits(&self) -> usize { self.fields.iter().map(|&(_, width)| width).sum() }
+
+       /// `bits` in binary, with a space between fields.
+       fn binary(&self, bits: u128) -> String {
+              let binary = format!("{bits:0width$b}", width = self.bits());
+              let mut split = Vec::with_capacity(self.fields.len());
+              let mut start = 0;
+              for &(_, width) in &self.fields {
+                     split.push(&binary[start..start + width]);
+                     start += width;
+              }
+              split.join(" ")
+       }
+}
+
+/// Fields as `{name, bits}`, and examples as `{value, binary, hex}`: strings, as a `u128` is more than JSON readers
+/// reliably hold exactly.
+impl Serialize for Layout {
+       fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+              #[derive(Serialize)]
+              struct Field {
+                     name: &'static str,
+                     bits: usize,
+              }
+              #[derive(Serialize)]
+              struct Example {
+                     value:  &'static str,
+                     binary: String,
+                     hex:    String,
+              }
+              let mut layout = serializer.serialize_struct("Layout", 3)?;
+              layout.serialize_field("fields", &self.fields.iter().map(|&(name, bits)| Field { name, bits }).collect::<Vec<_>>())?;
+              layout.serialize_field(
+                     "examples",
+                     &self.examples
+                            .iter()
+                            .map(|&(value, bits)| Example { value, binary: self.binary(bits), hex: format!("{bits:#x}") })
+                            .collect::<Vec<_>>(),
+              )?;
+              layout.serialize_field("note", &self.note)?;
+              layout.end()
+       }
+}
+
+impl fmt::Display for Layout {
+       fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              let fields: Vec<String> = self.fields.iter().map(|(name, width)| format!("{name} ({width})")).collect();
+              writeln!(f, "{}: {}", "layout".yellow().italic(), fields.join(" | ").cyan())?;
+              if let Some(note) = self.note {
+                     writeln!(f, " {}", note.italic())?;
+              }
+              let hex_width = self.bits().div_ceil(4);
+              let label_width = self.examples.iter().map(|(label, _)| label.len()).max().unwrap_or(0).max(4);
+              for &(label, bits) in &self.examples {
+                     writeln!(
+                            f,
+                            " {:>label_width$}: {} {}",
+                            label.yellow(),
+                            self.binary(bits).green(),
+                            format!("{bits:#0w$x}", w = hex_width + 2).blue()
+                     )?;
+              }
+              Ok(())
+       }
+}
+
+/// Convenience wrapper for usefil information about types.
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeDetails {
+       pub name:   &'static str,
+       /// Bytes.
+       pub size:   usize,
+       /// Bytes.
+       pub align:  usize,
+       pub bits:   usize,
+       /// See [`TypeInfo::facts`]; a JSON object, in order.
+       #[serde(serialize_with = "serialize_facts")]
+       pub facts:  Vec<(&'static str, String)>,
+       pub layout: Layout,
+}
+
+impl TypeDetails {
+       /// The fact labelled `label`, if this type has one.
+       pub fn fact(&self, label: &str) -> Option<&str> {
+              self.facts.iter().find(|(fact, _)| *fact == label).map(|(_, value)| value.as_str())
        }
 }
 
-impl<T> fmt::Display for TypeDetails<T>
-where
-       T: fmt::Display,
-{
+fn serialize_facts<S: Serializer>(facts: &[(&'static str, String)], serializer: S) -> Result<S::Ok, S::Error> {
+       serializer.collect_map(facts.iter().map(|(label, value)| (label, value)))
+}
+
+impl fmt::Display for TypeDetails {
        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+              write!(f, "{}: {}", "type".yellow().italic(), self.name.bold().cyan())?;
+              for (label, value) in &self.facts {
+                     write!(f, "\n {}: {},", label.yellow().italic(), value.green())?;
+              }
               write!(
                      f,
-                     "{}: {}\n {}: {},\n {}: {}",
-                     "type".yellow().italic(),
-                     self.name.bold().cyan(),
-                     "min".yellow().italic(),
-                     self.min.to_string().green(),
-                     "max".yellow().italic(),
-                     self.max.to_string().green()
+                     "\n {}: {} bytes, {}: {} bytes, {}: {}",
+                     "size".yellow().italic(),
+                     self.size.blue(),
+                     "align".yellow().italic(),
+                     self.align.blue(),
+                     "bits".yellow().italic(),
+                     self.bits.blue()
               )
        }
 }
 
 /// Get some useful information about types implementing `TypeInfo`.
-pub fn get_type_details<T>() -> TypeDetails<T>
-where
-       T: TypeInfo + std::fmt::Display,
-{
-       TypeDetails { name: T::type_name(), min: T::min_value(), max: T::max_value() }
+pub fn get_type_details<T: TypeInfo>() -> TypeDetails {
+       TypeDetails { name: T::type_name(), size: T::SIZE, align: T::ALIGN, bits: T::BITS, facts: T::facts(), layout: T::layout() }
+}
+
+/// The details of atomic `A`, holding a `V`: the value's facts and layout, the atomic's footprint (which can exceed
+/// the value's alignment, e.g. `AtomicU64` on 32-bit x86).
+///
+/// `lock_free` is whether it's always lock-free on this target: the stable stand-in for the unstable
+/// `is_always_lock_free`, as std only provides an `Atomic*` where `cfg(target_has_atomic)` says the target can do it
+/// lock-free.
+fn atomic_details<A, V: TypeInfo>(lock_free: bool) -> TypeDetails {
+       let value = get_type_details::<V>();
+       let mut facts = vec![("holds", value.name.to_string()), ("always lock-free", lock_free.to_string())];
+       // the atomic's own `Option` size, not the value's: `UnsafeCell` hides the value's niches
+       facts.extend(value.facts.into_iter().filter(|&(label, _)| label != "Option<_> size"));
+       facts.push(("Option<_> size", option_size::<A>()));
+       TypeDetails {
+              name: type_name::<A>(),
+              size: size_of::<A>(),
+              align: align_of::<A>(),
+              bits: size_of::<A>() * 8,
+              facts,
+              layout: value.layout,
+       }
+}
+
+/// `Option<T>`'s size, and whether `T` has a niche to keep it the same.
+fn option_size<T>() -> String {
+       let (size, option) = (size_of::<T>(), size_of::<Option<T>>());
+       if size == option {
+              format!("{option} bytes: `None` fits in a niche")
+       } else {
+              format!("{option} bytes: no niche, so a separate tag")
+       }
+}
+
+impl TypeInfo for char {
+       fn facts() -> Vec<(&'static str, String)> {
+              vec![
+                     ("min", format!("{:?} (U+{:04X})", char::MIN, u32::from(char::MIN))),
+                     ("max", format!("{:?} (U+{:04X})", char::MAX, u32::from(char::MAX))),
+                     ("valid ranges", "U+0000..=U+D7FF, U+E000..=U+10FFFF".to_string()),
+                     ("surrogate gap", "U+D800..=U+DFFF: UTF-16's surrogate halves, never a `char`".to_string()),
+                     // every code point but the surrogates
+                     ("valid values", format!("{} of 2^32", u32::from(char::MAX) + 1 - 0x800)),
+                     ("Option<_> size", option_size::<Self>()),
+              ]
+       }
+
+       fn layout() -> Layout {
+              Layout::new(
+                     vec![("unused", 11), ("scalar value", 21)],
+                     [
+                            ("MIN", u32::from(char::MIN).into()),
+                            ("'A'", u32::from('A').into()),
+                            ("U+D7FF", 0xd7ff),
+                            ("U+E000", 0xe000),
+                            ("MAX", u32::from(char::MAX).into()),
+                     ],
+                     Some(CHAR_NOTE),
+              )
+       }
+}
+
+impl TypeInfo for bool {
+       fn facts() -> Vec<(&'static str, String)> {
+              vec![
+                     ("min", false.to_string()),
+                     ("max", true.to_string()),
+                     ("valid values", "2 of 256".to_string()),
+                     ("Option<_> size", option_size::<Self>()),
+              ]
+       }
+
+       fn layout() -> Layout { Layout::new(vec![("value", 8)], [("false", 0), ("true", 1)], Some(BOOL_NOTE)) }
+}
+
+/// Raw pointers, `*const ()` and (for `AtomicPtr`) `*mut ()`: thin, and with no niche.
+macro_rules! impl_pointer_type_info {
+       ($($t:ty),*) => {
+              $(
+                     impl TypeInfo for $t {
+                            fn facts() -> Vec<(&'static str, String)> {
+                                   vec![
+                                          ("min", "0x0 (null)".to_string()),
+                                          ("max", format!("{:#x}", usize::MAX)),
+                                          ("pointer width", format!("{} bits, as `usize`", usize::BITS)),
+                                          ("Option<_> size", option_size::<Self>()),
+                                   ]
+                            }
+
+                            fn layout() -> Layout {
+                                   Layout::new(vec![("address", usize::BITS as usize)], [("null", 0), ("MAX", usize::MAX as u128)], Some(POINTER_NOTE))
+                            }
+                     }
+              )*
+       };
 }
+impl_pointer_type_info!(*const (), *mut ());
+
+impl TypeInfo for &u8 {
+       fn facts() -> Vec<(&'static str, String)> {
+              vec![("kind", "thin: an address alone".to_string()), ("Option<_> size", option_size::<Self>())]
+       }
+
+       fn layout() -> Layout { Layout::new(vec![("address", usize::BITS as usize)], [], Some(REFERENCE_NOTE)) }
+}
+
+/// Fat references: an address, and a second word saying how much is there (`$meta`).
+macro_rules! impl_fat_type_info {
+       ($($t:ty => $meta:literal, $kind:literal);*) => {
+              $(
+                     impl TypeInfo for $t {
+                            fn facts() -> Vec<(&'static str, String)> {
+                                   vec![("kind", $kind.to_string()), ("Option<_> size", option_size::<Self>())]
+                            }
+
+                            fn layout() -> Layout {
+                                   Layout::new(
+                                          vec![("address", usize::BITS as usize), ($meta, usize::BITS as usize)],
+                                          [],
+                                          Some(REFERENCE_NOTE),
+                                   )
+                            }
+                     }
+              )*
+       };
+}
+impl_fat_type_info!(
+       &[u8] => "length", "fat: an address, and the number of elements";
+       &str => "length", "fat: an address, and the number of bytes (of UTF-8)";
+       &dyn Any => "vtable", "fat: an address, and a pointer to the concrete type's vtable"
+);